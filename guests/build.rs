@@ -12,29 +12,141 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+/// Guests whose journal encoding is selected by the `journal-*` features.
+const JOURNAL_GUESTS: [&str; 2] = ["op-derive", "op-compose"];
+
+/// Returns the parts of zeth-lib used by the given guest, so that all other parts are
+/// left out of its image.
+fn lib_features(guest_pkg: &str) -> &'static [&'static str] {
+    match guest_pkg {
+        "eth-block" | "eth-bundle" | "op-block" => &["executor"],
+        _ => &["optimism"],
+    }
+}
+
+/// Returns the features to build the given guest with.
+fn guest_features(guest_pkg: &str) -> Vec<String> {
+    let mut features: Vec<String> = lib_features(guest_pkg)
+        .iter()
+        .map(|feature| feature.to_string())
+        .collect();
+    if guest_pkg == "op-derive" && std::env::var_os("CARGO_FEATURE_DEBUG_TRACE").is_some() {
+        features.push("debug-trace".to_string());
+    }
+    if guest_pkg == "op-derive" && std::env::var_os("CARGO_FEATURE_KZG").is_some() {
+        features.push("kzg".to_string());
+    }
+    if guest_pkg == "op-compose" && std::env::var_os("CARGO_FEATURE_LIGHT_CLIENT").is_some() {
+        features.push("light-client".to_string());
+    }
+    if GUESTS.contains(&guest_pkg) && std::env::var_os("CARGO_FEATURE_SEPOLIA").is_some() {
+        features.push("sepolia".to_string());
+    }
+    if JOURNAL_GUESTS.contains(&guest_pkg) {
+        features.extend(
+            ["journal-bincode", "journal-abi", "journal-cbor"]
+                .into_iter()
+                .filter(|feature| {
+                    let var = format!("CARGO_FEATURE_{}", feature.to_uppercase().replace('-', "_"));
+                    std::env::var_os(var).is_some()
+                })
+                .map(String::from),
+        );
+    }
+    features
+}
+
+const GUESTS: [&str; 6] = [
+    "eth-block",
+    "eth-bundle",
+    "op-block",
+    "op-derive",
+    "op-compose",
+    "op-unsafe",
+];
+
+/// Environment variable naming a directory of guest ELFs from earlier releases, named
+/// `<guest>-v<input version>.elf`, which are embedded next to the current guests.
+const PREVIOUS_GUESTS_ENV: &str = "ZETH_PREVIOUS_GUESTS";
+
+/// Writes the `PREVIOUS_GUEST_ELFS` constant, embedding all ELFs found in the directory
+/// given by [PREVIOUS_GUESTS_ENV].
+fn embed_previous_guests() {
+    println!("cargo:rerun-if-env-changed={}", PREVIOUS_GUESTS_ENV);
+    let mut entries = Vec::new();
+    if let Some(dir) = std::env::var_os(PREVIOUS_GUESTS_ENV) {
+        println!("cargo:rerun-if-changed={}", dir.to_string_lossy());
+        for entry in std::fs::read_dir(&dir).expect("Could not read the previous guests") {
+            let path = entry.unwrap().path().canonicalize().unwrap();
+            let Some(stem) = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_suffix(".elf"))
+            else {
+                continue;
+            };
+            let (guest_pkg, version) = stem
+                .rsplit_once("-v")
+                .unwrap_or_else(|| panic!("Previous guest without input version: {}", stem));
+            assert!(
+                GUESTS.contains(&guest_pkg),
+                "Unknown previous guest: {}",
+                guest_pkg
+            );
+            let version: u32 = version
+                .parse()
+                .unwrap_or_else(|_| panic!("Invalid input version of previous guest: {}", stem));
+            entries.push(format!(
+                "    ({:?}, {}, include_bytes!({:?})),",
+                guest_pkg, version, path
+            ));
+        }
+    }
+    entries.sort();
+
+    let code = format!(
+        "/// The guests of earlier releases as (name, input version, ELF) tuples.\n\
+         pub const PREVIOUS_GUEST_ELFS: &[(&str, u32, &[u8])] = &[\n{}\n];\n",
+        entries.join("\n")
+    );
+    let out_dir = std::env::var_os("OUT_DIR").unwrap();
+    std::fs::write(
+        std::path::Path::new(&out_dir).join("previous_methods.rs"),
+        code,
+    )
+    .unwrap();
+}
+
 #[cfg(not(any(feature = "debug-guest-build", debug_assertions)))]
 fn main() {
+    embed_previous_guests();
     let cwd = std::env::current_dir().unwrap();
     let root_dir = cwd.parent().map(|d| d.to_path_buf());
-    let build_opts = std::collections::HashMap::from_iter(
-        ["eth-block", "op-block", "op-derive", "op-compose"]
-            .into_iter()
-            .map(|guest_pkg| {
-                (
-                    guest_pkg,
-                    risc0_build::GuestOptions {
-                        features: vec![],
-                        use_docker: Some(risc0_build::DockerOptions {
-                            root_dir: root_dir.clone(),
-                        }),
-                    },
-                )
-            }),
-    );
+    let build_opts = std::collections::HashMap::from_iter(GUESTS.into_iter().map(|guest_pkg| {
+        (
+            guest_pkg,
+            risc0_build::GuestOptions {
+                features: guest_features(guest_pkg),
+                use_docker: Some(risc0_build::DockerOptions {
+                    root_dir: root_dir.clone(),
+                }),
+            },
+        )
+    }));
     risc0_build::embed_methods_with_options(build_opts);
 }
 
 #[cfg(any(feature = "debug-guest-build", debug_assertions))]
 fn main() {
-    risc0_build::embed_methods();
+    embed_previous_guests();
+    let build_opts = std::collections::HashMap::from_iter(GUESTS.into_iter().map(|guest_pkg| {
+        (
+            guest_pkg,
+            risc0_build::GuestOptions {
+                features: guest_features(guest_pkg),
+                use_docker: None,
+            },
+        )
+    }));
+    risc0_build::embed_methods_with_options(build_opts);
 }
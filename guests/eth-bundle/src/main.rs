@@ -0,0 +1,34 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#![no_main]
+
+use risc0_zkvm::guest::env;
+use zeth_lib::{
+    abort::{AbortCode, OrAbort},
+    builder::{BlockBuilderStrategy, EthereumStrategy},
+    consts::ETH_CHAIN_SPEC,
+};
+
+risc0_zkvm::guest::entry!(main);
+
+pub fn main() {
+    // Read the initial state and the data of the consecutive blocks
+    let input = env::read();
+    // Build all the blocks of the bundle
+    let output =
+        EthereumStrategy::build_bundle(ETH_CHAIN_SPEC, input).or_abort(AbortCode::BlockExecution);
+    // Output the first parent hash and the last block hash
+    env::commit(&output);
+}
@@ -16,18 +16,21 @@
 
 use risc0_zkvm::guest::env;
 use zeth_lib::{
+    abort::{AbortCode, OrAbort},
     builder::{BlockBuilderStrategy, OptimismStrategy},
-    consts::OP_MAINNET_CHAIN_SPEC,
+    consts::OP_CHAIN_SPEC,
+    input::BlockWitness,
 };
 
 risc0_zkvm::guest::entry!(main);
 
 pub fn main() {
     // Read the input previous block and transaction data
-    let input = env::read();
+    let witness: BlockWitness<_> = env::read();
+    let input = witness.into_input().or_abort(AbortCode::WitnessInvalidTrie);
     // Build the resulting block
-    let mut output = OptimismStrategy::build_from(&OP_MAINNET_CHAIN_SPEC, input)
-        .expect("Failed to build the resulting block");
+    let mut output =
+        OptimismStrategy::build_from(OP_CHAIN_SPEC, input).or_abort(AbortCode::BlockExecution);
     // Abridge successful construction results
     if let Some(replaced_state) = output.replace_state_with_hash() {
         // Leak memory, save cycles
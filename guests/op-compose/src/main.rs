@@ -15,7 +15,10 @@
 #![no_main]
 
 use risc0_zkvm::guest::env;
-use zeth_lib::optimism::composition::ComposeInput;
+use zeth_lib::{
+    abort::{AbortCode, OrAbort},
+    optimism::composition::ComposeInput,
+};
 
 risc0_zkvm::guest::entry!(main);
 
@@ -23,9 +26,7 @@ pub fn main() {
     // read composition input
     let compose_input: ComposeInput = env::read();
     // process input
-    let compose_output = compose_input
-        .process()
-        .expect("Failed to process composition.");
+    let compose_output = compose_input.process().or_abort(AbortCode::Composition);
     // output statement about larger segment
     env::commit(&compose_output);
 }
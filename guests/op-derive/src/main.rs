@@ -15,16 +15,31 @@
 #![no_main]
 
 use risc0_zkvm::guest::env;
-use zeth_lib::optimism::{batcher_db::MemDb, config::ChainConfig, DeriveInput, DeriveMachine};
+use zeth_lib::{
+    abort::{AbortCode, OrAbort},
+    journal::Journal,
+    optimism::{
+        framed_db::{EnvFrameSource, FramedDb},
+        DeriveInput, DeriveMachine,
+    },
+};
 
 risc0_zkvm::guest::entry!(main);
 
 pub fn main() {
-    let derive_input: DeriveInput<MemDb> = env::read();
-    let mut derive_machine = DeriveMachine::new(ChainConfig::optimism(), derive_input, None)
-        .expect("Could not create derive machine");
-    let output = derive_machine
-        .derive(None)
-        .expect("Failed to process derivation input");
-    env::commit(&output);
+    // the blocks are read on demand, following the derive input
+    let derive_input: DeriveInput<()> = env::read();
+    // the chain is given by the rollup config of the input, if any
+    let chain_config = derive_input
+        .chain_config()
+        .or_abort(AbortCode::InvalidInput);
+    let derive_input = derive_input.map_db(|_| FramedDb::new(chain_config.clone(), EnvFrameSource));
+    let mut derive_machine =
+        DeriveMachine::new(chain_config, derive_input, None).or_abort(AbortCode::Derivation);
+    let output = derive_machine.derive(None).or_abort(AbortCode::Derivation);
+    // the journal is encoded in the format selected by the journal-* features
+    let journal = output.to_journal().or_abort(AbortCode::Derivation);
+    env::commit_slice(&journal);
+    // the details of the derivation are only written to the host
+    env::write(&derive_machine.private_output());
 }
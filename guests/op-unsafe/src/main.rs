@@ -0,0 +1,35 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#![no_main]
+
+use risc0_zkvm::guest::env;
+use zeth_lib::{
+    abort::{AbortCode, OrAbort},
+    optimism::unsafe_payload::UnsafeInput,
+};
+
+risc0_zkvm::guest::entry!(main);
+
+pub fn main() {
+    // Read the payloads gossiped by the sequencer
+    let input: UnsafeInput = env::read();
+    // Verify the signatures against the unsafe block signer of the chain
+    let chain_config = input.chain_config().or_abort(AbortCode::InvalidInput);
+    let output = input
+        .process(&chain_config)
+        .or_abort(AbortCode::UnsafeAttestation);
+    // Output the signer and the attested blocks
+    env::commit(&output);
+}
@@ -13,3 +13,4 @@
 // limitations under the License.
 
 include!(concat!(env!("OUT_DIR"), "/methods.rs"));
+include!(concat!(env!("OUT_DIR"), "/previous_methods.rs"));
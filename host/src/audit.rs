@@ -0,0 +1,181 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Signed manifests of the RPC responses used to build a witness.
+
+use std::{fs, path::Path};
+
+use anyhow::{ensure, Context};
+use ethers_core::k256::{
+    ecdsa::{SigningKey, VerifyingKey},
+    elliptic_curve::sec1::ToEncodedPoint,
+    PublicKey,
+};
+use log::info;
+use serde::{Deserialize, Serialize};
+use zeth_lib::{
+    host::{
+        cache_file_path,
+        provider::audit::{self, AuditEntry},
+    },
+    optimism::unsafe_payload::recover_address,
+};
+use zeth_primitives::{keccak::keccak, Address, Bytes, B256};
+
+use crate::cli::BuildArgs;
+
+/// Version of the audit manifest format.
+pub const AUDIT_MANIFEST_VERSION: u32 = 1;
+
+/// The RPC responses received while building the input of a run, signed by its operator.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditManifest {
+    /// Version of the manifest format.
+    pub version: u32,
+    /// Name of the network of the run.
+    pub network: String,
+    /// Number of the start block of the run.
+    pub block_number: u64,
+    /// All RPC responses in the order they were received.
+    pub entries: Vec<AuditEntry>,
+    /// Address of the key signing the manifest.
+    pub signer: Address,
+    /// Signature over [AuditManifest::signing_hash] as `r || s || v`.
+    pub signature: Bytes,
+}
+
+impl AuditManifest {
+    /// Creates a manifest of the given responses, signed with the given key.
+    pub fn new(
+        network: String,
+        block_number: u64,
+        entries: Vec<AuditEntry>,
+        signing_key: &SigningKey,
+    ) -> anyhow::Result<Self> {
+        let mut manifest = AuditManifest {
+            version: AUDIT_MANIFEST_VERSION,
+            network,
+            block_number,
+            entries,
+            signer: key_address(signing_key.verifying_key()),
+            signature: Bytes::new(),
+        };
+        manifest.signature = sign_hash(signing_key, manifest.signing_hash())
+            .context("Failed to sign audit manifest")?;
+
+        Ok(manifest)
+    }
+
+    /// Returns the hash committed to by the signature of the manifest.
+    pub fn signing_hash(&self) -> B256 {
+        let entries = serde_json::to_vec(&self.entries).expect("Failed to serialize entries");
+        let mut data = self.version.to_be_bytes().to_vec();
+        data.extend_from_slice(&keccak(self.network.as_bytes()));
+        data.extend_from_slice(&self.block_number.to_be_bytes());
+        data.extend_from_slice(&keccak(entries));
+        keccak(data).into()
+    }
+
+    /// Verifies the signature of the manifest.
+    pub fn verify(&self) -> anyhow::Result<()> {
+        ensure!(
+            recover_address(&self.signing_hash(), &self.signature)? == self.signer,
+            "Audit manifest is not signed by {}",
+            self.signer
+        );
+
+        Ok(())
+    }
+}
+
+/// Signs all RPC responses recorded so far and stores them as a manifest next to the
+/// cached input of the run.
+pub fn write_manifest(build_args: &BuildArgs, signing_key_path: &Path) -> anyhow::Result<()> {
+    let cache = build_args
+        .cache
+        .as_ref()
+        .context("Audit manifests are stored in the cache directory")?;
+    let signing_key = load_signing_key(signing_key_path)?;
+    let manifest = AuditManifest::new(
+        build_args.network.to_string(),
+        build_args.block_number,
+        audit::take(),
+        &signing_key,
+    )?;
+
+    let file_path = cache_file_path(
+        cache,
+        &manifest.network,
+        manifest.block_number,
+        "audit.json",
+    );
+    fs::write(&file_path, serde_json::to_vec_pretty(&manifest)?)
+        .with_context(|| format!("Failed to write '{}'", file_path.display()))?;
+    info!(
+        "Stored {} audited RPC responses in {}",
+        manifest.entries.len(),
+        file_path.display()
+    );
+
+    Ok(())
+}
+
+/// Reads a hex-encoded secp256k1 signing key from the given file.
+fn load_signing_key(file_path: &Path) -> anyhow::Result<SigningKey> {
+    let key_hex = fs::read_to_string(file_path)
+        .with_context(|| format!("Failed to read signing key '{}'", file_path.display()))?;
+    let key_bytes = hex::decode(key_hex.trim().trim_start_matches("0x"))
+        .context("signing key is not valid hex")?;
+    SigningKey::from_slice(&key_bytes).context("invalid signing key")
+}
+
+/// Signs the hash, returning the signature as `r || s || v`.
+fn sign_hash(signing_key: &SigningKey, hash: B256) -> anyhow::Result<Bytes> {
+    let (signature, recovery_id) = signing_key.sign_prehash_recoverable(hash.as_slice())?;
+    let mut signature = signature.to_bytes().to_vec();
+    signature.push(recovery_id.to_byte());
+
+    Ok(signature.into())
+}
+
+/// Returns the Ethereum address corresponding to the public key.
+fn key_address(verifying_key: &VerifyingKey) -> Address {
+    let public_key = PublicKey::from(verifying_key).to_encoded_point(false);
+    let hash = keccak(&public_key.as_bytes()[1..]);
+    Address::from_slice(&hash[12..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sign_and_verify() {
+        let signing_key = SigningKey::from_slice(&[1; 32]).unwrap();
+        let entry = AuditEntry {
+            endpoint: "https://localhost".to_string(),
+            method: "eth_getBlockByNumber".to_string(),
+            block_number: 42,
+            block_hash: Some(B256::repeat_byte(2)),
+            response_hash: B256::repeat_byte(3),
+            timestamp: 1_700_000_000,
+        };
+        let mut manifest =
+            AuditManifest::new("ethereum".to_string(), 42, vec![entry], &signing_key).unwrap();
+        manifest.verify().unwrap();
+
+        manifest.entries[0].response_hash = B256::repeat_byte(4);
+        assert!(manifest.verify().is_err());
+    }
+}
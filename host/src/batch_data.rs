@@ -0,0 +1,194 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{collections::BTreeMap, fmt::Debug};
+
+use log::{info, warn};
+use serde::Serialize;
+use zeth_lib::optimism::{
+    batcher::{BatchDropReason, Batcher},
+    batcher_channel::{ChannelDropReason, DropReason, FrameSkipReason},
+    batcher_db::MemDb,
+    blob::{blob_to_data, BlobSource},
+    DerivePrivateOutput,
+};
+use zeth_primitives::transactions::{ethereum::EthereumTxEssence, TxEssence};
+
+/// Batch data posted to the batch inbox in a single L1 block.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct BatchDataUsage {
+    /// Number of the L1 block.
+    pub block_number: u64,
+    /// Number of batcher transactions carrying their data as calldata.
+    pub calldata_txs: usize,
+    /// Number of batch data bytes posted as calldata.
+    pub calldata_bytes: usize,
+    /// Number of batcher transactions carrying their data in blobs.
+    pub blob_txs: usize,
+    /// Number of batch data bytes posted in blobs.
+    pub blob_bytes: usize,
+}
+
+/// Computes the batch data of every L1 block processed by the batcher, i.e. all blocks
+/// in the database except the L1 origin of the head. Only transactions from the batch
+/// sender authenticated for the respective block are counted.
+pub fn batch_data_usage(batcher: &Batcher, db: &MemDb) -> Vec<BatchDataUsage> {
+    let mut block_numbers: Vec<_> = db.full_eth_block.keys().copied().collect();
+    block_numbers.sort_unstable();
+
+    let config = batcher.config();
+    let batch_inbox = config.batch_inbox;
+    block_numbers
+        .into_iter()
+        .skip(1)
+        .map(|block_number| {
+            let mut usage = BatchDataUsage {
+                block_number,
+                ..Default::default()
+            };
+            let batch_sender = batcher.effective_batch_sender(block_number);
+            let block = &db.full_eth_block[&block_number];
+            let is_ecotone = config.is_ecotone(block.block_header.timestamp.to());
+            let blobs = BlobSource::new(&block.blob_sidecars);
+            for tx in &block.transactions {
+                if tx.essence.to() != Some(batch_inbox) || tx.recover_from().ok() != batch_sender {
+                    continue;
+                }
+                match &tx.essence {
+                    EthereumTxEssence::Eip4844(blob_tx) if is_ecotone => {
+                        usage.blob_txs += 1;
+                        // blobs that are missing or invalid carry no batch data
+                        usage.blob_bytes += blob_tx
+                            .blob_versioned_hashes
+                            .iter()
+                            .filter_map(|hash| blobs.get(hash).and_then(blob_to_data).ok())
+                            .map(|data| data.len())
+                            .sum::<usize>();
+                    }
+                    essence => {
+                        usage.calldata_txs += 1;
+                        usage.calldata_bytes += essence.data().len();
+                    }
+                }
+            }
+            usage
+        })
+        .collect()
+}
+
+/// Prints the batch data of every L1 block containing batcher transactions, followed by
+/// the totals per data source.
+pub fn report_batch_data_usage(usage: &[BatchDataUsage]) {
+    for block in usage {
+        if block.calldata_txs + block.blob_txs == 0 {
+            continue;
+        }
+        println!(
+            "Batch data: {} calldata {} bytes ({} txs), blobs {} bytes ({} txs)",
+            block.block_number,
+            block.calldata_bytes,
+            block.calldata_txs,
+            block.blob_bytes,
+            block.blob_txs
+        );
+    }
+    let calldata_bytes: usize = usage.iter().map(|block| block.calldata_bytes).sum();
+    let blob_bytes: usize = usage.iter().map(|block| block.blob_bytes).sum();
+    let total_bytes = calldata_bytes + blob_bytes;
+    if total_bytes > 0 {
+        println!(
+            "Total batch data: {} bytes in {} L1 blocks; calldata {} bytes ({}%), blobs {} bytes ({}%)",
+            total_bytes,
+            usage.len(),
+            calldata_bytes,
+            calldata_bytes * 100 / total_bytes,
+            blob_bytes,
+            blob_bytes * 100 / total_bytes
+        );
+    } else {
+        info!("No batch data in {} L1 blocks", usage.len());
+    }
+}
+
+/// Number of batcher transactions, frames, channels and batches dropped during the
+/// derivation, by reason. Drops are part of normal operation, e.g. after a batcher
+/// restart, but a high count indicates a misbehaving batcher.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DroppedBatchData {
+    /// Transactions sent to the batch inbox whose signature or frames were invalid.
+    pub transactions: BTreeMap<DropReason, usize>,
+    /// Frames that were not added to their channel.
+    pub frames: BTreeMap<FrameSkipReason, usize>,
+    /// Channels that were dropped, or only read partially.
+    pub channels: BTreeMap<ChannelDropReason, usize>,
+    /// Batches rejected by the batch status rules.
+    pub batches: BTreeMap<BatchDropReason, usize>,
+}
+
+impl DroppedBatchData {
+    /// Returns whether nothing was dropped.
+    pub fn is_empty(&self) -> bool {
+        self.transactions.is_empty()
+            && self.frames.is_empty()
+            && self.channels.is_empty()
+            && self.batches.is_empty()
+    }
+}
+
+/// Counts the dropped items of the derivation by reason.
+pub fn dropped_batch_data(private_output: &DerivePrivateOutput) -> DroppedBatchData {
+    fn count<T, R: Ord>(items: &[T], reason: impl Fn(&T) -> R) -> BTreeMap<R, usize> {
+        let mut counts = BTreeMap::new();
+        for item in items {
+            *counts.entry(reason(item)).or_default() += 1;
+        }
+        counts
+    }
+
+    DroppedBatchData {
+        transactions: count(&private_output.dropped_transactions, |tx| tx.reason),
+        frames: count(&private_output.skipped_frames, |frame| frame.reason),
+        channels: count(&private_output.dropped_channels, |channel| channel.reason),
+        batches: count(&private_output.dropped_batches, |batch| batch.reason),
+    }
+}
+
+/// Prints the number of dropped items of every kind, followed by the counts per reason.
+pub fn report_dropped_batch_data(dropped: &DroppedBatchData) {
+    fn report<R: Debug>(kind: &str, counts: &BTreeMap<R, usize>) {
+        if counts.is_empty() {
+            return;
+        }
+        let reasons: Vec<_> = counts
+            .iter()
+            .map(|(reason, count)| format!("{:?} {}", reason, count))
+            .collect();
+        println!(
+            "Dropped {}: {} ({})",
+            kind,
+            counts.values().sum::<usize>(),
+            reasons.join(", ")
+        );
+    }
+
+    if dropped.is_empty() {
+        info!("No batch data was dropped");
+        return;
+    }
+    report("batcher transactions", &dropped.transactions);
+    report("frames", &dropped.frames);
+    report("channels", &dropped.channels);
+    report("batches", &dropped.batches);
+    warn!("The batcher posted data that was dropped during derivation");
+}
@@ -13,14 +13,37 @@
 // limitations under the License.
 
 use core::fmt;
-use std::path::PathBuf;
+use std::{ops::Range, path::PathBuf};
 
+use anyhow::{ensure, Context};
 use clap::ValueEnum;
+use zeth_lib::{
+    consts::ETH_CHAIN_SPEC,
+    host::provider::{new_provider, BlockId},
+    optimism::{
+        batcher_db::ValidationPolicy, composition::PreBedrockHistory, config::ChainConfig,
+        registry::ChainRegistry, rollup_config::RollupConfig,
+    },
+};
+use zeth_primitives::B256;
+
+use crate::{input_size::DEFAULT_MAX_INPUT_MB, operations::backend::ProverBackend};
 
 #[derive(clap::Parser, Debug, Clone)]
 #[command(name = "zeth")]
 #[command(bin_name = "zeth")]
 #[command(author, version, about, long_about = None)]
+pub struct ZethArgs {
+    #[command(subcommand)]
+    pub command: Cli,
+
+    /// Report failures as a JSON object on stdout. The exit code identifies the kind
+    /// of the failure in either case.
+    #[clap(long, global = true)]
+    pub json_errors: bool,
+}
+
+#[derive(clap::Subcommand, Debug, Clone)]
 pub enum Cli {
     /// Build blocks only on the host
     Build(BuildArgs),
@@ -30,28 +53,82 @@ pub enum Cli {
     Prove(ProveArgs),
     /// Verify a block building receipt
     Verify(VerifyArgs),
+    /// Continuously derive blocks and alert on divergence from the sequencer
+    Monitor(MonitorArgs),
+    /// Query the database of derived blocks
+    Query(QueryArgs),
+    /// Precompute signed witness bundles for external provers
+    Witness(WitnessArgs),
+    /// Compare the guest cycle counts of cached blocks against a baseline
+    Cycles(CyclesArgs),
+    /// Measure the executor on representative blocks of the test corpus
+    Bench(BenchArgs),
+    /// Derive a range of blocks without proving and export them as CSV files
+    Backfill(BackfillArgs),
+    /// Download blocks into the test corpus and regenerate its manifest
+    Corpus(CorpusArgs),
+    /// Report the section sizes and largest functions of the guest images
+    ImageSize(ImageSizeArgs),
+    /// Structurally compare the witnesses of two cached derive inputs or witness bundles
+    WitnessDiff(WitnessDiffArgs),
 }
 
 impl Cli {
-    pub fn build_args(&self) -> &BuildArgs {
+    pub fn build_args(&self) -> Option<&BuildArgs> {
         match &self {
-            Cli::Build(build_args) => build_args,
-            Cli::Run(run_args) => &run_args.build_args,
-            Cli::Prove(prove_args) => &prove_args.run_args.build_args,
-            Cli::Verify(..) => unimplemented!(),
+            Cli::Build(build_args) => Some(build_args),
+            Cli::Run(run_args) => Some(&run_args.build_args),
+            Cli::Prove(prove_args) => Some(&prove_args.run_args.build_args),
+            Cli::Monitor(monitor_args) => Some(&monitor_args.build_args),
+            Cli::Backfill(backfill_args) => Some(&backfill_args.build_args),
+            Cli::Verify(..)
+            | Cli::Query(..)
+            | Cli::Witness(..)
+            | Cli::Cycles(..)
+            | Cli::Bench(..)
+            | Cli::Corpus(..)
+            | Cli::ImageSize(..)
+            | Cli::WitnessDiff(..) => None,
+        }
+    }
+
+    pub fn build_args_mut(&mut self) -> Option<&mut BuildArgs> {
+        match self {
+            Cli::Build(build_args) => Some(build_args),
+            Cli::Run(run_args) => Some(&mut run_args.build_args),
+            Cli::Prove(prove_args) => Some(&mut prove_args.run_args.build_args),
+            Cli::Monitor(monitor_args) => Some(&mut monitor_args.build_args),
+            Cli::Backfill(backfill_args) => Some(&mut backfill_args.build_args),
+            Cli::Verify(..)
+            | Cli::Query(..)
+            | Cli::Witness(..)
+            | Cli::Cycles(..)
+            | Cli::Bench(..)
+            | Cli::Corpus(..)
+            | Cli::ImageSize(..)
+            | Cli::WitnessDiff(..) => None,
         }
     }
 
     /// Generate a unique tag for the command execution
-    pub fn execution_tag(&self) -> String {
+    pub fn execution_tag(&self) -> Option<String> {
         let time = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
             .unwrap();
         match &self {
-            Cli::Build(args) => format!("{}_build_{}", time.as_secs(), args.tag()),
-            Cli::Run(args) => format!("{}_run_{}", time.as_secs(), args.tag()),
-            Cli::Prove(args) => format!("{}_prove_{}", time.as_secs(), args.tag()),
-            Cli::Verify(..) => unimplemented!(),
+            Cli::Build(args) => Some(format!("{}_build_{}", time.as_secs(), args.tag())),
+            Cli::Run(args) => Some(format!("{}_run_{}", time.as_secs(), args.tag())),
+            Cli::Prove(args) => Some(format!("{}_prove_{}", time.as_secs(), args.tag())),
+            Cli::Monitor(args) => Some(format!("{}_monitor_{}", time.as_secs(), args.tag())),
+            Cli::Backfill(args) => Some(format!("{}_backfill_{}", time.as_secs(), args.tag())),
+            Cli::Verify(..)
+            | Cli::Query(..)
+            | Cli::Witness(..)
+            | Cli::Cycles(..)
+            | Cli::Bench(..)
+            | Cli::Corpus(..)
+            | Cli::ImageSize(..)
+            | Cli::WitnessDiff(..) => None,
         }
     }
 
@@ -65,7 +142,7 @@ impl Cli {
 
     pub fn snark(&self) -> bool {
         if let Cli::Prove(prove_args) = self {
-            prove_args.snark_args.snark
+            prove_args.snark_args.snark || prove_args.receipt_kind == ReceiptKind::Groth16
         } else {
             false
         }
@@ -77,7 +154,7 @@ impl Cli {
         } else {
             None
         };
-        verifier_rpc_url.or(self.build_args().eth_rpc_url.clone())
+        verifier_rpc_url.or_else(|| self.build_args()?.eth_rpc_url.clone())
     }
 
     pub fn verifier_contract(&self) -> Option<String> {
@@ -107,6 +184,52 @@ impl fmt::Display for Network {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum OpChain {
+    /// OP Mainnet
+    Optimism,
+    /// Base Mainnet
+    Base,
+    /// OP Sepolia testnet
+    OpSepolia,
+}
+
+impl OpChain {
+    /// Returns the derivation configuration of the chain.
+    pub fn config(&self) -> ChainConfig {
+        match self {
+            OpChain::Optimism => ChainConfig::optimism(),
+            OpChain::Base => ChainConfig::base(),
+            OpChain::OpSepolia => ChainConfig::op_sepolia(),
+        }
+    }
+
+    /// Returns the chain with the given L2 chain ID, if any.
+    pub fn from_chain_id(chain_id: u64) -> Option<Self> {
+        OpChain::value_variants()
+            .iter()
+            .copied()
+            .find(|chain| chain.config().chain_spec.chain_id() == chain_id)
+    }
+
+    /// Returns the chain the guests are built for, selected by the `sepolia` feature.
+    pub fn guest() -> Self {
+        if cfg!(feature = "sepolia") {
+            OpChain::OpSepolia
+        } else {
+            OpChain::Optimism
+        }
+    }
+}
+
+impl fmt::Display for OpChain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // use the name of the clap::ValueEnum
+        let val = self.to_possible_value().unwrap();
+        write!(f, "{}", val.get_name())
+    }
+}
+
 trait Tag {
     fn tag(&self) -> String;
 }
@@ -123,6 +246,32 @@ pub struct BuildArgs {
     /// Network name
     pub network: Network,
 
+    #[clap(long, require_equals = true, value_enum, default_value_t = OpChain::guest())]
+    /// OP Stack chain of the optimism and optimism-derived networks; chains other than
+    /// the one the guests are built for (op-sepolia with the sepolia feature, optimism
+    /// otherwise) can only be built natively
+    pub op_chain: OpChain,
+
+    #[clap(long, require_equals = true, conflicts_with_all = ["op_chain", "composition"])]
+    /// Derive the OP Stack chain described by the op-node rollup.json file at the given
+    /// path instead of a built-in chain; the derivation journal commits to the hash of
+    /// the config (optimism-derived network without composition only)
+    pub rollup_config: Option<PathBuf>,
+
+    #[clap(long, require_equals = true, conflicts_with_all = ["op_chain", "rollup_config"])]
+    /// Derive the OP Stack chain with the given name or L2 chain ID from the chain
+    /// registry; built-in chains behave like --op-chain, all others like --rollup-config
+    pub chain: Option<String>,
+
+    #[clap(long, require_equals = true, requires = "chain")]
+    /// Directory of op-node rollup.json files merged into the chain registry, each named
+    /// after its file and replacing the chains with the same name or L2 chain ID
+    pub chain_registry: Option<PathBuf>,
+
+    #[clap(skip)]
+    /// The rollup config loaded from the file, and the chain configuration built from it
+    pub custom_chain: Option<(RollupConfig, ChainConfig)>,
+
     #[clap(short, long, require_equals = true)]
     /// URL of the Ethereum RPC node
     pub eth_rpc_url: Option<String>,
@@ -131,37 +280,331 @@ pub struct BuildArgs {
     /// URL of the Optimism RPC node
     pub op_rpc_url: Option<String>,
 
+    #[clap(long, require_equals = true)]
+    /// URL of the beacon node serving the blobs of the batcher transactions after Ecotone
+    pub beacon_rpc_url: Option<String>,
+
     #[clap(short, long, require_equals = true, num_args = 0..=1, default_missing_value = "cache_rpc")]
     /// Cache RPC calls locally; the value specifies the cache directory
     ///
     /// [default when the flag is present: cache_rpc]
     pub cache: Option<PathBuf>,
 
-    #[clap(short, long, require_equals = true)]
-    /// Start block number
+    #[clap(short, long = "block-number", require_equals = true)]
+    /// Start block: a block number, a block hash, latest, safe or finalized
+    pub block: BlockId,
+
+    #[clap(skip)]
+    /// Start block number, resolved from the start block
     pub block_number: u64,
 
     #[clap(short = 'n', long, require_equals = true, default_value_t = 1)]
-    /// Number of blocks to derive (optimism-derived network only)
+    /// Number of blocks to build (ethereum and optimism-derived networks only); zero
+    /// only attests the start block and its L1 origin (optimism-derived network only)
     pub block_count: u32,
 
+    #[clap(
+        long,
+        require_equals = true,
+        conflicts_with_all = ["block_count", "composition"],
+        value_parser = parse_derive_until
+    )]
+    /// Derive all blocks up to the given time instead of a fixed number of blocks: a
+    /// unix timestamp, or a duration such as 90m, 12h or 1d after the start block
+    /// (optimism-derived network without composition only)
+    pub until: Option<DeriveUntil>,
+
     #[clap(short='m', long, require_equals = true, num_args = 0..=1, default_missing_value = "1")]
     /// Derive the Optimism blocks using proof composition (optimism-derived network
     /// only); the value specifies the the number of blocks to process per derivation call
     ///
     /// [default when the flag is present: 1]
     pub composition: Option<u32>,
+
+    #[clap(long, requires = "composition")]
+    /// Move the boundaries between the derivation calls to the start of the next L1
+    /// epoch, so that no epoch is split across calls (optimism-derived network with
+    /// composition only)
+    pub epoch_aligned: bool,
+
+    #[clap(short, long, require_equals = true)]
+    /// Store the derived blocks in the SQLite database at the given path
+    /// (optimism-derived network only)
+    pub db: Option<PathBuf>,
+
+    #[clap(long, require_equals = true)]
+    /// Attest the sequencer-signed payloads in the given JSON file instead of building
+    /// blocks (optimism network only)
+    pub unsafe_payloads: Option<PathBuf>,
+
+    #[clap(long, require_equals = true, value_parser = parse_pre_bedrock_history)]
+    /// Chain the composition starting at the Bedrock genesis to the pre-Bedrock history
+    /// given as <BEDROCK_PARENT_HASH>:<LEGACY_DATA_HASH> (optimism-derived network with
+    /// composition only)
+    pub pre_bedrock_history: Option<PreBedrockHistory>,
+
+    #[clap(long, require_equals = true, requires = "composition")]
+    /// Verify the sync committee proof of the final Ethereum block in the given JSON
+    /// file, attesting the Ethereum tail by the beacon chain (optimism-derived network
+    /// with composition only)
+    pub l1_light_client_proof: Option<PathBuf>,
+
+    #[clap(long)]
+    /// Commit to a keccak accumulator of all consumed Ethereum blocks in the derivation
+    /// journal (optimism-derived network without composition only)
+    pub eth_accumulator: bool,
+
+    #[clap(long, require_equals = true, conflicts_with = "composition")]
+    /// Anchor the Ethereum tail of the derivation journal to the L1 block recorded by
+    /// the L1Block predeploy in the given Optimism block, proving that the tail is an
+    /// ancestor of that block (optimism-derived network without composition only)
+    pub l1_anchor: Option<u64>,
+
+    #[clap(long, conflicts_with = "composition")]
+    /// Commit to the channel and the L1 inclusion block of the batch of every derived
+    /// block in the derivation journal (optimism-derived network without composition
+    /// only)
+    pub batch_provenance: bool,
+
+    #[clap(long, require_equals = true, conflicts_with = "composition")]
+    /// Continue the derivation output committed by the op-derive receipt in the given
+    /// file, as saved by the prove command; the derivation journal commits to the digest
+    /// of the prior journal (optimism-derived network without composition only)
+    pub prior_receipt: Option<PathBuf>,
+
+    #[clap(long, require_equals = true, conflicts_with = "composition")]
+    /// Commit to the hash of the derivation state at the end of the run and save the
+    /// state to the given file, from which a run continuing this one can resume with
+    /// --resume-checkpoint (optimism-derived network without composition only)
+    pub checkpoint: Option<PathBuf>,
+
+    #[clap(long, require_equals = true, requires = "prior_receipt")]
+    /// Resume the derivation from the state saved with --checkpoint by the run of the
+    /// prior receipt, instead of traversing the Ethereum blocks again from the L1 origin
+    /// of its last derived block (optimism-derived network without composition only)
+    pub resume_checkpoint: Option<PathBuf>,
+
+    #[clap(long, require_equals = true, num_args = 0..=1, default_missing_value = "8")]
+    /// Before running, refetch a sample of the cached blocks from the RPC nodes and abort
+    /// if their hashes differ from the cache; the value specifies the number of blocks
+    /// to sample per cache file
+    ///
+    /// [default when the flag is present: 8]
+    pub verify_cache: Option<usize>,
+
+    #[clap(long, require_equals = true, requires = "cache")]
+    /// Record every RPC response used to build the input in a manifest signed with the
+    /// hex-encoded secp256k1 key in the given file, stored in the cache directory
+    pub audit_key: Option<PathBuf>,
+
+    #[clap(long, require_equals = true, num_args = 0..=1, default_missing_value = "90")]
+    /// Report the drift between each derived block and its L1 origin, warning about
+    /// blocks whose drift reaches the given percentage of the maximum sequencer drift
+    /// (optimism-derived network without composition only)
+    ///
+    /// [default when the flag is present: 90]
+    pub drift_report: Option<u64>,
+
+    #[clap(long)]
+    /// Report how many bytes of batch data each L1 block posted as calldata and in
+    /// blobs, and how many batcher transactions, frames, channels and batches were
+    /// dropped (optimism-derived network without composition only)
+    pub batch_data_report: bool,
+
+    #[clap(long)]
+    /// Log and ignore non-critical mismatches of the RPC data, like inconsistent receipt
+    /// log indices, during the preflight; the guests always validate strictly
+    /// (optimism-derived network only)
+    pub permissive: bool,
+
+    #[clap(long)]
+    /// Send the parent tries to the guest as sorted leaves and boundary digests instead
+    /// of trie nodes (ethereum and optimism networks with a single block only)
+    pub flat_witness: bool,
+
+    #[clap(long, require_equals = true, default_value_t = DEFAULT_MAX_INPUT_MB)]
+    /// Abort the preflight if the serialized input of a guest exceeds the given size in
+    /// MB, instead of running the guest out of memory; 0 disables the check
+    pub max_input_size: u64,
+
+    #[clap(
+        long,
+        require_equals = true,
+        requires = "trusted_signer",
+        conflicts_with_all = ["block_count", "unsafe_payloads"]
+    )]
+    /// Build the block from the input of the signed witness bundle in the given file
+    /// instead of running the preflight (ethereum and optimism networks with a single
+    /// block only)
+    pub witness_bundle: Option<PathBuf>,
+
+    #[clap(long, require_equals = true, requires = "witness_bundle")]
+    /// Hex-encoded ed25519 public key whose witness bundles are accepted; can be given
+    /// multiple times
+    pub trusted_signer: Vec<B256>,
+}
+
+impl BuildArgs {
+    /// Moves the cache of chains other than OP mainnet into a sub-directory named after
+    /// the chain, so that their blocks never collide with the cached OP mainnet blocks.
+    /// Chains of a rollup config are named after their chain ID.
+    pub fn scope_cache(&mut self) {
+        let scope = match &self.custom_chain {
+            Some((rollup_config, _)) => Some(format!("chain-{}", rollup_config.l2_chain_id)),
+            None => (self.op_chain != OpChain::Optimism).then(|| self.op_chain.to_string()),
+        };
+        if let Some(scope) = scope {
+            self.cache = self.cache.take().map(|dir| dir.join(scope));
+        }
+    }
+
+    /// Loads the registry chain or the rollup config file, if any, and builds the
+    /// configuration of its chain.
+    pub fn load_chain(&mut self) -> anyhow::Result<()> {
+        if let Some(name) = &self.chain {
+            let mut registry = ChainRegistry::embedded()?;
+            if let Some(dir) = &self.chain_registry {
+                registry.merge_dir(dir)?;
+            }
+            let chain = match name.parse::<u64>() {
+                Ok(chain_id) => registry.by_chain_id(chain_id),
+                Err(_) => registry.by_name(name),
+            }
+            .with_context(|| {
+                let names: Vec<_> = registry.names().collect();
+                format!("Unknown chain '{}', known: {}", name, names.join(", "))
+            })?;
+            match &chain.rollup_config {
+                Some(rollup_config) => {
+                    self.set_custom_chain(rollup_config.clone(), chain.config.clone())?
+                }
+                None => {
+                    self.op_chain = OpChain::from_chain_id(chain.chain_id())
+                        .context("Unknown built-in chain")?
+                }
+            }
+            return Ok(());
+        }
+
+        let Some(path) = &self.rollup_config else {
+            return Ok(());
+        };
+        let json = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read '{}'", path.display()))?;
+        let rollup_config = RollupConfig::from_json(&json)
+            .with_context(|| format!("Failed to parse '{}'", path.display()))?;
+        let chain_config = rollup_config
+            .chain_config()
+            .with_context(|| format!("Invalid rollup config '{}'", path.display()))?;
+        self.set_custom_chain(rollup_config, chain_config)
+    }
+
+    /// Derives the chain of the given rollup config instead of a built-in chain.
+    fn set_custom_chain(
+        &mut self,
+        rollup_config: RollupConfig,
+        chain_config: ChainConfig,
+    ) -> anyhow::Result<()> {
+        ensure!(
+            rollup_config.l1_chain_id == ETH_CHAIN_SPEC.chain_id(),
+            "The rollup config settles on L1 chain {}, but zeth is built for chain {}",
+            rollup_config.l1_chain_id,
+            ETH_CHAIN_SPEC.chain_id()
+        );
+        self.custom_chain = Some((rollup_config, chain_config));
+
+        Ok(())
+    }
+
+    /// Returns the derivation configuration of the chain: the one built from the rollup
+    /// config if loaded, the one of the OP Stack chain otherwise.
+    pub fn chain_config(&self) -> ChainConfig {
+        match &self.custom_chain {
+            Some((_, chain_config)) => chain_config.clone(),
+            None => self.op_chain.config(),
+        }
+    }
+
+    /// Returns the policy for non-critical mismatches of the RPC data of the preflight.
+    pub fn validation_policy(&self) -> ValidationPolicy {
+        if self.permissive {
+            ValidationPolicy::Permissive
+        } else {
+            ValidationPolicy::Strict
+        }
+    }
+
+    /// Returns the loaded rollup config, if any.
+    pub fn custom_rollup_config(&self) -> Option<RollupConfig> {
+        self.custom_chain
+            .as_ref()
+            .map(|(rollup_config, _)| rollup_config.clone())
+    }
+
+    /// Resolves the start block into its number, using the RPC node of the network for
+    /// block hashes and tags.
+    pub async fn resolve_block_number(&mut self) -> anyhow::Result<()> {
+        self.block_number = match self.block {
+            BlockId::Number(block_no) => block_no,
+            block_id => {
+                let rpc_url = match self.network {
+                    Network::Ethereum => self.eth_rpc_url.clone(),
+                    Network::Optimism | Network::OptimismDerived => self.op_rpc_url.clone(),
+                }
+                .with_context(|| format!("Resolving block {} requires an RPC URL", block_id))?;
+                tokio::task::spawn_blocking(move || {
+                    new_provider(None, Some(rpc_url))?.resolve_block_number(&block_id)
+                })
+                .await??
+            }
+        };
+
+        Ok(())
+    }
 }
 
 impl Tag for BuildArgs {
     fn tag(&self) -> String {
-        format!(
+        let tag = format!(
             "{}_{}_{}_{}",
             self.network,
             self.block_number,
             self.block_count,
             self.composition.unwrap_or_default()
-        )
+        );
+        match self.until {
+            Some(until) => format!("{}_{}", tag, until),
+            None => tag,
+        }
+    }
+}
+
+/// The end of a time-boxed derivation window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeriveUntil {
+    /// An absolute L2 timestamp in seconds.
+    Timestamp(u64),
+    /// A number of seconds after the timestamp of the start block.
+    Duration(u64),
+}
+
+impl DeriveUntil {
+    /// Returns the maximum timestamp of the derived blocks, given the timestamp of the
+    /// start block.
+    pub fn max_timestamp(&self, head_timestamp: u64) -> u64 {
+        match self {
+            DeriveUntil::Timestamp(timestamp) => *timestamp,
+            DeriveUntil::Duration(seconds) => head_timestamp.saturating_add(*seconds),
+        }
+    }
+}
+
+impl fmt::Display for DeriveUntil {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeriveUntil::Timestamp(timestamp) => write!(f, "{}", timestamp),
+            DeriveUntil::Duration(seconds) => write!(f, "{}s", seconds),
+        }
     }
 }
 
@@ -194,10 +637,46 @@ pub struct ProveArgs {
     /// Prove remotely using Bonsai
     pub submit_to_bonsai: bool,
 
+    #[clap(long)]
+    /// Print the progress of local proving as JSON lines to stdout
+    pub json_progress: bool,
+
+    #[clap(
+        long,
+        require_equals = true,
+        value_enum,
+        default_value_t = ReceiptKind::Composite
+    )]
+    /// Kind of the receipt to create
+    pub receipt_kind: ReceiptKind,
+
+    #[clap(long, require_equals = true, default_value = "poseidon2")]
+    /// Hash function of the local prover (poseidon2, sha-256 or blake2b)
+    pub hashfn: String,
+
+    #[clap(long, require_equals = true, value_enum)]
+    /// Hardware backend of the local prover [default: detected]
+    pub prover_backend: Option<ProverBackend>,
+
+    #[clap(long, require_equals = true, conflicts_with = "composition")]
+    /// Prove the derivation with the embedded guest of an earlier release reading the
+    /// given version of the derivation input (optimism-derived network only)
+    pub input_version: Option<u32>,
+
     #[clap(flatten)]
     pub snark_args: SnarkArgs,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ReceiptKind {
+    /// One STARK receipt per segment
+    Composite,
+    /// A single STARK receipt compressed from the segment receipts
+    Succinct,
+    /// A Groth16 SNARK receipt, converted from the STARK receipt using Bonsai
+    Groth16,
+}
+
 #[derive(clap::Args, Debug, Clone)]
 pub struct SnarkArgs {
     /// Convert the resulting STARK receipt into a Groth-16 SNARK using Bonsai
@@ -226,3 +705,263 @@ pub struct VerifyArgs {
     /// Verify the receipt from the provided Bonsai Session UUID
     pub bonsai_receipt_uuid: String,
 }
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct MonitorArgs {
+    #[clap(flatten)]
+    pub build_args: BuildArgs,
+
+    #[clap(short = 'u', long, require_equals = true)]
+    /// URL of a webhook receiving divergence alerts as JSON
+    pub webhook_url: Option<String>,
+
+    #[clap(short = 'i', long, require_equals = true, default_value_t = 12)]
+    /// Number of seconds to wait before polling for new blocks
+    pub poll_interval: u64,
+
+    #[clap(long, require_equals = true, default_value_t = 80)]
+    /// Warn when no new blocks are derived and the given percentage of the sequencing
+    /// window of the last derived block has elapsed
+    pub window_warning: u64,
+}
+
+impl Tag for MonitorArgs {
+    fn tag(&self) -> String {
+        self.build_args.tag()
+    }
+}
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct BackfillArgs {
+    #[clap(flatten)]
+    pub build_args: BuildArgs,
+
+    #[clap(long, require_equals = true, default_value = "backfill")]
+    /// Directory receiving the blocks.csv, deposits.csv and batches.csv files
+    pub out_dir: PathBuf,
+
+    #[clap(long, require_equals = true, default_value_t = 100)]
+    /// Number of blocks to derive per derivation call
+    pub chunk_size: u32,
+}
+
+impl Tag for BackfillArgs {
+    fn tag(&self) -> String {
+        self.build_args.tag()
+    }
+}
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct QueryArgs {
+    #[clap(short, long, require_equals = true)]
+    /// Path of the SQLite database of derived blocks
+    pub db: PathBuf,
+
+    #[command(subcommand)]
+    pub target: QueryTarget,
+}
+
+#[derive(clap::Subcommand, Debug, Clone)]
+pub enum QueryTarget {
+    /// Print the derived block with the given number
+    Block {
+        /// Block number
+        number: u64,
+    },
+}
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct WitnessArgs {
+    #[clap(
+        short = 'w',
+        long,
+        require_equals = true,
+        value_enum,
+        default_value_t = Network::Ethereum
+    )]
+    /// Network name (ethereum or optimism)
+    pub network: Network,
+
+    #[clap(short, long, require_equals = true)]
+    /// URL of the Ethereum RPC node
+    pub eth_rpc_url: Option<String>,
+
+    #[clap(short, long, require_equals = true)]
+    /// URL of the Optimism RPC node
+    pub op_rpc_url: Option<String>,
+
+    #[clap(short, long, require_equals = true, num_args = 0..=1, default_missing_value = "cache_rpc")]
+    /// Cache RPC calls locally; the value specifies the cache directory
+    ///
+    /// [default when the flag is present: cache_rpc]
+    pub cache: Option<PathBuf>,
+
+    #[clap(short, long, require_equals = true, value_parser = parse_block_range)]
+    /// Range of block numbers, excluding the end (e.g. 100..110)
+    pub range: Range<u64>,
+
+    #[clap(short, long, require_equals = true)]
+    /// Directory or HTTP(S) URL receiving the witness bundles
+    pub publish: String,
+
+    #[clap(short = 'k', long, require_equals = true)]
+    /// Path of a file containing the hex-encoded ed25519 secret key signing the bundles
+    pub signing_key: PathBuf,
+}
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct CyclesArgs {
+    #[clap(short, long, require_equals = true, default_value = "testdata")]
+    /// Directory of the cached ethereum and optimism blocks to execute
+    pub cache: PathBuf,
+
+    #[clap(short, long, require_equals = true, default_value = "cycles.json")]
+    /// Path of the JSON file containing the cycle baseline
+    pub baseline: PathBuf,
+
+    #[clap(short, long, require_equals = true, default_value_t = 5.0)]
+    /// Maximum allowed cycle increase over the baseline in percent
+    pub threshold: f64,
+
+    #[clap(short, long, default_value_t = false)]
+    /// Record the measured cycles as the new baseline instead of comparing
+    pub update: bool,
+
+    #[clap(short = 'x', long, require_equals = true, default_value_t = 20)]
+    /// The maximum cycle count of a segment as a power of 2
+    pub execution_po2: u32,
+}
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct BenchArgs {
+    #[clap(short, long, require_equals = true, default_value = "testdata")]
+    /// Directory of the test corpus containing the benchmark blocks
+    pub cache: PathBuf,
+
+    #[clap(short = 'x', long, require_equals = true, default_value_t = 20)]
+    /// The maximum cycle count of a segment as a power of 2
+    pub execution_po2: u32,
+
+    #[clap(long, default_value_t = false)]
+    /// Print the measurements as JSON
+    pub json: bool,
+}
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct CorpusArgs {
+    #[clap(short, long, require_equals = true, default_value = "testdata")]
+    /// Directory of the test corpus of cached blocks
+    pub cache: PathBuf,
+
+    #[clap(
+        short = 'w',
+        long,
+        require_equals = true,
+        value_enum,
+        default_value_t = Network::Ethereum
+    )]
+    /// Network of the blocks to download (ethereum and optimism networks only)
+    pub network: Network,
+
+    #[clap(short, long, require_equals = true)]
+    /// URL of the Ethereum RPC node
+    pub eth_rpc_url: Option<String>,
+
+    #[clap(short, long, require_equals = true)]
+    /// URL of the Optimism RPC node
+    pub op_rpc_url: Option<String>,
+
+    #[clap(
+        short,
+        long = "block-numbers",
+        require_equals = true,
+        value_delimiter = ','
+    )]
+    /// Comma-separated numbers of the blocks to download
+    pub blocks: Vec<u64>,
+
+    #[clap(long)]
+    /// Fail if the manifest does not match the corpus instead of regenerating it
+    pub check: bool,
+}
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct ImageSizeArgs {
+    #[clap(short, long, require_equals = true)]
+    /// Name of the guest to report, e.g. op-derive [default: all guests]
+    pub guest: Option<String>,
+
+    #[clap(short, long, require_equals = true, default_value_t = 20)]
+    /// Number of the largest functions to list per guest
+    pub top: usize,
+}
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct WitnessDiffArgs {
+    #[clap(long, require_equals = true)]
+    /// Cached derive input or witness bundle to compare, e.g. created before an upgrade
+    pub left: PathBuf,
+
+    #[clap(long, require_equals = true)]
+    /// Cached derive input or witness bundle to compare against
+    pub right: PathBuf,
+
+    #[clap(long)]
+    /// Compare the state and storage tries of two witness bundles instead
+    pub bundles: bool,
+}
+
+fn parse_block_range(s: &str) -> Result<Range<u64>, String> {
+    let (start, end) = s
+        .split_once("..")
+        .ok_or_else(|| format!("expected a range of the form A..B, got '{}'", s))?;
+    let start = start.parse::<u64>().map_err(|err| err.to_string())?;
+    let end = end.parse::<u64>().map_err(|err| err.to_string())?;
+    if start >= end {
+        return Err(format!("range {} is empty", s));
+    }
+
+    Ok(start..end)
+}
+
+fn parse_derive_until(s: &str) -> Result<DeriveUntil, String> {
+    if let Ok(timestamp) = s.parse::<u64>() {
+        return Ok(DeriveUntil::Timestamp(timestamp));
+    }
+    let unit = match s.chars().last() {
+        Some('s') => 1,
+        Some('m') => 60,
+        Some('h') => 60 * 60,
+        Some('d') => 24 * 60 * 60,
+        _ => {
+            return Err(format!(
+                "expected a unix timestamp or a duration like 90m, got '{}'",
+                s
+            ))
+        }
+    };
+    let value = s[..s.len() - 1]
+        .parse::<u64>()
+        .map_err(|err| format!("invalid duration '{}': {}", s, err))?;
+    let seconds = value
+        .checked_mul(unit)
+        .ok_or_else(|| format!("duration {} is too long", s))?;
+
+    Ok(DeriveUntil::Duration(seconds))
+}
+
+fn parse_pre_bedrock_history(s: &str) -> Result<PreBedrockHistory, String> {
+    let (bedrock_parent_hash, legacy_data_hash) = s.split_once(':').ok_or_else(|| {
+        format!(
+            "expected <BEDROCK_PARENT_HASH>:<LEGACY_DATA_HASH>, got '{}'",
+            s
+        )
+    })?;
+
+    Ok(PreBedrockHistory {
+        bedrock_parent_hash: bedrock_parent_hash
+            .parse()
+            .map_err(|err| format!("{}", err))?,
+        legacy_data_hash: legacy_data_hash.parse().map_err(|err| format!("{}", err))?,
+    })
+}
@@ -0,0 +1,120 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{ensure, Context, Result};
+use serde::{Deserialize, Serialize};
+use zeth_lib::host::provider::{file_provider::FileProvider, BlockQuery, Provider};
+use zeth_primitives::{block::Header, ethers::from_ethers_h256, keccak::keccak, B256};
+
+/// File name of the manifest in the root of the corpus.
+pub const MANIFEST_FILE_NAME: &str = "manifest.json";
+
+/// Pinned contents of a single cache file of the corpus.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CorpusEntry {
+    /// Keccak hash of the compressed file.
+    pub digest: B256,
+    /// Hashes of all blocks contained in the file, keyed by block number.
+    pub blocks: BTreeMap<u64, B256>,
+}
+
+/// Manifest of the corpus, keyed by the path of each cache file relative to the root.
+pub type CorpusManifest = BTreeMap<String, CorpusEntry>;
+
+/// Computes the manifest of all cache files below `root`. The hash of every cached block
+/// is recomputed from its header and must match the hash served by the RPC node.
+pub fn corpus_manifest(root: &Path) -> Result<CorpusManifest> {
+    let mut manifest = CorpusManifest::new();
+    for path in cache_files(root)? {
+        let key = path
+            .strip_prefix(root)?
+            .to_str()
+            .context("invalid file name")?
+            .replace('\\', "/");
+        let data =
+            fs::read(&path).with_context(|| format!("Failed to read '{}'", path.display()))?;
+        let blocks = pinned_block_hashes(&path)
+            .with_context(|| format!("Invalid cache file '{}'", path.display()))?;
+        manifest.insert(
+            key,
+            CorpusEntry {
+                digest: keccak(data).into(),
+                blocks,
+            },
+        );
+    }
+
+    Ok(manifest)
+}
+
+/// Returns the keys of all entries that differ between the two manifests.
+pub fn manifest_differences(expected: &CorpusManifest, actual: &CorpusManifest) -> Vec<String> {
+    let mut keys: Vec<_> = expected.keys().chain(actual.keys()).cloned().collect();
+    keys.sort_unstable();
+    keys.dedup();
+    keys.retain(|key| expected.get(key) != actual.get(key));
+    keys
+}
+
+/// Returns the hashes of all blocks in the cache file, verifying each against its header.
+fn pinned_block_hashes(path: &Path) -> Result<BTreeMap<u64, B256>> {
+    let mut provider = FileProvider::new(path.to_path_buf())?;
+    let mut blocks = BTreeMap::new();
+    for (block_no, hash) in provider.block_hashes() {
+        let hash = from_ethers_h256(hash);
+        let header: Header = provider
+            .get_partial_block(&BlockQuery { block_no })
+            .or_else(|_| {
+                provider
+                    .get_full_block(&BlockQuery { block_no })
+                    .map(|block| block.into())
+            })?
+            .try_into()?;
+        ensure!(
+            header.hash() == hash,
+            "block {} is cached with hash {}, but its header hashes to {}",
+            block_no,
+            hash,
+            header.hash()
+        );
+        blocks.insert(block_no, hash);
+    }
+
+    Ok(blocks)
+}
+
+/// Returns the paths of all cache files below `dir`, in ascending order.
+fn cache_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir).with_context(|| format!("Failed to read '{}'", dir.display()))? {
+        let path = entry?.path();
+        if path.is_dir() {
+            files.extend(cache_files(&path)?);
+        } else if path
+            .to_str()
+            .map_or(false, |name| name.ends_with(".json.gz"))
+        {
+            files.push(path);
+        }
+    }
+    files.sort();
+
+    Ok(files)
+}
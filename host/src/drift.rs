@@ -0,0 +1,128 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::Result;
+use log::warn;
+use serde::Serialize;
+use zeth_lib::{input::BlockBuildInput, optimism::DeriveOutput};
+use zeth_primitives::transactions::optimism::OptimismTxEssence;
+
+use crate::store::l1_block_values;
+
+/// Drift between the timestamp of a derived Optimism block and its L1 origin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct BlockDrift {
+    /// Number of the block.
+    pub number: u64,
+    /// Timestamp of the block.
+    pub timestamp: u64,
+    /// Number of the L1 origin of the block.
+    pub epoch_number: u64,
+    /// Timestamp of the L1 origin of the block.
+    pub epoch_timestamp: u64,
+}
+
+impl BlockDrift {
+    /// Returns the number of seconds the block is ahead of its L1 origin.
+    pub fn drift(&self) -> u64 {
+        self.timestamp.saturating_sub(self.epoch_timestamp)
+    }
+
+    /// Returns whether the drift is at least `percent` percent of the maximum sequencer
+    /// drift.
+    pub fn is_near_limit(&self, max_seq_drift: u64, percent: u64) -> bool {
+        self.drift() * 100 >= max_seq_drift * percent
+    }
+}
+
+/// Computes the drift of all blocks that are part of the derived chain.
+pub fn block_drifts(
+    op_block_inputs: &[BlockBuildInput<OptimismTxEssence>],
+    derive_output: &DeriveOutput,
+) -> Result<Vec<BlockDrift>> {
+    let mut drifts = Vec::new();
+    for input in op_block_inputs {
+        let number = input.state_input.parent_header.number + 1;
+        if !derive_output
+            .derived_op_blocks
+            .iter()
+            .any(|block| block.number == number)
+        {
+            continue;
+        }
+        let l1_info = l1_block_values(input)?;
+        drifts.push(BlockDrift {
+            number,
+            timestamp: input.state_input.timestamp.to(),
+            epoch_number: l1_info.number(),
+            epoch_timestamp: l1_info.timestamp(),
+        });
+    }
+    Ok(drifts)
+}
+
+/// Prints the drift of every block and warns about blocks whose drift is at least
+/// `percent` percent of the maximum sequencer drift.
+pub fn report_drifts(drifts: &[BlockDrift], max_seq_drift: u64, percent: u64) {
+    for block in drifts {
+        println!(
+            "Drift: {} {}s (L1 origin {})",
+            block.number,
+            block.drift(),
+            block.epoch_number
+        );
+    }
+    let near_limit: Vec<_> = drifts
+        .iter()
+        .filter(|block| block.is_near_limit(max_seq_drift, percent))
+        .collect();
+    for block in &near_limit {
+        warn!(
+            "Block {} drifts {}s from its L1 origin {}, near the maximum sequencer drift of {}s",
+            block.number,
+            block.drift(),
+            block.epoch_number,
+            max_seq_drift
+        );
+    }
+    if let Some(max) = drifts.iter().map(BlockDrift::drift).max() {
+        println!(
+            "Maximum drift: {}s of {}s; {} of {} blocks at or above {}%",
+            max,
+            max_seq_drift,
+            near_limit.len(),
+            drifts.len(),
+            percent
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn near_limit() {
+        let block = BlockDrift {
+            number: 1,
+            timestamp: 1_700_000_540,
+            epoch_number: 2,
+            epoch_timestamp: 1_700_000_000,
+        };
+        assert_eq!(block.drift(), 540);
+        assert!(block.is_near_limit(600, 90));
+        assert!(!block.is_near_limit(600, 91));
+        assert!(!block.is_near_limit(1800, 90));
+    }
+}
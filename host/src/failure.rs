@@ -0,0 +1,170 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::fmt;
+use std::process::ExitCode;
+
+use serde::Serialize;
+use zeth_lib::{abort::AbortCode, optimism::batcher_db::EthGapError};
+
+/// The categories of failures with a stable exit code, so that scripts can react to
+/// them without parsing log messages. Failures are tagged by attaching the category as
+/// context to the error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Failure {
+    /// The witness of the guest could not be built from the provider data.
+    Witness,
+    /// An RPC request to a provider failed.
+    Rpc,
+    /// The in-memory derivation and the derivation from the witness disagree.
+    DerivationMismatch,
+    /// The prover failed to create a receipt.
+    Proving,
+    /// A receipt or SNARK did not verify.
+    Verification,
+    /// The guest aborted with an abort code, e.g. because the witness is incomplete.
+    GuestAbort,
+}
+
+impl Failure {
+    /// The exit code of every failure not covered by a category.
+    pub const GENERIC_EXIT_CODE: u8 = 1;
+
+    /// Returns the exit code of the category.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Failure::Witness => 10,
+            Failure::Rpc => 11,
+            Failure::DerivationMismatch => 12,
+            Failure::Proving => 13,
+            Failure::Verification => 14,
+            Failure::GuestAbort => 15,
+        }
+    }
+
+    /// Returns the category of the error, if any. RPC errors take precedence over the
+    /// tag, as they usually cause the tagged operation to fail.
+    pub fn classify(err: &anyhow::Error) -> Option<Failure> {
+        let is_rpc_error = err.chain().any(|cause| {
+            cause.is::<ethers_providers::ProviderError>() || cause.is::<reqwest::Error>()
+        });
+        if is_rpc_error {
+            Some(Failure::Rpc)
+        } else if let Some(failure) = err.downcast_ref::<Failure>() {
+            Some(*failure)
+        } else if err.chain().any(|cause| cause.is::<AbortCode>()) {
+            Some(Failure::GuestAbort)
+        } else if err.chain().any(|cause| cause.is::<EthGapError>()) {
+            Some(Failure::Witness)
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let description = match self {
+            Failure::Witness => "witness error",
+            Failure::Rpc => "RPC error",
+            Failure::DerivationMismatch => "derivation mismatch",
+            Failure::Proving => "proving failure",
+            Failure::Verification => "verification failure",
+            Failure::GuestAbort => "guest abort",
+        };
+        f.write_str(description)
+    }
+}
+
+/// The error object printed by `--json-errors`.
+#[derive(Debug, Serialize)]
+pub struct ErrorReport {
+    /// The category of the failure, or `null` for uncategorized failures.
+    pub category: Option<Failure>,
+    pub exit_code: u8,
+    /// The message of the outermost error.
+    pub message: String,
+    /// The messages of the underlying errors, outermost first.
+    pub causes: Vec<String>,
+}
+
+impl ErrorReport {
+    pub fn new(err: &anyhow::Error) -> Self {
+        let category = Failure::classify(err);
+        let mut chain = err.chain().map(|cause| cause.to_string());
+        ErrorReport {
+            category,
+            exit_code: category.map_or(Failure::GENERIC_EXIT_CODE, |c| c.exit_code()),
+            message: chain.next().unwrap_or_default(),
+            causes: chain.collect(),
+        }
+    }
+}
+
+/// Reports the error, either as a log message or as a JSON object on stdout, and returns
+/// the exit code of its category.
+pub fn report(err: &anyhow::Error, json: bool) -> ExitCode {
+    let report = ErrorReport::new(err);
+    if json {
+        println!("{}", serde_json::to_string(&report).unwrap());
+    } else {
+        log::error!("{:#}", err);
+    }
+    ExitCode::from(report.exit_code)
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::{anyhow, Context};
+
+    use super::*;
+
+    #[test]
+    fn classify_tagged_errors() {
+        let err = Err::<(), _>(anyhow!("journal mismatch"))
+            .context(Failure::Verification)
+            .context("failed to prove block")
+            .unwrap_err();
+        let report = ErrorReport::new(&err);
+        assert_eq!(report.category, Some(Failure::Verification));
+        assert_eq!(report.exit_code, 14);
+        assert_eq!(report.message, "failed to prove block");
+        assert_eq!(report.causes, ["verification failure", "journal mismatch"]);
+
+        let err = Err::<(), _>(EthGapError::MissingBlock(1))
+            .context("replay failed")
+            .unwrap_err();
+        assert_eq!(Failure::classify(&err), Some(Failure::Witness));
+
+        let err = Err::<(), _>(AbortCode::WitnessMissingBlock)
+            .context(Failure::GuestAbort)
+            .unwrap_err();
+        let report = ErrorReport::new(&err);
+        assert_eq!(report.exit_code, 15);
+        assert_eq!(report.causes, ["block missing from the witness"]);
+
+        let err = anyhow!("unknown");
+        assert_eq!(Failure::classify(&err), None);
+        assert_eq!(ErrorReport::new(&err).exit_code, Failure::GENERIC_EXIT_CODE);
+    }
+
+    #[test]
+    fn rpc_errors_take_precedence() {
+        let err = Err::<(), _>(ethers_providers::ProviderError::CustomError("503".into()))
+            .context(Failure::Witness)
+            .unwrap_err();
+        assert_eq!(Failure::classify(&err), Some(Failure::Rpc));
+    }
+}
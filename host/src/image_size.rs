@@ -0,0 +1,118 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::{Context, Result};
+use elf::{
+    abi::{SHF_ALLOC, STT_FUNC},
+    endian::AnyEndian,
+    ElfBytes,
+};
+
+/// Size of a section or function of an ELF image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ItemSize {
+    /// Name of the section, or demangled name of the function.
+    pub name: String,
+    /// Size in bytes.
+    pub size: u64,
+}
+
+/// Size report of an ELF image.
+#[derive(Debug, Clone, Default)]
+pub struct ImageReport {
+    /// Sections loaded into memory, in the order of the section header table.
+    pub sections: Vec<ItemSize>,
+    /// All functions, largest first.
+    pub functions: Vec<ItemSize>,
+}
+
+impl ImageReport {
+    /// Returns the total size of the loaded sections.
+    pub fn loaded_size(&self) -> u64 {
+        self.sections.iter().map(|section| section.size).sum()
+    }
+}
+
+/// Computes the sizes of the loaded sections and of all function symbols of the image.
+///
+/// Every monomorphization of a generic function is a separate symbol, so the largest
+/// instances show which generic code contributes most to the image.
+pub fn image_report(data: &[u8]) -> Result<ImageReport> {
+    let file = ElfBytes::<AnyEndian>::minimal_parse(data).context("invalid ELF")?;
+
+    let mut report = ImageReport::default();
+    if let (Some(headers), Some(names)) = file.section_headers_with_strtab()? {
+        for header in headers.iter() {
+            if header.sh_flags & u64::from(SHF_ALLOC) == 0 || header.sh_size == 0 {
+                continue;
+            }
+            report.sections.push(ItemSize {
+                name: names.get(header.sh_name as usize)?.to_string(),
+                size: header.sh_size,
+            });
+        }
+    }
+
+    let (symbols, names) = file
+        .symbol_table()?
+        .context("the image has no symbol table")?;
+    for symbol in symbols.iter() {
+        if symbol.st_symtype() != STT_FUNC || symbol.st_size == 0 {
+            continue;
+        }
+        let name = names.get(symbol.st_name as usize)?;
+        report.functions.push(ItemSize {
+            // the alternate format omits the hash of legacy mangled names
+            name: format!("{:#}", rustc_demangle::demangle(name)),
+            size: symbol.st_size,
+        });
+    }
+    report
+        .functions
+        .sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.name.cmp(&b.name)));
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn report_of_current_exe() {
+        let data = std::fs::read(std::env::current_exe().unwrap()).unwrap();
+        let report = image_report(&data).unwrap();
+
+        assert!(report
+            .sections
+            .iter()
+            .any(|section| section.name == ".text"));
+        assert!(report.loaded_size() > 0);
+        assert!(!report.functions.is_empty());
+        assert!(report
+            .functions
+            .windows(2)
+            .all(|pair| pair[0].size >= pair[1].size));
+        assert!(report
+            .functions
+            .iter()
+            .any(|function| function.name.contains("report_of_current_exe")));
+    }
+
+    #[test]
+    fn invalid_image() {
+        assert!(image_report(b"not an ELF file").is_err());
+    }
+}
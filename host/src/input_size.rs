@@ -0,0 +1,67 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Guardrails against guest inputs that are too large to fit into the guest memory.
+
+use anyhow::{ensure, Context};
+use risc0_zkvm::serde::to_vec;
+use serde::Serialize;
+
+/// Default limit of the serialized guest input in MB.
+pub const DEFAULT_MAX_INPUT_MB: u64 = 64;
+
+/// Returns the size in bytes of the input as written to the guest.
+pub fn input_size<T: Serialize + ?Sized>(input: &T) -> anyhow::Result<u64> {
+    let words = to_vec(input).context("Failed to serialize guest input")?;
+    Ok(words.len() as u64 * 4)
+}
+
+/// Ensures that an input of the given size in bytes does not exceed `max_mb` MB, so that
+/// the preflight aborts instead of the guest running out of memory later. The hint
+/// suggests how to make the input smaller. A limit of zero disables the check.
+pub fn ensure_input_size(size: u64, max_mb: u64, hint: &str) -> anyhow::Result<()> {
+    ensure!(
+        max_mb == 0 || size <= max_mb * 1_000_000,
+        "The guest input of {} MB exceeds the limit of {} MB (--max-input-size); {}",
+        size.div_ceil(1_000_000),
+        max_mb,
+        hint
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limit() {
+        ensure_input_size(64_000_000, 64, "").unwrap();
+        ensure_input_size(u64::MAX, 0, "").unwrap();
+        let err = ensure_input_size(64_000_001, 64, "use fewer blocks").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "The guest input of 65 MB exceeds the limit of 64 MB (--max-input-size); use fewer \
+             blocks"
+        );
+    }
+
+    #[test]
+    fn serialized_size() {
+        // every byte of a vector takes a full word
+        assert_eq!(input_size(&vec![0u8; 10]).unwrap(), 44);
+        assert_eq!(input_size(&42u64).unwrap(), 8);
+    }
+}
@@ -17,8 +17,16 @@ use std::{fs, path::Path};
 use risc0_zkvm::is_dev_mode;
 use tracing::debug;
 
+pub mod audit;
+pub mod batch_data;
 pub mod cli;
+pub mod corpus;
+pub mod drift;
+pub mod failure;
+pub mod image_size;
+pub mod input_size;
 pub mod operations;
+pub mod store;
 
 pub fn load_receipt<T: serde::de::DeserializeOwned>(
     file_name: &String,
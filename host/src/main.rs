@@ -14,72 +14,218 @@
 
 extern crate core;
 
-use anyhow::Result;
+use std::process::ExitCode;
+
+use anyhow::{bail, ensure, Result};
 use clap::Parser;
 use log::info;
-use risc0_zkvm::sha::Digest;
+use risc0_zkvm::{compute_image_id, is_dev_mode, sha::Digest};
 use zeth::{
-    cli::{Cli, Network},
-    operations::{build, rollups, snarks::verify_groth16_snark, stark2snark},
+    audit,
+    cli::{Cli, Network, OpChain, QueryTarget, ZethArgs},
+    failure,
+    operations::{
+        backend, backfill, bench, build, corpus, cycles,
+        guest::{
+            EthBlock, EthBundle, GuestProgram, OpBlock, OpCompose, OpDerive, OpUnsafe,
+            GUEST_IMAGE_IDS,
+        },
+        image_size, monitor, rollups,
+        snarks::verify_groth16_snark,
+        stark2snark, unsafe_payloads, verify_cache, witness, witness_diff,
+    },
+    store::DerivedBlockStore,
 };
-use zeth_guests::*;
+use zeth_guests::PREVIOUS_GUEST_ELFS;
 use zeth_lib::{
     builder::{EthereumStrategy, OptimismStrategy},
-    consts::{ETH_MAINNET_CHAIN_SPEC, OP_MAINNET_CHAIN_SPEC},
+    consts::ETH_CHAIN_SPEC,
+    host::provider::audit as rpc_audit,
 };
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> ExitCode {
     env_logger::init();
-    let cli = Cli::parse();
+    let args = ZethArgs::parse();
+    match run(args.command).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => failure::report(&err, args.json_errors),
+    }
+}
+
+async fn run(mut cli: Cli) -> Result<()> {
+    // the Optimism guests are only built for OP mainnet
+    let native_only = matches!(cli, Cli::Build(..) | Cli::Monitor(..) | Cli::Backfill(..));
+    if let Some(build_args) = cli.build_args_mut() {
+        // a registry chain may select a built-in chain
+        build_args.load_chain()?;
+        if build_args.op_chain != OpChain::guest() {
+            ensure!(
+                native_only && build_args.composition.is_none(),
+                "The {} chain can only be built natively and without composition",
+                build_args.op_chain
+            );
+        }
+        if build_args.custom_chain.is_some() {
+            // only the derivation guest reads the chain configuration from its input
+            ensure!(
+                native_only || matches!(build_args.network, Network::OptimismDerived),
+                "A rollup config can only be used natively or to derive blocks"
+            );
+            ensure!(
+                build_args.composition.is_none(),
+                "A rollup config cannot be used with composition"
+            );
+        }
+        build_args.scope_cache();
+        build_args.resolve_block_number().await?;
+        // detect inconsistent providers before any expensive work
+        if let Some(sample_size) = build_args.verify_cache {
+            verify_cache::verify_cache(build_args, sample_size).await?;
+        }
+        if build_args.audit_key.is_some() {
+            rpc_audit::enable();
+        }
+    }
+
+    // fail early if the local prover cannot run on this machine
+    if let Cli::Prove(prove_args) = &cli {
+        if !prove_args.submit_to_bonsai && !is_dev_mode() {
+            let backend = backend::select(prove_args.prover_backend)?;
+            info!(
+                "Proving on {} (roughly {} cycles/s)",
+                backend,
+                backend.expected_throughput()
+            );
+        }
+    }
 
     info!("Using the following image ids:");
-    info!("  eth-block: {}", Digest::from(ETH_BLOCK_ID));
-    info!("  op-block: {}", Digest::from(OP_BLOCK_ID));
-    info!("  op-derive: {}", Digest::from(OP_DERIVE_ID));
-    info!("  op-compose: {}", Digest::from(OP_COMPOSE_ID));
+    for (name, image_id) in GUEST_IMAGE_IDS {
+        info!("  {}: {}", name, Digest::from(image_id));
+    }
+    for (name, input_version, elf) in PREVIOUS_GUEST_ELFS {
+        info!(
+            "  {} (input version {}): {}",
+            name,
+            input_version,
+            compute_image_id(elf)?
+        );
+    }
+
+    // monitoring runs until interrupted and does not create any proofs
+    if let Cli::Monitor(..) = cli {
+        return monitor::monitor_derived_chain(&cli).await;
+    }
+
+    // backfills only derive blocks, without creating any proofs
+    if let Cli::Backfill(backfill_args) = &cli {
+        return backfill::backfill(backfill_args).await;
+    }
+
+    // queries only read the database of derived blocks
+    if let Cli::Query(query_args) = &cli {
+        let store = DerivedBlockStore::open(&query_args.db)?;
+        match query_args.target {
+            QueryTarget::Block { number } => match store.get(number)? {
+                Some(record) => println!("{}", serde_json::to_string_pretty(&record)?),
+                None => bail!("Block {} not found in the database", number),
+            },
+        }
+        return Ok(());
+    }
+
+    // witness bundles are proven by external provers
+    if let Cli::Witness(witness_args) = &cli {
+        return witness::publish_witnesses(witness_args).await;
+    }
+
+    // cycle regression checks only execute cached blocks
+    if let Cli::Cycles(cycles_args) = &cli {
+        return cycles::check_cycles(cycles_args).await;
+    }
+
+    // benchmarks only execute the blocks of the test corpus
+    if let Cli::Bench(bench_args) = &cli {
+        return bench::run_bench(bench_args).await;
+    }
+
+    // image reports only inspect the embedded guests
+    if let Cli::ImageSize(image_size_args) = &cli {
+        return image_size::report_image_sizes(image_size_args);
+    }
+
+    // witness diffs only read cached derive inputs
+    if let Cli::WitnessDiff(witness_diff_args) = &cli {
+        return witness_diff::print_witness_diff(witness_diff_args);
+    }
+
+    // the corpus only caches RPC data for the tests
+    if let Cli::Corpus(corpus_args) = &cli {
+        return corpus::update_corpus(corpus_args).await;
+    }
 
     // execute the command
-    let build_args = cli.build_args();
+    let Some(build_args) = cli.build_args() else {
+        bail!("The command does not build any blocks");
+    };
     let (image_id, stark) = match build_args.network {
         Network::Ethereum => {
             let rpc_url = build_args.eth_rpc_url.clone();
-            (
-                ETH_BLOCK_ID,
-                build::build_block::<EthereumStrategy>(
-                    &cli,
-                    rpc_url,
-                    &ETH_MAINNET_CHAIN_SPEC,
-                    ETH_BLOCK_ELF,
+            if build_args.block_count > 1 {
+                (
+                    EthBundle::ID,
+                    build::build_bundle::<EthereumStrategy, EthBundle>(
+                        &cli,
+                        rpc_url,
+                        ETH_CHAIN_SPEC,
+                    )
+                    .await?,
                 )
-                .await?,
-            )
+            } else {
+                (
+                    EthBlock::ID,
+                    build::build_block::<EthereumStrategy, EthBlock>(&cli, rpc_url, ETH_CHAIN_SPEC)
+                        .await?,
+                )
+            }
         }
         Network::Optimism => {
-            let rpc_url = build_args.op_rpc_url.clone();
-            (
-                OP_BLOCK_ID,
-                build::build_block::<OptimismStrategy>(
-                    &cli,
-                    rpc_url,
-                    &OP_MAINNET_CHAIN_SPEC,
-                    OP_BLOCK_ELF,
+            if let Some(payloads_path) = &build_args.unsafe_payloads {
+                (
+                    OpUnsafe::ID,
+                    unsafe_payloads::attest_unsafe_payloads(&cli, payloads_path).await?,
                 )
-                .await?,
-            )
+            } else {
+                let rpc_url = build_args.op_rpc_url.clone();
+                (
+                    OpBlock::ID,
+                    build::build_block::<OptimismStrategy, OpBlock>(
+                        &cli,
+                        rpc_url,
+                        build_args.chain_config().chain_spec,
+                    )
+                    .await?,
+                )
+            }
         }
         Network::OptimismDerived => {
             if let Some(composition_size) = build_args.composition {
                 (
-                    OP_COMPOSE_ID,
+                    OpCompose::ID,
                     rollups::compose_derived_rollup_blocks(&cli, composition_size).await?,
                 )
             } else {
-                (OP_DERIVE_ID, rollups::derive_rollup_blocks(&cli).await?)
+                (OpDerive::ID, rollups::derive_rollup_blocks(&cli).await?)
             }
         }
     };
 
+    // sign the data sources of the input
+    if let Some(audit_key) = &build_args.audit_key {
+        audit::write_manifest(build_args, audit_key)?;
+    }
+
     // Create/verify Groth16 SNARK
     if cli.snark() {
         let Some((stark_uuid, stark_receipt)) = stark else {
@@ -0,0 +1,157 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::fmt;
+use std::path::Path;
+
+use anyhow::{bail, ensure, Result};
+use clap::ValueEnum;
+use log::warn;
+
+/// A hardware backend of the local prover.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ProverBackend {
+    /// Prove on the CPU
+    Cpu,
+    /// Prove on an Apple GPU using Metal
+    Metal,
+    /// Prove on an NVIDIA GPU using CUDA
+    Cuda,
+}
+
+impl fmt::Display for ProverBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // use the name of the clap::ValueEnum
+        let val = self.to_possible_value().unwrap();
+        write!(f, "{}", val.get_name())
+    }
+}
+
+impl ProverBackend {
+    /// Returns the backend compiled into this binary.
+    ///
+    /// The prover selects its backend when it is built, so a binary can only prove on the
+    /// backend of its features.
+    pub fn compiled() -> Self {
+        if cfg!(feature = "cuda") {
+            ProverBackend::Cuda
+        } else if cfg!(feature = "metal") {
+            ProverBackend::Metal
+        } else {
+            ProverBackend::Cpu
+        }
+    }
+
+    /// Returns whether a device of this backend is present on the machine.
+    pub fn is_available(self) -> bool {
+        match self {
+            ProverBackend::Cpu => true,
+            ProverBackend::Metal => cfg!(target_os = "macos"),
+            ProverBackend::Cuda => {
+                Path::new("/dev/nvidiactl").exists()
+                    || Path::new("/proc/driver/nvidia/version").exists()
+            }
+        }
+    }
+
+    /// Returns the rough number of cycles proven per second on typical hardware.
+    pub fn expected_throughput(self) -> u64 {
+        match self {
+            ProverBackend::Cpu => 25_000,
+            ProverBackend::Metal => 100_000,
+            ProverBackend::Cuda => 500_000,
+        }
+    }
+
+    /// Returns the cargo feature building the prover for this backend, if any.
+    fn feature(self) -> Option<&'static str> {
+        match self {
+            ProverBackend::Cpu => None,
+            ProverBackend::Metal => Some("metal"),
+            ProverBackend::Cuda => Some("cuda"),
+        }
+    }
+}
+
+/// Selects the backend of the local prover, detecting it unless `requested` is given.
+///
+/// Fails if the selected backend was not compiled into this binary or no device of it is
+/// present, before any proving work is done.
+pub fn select(requested: Option<ProverBackend>) -> Result<ProverBackend> {
+    let compiled = ProverBackend::compiled();
+    let backend = match requested {
+        Some(backend) => backend,
+        None => {
+            // the fastest available backend that is not compiled in can only be recommended
+            let best = [ProverBackend::Cuda, ProverBackend::Metal]
+                .into_iter()
+                .find(|backend| backend.is_available())
+                .unwrap_or(ProverBackend::Cpu);
+            if best.expected_throughput() > compiled.expected_throughput() {
+                warn!(
+                    "A {} device was detected, build with `-F {}` for faster proving",
+                    best,
+                    best.feature().unwrap()
+                );
+            }
+            compiled
+        }
+    };
+
+    if backend != compiled {
+        match backend.feature() {
+            Some(feature) => bail!(
+                "The {} backend requires a build with `-F {}`, this build proves on {}",
+                backend,
+                feature,
+                compiled
+            ),
+            None => bail!(
+                "The cpu backend requires a build without GPU features, this build proves on {}",
+                compiled
+            ),
+        }
+    }
+    ensure!(
+        backend.is_available(),
+        "This build proves on {}, but no {} device was found",
+        backend,
+        backend
+    );
+
+    Ok(backend)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn select_backend() {
+        let compiled = ProverBackend::compiled();
+        if compiled.is_available() {
+            assert_eq!(select(None).unwrap(), compiled);
+            assert_eq!(select(Some(compiled)).unwrap(), compiled);
+        }
+        for backend in [
+            ProverBackend::Cpu,
+            ProverBackend::Metal,
+            ProverBackend::Cuda,
+        ] {
+            if backend != compiled {
+                assert!(select(Some(backend)).is_err());
+            }
+        }
+    }
+}
@@ -0,0 +1,258 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use anyhow::{ensure, Context};
+use log::info;
+use zeth_guests::OP_BLOCK_ID;
+use zeth_lib::{
+    host::{channel_cache::ChannelCache, rpc_db::RpcDb, ProviderFactory},
+    input::BlockBuildInput,
+    optimism::{
+        config::ChainConfig, BlockProvenance, DeriveCheckpoint, DeriveInput, DeriveMachine,
+        DeriveOutput,
+    },
+};
+use zeth_primitives::{transactions::optimism::OptimismTxEssence, Address};
+
+use crate::{
+    cli::{BackfillArgs, BuildArgs, Network},
+    operations::rollups::{load_checkpoint, load_prior_output, save_checkpoint},
+    store::l1_block_values,
+};
+
+/// Columns of the CSV file of derived blocks.
+const BLOCK_COLUMNS: &str =
+    "number,hash,parent_hash,timestamp,epoch_number,epoch_hash,tx_count,deposit_count";
+/// Columns of the CSV file of user deposits.
+const DEPOSIT_COLUMNS: &str =
+    "op_block_number,tx_index,tx_hash,source_hash,from,to,mint,value,gas_limit";
+/// Columns of the CSV file of batch provenance.
+const BATCH_COLUMNS: &str = "op_block_number,epoch_number,batch_eth_block_number";
+
+/// Blocks derived in a single derivation call.
+struct DerivedChunk {
+    op_block_inputs: Vec<BlockBuildInput<OptimismTxEssence>>,
+    derive_output: DeriveOutput,
+    provenance: Vec<BlockProvenance>,
+    checkpoint: DeriveCheckpoint,
+}
+
+/// Derives the Optimism blocks of the given range without proving and writes their
+/// metadata, user deposits and batch provenance as CSV files into the output directory.
+pub async fn backfill(args: &BackfillArgs) -> anyhow::Result<()> {
+    let build_args = &args.build_args;
+    ensure!(
+        matches!(build_args.network, Network::OptimismDerived),
+        "Backfilling is only supported for the optimism-derived network"
+    );
+    ensure!(args.chunk_size > 0, "The chunk size must be positive");
+
+    fs::create_dir_all(&args.out_dir)
+        .with_context(|| format!("Failed to create '{}'", args.out_dir.display()))?;
+    let mut blocks = create_csv(&args.out_dir.join("blocks.csv"), BLOCK_COLUMNS)?;
+    let mut deposits = create_csv(&args.out_dir.join("deposits.csv"), DEPOSIT_COLUMNS)?;
+    let mut batches = create_csv(&args.out_dir.join("batches.csv"), BATCH_COLUMNS)?;
+
+    let end_block_no = build_args.block_number + build_args.block_count as u64;
+    let mut op_head_block_no = build_args.block_number;
+    // every chunk continues the output of the previous one from its checkpoint
+    let mut prior_output = build_args
+        .prior_receipt
+        .as_deref()
+        .map(load_prior_output)
+        .transpose()?;
+    let mut checkpoint = build_args
+        .resume_checkpoint
+        .as_deref()
+        .map(load_checkpoint)
+        .transpose()?;
+    while op_head_block_no < end_block_no {
+        let block_count = (end_block_no - op_head_block_no).min(args.chunk_size as u64) as u32;
+        let chunk_args = build_args.clone();
+        let chunk = tokio::task::spawn_blocking(move || {
+            derive_chunk(
+                &chunk_args,
+                op_head_block_no,
+                block_count,
+                prior_output,
+                checkpoint,
+            )
+        })
+        .await??;
+
+        for input in &chunk.op_block_inputs {
+            let number = input.state_input.parent_header.number + 1;
+            let Some(derived) = chunk
+                .derive_output
+                .derived_op_blocks
+                .iter()
+                .find(|block| block.number == number)
+            else {
+                continue;
+            };
+            let l1_info = l1_block_values(input)?;
+            // the first transaction is the L1 attributes deposited transaction
+            let user_deposits: Vec<_> = input
+                .state_input
+                .transactions
+                .iter()
+                .enumerate()
+                .skip(1)
+                .filter_map(|(tx_index, tx)| match &tx.essence {
+                    OptimismTxEssence::OptimismDeposited(deposit) => {
+                        Some((tx_index, tx.hash(), deposit))
+                    }
+                    OptimismTxEssence::Ethereum(_) => None,
+                })
+                .collect();
+
+            writeln!(
+                blocks,
+                "{},{},{},{},{},{},{},{}",
+                number,
+                derived.hash,
+                input.state_input.parent_header.hash(),
+                input.state_input.timestamp,
+                l1_info.number(),
+                l1_info.hash(),
+                input.state_input.transactions.len(),
+                user_deposits.len()
+            )?;
+            for (tx_index, tx_hash, deposit) in user_deposits {
+                let to: Option<Address> = deposit.to.into();
+                writeln!(
+                    deposits,
+                    "{},{},{},{},{},{},{},{},{}",
+                    number,
+                    tx_index,
+                    tx_hash,
+                    deposit.source_hash,
+                    deposit.from,
+                    to.map(|to| to.to_string()).unwrap_or_default(),
+                    deposit.mint,
+                    deposit.value,
+                    deposit.gas_limit
+                )?;
+            }
+        }
+        for provenance in &chunk.provenance {
+            writeln!(
+                batches,
+                "{},{},{}",
+                provenance.op_block_number,
+                provenance.epoch_number,
+                provenance.batch_eth_block_number
+            )?;
+        }
+
+        let last_block_no = chunk
+            .derive_output
+            .derived_op_blocks
+            .last()
+            .map(|block| block.number)
+            .with_context(|| format!("No blocks derived after {}", op_head_block_no))?;
+        info!(
+            "Backfilled blocks {} to {}",
+            op_head_block_no + 1,
+            last_block_no
+        );
+        op_head_block_no = last_block_no;
+        prior_output = Some(chunk.derive_output);
+        checkpoint = Some(chunk.checkpoint);
+    }
+    if let (Some(path), Some(checkpoint)) = (&build_args.checkpoint, &checkpoint) {
+        save_checkpoint(path, checkpoint)?;
+    }
+
+    blocks.flush()?;
+    deposits.flush()?;
+    batches.flush()?;
+    info!(
+        "Wrote {} blocks to {}",
+        build_args.block_count,
+        args.out_dir.display()
+    );
+
+    Ok(())
+}
+
+/// Derives the blocks following the given Optimism head, continuing the prior output if
+/// given, from its checkpoint if given.
+fn derive_chunk(
+    build_args: &BuildArgs,
+    op_head_block_no: u64,
+    block_count: u32,
+    prior_output: Option<DeriveOutput>,
+    resume_checkpoint: Option<DeriveCheckpoint>,
+) -> anyhow::Result<DerivedChunk> {
+    let config = build_args.chain_config();
+    let derive_input = DeriveInput {
+        db: RpcDb::new(
+            &config,
+            build_args.eth_rpc_url.clone(),
+            build_args.op_rpc_url.clone(),
+            build_args.cache.clone(),
+        )
+        .with_beacon_rpc_url(build_args.beacon_rpc_url.clone())
+        .with_validation_policy(build_args.validation_policy()),
+        op_head_block_no,
+        op_derive_block_count: block_count,
+        op_derive_max_timestamp: None,
+        op_block_outputs: vec![],
+        block_image_id: OP_BLOCK_ID,
+        eth_accumulator: false,
+        op_anchor_block_no: None,
+        batch_provenance: false,
+        rollup_config: build_args.custom_rollup_config(),
+        prior_output,
+        checkpoint: true,
+        resume_checkpoint,
+    };
+    let provider_factory = ProviderFactory::new(
+        build_args.cache.clone(),
+        Network::Optimism.to_string(),
+        build_args.op_rpc_url.clone(),
+    );
+    let mut derive_machine = DeriveMachine::new(config, derive_input, Some(provider_factory))
+        .context("Could not create derive machine")?;
+    derive_machine
+        .op_batcher
+        .set_channel_cache(build_args.cache.as_deref().map(ChannelCache::new));
+    let mut op_block_inputs = vec![];
+    let derive_output = derive_machine
+        .derive(Some(&mut op_block_inputs))
+        .context("could not derive")?;
+
+    Ok(DerivedChunk {
+        op_block_inputs,
+        derive_output,
+        checkpoint: derive_machine.checkpoint(),
+        provenance: derive_machine.op_block_provenance,
+    })
+}
+
+/// Creates the CSV file at the given path and writes its header.
+fn create_csv(path: &Path, columns: &str) -> anyhow::Result<BufWriter<File>> {
+    let file =
+        File::create(path).with_context(|| format!("Failed to create '{}'", path.display()))?;
+    let mut writer = BufWriter::new(file);
+    writeln!(writer, "{}", columns)?;
+    Ok(writer)
+}
@@ -0,0 +1,203 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{fmt::Debug, fs, time::Instant};
+
+use anyhow::{ensure, Context};
+use ethers_core::types::Transaction as EthersTransaction;
+use log::info;
+use serde::{Deserialize, Serialize};
+use zeth_lib::{
+    builder::{BlockBuilderStrategy, EthereumStrategy, OptimismStrategy},
+    consts::{ChainSpec, ETH_CHAIN_SPEC, OP_CHAIN_SPEC},
+    input::BlockWitness,
+    output::BlockBuildOutput,
+};
+
+use crate::{
+    cli::{BenchArgs, Network},
+    operations::{
+        cycles::cached_input,
+        guest::{EthBlock, GuestProgram, OpBlock},
+        run_executor,
+    },
+};
+
+/// The representative blocks of the test corpus executed by the benchmark.
+const BENCH_INPUTS: [(&str, Network, u64); 3] = [
+    ("small", Network::Optimism, 107728767),
+    ("medium", Network::Ethereum, 16424130),
+    ("large", Network::Ethereum, 17034871),
+];
+
+/// The measurements of a single benchmark input.
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchResult {
+    /// The name of the input.
+    pub input: &'static str,
+    /// The block, as `<network>/<block number>`.
+    pub block: String,
+    /// Cycles spent executing guest instructions.
+    pub user_cycles: u64,
+    /// Cycles of all segments, including paging and padding.
+    pub total_cycles: u64,
+    /// The number of segments.
+    pub segments: usize,
+    /// The peak resident memory of the host during the execution in bytes, if known.
+    pub peak_memory: Option<u64>,
+    /// The wall time of the execution in seconds.
+    pub wall_time: f64,
+}
+
+/// Executes the guests for the benchmark inputs without proving and prints the cycles,
+/// memory and wall time of every execution.
+pub async fn run_bench(args: &BenchArgs) -> anyhow::Result<()> {
+    let mut results = Vec::with_capacity(BENCH_INPUTS.len());
+    for (input, network, block_number) in BENCH_INPUTS {
+        let result = match network {
+            Network::Ethereum => {
+                bench::<EthereumStrategy, EthBlock>(
+                    args,
+                    input,
+                    network,
+                    ETH_CHAIN_SPEC,
+                    block_number,
+                )
+                .await?
+            }
+            Network::Optimism => {
+                bench::<OptimismStrategy, OpBlock>(
+                    args,
+                    input,
+                    network,
+                    OP_CHAIN_SPEC,
+                    block_number,
+                )
+                .await?
+            }
+            Network::OptimismDerived => unreachable!(),
+        };
+        results.push(result);
+    }
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&results)?);
+        return Ok(());
+    }
+    println!(
+        "zeth {} (risc0-zkvm {}), segment limit 2^{}",
+        env!("CARGO_PKG_VERSION"),
+        risc0_zkvm::VERSION,
+        args.execution_po2
+    );
+    println!(
+        "{:<8} {:<20} {:>14} {:>14} {:>9} {:>12} {:>10}",
+        "input", "block", "user cycles", "total cycles", "segments", "peak memory", "wall time"
+    );
+    for result in &results {
+        let peak_memory = result.peak_memory.map_or("n/a".to_string(), |bytes| {
+            format!("{} MiB", bytes / (1024 * 1024))
+        });
+        println!(
+            "{:<8} {:<20} {:>14} {:>14} {:>9} {:>12} {:>9.2}s",
+            result.input,
+            result.block,
+            result.user_cycles,
+            result.total_cycles,
+            result.segments,
+            peak_memory,
+            result.wall_time
+        );
+    }
+
+    Ok(())
+}
+
+/// Executes the guest for the cached block and measures the execution.
+async fn bench<N: BlockBuilderStrategy, G>(
+    args: &BenchArgs,
+    input: &'static str,
+    network: Network,
+    chain_spec: &ChainSpec,
+    block_number: u64,
+) -> anyhow::Result<BenchResult>
+where
+    G: GuestProgram<Input = BlockWitness<N::TxEssence>, Output = BlockBuildOutput>,
+    N::TxEssence: 'static + Send + TryFrom<EthersTransaction> + Serialize + Deserialize<'static>,
+    <N::TxEssence as TryFrom<EthersTransaction>>::Error: Debug,
+{
+    let block = format!("{}/{}", network, block_number);
+    let (encoded_input, expected_output) =
+        cached_input::<N>(&args.cache, &network, chain_spec, block_number)
+            .await
+            .with_context(|| format!("Failed to load the {} input {}", input, block))?;
+
+    info!("Executing {} ...", block);
+    reset_peak_memory();
+    let start = Instant::now();
+    let (session, _) = run_executor(&encoded_input, args.execution_po2, false, G::ELF, &block)
+        .with_context(|| format!("execution of {} failed", block))?;
+    let wall_time = start.elapsed().as_secs_f64();
+    let peak_memory = peak_memory();
+
+    let output: BlockBuildOutput = session
+        .journal
+        .as_ref()
+        .context("missing journal")?
+        .decode()
+        .context("Could not decode journal")?;
+    ensure!(output == expected_output, "Output mismatch for {}", block);
+
+    Ok(BenchResult {
+        input,
+        block,
+        user_cycles: session.user_cycles,
+        total_cycles: session.total_cycles,
+        segments: session.segments.len(),
+        peak_memory,
+        wall_time,
+    })
+}
+
+/// Resets the peak resident memory of the process. It is only supported on Linux.
+fn reset_peak_memory() {
+    let _ = fs::write("/proc/self/clear_refs", "5");
+}
+
+/// Returns the peak resident memory of the process in bytes, if known.
+fn peak_memory() -> Option<u64> {
+    parse_peak_memory(&fs::read_to_string("/proc/self/status").ok()?)
+}
+
+/// Parses the peak resident memory from the contents of `/proc/<pid>/status`.
+fn parse_peak_memory(status: &str) -> Option<u64> {
+    let value = status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmHWM:"))?;
+    let kib: u64 = value.trim().strip_suffix("kB")?.trim().parse().ok()?;
+    Some(kib * 1024)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_status() {
+        let status =
+            "Name:\tzeth\nVmPeak:\t  204800 kB\nVmHWM:\t   10240 kB\nVmRSS:\t    8192 kB\n";
+        assert_eq!(parse_peak_memory(status), Some(10 * 1024 * 1024));
+        assert_eq!(parse_peak_memory("Name:\tzeth\n"), None);
+    }
+}
@@ -14,73 +14,115 @@
 
 use std::fmt::Debug;
 
-use anyhow::Context;
+use anyhow::{ensure, Context};
 use ethers_core::types::Transaction as EthersTransaction;
 use log::{info, warn};
-use risc0_zkvm::{compute_image_id, Receipt};
-use serde::{Deserialize, Serialize};
+use risc0_zkvm::Receipt;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use zeth_lib::{
     builder::BlockBuilderStrategy,
     consts::ChainSpec,
-    host::{cache_file_path, preflight::Preflight, verify::Verifier},
-    input::BlockBuildInput,
-    output::BlockBuildOutput,
+    host::{
+        cache_file_path,
+        preflight::{preflight_bundle, Preflight},
+        verify::Verifier,
+    },
+    input::{BlockBuildInput, BlockBundleInput, BlockWitness, FlatBlockBuildInput},
+    output::{BlockBuildOutput, BlockBundleOutput},
 };
 
 use crate::{
     cli::Cli,
-    operations::{execute, maybe_prove, verify_bonsai_receipt},
+    failure::Failure,
+    input_size::{ensure_input_size, input_size},
+    operations::{
+        guest::{run_guest, GuestProgram},
+        witness::load_bundle_input,
+    },
 };
 
 /// Build a single block using the specified strategy.
-pub async fn build_block<N: BlockBuilderStrategy>(
+pub async fn build_block<N: BlockBuilderStrategy, G>(
     cli: &Cli,
     rpc_url: Option<String>,
     chain_spec: &ChainSpec,
-    guest_elf: &[u8],
 ) -> anyhow::Result<Option<(String, Receipt)>>
 where
-    N::TxEssence: 'static + Send + TryFrom<EthersTransaction> + Serialize + Deserialize<'static>,
+    G: GuestProgram<Input = BlockWitness<N::TxEssence>, Output = BlockBuildOutput>,
+    N::TxEssence: 'static + Send + TryFrom<EthersTransaction> + Serialize + DeserializeOwned,
     <N::TxEssence as TryFrom<EthersTransaction>>::Error: Debug,
 {
-    let build_args = cli.build_args().clone();
+    let build_args = cli.build_args().context("missing build arguments")?.clone();
     if build_args.block_count > 1 {
         warn!("Building multiple blocks is not supported. Only the first block will be built.");
     }
 
-    // Fetch all of the initial data
-    let rpc_cache = build_args.cache.as_ref().map(|dir| {
-        cache_file_path(
-            dir,
-            &build_args.network.to_string(),
+    // A signed witness bundle replaces the preflight, so there is no provider data to
+    // verify the final state against
+    let (input, preflight_data) = if let Some(bundle_path) = &build_args.witness_bundle {
+        info!("Loading witness bundle '{}'", bundle_path.display());
+        let input = load_bundle_input(
+            bundle_path,
+            &build_args.trusted_signer,
+            &build_args.network,
             build_args.block_number,
-            "json.gz",
+            chain_spec,
         )
-    });
+        .context("invalid witness bundle")
+        .context(Failure::Witness)?;
+        (input, None)
+    } else {
+        // Fetch all of the initial data
+        let rpc_cache = build_args.cache.as_ref().map(|dir| {
+            cache_file_path(
+                dir,
+                &build_args.network.to_string(),
+                build_args.block_number,
+                "json.gz",
+            )
+        });
 
-    let init_spec = chain_spec.clone();
-    let preflight_result = tokio::task::spawn_blocking(move || {
-        N::preflight_with_external_data(&init_spec, rpc_cache, rpc_url, build_args.block_number)
-    })
-    .await?;
-    let preflight_data = preflight_result.context("preflight failed")?;
+        let init_spec = chain_spec.clone();
+        let preflight_result = tokio::task::spawn_blocking(move || {
+            N::preflight_with_external_data(&init_spec, rpc_cache, rpc_url, build_args.block_number)
+        })
+        .await?;
+        let preflight_data = preflight_result
+            .context("preflight failed")
+            .context(Failure::Witness)?;
 
-    // Create the guest input from [Init]
-    let input: BlockBuildInput<N::TxEssence> = preflight_data
-        .clone()
-        .try_into()
-        .context("invalid preflight data")?;
+        // Create the guest input from [Init]
+        let input: BlockBuildInput<N::TxEssence> = preflight_data
+            .clone()
+            .try_into()
+            .context("invalid preflight data")?;
+        (input, Some(preflight_data))
+    };
 
     // Verify that the transactions run correctly
     info!("Running from memory ...");
     let output = N::build_from(chain_spec, input.clone()).context("Error while building block")?;
+    let witness = if build_args.flat_witness {
+        let flat_input = FlatBlockBuildInput::try_from(input).context("invalid parent tries")?;
+        BlockWitness::Flat(flat_input)
+    } else {
+        BlockWitness::Trie(input)
+    };
+    ensure_input_size(
+        input_size(&witness)?,
+        build_args.max_input_size,
+        "the block is too large to be built by a single guest",
+    )
+    .context(Failure::Witness)?;
 
     match &output {
         BlockBuildOutput::SUCCESS {
             hash, head, state, ..
         } => {
-            info!("Verifying final state using provider data ...");
-            preflight_data.verify_block(head, state)?;
+            if let Some(preflight_data) = preflight_data {
+                info!("Verifying final state using provider data ...");
+                preflight_data.verify_block(head, state)?;
+            }
 
             info!("Final block hash derived successfully. {}", hash);
         }
@@ -90,39 +132,80 @@ where
     }
 
     let compressed_output = output.with_state_hashed();
-    let result = match cli {
-        Cli::Build(..) => None,
-        Cli::Run(run_args) => {
-            execute(
-                &input,
-                run_args.execution_po2,
-                run_args.profile,
-                guest_elf,
-                &compressed_output,
-                &cli.execution_tag(),
-            );
-            None
-        }
-        Cli::Prove(..) => {
-            maybe_prove(
-                cli,
-                &input,
-                guest_elf,
-                &compressed_output,
-                Default::default(),
-            )
-            .await
-        }
-        Cli::Verify(verify_args) => Some(
-            verify_bonsai_receipt(
-                compute_image_id(guest_elf)?,
-                &compressed_output,
-                verify_args.bonsai_receipt_uuid.clone(),
-                4,
-            )
-            .await?,
-        ),
-    };
+    run_guest::<G>(cli, &witness, &compressed_output, Default::default()).await
+}
+
+/// Build a bundle of consecutive blocks using the specified strategy.
+pub async fn build_bundle<N: BlockBuilderStrategy, G>(
+    cli: &Cli,
+    rpc_url: Option<String>,
+    chain_spec: &ChainSpec,
+) -> anyhow::Result<Option<(String, Receipt)>>
+where
+    G: GuestProgram<Input = BlockBundleInput<N::TxEssence>, Output = BlockBundleOutput>,
+    N::TxEssence: 'static + Send + TryFrom<EthersTransaction> + Serialize + Deserialize<'static>,
+    <N::TxEssence as TryFrom<EthersTransaction>>::Error: Debug,
+{
+    let build_args = cli.build_args().context("missing build arguments")?.clone();
+
+    // Fetch the initial state and execute every block of the bundle on top of it
+    let rpc_cache = build_args.cache.as_ref().map(|dir| {
+        cache_file_path(
+            dir,
+            &build_args.network.to_string(),
+            build_args.block_number,
+            "json.gz",
+        )
+    });
+    let init_spec = chain_spec.clone();
+    let preflight_result = tokio::task::spawn_blocking(move || {
+        preflight_bundle::<N>(
+            &init_spec,
+            rpc_cache,
+            rpc_url,
+            build_args.block_number,
+            build_args.block_count as u64,
+        )
+    })
+    .await?;
+    let preflight_data = preflight_result
+        .context("preflight failed")
+        .context(Failure::Witness)?;
+    let last_header = preflight_data
+        .headers
+        .last()
+        .cloned()
+        .context("empty bundle")?;
+
+    // Create the guest input from the preflight data
+    let input: BlockBundleInput<N::TxEssence> = preflight_data
+        .try_into()
+        .context("invalid preflight data")?;
+
+    ensure_input_size(
+        input_size(&input)?,
+        build_args.max_input_size,
+        "bundle fewer blocks with a smaller --block-count",
+    )
+    .context(Failure::Witness)?;
+
+    // Verify that the transactions of all blocks run correctly
+    info!("Running from memory ...");
+    let output = N::build_bundle(chain_spec, input.clone()).context("invalid block bundle")?;
+    info!("Verifying the last block using provider data ...");
+    ensure!(
+        output.hash == last_header.hash(),
+        "Block {} of the bundle has hash {}, expected {}",
+        last_header.number,
+        output.hash,
+        last_header.hash()
+    );
+    info!(
+        "Bundle of {} blocks derived successfully. {} -> {}",
+        input.state_inputs.len(),
+        output.parent_hash,
+        output.hash
+    );
 
-    Ok(result)
+    run_guest::<G>(cli, &input, &output, Default::default()).await
 }
@@ -0,0 +1,132 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{fmt::Debug, fs, path::Path};
+
+use anyhow::{bail, ensure, Context};
+use ethers_core::types::Transaction as EthersTransaction;
+use log::info;
+use serde::{Deserialize, Serialize};
+use zeth_lib::{
+    builder::{BlockBuilderStrategy, EthereumStrategy, OptimismStrategy},
+    consts::{ChainSpec, ETH_CHAIN_SPEC, OP_CHAIN_SPEC},
+    host::cache_file_path,
+};
+
+use crate::{
+    cli::{CorpusArgs, Network},
+    corpus::{corpus_manifest, manifest_differences, CorpusManifest, MANIFEST_FILE_NAME},
+};
+
+/// Downloads the requested blocks into the corpus and regenerates its manifest. With
+/// `--check`, the manifest is instead compared against the files of the corpus.
+pub async fn update_corpus(args: &CorpusArgs) -> anyhow::Result<()> {
+    let manifest_path = args.cache.join(MANIFEST_FILE_NAME);
+
+    if args.check {
+        ensure!(
+            args.blocks.is_empty(),
+            "Blocks cannot be downloaded when checking the manifest"
+        );
+        let expected: CorpusManifest = {
+            let data = fs::read(&manifest_path).with_context(|| {
+                format!(
+                    "Failed to read manifest '{}'; create it by running without --check",
+                    manifest_path.display()
+                )
+            })?;
+            serde_json::from_slice(&data).context("invalid manifest")?
+        };
+        let corpus = args.cache.clone();
+        let actual = tokio::task::spawn_blocking(move || corpus_manifest(&corpus)).await??;
+
+        let differences = manifest_differences(&expected, &actual);
+        ensure!(
+            differences.is_empty(),
+            "The corpus does not match its manifest: {}",
+            differences.join(", ")
+        );
+        info!("All {} corpus files match the manifest", actual.len());
+        return Ok(());
+    }
+
+    for &block_no in &args.blocks {
+        match args.network {
+            Network::Ethereum => {
+                download::<EthereumStrategy>(
+                    &args.cache,
+                    &args.network,
+                    ETH_CHAIN_SPEC,
+                    args.eth_rpc_url.clone(),
+                    block_no,
+                )
+                .await?
+            }
+            Network::Optimism => {
+                download::<OptimismStrategy>(
+                    &args.cache,
+                    &args.network,
+                    OP_CHAIN_SPEC,
+                    args.op_rpc_url.clone(),
+                    block_no,
+                )
+                .await?
+            }
+            Network::OptimismDerived => bail!(
+                "Derivation cases are added by building them with --cache={}",
+                args.cache.join("derivation").display()
+            ),
+        }
+    }
+
+    let corpus = args.cache.clone();
+    let manifest = tokio::task::spawn_blocking(move || corpus_manifest(&corpus)).await??;
+    fs::write(&manifest_path, serde_json::to_vec_pretty(&manifest)?)
+        .with_context(|| format!("Failed to write '{}'", manifest_path.display()))?;
+    info!(
+        "Wrote manifest of {} corpus files to {}",
+        manifest.len(),
+        manifest_path.display()
+    );
+
+    Ok(())
+}
+
+/// Runs the preflight of the block against the RPC node, storing all fetched data in the
+/// cache file of the block.
+async fn download<N: BlockBuilderStrategy>(
+    corpus: &Path,
+    network: &Network,
+    chain_spec: &ChainSpec,
+    rpc_url: Option<String>,
+    block_no: u64,
+) -> anyhow::Result<()>
+where
+    N::TxEssence: 'static + Send + TryFrom<EthersTransaction> + Serialize + Deserialize<'static>,
+    <N::TxEssence as TryFrom<EthersTransaction>>::Error: Debug,
+{
+    let rpc_url =
+        rpc_url.with_context(|| format!("Downloading {} blocks requires an RPC URL", network))?;
+    let rpc_cache = cache_file_path(corpus, &network.to_string(), block_no, "json.gz");
+
+    info!("Downloading {} block {} ...", network, block_no);
+    let init_spec = chain_spec.clone();
+    tokio::task::spawn_blocking(move || {
+        N::preflight_with_external_data(&init_spec, Some(rpc_cache), Some(rpc_url), block_no)
+    })
+    .await?
+    .with_context(|| format!("preflight of block {} failed", block_no))?;
+
+    Ok(())
+}
@@ -0,0 +1,222 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{collections::BTreeMap, fmt::Debug, fs, path::Path};
+
+use anyhow::{bail, ensure, Context};
+use ethers_core::types::Transaction as EthersTransaction;
+use log::{info, warn};
+use risc0_zkvm::serde::to_vec;
+use serde::{Deserialize, Serialize};
+use zeth_lib::{
+    builder::{BlockBuilderStrategy, EthereumStrategy, OptimismStrategy},
+    consts::{ChainSpec, ETH_CHAIN_SPEC, OP_CHAIN_SPEC},
+    host::{cache_file_path, preflight::Preflight},
+    input::{BlockBuildInput, BlockWitness},
+    output::BlockBuildOutput,
+};
+
+use crate::{
+    cli::{CyclesArgs, Network},
+    operations::{
+        guest::{EthBlock, GuestProgram, OpBlock},
+        run_executor,
+    },
+};
+
+/// Cycle counts of a single guest execution.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CycleCount {
+    /// Cycles spent executing guest instructions.
+    pub user_cycles: u64,
+    /// Cycles of all segments, including paging and padding.
+    pub total_cycles: u64,
+}
+
+/// Cycle counts keyed by `<network>/<block number>`.
+pub type CycleBaseline = BTreeMap<String, CycleCount>;
+
+/// Executes the guests for all cached blocks and compares the cycle counts against the
+/// baseline, failing if any input regressed by more than the threshold.
+pub async fn check_cycles(args: &CyclesArgs) -> anyhow::Result<()> {
+    let mut measured = CycleBaseline::new();
+    measure::<EthereumStrategy, EthBlock>(args, Network::Ethereum, ETH_CHAIN_SPEC, &mut measured)
+        .await?;
+    measure::<OptimismStrategy, OpBlock>(args, Network::Optimism, OP_CHAIN_SPEC, &mut measured)
+        .await?;
+    ensure!(
+        !measured.is_empty(),
+        "No cached blocks found in '{}'",
+        args.cache.display()
+    );
+
+    if args.update {
+        fs::write(&args.baseline, serde_json::to_vec_pretty(&measured)?)
+            .with_context(|| format!("Failed to write '{}'", args.baseline.display()))?;
+        info!(
+            "Wrote cycle baseline of {} inputs to {}",
+            measured.len(),
+            args.baseline.display()
+        );
+        return Ok(());
+    }
+
+    let baseline: CycleBaseline = {
+        let data = fs::read(&args.baseline).with_context(|| {
+            format!(
+                "Failed to read baseline '{}'; create it with --update",
+                args.baseline.display()
+            )
+        })?;
+        serde_json::from_slice(&data).context("invalid baseline")?
+    };
+
+    let mut regressions = Vec::new();
+    for (key, cycles) in &measured {
+        let Some(expected) = baseline.get(key) else {
+            warn!("{}: {} cycles, no baseline", key, cycles.user_cycles);
+            continue;
+        };
+        let change = percent_change(expected.user_cycles, cycles.user_cycles);
+        info!(
+            "{}: {} cycles, baseline {} ({:+.2}%)",
+            key, cycles.user_cycles, expected.user_cycles, change
+        );
+        if change > args.threshold {
+            regressions.push(key.as_str());
+        }
+    }
+    if !regressions.is_empty() {
+        bail!(
+            "Cycle count regressed by more than {}% for: {}",
+            args.threshold,
+            regressions.join(", ")
+        );
+    }
+
+    Ok(())
+}
+
+/// Executes the guest for every block cached for the network.
+async fn measure<N: BlockBuilderStrategy, G>(
+    args: &CyclesArgs,
+    network: Network,
+    chain_spec: &ChainSpec,
+    measured: &mut CycleBaseline,
+) -> anyhow::Result<()>
+where
+    G: GuestProgram<Input = BlockWitness<N::TxEssence>, Output = BlockBuildOutput>,
+    N::TxEssence: 'static + Send + TryFrom<EthersTransaction> + Serialize + Deserialize<'static>,
+    <N::TxEssence as TryFrom<EthersTransaction>>::Error: Debug,
+{
+    for block_number in cached_blocks(&args.cache.join(network.to_string()))? {
+        let (encoded_input, expected_output) =
+            cached_input::<N>(&args.cache, &network, chain_spec, block_number).await?;
+
+        let key = format!("{}/{}", network, block_number);
+        info!("Executing {} ...", key);
+        let (session, _) = run_executor(&encoded_input, args.execution_po2, false, G::ELF, &key)
+            .with_context(|| format!("execution of {} failed", key))?;
+        let cycles = CycleCount {
+            user_cycles: session.user_cycles,
+            total_cycles: session.total_cycles,
+        };
+
+        // only count cycles of executions producing the expected block
+        let output: BlockBuildOutput = session
+            .journal
+            .context("missing journal")?
+            .decode()
+            .context("Could not decode journal")?;
+        ensure!(output == expected_output, "Output mismatch for {}", key);
+
+        measured.insert(key, cycles);
+    }
+
+    Ok(())
+}
+
+/// Returns the encoded guest input of the block cached for the network, together with
+/// the output the guest is expected to commit.
+pub(crate) async fn cached_input<N: BlockBuilderStrategy>(
+    cache: &Path,
+    network: &Network,
+    chain_spec: &ChainSpec,
+    block_number: u64,
+) -> anyhow::Result<(Vec<u32>, BlockBuildOutput)>
+where
+    N::TxEssence: 'static + Send + TryFrom<EthersTransaction> + Serialize + Deserialize<'static>,
+    <N::TxEssence as TryFrom<EthersTransaction>>::Error: Debug,
+{
+    let rpc_cache = cache_file_path(cache, &network.to_string(), block_number, "json.gz");
+    let init_spec = chain_spec.clone();
+    let preflight_data = tokio::task::spawn_blocking(move || {
+        N::preflight_with_external_data(&init_spec, Some(rpc_cache), None, block_number)
+    })
+    .await?
+    .with_context(|| format!("preflight of block {} failed", block_number))?;
+    let input: BlockBuildInput<N::TxEssence> = preflight_data
+        .try_into()
+        .context("invalid preflight data")?;
+    let expected_output = N::build_from(chain_spec, input.clone())
+        .with_context(|| format!("Error while building block {}", block_number))?
+        .with_state_hashed();
+    let encoded_input = to_vec(&BlockWitness::Trie(input)).context("Failed to serialize input")?;
+
+    Ok((encoded_input, expected_output))
+}
+
+/// Returns the numbers of all blocks cached in the directory, in ascending order.
+pub(crate) fn cached_blocks(dir: &Path) -> anyhow::Result<Vec<u64>> {
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut block_numbers = Vec::new();
+    for entry in fs::read_dir(dir).with_context(|| format!("Failed to read '{}'", dir.display()))? {
+        let file_name = entry?.file_name();
+        let Some(block_number) = file_name
+            .to_str()
+            .and_then(|name| name.strip_suffix(".json.gz"))
+            .and_then(|prefix| prefix.parse().ok())
+        else {
+            continue;
+        };
+        block_numbers.push(block_number);
+    }
+    block_numbers.sort_unstable();
+
+    Ok(block_numbers)
+}
+
+/// Returns the relative change from `base` to `value` in percent.
+fn percent_change(base: u64, value: u64) -> f64 {
+    if base == 0 {
+        return if value == 0 { 0.0 } else { f64::INFINITY };
+    }
+    (value as f64 - base as f64) * 100.0 / base as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percent_change_of_cycles() {
+        assert_eq!(percent_change(1000, 1000), 0.0);
+        assert_eq!(percent_change(1000, 1050), 5.0);
+        assert_eq!(percent_change(1000, 900), -10.0);
+        assert_eq!(percent_change(0, 0), 0.0);
+        assert!(percent_change(0, 1).is_infinite());
+    }
+}
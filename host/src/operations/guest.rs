@@ -0,0 +1,224 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt::Debug;
+
+use anyhow::Context;
+use log::debug;
+use risc0_zkvm::{serde::from_slice, Assumption, Receipt};
+use serde::{de::DeserializeOwned, Serialize};
+use zeth_guests::*;
+use zeth_lib::{
+    input::{BlockBundleInput, BlockWitness},
+    journal::Journal,
+    optimism::{
+        composition::{ComposeInput, ComposeOutput, ImageId},
+        framed_db::FramedDeriveInput,
+        unsafe_payload::{UnsafeInput, UnsafeOutput},
+        DeriveOutput, DerivePrivateOutput,
+    },
+    output::{BlockBuildOutput, BlockBundleOutput},
+};
+use zeth_primitives::transactions::{ethereum::EthereumTxEssence, optimism::OptimismTxEssence};
+
+use crate::{
+    cli::Cli,
+    operations::{execute, maybe_prove, verify_bonsai_receipt},
+};
+
+/// A guest program embedded in the host, together with the types it reads and commits.
+pub trait GuestProgram {
+    /// The name of the guest package.
+    const NAME: &'static str;
+    /// The ELF binary of the guest.
+    const ELF: &'static [u8];
+    /// The image ID of the guest.
+    const ID: ImageId;
+
+    /// The input read by the guest.
+    type Input: Serialize;
+    /// The output committed to the journal of the guest.
+    type Output: Eq + Debug + Journal;
+    /// The output only written to the host, which is not part of the journal.
+    type PrivateOutput: DeserializeOwned + Debug;
+}
+
+/// The guest that builds a single Ethereum block.
+pub struct EthBlock;
+
+impl GuestProgram for EthBlock {
+    const NAME: &'static str = "eth-block";
+    const ELF: &'static [u8] = ETH_BLOCK_ELF;
+    const ID: ImageId = ETH_BLOCK_ID;
+
+    type Input = BlockWitness<EthereumTxEssence>;
+    type Output = BlockBuildOutput;
+    type PrivateOutput = ();
+}
+
+/// The guest that builds a bundle of consecutive Ethereum blocks.
+pub struct EthBundle;
+
+impl GuestProgram for EthBundle {
+    const NAME: &'static str = "eth-bundle";
+    const ELF: &'static [u8] = ETH_BUNDLE_ELF;
+    const ID: ImageId = ETH_BUNDLE_ID;
+
+    type Input = BlockBundleInput<EthereumTxEssence>;
+    type Output = BlockBundleOutput;
+    type PrivateOutput = ();
+}
+
+/// The guest that builds a single Optimism block.
+pub struct OpBlock;
+
+impl GuestProgram for OpBlock {
+    const NAME: &'static str = "op-block";
+    const ELF: &'static [u8] = OP_BLOCK_ELF;
+    const ID: ImageId = OP_BLOCK_ID;
+
+    type Input = BlockWitness<OptimismTxEssence>;
+    type Output = BlockBuildOutput;
+    type PrivateOutput = ();
+}
+
+/// The guest that derives Optimism blocks from the data posted to Ethereum.
+pub struct OpDerive;
+
+impl GuestProgram for OpDerive {
+    const NAME: &'static str = "op-derive";
+    const ELF: &'static [u8] = OP_DERIVE_ELF;
+    const ID: ImageId = OP_DERIVE_ID;
+
+    type Input = FramedDeriveInput;
+    type Output = DeriveOutput;
+    type PrivateOutput = DerivePrivateOutput;
+}
+
+/// The guest that composes derivation proofs into a proof of a larger segment.
+pub struct OpCompose;
+
+impl GuestProgram for OpCompose {
+    const NAME: &'static str = "op-compose";
+    const ELF: &'static [u8] = OP_COMPOSE_ELF;
+    const ID: ImageId = OP_COMPOSE_ID;
+
+    type Input = ComposeInput;
+    type Output = ComposeOutput;
+    type PrivateOutput = ();
+}
+
+/// The guest that attests Optimism payloads signed by the sequencer.
+pub struct OpUnsafe;
+
+impl GuestProgram for OpUnsafe {
+    const NAME: &'static str = "op-unsafe";
+    const ELF: &'static [u8] = OP_UNSAFE_ELF;
+    const ID: ImageId = OP_UNSAFE_ID;
+
+    type Input = UnsafeInput;
+    type Output = UnsafeOutput;
+    type PrivateOutput = ();
+}
+
+/// The names and image IDs of all embedded guests.
+pub const GUEST_IMAGE_IDS: [(&str, ImageId); 6] = [
+    (EthBlock::NAME, EthBlock::ID),
+    (EthBundle::NAME, EthBundle::ID),
+    (OpBlock::NAME, OpBlock::ID),
+    (OpDerive::NAME, OpDerive::ID),
+    (OpCompose::NAME, OpCompose::ID),
+    (OpUnsafe::NAME, OpUnsafe::ID),
+];
+
+/// The names and ELF binaries of all embedded guests.
+pub const GUEST_ELFS: [(&str, &[u8]); 6] = [
+    (EthBlock::NAME, EthBlock::ELF),
+    (EthBundle::NAME, EthBundle::ELF),
+    (OpBlock::NAME, OpBlock::ELF),
+    (OpDerive::NAME, OpDerive::ELF),
+    (OpCompose::NAME, OpCompose::ELF),
+    (OpUnsafe::NAME, OpUnsafe::ELF),
+];
+
+/// Returns the ELF of the embedded guest of an earlier release reading the given
+/// version of its input format.
+pub fn previous_guest_elf(name: &str, input_version: u32) -> anyhow::Result<&'static [u8]> {
+    PREVIOUS_GUEST_ELFS
+        .iter()
+        .find(|(guest_name, version, _)| *guest_name == name && *version == input_version)
+        .map(|(_, _, elf)| *elf)
+        .with_context(|| {
+            format!(
+                "No {} guest for input version {} is embedded; rebuild with \
+                 ZETH_PREVIOUS_GUESTS set",
+                name, input_version
+            )
+        })
+}
+
+/// Proves the guest with the given input, if requested by the CLI.
+pub async fn prove_guest<G: GuestProgram>(
+    cli: &Cli,
+    input: &G::Input,
+    expected_output: &G::Output,
+    assumptions: (Vec<Assumption>, Vec<String>),
+) -> anyhow::Result<Option<(String, Receipt)>> {
+    maybe_prove(cli, input, G::ELF, expected_output, assumptions).await
+}
+
+/// Runs the guest as requested by the CLI: executes it, proves it or verifies its Bonsai
+/// receipt against the expected output.
+pub async fn run_guest<G: GuestProgram>(
+    cli: &Cli,
+    input: &G::Input,
+    expected_output: &G::Output,
+    assumptions: (Vec<Assumption>, Vec<String>),
+) -> anyhow::Result<Option<(String, Receipt)>> {
+    let result = match cli {
+        Cli::Run(run_args) => {
+            let private_output = execute(
+                input,
+                run_args.execution_po2,
+                run_args.profile,
+                G::ELF,
+                expected_output,
+                &cli.execution_tag().context("missing execution tag")?,
+            )?;
+            if !private_output.is_empty() {
+                let private_output: G::PrivateOutput =
+                    from_slice(&private_output).context("invalid private output")?;
+                debug!(
+                    "Private output of the {} guest: {:?}",
+                    G::NAME,
+                    private_output
+                );
+            }
+            None
+        }
+        Cli::Prove(..) => prove_guest::<G>(cli, input, expected_output, assumptions).await?,
+        Cli::Verify(verify_args) => Some(
+            verify_bonsai_receipt(
+                G::ID.into(),
+                expected_output,
+                verify_args.bonsai_receipt_uuid.clone(),
+                4,
+            )
+            .await?,
+        ),
+        _ => None,
+    };
+
+    Ok(result)
+}
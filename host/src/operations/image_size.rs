@@ -0,0 +1,52 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::{ensure, Context};
+
+use crate::{cli::ImageSizeArgs, image_size::image_report, operations::guest::GUEST_ELFS};
+
+/// Prints the sizes of the loaded sections and the largest functions of the guest images.
+pub fn report_image_sizes(args: &ImageSizeArgs) -> anyhow::Result<()> {
+    let guests: Vec<_> = GUEST_ELFS
+        .iter()
+        .filter(|(name, _)| match &args.guest {
+            Some(guest) => guest == name,
+            None => true,
+        })
+        .collect();
+    ensure!(
+        !guests.is_empty(),
+        "Unknown guest '{}'",
+        args.guest.as_deref().unwrap_or_default()
+    );
+
+    for (name, elf) in guests {
+        let report = image_report(elf).with_context(|| format!("Invalid image of {}", name))?;
+        println!(
+            "{}: {} bytes loaded, {} bytes ELF",
+            name,
+            report.loaded_size(),
+            elf.len()
+        );
+        for section in &report.sections {
+            println!("  {:>10}  {}", section.size, section.name);
+        }
+        println!("  largest functions:");
+        for function in report.functions.iter().take(args.top) {
+            println!("  {:>10}  {}", function.size, function.name);
+        }
+    }
+
+    Ok(())
+}
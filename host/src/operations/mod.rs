@@ -12,24 +12,47 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+pub mod backend;
+pub mod backfill;
+pub mod bench;
 pub mod build;
+pub mod corpus;
+pub mod cycles;
+pub mod guest;
+pub mod image_size;
+pub mod monitor;
+pub mod progress;
 pub mod rollups;
 pub mod snarks;
+pub mod unsafe_payloads;
+pub mod verify_all;
+pub mod verify_cache;
+pub mod witness;
+pub mod witness_diff;
 
 use std::fmt::Debug;
 
+use anyhow::{bail, ensure, Context};
 use bonsai_sdk::alpha::responses::SnarkReceipt;
 use log::{debug, error, info, warn};
 use risc0_zkvm::{
-    compute_image_id,
+    compute_image_id, get_prover_server,
     serde::to_vec,
     sha::{Digest, Digestible},
-    Assumption, ExecutorEnv, ExecutorImpl, Receipt, Segment, SegmentRef,
+    Assumption, ExecutorEnv, ExecutorImpl, ExitCode, ProverOpts, Receipt, Segment, SegmentRef,
+    Session, VerifierContext,
 };
-use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde::{Deserialize, Serialize};
+use zeth_lib::{abort::AbortCode, journal::Journal};
 use zeth_primitives::keccak::keccak;
 
-use crate::{cli::Cli, load_receipt, save_receipt};
+use crate::{
+    cli::{Cli, ProveArgs, ReceiptKind},
+    failure::Failure,
+    load_receipt,
+    operations::progress::ProveProgress,
+    save_receipt,
+};
 
 pub async fn stark2snark(
     image_id: Digest,
@@ -99,7 +122,7 @@ pub async fn stark2snark(
     Ok(snark_data)
 }
 
-pub async fn verify_bonsai_receipt<O: Eq + Debug + DeserializeOwned>(
+pub async fn verify_bonsai_receipt<O: Eq + Debug + Journal>(
     image_id: Digest,
     expected_output: &O,
     uuid: String,
@@ -145,15 +168,16 @@ pub async fn verify_bonsai_receipt<O: Eq + Debug + DeserializeOwned>(
             // Download the receipt, containing the output
             let receipt_url = res
                 .receipt_url
-                .expect("API error, missing receipt on completed session");
+                .context("API error, missing receipt on completed session")
+                .context(Failure::Proving)?;
             let client = bonsai_sdk::alpha_async::get_client_from_env(risc0_zkvm::VERSION).await?;
             let receipt_buf = client.download(&receipt_url)?;
             let receipt: Receipt = bincode::deserialize(&receipt_buf)?;
-            receipt
-                .verify(image_id)
-                .expect("Receipt verification failed");
+            receipt.verify(image_id).context(Failure::Verification)?;
             // verify output
-            let receipt_output: O = receipt.journal.decode().unwrap();
+            let receipt_output = O::from_journal(&receipt.journal.bytes)
+                .context("Could not decode journal")
+                .context(Failure::Verification)?;
             if expected_output == &receipt_output {
                 info!("Receipt validated!");
             } else {
@@ -173,15 +197,15 @@ pub async fn verify_bonsai_receipt<O: Eq + Debug + DeserializeOwned>(
     }
 }
 
-pub async fn maybe_prove<I: Serialize, O: Eq + Debug + Serialize + DeserializeOwned>(
+pub async fn maybe_prove<I: Serialize, O: Eq + Debug + Journal>(
     cli: &Cli,
     input: &I,
     elf: &[u8],
     expected_output: &O,
     assumptions: (Vec<Assumption>, Vec<String>),
-) -> Option<(String, Receipt)> {
+) -> anyhow::Result<Option<(String, Receipt)>> {
     let Cli::Prove(prove_args) = cli else {
-        return None;
+        return Ok(None);
     };
 
     let (assumption_instances, assumption_uuids) = assumptions;
@@ -191,56 +215,74 @@ pub async fn maybe_prove<I: Serialize, O: Eq + Debug + Serialize + DeserializeOw
         to_vec(expected_output).expect("Could not serialize expected proving output!");
     let computed_image_id = compute_image_id(elf).expect("Failed to compute elf image id!");
 
-    let receipt_label = format!(
-        "{}-{}",
-        hex::encode(computed_image_id),
-        hex::encode(keccak(bytemuck::cast_slice(&encoded_output)))
-    );
+    let settings = ProverSettings::from(prove_args);
+    if prove_args.submit_to_bonsai && settings.prover_opts.hashfn != ProverOpts::default().hashfn {
+        warn!("The hash function is ignored when proving on Bonsai");
+    }
 
-    // get receipt
-    let (mut receipt_uuid, receipt, cached) =
-        if let Ok(Some(cached_data)) = load_receipt(&receipt_label) {
-            info!("Loaded locally cached receipt");
-            (cached_data.0, cached_data.1, true)
-        } else if prove_args.submit_to_bonsai {
-            // query bonsai service until it works
-            loop {
-                if let Ok(remote_proof) = prove_bonsai(
-                    encoded_input.clone(),
-                    elf,
-                    expected_output,
-                    assumption_uuids.clone(),
-                )
-                .await
-                {
-                    break (remote_proof.0, remote_proof.1, false);
+    // receipts are stored by image ID and input digest, so that the same guest run is
+    // never proven twice; older receipts were stored by output digest instead
+    let label_suffix = settings.label_suffix();
+    let receipt_label = receipt_label_for(computed_image_id, &encoded_input) + &label_suffix;
+    let legacy_receipt_label =
+        receipt_label_for(computed_image_id, &encoded_output) + &label_suffix;
+
+    let cached_receipt = load_receipt(&receipt_label)
+        .ok()
+        .flatten()
+        .or_else(|| load_receipt(&legacy_receipt_label).ok().flatten())
+        .filter(|(_, receipt): &(String, Receipt)| {
+            match verify_receipt(receipt, computed_image_id, expected_output) {
+                Ok(()) => true,
+                Err(err) => {
+                    warn!("Ignoring invalid cached receipt: {:#}", err);
+                    false
                 }
             }
-        } else {
-            // run prover
-            (
-                Default::default(),
-                prove_locally(
-                    prove_args.run_args.execution_po2,
-                    encoded_input,
-                    elf,
-                    assumption_instances,
-                    prove_args.run_args.profile,
-                    &cli.execution_tag(),
-                ),
-                false,
+        });
+
+    // get receipt
+    let (mut receipt_uuid, receipt, cached) = if let Some(cached_data) = cached_receipt {
+        info!("Loaded locally cached receipt");
+        (cached_data.0, cached_data.1, true)
+    } else if prove_args.submit_to_bonsai {
+        // query bonsai service until it works
+        loop {
+            if let Ok(remote_proof) = prove_bonsai(
+                encoded_input.clone(),
+                elf,
+                expected_output,
+                assumption_uuids.clone(),
             )
-        };
+            .await
+            {
+                break (remote_proof.0, remote_proof.1, false);
+            }
+        }
+    } else {
+        // run prover
+        (
+            Default::default(),
+            prove_locally(
+                &settings,
+                encoded_input,
+                elf,
+                assumption_instances,
+                prove_args.run_args.profile,
+                &cli.execution_tag().context("missing execution tag")?,
+                prove_args.json_progress,
+            )
+            .context(Failure::Proving)?,
+            false,
+        )
+    };
 
-    // verify output
-    let output_guest: O = receipt.journal.decode().unwrap();
-    if expected_output == &output_guest {
+    // verify the receipt, so that invalid receipts are never uploaded or cached
+    if !cached {
+        verify_receipt(&receipt, computed_image_id, expected_output)
+            .context("Prover returned an invalid receipt")
+            .context(Failure::Verification)?;
         info!("Prover succeeded");
-    } else {
-        error!(
-            "Output mismatch! Prover: {:?}, expected: {:?}",
-            output_guest, expected_output,
-        );
     }
 
     // upload receipt to bonsai
@@ -248,7 +290,7 @@ pub async fn maybe_prove<I: Serialize, O: Eq + Debug + Serialize + DeserializeOw
         info!("Uploading cached receipt without UUID to Bonsai.");
         receipt_uuid = upload_receipt(&receipt)
             .await
-            .expect("Failed to upload cached receipt to Bonsai");
+            .context("Failed to upload cached receipt to Bonsai")?;
     }
 
     let result = (receipt_uuid, receipt);
@@ -259,7 +301,37 @@ pub async fn maybe_prove<I: Serialize, O: Eq + Debug + Serialize + DeserializeOw
     }
 
     // return result
-    Some(result)
+    Ok(Some(result))
+}
+
+/// Verifies the receipt against the image ID and checks that its journal decodes to the
+/// expected output.
+fn verify_receipt<O: Eq + Debug + Journal>(
+    receipt: &Receipt,
+    image_id: Digest,
+    expected_output: &O,
+) -> anyhow::Result<()> {
+    receipt
+        .verify(image_id)
+        .context("receipt verification failed")?;
+    let output = O::from_journal(&receipt.journal.bytes).context("invalid journal")?;
+    ensure!(
+        expected_output == &output,
+        "Output mismatch! Receipt: {:?}, expected: {:?}",
+        output,
+        expected_output
+    );
+    Ok(())
+}
+
+/// Returns the label of the receipt of the given image ID, combined with the digest of
+/// the encoded data.
+fn receipt_label_for(image_id: Digest, encoded_data: &[u32]) -> String {
+    format!(
+        "{}-{}",
+        hex::encode(image_id),
+        hex::encode(keccak(bytemuck::cast_slice(encoded_data)))
+    )
 }
 
 pub async fn upload_receipt(receipt: &Receipt) -> anyhow::Result<String> {
@@ -267,7 +339,7 @@ pub async fn upload_receipt(receipt: &Receipt) -> anyhow::Result<String> {
     Ok(client.upload_receipt(bincode::serialize(receipt)?)?)
 }
 
-pub async fn prove_bonsai<O: Eq + Debug + DeserializeOwned>(
+pub async fn prove_bonsai<O: Eq + Debug + Journal>(
     encoded_input: Vec<u32>,
     elf: &[u8],
     expected_output: &O,
@@ -294,17 +366,63 @@ pub async fn prove_bonsai<O: Eq + Debug + DeserializeOwned>(
     verify_bonsai_receipt(image_id, expected_output, session.uuid.clone(), 8).await
 }
 
+/// The settings of the local prover.
+#[derive(Debug, Clone)]
+pub struct ProverSettings {
+    /// The maximum cycle count of a segment as a power of 2.
+    pub segment_limit_po2: u32,
+    /// The kind of the receipt to create.
+    pub receipt_kind: ReceiptKind,
+    /// The options passed to the prover, such as the hash function.
+    pub prover_opts: ProverOpts,
+}
+
+impl From<&ProveArgs> for ProverSettings {
+    fn from(prove_args: &ProveArgs) -> Self {
+        ProverSettings {
+            segment_limit_po2: prove_args.run_args.execution_po2,
+            receipt_kind: prove_args.receipt_kind,
+            prover_opts: ProverOpts {
+                hashfn: prove_args.hashfn.clone(),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+impl ProverSettings {
+    /// Returns the suffix distinguishing the cached receipts of these settings from the
+    /// receipts created with the default settings.
+    fn label_suffix(&self) -> String {
+        let mut suffix = String::new();
+        if self.receipt_kind == ReceiptKind::Succinct {
+            suffix.push_str("-succinct");
+        }
+        if self.prover_opts.hashfn != ProverOpts::default().hashfn {
+            suffix.push('-');
+            suffix.push_str(&self.prover_opts.hashfn);
+        }
+        suffix
+    }
+}
+
 /// Prove the given ELF locally with the given input and assumptions. The segments are
 /// stored in a temporary directory, to allow for proofs larger than the available memory.
+/// The progress is reported after every proven segment.
 pub fn prove_locally(
-    segment_limit_po2: u32,
+    settings: &ProverSettings,
     encoded_input: Vec<u32>,
     elf: &[u8],
     assumptions: Vec<Assumption>,
     profile: bool,
     profile_reference: &String,
-) -> Receipt {
-    debug!("Proving with segment_limit_po2 = {:?}", segment_limit_po2);
+    json_progress: bool,
+) -> anyhow::Result<Receipt> {
+    let segment_limit_po2 = settings.segment_limit_po2;
+    debug!(
+        "Proving with segment_limit_po2 = {:?}, hashfn = {}",
+        segment_limit_po2, settings.prover_opts.hashfn
+    );
     debug!(
         "Input size: {} words ( {} MB )",
         encoded_input.len(),
@@ -312,12 +430,14 @@ pub fn prove_locally(
     );
 
     info!("Running the prover...");
-    let session = {
+    let mut session = {
         let mut env_builder = ExecutorEnv::builder();
+        // the private output of the guest is not needed for the proof
         env_builder
             .session_limit(None)
             .segment_limit_po2(segment_limit_po2)
-            .write_slice(&encoded_input);
+            .write_slice(&encoded_input)
+            .stdout(std::io::sink());
 
         if profile {
             info!("Profiling enabled.");
@@ -328,11 +448,23 @@ pub fn prove_locally(
             env_builder.add_assumption(assumption);
         }
 
-        let env = env_builder.build().unwrap();
-        let mut exec = ExecutorImpl::from_elf(env, elf).unwrap();
-        exec.run().unwrap()
+        let env = env_builder.build()?;
+        let mut exec = ExecutorImpl::from_elf(env, elf)?;
+        exec.run().context("guest execution failed")?
     };
-    session.prove().unwrap()
+    ensure_success(&session)?;
+    session.add_hook(ProveProgress::new(session.segments.len(), json_progress));
+
+    let prover = get_prover_server(&settings.prover_opts)?;
+    let receipt = prover.prove_session(&VerifierContext::default(), &session)?;
+    match settings.receipt_kind {
+        ReceiptKind::Succinct => {
+            info!("Compressing the receipt...");
+            prover.compress(&receipt)
+        }
+        // SNARKs are converted from the STARK receipt by Bonsai
+        ReceiptKind::Composite | ReceiptKind::Groth16 => Ok(receipt),
+    }
 }
 
 const NULL_SEGMENT_REF: NullSegmentRef = NullSegmentRef {};
@@ -345,15 +477,64 @@ impl SegmentRef for NullSegmentRef {
     }
 }
 
-/// Execute the guest code with the given input and verify the output.
-pub fn execute<T: Serialize, O: Eq + Debug + DeserializeOwned>(
+/// Returns an error if the guest did not halt successfully. Guests aborting with an
+/// [AbortCode] are reported with its category.
+fn ensure_success(session: &Session) -> anyhow::Result<()> {
+    match session.exit_code {
+        ExitCode::Halted(0) => Ok(()),
+        ExitCode::Halted(exit_code) => match AbortCode::from_exit_code(exit_code) {
+            Some(abort_code) => Err(abort_code).context(Failure::GuestAbort),
+            None => bail!("guest halted with exit code {}", exit_code),
+        },
+        exit_code => bail!("guest did not halt: {:?}", exit_code),
+    }
+}
+
+/// Run the guest code in the executor without storing any segments. Returns the session
+/// together with the private output the guest wrote to the host, or an error if the
+/// guest aborted.
+fn run_executor(
+    encoded_input: &[u32],
+    segment_limit_po2: u32,
+    profile: bool,
+    elf: &[u8],
+    profile_reference: &String,
+) -> anyhow::Result<(Session, Vec<u8>)> {
+    let mut private_output = Vec::new();
+    let session = {
+        let mut env_builder = ExecutorEnv::builder();
+        env_builder
+            .session_limit(None)
+            .segment_limit_po2(segment_limit_po2)
+            .write_slice(encoded_input)
+            .stdout(&mut private_output);
+
+        if profile {
+            info!("Profiling enabled.");
+            env_builder.enable_profiler(format!("profile_{}.pb", profile_reference));
+        }
+
+        let env = env_builder.build()?;
+        let mut exec = ExecutorImpl::from_elf(env, elf)?;
+
+        exec.run_with_callback(|_| Ok(Box::new(NULL_SEGMENT_REF)))
+            .context("guest execution failed")?
+    };
+    ensure_success(&session)?;
+
+    Ok((session, private_output))
+}
+
+/// Execute the guest code with the given input and verify the output. Returns the
+/// private output the guest wrote to the host, which is not part of the journal.
+pub fn execute<T: Serialize, O: Eq + Debug + Journal>(
     input: &T,
     segment_limit_po2: u32,
     profile: bool,
     elf: &[u8],
     expected_output: &O,
     profile_reference: &String,
-) {
+) -> anyhow::Result<Vec<u8>> {
     debug!(
         "Running in executor with segment_limit_po2 = {:?}",
         segment_limit_po2
@@ -367,31 +548,20 @@ pub fn execute<T: Serialize, O: Eq + Debug + DeserializeOwned>(
     );
 
     info!("Running the executor...");
-    let session = {
-        let mut env_builder = ExecutorEnv::builder();
-        env_builder
-            .session_limit(None)
-            .segment_limit_po2(segment_limit_po2)
-            .write_slice(&input);
-
-        if profile {
-            info!("Profiling enabled.");
-            env_builder.enable_profiler(format!("profile_{}.pb", profile_reference));
-        }
-
-        let env = env_builder.build().unwrap();
-        let mut exec = ExecutorImpl::from_elf(env, elf).unwrap();
-
-        exec.run_with_callback(|_| Ok(Box::new(NULL_SEGMENT_REF)))
-            .unwrap()
-    };
+    let (session, private_output) =
+        run_executor(&input, segment_limit_po2, profile, elf, profile_reference)?;
     println!(
         "Executor ran in (roughly) {} cycles",
         session.segments.len() * (1 << segment_limit_po2)
     );
     // verify output
-    let journal = session.journal.unwrap();
-    let output_guest: O = journal.decode().expect("Could not decode journal");
+    let journal = session
+        .journal
+        .context("Executor returned no journal")
+        .context(Failure::Proving)?;
+    let output_guest = O::from_journal(&journal.bytes)
+        .context("Could not decode journal")
+        .context(Failure::Verification)?;
     if expected_output == &output_guest {
         info!("Executor succeeded");
     } else {
@@ -400,4 +570,6 @@ pub fn execute<T: Serialize, O: Eq + Debug + DeserializeOwned>(
             output_guest, expected_output,
         );
     }
+
+    Ok(private_output)
 }
@@ -0,0 +1,334 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::time::Duration;
+
+use anyhow::{ensure, Context};
+use log::{error, info, warn};
+use serde::Serialize;
+use zeth_guests::OP_BLOCK_ID;
+use zeth_lib::{
+    host::{
+        channel_cache::ChannelCache,
+        provider::{self, new_provider, BlockQuery},
+        rpc_db::RpcDb,
+        ProviderFactory,
+    },
+    optimism::{
+        batcher::BlockId, batcher_db::BatcherDb, config::ChainConfig, DeriveError, DeriveInput,
+        DeriveMachine, DeriveOutput,
+    },
+};
+use zeth_primitives::{block::Header, B256};
+
+use crate::{
+    cli::{BuildArgs, Cli, Network},
+    operations::rollups::load_prior_output,
+    store::l1_attributes,
+};
+
+/// Alert emitted when a derived block does not match the block of the sequencer.
+#[derive(Debug, Clone, Serialize)]
+pub struct DivergenceAlert {
+    /// Number of the diverging Optimism block.
+    pub op_block_number: u64,
+    /// Hash of the block derived from the Ethereum chain.
+    pub derived_hash: B256,
+    /// Hash of the block reported by the sequencer.
+    pub sequencer_hash: B256,
+    /// Number of the last Ethereum block processed during derivation.
+    pub eth_tail_number: u64,
+}
+
+/// Warning emitted when the sequencing window of the safe head is about to expire, after
+/// which deposit-only blocks are derived.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct WindowWarning {
+    /// Number of the last derived Optimism block.
+    pub op_head_block_number: u64,
+    /// Number of the L1 origin of the last derived block.
+    pub epoch_number: u64,
+    /// Number of the latest Ethereum block.
+    pub eth_head_number: u64,
+    /// First Ethereum block at which deposit-only blocks are derived, if no batches are
+    /// posted until then.
+    pub force_inclusion_block: u64,
+    /// Number of Ethereum blocks left before the force-inclusion block.
+    pub remaining_blocks: u64,
+}
+
+impl WindowWarning {
+    /// Returns a warning if at least `percent` percent of the sequencing window of the
+    /// epoch have elapsed at the given Ethereum head.
+    pub fn check(
+        op_head_block_number: u64,
+        epoch_number: u64,
+        eth_head_number: u64,
+        seq_window_size: u64,
+        percent: u64,
+    ) -> Option<Self> {
+        let elapsed = eth_head_number.saturating_sub(epoch_number);
+        if elapsed * 100 < seq_window_size * percent {
+            return None;
+        }
+        // default batches are derived once the last L1 block of the window is processed
+        let force_inclusion_block = epoch_number + seq_window_size;
+        Some(WindowWarning {
+            op_head_block_number,
+            epoch_number,
+            eth_head_number,
+            force_inclusion_block,
+            remaining_blocks: force_inclusion_block.saturating_sub(eth_head_number),
+        })
+    }
+}
+
+/// Continuously derives the Optimism chain and compares every derived block against the
+/// unsafe chain of the sequencer, emitting an alert on divergence.
+pub async fn monitor_derived_chain(cli: &Cli) -> anyhow::Result<()> {
+    let Cli::Monitor(monitor_args) = cli else {
+        unreachable!()
+    };
+    let build_args = &monitor_args.build_args;
+    ensure!(
+        matches!(build_args.network, Network::OptimismDerived),
+        "Monitoring is only supported for the optimism-derived network"
+    );
+    ensure!(
+        build_args.op_rpc_url.is_some(),
+        "Monitoring requires the URL of the sequencer RPC node"
+    );
+    ensure!(
+        build_args.checkpoint.is_none() && build_args.resume_checkpoint.is_none(),
+        "Monitoring does not support checkpoints"
+    );
+
+    let mut op_head_block_no = build_args.block_number;
+    // every derivation continues the output of the previous one
+    let mut prior_output = build_args
+        .prior_receipt
+        .as_deref()
+        .map(load_prior_output)
+        .transpose()?;
+    loop {
+        let args = build_args.clone();
+        let prior_clone = prior_output.clone();
+        let result = tokio::task::spawn_blocking(move || {
+            derive_and_compare(&args, op_head_block_no, prior_clone)
+        })
+        .await?;
+
+        let progressed = match result {
+            Ok((derive_output, alerts)) => {
+                for alert in alerts {
+                    emit_alert(monitor_args.webhook_url.as_deref(), &alert).await;
+                }
+                let last_block_no = derive_output.op_tail().number;
+                let progressed = last_block_no != op_head_block_no;
+                op_head_block_no = last_block_no;
+                prior_output = Some(derive_output);
+                progressed
+            }
+            Err(err) => {
+                warn!(
+                    "Could not derive blocks after {}, retrying: {:#}",
+                    op_head_block_no, err
+                );
+                // after an L1 reorg, the prior output can never be continued
+                if is_prior_output_mismatch(&err) {
+                    warn!("Discarding the prior output, restarting the chain of outputs");
+                    prior_output = None;
+                }
+                false
+            }
+        };
+
+        // without new batches, the safe head falls behind the sequencing window
+        if !progressed {
+            let args = build_args.clone();
+            let percent = monitor_args.window_warning;
+            let result = tokio::task::spawn_blocking(move || {
+                check_sequencing_window(&args, op_head_block_no, percent)
+            })
+            .await?;
+            match result {
+                Ok(Some(warning)) => {
+                    emit_window_warning(monitor_args.webhook_url.as_deref(), &warning).await
+                }
+                Ok(None) => {}
+                Err(err) => warn!("Could not check the sequencing window: {:#}", err),
+            }
+        }
+
+        tokio::time::sleep(Duration::from_secs(monitor_args.poll_interval)).await;
+    }
+}
+
+/// Derives the blocks following the given Optimism head, continuing the prior output if
+/// given, and compares them to the blocks of the sequencer. Returns the derivation output
+/// and all divergences.
+fn derive_and_compare(
+    build_args: &BuildArgs,
+    op_head_block_no: u64,
+    prior_output: Option<DeriveOutput>,
+) -> anyhow::Result<(DeriveOutput, Vec<DivergenceAlert>)> {
+    let config = build_args.chain_config();
+    let derive_input = DeriveInput {
+        db: RpcDb::new(
+            &config,
+            build_args.eth_rpc_url.clone(),
+            build_args.op_rpc_url.clone(),
+            None,
+        )
+        .with_beacon_rpc_url(build_args.beacon_rpc_url.clone())
+        .with_validation_policy(build_args.validation_policy()),
+        op_head_block_no,
+        op_derive_block_count: build_args.block_count,
+        op_derive_max_timestamp: None,
+        op_block_outputs: vec![],
+        block_image_id: OP_BLOCK_ID,
+        eth_accumulator: false,
+        op_anchor_block_no: None,
+        batch_provenance: false,
+        rollup_config: build_args.custom_rollup_config(),
+        prior_output,
+        checkpoint: false,
+        resume_checkpoint: None,
+    };
+    let provider_factory = ProviderFactory::new(
+        None,
+        Network::Optimism.to_string(),
+        build_args.op_rpc_url.clone(),
+    );
+    let mut derive_machine = DeriveMachine::new(config, derive_input, Some(provider_factory))
+        .context("Could not create derive machine")?;
+    // unlike the RPC data near the chain tip, cached channels can never become stale
+    derive_machine
+        .op_batcher
+        .set_channel_cache(build_args.cache.as_deref().map(ChannelCache::new));
+    let derive_output = derive_machine.derive(None).context("could not derive")?;
+
+    let mut alerts = vec![];
+    for BlockId { number, hash } in &derive_output.derived_op_blocks {
+        let sequencer_hash = sequencer_block_hash(build_args, *number)?;
+        if sequencer_hash == *hash {
+            info!("Derived Op block {} matches the sequencer", number);
+        } else {
+            alerts.push(DivergenceAlert {
+                op_block_number: *number,
+                derived_hash: *hash,
+                sequencer_hash,
+                eth_tail_number: derive_output.eth_tail.number,
+            });
+        }
+    }
+
+    Ok((derive_output, alerts))
+}
+
+/// Returns whether the derivation failed because it does not continue the prior output.
+fn is_prior_output_mismatch(err: &anyhow::Error) -> bool {
+    matches!(
+        err.downcast_ref::<DeriveError>(),
+        Some(
+            DeriveError::PriorOpTailMismatch { .. }
+                | DeriveError::PriorEthTailMismatch { .. }
+                | DeriveError::PriorOutputMismatch(_)
+        )
+    )
+}
+
+/// Checks how much of the sequencing window of the given Optimism head has elapsed at the
+/// latest Ethereum block.
+fn check_sequencing_window(
+    build_args: &BuildArgs,
+    op_head_block_no: u64,
+    percent: u64,
+) -> anyhow::Result<Option<WindowWarning>> {
+    let config = build_args.chain_config();
+    let mut db = RpcDb::new(
+        &config,
+        build_args.eth_rpc_url.clone(),
+        build_args.op_rpc_url.clone(),
+        None,
+    );
+    let op_head = db.get_full_op_block(op_head_block_no)?;
+    let epoch_number = l1_attributes(&op_head.transactions)?.number();
+
+    let mut eth_provider = new_provider(None, build_args.eth_rpc_url.clone())?;
+    let eth_head_number = eth_provider.resolve_block_number(&provider::BlockId::Latest)?;
+
+    Ok(WindowWarning::check(
+        op_head_block_no,
+        epoch_number,
+        eth_head_number,
+        config.seq_window_size,
+        percent,
+    ))
+}
+
+/// Returns the hash of the given block as reported by the sequencer.
+fn sequencer_block_hash(build_args: &BuildArgs, block_no: u64) -> anyhow::Result<B256> {
+    let mut provider = new_provider(None, build_args.op_rpc_url.clone())?;
+    let header: Header = provider
+        .get_partial_block(&BlockQuery { block_no })?
+        .try_into()?;
+    Ok(header.hash())
+}
+
+/// Logs the alert and posts it to the webhook, if any.
+async fn emit_alert(webhook_url: Option<&str>, alert: &DivergenceAlert) {
+    error!(
+        "Derived chain diverges from the sequencer: {}",
+        serde_json::to_string(alert).unwrap()
+    );
+    post_to_webhook(webhook_url, alert).await;
+}
+
+/// Logs the warning and posts it to the webhook, if any.
+async fn emit_window_warning(webhook_url: Option<&str>, warning: &WindowWarning) {
+    warn!(
+        "No batches for {} of {} L1 blocks of the sequencing window; deposit-only blocks from \
+         L1 block {}: {}",
+        warning.eth_head_number - warning.epoch_number,
+        warning.force_inclusion_block - warning.epoch_number,
+        warning.force_inclusion_block,
+        serde_json::to_string(warning).unwrap()
+    );
+    post_to_webhook(webhook_url, warning).await;
+}
+
+async fn post_to_webhook(webhook_url: Option<&str>, body: &impl Serialize) {
+    if let Some(url) = webhook_url {
+        let response = reqwest::Client::new().post(url).json(body).send().await;
+        if let Err(err) = response.and_then(|response| response.error_for_status()) {
+            warn!("Failed to post alert to webhook: {:#}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn window_warning() {
+        assert_eq!(WindowWarning::check(100, 1000, 3000, 3600, 80), None);
+        let warning = WindowWarning::check(100, 1000, 3900, 3600, 80).unwrap();
+        assert_eq!(warning.force_inclusion_block, 4600);
+        assert_eq!(warning.remaining_blocks, 700);
+        let warning = WindowWarning::check(100, 1000, 4700, 3600, 80).unwrap();
+        assert_eq!(warning.remaining_blocks, 0);
+    }
+}
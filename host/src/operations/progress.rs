@@ -0,0 +1,124 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+
+use log::info;
+use risc0_zkvm::{Segment, SessionEvents};
+use serde::Serialize;
+
+/// Reports the progress of a local proof after every proven segment, either as log
+/// messages or as JSON lines on stdout.
+pub struct ProveProgress {
+    total_segments: usize,
+    json: bool,
+    start: Instant,
+    proven_segments: AtomicUsize,
+}
+
+/// A single progress event of the `--json-progress` stream.
+#[derive(Debug, Serialize)]
+struct ProgressEvent {
+    proven_segments: usize,
+    total_segments: usize,
+    elapsed_secs: u64,
+    eta_secs: Option<u64>,
+}
+
+impl ProveProgress {
+    /// Creates the progress report of a session with the given number of segments.
+    pub fn new(total_segments: usize, json: bool) -> Self {
+        let progress = Self {
+            total_segments,
+            json,
+            start: Instant::now(),
+            proven_segments: AtomicUsize::new(0),
+        };
+        progress.report(0);
+        progress
+    }
+
+    fn report(&self, proven_segments: usize) {
+        let elapsed = self.start.elapsed();
+        let eta = eta(elapsed, proven_segments, self.total_segments);
+        if self.json {
+            let event = ProgressEvent {
+                proven_segments,
+                total_segments: self.total_segments,
+                elapsed_secs: elapsed.as_secs(),
+                eta_secs: eta.map(|eta| eta.as_secs()),
+            };
+            println!("{}", serde_json::to_string(&event).unwrap());
+        } else if proven_segments == 0 {
+            info!("Proving {} segments ...", self.total_segments);
+        } else {
+            info!(
+                "Proved segment {}/{} in {}, ETA {}",
+                proven_segments,
+                self.total_segments,
+                format_duration(elapsed),
+                eta.map_or_else(|| "unknown".to_string(), format_duration)
+            );
+        }
+    }
+}
+
+impl SessionEvents for ProveProgress {
+    fn on_post_prove_segment(&self, _segment: &Segment) {
+        let proven_segments = self.proven_segments.fetch_add(1, Ordering::Relaxed) + 1;
+        self.report(proven_segments);
+    }
+}
+
+/// Estimates the remaining time, assuming all segments take equally long to prove.
+fn eta(elapsed: Duration, proven_segments: usize, total_segments: usize) -> Option<Duration> {
+    if proven_segments == 0 {
+        return None;
+    }
+    let remaining = total_segments.saturating_sub(proven_segments);
+    Some(elapsed.mul_f64(remaining as f64 / proven_segments as f64))
+}
+
+/// Formats the duration with a resolution of seconds, e.g. `1h02m03s`.
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    match (secs / 3600, secs / 60 % 60, secs % 60) {
+        (0, 0, s) => format!("{}s", s),
+        (0, m, s) => format!("{}m{:02}s", m, s),
+        (h, m, s) => format!("{}h{:02}m{:02}s", h, m, s),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eta_of_segments() {
+        let elapsed = Duration::from_secs(60);
+        assert_eq!(eta(elapsed, 0, 10), None);
+        assert_eq!(eta(elapsed, 2, 10), Some(Duration::from_secs(240)));
+        assert_eq!(eta(elapsed, 10, 10), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn duration_format() {
+        assert_eq!(format_duration(Duration::from_millis(5_900)), "5s");
+        assert_eq!(format_duration(Duration::from_secs(125)), "2m05s");
+        assert_eq!(format_duration(Duration::from_secs(3723)), "1h02m03s");
+    }
+}
@@ -12,22 +12,29 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::collections::VecDeque;
+use std::{collections::VecDeque, fs, path::Path};
 
-use anyhow::Context;
+use anyhow::{anyhow, bail, ensure, Context};
 use log::{info, trace};
 use risc0_zkvm::{Assumption, Receipt};
 use zeth_guests::*;
 use zeth_lib::{
     builder::{BlockBuilderStrategy, OptimismStrategy},
-    consts::OP_MAINNET_CHAIN_SPEC,
-    host::{rpc_db::RpcDb, ProviderFactory},
-    input::BlockBuildInput,
+    host::{
+        cache_file_path,
+        channel_cache::ChannelCache,
+        derive_cache::{self, PreviousFramedDeriveInput},
+        rpc_db::{BackfillDb, RpcDb},
+        ProviderFactory,
+    },
+    input::{BlockBuildInput, BlockWitness},
+    journal::Journal,
+    light_client::SyncCommitteeProof,
     optimism::{
-        batcher_db::BatcherDb,
+        batcher_db::{BatcherDb, MemDb},
         composition::{ComposeInput, ComposeInputOperation, ComposeOutputOperation},
-        config::ChainConfig,
-        DeriveInput, DeriveMachine,
+        framed_db::{FrameRecorder, FramedDeriveInput},
+        DeriveCheckpoint, DeriveInput, DeriveMachine, DeriveOutput, DeriveOutputV1,
     },
     output::BlockBuildOutput,
 };
@@ -35,16 +42,27 @@ use zeth_primitives::{
     block::Header,
     mmr::{MerkleMountainRange, MerkleProof},
     transactions::optimism::OptimismTxEssence,
+    Address,
 };
 
 use crate::{
-    cli::{Cli, Network},
-    operations::{maybe_prove, verify_bonsai_receipt},
+    batch_data::{
+        batch_data_usage, dropped_batch_data, report_batch_data_usage, report_dropped_batch_data,
+    },
+    cli::{BuildArgs, Cli, Network},
+    drift::{block_drifts, report_drifts},
+    failure::Failure,
+    input_size::{ensure_input_size, input_size},
+    operations::{
+        guest::{previous_guest_elf, prove_guest, GuestProgram, OpBlock, OpCompose, OpDerive},
+        maybe_prove, verify_bonsai_receipt,
+    },
+    store::{derived_block_records, l1_attributes, DerivedBlockStore},
 };
 
 pub async fn derive_rollup_blocks(cli: &Cli) -> anyhow::Result<Option<(String, Receipt)>> {
     info!("Fetching data ...");
-    let build_args = cli.build_args();
+    let build_args = cli.build_args().context("missing build arguments")?;
     let op_builder_provider_factory = ProviderFactory::new(
         build_args.cache.clone(),
         Network::Optimism.to_string(),
@@ -52,58 +70,159 @@ pub async fn derive_rollup_blocks(cli: &Cli) -> anyhow::Result<Option<(String, R
     );
 
     info!("Running preflight");
-    let config = ChainConfig::optimism();
+    let config = build_args.chain_config();
+    // a time-boxed derivation is only bounded by the timestamp of its blocks
+    let op_derive_block_count = match build_args.until {
+        Some(_) => u32::MAX,
+        None => build_args.block_count,
+    };
+    let prior_output = build_args
+        .prior_receipt
+        .as_deref()
+        .map(load_prior_output)
+        .transpose()?;
+    let resume_checkpoint = build_args
+        .resume_checkpoint
+        .as_deref()
+        .map(load_checkpoint)
+        .transpose()?;
     let derive_input = DeriveInput {
         db: RpcDb::new(
             &config,
             build_args.eth_rpc_url.clone(),
             build_args.op_rpc_url.clone(),
             build_args.cache.clone(),
-        ),
+        )
+        .with_beacon_rpc_url(build_args.beacon_rpc_url.clone())
+        .with_validation_policy(build_args.validation_policy()),
         op_head_block_no: build_args.block_number,
-        op_derive_block_count: build_args.block_count,
+        op_derive_block_count,
+        op_derive_max_timestamp: None,
         op_block_outputs: vec![],
         block_image_id: OP_BLOCK_ID,
+        eth_accumulator: build_args.eth_accumulator,
+        op_anchor_block_no: build_args.l1_anchor,
+        batch_provenance: build_args.batch_provenance,
+        rollup_config: build_args.custom_rollup_config(),
+        prior_output: prior_output.clone(),
+        checkpoint: build_args.checkpoint.is_some(),
+        resume_checkpoint: resume_checkpoint.clone(),
     };
     let factory_clone = op_builder_provider_factory.clone();
+    let until = build_args.until;
+    let channel_cache = build_args.cache.as_deref().map(ChannelCache::new);
     let (op_block_inputs, derive_machine, derive_output) = tokio::task::spawn_blocking(move || {
         let mut derive_machine = DeriveMachine::new(config, derive_input, Some(factory_clone))
-            .expect("Could not create derive machine");
+            .context("Could not create derive machine")?;
+        derive_machine.op_batcher.set_channel_cache(channel_cache);
+        derive_machine
+            .op_batcher
+            .set_decompression_threads(decompression_threads());
+        if let Some(until) = until {
+            let max_timestamp =
+                until.max_timestamp(derive_machine.op_batcher.state.safe_head.timestamp);
+            info!("Deriving all blocks up to timestamp {}", max_timestamp);
+            derive_machine.derive_input.op_derive_max_timestamp = Some(max_timestamp);
+        }
         let mut op_block_inputs = vec![];
         let derive_output = derive_machine
             .derive(Some(&mut op_block_inputs))
-            .expect("could not derive");
-        (op_block_inputs, derive_machine, derive_output)
+            .context("could not derive")?;
+        anyhow::Ok((op_block_inputs, derive_machine, derive_output))
     })
-    .await?;
+    .await?
+    .context(Failure::Witness)?;
+
+    if let Some(path) = &build_args.checkpoint {
+        save_checkpoint(path, &derive_machine.checkpoint())?;
+    }
 
     let (assumptions, bonsai_receipt_uuids, op_block_outputs) =
-        build_op_blocks(cli, op_block_inputs).await;
+        build_op_blocks(cli, &op_block_inputs).await?;
+
+    if let Some(percent) = build_args.drift_report {
+        let drifts = block_drifts(&op_block_inputs, &derive_output)?;
+        report_drifts(
+            &drifts,
+            derive_machine.op_batcher.config().max_seq_drift,
+            percent,
+        );
+    }
+
+    if let Some(db) = &build_args.db {
+        store_derived_blocks(
+            db,
+            derive_machine.op_batcher.config().message_passer,
+            op_builder_provider_factory.clone(),
+            op_block_inputs,
+            op_block_outputs.clone(),
+            derive_output.clone(),
+        )
+        .await?;
+    }
 
     let derive_input_mem = DeriveInput {
         db: derive_machine.derive_input.db.get_mem_db(),
         op_head_block_no: build_args.block_number,
-        op_derive_block_count: build_args.block_count,
+        op_derive_block_count,
+        op_derive_max_timestamp: derive_machine.derive_input.op_derive_max_timestamp,
         op_block_outputs,
         block_image_id: OP_BLOCK_ID,
+        eth_accumulator: build_args.eth_accumulator,
+        op_anchor_block_no: build_args.l1_anchor,
+        batch_provenance: build_args.batch_provenance,
+        rollup_config: build_args.custom_rollup_config(),
+        prior_output,
+        checkpoint: build_args.checkpoint.is_some(),
+        resume_checkpoint,
     };
+    if let Some(cache) = &build_args.cache {
+        save_derive_input(cache, &derive_input_mem)?;
+    }
+    if build_args.batch_data_report {
+        let usage = batch_data_usage(&derive_machine.op_batcher, &derive_input_mem.db);
+        report_batch_data_usage(&usage);
+        report_dropped_batch_data(&dropped_batch_data(&derive_machine.private_output()));
+    }
 
     info!("Running from memory ...");
-    {
+    let framed_input = {
         let input_clone = derive_input_mem.clone();
-        let output_mem = tokio::task::spawn_blocking(move || {
-            DeriveMachine::new(
-                ChainConfig::optimism(),
-                input_clone,
+        // blocks missing from the witness are fetched again instead of failing the replay
+        let rpc_db = RpcDb::new(
+            &build_args.chain_config(),
+            build_args.eth_rpc_url.clone(),
+            build_args.op_rpc_url.clone(),
+            build_args.cache.clone(),
+        )
+        .with_beacon_rpc_url(build_args.beacon_rpc_url.clone());
+        let config = build_args.chain_config();
+        let (output_mem, frames) = tokio::task::spawn_blocking(move || {
+            let mut derive_machine = DeriveMachine::new(
+                config,
+                input_clone.map_db(|db| FrameRecorder::new(BackfillDb::new(db, rpc_db))),
                 Some(op_builder_provider_factory),
             )
-            .expect("Could not create derive machine")
-            .derive(None)
-            .expect("could not derive")
+            .context("Could not create derive machine")?;
+            let output_mem = derive_machine.derive(None).context("could not derive")?;
+            anyhow::Ok((output_mem, derive_machine.derive_input.db.frames))
         })
-        .await?;
-        assert_eq!(derive_output, output_mem);
-    }
+        .await?
+        .context(Failure::DerivationMismatch)?;
+        ensure_same_derivation(&derive_output, &output_mem)?;
+
+        FramedDeriveInput {
+            derive_input: derive_input_mem.map_db(|_| ()),
+            frames,
+        }
+    };
+    ensure_input_size(
+        input_size(&framed_input)?,
+        build_args.max_input_size,
+        "derive fewer blocks with a smaller --block-count, or split the derivation into \
+         epoch-aligned calls with --composition and --epoch-aligned",
+    )
+    .context(Failure::Witness)?;
 
     info!("In-memory test complete");
     println!(
@@ -117,21 +236,71 @@ pub async fn derive_rollup_blocks(cli: &Cli) -> anyhow::Result<Option<(String, R
     for derived_block in &derive_output.derived_op_blocks {
         println!("Derived: {} {}", derived_block.number, derived_block.hash);
     }
+    if let Some(eth_accumulator) = &derive_output.eth_accumulator {
+        println!(
+            "Eth accumulator: {} (blocks {} to {})",
+            eth_accumulator.hash, eth_accumulator.first.number, eth_accumulator.last.number
+        );
+    }
+    if let Some(l1_anchor) = &derive_output.l1_anchor {
+        println!(
+            "L1 anchor: {} {} (Op block {} {})",
+            l1_anchor.eth_block.number,
+            l1_anchor.eth_block.hash,
+            l1_anchor.op_block.number,
+            l1_anchor.op_block.hash
+        );
+    }
+    if let Some(rollup_config_hash) = &derive_output.rollup_config_hash {
+        println!("Rollup config: {}", rollup_config_hash);
+    }
+    if let Some(prior_output_digest) = &derive_output.prior_output_digest {
+        println!("Prior output: {}", prior_output_digest);
+    }
+    if let Some(checkpoint_hash) = &derive_output.checkpoint_hash {
+        println!("Checkpoint: {}", checkpoint_hash);
+    }
+    for provenance in &derive_output.batch_provenance {
+        match &provenance.source {
+            Some(source) => println!(
+                "Batch of Op block {}: channel {:#034x} included in Eth block {}",
+                provenance.op_block.number, source.channel_id, source.inclusion_block_number
+            ),
+            None => println!(
+                "Batch of Op block {}: default batch",
+                provenance.op_block.number
+            ),
+        }
+    }
 
     let final_result = match cli {
-        Cli::Prove(..) => {
-            maybe_prove(
-                cli,
-                &derive_input_mem,
-                OP_DERIVE_ELF,
-                &derive_output,
-                (assumptions, bonsai_receipt_uuids),
-            )
-            .await
-        }
+        Cli::Prove(prove_args) => match prove_args.input_version {
+            // serve inputs of an earlier format with the guest of that release
+            Some(version) if version != derive_cache::DERIVE_INPUT_VERSION => {
+                let elf = previous_guest_elf(OpDerive::NAME, version)?;
+                info!(
+                    "Proving with the {} guest of input version {}",
+                    OpDerive::NAME,
+                    version
+                );
+                let input = PreviousFramedDeriveInput::new(framed_input, version)?;
+                let assumptions = (assumptions, bonsai_receipt_uuids);
+                let output = DeriveOutputV1::try_from(derive_output)?;
+                maybe_prove(cli, &input, elf, &output, assumptions).await?
+            }
+            _ => {
+                prove_guest::<OpDerive>(
+                    cli,
+                    &framed_input,
+                    &derive_output,
+                    (assumptions, bonsai_receipt_uuids),
+                )
+                .await?
+            }
+        },
         Cli::Verify(verify_args) => Some(
             verify_bonsai_receipt(
-                OP_DERIVE_ID.into(),
+                OpDerive::ID.into(),
                 &derive_output,
                 verify_args.bonsai_receipt_uuid.clone(),
                 4,
@@ -148,19 +317,41 @@ pub async fn compose_derived_rollup_blocks(
     cli: &Cli,
     composition_size: u32,
 ) -> anyhow::Result<Option<(String, Receipt)>> {
-    let build_args = cli.build_args();
+    let build_args = cli.build_args().context("missing build arguments")?;
     // OP Composition
     info!("Fetching data ...");
+    let mut segments = if build_args.epoch_aligned {
+        let sequence_numbers = fetch_sequence_numbers(build_args).await?;
+        epoch_aligned_segments(build_args.block_number, &sequence_numbers, composition_size)
+    } else {
+        (0..build_args.block_count)
+            .step_by(composition_size as usize)
+            .map(|op_block_index| {
+                (
+                    build_args.block_number + op_block_index as u64,
+                    composition_size,
+                )
+            })
+            .collect()
+    };
+    // a derivation of zero blocks still attests the head and its L1 origin
+    if segments.is_empty() {
+        segments.push((build_args.block_number, 0));
+    }
+    info!("Deriving in {} calls", segments.len());
+
     let mut lift_queue = Vec::new();
     let mut complete_eth_chain: Vec<Header> = Vec::new();
-    for op_block_index in (0..build_args.block_count).step_by(composition_size as usize) {
-        let config = ChainConfig::optimism();
+    for (op_head_block_no, op_derive_block_count) in segments {
+        let config = build_args.chain_config();
         let db = RpcDb::new(
             &config,
             build_args.eth_rpc_url.clone(),
             build_args.op_rpc_url.clone(),
             build_args.cache.clone(),
-        );
+        )
+        .with_beacon_rpc_url(build_args.beacon_rpc_url.clone())
+        .with_validation_policy(build_args.validation_policy());
         let op_builder_provider_factory = ProviderFactory::new(
             build_args.cache.clone(),
             Network::Optimism.to_string(),
@@ -169,17 +360,32 @@ pub async fn compose_derived_rollup_blocks(
 
         let derive_input = DeriveInput {
             db,
-            op_head_block_no: build_args.block_number + op_block_index as u64,
-            op_derive_block_count: composition_size,
+            op_head_block_no,
+            op_derive_block_count,
+            op_derive_max_timestamp: None,
             op_block_outputs: vec![],
             block_image_id: OP_BLOCK_ID,
+            eth_accumulator: false,
+            op_anchor_block_no: None,
+            batch_provenance: false,
+            rollup_config: None,
+            prior_output: None,
+            checkpoint: false,
+            resume_checkpoint: None,
         };
         let factory_clone = op_builder_provider_factory.clone();
         let mut derive_machine = tokio::task::spawn_blocking(move || {
             DeriveMachine::new(config, derive_input, Some(factory_clone))
-                .expect("Could not create derive machine")
+                .context("Could not create derive machine")
         })
-        .await?;
+        .await?
+        .context(Failure::Witness)?;
+        derive_machine
+            .op_batcher
+            .set_channel_cache(build_args.cache.as_deref().map(ChannelCache::new));
+        derive_machine
+            .op_batcher
+            .set_decompression_threads(decompression_threads());
         let eth_head_no = derive_machine.op_batcher.state.epoch.number;
         let eth_head = derive_machine
             .derive_input
@@ -193,10 +399,11 @@ pub async fn compose_derived_rollup_blocks(
                 let mut op_block_inputs = vec![];
                 let derive_output = derive_machine
                     .derive(Some(&mut op_block_inputs))
-                    .expect("could not derive");
-                (op_block_inputs, derive_machine, derive_output)
+                    .context("could not derive")?;
+                anyhow::Ok((op_block_inputs, derive_machine, derive_output))
             })
-            .await?;
+            .await?
+            .context(Failure::Witness)?;
 
         let eth_tail = derive_machine
             .derive_input
@@ -220,41 +427,84 @@ pub async fn compose_derived_rollup_blocks(
         eth_chain.push(eth_tail);
 
         let (assumptions, bonsai_receipt_uuids, op_block_outputs) =
-            build_op_blocks(cli, op_block_inputs).await;
+            build_op_blocks(cli, &op_block_inputs).await?;
+
+        if let Some(db) = &build_args.db {
+            store_derived_blocks(
+                db,
+                derive_machine.op_batcher.config().message_passer,
+                op_builder_provider_factory.clone(),
+                op_block_inputs,
+                op_block_outputs.clone(),
+                derive_output.clone(),
+            )
+            .await?;
+        }
 
         let derive_input_mem = DeriveInput {
             db: derive_machine.derive_input.db.get_mem_db(),
-            op_head_block_no: build_args.block_number + op_block_index as u64,
-            op_derive_block_count: composition_size,
+            op_head_block_no,
+            op_derive_block_count,
+            op_derive_max_timestamp: None,
             op_block_outputs,
             block_image_id: OP_BLOCK_ID,
+            eth_accumulator: false,
+            op_anchor_block_no: None,
+            batch_provenance: false,
+            rollup_config: None,
+            prior_output: None,
+            checkpoint: false,
+            resume_checkpoint: None,
         };
+        if let Some(cache) = &build_args.cache {
+            save_derive_input(cache, &derive_input_mem)?;
+        }
 
         info!("Deriving ...");
-        {
+        let framed_input = {
             let input_clone = derive_input_mem.clone();
-            let output_mem = tokio::task::spawn_blocking(move || {
-                DeriveMachine::new(
-                    ChainConfig::optimism(),
-                    input_clone,
+            let rpc_db = RpcDb::new(
+                &build_args.chain_config(),
+                build_args.eth_rpc_url.clone(),
+                build_args.op_rpc_url.clone(),
+                build_args.cache.clone(),
+            )
+            .with_beacon_rpc_url(build_args.beacon_rpc_url.clone());
+            let config = build_args.chain_config();
+            let (output_mem, frames) = tokio::task::spawn_blocking(move || {
+                let mut derive_machine = DeriveMachine::new(
+                    config,
+                    input_clone.map_db(|db| FrameRecorder::new(BackfillDb::new(db, rpc_db))),
                     Some(op_builder_provider_factory),
                 )
-                .expect("Could not create derive machine")
-                .derive(None)
-                .context("could not derive")
+                .context("Could not create derive machine")?;
+                let output_mem = derive_machine.derive(None).context("could not derive")?;
+                anyhow::Ok((output_mem, derive_machine.derive_input.db.frames))
             })
-            .await??;
-            assert_eq!(derive_output, output_mem);
-        }
+            .await?
+            .context(Failure::DerivationMismatch)?;
+            ensure_same_derivation(&derive_output, &output_mem)?;
 
-        let receipt = maybe_prove(
+            FramedDeriveInput {
+                derive_input: derive_input_mem.map_db(|_| ()),
+                frames,
+            }
+        };
+        ensure_input_size(
+            input_size(&framed_input)?,
+            build_args.max_input_size,
+            "derive fewer blocks per call with a smaller --composition value, and split at \
+             epoch boundaries with --epoch-aligned",
+        )
+        .context(Failure::Witness)?;
+
+        let receipt = prove_guest::<OpDerive>(
             cli,
-            &derive_input_mem,
-            OP_DERIVE_ELF,
+            &framed_input,
             &derive_output,
             (assumptions, bonsai_receipt_uuids),
         )
-        .await;
+        .await?;
 
         // Append derivation outputs to lift queue
         lift_queue.push((derive_output, receipt));
@@ -280,7 +530,8 @@ pub async fn compose_derived_rollup_blocks(
     }
     let eth_chain_root = eth_mountain_range
         .root(Some(&mut sibling_map))
-        .expect("No eth blocks loaded!");
+        .context("No eth blocks loaded!")
+        .context(Failure::Witness)?;
     let prep_compose_input = ComposeInput {
         block_image_id: OP_BLOCK_ID,
         derive_image_id: OP_DERIVE_ID,
@@ -295,16 +546,16 @@ pub async fn compose_derived_rollup_blocks(
     let prep_compose_output = prep_compose_input
         .clone()
         .process()
-        .expect("Prep composition failed.");
+        .context("Prep composition failed.")
+        .context(Failure::Proving)?;
 
-    let prep_compose_receipt = maybe_prove(
+    let prep_compose_receipt = prove_guest::<OpCompose>(
         cli,
         &prep_compose_input,
-        OP_COMPOSE_ELF,
         &prep_compose_output,
         Default::default(),
     )
-    .await;
+    .await?;
 
     // Lift
     info!("Lifting {} proofs...", lift_queue.len());
@@ -325,18 +576,18 @@ pub async fn compose_derived_rollup_blocks(
         let lift_compose_output = lift_compose_input
             .clone()
             .process()
-            .expect("Lift composition failed.");
+            .context("Lift composition failed.")
+            .context(Failure::Proving)?;
         trace!("Lifted ... {:?}", &lift_compose_output);
 
         let lift_compose_receipt = if let Some((receipt_uuid, receipt)) = derive_receipt {
-            maybe_prove(
+            prove_guest::<OpCompose>(
                 cli,
                 &lift_compose_input,
-                OP_COMPOSE_ELF,
                 &lift_compose_output,
                 (vec![receipt.into()], vec![receipt_uuid]),
             )
-            .await
+            .await?
         } else {
             None
         };
@@ -348,23 +599,23 @@ pub async fn compose_derived_rollup_blocks(
     info!("Composing {} proofs...", join_queue.len());
     while join_queue.len() > 1 {
         // Pop left output
-        let (left, left_receipt) = join_queue.pop_front().unwrap();
+        let (left, left_receipt) = join_queue.pop_front().context("join queue is empty")?;
         // Only peek at right output
-        let (right, _right_receipt) = join_queue.front().unwrap();
+        let (right, _right_receipt) = join_queue.front().context("join queue is empty")?;
         trace!("Joining");
         let ComposeOutputOperation::AGGREGATE {
             op_tail: left_op_tail,
             ..
         } = &left.operation
         else {
-            panic!("Expected left aggregate operation output!")
+            bail!("Expected left aggregate operation output!")
         };
         let ComposeOutputOperation::AGGREGATE {
             op_head: right_op_head,
             ..
         } = &right.operation
         else {
-            panic!("Expected right aggregate operation output!")
+            bail!("Expected right aggregate operation output!")
         };
         // Push dangling workloads (odd block count) to next round
         if left_op_tail != right_op_head {
@@ -377,7 +628,7 @@ pub async fn compose_derived_rollup_blocks(
             continue;
         }
         // Actually pop right output for pairing
-        let (right, right_receipt) = join_queue.pop_front().unwrap();
+        let (right, right_receipt) = join_queue.pop_front().context("join queue is empty")?;
         let join_compose_input = ComposeInput {
             block_image_id: OP_BLOCK_ID,
             derive_image_id: OP_DERIVE_ID,
@@ -389,24 +640,24 @@ pub async fn compose_derived_rollup_blocks(
         let join_compose_output = join_compose_input
             .clone()
             .process()
-            .expect("Join composition failed.");
+            .context("Join composition failed.")
+            .context(Failure::Proving)?;
 
         let join_compose_receipt = if let (
             Some((left_receipt_uuid, left_receipt)),
             Some((right_receipt_uuid, right_receipt)),
         ) = (left_receipt, right_receipt)
         {
-            maybe_prove(
+            prove_guest::<OpCompose>(
                 cli,
                 &join_compose_input,
-                OP_COMPOSE_ELF,
                 &join_compose_output,
                 (
                     vec![left_receipt.into(), right_receipt.into()],
                     vec![left_receipt_uuid, right_receipt_uuid],
                 ),
             )
-            .await
+            .await?
         } else {
             None
         };
@@ -416,7 +667,26 @@ pub async fn compose_derived_rollup_blocks(
     }
 
     // Finish
-    let (aggregate_output, aggregate_receipt) = join_queue.pop_front().unwrap();
+    let (aggregate_output, aggregate_receipt) = join_queue
+        .pop_front()
+        .context("no derivation to aggregate")?;
+    let pre_bedrock = match build_args.pre_bedrock_history {
+        Some(history) => Some(Box::new((
+            fetch_bedrock_genesis(build_args).await?,
+            history,
+        ))),
+        None => None,
+    };
+    let l1_light_client = match &build_args.l1_light_client_proof {
+        Some(path) => {
+            let proof: SyncCommitteeProof = serde_json::from_slice(
+                &fs::read(path).with_context(|| format!("Failed to read '{}'", path.display()))?,
+            )
+            .context("invalid sync committee proof file")?;
+            Some(Box::new(proof))
+        }
+        None => None,
+    };
     let finish_compose_input = ComposeInput {
         block_image_id: OP_BLOCK_ID,
         derive_image_id: OP_DERIVE_ID,
@@ -424,6 +694,8 @@ pub async fn compose_derived_rollup_blocks(
         operation: ComposeInputOperation::FINISH {
             prep: prep_compose_output,
             aggregate: aggregate_output,
+            pre_bedrock,
+            l1_light_client,
         },
         eth_chain_merkle_root: eth_chain_root,
     };
@@ -431,28 +703,28 @@ pub async fn compose_derived_rollup_blocks(
     let finish_compose_output = finish_compose_input
         .clone()
         .process()
-        .expect("Finish composition failed.");
+        .context("Finish composition failed.")
+        .context(Failure::Proving)?;
 
     let final_result = if let (
         Some((prep_receipt_uuid, prep_receipt)),
         Some((aggregate_receipt_uuid, aggregate_receipt)),
     ) = (prep_compose_receipt, aggregate_receipt)
     {
-        maybe_prove(
+        prove_guest::<OpCompose>(
             cli,
             &finish_compose_input,
-            OP_COMPOSE_ELF,
             &finish_compose_output,
             (
                 vec![prep_receipt.into(), aggregate_receipt.into()],
                 vec![prep_receipt_uuid, aggregate_receipt_uuid],
             ),
         )
-        .await
+        .await?
     } else if let Cli::Verify(verify_args) = cli {
         Some(
             verify_bonsai_receipt(
-                OP_COMPOSE_ID.into(),
+                OpCompose::ID.into(),
                 &finish_compose_output,
                 verify_args.bonsai_receipt_uuid.clone(),
                 4,
@@ -469,25 +741,234 @@ pub async fn compose_derived_rollup_blocks(
     Ok(final_result)
 }
 
+/// Checks that the derivation from the in-memory witness matches the derivation from
+/// the provider data.
+fn ensure_same_derivation(expected: &DeriveOutput, actual: &DeriveOutput) -> anyhow::Result<()> {
+    if expected != actual {
+        return Err(anyhow!(
+            "in-memory derivation returned {:?}, expected {:?}",
+            actual,
+            expected
+        ))
+        .context(Failure::DerivationMismatch);
+    }
+    Ok(())
+}
+
+/// Loads the derivation output committed by the op-derive receipt in the given file,
+/// after verifying the receipt against the image ID of the derivation guest.
+pub fn load_prior_output(path: &Path) -> anyhow::Result<DeriveOutput> {
+    let bytes = fs::read(path).with_context(|| format!("could not read {}", path.display()))?;
+    let (_, receipt): (String, Receipt) =
+        bincode::deserialize(&bytes).context("invalid prior receipt")?;
+    receipt
+        .verify(OpDerive::ID)
+        .context(Failure::Verification)
+        .context("prior receipt verification failed")?;
+    DeriveOutput::from_journal(&receipt.journal.bytes).context("invalid prior journal")
+}
+
+/// Reads the derivation checkpoint saved in the given file.
+pub fn load_checkpoint(path: &Path) -> anyhow::Result<DeriveCheckpoint> {
+    let bytes = fs::read(path).with_context(|| format!("could not read {}", path.display()))?;
+    bincode::deserialize(&bytes).context("invalid checkpoint")
+}
+
+/// Saves the derivation checkpoint to the given file.
+pub fn save_checkpoint(path: &Path, checkpoint: &DeriveCheckpoint) -> anyhow::Result<()> {
+    let bytes = bincode::serialize(checkpoint)?;
+    fs::write(path, bytes).with_context(|| format!("could not write {}", path.display()))?;
+    info!("Saved checkpoint in {}", path.display());
+    Ok(())
+}
+
+/// Returns the number of threads decompressing the channels during the preflight, which
+/// does not affect the derivation inside the guest.
+fn decompression_threads() -> usize {
+    std::thread::available_parallelism().map_or(1, usize::from)
+}
+
+/// Fetches the header of the Bedrock genesis, which must be the start of the composition.
+async fn fetch_bedrock_genesis(build_args: &BuildArgs) -> anyhow::Result<Header> {
+    let config = build_args.chain_config();
+    let bedrock_genesis = config.bedrock_genesis;
+    ensure!(
+        build_args.block_number == bedrock_genesis.number,
+        "Chaining the pre-Bedrock history requires starting at the Bedrock genesis {}",
+        bedrock_genesis.number
+    );
+
+    let mut db = RpcDb::new(
+        &config,
+        build_args.eth_rpc_url.clone(),
+        build_args.op_rpc_url.clone(),
+        build_args.cache.clone(),
+    );
+    let header =
+        tokio::task::spawn_blocking(move || db.get_op_block_header(bedrock_genesis.number))
+            .await??;
+    ensure!(
+        header.hash() == bedrock_genesis.hash,
+        "Bedrock genesis hash mismatch: expected {}, got {}",
+        bedrock_genesis.hash,
+        header.hash()
+    );
+
+    Ok(header)
+}
+
 async fn build_op_blocks(
     cli: &Cli,
-    op_block_inputs: Vec<BlockBuildInput<OptimismTxEssence>>,
-) -> (Vec<Assumption>, Vec<String>, Vec<BlockBuildOutput>) {
+    op_block_inputs: &[BlockBuildInput<OptimismTxEssence>],
+) -> anyhow::Result<(Vec<Assumption>, Vec<String>, Vec<BlockBuildOutput>)> {
     let mut assumptions: Vec<Assumption> = vec![];
     let mut bonsai_uuids = vec![];
     let mut op_block_outputs = vec![];
+    let chain_config = cli
+        .build_args()
+        .context("missing build arguments")?
+        .chain_config();
     for input in op_block_inputs {
-        let output = OptimismStrategy::build_from(&OP_MAINNET_CHAIN_SPEC, input.clone())
-            .expect("Failed to build op block")
+        let output = OptimismStrategy::build_from(chain_config.chain_spec, input.clone())
+            .context("Failed to build op block")
+            .context(Failure::Witness)?
             .with_state_hashed();
 
+        let witness = BlockWitness::Trie(input.clone());
         if let Some((bonsai_receipt_uuid, receipt)) =
-            maybe_prove(cli, &input, OP_BLOCK_ELF, &output, Default::default()).await
+            prove_guest::<OpBlock>(cli, &witness, &output, Default::default()).await?
         {
             assumptions.push(receipt.into());
             bonsai_uuids.push(bonsai_receipt_uuid);
         }
         op_block_outputs.push(output);
     }
-    (assumptions, bonsai_uuids, op_block_outputs)
+    Ok((assumptions, bonsai_uuids, op_block_outputs))
+}
+
+/// Stores the derived blocks in the database at the given path.
+async fn store_derived_blocks(
+    db: &Path,
+    message_passer: Address,
+    provider_factory: ProviderFactory,
+    op_block_inputs: Vec<BlockBuildInput<OptimismTxEssence>>,
+    op_block_outputs: Vec<BlockBuildOutput>,
+    derive_output: DeriveOutput,
+) -> anyhow::Result<()> {
+    let records = tokio::task::spawn_blocking(move || {
+        derived_block_records(
+            &provider_factory,
+            message_passer,
+            &op_block_inputs,
+            &op_block_outputs,
+            &derive_output,
+        )
+    })
+    .await??;
+
+    let store = DerivedBlockStore::open(db)?;
+    for record in &records {
+        store.insert(record)?;
+    }
+    info!(
+        "Stored {} derived blocks in {}",
+        records.len(),
+        db.display()
+    );
+
+    Ok(())
+}
+
+/// Fetches the sequence numbers of the Optimism blocks to derive from the RPC node.
+async fn fetch_sequence_numbers(build_args: &BuildArgs) -> anyhow::Result<Vec<u64>> {
+    let mut db = RpcDb::new(
+        &build_args.chain_config(),
+        build_args.eth_rpc_url.clone(),
+        build_args.op_rpc_url.clone(),
+        build_args.cache.clone(),
+    );
+    let block_numbers =
+        (build_args.block_number + 1)..=(build_args.block_number + build_args.block_count as u64);
+    tokio::task::spawn_blocking(move || {
+        block_numbers
+            .map(|block_no| {
+                let block = db.get_full_op_block(block_no)?;
+                let l1_info = l1_attributes(&block.transactions)
+                    .with_context(|| format!("invalid Op block {}", block_no))?;
+                anyhow::Ok(l1_info.sequence_number())
+            })
+            .collect()
+    })
+    .await?
+}
+
+/// Splits the derivation of the blocks following `op_head_block_no` into calls of at
+/// least `composition_size` blocks, each starting with the first block of an L1 epoch.
+///
+/// The sequence numbers of the derived blocks mark the start of each epoch with 0. Only
+/// the first call may begin in the middle of an epoch. Returns the head block number and
+/// block count of each call.
+fn epoch_aligned_segments(
+    op_head_block_no: u64,
+    sequence_numbers: &[u64],
+    composition_size: u32,
+) -> Vec<(u64, u32)> {
+    let mut segments = Vec::new();
+    let mut segment_head = op_head_block_no;
+    let mut segment_count = 0;
+    for (i, sequence_number) in sequence_numbers.iter().enumerate() {
+        if segment_count >= composition_size && *sequence_number == 0 {
+            segments.push((segment_head, segment_count));
+            segment_head = op_head_block_no + i as u64;
+            segment_count = 0;
+        }
+        segment_count += 1;
+    }
+    if segment_count > 0 {
+        segments.push((segment_head, segment_count));
+    }
+
+    segments
+}
+
+/// Stores the derivation input in the cache directory using the versioned cache format.
+fn save_derive_input(cache: &Path, derive_input: &DeriveInput<MemDb>) -> anyhow::Result<()> {
+    let ext = match derive_input.op_derive_max_timestamp {
+        Some(max_timestamp) => format!("until_{}.input", max_timestamp),
+        None => format!("{}.input", derive_input.op_derive_block_count),
+    };
+    let file_path = cache_file_path(
+        cache,
+        &Network::OptimismDerived.to_string(),
+        derive_input.op_head_block_no,
+        &ext,
+    );
+    derive_cache::save(&file_path, derive_input)?;
+    info!("Cached derive input in {}", file_path.display());
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn segments_start_with_epochs() {
+        // two blocks of the current epoch, followed by epochs of 3, 1 and 4 blocks
+        let sequence_numbers = [4, 5, 0, 1, 2, 0, 0, 1, 2, 3];
+        assert_eq!(
+            epoch_aligned_segments(100, &sequence_numbers, 2),
+            vec![(100, 2), (102, 3), (105, 5)]
+        );
+        assert_eq!(
+            epoch_aligned_segments(100, &sequence_numbers, 4),
+            vec![(100, 5), (105, 5)]
+        );
+        assert_eq!(
+            epoch_aligned_segments(100, &sequence_numbers, 1),
+            vec![(100, 2), (102, 3), (105, 1), (106, 4)]
+        );
+        assert!(epoch_aligned_segments(100, &[], 1).is_empty());
+    }
 }
@@ -16,13 +16,13 @@ use std::{str::FromStr, sync::Arc};
 
 use alloy_primitives::U256;
 use alloy_sol_types::{sol, SolValue};
-use anyhow::anyhow;
+use anyhow::{anyhow, Context};
 use bonsai_sdk::alpha::responses::{Groth16Seal, SnarkReceipt};
 use ethers_contract::abigen;
 use ethers_providers::{Http, Provider, RetryClient};
 use risc0_zkvm::sha::{Digest, Digestible};
 
-use crate::cli::Cli;
+use crate::{cli::Cli, failure::Failure};
 
 sol!(
     /// A Groth16 seal over the claimed receipt claim.
@@ -125,14 +125,13 @@ pub async fn verify_groth16_snark(
         )
         .await?;
 
-    if verification {
-        log::info!(
-            "SNARK verified successfully using {}!",
-            verifier_contract_address
-        );
-    } else {
-        log::error!("SNARK verification failed!");
+    if !verification {
+        return Err(anyhow!("SNARK verification failed!")).context(Failure::Verification);
     }
+    log::info!(
+        "SNARK verified successfully using {}!",
+        verifier_contract_address
+    );
 
     Ok(())
 }
@@ -0,0 +1,52 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{fs, path::Path};
+
+use anyhow::Context;
+use log::info;
+use risc0_zkvm::Receipt;
+use zeth_lib::optimism::unsafe_payload::{SignedPayload, UnsafeInput};
+
+use crate::{
+    cli::Cli,
+    operations::guest::{run_guest, OpUnsafe},
+};
+
+/// Attests the sequencer-signed payloads stored in the given JSON file.
+pub async fn attest_unsafe_payloads(
+    cli: &Cli,
+    payloads_path: &Path,
+) -> anyhow::Result<Option<(String, Receipt)>> {
+    let payloads: Vec<SignedPayload> = serde_json::from_slice(
+        &fs::read(payloads_path)
+            .with_context(|| format!("Failed to read '{}'", payloads_path.display()))?,
+    )
+    .context("invalid payloads file")?;
+    let build_args = cli.build_args().context("missing build arguments")?;
+    let input = UnsafeInput {
+        payloads,
+        rollup_config: build_args.custom_rollup_config(),
+    };
+
+    info!("Verifying {} payloads ...", input.payloads.len());
+    // the guest verifies the payloads against the same configuration
+    let output = input.process(&input.chain_config()?)?;
+    println!("Signer: {}", output.signer);
+    for block in &output.unsafe_op_blocks {
+        println!("Unsafe: {} {}", block.number, block.hash);
+    }
+
+    run_guest::<OpUnsafe>(cli, &input, &output, Default::default()).await
+}
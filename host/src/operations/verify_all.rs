@@ -0,0 +1,327 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt;
+
+use anyhow::{bail, ensure, Context};
+use risc0_zkvm::{
+    sha::{Digest, Digestible},
+    Receipt,
+};
+use zeth_lib::{
+    journal::Journal,
+    optimism::{
+        batcher::BlockId,
+        composition::{ComposeOutput, ComposeOutputOperation},
+        DeriveOutput,
+    },
+};
+
+use crate::{
+    failure::Failure,
+    operations::guest::{GuestProgram, OpBlock, OpCompose, OpDerive},
+};
+
+/// The Optimism blocks proven by a single derivation or aggregation receipt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProvenSegment {
+    /// The name of the guest that created the receipt.
+    pub guest: &'static str,
+    /// The block the segment starts from; it is not proven by the receipt.
+    pub op_head: BlockId,
+    /// The last block proven by the receipt.
+    pub op_tail: BlockId,
+    /// The last Ethereum block the blocks were derived from.
+    pub eth_tail: BlockId,
+}
+
+/// A contiguous range of Optimism blocks proven by a chain of receipts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProvenRange {
+    /// The block the range starts from; it is not proven itself.
+    pub op_head: BlockId,
+    /// The last proven block of the range.
+    pub op_tail: BlockId,
+    /// The indices of the receipts proving the range, in chain order.
+    pub receipts: Vec<usize>,
+}
+
+impl ProvenRange {
+    /// Returns the number of proven blocks.
+    pub fn block_count(&self) -> u64 {
+        self.op_tail.number - self.op_head.number
+    }
+}
+
+/// A receipt starting at the number of the tail of a proven range, but at a different
+/// block, i.e. the journals of the receipts do not chain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainConflict {
+    /// The index of the receipt.
+    pub receipt: usize,
+    /// The tail of the proven range.
+    pub expected: BlockId,
+    /// The head of the receipt.
+    pub op_head: BlockId,
+}
+
+/// The result of verifying a set of receipts with [verify_all].
+#[derive(Debug, Default)]
+pub struct Report {
+    /// The segment proven by every receipt, or the reason it is invalid, in the order of
+    /// the receipts.
+    pub segments: Vec<anyhow::Result<ProvenSegment>>,
+    /// The contiguous ranges proven by the valid receipts, ordered by their head.
+    /// Overlapping receipts form separate ranges.
+    pub ranges: Vec<ProvenRange>,
+    /// The valid receipts that conflict with a proven range.
+    pub conflicts: Vec<ChainConflict>,
+}
+
+impl Report {
+    /// Creates the report of the given verification results.
+    pub fn new(segments: Vec<anyhow::Result<ProvenSegment>>) -> Self {
+        let mut valid: Vec<_> = segments
+            .iter()
+            .enumerate()
+            .filter_map(|(index, segment)| Some((index, *segment.as_ref().ok()?)))
+            .collect();
+        valid.sort_by_key(|(_, segment)| (segment.op_head.number, segment.op_tail.number));
+
+        let mut ranges: Vec<ProvenRange> = Vec::new();
+        let mut conflicts = Vec::new();
+        for (index, segment) in valid {
+            if let Some(range) = ranges
+                .iter_mut()
+                .find(|range| range.op_tail == segment.op_head)
+            {
+                range.op_tail = segment.op_tail;
+                range.receipts.push(index);
+                continue;
+            }
+            if let Some(range) = ranges
+                .iter()
+                .find(|range| range.op_tail.number == segment.op_head.number)
+            {
+                conflicts.push(ChainConflict {
+                    receipt: index,
+                    expected: range.op_tail,
+                    op_head: segment.op_head,
+                });
+            }
+            ranges.push(ProvenRange {
+                op_head: segment.op_head,
+                op_tail: segment.op_tail,
+                receipts: vec![index],
+            });
+        }
+        ranges.sort_by_key(|range| range.op_head.number);
+
+        Report {
+            segments,
+            ranges,
+            conflicts,
+        }
+    }
+
+    /// Returns whether all receipts are valid and chain into a single range.
+    pub fn is_contiguous(&self) -> bool {
+        self.segments.iter().all(Result::is_ok) && self.ranges.len() <= 1
+    }
+
+    /// Returns the number of invalid receipts.
+    pub fn invalid_count(&self) -> usize {
+        self.segments
+            .iter()
+            .filter(|segment| segment.is_err())
+            .count()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} of {} receipt(s) verified",
+            self.segments.len() - self.invalid_count(),
+            self.segments.len()
+        )?;
+        for (index, segment) in self.segments.iter().enumerate() {
+            if let Err(err) = segment {
+                writeln!(f, "receipt {}: {:#}", index, err)?;
+            }
+        }
+        for conflict in &self.conflicts {
+            writeln!(
+                f,
+                "receipt {} starts at block {} ({}), but the proven chain has {}",
+                conflict.receipt,
+                conflict.op_head.number,
+                conflict.op_head.hash,
+                conflict.expected.hash
+            )?;
+        }
+        for range in &self.ranges {
+            writeln!(
+                f,
+                "Op blocks {} to {} proven by {} receipt(s)",
+                range.op_head.number + 1,
+                range.op_tail.number,
+                range.receipts.len()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Verifies the derivation and aggregation receipts concurrently and summarizes the
+/// ranges of Optimism blocks they prove. Every receipt must be created by the embedded
+/// guests and refer to the embedded block building guest.
+pub async fn verify_all(receipts: Vec<Receipt>) -> Report {
+    let tasks: Vec<_> = receipts
+        .into_iter()
+        .map(|receipt| tokio::task::spawn_blocking(move || verify_segment(&receipt)))
+        .collect();
+    let mut segments = Vec::with_capacity(tasks.len());
+    for task in tasks {
+        segments.push(task.await.map_err(anyhow::Error::from).and_then(|r| r));
+    }
+
+    Report::new(segments)
+}
+
+/// Verifies a single receipt and returns the segment it proves.
+pub fn verify_segment(receipt: &Receipt) -> anyhow::Result<ProvenSegment> {
+    let image_id = receipt
+        .get_claim()
+        .context("invalid receipt claim")?
+        .pre
+        .digest();
+    let guest = if image_id == Digest::from(OpDerive::ID) {
+        OpDerive::NAME
+    } else if image_id == Digest::from(OpCompose::ID) {
+        OpCompose::NAME
+    } else {
+        bail!("Receipt of unknown image ID {}", image_id);
+    };
+    receipt
+        .verify(image_id)
+        .context(Failure::Verification)
+        .context("receipt verification failed")?;
+
+    let journal = &receipt.journal.bytes;
+    if guest == OpDerive::NAME {
+        let output = DeriveOutput::from_journal(journal).context("invalid journal")?;
+        ensure!(
+            output.block_image_id == OpBlock::ID,
+            "Derivation refers to an unknown block image ID"
+        );
+        Ok(ProvenSegment {
+            guest,
+            op_head: output.op_head,
+            op_tail: output.op_tail(),
+            eth_tail: output.eth_tail,
+        })
+    } else {
+        let output = ComposeOutput::from_journal(journal).context("invalid journal")?;
+        ensure!(
+            output.block_image_id == OpBlock::ID
+                && output.derive_image_id == OpDerive::ID
+                && output.compose_image_id == OpCompose::ID,
+            "Aggregation refers to unknown image IDs"
+        );
+        let ComposeOutputOperation::AGGREGATE {
+            op_head, op_tail, ..
+        } = output.operation
+        else {
+            bail!("Receipt of a composition preparation does not prove any blocks");
+        };
+        Ok(ProvenSegment {
+            guest,
+            op_head,
+            op_tail,
+            eth_tail: output.eth_chain_tail_block,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::anyhow;
+    use zeth_primitives::B256;
+
+    use super::*;
+
+    fn block(number: u64, fork: u8) -> BlockId {
+        BlockId {
+            hash: B256::with_last_byte(fork),
+            number,
+        }
+    }
+
+    fn segment(op_head: BlockId, op_tail: BlockId) -> anyhow::Result<ProvenSegment> {
+        Ok(ProvenSegment {
+            guest: OpDerive::NAME,
+            op_head,
+            op_tail,
+            eth_tail: block(op_tail.number, 0),
+        })
+    }
+
+    #[test]
+    fn contiguous_ranges() {
+        let report = Report::new(vec![
+            segment(block(20, 1), block(30, 1)),
+            segment(block(10, 1), block(20, 1)),
+            Err(anyhow!("invalid journal")),
+            segment(block(40, 1), block(50, 1)),
+        ]);
+        assert_eq!(report.invalid_count(), 1);
+        assert!(!report.is_contiguous());
+        assert!(report.conflicts.is_empty());
+        assert_eq!(
+            report.ranges,
+            [
+                ProvenRange {
+                    op_head: block(10, 1),
+                    op_tail: block(30, 1),
+                    receipts: vec![1, 0],
+                },
+                ProvenRange {
+                    op_head: block(40, 1),
+                    op_tail: block(50, 1),
+                    receipts: vec![3],
+                },
+            ]
+        );
+        assert_eq!(report.ranges[0].block_count(), 20);
+    }
+
+    #[test]
+    fn chain_conflict() {
+        let report = Report::new(vec![
+            segment(block(10, 1), block(20, 1)),
+            segment(block(20, 2), block(30, 2)),
+        ]);
+        assert_eq!(
+            report.conflicts,
+            [ChainConflict {
+                receipt: 1,
+                expected: block(20, 1),
+                op_head: block(20, 2),
+            }]
+        );
+        assert_eq!(report.ranges.len(), 2);
+    }
+}
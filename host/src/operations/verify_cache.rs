@@ -0,0 +1,104 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::{bail, ensure, Context};
+use log::{error, info};
+use zeth_lib::host::{
+    cache_file_path,
+    provider::{file_provider::FileProvider, rpc_provider, verify_cache::verify_cached_blocks},
+};
+
+use crate::{
+    cli::{BuildArgs, Network},
+    operations::cycles::cached_blocks,
+};
+
+/// Refetches a sample of the cached blocks from the RPC nodes and fails if any node
+/// serves block hashes that differ from the cache.
+pub async fn verify_cache(build_args: &BuildArgs, sample_size: usize) -> anyhow::Result<()> {
+    let Some(cache) = &build_args.cache else {
+        bail!("Verifying the cache requires a cache directory");
+    };
+    let block_numbers: Vec<u64> = (build_args.block_number
+        ..build_args.block_number + build_args.block_count as u64)
+        .collect();
+    let targets = match build_args.network {
+        Network::Ethereum => vec![(
+            Network::Ethereum,
+            &build_args.eth_rpc_url,
+            Some(block_numbers),
+        )],
+        Network::Optimism => vec![(
+            Network::Optimism,
+            &build_args.op_rpc_url,
+            Some(block_numbers),
+        )],
+        // the Ethereum blocks used for the derivation are not known in advance
+        Network::OptimismDerived => vec![
+            (Network::Ethereum, &build_args.eth_rpc_url, None),
+            (Network::Optimism, &build_args.op_rpc_url, None),
+        ],
+    };
+
+    let mut inconsistent = vec![];
+    for (network, rpc_url, block_numbers) in targets {
+        let network = network.to_string();
+        let Some(rpc_url) = rpc_url.clone() else {
+            bail!("Verifying the {} cache requires an RPC URL", network);
+        };
+        let block_numbers = match block_numbers {
+            Some(block_numbers) => block_numbers,
+            None => cached_blocks(&cache.join(&network))?,
+        };
+        let files: Vec<_> = block_numbers
+            .into_iter()
+            .map(|block_no| cache_file_path(cache, &network, block_no, "json.gz"))
+            .filter(|file| file.exists())
+            .collect();
+        info!("Verifying {} cached {} file(s) ...", files.len(), network);
+
+        let mismatches = tokio::task::spawn_blocking(move || {
+            let mut rpc = rpc_provider::connect(rpc_url)?;
+            let mut mismatches = 0;
+            for file in files {
+                let cache = FileProvider::new(file.clone())?;
+                for mismatch in verify_cached_blocks(&cache, rpc.as_mut(), sample_size)
+                    .with_context(|| format!("Failed to verify '{}'", file.display()))?
+                {
+                    error!(
+                        "'{}': block {} is cached as {:#x}, but the RPC node serves {:#x}",
+                        file.display(),
+                        mismatch.block_no,
+                        mismatch.cached_hash,
+                        mismatch.rpc_hash
+                    );
+                    mismatches += 1;
+                }
+            }
+            anyhow::Ok(mismatches)
+        })
+        .await??;
+        if mismatches > 0 {
+            inconsistent.push(format!("{} ({} block(s))", network, mismatches));
+        }
+    }
+    ensure!(
+        inconsistent.is_empty(),
+        "RPC providers serve data inconsistent with the cache: {}",
+        inconsistent.join(", ")
+    );
+    info!("Cache is consistent with the RPC providers");
+
+    Ok(())
+}
@@ -0,0 +1,344 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{fmt::Debug, fs, path::Path};
+
+use anyhow::{bail, ensure, Context};
+use ed25519_dalek::{self as ed25519, Signer};
+use ethers_core::types::Transaction as EthersTransaction;
+use log::info;
+use risc0_zkvm::serde::{from_slice, to_vec};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use zeth_lib::{
+    builder::{BlockBuilderStrategy, EthereumStrategy, OptimismStrategy},
+    consts::{ChainSpec, ETH_CHAIN_SPEC, OP_CHAIN_SPEC},
+    host::{cache_file_path, preflight::Preflight, verify::Verifier},
+    input::{BlockBuildInput, BlockWitness},
+    output::BlockBuildOutput,
+};
+use zeth_primitives::{keccak::keccak, transactions::TxEssence, Bytes, B256};
+
+use crate::cli::{Network, WitnessArgs};
+
+/// Version of the witness bundle format.
+///
+/// Version 3 replaced the secp256k1 signatures by ed25519 signatures.
+pub const WITNESS_BUNDLE_VERSION: u32 = 3;
+
+/// A signed, self-contained guest input that can be proven by an untrusted prover.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WitnessBundle {
+    /// Version of the bundle format.
+    pub version: u32,
+    /// Name of the network of the block.
+    pub network: String,
+    /// Number of the block built by the input.
+    pub block_number: u64,
+    /// Keccak hash of the chain specification used for building the block.
+    pub chain_config_hash: B256,
+    /// Keccak hash of the canonical input.
+    pub input_digest: B256,
+    /// Canonical input, encoded exactly as read by the guest.
+    pub input: Bytes,
+    /// Ed25519 public key signing the bundle.
+    pub signer: B256,
+    /// Ed25519 signature over [WitnessBundle::signing_hash].
+    pub signature: Bytes,
+}
+
+impl WitnessBundle {
+    /// Returns the hash committed to by the signature of the bundle.
+    pub fn signing_hash(&self) -> B256 {
+        let mut data = self.version.to_be_bytes().to_vec();
+        data.extend_from_slice(&keccak(self.network.as_bytes()));
+        data.extend_from_slice(&self.block_number.to_be_bytes());
+        data.extend_from_slice(self.chain_config_hash.as_slice());
+        data.extend_from_slice(self.input_digest.as_slice());
+        keccak(data).into()
+    }
+
+    /// Verifies the input digest and the signature of the bundle.
+    pub fn verify(&self) -> anyhow::Result<()> {
+        ensure!(
+            self.input_digest == B256::from(keccak(&self.input)),
+            "Input digest mismatch"
+        );
+        let verifying_key =
+            ed25519::VerifyingKey::from_bytes(&self.signer.0).context("invalid signer key")?;
+        let signature =
+            ed25519::Signature::from_slice(&self.signature).context("invalid signature")?;
+        verifying_key
+            .verify_strict(self.signing_hash().as_slice(), &signature)
+            .with_context(|| format!("Bundle is not signed by {}", self.signer))?;
+
+        Ok(())
+    }
+}
+
+/// Loads the witness bundle of a block from the given file and returns its input, after
+/// checking that it is signed by one of the trusted signers and matches the block and
+/// chain it is supposed to build.
+pub fn load_bundle_input<E: TxEssence + DeserializeOwned>(
+    file_path: &Path,
+    trusted_signers: &[B256],
+    network: &Network,
+    block_number: u64,
+    chain_spec: &ChainSpec,
+) -> anyhow::Result<BlockBuildInput<E>> {
+    let bundle = read_bundle(file_path)?;
+    bundle.verify()?;
+    ensure!(
+        trusted_signers.contains(&bundle.signer),
+        "Bundle signer {} is not trusted",
+        bundle.signer
+    );
+    info!("Witness bundle signed by {}", bundle.signer);
+
+    ensure!(
+        bundle.network == network.to_string(),
+        "Bundle is for the {} network instead of {}",
+        bundle.network,
+        network
+    );
+    ensure!(
+        bundle.block_number == block_number,
+        "Bundle is for block {} instead of {}",
+        bundle.block_number,
+        block_number
+    );
+    ensure!(
+        bundle.chain_config_hash == chain_config_hash(chain_spec)?,
+        "Bundle was created for a different chain specification"
+    );
+
+    decode_bundle_input(&bundle)
+}
+
+/// Reads the witness bundle from the given file, without verifying its signature.
+pub fn read_bundle(file_path: &Path) -> anyhow::Result<WitnessBundle> {
+    let bundle_json = fs::read(file_path)
+        .with_context(|| format!("Failed to read witness bundle '{}'", file_path.display()))?;
+    let bundle: WitnessBundle = serde_json::from_slice(&bundle_json)
+        .with_context(|| format!("Failed to parse witness bundle '{}'", file_path.display()))?;
+    ensure!(
+        bundle.version == WITNESS_BUNDLE_VERSION,
+        "Unsupported witness bundle version {}",
+        bundle.version
+    );
+    Ok(bundle)
+}
+
+/// Decodes the input of the witness bundle.
+pub fn decode_bundle_input<E: TxEssence + DeserializeOwned>(
+    bundle: &WitnessBundle,
+) -> anyhow::Result<BlockBuildInput<E>> {
+    ensure!(
+        bundle.input.len().is_multiple_of(4),
+        "Bundle input is not a sequence of words"
+    );
+    let input_words: Vec<u32> = bundle
+        .input
+        .as_chunks::<4>()
+        .0
+        .iter()
+        .map(|word| u32::from_le_bytes(*word))
+        .collect();
+    let witness: BlockWitness<E> =
+        from_slice(&input_words).context("Failed to decode bundle input")?;
+
+    witness.into_input()
+}
+
+/// Generates, signs and publishes the witness bundles of all blocks in the range.
+pub async fn publish_witnesses(args: &WitnessArgs) -> anyhow::Result<()> {
+    let signing_key = load_bundle_key(&args.signing_key)?;
+    info!(
+        "Signing witness bundles as {}",
+        B256::from(signing_key.verifying_key().to_bytes())
+    );
+
+    for block_number in args.range.clone() {
+        let bundle = match args.network {
+            Network::Ethereum => {
+                create_bundle::<EthereumStrategy>(
+                    args,
+                    args.eth_rpc_url.clone(),
+                    ETH_CHAIN_SPEC,
+                    &signing_key,
+                    block_number,
+                )
+                .await?
+            }
+            Network::Optimism => {
+                create_bundle::<OptimismStrategy>(
+                    args,
+                    args.op_rpc_url.clone(),
+                    OP_CHAIN_SPEC,
+                    &signing_key,
+                    block_number,
+                )
+                .await?
+            }
+            Network::OptimismDerived => {
+                bail!("Witness bundles are only supported for the ethereum and optimism networks")
+            }
+        };
+        publish(&args.publish, &bundle).await?;
+    }
+
+    Ok(())
+}
+
+/// Runs the preflight for a single block and wraps the verified input into a signed
+/// bundle.
+async fn create_bundle<N: BlockBuilderStrategy>(
+    args: &WitnessArgs,
+    rpc_url: Option<String>,
+    chain_spec: &ChainSpec,
+    signing_key: &ed25519::SigningKey,
+    block_number: u64,
+) -> anyhow::Result<WitnessBundle>
+where
+    N::TxEssence: 'static + Send + TryFrom<EthersTransaction> + Serialize + Deserialize<'static>,
+    <N::TxEssence as TryFrom<EthersTransaction>>::Error: Debug,
+{
+    let network = args.network.to_string();
+    let rpc_cache = args
+        .cache
+        .as_ref()
+        .map(|dir| cache_file_path(dir, &network, block_number, "json.gz"));
+
+    info!("Running preflight for block {}", block_number);
+    let init_spec = chain_spec.clone();
+    let preflight_data = tokio::task::spawn_blocking(move || {
+        N::preflight_with_external_data(&init_spec, rpc_cache, rpc_url, block_number)
+    })
+    .await?
+    .context("preflight failed")?;
+    let input: BlockBuildInput<N::TxEssence> = preflight_data
+        .clone()
+        .try_into()
+        .context("invalid preflight data")?;
+
+    // Only publish inputs that actually build the expected block
+    match N::build_from(chain_spec, input.clone()).context("Error while building block")? {
+        BlockBuildOutput::SUCCESS { head, state, .. } => {
+            preflight_data.verify_block(&head, &state)?
+        }
+        BlockBuildOutput::FAILURE { .. } => bail!("Block {} could not be built", block_number),
+    }
+
+    let input_words = to_vec(&BlockWitness::Trie(input)).context("Failed to serialize input")?;
+    let input = Bytes::copy_from_slice(bytemuck::cast_slice(&input_words));
+
+    let mut bundle = WitnessBundle {
+        version: WITNESS_BUNDLE_VERSION,
+        network,
+        block_number,
+        chain_config_hash: chain_config_hash(chain_spec)?,
+        input_digest: keccak(&input).into(),
+        input,
+        signer: signing_key.verifying_key().to_bytes().into(),
+        signature: Bytes::new(),
+    };
+    let signature = signing_key.sign(bundle.signing_hash().as_slice());
+    bundle.signature = Bytes::copy_from_slice(&signature.to_bytes());
+
+    Ok(bundle)
+}
+
+/// Returns the keccak hash of the serialized chain specification.
+fn chain_config_hash(chain_spec: &ChainSpec) -> anyhow::Result<B256> {
+    let chain_config = bincode::serialize(chain_spec).context("Failed to serialize chain spec")?;
+    Ok(keccak(chain_config).into())
+}
+
+/// Writes the bundle into the target directory or posts it to the target URL.
+async fn publish(target: &str, bundle: &WitnessBundle) -> anyhow::Result<()> {
+    if target.starts_with("http://") || target.starts_with("https://") {
+        reqwest::Client::new()
+            .post(target)
+            .json(bundle)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .with_context(|| format!("Failed to publish bundle to {}", target))?;
+        info!(
+            "Published bundle of block {} to {}",
+            bundle.block_number, target
+        );
+    } else {
+        let file_path = cache_file_path(
+            Path::new(target),
+            &bundle.network,
+            bundle.block_number,
+            "json",
+        );
+        fs::write(&file_path, serde_json::to_vec_pretty(bundle)?)
+            .with_context(|| format!("Failed to write '{}'", file_path.display()))?;
+        info!(
+            "Published bundle of block {} to {}",
+            bundle.block_number,
+            file_path.display()
+        );
+    }
+
+    Ok(())
+}
+
+/// Reads a hex-encoded ed25519 secret key signing witness bundles from the given file.
+fn load_bundle_key(file_path: &Path) -> anyhow::Result<ed25519::SigningKey> {
+    let key_hex = fs::read_to_string(file_path)
+        .with_context(|| format!("Failed to read signing key '{}'", file_path.display()))?;
+    let key_bytes = hex::decode(key_hex.trim().trim_start_matches("0x"))
+        .context("signing key is not valid hex")?;
+    let secret_key = key_bytes
+        .as_slice()
+        .try_into()
+        .context("signing key must be 32 bytes")?;
+
+    Ok(ed25519::SigningKey::from_bytes(secret_key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sign_and_verify() {
+        let signing_key = ed25519::SigningKey::from_bytes(&[1; 32]);
+        let input = Bytes::from(vec![1, 2, 3, 4]);
+        let mut bundle = WitnessBundle {
+            version: WITNESS_BUNDLE_VERSION,
+            network: "ethereum".to_string(),
+            block_number: 42,
+            chain_config_hash: B256::repeat_byte(2),
+            input_digest: keccak(&input).into(),
+            input,
+            signer: signing_key.verifying_key().to_bytes().into(),
+            signature: Bytes::new(),
+        };
+        let signature = signing_key.sign(bundle.signing_hash().as_slice());
+        bundle.signature = Bytes::copy_from_slice(&signature.to_bytes());
+        bundle.verify().unwrap();
+
+        let mut tampered = bundle.clone();
+        tampered.block_number = 43;
+        assert!(tampered.verify().is_err());
+
+        let mut tampered = bundle.clone();
+        tampered.input = Bytes::from(vec![1, 2, 3, 5]);
+        assert!(tampered.verify().is_err());
+    }
+}
@@ -0,0 +1,63 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::ensure;
+use zeth_lib::host::{
+    derive_cache,
+    witness_diff::{diff_batcher_dbs, diff_block_witnesses, WitnessDiff},
+};
+use zeth_primitives::transactions::{ethereum::EthereumTxEssence, optimism::OptimismTxEssence};
+
+use crate::{
+    cli::{Network, WitnessDiffArgs},
+    operations::witness::{decode_bundle_input, read_bundle},
+};
+
+/// Prints the structural differences between the witnesses of two cached derive inputs,
+/// or between the state and storage tries of two witness bundles.
+pub fn print_witness_diff(args: &WitnessDiffArgs) -> anyhow::Result<()> {
+    if args.bundles {
+        print!("{}", diff_bundles(args)?);
+        return Ok(());
+    }
+
+    let left = derive_cache::load(&args.left)?;
+    let right = derive_cache::load(&args.right)?;
+    print!("{}", diff_batcher_dbs(&left.db, &right.db));
+    Ok(())
+}
+
+fn diff_bundles(args: &WitnessDiffArgs) -> anyhow::Result<WitnessDiff> {
+    let left = read_bundle(&args.left)?;
+    let right = read_bundle(&args.right)?;
+    ensure!(
+        left.network == right.network,
+        "Bundles are for different networks: {} != {}",
+        left.network,
+        right.network
+    );
+
+    let diff = if left.network == Network::Ethereum.to_string() {
+        diff_block_witnesses(
+            &decode_bundle_input::<EthereumTxEssence>(&left)?,
+            &decode_bundle_input::<EthereumTxEssence>(&right)?,
+        )
+    } else {
+        diff_block_witnesses(
+            &decode_bundle_input::<OptimismTxEssence>(&left)?,
+            &decode_bundle_input::<OptimismTxEssence>(&right)?,
+        )
+    };
+    Ok(diff)
+}
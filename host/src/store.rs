@@ -0,0 +1,272 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::path::Path;
+
+use anyhow::{ensure, Context, Result};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use zeth_lib::{
+    host::{
+        mpt::{mpt_from_proof, parse_proof},
+        provider::ProofQuery,
+        ProviderFactory,
+    },
+    input::BlockBuildInput,
+    optimism::{l1_block_info::L1BlockValues, DeriveOutput},
+    output::BlockBuildOutput,
+};
+use zeth_primitives::{
+    keccak::keccak,
+    transactions::{optimism::OptimismTxEssence, Transaction, TxEssence},
+    trie::StateAccount,
+    Address, B256,
+};
+
+/// Summary of a derived Optimism block.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DerivedBlockRecord {
+    /// Number of the block.
+    pub number: u64,
+    /// Hash of the block.
+    pub hash: B256,
+    /// Number of the L1 origin of the block.
+    pub epoch_number: u64,
+    /// Hash of the L1 origin of the block.
+    pub epoch_hash: B256,
+    /// Number of transactions in the block.
+    pub tx_count: u64,
+    /// Version 0 output root of the block.
+    pub output_root: B256,
+}
+
+/// SQLite database of derived blocks.
+pub struct DerivedBlockStore {
+    conn: Connection,
+}
+
+impl DerivedBlockStore {
+    /// Opens the database at the given path, creating it if necessary.
+    pub fn open(path: &Path) -> Result<Self> {
+        let conn = Connection::open(path)
+            .with_context(|| format!("failed to open database {}", path.display()))?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS derived_blocks (
+                number INTEGER PRIMARY KEY,
+                hash BLOB NOT NULL,
+                epoch_number INTEGER NOT NULL,
+                epoch_hash BLOB NOT NULL,
+                tx_count INTEGER NOT NULL,
+                output_root BLOB NOT NULL
+            )",
+            (),
+        )?;
+        Ok(Self { conn })
+    }
+
+    /// Inserts the record, replacing any existing record of the same block number.
+    pub fn insert(&self, record: &DerivedBlockRecord) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO derived_blocks
+                (number, hash, epoch_number, epoch_hash, tx_count, output_root)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                record.number,
+                record.hash.as_slice(),
+                record.epoch_number,
+                record.epoch_hash.as_slice(),
+                record.tx_count,
+                record.output_root.as_slice(),
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Returns the record of the given block number, if any.
+    pub fn get(&self, number: u64) -> Result<Option<DerivedBlockRecord>> {
+        let row = self
+            .conn
+            .query_row(
+                "SELECT hash, epoch_number, epoch_hash, tx_count, output_root
+                    FROM derived_blocks WHERE number = ?1",
+                params![number],
+                |row| {
+                    Ok((
+                        row.get::<_, Vec<u8>>(0)?,
+                        row.get::<_, u64>(1)?,
+                        row.get::<_, Vec<u8>>(2)?,
+                        row.get::<_, u64>(3)?,
+                        row.get::<_, Vec<u8>>(4)?,
+                    ))
+                },
+            )
+            .optional()?;
+        let Some((hash, epoch_number, epoch_hash, tx_count, output_root)) = row else {
+            return Ok(None);
+        };
+
+        Ok(Some(DerivedBlockRecord {
+            number,
+            hash: B256::try_from(hash.as_slice()).context("invalid hash")?,
+            epoch_number,
+            epoch_hash: B256::try_from(epoch_hash.as_slice()).context("invalid epoch hash")?,
+            tx_count,
+            output_root: B256::try_from(output_root.as_slice()).context("invalid output root")?,
+        }))
+    }
+}
+
+/// Creates the records of all derived blocks from the inputs and outputs of the block
+/// builder. The storage root of the given message passer is read from the state root of
+/// the built block, using an account proof fetched from the provider.
+pub fn derived_block_records(
+    provider_factory: &ProviderFactory,
+    message_passer: Address,
+    op_block_inputs: &[BlockBuildInput<OptimismTxEssence>],
+    op_block_outputs: &[BlockBuildOutput],
+    derive_output: &DeriveOutput,
+) -> Result<Vec<DerivedBlockRecord>> {
+    let mut records = Vec::new();
+    for (input, output) in op_block_inputs.iter().zip(op_block_outputs) {
+        // only successfully built blocks that are part of the derived chain are recorded
+        let BlockBuildOutput::SUCCESS { hash, head, .. } = output else {
+            continue;
+        };
+        if !derive_output
+            .derived_op_blocks
+            .iter()
+            .any(|block| block.hash == *hash)
+        {
+            continue;
+        }
+
+        let l1_info = l1_block_values(input)?;
+
+        let mut provider = provider_factory.create_provider(head.number)?;
+        let proof = provider
+            .get_proof(&ProofQuery {
+                block_no: head.number,
+                address: message_passer.into_array().into(),
+                indices: Default::default(),
+            })
+            .context("failed to fetch the message passer account proof")?;
+        provider.save()?;
+        let message_passer_storage_root =
+            proven_storage_root(head.state_root, message_passer, &proof.account_proof)?;
+
+        records.push(DerivedBlockRecord {
+            number: head.number,
+            hash: *hash,
+            epoch_number: l1_info.number(),
+            epoch_hash: l1_info.hash(),
+            tx_count: input.state_input.transactions.len() as u64,
+            output_root: output_root(head.state_root, message_passer_storage_root, *hash),
+        });
+    }
+    Ok(records)
+}
+
+/// Returns the storage root of the given account in the state with the given root. The
+/// account proof is only used to resolve the account, so that the result never depends on
+/// the provider.
+fn proven_storage_root(
+    state_root: B256,
+    address: Address,
+    account_proof: &[impl AsRef<[u8]>],
+) -> Result<B256> {
+    let proof_nodes = parse_proof(account_proof).context("invalid account proof encoding")?;
+    let proof_trie = mpt_from_proof(&proof_nodes).context("invalid account proof")?;
+    ensure!(
+        proof_trie.hash() == state_root,
+        "account proof of {} does not match the state root {}",
+        address,
+        state_root
+    );
+    let account = proof_trie
+        .get_rlp::<StateAccount>(&keccak(address))
+        .context("incomplete account proof")?
+        .unwrap_or_default();
+    Ok(account.storage_root)
+}
+
+/// Decodes the L1 block attributes set by the first transaction of the block.
+pub(crate) fn l1_block_values(input: &BlockBuildInput<OptimismTxEssence>) -> Result<L1BlockValues> {
+    l1_attributes(&input.state_input.transactions)
+}
+
+/// Decodes the L1 block attributes set by the first of the given block transactions.
+pub(crate) fn l1_attributes(
+    transactions: &[Transaction<OptimismTxEssence>],
+) -> Result<L1BlockValues> {
+    // the first transaction is the L1 attributes deposited transaction
+    let l1_attributes_tx = transactions.first().context("block is empty")?;
+    L1BlockValues::decode(l1_attributes_tx.essence.data())
+}
+
+/// Computes the version 0 output root.
+fn output_root(state_root: B256, message_passer_storage_root: B256, block_hash: B256) -> B256 {
+    keccak(
+        [
+            B256::ZERO.0,
+            state_root.0,
+            message_passer_storage_root.0,
+            block_hash.0,
+        ]
+        .concat(),
+    )
+    .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use zeth_primitives::{alloy_rlp, trie::MptNode};
+
+    use super::*;
+
+    #[test]
+    fn store_roundtrip() {
+        let store = DerivedBlockStore::open(Path::new(":memory:")).unwrap();
+        let record = DerivedBlockRecord {
+            number: 1,
+            hash: B256::repeat_byte(0x01),
+            epoch_number: 2,
+            epoch_hash: B256::repeat_byte(0x02),
+            tx_count: 3,
+            output_root: B256::repeat_byte(0x03),
+        };
+        store.insert(&record).unwrap();
+
+        assert_eq!(store.get(1).unwrap(), Some(record));
+        assert_eq!(store.get(2).unwrap(), None);
+    }
+
+    #[test]
+    fn storage_root_from_state() {
+        let address = Address::repeat_byte(0x42);
+        let account = StateAccount {
+            storage_root: B256::repeat_byte(0x04),
+            ..Default::default()
+        };
+        let mut state_trie = MptNode::default();
+        state_trie.insert_rlp(&keccak(address), account).unwrap();
+        let account_proof = vec![alloy_rlp::encode(&state_trie)];
+
+        assert_eq!(
+            proven_storage_root(state_trie.hash(), address, &account_proof).unwrap(),
+            B256::repeat_byte(0x04)
+        );
+        // a proof of a different state is rejected
+        assert!(proven_storage_root(B256::ZERO, address, &account_proof).is_err());
+    }
+}
@@ -92,3 +92,30 @@ fn build_optimism_derived(#[case] block_number: u64, #[case] block_count: u64) {
         .success()
         .stderr(predicate::str::contains(" WARN ").not());
 }
+
+#[test]
+fn corpus_manifest() {
+    Command::cargo_bin("zeth")
+        .unwrap()
+        .env("RUST_LOG", "info")
+        .args(["corpus", "--cache=testdata", "--check"])
+        .assert()
+        .success();
+}
+
+#[test]
+fn json_errors() {
+    // block 1 is not part of the cached test data
+    Command::cargo_bin("zeth")
+        .unwrap()
+        .args([
+            "build",
+            "--network=ethereum",
+            "--cache=testdata",
+            "--block-number=1",
+            "--json-errors",
+        ])
+        .assert()
+        .code(10)
+        .stdout(predicate::str::contains(r#""category":"witness""#));
+}
@@ -0,0 +1,51 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{env, fs, path::PathBuf};
+
+/// Directory of the op-node `rollup.json` files embedded into the chain registry,
+/// relative to the manifest. It can be replaced with the `ZETH_CHAINS_DIR` environment
+/// variable, e.g. with the configs exported from the superchain registry.
+const CHAINS_DIR: &str = "chains";
+
+fn main() {
+    println!("cargo:rerun-if-env-changed=ZETH_CHAINS_DIR");
+    let chains_dir = match env::var_os("ZETH_CHAINS_DIR") {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap()).join(CHAINS_DIR),
+    };
+    println!("cargo:rerun-if-changed={}", chains_dir.display());
+
+    // every `<name>.json` file is embedded as the chain `<name>`
+    let mut chains = Vec::new();
+    if let Ok(entries) = fs::read_dir(&chains_dir) {
+        for entry in entries {
+            let path = entry.unwrap().path();
+            if path.extension().is_some_and(|ext| ext == "json") {
+                let name = path.file_stem().unwrap().to_str().unwrap().to_string();
+                chains.push((name, fs::canonicalize(&path).unwrap()));
+            }
+        }
+    }
+    chains.sort();
+
+    let mut code = String::from("pub(crate) const EMBEDDED_CHAINS: &[(&str, &str)] = &[\n");
+    for (name, path) in chains {
+        code += &format!("    ({:?}, include_str!({:?})),\n", name, path);
+    }
+    code += "];\n";
+
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+    fs::write(out_dir.join("chains.rs"), code).unwrap();
+}
@@ -0,0 +1,201 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Abort codes of failed guest runs.
+//!
+//! Instead of panicking, a guest halts with the exit code of the category of its error,
+//! without committing a journal. The host maps the exit code of the session back to the
+//! [AbortCode], so that failed runs report an actionable category.
+
+#[cfg(target_os = "zkvm")]
+use risc0_zkvm::guest::env;
+use thiserror::Error as ThisError;
+use zeth_primitives::trie::{flat, mpt};
+
+/// The categories of guest failures, with their stable exit codes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ThisError)]
+#[repr(u8)]
+pub enum AbortCode {
+    /// The input of the guest is inconsistent, e.g. an invalid chain configuration.
+    #[error("invalid guest input")]
+    InvalidInput = 1,
+    /// A block required by the guest is not contained in the witness.
+    #[error("block missing from the witness")]
+    WitnessMissingBlock = 2,
+    /// An account, storage slot or trie node required by the guest is not contained in
+    /// the witness.
+    #[error("state missing from the witness")]
+    WitnessMissingState = 3,
+    /// A trie of the witness is malformed or does not match its root.
+    #[error("invalid trie in the witness")]
+    WitnessInvalidTrie = 4,
+    /// A block could not be built from its transactions.
+    #[error("block execution failed")]
+    BlockExecution = 5,
+    /// The Optimism blocks could not be derived from the Ethereum blocks.
+    #[error("derivation failed")]
+    Derivation = 6,
+    /// The receipts to compose do not form a valid composition.
+    #[error("composition failed")]
+    Composition = 7,
+    /// The unsafe payloads could not be attested.
+    #[error("unsafe payload attestation failed")]
+    UnsafeAttestation = 8,
+}
+
+impl AbortCode {
+    const ALL: [AbortCode; 8] = [
+        AbortCode::InvalidInput,
+        AbortCode::WitnessMissingBlock,
+        AbortCode::WitnessMissingState,
+        AbortCode::WitnessInvalidTrie,
+        AbortCode::BlockExecution,
+        AbortCode::Derivation,
+        AbortCode::Composition,
+        AbortCode::UnsafeAttestation,
+    ];
+
+    /// Returns the exit code the guest halts with.
+    pub fn exit_code(&self) -> u8 {
+        *self as u8
+    }
+
+    /// Returns the abort code of the given exit code of a guest, if any.
+    pub fn from_exit_code(exit_code: u32) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|code| u32::from(code.exit_code()) == exit_code)
+    }
+
+    /// Returns the category of the error, falling back to `default` if the error does not
+    /// originate from the witness.
+    pub fn classify(err: &anyhow::Error, default: AbortCode) -> Self {
+        for cause in err.chain() {
+            #[cfg(any(not(target_os = "zkvm"), feature = "optimism"))]
+            if cause.is::<crate::optimism::batcher_db::EthGapError>()
+                || matches!(
+                    cause.downcast_ref(),
+                    Some(crate::optimism::DeriveError::MissingBlockOutput(_))
+                )
+            {
+                return AbortCode::WitnessMissingBlock;
+            }
+            #[cfg(any(not(target_os = "zkvm"), feature = "executor"))]
+            if let Some(err) = cause.downcast_ref::<crate::mem_db::DbError>() {
+                match err {
+                    crate::mem_db::DbError::BlockNotFound(_) => {
+                        return AbortCode::WitnessMissingBlock
+                    }
+                    crate::mem_db::DbError::AccountNotFound(_)
+                    | crate::mem_db::DbError::SlotNotFound(..)
+                    | crate::mem_db::DbError::CodeNotFound(_) => {
+                        return AbortCode::WitnessMissingState
+                    }
+                    crate::mem_db::DbError::Unspecified(_) => {}
+                }
+            }
+            if let Some(err) = cause.downcast_ref::<mpt::Error>() {
+                return match err {
+                    mpt::Error::NodeNotResolved(_) => AbortCode::WitnessMissingState,
+                    _ => AbortCode::WitnessInvalidTrie,
+                };
+            }
+            if cause.is::<flat::Error>() {
+                return AbortCode::WitnessInvalidTrie;
+            }
+        }
+        default
+    }
+}
+
+/// Aborts the guest with the category of the error, logging the error itself. Outside of
+/// the zkVM, it panics instead.
+pub fn abort(code: AbortCode, err: &anyhow::Error) -> ! {
+    #[cfg(target_os = "zkvm")]
+    {
+        env::log(&format!("{}: {:#}", code, err));
+        env::exit(code.exit_code())
+    }
+    #[cfg(not(target_os = "zkvm"))]
+    panic!("{}: {:#}", code, err)
+}
+
+/// Unwraps a result, aborting the guest on errors.
+pub trait OrAbort<T> {
+    /// Returns the value, or aborts with the category of the error, using `default` for
+    /// errors that do not originate from the witness.
+    fn or_abort(self, default: AbortCode) -> T;
+}
+
+impl<T, E: Into<anyhow::Error>> OrAbort<T> for Result<T, E> {
+    fn or_abort(self, default: AbortCode) -> T {
+        match self {
+            Ok(value) => value,
+            Err(err) => {
+                let err = err.into();
+                abort(AbortCode::classify(&err, default), &err)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::{anyhow, Context};
+    use zeth_primitives::U256;
+
+    use super::*;
+    use crate::{mem_db::DbError, optimism::batcher_db::EthGapError};
+
+    #[test]
+    fn exit_codes() {
+        for code in AbortCode::ALL {
+            assert_eq!(
+                AbortCode::from_exit_code(code.exit_code().into()),
+                Some(code)
+            );
+        }
+        assert_eq!(AbortCode::from_exit_code(0), None);
+        assert_eq!(AbortCode::from_exit_code(256 + 2), None);
+    }
+
+    #[test]
+    fn classify() {
+        let err = Err::<(), _>(EthGapError::MissingBlock(1))
+            .context("block not found")
+            .unwrap_err();
+        assert_eq!(
+            AbortCode::classify(&err, AbortCode::Derivation),
+            AbortCode::WitnessMissingBlock
+        );
+
+        let err = anyhow::Error::new(DbError::SlotNotFound(Default::default(), U256::from(1)));
+        assert_eq!(
+            AbortCode::classify(&err, AbortCode::BlockExecution),
+            AbortCode::WitnessMissingState
+        );
+
+        let err = anyhow::Error::new(flat::Error::Unsorted).context("invalid witness");
+        assert_eq!(
+            AbortCode::classify(&err, AbortCode::InvalidInput),
+            AbortCode::WitnessInvalidTrie
+        );
+
+        let err = anyhow!("invalid batch");
+        assert_eq!(
+            AbortCode::classify(&err, AbortCode::Derivation),
+            AbortCode::Derivation
+        );
+    }
+}
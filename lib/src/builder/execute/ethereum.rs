@@ -25,7 +25,8 @@ use revm::{
 use ruint::aliases::U256;
 use zeth_primitives::{
     alloy_rlp,
-    receipt::Receipt,
+    receipt::{validate_cumulative_gas_used, Receipt},
+    rlp::encode_list_with_lengths,
     transactions::{
         ethereum::{EthereumTxEssence, TransactionKind},
         TxEssence,
@@ -119,10 +120,11 @@ impl TxExecStrategy<EthereumTxEssence> for EthTxExecStrategy {
         // process all the transactions
         let mut tx_trie = MptNode::default();
         let mut receipt_trie = MptNode::default();
-        for (tx_no, tx) in take(&mut block_builder.input.state_input.transactions)
-            .into_iter()
-            .enumerate()
-        {
+        let mut receipts = Vec::new();
+        // encode every transaction only once
+        let transactions = take(&mut block_builder.input.state_input.transactions);
+        let encoded_txs = encode_list_with_lengths(&transactions).into_items();
+        for (tx_no, (tx, tx_rlp)) in transactions.into_iter().zip(encoded_txs).enumerate() {
             // verify the transaction signature
             let tx_from = tx
                 .recover_from()
@@ -172,13 +174,14 @@ impl TxExecStrategy<EthereumTxEssence> for EthTxExecStrategy {
             // Add receipt and tx to tries
             let trie_key = alloy_rlp::encode(tx_no);
             tx_trie
-                .insert_rlp(&trie_key, tx)
+                .insert(&trie_key, tx_rlp)
                 // todo: change unrecoverable panic to host-side recoverable `Result`
                 .expect("failed to insert transaction");
             receipt_trie
-                .insert_rlp(&trie_key, receipt)
+                .insert_rlp(&trie_key, &receipt)
                 // todo: change unrecoverable panic to host-side recoverable `Result`
                 .expect("failed to insert receipt");
+            receipts.push(receipt);
 
             // update account states
             #[cfg(not(target_os = "zkvm"))]
@@ -242,6 +245,10 @@ impl TxExecStrategy<EthereumTxEssence> for EthTxExecStrategy {
                 .expect("failed to insert withdrawal");
         }
 
+        // The receipts must account for all the gas used by the block
+        validate_cumulative_gas_used(&receipts, cumulative_gas_used)
+            .context("invalid block receipts")?;
+
         // Update result header with computed values
         header.transactions_root = tx_trie.hash();
         header.receipts_root = receipt_trie.hash();
@@ -255,6 +262,7 @@ impl TxExecStrategy<EthereumTxEssence> for EthTxExecStrategy {
 
         // Leak memory, save cycles
         guest_mem_forget([tx_trie, receipt_trie, withdrawals_trie]);
+        guest_mem_forget(receipts);
         // Return block builder with updated database
         Ok(block_builder.with_db(evm.context.evm.db))
     }
@@ -277,6 +285,8 @@ pub fn fill_eth_tx_env(tx_env: &mut TxEnv, essence: &EthereumTxEssence, caller:
             tx_env.chain_id = tx.chain_id;
             tx_env.nonce = Some(tx.nonce);
             tx_env.access_list.clear();
+            tx_env.blob_hashes.clear();
+            tx_env.max_fee_per_blob_gas = None;
         }
         EthereumTxEssence::Eip2930(tx) => {
             tx_env.caller = caller;
@@ -293,6 +303,8 @@ pub fn fill_eth_tx_env(tx_env: &mut TxEnv, essence: &EthereumTxEssence, caller:
             tx_env.chain_id = Some(tx.chain_id);
             tx_env.nonce = Some(tx.nonce);
             tx_env.access_list = tx.access_list.clone().into();
+            tx_env.blob_hashes.clear();
+            tx_env.max_fee_per_blob_gas = None;
         }
         EthereumTxEssence::Eip1559(tx) => {
             tx_env.caller = caller;
@@ -309,6 +321,22 @@ pub fn fill_eth_tx_env(tx_env: &mut TxEnv, essence: &EthereumTxEssence, caller:
             tx_env.chain_id = Some(tx.chain_id);
             tx_env.nonce = Some(tx.nonce);
             tx_env.access_list = tx.access_list.clone().into();
+            tx_env.blob_hashes.clear();
+            tx_env.max_fee_per_blob_gas = None;
+        }
+        EthereumTxEssence::Eip4844(tx) => {
+            tx_env.caller = caller;
+            tx_env.gas_limit = tx.gas_limit.try_into().unwrap();
+            tx_env.gas_price = tx.max_fee_per_gas;
+            tx_env.gas_priority_fee = Some(tx.max_priority_fee_per_gas);
+            tx_env.transact_to = TransactTo::Call(tx.to);
+            tx_env.value = tx.value;
+            tx_env.data = tx.data.clone();
+            tx_env.chain_id = Some(tx.chain_id);
+            tx_env.nonce = Some(tx.nonce);
+            tx_env.access_list = tx.access_list.clone().into();
+            tx_env.blob_hashes = tx.blob_versioned_hashes.clone();
+            tx_env.max_fee_per_blob_gas = Some(tx.max_fee_per_blob_gas);
         }
     };
 }
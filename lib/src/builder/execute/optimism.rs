@@ -25,7 +25,8 @@ use revm::{
 use ruint::aliases::U256;
 use zeth_primitives::{
     alloy_rlp,
-    receipt::Receipt,
+    receipt::{validate_cumulative_gas_used, Receipt},
+    rlp::encode_list_with_lengths,
     transactions::{
         ethereum::{EthereumTxEssence, TransactionKind},
         optimism::{OptimismTxEssence, TxEssenceOptimismDeposited},
@@ -36,7 +37,11 @@ use zeth_primitives::{
 };
 
 use super::{ethereum, TxExecStrategy};
-use crate::{builder::BlockBuilder, consts, guest_mem_forget};
+use crate::{
+    builder::BlockBuilder,
+    consts::{self, ForkCondition},
+    guest_mem_forget,
+};
 
 pub struct OpTxExecStrategy {}
 
@@ -88,6 +93,16 @@ impl TxExecStrategy<OptimismTxEssence> for OpTxExecStrategy {
             );
         }
 
+        // The Canyon activation block also deploys the Create2Deployer contract outside of
+        // any transaction, which is not supported
+        if let Some(ForkCondition::Timestamp(canyon_time)) =
+            block_builder.chain_spec.hard_forks().get(&SpecId::CANYON)
+        {
+            if header.timestamp == U256::from(*canyon_time) {
+                bail!("Building the Canyon activation block is not supported");
+            }
+        }
+
         let chain_id = block_builder.chain_spec.chain_id();
         let mut evm = Evm::builder()
             .with_db(block_builder.db.take().unwrap())
@@ -117,10 +132,11 @@ impl TxExecStrategy<OptimismTxEssence> for OpTxExecStrategy {
         // process all the transactions
         let mut tx_trie = MptNode::default();
         let mut receipt_trie = MptNode::default();
-        for (tx_no, tx) in take(&mut block_builder.input.state_input.transactions)
-            .into_iter()
-            .enumerate()
-        {
+        let mut receipts = Vec::new();
+        // encode every transaction only once
+        let transactions = take(&mut block_builder.input.state_input.transactions);
+        let encoded_txs = encode_list_with_lengths(&transactions).into_items();
+        for (tx_no, (tx, tx_rlp)) in transactions.into_iter().zip(encoded_txs).enumerate() {
             // verify the transaction signature
             let tx_from = tx
                 .recover_from()
@@ -164,12 +180,7 @@ impl TxExecStrategy<OptimismTxEssence> for OpTxExecStrategy {
                     fill_deposit_tx_env(&mut evm.env_mut().tx, deposit, tx_from);
                 }
                 OptimismTxEssence::Ethereum(essence) => {
-                    fill_eth_tx_env(
-                        &mut evm.env_mut().tx,
-                        alloy_rlp::encode(&tx),
-                        essence,
-                        tx_from,
-                    );
+                    fill_eth_tx_env(&mut evm.env_mut().tx, tx_rlp.clone(), essence, tx_from);
                 }
             };
 
@@ -235,15 +246,20 @@ impl TxExecStrategy<OptimismTxEssence> for OpTxExecStrategy {
             // Add receipt and tx to tries
             let trie_key = alloy_rlp::encode(tx_no);
             tx_trie
-                .insert_rlp(&trie_key, tx)
+                .insert(&trie_key, tx_rlp)
                 // todo: change unrecoverable panic to host-side recoverable `Result`
                 .expect("failed to insert transaction");
             receipt_trie
-                .insert_rlp(&trie_key, receipt)
+                .insert_rlp(&trie_key, &receipt)
                 // todo: change unrecoverable panic to host-side recoverable `Result`
                 .expect("failed to insert receipt");
+            receipts.push(receipt);
         }
 
+        // The receipts must account for all the gas used by the block
+        validate_cumulative_gas_used(&receipts, cumulative_gas_used)
+            .context("invalid block receipts")?;
+
         // Update result header with computed values
         header.transactions_root = tx_trie.hash();
         header.receipts_root = receipt_trie.hash();
@@ -257,6 +273,7 @@ impl TxExecStrategy<OptimismTxEssence> for OpTxExecStrategy {
 
         // Leak memory, save cycles
         guest_mem_forget([tx_trie, receipt_trie]);
+        guest_mem_forget(receipts);
         // Return block builder with updated database
         Ok(block_builder.with_db(evm.context.evm.db))
     }
@@ -15,18 +15,20 @@
 use core::mem;
 
 use anyhow::Result;
+use hashbrown::HashMap;
 use revm::{Database, DatabaseCommit};
 use zeth_primitives::{
     block::Header,
     keccak::keccak,
     transactions::TxEssence,
     trie::{MptNode, StateAccount},
-    U256,
+    Address, U256,
 };
 
 use crate::{
     builder::BlockBuilder,
     guest_mem_forget,
+    input::StorageEntry,
     mem_db::{AccountState, MemDb},
 };
 
@@ -50,62 +52,83 @@ impl BlockFinalizeStrategy<MemDb> for MemDbBlockFinalizeStrategy {
 
         // apply state updates
         let mut state_trie = mem::take(&mut block_builder.input.parent_state_trie);
-        for (address, account) in &db.accounts {
-            // if the account has not been touched, it can be ignored
-            if account.state == AccountState::None {
-                continue;
-            }
+        apply_state_updates(
+            &db,
+            &mut state_trie,
+            &mut block_builder.input.parent_storage,
+        )?;
 
-            // compute the index of the current account in the state trie
-            let state_trie_index = keccak(address);
+        // update result header with the new state root
+        let mut header = block_builder.header.take().expect("Header not initialized");
+        header.state_root = state_trie.hash();
 
-            // remove deleted accounts from the state trie
-            if account.state == AccountState::Deleted {
-                state_trie.delete(&state_trie_index)?;
-                continue;
-            }
+        // Leak memory, save cycles
+        guest_mem_forget(block_builder);
 
-            // otherwise, compute the updated storage root for that account
-            let state_storage = &account.storage;
-            let storage_root = {
-                // getting a mutable reference is more efficient than calling remove
-                // every account must have an entry, even newly created accounts
-                let (storage_trie, _) =
-                    block_builder.input.parent_storage.get_mut(address).unwrap();
-                // for cleared accounts always start from the empty trie
-                if account.state == AccountState::StorageCleared {
-                    storage_trie.clear();
-                }
+        Ok((header, state_trie))
+    }
+}
 
-                // apply all new storage entries for the current account (address)
-                for (key, value) in state_storage {
-                    let storage_trie_index = keccak(key.to_be_bytes::<32>());
-                    if value == &U256::ZERO {
-                        storage_trie.delete(&storage_trie_index)?;
-                    } else {
-                        storage_trie.insert_rlp(&storage_trie_index, *value)?;
-                    }
-                }
+/// Applies the changes of the accounts touched in the given database to the state trie
+/// and to the storage tries.
+///
+/// Every touched account must have an entry in the storage tries, even newly created
+/// accounts.
+pub(crate) fn apply_state_updates(
+    db: &MemDb,
+    state_trie: &mut MptNode,
+    storage_tries: &mut HashMap<Address, StorageEntry>,
+) -> Result<()> {
+    for (address, account) in &db.accounts {
+        // if the account has not been touched, it can be ignored
+        if account.state == AccountState::None {
+            continue;
+        }
 
-                storage_trie.hash()
-            };
+        // compute the index of the current account in the state trie
+        let state_trie_index = keccak(address);
 
-            let state_account = StateAccount {
-                nonce: account.info.nonce,
-                balance: account.info.balance,
-                storage_root,
-                code_hash: account.info.code_hash,
-            };
-            state_trie.insert_rlp(&state_trie_index, state_account)?;
+        // getting a mutable reference is more efficient than calling remove
+        // every account must have an entry, even newly created accounts
+        let (storage_trie, _) = storage_tries.get_mut(address).unwrap();
+
+        // remove deleted accounts from the state trie
+        if account.state == AccountState::Deleted {
+            state_trie.delete(&state_trie_index)?;
+            // the storage of a deleted account is empty, should it be accessed again
+            storage_trie.clear();
+            continue;
         }
 
-        // update result header with the new state root
-        let mut header = block_builder.header.take().expect("Header not initialized");
-        header.state_root = state_trie.hash();
+        // otherwise, compute the updated storage root for that account
+        let state_storage = &account.storage;
+        let storage_root = {
+            // for cleared accounts always start from the empty trie
+            if account.state == AccountState::StorageCleared {
+                storage_trie.clear();
+            }
 
-        // Leak memory, save cycles
-        guest_mem_forget(block_builder);
+            // apply all new storage entries for the current account (address)
+            for (key, value) in state_storage {
+                let storage_trie_index = keccak(key.to_be_bytes::<32>());
+                if value == &U256::ZERO {
+                    storage_trie.delete(&storage_trie_index)?;
+                } else {
+                    storage_trie.insert_rlp(&storage_trie_index, *value)?;
+                }
+            }
 
-        Ok((header, state_trie))
+            storage_trie.hash()
+        };
+
+        let state_account = StateAccount {
+            nonce: account.info.nonce,
+            balance: account.info.balance,
+            storage_root,
+            code_hash: account.info.code_hash,
+        };
+        state_trie.insert_rlp(&state_trie_index, state_account)?;
     }
+
+    Ok(())
 }
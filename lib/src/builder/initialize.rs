@@ -21,17 +21,19 @@ use revm::{
     Database, DatabaseCommit,
 };
 use zeth_primitives::{
+    block::Header,
     keccak::{keccak, KECCAK_EMPTY},
     transactions::TxEssence,
-    trie::StateAccount,
-    Bytes,
+    trie::{MptNode, StateAccount},
+    Address, Bytes,
 };
 
 use crate::{
     builder::BlockBuilder,
     consts::MAX_BLOCK_HASH_AGE,
     guest_mem_forget,
-    mem_db::{AccountState, DbAccount, MemDb},
+    input::StorageEntry,
+    mem_db::{AccountState, DbAccount, DbError, MemDb},
 };
 
 pub trait DbInitStrategy<D>
@@ -62,96 +64,21 @@ impl DbInitStrategy<MemDb> for MemDbInitStrategy {
         }
 
         // hash all the contract code
-        let contracts: HashMap<B256, Bytes> = mem::take(&mut block_builder.input.contracts)
-            .into_iter()
-            .map(|bytes| (keccak(&bytes).into(), bytes))
-            .collect();
+        let contracts = hash_contracts(mem::take(&mut block_builder.input.contracts));
 
         // Load account data into db
-        let mut accounts = HashMap::with_capacity(block_builder.input.parent_storage.len());
-        for (address, (storage_trie, slots)) in &mut block_builder.input.parent_storage {
-            // consume the slots, as they are no longer needed afterwards
-            let slots = mem::take(slots);
-
-            // load the account from the state trie or empty if it does not exist
-            let state_account = block_builder
-                .input
-                .parent_state_trie
-                .get_rlp::<StateAccount>(&keccak(address))?
-                .unwrap_or_default();
-            // Verify storage trie root
-            if storage_trie.hash() != state_account.storage_root {
-                bail!(
-                    "Invalid storage trie for {:?}: expected {}, got {}",
-                    address,
-                    state_account.storage_root,
-                    storage_trie.hash()
-                );
-            }
-
-            // load the corresponding code
-            let code_hash = state_account.code_hash;
-            let bytecode = if code_hash.0 == KECCAK_EMPTY.0 {
-                Bytecode::new()
-            } else {
-                let bytes = contracts.get(&code_hash).unwrap().clone();
-                Bytecode::new_raw(bytes)
-            };
-
-            // load storage reads
-            let mut storage = HashMap::with_capacity(slots.len());
-            for slot in slots {
-                let value: zeth_primitives::U256 = storage_trie
-                    .get_rlp(&keccak(slot.to_be_bytes::<32>()))?
-                    .unwrap_or_default();
-                storage.insert(slot, value);
-            }
-
-            let mem_account = DbAccount {
-                info: AccountInfo {
-                    balance: state_account.balance,
-                    nonce: state_account.nonce,
-                    code_hash: state_account.code_hash,
-                    code: Some(bytecode),
-                },
-                state: AccountState::None,
-                storage,
-            };
-
-            accounts.insert(*address, mem_account);
-        }
+        let accounts = load_accounts(
+            &block_builder.input.parent_state_trie,
+            &block_builder.input.parent_storage,
+            &contracts,
+        )?;
         guest_mem_forget(contracts);
 
         // prepare block hash history
-        let mut block_hashes =
-            HashMap::with_capacity(block_builder.input.ancestor_headers.len() + 1);
-        block_hashes.insert(
-            block_builder.input.state_input.parent_header.number,
-            block_builder.input.state_input.parent_header.hash(),
-        );
-        let mut prev = &block_builder.input.state_input.parent_header;
-        for current in &block_builder.input.ancestor_headers {
-            let current_hash = current.hash();
-            if prev.parent_hash != current_hash {
-                bail!(
-                    "Invalid chain: {} is not the parent of {}",
-                    current.number,
-                    prev.number
-                );
-            }
-            if block_builder.input.state_input.parent_header.number < current.number
-                || block_builder.input.state_input.parent_header.number - current.number
-                    >= MAX_BLOCK_HASH_AGE
-            {
-                bail!(
-                    "Invalid chain: {} is not one of the {} most recent blocks",
-                    current.number,
-                    MAX_BLOCK_HASH_AGE,
-                );
-            }
-            block_hashes.insert(current.number, current_hash);
-            prev = current;
-        }
+        let block_hashes = block_hash_history(
+            &block_builder.input.state_input.parent_header,
+            &block_builder.input.ancestor_headers,
+        )?;
 
         // Store database
         Ok(block_builder.with_db(MemDb {
@@ -160,3 +87,178 @@ impl DbInitStrategy<MemDb> for MemDbInitStrategy {
         }))
     }
 }
+
+/// Returns the given contract code indexed by its hash.
+pub(crate) fn hash_contracts(contracts: Vec<Bytes>) -> HashMap<B256, Bytes> {
+    contracts
+        .into_iter()
+        .map(|bytes| (keccak(&bytes).into(), bytes))
+        .collect()
+}
+
+/// Loads the accounts of the given storage entries and their used storage slots from the
+/// state trie and the storage tries.
+pub(crate) fn load_accounts(
+    state_trie: &MptNode,
+    parent_storage: &HashMap<Address, StorageEntry>,
+    contracts: &HashMap<B256, Bytes>,
+) -> Result<HashMap<Address, DbAccount>> {
+    let mut accounts = HashMap::with_capacity(parent_storage.len());
+    for (address, (storage_trie, slots)) in parent_storage {
+        // load the account from the state trie or empty if it does not exist
+        let state_account = state_trie
+            .get_rlp::<StateAccount>(&keccak(address))?
+            .unwrap_or_default();
+        // Verify storage trie root
+        if storage_trie.hash() != state_account.storage_root {
+            bail!(
+                "Invalid storage trie for {:?}: expected {}, got {}",
+                address,
+                state_account.storage_root,
+                storage_trie.hash()
+            );
+        }
+
+        // load the corresponding code
+        let code_hash = state_account.code_hash;
+        let bytecode = if code_hash.0 == KECCAK_EMPTY.0 {
+            Bytecode::new()
+        } else {
+            let bytes = contracts
+                .get(&code_hash)
+                .ok_or(DbError::CodeNotFound(code_hash))?
+                .clone();
+            Bytecode::new_raw(bytes)
+        };
+
+        // load storage reads
+        let mut storage = HashMap::with_capacity(slots.len());
+        for slot in slots {
+            let value: zeth_primitives::U256 = storage_trie
+                .get_rlp(&keccak(slot.to_be_bytes::<32>()))?
+                .unwrap_or_default();
+            storage.insert(*slot, value);
+        }
+
+        let mem_account = DbAccount {
+            info: AccountInfo {
+                balance: state_account.balance,
+                nonce: state_account.nonce,
+                code_hash: state_account.code_hash,
+                code: Some(bytecode),
+            },
+            state: AccountState::None,
+            storage,
+        };
+
+        accounts.insert(*address, mem_account);
+    }
+
+    Ok(accounts)
+}
+
+/// Returns the hashes of the parent and its ancestors that are available to `BLOCKHASH`.
+///
+/// The ancestors must be given in descending order, starting with the parent of `parent`.
+/// Each header is authenticated by the parent hash of its child, so the returned hashes
+/// are those of the chain of `parent`.
+pub(crate) fn block_hash_history(
+    parent: &Header,
+    ancestors: &[Header],
+) -> Result<HashMap<u64, B256>> {
+    let mut block_hashes = HashMap::with_capacity(ancestors.len() + 1);
+    block_hashes.insert(parent.number, parent.hash());
+
+    let mut prev = parent;
+    for current in ancestors {
+        let current_hash = current.hash();
+        if prev.parent_hash != current_hash || prev.number.checked_sub(1) != Some(current.number) {
+            bail!(
+                "Invalid chain: {} is not the parent of {}",
+                current.number,
+                prev.number
+            );
+        }
+        // BLOCKHASH in the new block can only access the `MAX_BLOCK_HASH_AGE` most recent
+        // blocks, i.e. the parent and its `MAX_BLOCK_HASH_AGE - 1` ancestors
+        if parent.number - current.number >= MAX_BLOCK_HASH_AGE {
+            bail!(
+                "Invalid chain: {} is not one of the {} most recent blocks",
+                current.number,
+                MAX_BLOCK_HASH_AGE,
+            );
+        }
+        block_hashes.insert(current.number, current_hash);
+        prev = current;
+    }
+
+    Ok(block_hashes)
+}
+
+#[cfg(test)]
+mod tests {
+    use zeth_primitives::U256;
+
+    use super::*;
+
+    /// Returns the `count` ancestors of `parent`, in descending order.
+    fn ancestors(parent: &mut Header, count: u64) -> Vec<Header> {
+        let mut headers: Vec<Header> = (parent.number - count..parent.number)
+            .map(|number| Header {
+                number,
+                ..Default::default()
+            })
+            .collect();
+        for i in 1..headers.len() {
+            headers[i].parent_hash = headers[i - 1].hash();
+        }
+        if let Some(last) = headers.last() {
+            parent.parent_hash = last.hash();
+        }
+        headers.reverse();
+        headers
+    }
+
+    #[test]
+    fn full_history() {
+        let mut parent = Header {
+            number: 1000,
+            ..Default::default()
+        };
+        let ancestors = ancestors(&mut parent, MAX_BLOCK_HASH_AGE - 1);
+
+        let block_hashes = block_hash_history(&parent, &ancestors).unwrap();
+        assert_eq!(block_hashes.len() as u64, MAX_BLOCK_HASH_AGE);
+        assert_eq!(block_hashes[&1000], parent.hash());
+        for header in &ancestors {
+            assert_eq!(block_hashes[&header.number], header.hash());
+        }
+    }
+
+    #[test]
+    fn too_old() {
+        let mut parent = Header {
+            number: 1000,
+            ..Default::default()
+        };
+        let ancestors = ancestors(&mut parent, MAX_BLOCK_HASH_AGE);
+        assert!(block_hash_history(&parent, &ancestors).is_err());
+    }
+
+    #[test]
+    fn broken_chain() {
+        let mut parent = Header {
+            number: 1000,
+            ..Default::default()
+        };
+        let mut ancestors = ancestors(&mut parent, 3);
+
+        // skipping an ancestor breaks the chain
+        let skipped = [ancestors[0].clone(), ancestors[2].clone()];
+        assert!(block_hash_history(&parent, &skipped).is_err());
+
+        // so does modifying one
+        ancestors[1].timestamp = U256::from(1);
+        assert!(block_hash_history(&parent, &ancestors).is_err());
+    }
+}
@@ -15,7 +15,8 @@
 #[cfg(not(target_os = "zkvm"))]
 use std::sync::{Arc, Mutex};
 
-use anyhow::Result;
+use anyhow::{bail, ensure, Context, Result};
+use hashbrown::HashMap;
 use revm::{primitives::SpecId, Database, DatabaseCommit};
 use serde::Serialize;
 use zeth_primitives::{
@@ -27,14 +28,17 @@ use zeth_primitives::{
 use crate::{
     builder::{
         execute::{ethereum::EthTxExecStrategy, optimism::OpTxExecStrategy, TxExecStrategy},
-        finalize::{BlockFinalizeStrategy, MemDbBlockFinalizeStrategy},
-        initialize::{DbInitStrategy, MemDbInitStrategy},
+        finalize::{apply_state_updates, BlockFinalizeStrategy, MemDbBlockFinalizeStrategy},
+        initialize::{
+            block_hash_history, hash_contracts, load_accounts, DbInitStrategy, MemDbInitStrategy,
+        },
         prepare::{EthHeaderPrepStrategy, HeaderPrepStrategy},
     },
     consts::ChainSpec,
-    input::BlockBuildInput,
+    guest_mem_forget,
+    input::{BlockBuildInput, BlockBundleInput},
     mem_db::MemDb,
-    output::BlockBuildOutput,
+    output::{BlockBuildOutput, BlockBundleOutput},
 };
 
 mod execute;
@@ -187,6 +191,85 @@ pub trait BlockBuilderStrategy {
             state_input_hash: input_hash.into(),
         })
     }
+
+    /// Builds a bundle of consecutive blocks from the given input.
+    ///
+    /// The parent tries of the input are verified once and then updated with the changes
+    /// of every block, so that each block is built on the state of its predecessor.
+    /// Unlike [BlockBuilderStrategy::build_from], a faulty block in the bundle is an
+    /// error.
+    fn build_bundle(
+        chain_spec: &ChainSpec,
+        input: BlockBundleInput<Self::TxEssence>,
+    ) -> Result<BlockBundleOutput> {
+        let BlockBundleInput {
+            state_inputs,
+            mut parent_state_trie,
+            mut parent_storage,
+            contracts,
+            ancestor_headers,
+        } = input;
+        let Some(first_input) = state_inputs.first() else {
+            bail!("Block bundle is empty");
+        };
+        let parent_hash = first_input.parent_header.hash();
+
+        // the contracts and the block hash history only need to be prepared once
+        let contracts = hash_contracts(contracts);
+        let mut block_hashes = block_hash_history(&first_input.parent_header, &ancestor_headers)?;
+
+        let mut hash = parent_hash;
+        for (i, state_input) in state_inputs.into_iter().enumerate() {
+            let input_parent_hash = state_input.parent_header.hash();
+            ensure!(
+                input_parent_hash == hash,
+                "Block {} of the bundle does not extend its predecessor: expected parent {}, got {}",
+                i,
+                hash,
+                input_parent_hash
+            );
+            // the parent of every further block was built from the carried state, so this
+            // only verifies the state trie of the first parent
+            ensure!(
+                parent_state_trie.hash() == state_input.parent_header.state_root,
+                "Invalid state trie: expected {}, got {}",
+                state_input.parent_header.state_root,
+                parent_state_trie.hash()
+            );
+
+            let db = MemDb {
+                accounts: load_accounts(&parent_state_trie, &parent_storage, &contracts)?,
+                block_hashes: block_hashes.clone(),
+            };
+            let input = BlockBuildInput {
+                state_input,
+                parent_state_trie: MptNode::default(),
+                parent_storage: HashMap::new(),
+                contracts: vec![],
+                ancestor_headers: vec![],
+            };
+            let mut builder = BlockBuilder::<MemDb, Self::TxEssence>::new(chain_spec, input, None)
+                .with_db(db)
+                .prepare_header::<Self::HeaderPrepStrategy>()
+                .and_then(|builder| builder.execute_transactions::<Self::TxExecStrategy>())
+                .with_context(|| format!("Block {} of the bundle is faulty", i))?;
+
+            // carry the state forward to the next block
+            let db = builder.db.take().expect("DB not initialized");
+            apply_state_updates(&db, &mut parent_state_trie, &mut parent_storage)?;
+            let mut header = builder.header.take().expect("Header not initialized");
+            header.state_root = parent_state_trie.hash();
+
+            hash = header.hash();
+            block_hashes.insert(header.number, hash);
+
+            // Leak memory, save cycles
+            guest_mem_forget(db);
+            guest_mem_forget(builder);
+        }
+
+        Ok(BlockBundleOutput { parent_hash, hash })
+    }
 }
 
 /// The [BlockBuilderStrategy] for building an Ethereum block.
@@ -15,12 +15,12 @@
 use core::fmt::Debug;
 
 use anyhow::{bail, Context, Result};
-use revm::{Database, DatabaseCommit};
-use zeth_primitives::{block::Header, transactions::TxEssence, U256};
+use revm::{primitives::SpecId, Database, DatabaseCommit};
+use zeth_primitives::{block::Header, transactions::TxEssence};
 
 use crate::{
     builder::BlockBuilder,
-    consts::{Eip1559Constants, GAS_LIMIT_BOUND_DIVISOR, MAX_EXTRA_DATA_BYTES, MIN_GAS_LIMIT, ONE},
+    consts::{derive_base_fee, GAS_LIMIT_BOUND_DIVISOR, MAX_EXTRA_DATA_BYTES, MIN_GAS_LIMIT},
 };
 
 pub trait HeaderPrepStrategy {
@@ -93,6 +93,11 @@ impl HeaderPrepStrategy for EthHeaderPrepStrategy {
             .chain_spec
             .active_fork(number, &timestamp)
             .unwrap_or_else(|err| panic!("Invalid version: {:#}", err));
+        // The header fields added by Cancun and Prague, e.g. the blob gas and the parent
+        // beacon block root, are not derived, so such blocks would get a wrong hash
+        if spec_id >= SpecId::CANCUN {
+            bail!("Invalid version: {:?} blocks are not supported", spec_id);
+        }
         block_builder.spec_id = Some(spec_id);
         // Derive header
         block_builder.header = Some(Header {
@@ -122,36 +127,47 @@ impl HeaderPrepStrategy for EthHeaderPrepStrategy {
     }
 }
 
-/// Base fee for next block. [EIP-1559](https://github.com/ethereum/EIPs/blob/master/EIPS/eip-1559.md) spec
-fn derive_base_fee(parent: &Header, eip_1559_constants: &Eip1559Constants) -> U256 {
-    let parent_gas_target = parent.gas_limit / eip_1559_constants.elasticity_multiplier;
+#[cfg(test)]
+mod tests {
+    use zeth_primitives::{transactions::ethereum::EthereumTxEssence, U256};
 
-    match parent.gas_used.cmp(&parent_gas_target) {
-        std::cmp::Ordering::Equal => parent.base_fee_per_gas,
+    use super::*;
+    use crate::{
+        consts::{ChainSpec, ETH_MAINNET_EIP1559_CONSTANTS},
+        input::{BlockBuildInput, StateInput},
+        mem_db::MemDb,
+    };
 
-        std::cmp::Ordering::Greater => {
-            let gas_used_delta = parent.gas_used - parent_gas_target;
-            let base_fee_delta = ONE
-                .max(
-                    parent.base_fee_per_gas * gas_used_delta
-                        / parent_gas_target
-                        / eip_1559_constants.base_fee_change_denominator,
-                )
-                .min(
-                    parent.base_fee_per_gas / eip_1559_constants.base_fee_max_increase_denominator,
-                );
-            parent.base_fee_per_gas + base_fee_delta
-        }
+    fn prepare(chain_spec: &ChainSpec) -> Result<()> {
+        let input = BlockBuildInput {
+            state_input: StateInput {
+                parent_header: Header {
+                    gas_limit: U256::from(30_000_000),
+                    ..Default::default()
+                },
+                beneficiary: Default::default(),
+                gas_limit: U256::from(30_000_000),
+                timestamp: U256::from(1),
+                extra_data: Default::default(),
+                mix_hash: Default::default(),
+                transactions: vec![],
+                withdrawals: vec![],
+            },
+            parent_state_trie: Default::default(),
+            parent_storage: Default::default(),
+            contracts: vec![],
+            ancestor_headers: vec![],
+        };
+        BlockBuilder::<MemDb, EthereumTxEssence>::new(chain_spec, input, None)
+            .prepare_header::<EthHeaderPrepStrategy>()
+            .map(|_| ())
+    }
 
-        std::cmp::Ordering::Less => {
-            let gas_used_delta = parent_gas_target - parent.gas_used;
-            let base_fee_delta = (parent.base_fee_per_gas * gas_used_delta
-                / parent_gas_target
-                / eip_1559_constants.base_fee_change_denominator)
-                .min(
-                    parent.base_fee_per_gas / eip_1559_constants.base_fee_max_decrease_denominator,
-                );
-            parent.base_fee_per_gas - base_fee_delta
+    #[test]
+    fn unsupported_header_fields() {
+        for (spec_id, supported) in [(SpecId::SHANGHAI, true), (SpecId::CANCUN, false)] {
+            let chain_spec = ChainSpec::new_single(1, spec_id, ETH_MAINNET_EIP1559_CONSTANTS);
+            assert_eq!(prepare(&chain_spec).is_ok(), supported, "{:?}", spec_id);
         }
     }
 }
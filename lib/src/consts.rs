@@ -21,7 +21,7 @@ use anyhow::{bail, Result};
 use once_cell::sync::Lazy;
 use revm::primitives::SpecId;
 use serde::{Deserialize, Serialize};
-use zeth_primitives::{uint, BlockNumber, ChainId, U256};
+use zeth_primitives::{block::Header, uint, BlockNumber, ChainId, U256};
 
 /// U256 representation of 0.
 pub const ZERO: U256 = U256::ZERO;
@@ -42,6 +42,12 @@ pub const MAX_BLOCK_HASH_AGE: u64 = 256;
 /// Multiplier for converting gwei to wei.
 pub const GWEI_TO_WEI: U256 = uint!(1_000_000_000_U256);
 
+/// Blob gas used by every blob, see [EIP-4844](https://eips.ethereum.org/EIPS/eip-4844).
+pub const GAS_PER_BLOB: U256 = uint!(131_072_U256);
+
+/// Blob gas targeted per block by [EIP-4844](https://eips.ethereum.org/EIPS/eip-4844).
+pub const TARGET_BLOB_GAS_PER_BLOCK: U256 = uint!(393_216_U256);
+
 /// The Ethereum mainnet specification.
 pub static ETH_MAINNET_CHAIN_SPEC: Lazy<ChainSpec> = Lazy::new(|| ChainSpec {
     chain_id: 1,
@@ -54,6 +60,18 @@ pub static ETH_MAINNET_CHAIN_SPEC: Lazy<ChainSpec> = Lazy::new(|| ChainSpec {
     gas_constants: BTreeMap::from([(SpecId::LONDON, ETH_MAINNET_EIP1559_CONSTANTS)]),
 });
 
+/// The Ethereum Sepolia testnet specification.
+pub static ETH_SEPOLIA_CHAIN_SPEC: Lazy<ChainSpec> = Lazy::new(|| ChainSpec {
+    chain_id: 11155111,
+    max_spec_id: SpecId::SHANGHAI,
+    hard_forks: BTreeMap::from([
+        (SpecId::MERGE, ForkCondition::Block(1735371)),
+        (SpecId::SHANGHAI, ForkCondition::Timestamp(1677557088)),
+        (SpecId::CANCUN, ForkCondition::Timestamp(1706655072)),
+    ]),
+    gas_constants: BTreeMap::from([(SpecId::LONDON, ETH_MAINNET_EIP1559_CONSTANTS)]),
+});
+
 /// The Ethereum mainnet EIP-1559 gas constants.
 pub const ETH_MAINNET_EIP1559_CONSTANTS: Eip1559Constants = Eip1559Constants {
     base_fee_change_denominator: uint!(8_U256),
@@ -72,8 +90,9 @@ pub static OP_MAINNET_CHAIN_SPEC: Lazy<ChainSpec> = Lazy::new(|| ChainSpec {
         (SpecId::REGOLITH, ForkCondition::Timestamp(1679079600)),
         // Canyon is activated 2024-01-11 at 17:00:01 UTC
         (SpecId::CANYON, ForkCondition::Timestamp(1704992401)),
-        // Delta is activated 2024-02-22 at 17:00:01 UTC
-        (SpecId::LATEST, ForkCondition::Timestamp(1708560000)),
+        // Delta only changes the derivation, see `ChainConfig::delta_time`
+        // Ecotone is activated 2024-03-14 at 00:00:01 UTC
+        (SpecId::LATEST, ForkCondition::Timestamp(1710374401)),
     ]),
     gas_constants: BTreeMap::from([
         (
@@ -97,8 +116,59 @@ pub static OP_MAINNET_CHAIN_SPEC: Lazy<ChainSpec> = Lazy::new(|| ChainSpec {
     ]),
 });
 
+/// The Base mainnet specification.
+pub static BASE_MAINNET_CHAIN_SPEC: Lazy<ChainSpec> = Lazy::new(|| ChainSpec {
+    chain_id: 8453,
+    max_spec_id: SpecId::CANYON,
+    hard_forks: BTreeMap::from([
+        // Base launched with Bedrock and Regolith active from genesis
+        (SpecId::BEDROCK, ForkCondition::Timestamp(1686789347)),
+        (SpecId::REGOLITH, ForkCondition::Timestamp(1686789347)),
+        // Canyon, Delta and Ecotone are activated at the same time as on OP mainnet
+        (SpecId::CANYON, ForkCondition::Timestamp(1704992401)),
+        (SpecId::LATEST, ForkCondition::Timestamp(1710374401)),
+    ]),
+    // Base uses the same EIP-1559 parameters as OP mainnet
+    gas_constants: OP_MAINNET_CHAIN_SPEC.gas_constants.clone(),
+});
+
+/// The OP Sepolia testnet specification.
+pub static OP_SEPOLIA_CHAIN_SPEC: Lazy<ChainSpec> = Lazy::new(|| ChainSpec {
+    chain_id: 11155420,
+    max_spec_id: SpecId::CANYON,
+    hard_forks: BTreeMap::from([
+        // OP Sepolia launched with Bedrock and Regolith active from genesis
+        (SpecId::BEDROCK, ForkCondition::Timestamp(1691802540)),
+        (SpecId::REGOLITH, ForkCondition::Timestamp(1691802540)),
+        // Canyon is activated 2023-11-14 at 17:00:00 UTC
+        (SpecId::CANYON, ForkCondition::Timestamp(1699981200)),
+        // Ecotone is activated 2024-02-21 at 17:00:00 UTC
+        (SpecId::LATEST, ForkCondition::Timestamp(1708534800)),
+    ]),
+    // OP Sepolia uses the same EIP-1559 parameters as OP mainnet
+    gas_constants: OP_MAINNET_CHAIN_SPEC.gas_constants.clone(),
+});
+
+/// The Ethereum specification the guests are built for: Sepolia with the `sepolia`
+/// feature, mainnet otherwise.
+#[cfg(not(feature = "sepolia"))]
+pub static ETH_CHAIN_SPEC: &Lazy<ChainSpec> = &ETH_MAINNET_CHAIN_SPEC;
+/// The Ethereum specification the guests are built for: Sepolia with the `sepolia`
+/// feature, mainnet otherwise.
+#[cfg(feature = "sepolia")]
+pub static ETH_CHAIN_SPEC: &Lazy<ChainSpec> = &ETH_SEPOLIA_CHAIN_SPEC;
+
+/// The Optimism specification the guests are built for: OP Sepolia with the `sepolia`
+/// feature, OP mainnet otherwise.
+#[cfg(not(feature = "sepolia"))]
+pub static OP_CHAIN_SPEC: &Lazy<ChainSpec> = &OP_MAINNET_CHAIN_SPEC;
+/// The Optimism specification the guests are built for: OP Sepolia with the `sepolia`
+/// feature, OP mainnet otherwise.
+#[cfg(feature = "sepolia")]
+pub static OP_CHAIN_SPEC: &Lazy<ChainSpec> = &OP_SEPOLIA_CHAIN_SPEC;
+
 /// The condition at which a fork is activated.
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ForkCondition {
     /// The fork is activated with a certain block.
     Block(BlockNumber),
@@ -129,6 +199,48 @@ pub struct Eip1559Constants {
     pub elasticity_multiplier: U256,
 }
 
+/// Returns the base fee of the block following `parent`, as mandated by EIP-1559.
+pub(crate) fn derive_base_fee(parent: &Header, eip_1559_constants: &Eip1559Constants) -> U256 {
+    let parent_gas_target = parent.gas_limit / eip_1559_constants.elasticity_multiplier;
+
+    match parent.gas_used.cmp(&parent_gas_target) {
+        std::cmp::Ordering::Equal => parent.base_fee_per_gas,
+
+        std::cmp::Ordering::Greater => {
+            let gas_used_delta = parent.gas_used - parent_gas_target;
+            let base_fee_delta = ONE
+                .max(
+                    parent.base_fee_per_gas * gas_used_delta
+                        / parent_gas_target
+                        / eip_1559_constants.base_fee_change_denominator,
+                )
+                .min(
+                    parent.base_fee_per_gas / eip_1559_constants.base_fee_max_increase_denominator,
+                );
+            parent.base_fee_per_gas + base_fee_delta
+        }
+
+        std::cmp::Ordering::Less => {
+            let gas_used_delta = parent_gas_target - parent.gas_used;
+            let base_fee_delta = (parent.base_fee_per_gas * gas_used_delta
+                / parent_gas_target
+                / eip_1559_constants.base_fee_change_denominator)
+                .min(
+                    parent.base_fee_per_gas / eip_1559_constants.base_fee_max_decrease_denominator,
+                );
+            parent.base_fee_per_gas - base_fee_delta
+        }
+    }
+}
+
+/// Returns the excess blob gas of the block following `parent`, as mandated by EIP-4844.
+/// A parent from before Cancun counts as having neither used nor excess blob gas.
+pub(crate) fn derive_excess_blob_gas(parent: &Header) -> U256 {
+    let parent_blob_gas =
+        parent.excess_blob_gas.unwrap_or_default() + parent.blob_gas_used.unwrap_or_default();
+    parent_blob_gas.saturating_sub(TARGET_BLOB_GAS_PER_BLOCK)
+}
+
 /// Specification of a specific chain.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChainSpec {
@@ -139,6 +251,20 @@ pub struct ChainSpec {
 }
 
 impl ChainSpec {
+    /// Creates a new configuration from its fork schedule and EIP-1559 parameters.
+    pub fn new(
+        chain_id: ChainId,
+        max_spec_id: SpecId,
+        hard_forks: BTreeMap<SpecId, ForkCondition>,
+        gas_constants: BTreeMap<SpecId, Eip1559Constants>,
+    ) -> Self {
+        ChainSpec {
+            chain_id,
+            max_spec_id,
+            hard_forks,
+            gas_constants,
+        }
+    }
     /// Creates a new configuration consisting of only one specification ID.
     pub fn new_single(
         chain_id: ChainId,
@@ -170,6 +296,10 @@ impl ChainSpec {
             None => bail!("no supported fork for block {}", block_number),
         }
     }
+    /// Returns the activation conditions of the hard forks, ordered by [SpecId].
+    pub fn hard_forks(&self) -> &BTreeMap<SpecId, ForkCondition> {
+        &self.hard_forks
+    }
     /// Returns the Eip1559 constants for a given [SpecId].
     pub fn gas_constants(&self, spec_id: SpecId) -> Option<&Eip1559Constants> {
         self.gas_constants
@@ -209,6 +339,20 @@ mod tests {
         );
     }
 
+    #[test]
+    fn excess_blob_gas() {
+        let mut parent = Header::default();
+        assert_eq!(derive_excess_blob_gas(&parent), U256::ZERO);
+
+        parent.excess_blob_gas = Some(U256::ZERO);
+        parent.blob_gas_used = Some(uint!(786_432_U256));
+        assert_eq!(derive_excess_blob_gas(&parent), uint!(393_216_U256));
+
+        parent.excess_blob_gas = Some(uint!(131_072_U256));
+        parent.blob_gas_used = Some(uint!(131_072_U256));
+        assert_eq!(derive_excess_blob_gas(&parent), U256::ZERO);
+    }
+
     #[test]
     fn gas_constants() {
         assert_eq!(ETH_MAINNET_CHAIN_SPEC.gas_constants(SpecId::BERLIN), None);
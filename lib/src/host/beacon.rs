@@ -0,0 +1,155 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Retrieval of the blob sidecars of Ethereum blocks from a beacon node.
+
+use std::{io::Write, path::Path};
+
+use anyhow::{ensure, Context, Result};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use log::debug;
+use serde::{de::DeserializeOwned, Deserialize};
+use zeth_primitives::B256;
+
+use crate::{host::encryption, optimism::blob::BlobSidecar};
+
+/// Envelope of all responses of the beacon node API.
+#[derive(Deserialize)]
+struct Response<T> {
+    data: T,
+}
+
+#[derive(Deserialize)]
+struct Genesis {
+    genesis_time: String,
+}
+
+#[derive(Deserialize)]
+struct Spec {
+    #[serde(rename = "SECONDS_PER_SLOT")]
+    seconds_per_slot: String,
+}
+
+/// A client of the beacon node API, which serves the blob sidecars by slot.
+pub struct BeaconClient {
+    url: String,
+    client: reqwest::Client,
+    tokio_handle: tokio::runtime::Handle,
+    /// Genesis time and slot duration of the beacon chain, fetched on first use.
+    clock: Option<(u64, u64)>,
+}
+
+impl BeaconClient {
+    pub fn new(beacon_url: String) -> Result<Self> {
+        reqwest::Url::parse(&beacon_url).context("invalid beacon URL")?;
+        Ok(BeaconClient {
+            url: beacon_url.trim_end_matches('/').to_string(),
+            client: reqwest::Client::new(),
+            tokio_handle: tokio::runtime::Handle::current(),
+            clock: None,
+        })
+    }
+
+    /// Returns the sidecars of the blobs with the given versioned hashes, which must all
+    /// be included in the Ethereum block with the given timestamp.
+    pub fn get_blob_sidecars(
+        &mut self,
+        timestamp: u64,
+        versioned_hashes: &[B256],
+    ) -> Result<Vec<BlobSidecar>> {
+        let slot = self.slot(timestamp)?;
+        debug!("Querying beacon node for blob sidecars: {}", slot);
+        let sidecars: Vec<BlobSidecar> =
+            self.get(&format!("eth/v1/beacon/blob_sidecars/{}", slot))?;
+
+        versioned_hashes
+            .iter()
+            .map(|versioned_hash| {
+                sidecars
+                    .iter()
+                    .find(|sidecar| sidecar.versioned_hash() == *versioned_hash)
+                    .cloned()
+                    .with_context(|| {
+                        format!("no blob sidecar for {} in slot {}", versioned_hash, slot)
+                    })
+            })
+            .collect()
+    }
+
+    /// Returns the slot of the beacon block with the given timestamp.
+    fn slot(&mut self, timestamp: u64) -> Result<u64> {
+        let (genesis_time, seconds_per_slot) = match self.clock {
+            Some(clock) => clock,
+            None => {
+                let genesis: Genesis = self.get("eth/v1/beacon/genesis")?;
+                let spec: Spec = self.get("eth/v1/config/spec")?;
+                let clock = (
+                    genesis
+                        .genesis_time
+                        .parse()
+                        .context("invalid genesis time")?,
+                    spec.seconds_per_slot
+                        .parse()
+                        .context("invalid seconds per slot")?,
+                );
+                ensure!(clock.1 > 0, "invalid seconds per slot: 0");
+                *self.clock.insert(clock)
+            }
+        };
+        ensure!(
+            timestamp >= genesis_time
+                && (timestamp - genesis_time).is_multiple_of(seconds_per_slot),
+            "timestamp {} is not the start of a slot",
+            timestamp
+        );
+        Ok((timestamp - genesis_time) / seconds_per_slot)
+    }
+
+    fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T> {
+        let url = format!("{}/{}", self.url, path);
+        let body = self
+            .tokio_handle
+            .block_on(async {
+                self.client
+                    .get(&url)
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .bytes()
+                    .await
+            })
+            .with_context(|| format!("beacon request {} failed", path))?;
+        let response: Response<T> = serde_json::from_slice(&body)
+            .with_context(|| format!("invalid beacon response to {}", path))?;
+        Ok(response.data)
+    }
+}
+
+/// Reads the blob sidecars cached in the given file, if it exists.
+pub fn read_cached_sidecars(file_path: &Path) -> Result<Option<Vec<BlobSidecar>>> {
+    if !file_path.exists() {
+        return Ok(None);
+    }
+    let bytes = encryption::read(file_path)?;
+    let sidecars = serde_json::from_reader(GzDecoder::new(bytes.as_slice()))
+        .with_context(|| format!("invalid blob sidecars in {}", file_path.display()))?;
+    Ok(Some(sidecars))
+}
+
+/// Caches the blob sidecars in the given file.
+pub fn write_cached_sidecars(file_path: &Path, sidecars: &[BlobSidecar]) -> Result<()> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+    encoder.write_all(&serde_json::to_vec(sidecars)?)?;
+    encryption::write(file_path, &encoder.finish()?)
+}
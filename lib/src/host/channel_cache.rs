@@ -0,0 +1,143 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use zeth_primitives::{keccak::keccak, B256};
+
+use crate::{host::encryption, optimism::batcher_channel::ChannelId};
+
+/// Name of the sub-directory of the cache directory holding the channel data.
+const CHANNEL_DIR: &str = "channels";
+
+/// A persistent cache of decompressed channel data.
+///
+/// Entries are keyed by the channel ID and the hash of the compressed channel data, so an
+/// entry can only be hit by a channel consisting of exactly the same frames. Every file
+/// is prefixed with the keccak hash of the decompressed data, so that truncated or
+/// corrupted entries are ignored instead of being decoded. Like all cache files, the
+/// entries are encrypted if a cache key is configured.
+#[derive(Clone, Debug)]
+pub struct ChannelCache {
+    dir: PathBuf,
+}
+
+impl ChannelCache {
+    /// Creates a channel cache in the given cache directory.
+    pub fn new(cache_path: &Path) -> Self {
+        Self {
+            dir: cache_path.join(CHANNEL_DIR),
+        }
+    }
+
+    /// Returns the cached decompressed data of the given channel, if present and intact.
+    pub fn get(&self, channel_id: ChannelId, data_hash: &B256) -> Option<Vec<u8>> {
+        let mut bytes = match encryption::read(&self.file_path(channel_id, data_hash)) {
+            Ok(bytes) => bytes,
+            Err(err) if err.is::<std::io::Error>() => return None,
+            Err(err) => {
+                log::warn!(
+                    "Ignoring unreadable cache entry of channel {:#x}: {:#}",
+                    channel_id,
+                    err
+                );
+                return None;
+            }
+        };
+        if bytes.len() < B256::len_bytes() {
+            return None;
+        }
+        let data = bytes.split_off(B256::len_bytes());
+        if keccak(&data) != bytes.as_slice() {
+            log::warn!(
+                "Ignoring corrupted cache entry of channel {:#x}",
+                channel_id
+            );
+            return None;
+        }
+
+        Some(data)
+    }
+
+    /// Stores the decompressed data of the given channel.
+    pub fn insert(&self, channel_id: ChannelId, data_hash: &B256, data: &[u8]) -> Result<()> {
+        fs::create_dir_all(&self.dir).context("could not create channel cache directory")?;
+
+        let mut bytes = keccak(data).to_vec();
+        bytes.extend_from_slice(data);
+        // write to a temporary file first, so that concurrent runs never read partial data
+        let file_path = self.file_path(channel_id, data_hash);
+        let tmp_path = file_path.with_extension(format!("tmp{}", std::process::id()));
+        encryption::write(&tmp_path, &bytes)?;
+        fs::rename(&tmp_path, &file_path)
+            .with_context(|| format!("could not write {}", file_path.display()))?;
+
+        Ok(())
+    }
+
+    fn file_path(&self, channel_id: ChannelId, data_hash: &B256) -> PathBuf {
+        self.dir
+            .join(format!("{:032x}-{:x}", channel_id, data_hash))
+            .with_extension("bin")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns a fresh temporary cache directory for the given test.
+    fn cache_dir(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("zeth-{}-{}", test, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn roundtrip() {
+        let dir = cache_dir("channel-cache-roundtrip");
+        let cache = ChannelCache::new(&dir);
+        let data_hash = B256::from(keccak(b"compressed"));
+
+        assert_eq!(cache.get(1, &data_hash), None);
+        cache.insert(1, &data_hash, b"decompressed").unwrap();
+        assert_eq!(cache.get(1, &data_hash).unwrap(), b"decompressed");
+        // the entry is only hit for the same channel and data
+        assert_eq!(cache.get(2, &data_hash), None);
+        assert_eq!(cache.get(1, &B256::ZERO), None);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn corrupted_entry() {
+        let dir = cache_dir("channel-cache-corrupted");
+        let cache = ChannelCache::new(&dir);
+        let data_hash = B256::ZERO;
+
+        cache.insert(1, &data_hash, b"decompressed").unwrap();
+        let file_path = cache.file_path(1, &data_hash);
+        let mut bytes = fs::read(&file_path).unwrap();
+        *bytes.last_mut().unwrap() ^= 1;
+        fs::write(&file_path, bytes).unwrap();
+
+        assert_eq!(cache.get(1, &data_hash), None);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,423 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::path::Path;
+
+use anyhow::{bail, ensure, Context, Result};
+use log::info;
+use serde::{ser::SerializeTuple, Deserialize, Serialize, Serializer};
+
+use crate::{
+    host::encryption,
+    optimism::{
+        batcher_db::MemDb,
+        composition::ImageId,
+        framed_db::{Frame, FramedDeriveInput},
+        DeriveInput,
+    },
+    output::BlockBuildOutput,
+};
+
+/// Magic bytes prefixing every versioned [DeriveInput] cache file.
+const MAGIC: &[u8; 4] = b"ZDIN";
+
+/// The current version of the [DeriveInput] cache format.
+///
+/// Caches without a version were written by older zeth versions, whose block and header
+/// layouts differ from the current ones, so they cannot be migrated. Caches of version 1
+/// are migrated with the options added since then disabled.
+/// Version 2 added the `op_derive_max_timestamp` bound and the `eth_accumulator`,
+/// `op_anchor_block_no`, `batch_provenance`, `rollup_config`, `prior_output`,
+/// `checkpoint` and `resume_checkpoint` options.
+pub const DERIVE_INPUT_VERSION: u32 = 2;
+
+/// The [DeriveInput] layout of version 1, read by the guests of earlier releases.
+#[derive(Deserialize, Serialize)]
+struct DeriveInputV1<D = MemDb> {
+    db: D,
+    op_head_block_no: u64,
+    op_derive_block_count: u32,
+    op_block_outputs: Vec<BlockBuildOutput>,
+    block_image_id: ImageId,
+}
+
+impl<D> DeriveInputV1<D> {
+    /// Converts the input into the layout of the given earlier version, failing if it
+    /// uses options that did not exist in that version.
+    fn new(input: DeriveInput<D>, version: u32) -> Result<Self> {
+        ensure!(
+            version < DERIVE_INPUT_VERSION,
+            "Derive input version {} is not an earlier version",
+            version
+        );
+        ensure!(
+            input.op_derive_max_timestamp.is_none(),
+            "Derive input version {} does not support a timestamp bound",
+            version
+        );
+        ensure!(
+            !input.eth_accumulator,
+            "Derive input version {} does not support the Ethereum accumulator",
+            version
+        );
+        ensure!(
+            input.op_anchor_block_no.is_none(),
+            "Derive input version {} does not support an L1 anchor",
+            version
+        );
+        ensure!(
+            !input.batch_provenance,
+            "Derive input version {} does not support batch provenance",
+            version
+        );
+        ensure!(
+            input.rollup_config.is_none(),
+            "Derive input version {} does not support a rollup config",
+            version
+        );
+        ensure!(
+            input.prior_output.is_none(),
+            "Derive input version {} does not support a prior output",
+            version
+        );
+        ensure!(
+            !input.checkpoint && input.resume_checkpoint.is_none(),
+            "Derive input version {} does not support checkpoints",
+            version
+        );
+        Ok(DeriveInputV1 {
+            db: input.db,
+            op_head_block_no: input.op_head_block_no,
+            op_derive_block_count: input.op_derive_block_count,
+            op_block_outputs: input.op_block_outputs,
+            block_image_id: input.block_image_id,
+        })
+    }
+}
+
+impl<D> From<DeriveInputV1<D>> for DeriveInput<D> {
+    fn from(input: DeriveInputV1<D>) -> Self {
+        DeriveInput {
+            db: input.db,
+            op_head_block_no: input.op_head_block_no,
+            op_derive_block_count: input.op_derive_block_count,
+            op_derive_max_timestamp: None,
+            op_block_outputs: input.op_block_outputs,
+            block_image_id: input.block_image_id,
+            eth_accumulator: false,
+            op_anchor_block_no: None,
+            batch_provenance: false,
+            rollup_config: None,
+            prior_output: None,
+            checkpoint: false,
+            resume_checkpoint: None,
+        }
+    }
+}
+
+/// The input of a derivation guest built for an earlier [DeriveInput] version.
+pub struct PreviousFramedDeriveInput {
+    derive_input: DeriveInputV1<()>,
+    frames: Vec<Frame>,
+}
+
+impl PreviousFramedDeriveInput {
+    /// Converts the guest input into the layout of the given earlier version.
+    pub fn new(input: FramedDeriveInput, version: u32) -> Result<Self> {
+        Ok(PreviousFramedDeriveInput {
+            derive_input: DeriveInputV1::new(input.derive_input, version)?,
+            frames: input.frames,
+        })
+    }
+}
+
+impl Serialize for PreviousFramedDeriveInput {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        // same layout as the current FramedDeriveInput
+        let mut tuple = serializer.serialize_tuple(1 + self.frames.len())?;
+        tuple.serialize_element(&self.derive_input)?;
+        for frame in &self.frames {
+            tuple.serialize_element(frame)?;
+        }
+        tuple.end()
+    }
+}
+
+/// Serializes the input using the current cache format.
+pub fn encode(input: &DeriveInput<MemDb>) -> Result<Vec<u8>> {
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend_from_slice(&DERIVE_INPUT_VERSION.to_le_bytes());
+    bincode::serialize_into(&mut bytes, input).context("Failed to serialize derive input")?;
+
+    Ok(bytes)
+}
+
+/// Returns the version of a cached input, failing for unversioned caches.
+fn version(bytes: &[u8]) -> Result<u32> {
+    let Some(versioned) = bytes.strip_prefix(MAGIC) else {
+        bail!(
+            "Unversioned derive input cache was produced by an older zeth version and \
+             cannot be migrated; delete it to regenerate the cache"
+        );
+    };
+    ensure!(versioned.len() >= 4, "Truncated derive input cache header");
+
+    Ok(u32::from_le_bytes(versioned[..4].try_into().unwrap()))
+}
+
+/// Deserializes a cached input, migrating caches of earlier versions and rejecting the
+/// ones that cannot be migrated with an error on how to regenerate them.
+pub fn decode(bytes: &[u8]) -> Result<DeriveInput<MemDb>> {
+    let version = version(bytes)?;
+    let payload = &bytes[MAGIC.len() + 4..];
+
+    match version {
+        DERIVE_INPUT_VERSION => bincode::deserialize(payload).with_context(|| {
+            format!(
+                "Failed to deserialize derive input cache (version {})",
+                version
+            )
+        }),
+        _ if version > DERIVE_INPUT_VERSION => bail!(
+            "Derive input cache version {} was produced by a newer zeth version (supported: {})",
+            version,
+            DERIVE_INPUT_VERSION
+        ),
+        _ => migrate(version, payload),
+    }
+}
+
+/// Deserializes the payload of a cache of an earlier version into the current layout.
+fn migrate(version: u32, payload: &[u8]) -> Result<DeriveInput<MemDb>> {
+    match version {
+        1 => {
+            let input: DeriveInputV1 = bincode::deserialize(payload)
+                .context("Failed to deserialize derive input cache (version 1)")?;
+            Ok(input.into())
+        }
+        _ => bail!(
+            "Derive input cache version {} is no longer supported; delete it to regenerate \
+             the cache",
+            version
+        ),
+    }
+}
+
+/// Writes the input to the given file using the current cache format.
+pub fn save(file_path: &Path, input: &DeriveInput<MemDb>) -> Result<()> {
+    encryption::write(file_path, &encode(input)?)
+}
+
+/// Reads the input from the given file, upgrading the file in place if it was written
+/// in an earlier version of the cache format.
+pub fn load(file_path: &Path) -> Result<DeriveInput<MemDb>> {
+    let bytes = encryption::read(file_path)
+        .with_context(|| format!("Failed to read '{}'", file_path.display()))?;
+    let input = decode(&bytes)
+        .with_context(|| format!("Invalid derive input cache '{}'", file_path.display()))?;
+    let version = version(&bytes)?;
+    if version < DERIVE_INPUT_VERSION {
+        info!(
+            "Migrating derive input cache '{}' from version {} to {}",
+            file_path.display(),
+            version,
+            DERIVE_INPUT_VERSION
+        );
+        save(file_path, &input)?;
+    }
+
+    Ok(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use zeth_primitives::{Address, B256};
+
+    use super::*;
+    use crate::optimism::{
+        batcher::BlockId,
+        rollup_config::{RollupConfig, RollupGenesis, RollupSystemConfig},
+        DeriveOutput,
+    };
+
+    fn test_input() -> DeriveInput<MemDb> {
+        DeriveInput {
+            db: MemDb::new(),
+            op_head_block_no: 42,
+            op_derive_block_count: 3,
+            op_derive_max_timestamp: Some(1_700_000_000),
+            op_block_outputs: vec![],
+            block_image_id: [7; 8],
+            eth_accumulator: true,
+            op_anchor_block_no: Some(105_235_100),
+            batch_provenance: true,
+            rollup_config: Some(test_rollup_config()),
+            prior_output: Some(test_prior_output()),
+            checkpoint: true,
+            resume_checkpoint: None,
+        }
+    }
+
+    fn test_prior_output() -> DeriveOutput {
+        DeriveOutput {
+            eth_tail: BlockId {
+                hash: B256::repeat_byte(8),
+                number: 100,
+            },
+            op_head: BlockId {
+                hash: B256::repeat_byte(9),
+                number: 40,
+            },
+            derived_op_blocks: vec![BlockId {
+                hash: B256::repeat_byte(10),
+                number: 41,
+            }],
+            block_image_id: [7; 8],
+            eth_accumulator: None,
+            l1_anchor: None,
+            batch_provenance: vec![],
+            rollup_config_hash: Some(test_rollup_config().hash()),
+            prior_output_digest: None,
+            checkpoint_hash: None,
+        }
+    }
+
+    fn test_rollup_config() -> RollupConfig {
+        RollupConfig {
+            genesis: RollupGenesis {
+                l1: BlockId {
+                    hash: B256::repeat_byte(1),
+                    number: 1,
+                },
+                l2: BlockId {
+                    hash: B256::repeat_byte(2),
+                    number: 0,
+                },
+                l2_time: 1_700_000_000,
+                system_config: RollupSystemConfig {
+                    batcher_addr: Address::repeat_byte(3),
+                    overhead: B256::ZERO,
+                    scalar: B256::with_last_byte(1),
+                    gas_limit: 30_000_000,
+                },
+            },
+            block_time: 2,
+            max_sequencer_drift: 600,
+            seq_window_size: 3600,
+            channel_timeout: 300,
+            l1_chain_id: 900,
+            l2_chain_id: 901,
+            regolith_time: Some(0),
+            canyon_time: None,
+            delta_time: None,
+            ecotone_time: None,
+            batch_inbox_address: Address::repeat_byte(4),
+            deposit_contract_address: Address::repeat_byte(5),
+            l1_system_config_address: Address::repeat_byte(6),
+            chain_op_config: None,
+        }
+    }
+
+    #[test]
+    fn roundtrip() {
+        let input = decode(&encode(&test_input()).unwrap()).unwrap();
+        assert_eq!(input.op_head_block_no, 42);
+        assert_eq!(input.op_derive_block_count, 3);
+        assert_eq!(input.block_image_id, [7; 8]);
+        assert!(input.eth_accumulator);
+        assert_eq!(input.op_derive_max_timestamp, Some(1_700_000_000));
+        assert_eq!(input.op_anchor_block_no, Some(105_235_100));
+        assert!(input.batch_provenance);
+        assert_eq!(input.rollup_config, Some(test_rollup_config()));
+        assert_eq!(input.prior_output, Some(test_prior_output()));
+        assert!(input.checkpoint);
+    }
+
+    #[test]
+    fn reject_unversioned() {
+        // cache written by zeth before the versioned format, whose headers lack the
+        // Cancun fields and whose blocks lack the blob sidecars
+        let bytes = include_bytes!("../../testdata/derive_input_v0.bin");
+        let err = decode(bytes).unwrap_err();
+        assert!(err.to_string().contains("regenerate"));
+    }
+
+    #[test]
+    fn migrate_v1() {
+        let previous = DeriveInputV1 {
+            db: MemDb::new(),
+            op_head_block_no: 42,
+            op_derive_block_count: 3,
+            op_block_outputs: vec![],
+            block_image_id: [7; 8],
+        };
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&1u32.to_le_bytes());
+        bincode::serialize_into(&mut bytes, &previous).unwrap();
+
+        let input = decode(&bytes).unwrap();
+        assert_eq!(input.op_head_block_no, 42);
+        assert_eq!(input.op_derive_block_count, 3);
+        assert_eq!(input.block_image_id, [7; 8]);
+        assert_eq!(input.op_derive_max_timestamp, None);
+        assert!(!input.eth_accumulator);
+        assert!(input.rollup_config.is_none());
+        assert!(!input.checkpoint);
+    }
+
+    #[test]
+    fn reject_outdated_version() {
+        let mut bytes = encode(&test_input()).unwrap();
+        bytes[4..8].copy_from_slice(&0u32.to_le_bytes());
+        let err = decode(&bytes).unwrap_err();
+        assert!(err.to_string().contains("no longer supported"));
+    }
+
+    #[test]
+    fn previous_layout() {
+        let mut input = test_input();
+        assert!(DeriveInputV1::new(input.clone(), 1).is_err());
+        input.op_derive_max_timestamp = None;
+        input.eth_accumulator = false;
+        input.op_anchor_block_no = None;
+        input.batch_provenance = false;
+        input.rollup_config = None;
+        input.prior_output = None;
+        assert!(DeriveInputV1::new(input.clone(), 1).is_err());
+        input.checkpoint = false;
+        assert!(DeriveInputV1::new(input.clone(), DERIVE_INPUT_VERSION).is_err());
+
+        let previous = DeriveInputV1::new(input, 1).unwrap();
+        let bytes = bincode::serialize(&previous).unwrap();
+        let input: DeriveInputV1 = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(input.op_head_block_no, 42);
+        assert_eq!(input.op_derive_block_count, 3);
+    }
+
+    #[test]
+    fn reject_newer_version() {
+        let mut bytes = encode(&test_input()).unwrap();
+        bytes[4..8].copy_from_slice(&(DERIVE_INPUT_VERSION + 1).to_le_bytes());
+        let err = decode(&bytes).unwrap_err();
+        assert!(err.to_string().contains("newer zeth version"));
+    }
+
+    #[test]
+    fn reject_corrupted() {
+        let mut bytes = encode(&test_input()).unwrap();
+        bytes.truncate(10);
+        assert!(decode(&bytes).is_err());
+        assert!(decode(&bytes[..6]).is_err());
+    }
+}
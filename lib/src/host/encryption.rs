@@ -0,0 +1,190 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Optional encryption of the cached witnesses and derivation inputs at rest.
+//!
+//! When a key is configured, every cache file is written as [MAGIC] followed by a random
+//! nonce and the AES-256-GCM ciphertext of its content. Unencrypted cache files can still
+//! be read, so existing caches keep working after a key has been set.
+
+use std::{fs, path::Path, process::Command, str::FromStr};
+
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit, OsRng},
+    Aes256Gcm, Key, Nonce,
+};
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use once_cell::sync::OnceCell;
+use zeth_primitives::B256;
+
+/// Environment variable containing the hex-encoded 256-bit key.
+pub const KEY_ENV: &str = "ZETH_CACHE_KEY";
+
+/// Environment variable containing a shell command that prints the hex-encoded key, e.g.
+/// a call to a key management service.
+pub const KEY_COMMAND_ENV: &str = "ZETH_CACHE_KEY_COMMAND";
+
+/// Magic bytes prefixing every encrypted cache file.
+pub const MAGIC: &[u8; 4] = b"ZENC";
+
+/// Size of the AES-GCM nonce in bytes.
+const NONCE_LEN: usize = 12;
+
+/// A key for encrypting cache files.
+pub struct CacheKey(Key<Aes256Gcm>);
+
+impl CacheKey {
+    /// Parses a hex-encoded 256-bit key.
+    pub fn from_hex(s: &str) -> Result<Self> {
+        let key = B256::from_str(s.trim()).context("Cache key must be 32 hex-encoded bytes")?;
+        Ok(CacheKey(key.0.into()))
+    }
+
+    /// Returns the key configured in the environment, if any.
+    ///
+    /// The key is taken from [KEY_ENV], or from the output of the command in
+    /// [KEY_COMMAND_ENV].
+    pub fn from_env() -> Result<Option<Self>> {
+        if let Ok(key) = std::env::var(KEY_ENV) {
+            return Self::from_hex(&key)
+                .with_context(|| format!("Invalid {}", KEY_ENV))
+                .map(Some);
+        }
+        let Ok(command) = std::env::var(KEY_COMMAND_ENV) else {
+            return Ok(None);
+        };
+        let output = Command::new("sh")
+            .arg("-c")
+            .arg(&command)
+            .output()
+            .with_context(|| format!("Failed to run {}", KEY_COMMAND_ENV))?;
+        ensure!(
+            output.status.success(),
+            "{} failed with {}",
+            KEY_COMMAND_ENV,
+            output.status
+        );
+        let key = String::from_utf8(output.stdout)
+            .with_context(|| format!("{} printed an invalid key", KEY_COMMAND_ENV))?;
+        Self::from_hex(&key)
+            .with_context(|| format!("{} printed an invalid key", KEY_COMMAND_ENV))
+            .map(Some)
+    }
+
+    /// Encrypts the data using a random nonce.
+    pub fn encrypt(&self, data: &[u8]) -> Result<Vec<u8>> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = Aes256Gcm::new(&self.0)
+            .encrypt(&nonce, data)
+            .map_err(|_| anyhow!("Failed to encrypt cache data"))?;
+
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&nonce);
+        bytes.extend(ciphertext);
+        Ok(bytes)
+    }
+
+    /// Decrypts data produced by [CacheKey::encrypt].
+    pub fn decrypt(&self, bytes: &[u8]) -> Result<Vec<u8>> {
+        let Some(encrypted) = bytes.strip_prefix(MAGIC) else {
+            bail!("Data is not encrypted");
+        };
+        ensure!(encrypted.len() >= NONCE_LEN, "Truncated encrypted data");
+        let (nonce, ciphertext) = encrypted.split_at(NONCE_LEN);
+        let nonce: [u8; NONCE_LEN] = nonce.try_into().unwrap();
+        Aes256Gcm::new(&self.0)
+            .decrypt(&Nonce::from(nonce), ciphertext)
+            .map_err(|_| anyhow!("Failed to decrypt cache data: wrong key or corrupted data"))
+    }
+}
+
+/// Returns the key configured in the environment, reading it only once per process.
+pub fn cache_key() -> Result<Option<&'static CacheKey>> {
+    static CACHE_KEY: OnceCell<Option<CacheKey>> = OnceCell::new();
+    Ok(CACHE_KEY.get_or_try_init(CacheKey::from_env)?.as_ref())
+}
+
+/// Returns whether the data was encrypted by [CacheKey::encrypt].
+pub fn is_encrypted(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+/// Reads a cache file, decrypting it if necessary.
+///
+/// I/O errors are returned unchanged, so that callers can check for missing files.
+pub fn read(file_path: &Path) -> Result<Vec<u8>> {
+    let bytes = fs::read(file_path)?;
+    if !is_encrypted(&bytes) {
+        return Ok(bytes);
+    }
+    let key = cache_key()?.with_context(|| {
+        format!(
+            "'{}' is encrypted, but neither {} nor {} is set",
+            file_path.display(),
+            KEY_ENV,
+            KEY_COMMAND_ENV
+        )
+    })?;
+    key.decrypt(&bytes)
+        .with_context(|| format!("Failed to decrypt '{}'", file_path.display()))
+}
+
+/// Writes a cache file, encrypting it if a key is configured.
+pub fn write(file_path: &Path, data: &[u8]) -> Result<()> {
+    let bytes = match cache_key()? {
+        Some(key) => key.encrypt(data)?,
+        None => data.to_vec(),
+    };
+    fs::write(file_path, bytes)
+        .with_context(|| format!("Failed to write '{}'", file_path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &str = "0x000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f";
+
+    #[test]
+    fn roundtrip() {
+        let key = CacheKey::from_hex(KEY).unwrap();
+        let encrypted = key.encrypt(b"witness").unwrap();
+        assert!(is_encrypted(&encrypted));
+        assert_ne!(&encrypted[MAGIC.len() + NONCE_LEN..], b"witness");
+        assert_eq!(key.decrypt(&encrypted).unwrap(), b"witness");
+
+        // every encryption uses a fresh nonce
+        assert_ne!(key.encrypt(b"witness").unwrap(), encrypted);
+    }
+
+    #[test]
+    fn reject_wrong_key_or_tampering() {
+        let key = CacheKey::from_hex(KEY).unwrap();
+        let mut encrypted = key.encrypt(b"witness").unwrap();
+
+        let other_key = CacheKey::from_hex(&KEY.replace("1f", "ff")).unwrap();
+        assert!(other_key.decrypt(&encrypted).is_err());
+
+        *encrypted.last_mut().unwrap() ^= 1;
+        assert!(key.decrypt(&encrypted).is_err());
+        assert!(key.decrypt(&encrypted[..MAGIC.len() + 4]).is_err());
+        assert!(key.decrypt(b"plaintext").is_err());
+    }
+
+    #[test]
+    fn invalid_key() {
+        assert!(CacheKey::from_hex("0x1234").is_err());
+        assert!(CacheKey::from_hex("not a key").is_err());
+    }
+}
@@ -16,12 +16,18 @@ use std::path::{Path, PathBuf};
 
 use crate::host::provider::{new_provider, Provider};
 
+pub mod beacon;
+pub mod channel_cache;
+pub mod derive_cache;
+pub mod encryption;
 pub mod mpt;
 pub mod preflight;
 pub mod provider;
 pub mod provider_db;
 pub mod rpc_db;
+pub mod tx_encoding;
 pub mod verify;
+pub mod witness_diff;
 
 pub fn cache_file_path(cache_path: &Path, network: &str, block_no: u64, ext: &str) -> PathBuf {
     let dir = cache_path.join(network);
@@ -15,10 +15,11 @@
 use std::{
     fmt::Debug,
     path::PathBuf,
+    slice,
     sync::{Arc, Mutex},
 };
 
-use anyhow::{anyhow, Context, Result};
+use anyhow::{anyhow, ensure, Context, Result};
 use ethers_core::types::{
     Block as EthersBlock, EIP1186ProofResponse, Transaction as EthersTransaction,
 };
@@ -41,8 +42,9 @@ use crate::{
         mpt::{is_not_included, mpt_from_proof, parse_proof, resolve_nodes, shorten_node_path},
         provider::{new_provider, BlockQuery},
         provider_db::ProviderDb,
+        tx_encoding::{assert_reencodes, raw_transaction},
     },
-    input::{BlockBuildInput, StateInput, StorageEntry},
+    input::{BlockBuildInput, BlockBundleInput, StateInput, StorageEntry},
     mem_db::MemDb,
 };
 
@@ -177,6 +179,102 @@ where
     }
 }
 
+/// The initial data required to build a bundle of consecutive blocks as returned by
+/// [preflight_bundle].
+#[derive(Debug, Clone)]
+pub struct BundleData<E: TxEssence> {
+    pub db: MemDb,
+    pub latest_db: MemDb,
+    pub parent_header: Header,
+    pub parent_proofs: HashMap<Address, EIP1186ProofResponse>,
+    pub state_inputs: Vec<StateInput<E>>,
+    pub headers: Vec<Header>,
+    pub proofs: Vec<HashMap<Address, EIP1186ProofResponse>>,
+    pub ancestor_headers: Vec<Header>,
+}
+
+/// Executes `block_count` consecutive blocks starting at `block_no` using the state from
+/// the RPC provider. It returns all the data required to build the blocks from the state
+/// of the parent of the first block.
+pub fn preflight_bundle<N: BlockBuilderStrategy>(
+    chain_spec: &ChainSpec,
+    cache_path: Option<PathBuf>,
+    rpc_url: Option<String>,
+    block_no: u64,
+    block_count: u64,
+) -> Result<BundleData<N::TxEssence>>
+where
+    N::TxEssence: TryFrom<EthersTransaction>,
+    <N::TxEssence as TryFrom<EthersTransaction>>::Error: Debug,
+{
+    ensure!(block_count > 0, "Block bundle is empty");
+    let parent_no = block_no
+        .checked_sub(1)
+        .context("the genesis block cannot be bundled")?;
+    let mut provider = new_provider(cache_path, rpc_url)?;
+
+    // Fetch the parent of the first block
+    let parent_block = provider.get_partial_block(&BlockQuery {
+        block_no: parent_no,
+    })?;
+    let parent_header: Header = parent_block.try_into().context("invalid parent block")?;
+
+    // Fetch the blocks of the bundle
+    let blocks = (block_no..block_no + block_count)
+        .map(|block_no| provider.get_full_block(&BlockQuery { block_no }))
+        .collect::<Result<Vec<_>>>()?;
+
+    // All blocks read the state of the first parent that they have not changed themselves
+    let mut provider_db = ProviderDb::new(provider, parent_header.number);
+
+    let mut state_inputs = Vec::with_capacity(blocks.len());
+    let mut headers: Vec<Header> = Vec::with_capacity(blocks.len());
+    for block in blocks {
+        let header: Header = block.clone().try_into().context("invalid block")?;
+        let parent = headers.last().unwrap_or(&parent_header).clone();
+        let input = new_preflight_input(block, parent)?;
+        state_inputs.push(input.state_input.clone());
+
+        info!("Executing block {} ...", header.number);
+        provider_db = BlockBuilder::new(chain_spec, input, None)
+            .with_db(provider_db)
+            .prepare_header::<N::HeaderPrepStrategy>()
+            .and_then(|builder| builder.execute_transactions::<N::TxExecStrategy>())
+            .with_context(|| format!("failed to execute block {}", header.number))?
+            .take_db()
+            .unwrap();
+        headers.push(header);
+    }
+
+    info!("Gathering inclusion proofs ...");
+
+    // Gather inclusion proofs for the initial state and the state after every block
+    let parent_proofs = provider_db.get_initial_proofs()?;
+    let proofs = headers
+        .iter()
+        .map(|header| provider_db.get_latest_proofs_at(header.number))
+        .collect::<Result<Vec<_>>>()?;
+
+    // Gather proofs for block history
+    let ancestor_headers = provider_db.get_ancestor_headers()?;
+
+    info!("Saving provider cache ...");
+
+    // Save the provider cache
+    provider_db.save_provider()?;
+
+    Ok(BundleData {
+        db: provider_db.get_initial_db().clone(),
+        latest_db: provider_db.get_latest_db().clone(),
+        parent_header,
+        parent_proofs,
+        state_inputs,
+        headers,
+        proofs,
+        ancestor_headers,
+    })
+}
+
 fn new_preflight_input<E>(
     block: EthersBlock<EthersTransaction>,
     parent_header: Header,
@@ -185,12 +283,14 @@ where
     E: TxEssence + TryFrom<EthersTransaction>,
     <E as TryFrom<EthersTransaction>>::Error: Debug,
 {
-    // convert each transaction
+    // convert each transaction, making sure that it encodes to the raw bytes of the node
     let transactions = block
         .transactions
         .into_iter()
         .enumerate()
         .map(|(i, tx)| {
+            let raw = raw_transaction(&tx).with_context(|| format!("transaction {i} invalid"))?;
+            assert_reencodes::<E>(&tx, &raw).with_context(|| format!("transaction {i} invalid"))?;
             tx.try_into()
                 .map_err(|err| anyhow!("transaction {i} invalid: {err:?}"))
         })
@@ -245,7 +345,7 @@ impl<E: TxEssence> TryFrom<Data<E>> for BlockBuildInput<E> {
         let (state_trie, storage) = proofs_to_tries(
             data.parent_header.state_root,
             data.parent_proofs,
-            data.proofs,
+            slice::from_ref(&data.proofs),
         )?;
 
         debug!(
@@ -279,10 +379,62 @@ impl<E: TxEssence> TryFrom<Data<E>> for BlockBuildInput<E> {
     }
 }
 
+/// Converts the [BundleData] returned by [preflight_bundle] into the [BlockBundleInput]
+/// of the bundle.
+impl<E: TxEssence> TryFrom<BundleData<E>> for BlockBundleInput<E> {
+    type Error = anyhow::Error;
+
+    fn try_from(data: BundleData<E>) -> Result<BlockBundleInput<E>> {
+        // collect the code from each account, including contracts created in the bundle
+        let mut contracts = HashSet::new();
+        for account in data.db.accounts.values() {
+            let code = account.info.code.clone().context("missing code")?;
+            if !code.is_empty() {
+                contracts.insert(code.bytecode);
+            }
+        }
+        for account in data.latest_db.accounts.values() {
+            if let Some(code) = &account.info.code {
+                if !code.is_empty() {
+                    contracts.insert(code.bytecode.clone());
+                }
+            }
+        }
+
+        // construct the sparse MPTs from the inclusion proofs
+        let (state_trie, mut storage) = proofs_to_tries(
+            data.parent_header.state_root,
+            data.parent_proofs,
+            &data.proofs,
+        )?;
+
+        // the slots of contracts created in the bundle are never read from the parent state
+        for (address, slots) in data.latest_db.storage_keys() {
+            if let Some((_, used_slots)) = storage.get_mut(&address) {
+                for slot in slots {
+                    if !used_slots.contains(&slot) {
+                        used_slots.push(slot);
+                    }
+                }
+            }
+        }
+
+        Ok(BlockBundleInput {
+            state_inputs: data.state_inputs,
+            parent_state_trie: state_trie,
+            parent_storage: storage,
+            contracts: contracts.into_iter().collect(),
+            ancestor_headers: data.ancestor_headers,
+        })
+    }
+}
+
+/// Constructs the sparse tries from the proofs of the parent state. The proofs of every
+/// subsequent state are used to assure that keys deleted in that state can be deleted.
 fn proofs_to_tries(
     state_root: B256,
     parent_proofs: HashMap<Address, EIP1186ProofResponse>,
-    proofs: HashMap<Address, EIP1186ProofResponse>,
+    proofs: &[HashMap<Address, EIP1186ProofResponse>],
 ) -> Result<(MptNode, HashMap<Address, StorageEntry>)> {
     // if no addresses are provided, return the trie only consisting of the state root
     if parent_proofs.is_empty() {
@@ -308,11 +460,18 @@ fn proofs_to_tries(
         });
 
         let fini_proofs = proofs
-            .get(&address)
-            .with_context(|| format!("missing fini_proofs for address {:#}", &address))?;
+            .iter()
+            .map(|proofs| {
+                proofs
+                    .get(&address)
+                    .with_context(|| format!("missing fini_proofs for address {:#}", address))
+            })
+            .collect::<Result<Vec<_>>>()?;
 
         // assure that addresses can be deleted from the state trie
-        add_orphaned_leafs(address, &fini_proofs.account_proof, &mut state_nodes)?;
+        for fini_proofs in &fini_proofs {
+            add_orphaned_leafs(address, &fini_proofs.account_proof, &mut state_nodes)?;
+        }
 
         // if no slots are provided, return the trie only consisting of the storage root
         let storage_root = from_ethers_h256(proof.storage_hash);
@@ -339,7 +498,7 @@ fn proofs_to_tries(
         }
 
         // assure that slots can be deleted from the storage trie
-        for storage_proof in &fini_proofs.storage_proof {
+        for storage_proof in fini_proofs.iter().flat_map(|p| &p.storage_proof) {
             let key = from_ethers_u256(storage_proof.key);
             add_orphaned_leafs(
                 key.to_be_bytes::<32>(),
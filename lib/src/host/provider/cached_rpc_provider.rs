@@ -20,19 +20,19 @@ use ethers_core::types::{
 };
 
 use super::{
-    file_provider::FileProvider, rpc_provider::RpcProvider, AccountQuery, BlockQuery, MutProvider,
+    file_provider::FileProvider, rpc_provider, AccountQuery, BlockId, BlockQuery, MutProvider,
     ProofQuery, Provider, StorageQuery,
 };
 
 pub struct CachedRpcProvider {
     cache: FileProvider,
-    rpc: RpcProvider,
+    rpc: Box<dyn Provider>,
 }
 
 impl CachedRpcProvider {
     pub fn new(cache_path: PathBuf, rpc_url: String) -> Result<Self> {
         let cache = FileProvider::new(cache_path).context("failed to init cache")?;
-        let rpc = RpcProvider::new(rpc_url).context("failed to init RPC")?;
+        let rpc = rpc_provider::connect(rpc_url).context("failed to init RPC")?;
 
         Ok(CachedRpcProvider { cache, rpc })
     }
@@ -43,6 +43,16 @@ impl Provider for CachedRpcProvider {
         self.cache.save()
     }
 
+    fn resolve_block_number(&mut self, block_id: &BlockId) -> Result<u64> {
+        // tags must always be resolved by the RPC node, as they change over time
+        let cache_out = self.cache.resolve_block_number(block_id);
+        if cache_out.is_ok() {
+            return cache_out;
+        }
+
+        self.rpc.resolve_block_number(block_id)
+    }
+
     fn get_full_block(&mut self, query: &BlockQuery) -> Result<Block<Transaction>> {
         let cache_out = self.cache.get_full_block(query);
         if cache_out.is_ok() {
@@ -13,21 +13,21 @@
 // limitations under the License.
 
 use std::{
-    collections::HashMap,
-    fs::{self, File},
-    io,
+    collections::{BTreeMap, HashMap},
+    fs, io,
     io::Write,
     path::{Path, PathBuf},
 };
 
-use anyhow::{anyhow, Context, Result};
+use anyhow::{anyhow, Result};
 use ethers_core::types::{
     Block, Bytes, EIP1186ProofResponse, Transaction, TransactionReceipt, H256, U256,
 };
 use flate2::{read::GzDecoder, write::GzEncoder, Compression};
 use serde::{Deserialize, Serialize};
 
-use super::{AccountQuery, BlockQuery, MutProvider, ProofQuery, Provider, StorageQuery};
+use super::{AccountQuery, BlockId, BlockQuery, MutProvider, ProofQuery, Provider, StorageQuery};
+use crate::host::encryption;
 
 #[derive(Clone, Default, Deserialize, Serialize)]
 pub struct FileProvider {
@@ -105,8 +105,8 @@ impl FileProvider {
     }
 
     fn read(file_path: PathBuf) -> Result<Self> {
-        let f = File::open(&file_path)?;
-        let mut out: Self = serde_json::from_reader(GzDecoder::new(f))?;
+        let bytes = encryption::read(&file_path)?;
+        let mut out: Self = serde_json::from_reader(GzDecoder::new(bytes.as_slice()))?;
         out.file_path = file_path;
         out.dirty = false;
 
@@ -115,30 +115,51 @@ impl FileProvider {
 
     pub fn save_to_file(&self, file_path: &Path) -> Result<()> {
         if self.dirty {
-            let mut encoder = flate2::write::GzEncoder::new(
-                File::create(file_path)
-                    .with_context(|| format!("Failed to create '{}'", file_path.display()))?,
-                flate2::Compression::best(),
-            );
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
             encoder.write_all(&serde_json::to_vec(self)?)?;
-            encoder.finish()?;
+            encryption::write(file_path, &encoder.finish()?)?;
         }
 
         Ok(())
     }
+
+    /// Returns the hashes of all cached blocks, ordered by block number.
+    pub fn block_hashes(&self) -> BTreeMap<u64, H256> {
+        self.partial_blocks
+            .iter()
+            .map(|(query, block)| (query, block.hash))
+            .chain(
+                self.full_blocks
+                    .iter()
+                    .map(|(query, block)| (query, block.hash)),
+            )
+            .filter_map(|(query, hash)| Some((query.block_no, hash?)))
+            .collect()
+    }
 }
 
 impl Provider for FileProvider {
     fn save(&self) -> Result<()> {
-        if self.dirty {
-            let f = File::create(&self.file_path)
-                .with_context(|| format!("Failed to create '{}'", self.file_path.display()))?;
-            let mut encoder = GzEncoder::new(f, Compression::best());
-            serde_json::to_writer(&mut encoder, &self)?;
-            encoder.finish()?;
-        }
+        self.save_to_file(&self.file_path)
+    }
 
-        Ok(())
+    fn resolve_block_number(&mut self, block_id: &BlockId) -> Result<u64> {
+        match block_id {
+            BlockId::Number(block_no) => Ok(*block_no),
+            BlockId::Hash(hash) => self
+                .partial_blocks
+                .iter()
+                .map(|(query, block)| (query, block.hash))
+                .chain(
+                    self.full_blocks
+                        .iter()
+                        .map(|(query, block)| (query, block.hash)),
+                )
+                .find(|(_, block_hash)| block_hash.as_ref() == Some(hash))
+                .map(|(query, _)| query.block_no)
+                .ok_or_else(|| anyhow!("No data for block {}", block_id)),
+            _ => Err(anyhow!("Cannot resolve block {} from the cache", block_id)),
+        }
     }
 
     fn get_full_block(&mut self, query: &BlockQuery) -> Result<Block<Transaction>> {
@@ -12,17 +12,75 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::{collections::BTreeSet, path::PathBuf};
+use std::{collections::BTreeSet, fmt, path::PathBuf, str::FromStr};
 
 use anyhow::{anyhow, Result};
 use ethers_core::types::{
-    Block, Bytes, EIP1186ProofResponse, Transaction, TransactionReceipt, H160, H256, U256,
+    Block, BlockNumber, Bytes, EIP1186ProofResponse, Transaction, TransactionReceipt, H160, H256,
+    U256,
 };
 use serde::{Deserialize, Serialize};
 
+pub mod audit;
 pub mod cached_rpc_provider;
 pub mod file_provider;
 pub mod rpc_provider;
+pub mod verify_cache;
+
+/// Identifies a block by its number, its hash or a tag.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BlockId {
+    Number(u64),
+    Hash(H256),
+    Latest,
+    Safe,
+    Finalized,
+}
+
+impl FromStr for BlockId {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "latest" => Ok(BlockId::Latest),
+            "safe" => Ok(BlockId::Safe),
+            "finalized" => Ok(BlockId::Finalized),
+            _ if s.starts_with("0x") => H256::from_str(s)
+                .map(BlockId::Hash)
+                .map_err(|err| format!("invalid block hash '{}': {}", s, err)),
+            _ => s.parse().map(BlockId::Number).map_err(|_| {
+                format!(
+                    "invalid block '{}': expected a number, a hash, latest, safe or finalized",
+                    s
+                )
+            }),
+        }
+    }
+}
+
+impl fmt::Display for BlockId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BlockId::Number(number) => write!(f, "{}", number),
+            BlockId::Hash(hash) => write!(f, "{:#x}", hash),
+            BlockId::Latest => write!(f, "latest"),
+            BlockId::Safe => write!(f, "safe"),
+            BlockId::Finalized => write!(f, "finalized"),
+        }
+    }
+}
+
+impl From<BlockId> for ethers_core::types::BlockId {
+    fn from(block_id: BlockId) -> Self {
+        match block_id {
+            BlockId::Number(number) => BlockNumber::Number(number.into()).into(),
+            BlockId::Hash(hash) => hash.into(),
+            BlockId::Latest => BlockNumber::Latest.into(),
+            BlockId::Safe => BlockNumber::Safe.into(),
+            BlockId::Finalized => BlockNumber::Finalized.into(),
+        }
+    }
+}
 
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize)]
 pub struct AccountQuery {
@@ -52,6 +110,9 @@ pub struct StorageQuery {
 pub trait Provider: Send {
     fn save(&self) -> Result<()>;
 
+    /// Returns the number of the identified block.
+    fn resolve_block_number(&mut self, block_id: &BlockId) -> Result<u64>;
+
     fn get_full_block(&mut self, query: &BlockQuery) -> Result<Block<Transaction>>;
     fn get_partial_block(&mut self, query: &BlockQuery) -> Result<Block<H256>>;
     fn get_block_receipts(&mut self, query: &BlockQuery) -> Result<Vec<TransactionReceipt>>;
@@ -80,9 +141,7 @@ pub fn new_file_provider(file_path: PathBuf) -> Result<Box<dyn Provider>> {
 }
 
 pub fn new_rpc_provider(rpc_url: String) -> Result<Box<dyn Provider>> {
-    let provider = rpc_provider::RpcProvider::new(rpc_url)?;
-
-    Ok(Box::new(provider))
+    rpc_provider::connect(rpc_url)
 }
 
 pub fn new_cached_rpc_provider(cache_path: PathBuf, rpc_url: String) -> Result<Box<dyn Provider>> {
@@ -102,3 +161,23 @@ pub fn new_provider(
         (None, None) => Err(anyhow!("No cache_path or rpc_url given")),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_block_id() {
+        assert_eq!("123".parse(), Ok(BlockId::Number(123)));
+        assert_eq!("latest".parse(), Ok(BlockId::Latest));
+        assert_eq!("safe".parse(), Ok(BlockId::Safe));
+        assert_eq!("finalized".parse(), Ok(BlockId::Finalized));
+
+        let hash = format!("{:#x}", H256::repeat_byte(0xab));
+        assert_eq!(hash.parse(), Ok(BlockId::Hash(H256::repeat_byte(0xab))));
+        assert_eq!(BlockId::Hash(H256::repeat_byte(0xab)).to_string(), hash);
+
+        assert!("pending".parse::<BlockId>().is_err());
+        assert!("0x12".parse::<BlockId>().is_err());
+    }
+}
@@ -12,47 +12,155 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use anyhow::{anyhow, Result};
+use std::{
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::Duration,
+};
+
+use anyhow::{anyhow, bail, Context, Result};
 use ethers_core::types::{
     Block, Bytes, EIP1186ProofResponse, Transaction, TransactionReceipt, H256, U256,
 };
-use ethers_providers::{Http, Middleware, RetryClient};
+use ethers_providers::{
+    Http, HttpRateLimitRetryPolicy, Ipc, JsonRpcClient, Middleware, RetryClient,
+};
+use hashbrown::HashMap;
 use log::debug;
+use once_cell::sync::Lazy;
+
+use super::{audit, AccountQuery, BlockId, BlockQuery, ProofQuery, Provider, StorageQuery};
+
+/// HTTP client shared by all RPC providers, so that connections to the same node are
+/// pooled and kept alive across providers.
+static HTTP_CLIENT: Lazy<reqwest::Client> = Lazy::new(|| {
+    reqwest::Client::builder()
+        .pool_idle_timeout(Duration::from_secs(90))
+        .tcp_keepalive(Duration::from_secs(60))
+        .build()
+        .expect("failed to build HTTP client")
+});
 
-use super::{AccountQuery, BlockQuery, ProofQuery, Provider, StorageQuery};
+/// IPC connections by socket path; each connection is shared by all its providers.
+static IPC_CONNECTIONS: Lazy<Mutex<HashMap<PathBuf, Ipc>>> = Lazy::new(Default::default);
 
-pub struct RpcProvider {
-    http_client: ethers_providers::Provider<RetryClient<Http>>,
+pub struct RpcProvider<C: JsonRpcClient = RetryClient<Http>> {
+    client: ethers_providers::Provider<C>,
     tokio_handle: tokio::runtime::Handle,
+    /// Endpoint recorded in the audit log.
+    endpoint: String,
 }
 
 impl RpcProvider {
+    /// Creates a provider for the HTTP(S) endpoint at the given URL.
     pub fn new(rpc_url: String) -> Result<Self> {
-        let http_client =
-            ethers_providers::Provider::<RetryClient<Http>>::new_client(&rpc_url, 3, 500)?;
+        let url = reqwest::Url::parse(&rpc_url).context("invalid RPC URL")?;
+        let http = Http::new_with_client(url, HTTP_CLIENT.clone());
+        let client = ethers_providers::Provider::new(RetryClient::new(
+            http,
+            Box::new(HttpRateLimitRetryPolicy),
+            3,
+            500,
+        ));
         let tokio_handle = tokio::runtime::Handle::current();
 
         Ok(RpcProvider {
-            http_client,
+            client,
             tokio_handle,
+            endpoint: audit::redact_endpoint(&rpc_url),
         })
     }
 }
 
-impl Provider for RpcProvider {
+impl RpcProvider<Ipc> {
+    /// Creates a provider for the IPC socket at the given path, reusing an existing
+    /// connection to the same socket.
+    pub fn new_ipc(path: &Path) -> Result<Self> {
+        let tokio_handle = tokio::runtime::Handle::current();
+
+        let mut connections = IPC_CONNECTIONS.lock().unwrap();
+        let ipc = match connections.get(path) {
+            Some(ipc) => ipc.clone(),
+            None => {
+                let ipc = tokio_handle
+                    .block_on(Ipc::connect(path))
+                    .with_context(|| format!("failed to connect to {}", path.display()))?;
+                connections.insert(path.to_path_buf(), ipc.clone());
+                ipc
+            }
+        };
+
+        Ok(RpcProvider {
+            client: ethers_providers::Provider::new(ipc),
+            tokio_handle,
+            endpoint: path.display().to_string(),
+        })
+    }
+}
+
+/// Creates a provider for the given endpoint, selecting the transport by its scheme:
+/// `http://` and `https://` URLs use HTTP, while `ipc://` URLs and plain file paths use
+/// IPC.
+pub fn connect(rpc_url: String) -> Result<Box<dyn Provider>> {
+    if rpc_url.starts_with("http://") || rpc_url.starts_with("https://") {
+        return Ok(Box::new(RpcProvider::new(rpc_url)?));
+    }
+    if let Some(path) = rpc_url.strip_prefix("ipc://") {
+        return Ok(Box::new(RpcProvider::new_ipc(Path::new(path))?));
+    }
+    if !rpc_url.contains("://") {
+        return Ok(Box::new(RpcProvider::new_ipc(Path::new(&rpc_url))?));
+    }
+    bail!("unsupported RPC URL '{}': expected HTTP(S) or IPC", rpc_url)
+}
+
+impl<C: JsonRpcClient + 'static> Provider for RpcProvider<C> {
     fn save(&self) -> Result<()> {
         Ok(())
     }
 
+    fn resolve_block_number(&mut self, block_id: &BlockId) -> Result<u64> {
+        if let BlockId::Number(block_no) = block_id {
+            return Ok(*block_no);
+        }
+        debug!("Querying RPC for block number: {}", block_id);
+
+        let response = self
+            .tokio_handle
+            .block_on(self.client.get_block(*block_id))?;
+
+        let block = response.with_context(|| format!("No data for block {}", block_id))?;
+        let block_no = block
+            .number
+            .with_context(|| format!("No data for block {}", block_id))?
+            .as_u64();
+        let method = match block_id {
+            BlockId::Hash(_) => "eth_getBlockByHash",
+            _ => "eth_getBlockByNumber",
+        };
+        audit::record(&self.endpoint, method, block_no, block.hash, &block);
+
+        Ok(block_no)
+    }
+
     fn get_full_block(&mut self, query: &BlockQuery) -> Result<Block<Transaction>> {
         debug!("Querying RPC for full block: {:?}", query);
 
         let response = self
             .tokio_handle
-            .block_on(self.http_client.get_block_with_txs(query.block_no))?;
+            .block_on(self.client.get_block_with_txs(query.block_no))?;
 
         match response {
-            Some(out) => Ok(out),
+            Some(out) => {
+                audit::record(
+                    &self.endpoint,
+                    "eth_getBlockByNumber",
+                    query.block_no,
+                    out.hash,
+                    &out,
+                );
+                Ok(out)
+            }
             None => Err(anyhow!("No data for {:?}", query)),
         }
     }
@@ -62,10 +170,19 @@ impl Provider for RpcProvider {
 
         let response = self
             .tokio_handle
-            .block_on(self.http_client.get_block(query.block_no))?;
+            .block_on(self.client.get_block(query.block_no))?;
 
         match response {
-            Some(out) => Ok(out),
+            Some(out) => {
+                audit::record(
+                    &self.endpoint,
+                    "eth_getBlockByNumber",
+                    query.block_no,
+                    out.hash,
+                    &out,
+                );
+                Ok(out)
+            }
             None => Err(anyhow!("No data for {:?}", query)),
         }
     }
@@ -75,7 +192,15 @@ impl Provider for RpcProvider {
 
         let response = self
             .tokio_handle
-            .block_on(self.http_client.get_block_receipts(query.block_no))?;
+            .block_on(self.client.get_block_receipts(query.block_no))?;
+        let block_hash = response.first().and_then(|receipt| receipt.block_hash);
+        audit::record(
+            &self.endpoint,
+            "eth_getBlockReceipts",
+            query.block_no,
+            block_hash,
+            &response,
+        );
 
         Ok(response)
     }
@@ -83,11 +208,12 @@ impl Provider for RpcProvider {
     fn get_proof(&mut self, query: &ProofQuery) -> Result<EIP1186ProofResponse> {
         debug!("Querying RPC for inclusion proof: {:?}", query);
 
-        let out = self.tokio_handle.block_on(self.http_client.get_proof(
+        let out = self.tokio_handle.block_on(self.client.get_proof(
             query.address,
             query.indices.iter().cloned().collect(),
             Some(query.block_no.into()),
         ))?;
+        audit::record(&self.endpoint, "eth_getProof", query.block_no, None, &out);
 
         Ok(out)
     }
@@ -96,9 +222,16 @@ impl Provider for RpcProvider {
         debug!("Querying RPC for transaction count: {:?}", query);
 
         let out = self.tokio_handle.block_on(
-            self.http_client
+            self.client
                 .get_transaction_count(query.address, Some(query.block_no.into())),
         )?;
+        audit::record(
+            &self.endpoint,
+            "eth_getTransactionCount",
+            query.block_no,
+            None,
+            &out,
+        );
 
         Ok(out)
     }
@@ -107,9 +240,10 @@ impl Provider for RpcProvider {
         debug!("Querying RPC for balance: {:?}", query);
 
         let out = self.tokio_handle.block_on(
-            self.http_client
+            self.client
                 .get_balance(query.address, Some(query.block_no.into())),
         )?;
+        audit::record(&self.endpoint, "eth_getBalance", query.block_no, None, &out);
 
         Ok(out)
     }
@@ -118,9 +252,10 @@ impl Provider for RpcProvider {
         debug!("Querying RPC for code: {:?}", query);
 
         let out = self.tokio_handle.block_on(
-            self.http_client
+            self.client
                 .get_code(query.address, Some(query.block_no.into())),
         )?;
+        audit::record(&self.endpoint, "eth_getCode", query.block_no, None, &out);
 
         Ok(out)
     }
@@ -128,12 +263,38 @@ impl Provider for RpcProvider {
     fn get_storage(&mut self, query: &StorageQuery) -> Result<H256> {
         debug!("Querying RPC for storage: {:?}", query);
 
-        let out = self.tokio_handle.block_on(self.http_client.get_storage_at(
+        let out = self.tokio_handle.block_on(self.client.get_storage_at(
             query.address,
             query.index,
             Some(query.block_no.into()),
         ))?;
+        audit::record(
+            &self.endpoint,
+            "eth_getStorageAt",
+            query.block_no,
+            None,
+            &out,
+        );
 
         Ok(out)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn connect_by_scheme() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let _guard = runtime.enter();
+
+        assert!(connect("http://localhost:8545".to_string()).is_ok());
+        assert!(connect("https://localhost:8545".to_string()).is_ok());
+        assert!(connect("ws://localhost:8546".to_string()).is_err());
+
+        // the IPC socket must exist
+        assert!(connect("ipc:///nonexistent/geth.ipc".to_string()).is_err());
+        assert!(connect("/nonexistent/geth.ipc".to_string()).is_err());
+    }
+}
@@ -0,0 +1,136 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Detection of RPC providers serving data inconsistent with an existing cache.
+
+use anyhow::{Context, Result};
+use ethers_core::types::H256;
+use log::debug;
+
+use super::{file_provider::FileProvider, BlockQuery, Provider};
+
+/// A cached block whose hash differs from the one currently served by the RPC node.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockHashMismatch {
+    pub block_no: u64,
+    pub cached_hash: H256,
+    pub rpc_hash: H256,
+}
+
+/// Returns up to `sample_size` evenly spaced items, including the first and the last.
+pub fn sample<T: Copy>(items: &[T], sample_size: usize) -> Vec<T> {
+    match sample_size {
+        0 => vec![],
+        _ if sample_size >= items.len() => items.to_vec(),
+        1 => vec![items[0]],
+        _ => (0..sample_size)
+            .map(|i| items[i * (items.len() - 1) / (sample_size - 1)])
+            .collect(),
+    }
+}
+
+/// Refetches a sample of the blocks in the cache from the RPC provider and returns all
+/// blocks whose hashes differ.
+pub fn verify_cached_blocks(
+    cache: &FileProvider,
+    rpc: &mut dyn Provider,
+    sample_size: usize,
+) -> Result<Vec<BlockHashMismatch>> {
+    let cached_hashes = cache.block_hashes();
+    let block_numbers: Vec<u64> = cached_hashes.keys().copied().collect();
+
+    let mut mismatches = vec![];
+    for block_no in sample(&block_numbers, sample_size) {
+        let cached_hash = cached_hashes[&block_no];
+        let rpc_hash = rpc
+            .get_partial_block(&BlockQuery { block_no })
+            .with_context(|| format!("Failed to refetch block {}", block_no))?
+            .hash
+            .with_context(|| format!("RPC returned block {} without hash", block_no))?;
+        debug!(
+            "Block {}: cached {:#x}, RPC {:#x}",
+            block_no, cached_hash, rpc_hash
+        );
+        if cached_hash != rpc_hash {
+            mismatches.push(BlockHashMismatch {
+                block_no,
+                cached_hash,
+                rpc_hash,
+            });
+        }
+    }
+
+    Ok(mismatches)
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers_core::types::Block;
+
+    use super::*;
+    use crate::host::provider::MutProvider;
+
+    fn provider(blocks: impl IntoIterator<Item = (u64, u8)>) -> FileProvider {
+        let mut provider = FileProvider::default();
+        for (block_no, hash) in blocks {
+            provider.insert_partial_block(
+                BlockQuery { block_no },
+                Block {
+                    number: Some(block_no.into()),
+                    hash: Some(H256::repeat_byte(hash)),
+                    ..Default::default()
+                },
+            );
+        }
+        provider
+    }
+
+    #[test]
+    fn sample_evenly() {
+        let items: Vec<u32> = (0..10).collect();
+        assert_eq!(sample(&items, 0), Vec::<u32>::new());
+        assert_eq!(sample(&items, 1), vec![0]);
+        assert_eq!(sample(&items, 2), vec![0, 9]);
+        assert_eq!(sample(&items, 4), vec![0, 3, 6, 9]);
+        assert_eq!(sample(&items, 20), items);
+        assert_eq!(sample::<u32>(&[], 3), Vec::<u32>::new());
+    }
+
+    #[test]
+    fn detect_mismatch() {
+        let cache = provider([(1, 1), (2, 2), (3, 3)]);
+
+        let mut consistent = provider([(1, 1), (2, 2), (3, 3)]);
+        assert!(verify_cached_blocks(&cache, &mut consistent, 3)
+            .unwrap()
+            .is_empty());
+
+        let mut inconsistent = provider([(1, 1), (2, 0xff), (3, 3)]);
+        assert_eq!(
+            verify_cached_blocks(&cache, &mut inconsistent, 3).unwrap(),
+            vec![BlockHashMismatch {
+                block_no: 2,
+                cached_hash: H256::repeat_byte(2),
+                rpc_hash: H256::repeat_byte(0xff),
+            }]
+        );
+        // the sample of two blocks does not contain the inconsistent block
+        assert!(verify_cached_blocks(&cache, &mut inconsistent, 2)
+            .unwrap()
+            .is_empty());
+
+        let mut missing = provider([(1, 1)]);
+        assert!(verify_cached_blocks(&cache, &mut missing, 3).is_err());
+    }
+}
@@ -16,6 +16,7 @@ extern crate alloc;
 
 use std::collections::BTreeSet;
 
+use anyhow::Context;
 use ethers_core::types::{EIP1186ProofResponse, H160, H256};
 use hashbrown::HashMap;
 use revm::{
@@ -96,6 +97,15 @@ impl ProviderDb {
 
     pub fn get_latest_proofs(
         &mut self,
+    ) -> Result<HashMap<Address, EIP1186ProofResponse>, anyhow::Error> {
+        self.get_latest_proofs_at(self.block_no + 1)
+    }
+
+    /// Returns the proofs of all accessed accounts and storage slots after the given
+    /// block.
+    pub fn get_latest_proofs_at(
+        &mut self,
+        block_no: u64,
     ) -> Result<HashMap<Address, EIP1186ProofResponse>, anyhow::Error> {
         let mut storage_keys = self.initial_db.storage_keys();
 
@@ -108,7 +118,7 @@ impl ProviderDb {
             }
         }
 
-        self.get_proofs(self.block_no + 1, storage_keys)
+        self.get_proofs(block_no, storage_keys)
     }
 
     pub fn get_ancestor_headers(&mut self) -> Result<Vec<Header>, anyhow::Error> {
@@ -118,16 +128,16 @@ impl ProviderDb {
             .keys()
             .min()
             .unwrap_or(&self.block_no);
-        let headers = (*earliest_block..self.block_no)
+        let headers: Vec<Header> = (*earliest_block..self.block_no)
             .rev()
             .map(|block_no| {
                 self.provider
                     .get_partial_block(&BlockQuery { block_no })
-                    .expect("Failed to retrieve ancestor block")
+                    .with_context(|| format!("failed to retrieve ancestor block {}", block_no))?
                     .try_into()
-                    .expect("Failed to convert ethers block to zeth block")
+                    .context("failed to convert ethers block to zeth block")
             })
-            .collect();
+            .collect::<anyhow::Result<_>>()?;
         Ok(headers)
     }
 }
@@ -15,19 +15,24 @@
 use std::path::PathBuf;
 
 use anyhow::Context;
+use log::warn;
 use zeth_primitives::{
     block::Header,
-    transactions::{ethereum::EthereumTxEssence, optimism::OptimismTxEssence},
-    Address,
+    ethers::validate_log_indices,
+    receipt::{validate_cumulative_gas_used, Receipt},
+    transactions::{ethereum::EthereumTxEssence, optimism::OptimismTxEssence, Transaction},
+    Address, B256,
 };
 
 use crate::{
     host::{
+        beacon::{self, BeaconClient},
         cache_file_path,
         provider::{new_provider, BlockQuery},
     },
     optimism::{
-        batcher_db::{BatcherDb, BlockInput, MemDb},
+        batcher_db::{BatcherDb, BlockInput, EthGapError, MemDb, ValidationPolicy},
+        blob::BlobSidecar,
         config::ChainConfig,
         deposits, system_config,
     },
@@ -45,12 +50,23 @@ fn op_cache_path(cache: &Option<PathBuf>, block_no: u64) -> Option<PathBuf> {
         .map(|dir| cache_file_path(dir, "optimism", block_no, "json.gz"))
 }
 
+fn beacon_cache_path(cache: &Option<PathBuf>, block_no: u64) -> Option<PathBuf> {
+    cache
+        .as_ref()
+        .map(|dir| cache_file_path(dir, "beacon", block_no, "json.gz"))
+}
+
 pub struct RpcDb {
     deposit_contract: Address,
     system_config_contract: Address,
+    batch_inbox: Address,
+    ecotone_time: Option<u64>,
     eth_rpc_url: Option<String>,
     op_rpc_url: Option<String>,
+    beacon_rpc_url: Option<String>,
+    beacon: Option<BeaconClient>,
     cache: Option<PathBuf>,
+    validation_policy: ValidationPolicy,
     mem_db: MemDb,
 }
 
@@ -64,16 +80,87 @@ impl RpcDb {
         RpcDb {
             deposit_contract: config.deposit_contract,
             system_config_contract: config.system_config_contract,
+            batch_inbox: config.batch_inbox,
+            ecotone_time: config.ecotone_time,
             eth_rpc_url,
             op_rpc_url,
+            beacon_rpc_url: None,
+            beacon: None,
             cache,
+            validation_policy: ValidationPolicy::Strict,
             mem_db: MemDb::new(),
         }
     }
 
+    /// Sets the policy for non-critical mismatches of the RPC data. The blocks are still
+    /// validated strictly once the [MemDb] is read by the guests.
+    pub fn with_validation_policy(mut self, validation_policy: ValidationPolicy) -> Self {
+        self.validation_policy = validation_policy;
+        self
+    }
+
+    /// Sets the beacon node serving the blobs of the batcher transactions after Ecotone.
+    pub fn with_beacon_rpc_url(mut self, beacon_rpc_url: Option<String>) -> Self {
+        self.beacon_rpc_url = beacon_rpc_url;
+        self
+    }
+
     pub fn get_mem_db(self) -> MemDb {
         self.mem_db
     }
+
+    /// Returns the sidecars of the blobs referenced by the batcher transactions of the
+    /// given Ethereum block, which are only read from blobs after Ecotone.
+    fn get_blob_sidecars(
+        &mut self,
+        block_header: &Header,
+        transactions: &[Transaction<EthereumTxEssence>],
+    ) -> anyhow::Result<Vec<BlobSidecar>> {
+        let block_no = block_header.number;
+        let timestamp: u64 = block_header.timestamp.try_into()?;
+        if !matches!(self.ecotone_time, Some(ecotone_time) if timestamp >= ecotone_time) {
+            return Ok(vec![]);
+        }
+        let versioned_hashes: Vec<B256> = transactions
+            .iter()
+            .filter_map(|tx| match &tx.essence {
+                EthereumTxEssence::Eip4844(blob_tx) if blob_tx.to == self.batch_inbox => {
+                    Some(blob_tx.blob_versioned_hashes.iter().copied())
+                }
+                _ => None,
+            })
+            .flatten()
+            .collect();
+        if versioned_hashes.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let cache_path = beacon_cache_path(&self.cache, block_no);
+        if let Some(cache_path) = &cache_path {
+            if let Some(sidecars) = beacon::read_cached_sidecars(cache_path)? {
+                return Ok(sidecars);
+            }
+        }
+        let beacon = match &mut self.beacon {
+            Some(beacon) => beacon,
+            None => {
+                let beacon_rpc_url = self.beacon_rpc_url.clone().with_context(|| {
+                    format!(
+                        "Eth block {} contains blobs, but no beacon RPC URL is set",
+                        block_no
+                    )
+                })?;
+                self.beacon.insert(BeaconClient::new(beacon_rpc_url)?)
+            }
+        };
+        let sidecars = beacon
+            .get_blob_sidecars(timestamp, &versioned_hashes)
+            .with_context(|| format!("failed to fetch the blobs of eth block {}", block_no))?;
+        if let Some(cache_path) = &cache_path {
+            beacon::write_cached_sidecars(cache_path, &sidecars)?;
+        }
+        Ok(sidecars)
+    }
 }
 
 impl BatcherDb for RpcDb {
@@ -81,6 +168,10 @@ impl BatcherDb for RpcDb {
         Ok(())
     }
 
+    fn validation_policy(&self) -> ValidationPolicy {
+        self.validation_policy
+    }
+
     fn get_full_op_block(
         &mut self,
         block_no: u64,
@@ -100,6 +191,7 @@ impl BatcherDb for RpcDb {
                     .map(|tx| tx.try_into().unwrap())
                     .collect(),
                 receipts: None,
+                blob_sidecars: vec![],
             }
         };
         self.mem_db.full_op_block.insert(block_no, block.clone());
@@ -139,24 +231,33 @@ impl BatcherDb for RpcDb {
                 system_config::can_contain(&self.system_config_contract, &block_header.logs_bloom);
             let receipts = if can_contain_config || can_contain_deposits {
                 let receipts = provider.get_block_receipts(&query)?;
-                Some(
-                    receipts
-                        .into_iter()
-                        .map(|receipt| receipt.try_into())
-                        .collect::<anyhow::Result<Vec<_>, _>>()
-                        .context("invalid receipt")?,
-                )
+                // deposits are identified by the index of their log within the block
+                self.validation_policy
+                    .check(validate_log_indices(&receipts).context("invalid receipt logs"))?;
+                let receipts: Vec<Receipt> = receipts
+                    .into_iter()
+                    .map(|receipt| receipt.try_into())
+                    .collect::<anyhow::Result<Vec<_>, _>>()
+                    .context("invalid receipt")?;
+                self.validation_policy.check(
+                    validate_cumulative_gas_used(&receipts, block_header.gas_used)
+                        .context("invalid receipt gas"),
+                )?;
+                Some(receipts)
             } else {
                 None
             };
+            let transactions: Vec<_> = ethers_block
+                .transactions
+                .into_iter()
+                .map(|tx| tx.try_into().unwrap())
+                .collect();
+            let blob_sidecars = self.get_blob_sidecars(&block_header, &transactions)?;
             BlockInput {
                 block_header,
-                transactions: ethers_block
-                    .transactions
-                    .into_iter()
-                    .map(|tx| tx.try_into().unwrap())
-                    .collect(),
+                transactions,
                 receipts,
+                blob_sidecars,
             }
         };
         self.mem_db.full_eth_block.insert(block_no, block);
@@ -164,3 +265,66 @@ impl BatcherDb for RpcDb {
         self.mem_db.get_full_eth_block(block_no)
     }
 }
+
+/// A [BatcherDb] serving the blocks of a [MemDb], which backfills the Ethereum blocks
+/// missing from it using an [RpcDb].
+pub struct BackfillDb {
+    mem_db: MemDb,
+    rpc_db: RpcDb,
+    /// Numbers of the backfilled Ethereum blocks.
+    pub backfilled: Vec<u64>,
+}
+
+impl BackfillDb {
+    pub fn new(mem_db: MemDb, rpc_db: RpcDb) -> Self {
+        BackfillDb {
+            mem_db,
+            rpc_db,
+            backfilled: Vec::new(),
+        }
+    }
+
+    pub fn get_mem_db(self) -> MemDb {
+        self.mem_db
+    }
+}
+
+impl BatcherDb for BackfillDb {
+    fn validate(&self, config: &ChainConfig) -> anyhow::Result<()> {
+        self.mem_db.validate(config)
+    }
+
+    fn validation_policy(&self) -> ValidationPolicy {
+        self.rpc_db.validation_policy()
+    }
+
+    fn get_full_op_block(
+        &mut self,
+        block_no: u64,
+    ) -> anyhow::Result<BlockInput<OptimismTxEssence>> {
+        self.mem_db.get_full_op_block(block_no)
+    }
+
+    fn get_op_block_header(&mut self, block_no: u64) -> anyhow::Result<Header> {
+        self.mem_db.get_op_block_header(block_no)
+    }
+
+    fn get_full_eth_block(
+        &mut self,
+        block_no: u64,
+    ) -> anyhow::Result<&BlockInput<EthereumTxEssence>> {
+        if !self.mem_db.full_eth_block.contains_key(&block_no) {
+            let gap = EthGapError::MissingBlock(block_no);
+            warn!("{}, backfilling it from the RPC node", gap);
+            let eth_block = self
+                .rpc_db
+                .get_full_eth_block(block_no)
+                .context(gap)?
+                .clone();
+            self.mem_db.full_eth_block.insert(block_no, eth_block);
+            self.backfilled.push(block_no);
+        }
+
+        self.mem_db.get_full_eth_block(block_no)
+    }
+}
@@ -0,0 +1,179 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt::Debug;
+
+use anyhow::{anyhow, bail, ensure, Result};
+use ethers_core::types::{Bytes, Transaction as EthersTransaction};
+use zeth_primitives::{
+    ethers::from_ethers_h256,
+    keccak::keccak,
+    transactions::{Transaction, TxEssence},
+};
+
+/// Returns the raw bytes of the transaction as committed to by the node.
+///
+/// The bytes are reconstructed from the RPC fields and accepted only if they hash to the
+/// transaction hash returned by the node.
+pub fn raw_transaction(tx: &EthersTransaction) -> Result<Bytes> {
+    let raw = tx.rlp();
+    ensure!(
+        from_ethers_h256(tx.hash) == keccak(&raw),
+        "raw bytes of transaction {:#x} do not match its hash",
+        tx.hash
+    );
+    Ok(raw)
+}
+
+/// Ensures that the zeth encoding of the RPC transaction reproduces its raw bytes
+/// exactly.
+///
+/// Any divergence would otherwise only surface as an invalid transaction root inside the
+/// guest.
+pub fn assert_reencodes<E>(tx: &EthersTransaction, raw_rlp: &[u8]) -> Result<()>
+where
+    E: TxEssence + TryFrom<EthersTransaction>,
+    <E as TryFrom<EthersTransaction>>::Error: Debug,
+{
+    let transaction: Transaction<E> = tx
+        .clone()
+        .try_into()
+        .map_err(|err| anyhow!("transaction {:#x} invalid: {:?}", tx.hash, err))?;
+    let encoded = alloy_rlp::encode(&transaction);
+    if encoded != raw_rlp {
+        let offset = encoded
+            .iter()
+            .zip(raw_rlp)
+            .position(|(a, b)| a != b)
+            .unwrap_or(encoded.len().min(raw_rlp.len()));
+        bail!(
+            "transaction {:#x} (type {}) re-encodes to {} bytes instead of {}, diverging at byte {}",
+            tx.hash,
+            transaction.essence.tx_type(),
+            encoded.len(),
+            raw_rlp.len(),
+            offset
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use zeth_primitives::transactions::{ethereum::EthereumTxEssence, optimism::OptimismTxEssence};
+
+    use super::*;
+
+    fn legacy_tx() -> EthersTransaction {
+        serde_json::from_value(json!({
+            "hash": "0xa71ad5ec416a69aa5f5100ba655cc75501dadf413e060abfb3d80607128fb072",
+            "nonce": "0x148632",
+            "blockHash": "0x17cf53189035bbae5bce5c844355badd701aa9d2dd4b4f5ab1f9f0e8dd9fea5b",
+            "blockNumber": "0x103ee77",
+            "transactionIndex": "0x5",
+            "from": "0x6dfc34609a05bc22319fa4cce1d1e2929548c0d7",
+            "to": "0xa349e1de10dbabaa2334c8598bd152388488e5ef",
+            "value": "0x96b5c509406bc00",
+            "gasPrice": "0x5908d0f00",
+            "gas": "0x5208",
+            "input": "0x",
+            "v": "0x26",
+            "r": "0x257c3c4b66a65e3da35057cdc119af0ddba1e4bf5c6ae4d49979a972523710bd",
+            "s": "0x10b3a89afc6f2f2d78e3961edda5e88605ae29b43acd9aade63383220176c255",
+            "type": "0x0",
+            "chainId": "0x1"
+        }))
+        .unwrap()
+    }
+
+    fn eip1559_tx() -> EthersTransaction {
+        serde_json::from_value(json!({
+            "hash": "0x3addc17bebc62d441d324e2de6d03f5666ee581bd5f94094453bffcb6d807d30",
+            "nonce": "0x813d",
+            "blockHash": "0x17cf53189035bbae5bce5c844355badd701aa9d2dd4b4f5ab1f9f0e8dd9fea5b",
+            "blockNumber": "0x103ee77",
+            "transactionIndex": "0x0",
+            "from": "0xdd8ef55055e01d39cb87571bd38c43ab9b5903a5",
+            "to": "0xf8b721bff6bf7095a0e10791ce8f998baa254fd0",
+            "value": "0x0",
+            "gasPrice": "0xcc0bf28259",
+            "gas": "0x55f0",
+            "input": "0x",
+            "v": "0x0",
+            "r": "0xf936c5a051ae5a1a5498c6001d946dccbe2b5a80835276a2bf2de545568c1b04",
+            "s": "0x15a6dd5d941f53cf8c47077588a2e1559cc510d1355bf03a3735e651fabc1213",
+            "type": "0x2",
+            "accessList": [],
+            "maxPriorityFeePerGas": "0xc756425ca9",
+            "maxFeePerGas": "0x2b26cf94db6",
+            "chainId": "0x1"
+        }))
+        .unwrap()
+    }
+
+    fn deposit_tx() -> EthersTransaction {
+        serde_json::from_value(json!({
+            "hash": "0x99066a281b87021f0f54073be92b24daf660d23ada0efc3126e28ae4d4d3cd24",
+            "nonce": "0x260d07",
+            "blockHash": "0x74ba2ecbd7e8d85998c32c446f1dc3616d994b40e23c3bc51344b650f959d31e",
+            "blockNumber": "0x66bcf7f",
+            "transactionIndex": "0x0",
+            "from": "0xdeaddeaddeaddeaddeaddeaddeaddeaddead0001",
+            "to": "0x4200000000000000000000000000000000000015",
+            "value": "0x0",
+            "gasPrice": "0x0",
+            "gas": "0xf4240",
+            "input": "0x015d8eb90000000000000000000000000000000000000000000000000000000001101ed60000000000000000000000000000000000000000000000000000000064cb786b00000000000000000000000000000000000000000000000000000003a09bf636935e3dd6de6916fd57a01e6e1d97481ea99010e1ab12c09910fd975fd356896c00000000000000000000000000000000000000000000000000000000000000020000000000000000000000006887246668a3b87f54deb3b94ba47a6f63f3298500000000000000000000000000000000000000000000000000000000000000bc00000000000000000000000000000000000000000000000000000000000a6fe0",
+            "v": "0x0",
+            "r": "0x0",
+            "s": "0x0",
+            "sourceHash": "0x738329c3f7cc6304f2b6103bb995b108bf85708e5e04427fb9f88bed2d25c2c5",
+            "mint": "0x0",
+            "isSystemTx": false,
+            "type": "0x7e"
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn reencodes() {
+        for tx in [legacy_tx(), eip1559_tx()] {
+            let raw = raw_transaction(&tx).unwrap();
+            assert_reencodes::<EthereumTxEssence>(&tx, &raw).unwrap();
+            assert_reencodes::<OptimismTxEssence>(&tx, &raw).unwrap();
+        }
+        let tx = deposit_tx();
+        let raw = raw_transaction(&tx).unwrap();
+        assert_reencodes::<OptimismTxEssence>(&tx, &raw).unwrap();
+    }
+
+    #[test]
+    fn divergent_encoding() {
+        let tx = eip1559_tx();
+        let mut raw = raw_transaction(&tx).unwrap().to_vec();
+        let last = raw.len() - 1;
+        raw[last] ^= 1;
+        assert!(assert_reencodes::<EthereumTxEssence>(&tx, &raw).is_err());
+        raw.push(0);
+        assert!(assert_reencodes::<EthereumTxEssence>(&tx, &raw).is_err());
+    }
+
+    #[test]
+    fn mismatched_hash() {
+        let mut tx = legacy_tx();
+        tx.nonce += 1.into();
+        assert!(raw_transaction(&tx).is_err());
+    }
+}
@@ -0,0 +1,574 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Structural diffing of witnesses, used to diagnose mismatched roots.
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt,
+};
+
+use zeth_primitives::{
+    alloy_rlp,
+    block::Header,
+    transactions::TxEssence,
+    trie::{MptNode, MptNodeData, MptNodeReference},
+    Address, B256,
+};
+
+use crate::{
+    input::BlockBuildInput,
+    optimism::batcher_db::{BlockInput, MemDb},
+};
+
+/// Identifies one of the two witnesses being compared.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+impl fmt::Display for Side {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Side::Left => write!(f, "left"),
+            Side::Right => write!(f, "right"),
+        }
+    }
+}
+
+/// A single structural difference between two witnesses.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Difference {
+    /// The block is only contained in one of the witnesses.
+    MissingBlock {
+        kind: &'static str,
+        block_no: u64,
+        missing_in: Side,
+    },
+    /// The block headers differ.
+    Header {
+        kind: &'static str,
+        block_no: u64,
+        left: B256,
+        right: B256,
+    },
+    /// The number of transactions differs.
+    TransactionCount {
+        kind: &'static str,
+        block_no: u64,
+        left: usize,
+        right: usize,
+    },
+    /// The transactions at the given index differ.
+    Transaction {
+        kind: &'static str,
+        block_no: u64,
+        tx_no: usize,
+        left: B256,
+        right: B256,
+    },
+    /// The receipts are only contained in one of the witnesses.
+    MissingReceipts {
+        kind: &'static str,
+        block_no: u64,
+        missing_in: Side,
+    },
+    /// The number of receipts differs.
+    ReceiptCount {
+        kind: &'static str,
+        block_no: u64,
+        left: usize,
+        right: usize,
+    },
+    /// The receipts at the given index differ.
+    Receipt {
+        kind: &'static str,
+        block_no: u64,
+        tx_no: usize,
+    },
+    /// The trie node with the given hash is only contained in one of the witnesses.
+    TrieNode { hash: B256, only_in: Side },
+    /// The trie node is resolved in one witness, but only its digest is contained in the
+    /// other one.
+    UnresolvedTrieNode { hash: B256, unresolved_in: Side },
+    /// The storage trie of the account is only contained in one of the witnesses.
+    MissingStorageTrie { address: Address, missing_in: Side },
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Difference::MissingBlock {
+                kind,
+                block_no,
+                missing_in,
+            } => write!(f, "{} block {} missing in {}", kind, block_no, missing_in),
+            Difference::Header {
+                kind,
+                block_no,
+                left,
+                right,
+            } => write!(
+                f,
+                "{} block {} header differs: {} != {}",
+                kind, block_no, left, right
+            ),
+            Difference::TransactionCount {
+                kind,
+                block_no,
+                left,
+                right,
+            } => write!(
+                f,
+                "{} block {} transaction count differs: {} != {}",
+                kind, block_no, left, right
+            ),
+            Difference::Transaction {
+                kind,
+                block_no,
+                tx_no,
+                left,
+                right,
+            } => write!(
+                f,
+                "{} block {} transaction {} differs: {} != {}",
+                kind, block_no, tx_no, left, right
+            ),
+            Difference::MissingReceipts {
+                kind,
+                block_no,
+                missing_in,
+            } => write!(
+                f,
+                "{} block {} receipts missing in {}",
+                kind, block_no, missing_in
+            ),
+            Difference::ReceiptCount {
+                kind,
+                block_no,
+                left,
+                right,
+            } => write!(
+                f,
+                "{} block {} receipt count differs: {} != {}",
+                kind, block_no, left, right
+            ),
+            Difference::Receipt {
+                kind,
+                block_no,
+                tx_no,
+            } => write!(f, "{} block {} receipt {} differs", kind, block_no, tx_no),
+            Difference::TrieNode { hash, only_in } => {
+                write!(f, "trie node {} only in {}", hash, only_in)
+            }
+            Difference::UnresolvedTrieNode {
+                hash,
+                unresolved_in,
+            } => write!(f, "trie node {} unresolved in {}", hash, unresolved_in),
+            Difference::MissingStorageTrie {
+                address,
+                missing_in,
+            } => write!(f, "storage trie of {} missing in {}", address, missing_in),
+        }
+    }
+}
+
+/// The list of all structural differences between two witnesses.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WitnessDiff(pub Vec<Difference>);
+
+impl WitnessDiff {
+    /// Returns true iff the two witnesses are structurally identical.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl fmt::Display for WitnessDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "Witnesses are identical");
+        }
+        writeln!(f, "Found {} difference(s):", self.0.len())?;
+        for difference in &self.0 {
+            writeln!(f, "  - {}", difference)?;
+        }
+        Ok(())
+    }
+}
+
+/// Diffs the blocks, transactions and receipts contained in two [MemDb] witnesses.
+pub fn diff_batcher_dbs(left: &MemDb, right: &MemDb) -> WitnessDiff {
+    let mut diff = Vec::new();
+    diff_block_inputs("op", &left.full_op_block, &right.full_op_block, &mut diff);
+    diff_headers(
+        "op",
+        &left.op_block_header,
+        &right.op_block_header,
+        &mut diff,
+    );
+    diff_block_inputs(
+        "eth",
+        &left.full_eth_block,
+        &right.full_eth_block,
+        &mut diff,
+    );
+    diff_headers(
+        "eth",
+        &left.eth_block_header,
+        &right.eth_block_header,
+        &mut diff,
+    );
+    WitnessDiff(diff)
+}
+
+/// Diffs the state trie and the storage tries of two block witnesses.
+pub fn diff_block_witnesses<E: TxEssence>(
+    left: &BlockBuildInput<E>,
+    right: &BlockBuildInput<E>,
+) -> WitnessDiff {
+    let mut diff = diff_tries(&left.parent_state_trie, &right.parent_state_trie).0;
+
+    let addresses: BTreeSet<Address> = left
+        .parent_storage
+        .keys()
+        .chain(right.parent_storage.keys())
+        .copied()
+        .collect();
+    for address in addresses {
+        match (
+            left.parent_storage.get(&address),
+            right.parent_storage.get(&address),
+        ) {
+            (Some((left, _)), Some((right, _))) => diff.extend(diff_tries(left, right).0),
+            (Some(_), None) => diff.push(Difference::MissingStorageTrie {
+                address,
+                missing_in: Side::Right,
+            }),
+            (None, _) => diff.push(Difference::MissingStorageTrie {
+                address,
+                missing_in: Side::Left,
+            }),
+        }
+    }
+    WitnessDiff(diff)
+}
+
+/// Diffs the nodes contained in two (partially resolved) tries.
+pub fn diff_tries(left: &MptNode, right: &MptNode) -> WitnessDiff {
+    let (left_resolved, left_digests) = collect_nodes(left);
+    let (right_resolved, right_digests) = collect_nodes(right);
+
+    let mut diff = Vec::new();
+    for (resolved, digests, other_resolved, other_digests, side) in [
+        (
+            &left_resolved,
+            &left_digests,
+            &right_resolved,
+            &right_digests,
+            Side::Left,
+        ),
+        (
+            &right_resolved,
+            &right_digests,
+            &left_resolved,
+            &left_digests,
+            Side::Right,
+        ),
+    ] {
+        for hash in resolved.difference(other_resolved) {
+            if other_digests.contains(hash) {
+                let unresolved_in = match side {
+                    Side::Left => Side::Right,
+                    Side::Right => Side::Left,
+                };
+                diff.push(Difference::UnresolvedTrieNode {
+                    hash: *hash,
+                    unresolved_in,
+                });
+            } else {
+                diff.push(Difference::TrieNode {
+                    hash: *hash,
+                    only_in: side,
+                });
+            }
+        }
+        for hash in digests.difference(other_digests) {
+            if !other_resolved.contains(hash) {
+                diff.push(Difference::TrieNode {
+                    hash: *hash,
+                    only_in: side,
+                });
+            }
+        }
+    }
+    WitnessDiff(diff)
+}
+
+fn diff_headers(
+    kind: &'static str,
+    left: &HashMap<u64, Header>,
+    right: &HashMap<u64, Header>,
+    diff: &mut Vec<Difference>,
+) {
+    for block_no in sorted_keys(left.keys(), right.keys()) {
+        match (left.get(&block_no), right.get(&block_no)) {
+            (Some(left), Some(right)) => diff_header(kind, block_no, left, right, diff),
+            (Some(_), None) => diff.push(Difference::MissingBlock {
+                kind,
+                block_no,
+                missing_in: Side::Right,
+            }),
+            (None, _) => diff.push(Difference::MissingBlock {
+                kind,
+                block_no,
+                missing_in: Side::Left,
+            }),
+        }
+    }
+}
+
+fn diff_header(
+    kind: &'static str,
+    block_no: u64,
+    left: &Header,
+    right: &Header,
+    diff: &mut Vec<Difference>,
+) {
+    let (left, right) = (left.hash(), right.hash());
+    if left != right {
+        diff.push(Difference::Header {
+            kind,
+            block_no,
+            left,
+            right,
+        });
+    }
+}
+
+fn diff_block_inputs<E: TxEssence + fmt::Debug>(
+    kind: &'static str,
+    left: &HashMap<u64, BlockInput<E>>,
+    right: &HashMap<u64, BlockInput<E>>,
+    diff: &mut Vec<Difference>,
+) {
+    for block_no in sorted_keys(left.keys(), right.keys()) {
+        let (left, right) = match (left.get(&block_no), right.get(&block_no)) {
+            (Some(left), Some(right)) => (left, right),
+            (Some(_), None) => {
+                diff.push(Difference::MissingBlock {
+                    kind,
+                    block_no,
+                    missing_in: Side::Right,
+                });
+                continue;
+            }
+            (None, _) => {
+                diff.push(Difference::MissingBlock {
+                    kind,
+                    block_no,
+                    missing_in: Side::Left,
+                });
+                continue;
+            }
+        };
+
+        diff_header(
+            kind,
+            block_no,
+            &left.block_header,
+            &right.block_header,
+            diff,
+        );
+
+        // compare the transactions
+        if left.transactions.len() != right.transactions.len() {
+            diff.push(Difference::TransactionCount {
+                kind,
+                block_no,
+                left: left.transactions.len(),
+                right: right.transactions.len(),
+            });
+        }
+        for (tx_no, (left_tx, right_tx)) in left
+            .transactions
+            .iter()
+            .zip(&right.transactions)
+            .enumerate()
+        {
+            let (left, right) = (left_tx.hash(), right_tx.hash());
+            if left != right {
+                diff.push(Difference::Transaction {
+                    kind,
+                    block_no,
+                    tx_no,
+                    left,
+                    right,
+                });
+            }
+        }
+
+        // compare the receipts
+        match (&left.receipts, &right.receipts) {
+            (Some(left), Some(right)) => {
+                if left.len() != right.len() {
+                    diff.push(Difference::ReceiptCount {
+                        kind,
+                        block_no,
+                        left: left.len(),
+                        right: right.len(),
+                    });
+                }
+                for (tx_no, (left, right)) in left.iter().zip(right).enumerate() {
+                    if alloy_rlp::encode(left) != alloy_rlp::encode(right) {
+                        diff.push(Difference::Receipt {
+                            kind,
+                            block_no,
+                            tx_no,
+                        });
+                    }
+                }
+            }
+            (Some(_), None) => diff.push(Difference::MissingReceipts {
+                kind,
+                block_no,
+                missing_in: Side::Right,
+            }),
+            (None, Some(_)) => diff.push(Difference::MissingReceipts {
+                kind,
+                block_no,
+                missing_in: Side::Left,
+            }),
+            (None, None) => {}
+        }
+    }
+}
+
+fn sorted_keys<'a>(
+    left: impl Iterator<Item = &'a u64>,
+    right: impl Iterator<Item = &'a u64>,
+) -> BTreeSet<u64> {
+    left.chain(right).copied().collect()
+}
+
+/// Returns the hashes of all resolved nodes and of all digest nodes in the trie.
+fn collect_nodes(trie: &MptNode) -> (BTreeSet<B256>, BTreeSet<B256>) {
+    let mut resolved = BTreeSet::new();
+    let mut digests = BTreeSet::new();
+    let mut stack = vec![trie];
+    while let Some(node) = stack.pop() {
+        match node.as_data() {
+            MptNodeData::Null => continue,
+            MptNodeData::Digest(digest) => {
+                digests.insert(*digest);
+                continue;
+            }
+            MptNodeData::Branch(children) => stack.extend(children.iter().flatten().map(|c| &**c)),
+            MptNodeData::Extension(_, child) => stack.push(child),
+            MptNodeData::Leaf(_, _) => {}
+        }
+        // only nodes referenced by their hash can be compared by hash
+        if let MptNodeReference::Digest(_) = node.reference() {
+            resolved.insert(node.hash());
+        }
+    }
+    (resolved, digests)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identical_tries() {
+        let mut trie = MptNode::default();
+        for i in 0..16u64 {
+            trie.insert_rlp(&alloy_rlp::encode(i), [0xAAu8; 32])
+                .unwrap();
+        }
+        assert!(diff_tries(&trie, &trie).is_empty());
+    }
+
+    #[test]
+    fn unresolved_trie() {
+        let mut trie = MptNode::default();
+        for i in 0..16u64 {
+            trie.insert_rlp(&alloy_rlp::encode(i), [0xAAu8; 32])
+                .unwrap();
+        }
+        let digest = MptNode::from(trie.hash());
+
+        let diff = diff_tries(&trie, &digest);
+        assert!(diff.0.contains(&Difference::UnresolvedTrieNode {
+            hash: trie.hash(),
+            unresolved_in: Side::Right,
+        }));
+    }
+
+    #[test]
+    fn missing_storage_trie() {
+        use zeth_primitives::transactions::ethereum::EthereumTxEssence;
+
+        use crate::input::StateInput;
+
+        let left = BlockBuildInput {
+            state_input: StateInput::<EthereumTxEssence> {
+                parent_header: Default::default(),
+                beneficiary: Default::default(),
+                gas_limit: Default::default(),
+                timestamp: Default::default(),
+                extra_data: Default::default(),
+                mix_hash: Default::default(),
+                transactions: vec![],
+                withdrawals: vec![],
+            },
+            parent_state_trie: Default::default(),
+            parent_storage: Default::default(),
+            contracts: vec![],
+            ancestor_headers: vec![],
+        };
+        let mut right = left.clone();
+        let address = Address::repeat_byte(0x42);
+        right
+            .parent_storage
+            .insert(address, (MptNode::default(), vec![]));
+
+        let diff = diff_block_witnesses(&left, &right);
+        assert_eq!(
+            diff.0,
+            vec![Difference::MissingStorageTrie {
+                address,
+                missing_in: Side::Left,
+            }]
+        );
+    }
+
+    #[test]
+    fn missing_block() {
+        let left = MemDb::new();
+        let mut right = MemDb::new();
+        right.op_block_header.insert(1, Header::default());
+
+        let diff = diff_batcher_dbs(&left, &right);
+        assert_eq!(
+            diff.0,
+            vec![Difference::MissingBlock {
+                kind: "op",
+                block_no: 1,
+                missing_in: Side::Left,
+            }]
+        );
+    }
+}
@@ -13,14 +13,16 @@
 // limitations under the License.
 
 use alloy_rlp_derive::RlpEncodable;
+use anyhow::{Context, Result};
 use ethers_core::k256::sha2::{Digest, Sha256};
 use hashbrown::HashMap;
 use serde::{Deserialize, Serialize};
 use zeth_primitives::{
     block::Header,
+    keccak::keccak,
     mmr::Hash,
     transactions::{Transaction, TxEssence},
-    trie::MptNode,
+    trie::{FlatTrie, MptNode, StateAccount},
     withdrawal::Withdrawal,
     Address, Bytes, B256, U256,
 };
@@ -45,6 +47,119 @@ pub struct BlockBuildInput<E: TxEssence> {
     pub ancestor_headers: Vec<Header>,
 }
 
+/// External block input with the parent tries given as flat witnesses.
+///
+/// The guest reconstructs the tries from their sorted leaves and boundary digests and
+/// verifies their roots against the parent header and the parent accounts.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Deserialize, Serialize)]
+pub struct FlatBlockBuildInput<E: TxEssence> {
+    /// Block and transaction data to execute
+    pub state_input: StateInput<E>,
+    /// Flat witness of the state trie of the parent block.
+    pub parent_state_trie: FlatTrie,
+    /// Maps each address with the flat witness of its storage trie and the used storage
+    /// slots.
+    pub parent_storage: HashMap<Address, (FlatTrie, Vec<U256>)>,
+    /// The code of all unique contracts.
+    pub contracts: Vec<Bytes>,
+    /// List of at most 256 previous block headers
+    pub ancestor_headers: Vec<Header>,
+}
+
+impl<E: TxEssence> TryFrom<BlockBuildInput<E>> for FlatBlockBuildInput<E> {
+    type Error = anyhow::Error;
+
+    fn try_from(input: BlockBuildInput<E>) -> Result<Self> {
+        let parent_state_trie =
+            FlatTrie::from_trie(&input.parent_state_trie).context("invalid state trie")?;
+        let parent_storage = input
+            .parent_storage
+            .into_iter()
+            .map(|(address, (storage_trie, slots))| {
+                let storage_trie = FlatTrie::from_trie(&storage_trie)
+                    .with_context(|| format!("invalid storage trie for {}", address))?;
+                Ok((address, (storage_trie, slots)))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(FlatBlockBuildInput {
+            state_input: input.state_input,
+            parent_state_trie,
+            parent_storage,
+            contracts: input.contracts,
+            ancestor_headers: input.ancestor_headers,
+        })
+    }
+}
+
+impl<E: TxEssence> TryFrom<FlatBlockBuildInput<E>> for BlockBuildInput<E> {
+    type Error = anyhow::Error;
+
+    fn try_from(input: FlatBlockBuildInput<E>) -> Result<Self> {
+        let parent_state_trie = input
+            .parent_state_trie
+            .reconstruct(input.state_input.parent_header.state_root)
+            .context("invalid state trie")?;
+        let mut parent_storage = HashMap::with_capacity(input.parent_storage.len());
+        for (address, (storage_trie, slots)) in input.parent_storage {
+            let storage_root = parent_state_trie
+                .get_rlp::<StateAccount>(&keccak(address))?
+                .unwrap_or_default()
+                .storage_root;
+            let storage_trie = storage_trie
+                .reconstruct(storage_root)
+                .with_context(|| format!("invalid storage trie for {}", address))?;
+            parent_storage.insert(address, (storage_trie, slots));
+        }
+
+        Ok(BlockBuildInput {
+            state_input: input.state_input,
+            parent_state_trie,
+            parent_storage,
+            contracts: input.contracts,
+            ancestor_headers: input.ancestor_headers,
+        })
+    }
+}
+
+/// External input of a bundle of consecutive blocks.
+///
+/// The parent tries contain the state accessed by any block of the bundle, so that they
+/// are only deserialized once. Each block is built on the state left by the previous
+/// block.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Deserialize, Serialize)]
+pub struct BlockBundleInput<E: TxEssence> {
+    /// Block and transaction data of the consecutive blocks to execute
+    pub state_inputs: Vec<StateInput<E>>,
+    /// State trie of the parent of the first block.
+    pub parent_state_trie: MptNode,
+    /// Maps each address with its storage trie and the storage slots used by any block.
+    pub parent_storage: HashMap<Address, StorageEntry>,
+    /// The code of all unique contracts used by any block.
+    pub contracts: Vec<Bytes>,
+    /// List of at most 256 previous block headers of the first block
+    pub ancestor_headers: Vec<Header>,
+}
+
+/// Guest input of a single block, with the parent tries given in either witness format.
+#[derive(Debug, Clone, Eq, PartialEq, Deserialize, Serialize)]
+pub enum BlockWitness<E: TxEssence> {
+    /// The parent tries are given as sparse tries.
+    Trie(BlockBuildInput<E>),
+    /// The parent tries are given as flat witnesses.
+    Flat(FlatBlockBuildInput<E>),
+}
+
+impl<E: TxEssence> BlockWitness<E> {
+    /// Returns the block input, reconstructing the parent tries if necessary.
+    pub fn into_input(self) -> Result<BlockBuildInput<E>> {
+        match self {
+            BlockWitness::Trie(input) => Ok(input),
+            BlockWitness::Flat(input) => input.try_into(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Default, Eq, PartialEq, Deserialize, Serialize, RlpEncodable)]
 pub struct StateInput<E: TxEssence> {
     /// Previous block header
@@ -100,4 +215,53 @@ mod tests {
         let _: BlockBuildInput<EthereumTxEssence> =
             bincode::deserialize(&bincode::serialize(&input).unwrap()).unwrap();
     }
+
+    #[test]
+    fn flat_witness_roundtrip() {
+        let address = Address::repeat_byte(0x42);
+        let mut storage_trie = MptNode::default();
+        storage_trie
+            .insert_rlp(&keccak(B256::with_last_byte(1)), U256::from(1))
+            .unwrap();
+        let mut parent_state_trie = MptNode::default();
+        parent_state_trie
+            .insert_rlp(
+                &keccak(address),
+                StateAccount {
+                    storage_root: storage_trie.hash(),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        let input = BlockBuildInput {
+            state_input: StateInput::<EthereumTxEssence> {
+                parent_header: Header {
+                    state_root: parent_state_trie.hash(),
+                    ..Default::default()
+                },
+                beneficiary: Default::default(),
+                gas_limit: Default::default(),
+                timestamp: Default::default(),
+                extra_data: Default::default(),
+                mix_hash: Default::default(),
+                transactions: vec![],
+                withdrawals: vec![],
+            },
+            parent_state_trie,
+            parent_storage: HashMap::from([(address, (storage_trie, vec![U256::from(1)]))]),
+            contracts: vec![],
+            ancestor_headers: vec![],
+        };
+        let flat = FlatBlockBuildInput::try_from(input.clone()).unwrap();
+        assert_eq!(
+            BlockWitness::Flat(flat.clone()).into_input().unwrap(),
+            input
+        );
+
+        // a storage witness not matching the account is rejected
+        let mut invalid = flat;
+        invalid.parent_storage.get_mut(&address).unwrap().0 = FlatTrie::default();
+        assert!(BlockWitness::Flat(invalid).into_input().is_err());
+    }
 }
@@ -0,0 +1,313 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Encodings of the journals committed by the guests.
+//!
+//! The derivation guest commits its [DeriveOutput] in the format selected at build time
+//! by one of the `journal-bincode`, `journal-abi` or `journal-cbor` features, so that its
+//! receipts can be consumed by Rust, EVM or other verifiers respectively. Without any of
+//! these features, and for all other guests, the journal uses the RISC Zero serde format.
+
+use anyhow::{bail, Result};
+use risc0_zkvm::serde::{from_slice, to_vec};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::output::{BlockBuildOutput, BlockBundleOutput};
+
+#[cfg(any(not(target_os = "zkvm"), feature = "optimism"))]
+mod optimism;
+
+#[cfg(any(
+    all(feature = "journal-bincode", feature = "journal-abi"),
+    all(feature = "journal-bincode", feature = "journal-cbor"),
+    all(feature = "journal-abi", feature = "journal-cbor"),
+))]
+compile_error!("at most one of the journal-* features can be enabled");
+
+/// Encoding of a guest journal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JournalFormat {
+    /// The word-based RISC Zero serde format, as written by `env::commit`.
+    Risc0,
+    /// The [bincode](https://docs.rs/bincode) format, for Rust verifiers.
+    Bincode,
+    /// The Solidity ABI encoding, for EVM verifiers.
+    Abi,
+    /// The [CBOR](https://cbor.io) format, for all other verifiers.
+    Cbor,
+}
+
+/// The journal format of the derivation guest, selected by the `journal-*` features.
+pub const JOURNAL_FORMAT: JournalFormat = if cfg!(feature = "journal-bincode") {
+    JournalFormat::Bincode
+} else if cfg!(feature = "journal-abi") {
+    JournalFormat::Abi
+} else if cfg!(feature = "journal-cbor") {
+    JournalFormat::Cbor
+} else {
+    JournalFormat::Risc0
+};
+
+/// A guest output that is committed to the journal.
+pub trait Journal: Serialize + DeserializeOwned {
+    /// The format of the journal.
+    const FORMAT: JournalFormat = JournalFormat::Risc0;
+
+    /// Encodes the output as journal bytes.
+    fn to_journal(&self) -> Result<Vec<u8>> {
+        encode(self, Self::FORMAT)
+    }
+
+    /// Decodes the output from journal bytes.
+    fn from_journal(journal: &[u8]) -> Result<Self> {
+        decode(journal, Self::FORMAT)
+    }
+}
+
+impl Journal for BlockBuildOutput {}
+impl Journal for BlockBundleOutput {}
+
+/// Encodes a value in the given serde-based format.
+pub fn encode<T: Serialize + ?Sized>(value: &T, format: JournalFormat) -> Result<Vec<u8>> {
+    match format {
+        JournalFormat::Risc0 => Ok(to_vec(value)?
+            .into_iter()
+            .flat_map(u32::to_le_bytes)
+            .collect()),
+        JournalFormat::Bincode => Ok(bincode::serialize(value)?),
+        JournalFormat::Abi => bail!("the ABI encoding is not serde-based"),
+        JournalFormat::Cbor => {
+            let mut buf = Vec::new();
+            ciborium::into_writer(value, &mut buf)?;
+            Ok(buf)
+        }
+    }
+}
+
+/// Decodes a value from the given serde-based format.
+pub fn decode<T: DeserializeOwned>(journal: &[u8], format: JournalFormat) -> Result<T> {
+    match format {
+        JournalFormat::Risc0 => Ok(from_slice(journal)?),
+        JournalFormat::Bincode => Ok(bincode::deserialize(journal)?),
+        JournalFormat::Abi => bail!("the ABI encoding is not serde-based"),
+        JournalFormat::Cbor => Ok(ciborium::from_reader(journal)?),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers_core::k256::sha2::{Digest, Sha256};
+    use zeth_primitives::{b256, B256};
+
+    use super::*;
+    use crate::optimism::{
+        batcher::{BatchSource, BlockId},
+        header_accumulator::HeaderAccumulator,
+        BatchProvenance, DeriveOutput, DeriveOutputV1, L1Anchor,
+    };
+
+    fn derive_output() -> DeriveOutput {
+        DeriveOutput {
+            eth_tail: BlockId {
+                hash: b256!("1e8e5b1b5ad7e6a3b8e8b0fd5cdcf32b0c3a1e3a6f6f0a8c1ab9a3e7a0b4c1d2"),
+                number: 19_000_000,
+            },
+            op_head: BlockId {
+                hash: b256!("dbf6a80fef073de06add9b0d14026d6e5a86c85f6d102c36d3d8e9cf89c2afd3"),
+                number: 105_235_063,
+            },
+            derived_op_blocks: vec![
+                BlockId {
+                    hash: B256::repeat_byte(1),
+                    number: 105_235_064,
+                },
+                BlockId {
+                    hash: B256::repeat_byte(2),
+                    number: 105_235_065,
+                },
+            ],
+            block_image_id: [1, 2, 3, 4, 5, 6, 7, u32::MAX],
+            eth_accumulator: None,
+            l1_anchor: None,
+            batch_provenance: vec![],
+            rollup_config_hash: None,
+            prior_output_digest: None,
+            checkpoint_hash: None,
+        }
+    }
+
+    fn derive_output_with_accumulator() -> DeriveOutput {
+        let output = derive_output();
+        DeriveOutput {
+            eth_accumulator: HeaderAccumulator::from_blocks([output.eth_tail]).unwrap(),
+            ..output
+        }
+    }
+
+    fn derive_output_with_anchor() -> DeriveOutput {
+        let output = derive_output();
+        DeriveOutput {
+            l1_anchor: Some(L1Anchor {
+                op_block: BlockId {
+                    hash: B256::repeat_byte(3),
+                    number: 105_235_100,
+                },
+                eth_block: BlockId {
+                    hash: B256::repeat_byte(4),
+                    number: 19_000_010,
+                },
+            }),
+            ..output
+        }
+    }
+
+    fn derive_output_with_provenance() -> DeriveOutput {
+        let output = derive_output();
+        DeriveOutput {
+            batch_provenance: vec![
+                BatchProvenance {
+                    op_block: output.derived_op_blocks[0],
+                    source: Some(BatchSource {
+                        channel_id: u128::MAX,
+                        inclusion_block_number: 18_999_990,
+                    }),
+                },
+                BatchProvenance {
+                    op_block: output.derived_op_blocks[1],
+                    source: None,
+                },
+            ],
+            ..output
+        }
+    }
+
+    fn derive_output_with_rollup_config() -> DeriveOutput {
+        DeriveOutput {
+            rollup_config_hash: Some(B256::repeat_byte(5)),
+            ..derive_output_with_provenance()
+        }
+    }
+
+    fn derive_output_with_prior_output() -> DeriveOutput {
+        DeriveOutput {
+            prior_output_digest: Some(derive_output().journal_digest().unwrap()),
+            ..derive_output_with_rollup_config()
+        }
+    }
+
+    fn derive_output_with_checkpoint() -> DeriveOutput {
+        DeriveOutput {
+            checkpoint_hash: Some(B256::repeat_byte(6)),
+            ..derive_output_with_prior_output()
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        for output in [
+            derive_output(),
+            derive_output_with_accumulator(),
+            derive_output_with_anchor(),
+            derive_output_with_provenance(),
+            derive_output_with_rollup_config(),
+            derive_output_with_prior_output(),
+            derive_output_with_checkpoint(),
+        ] {
+            for format in [
+                JournalFormat::Risc0,
+                JournalFormat::Bincode,
+                JournalFormat::Cbor,
+            ] {
+                let journal = encode(&output, format).unwrap();
+                let decoded: DeriveOutput = decode(&journal, format).unwrap();
+                assert_eq!(decoded, output, "{:?}", format);
+            }
+            let decoded = DeriveOutput::abi_decode(&output.abi_encode()).unwrap();
+            assert_eq!(decoded, output);
+        }
+    }
+
+    #[test]
+    fn output_v1() {
+        for output in [
+            derive_output_with_accumulator(),
+            derive_output_with_anchor(),
+            derive_output_with_provenance(),
+            derive_output_with_rollup_config(),
+            derive_output_with_prior_output(),
+            derive_output_with_checkpoint(),
+        ] {
+            assert!(DeriveOutputV1::try_from(output).is_err());
+        }
+        let output = DeriveOutputV1::try_from(derive_output()).unwrap();
+        let decoded = DeriveOutputV1::abi_decode(&output.abi_encode()).unwrap();
+        assert_eq!(decoded, output);
+        // the layout of version 1 lacks all later fields
+        assert!(output.abi_encode().len() < derive_output().abi_encode().len());
+        let journal = encode(&output, JournalFormat::Risc0).unwrap();
+        assert!(decode::<DeriveOutput>(&journal, JournalFormat::Risc0).is_err());
+    }
+
+    #[test]
+    fn formats_differ() {
+        let output = derive_output();
+        let journals = [
+            encode(&output, JournalFormat::Risc0).unwrap(),
+            encode(&output, JournalFormat::Bincode).unwrap(),
+            encode(&output, JournalFormat::Cbor).unwrap(),
+            output.abi_encode(),
+        ];
+        for (i, a) in journals.iter().enumerate() {
+            for b in &journals[i + 1..] {
+                assert_ne!(a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn abi_layout() {
+        let journal = derive_output().abi_encode();
+        // offset of the dynamic tuple, followed by its head
+        assert_eq!(journal[..32], B256::with_last_byte(32)[..]);
+        assert_eq!(journal[32..64], derive_output().eth_tail.hash[..]);
+        assert_eq!(journal.len() % 32, 0);
+        assert!(DeriveOutput::abi_decode(&journal[..journal.len() - 32]).is_err());
+    }
+
+    #[test]
+    fn selected_format() {
+        let output = derive_output();
+        let journal = output.to_journal().unwrap();
+        assert_eq!(DeriveOutput::from_journal(&journal).unwrap(), output);
+        let expected = match JOURNAL_FORMAT {
+            JournalFormat::Abi => output.abi_encode(),
+            format => encode(&output, format).unwrap(),
+        };
+        assert_eq!(journal, expected);
+        assert_eq!(
+            output.journal_digest().unwrap()[..],
+            Sha256::digest(&journal)[..]
+        );
+
+        // all other outputs always use the RISC Zero format
+        let bundle = BlockBundleOutput {
+            parent_hash: B256::ZERO,
+            hash: B256::with_last_byte(1),
+        };
+        assert_eq!(
+            bundle.to_journal().unwrap(),
+            encode(&bundle, JournalFormat::Risc0).unwrap()
+        );
+    }
+}
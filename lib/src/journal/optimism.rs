@@ -0,0 +1,310 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Journal encodings of the Optimism guests.
+
+use anyhow::{Context, Result};
+use ethers_core::k256::sha2::{Digest, Sha256};
+use zeth_primitives::B256;
+
+use super::{decode, encode, Journal, JournalFormat, JOURNAL_FORMAT};
+use crate::optimism::{
+    batcher::{BatchSource, BlockId},
+    composition::{ComposeOutput, ImageId},
+    header_accumulator::HeaderAccumulator,
+    unsafe_payload::UnsafeOutput,
+    BatchProvenance, DeriveOutput, DeriveOutputV1, L1Anchor,
+};
+
+impl Journal for ComposeOutput {}
+impl Journal for UnsafeOutput {}
+
+impl Journal for DeriveOutput {
+    const FORMAT: JournalFormat = JOURNAL_FORMAT;
+
+    fn to_journal(&self) -> Result<Vec<u8>> {
+        match Self::FORMAT {
+            JournalFormat::Abi => Ok(self.abi_encode()),
+            format => encode(self, format),
+        }
+    }
+
+    fn from_journal(journal: &[u8]) -> Result<Self> {
+        match Self::FORMAT {
+            JournalFormat::Abi => DeriveOutput::abi_decode(journal),
+            format => decode(journal, format),
+        }
+    }
+}
+
+impl Journal for DeriveOutputV1 {
+    const FORMAT: JournalFormat = JOURNAL_FORMAT;
+
+    fn to_journal(&self) -> Result<Vec<u8>> {
+        match Self::FORMAT {
+            JournalFormat::Abi => Ok(self.abi_encode()),
+            format => encode(self, format),
+        }
+    }
+
+    fn from_journal(journal: &[u8]) -> Result<Self> {
+        match Self::FORMAT {
+            JournalFormat::Abi => DeriveOutputV1::abi_decode(journal),
+            format => decode(journal, format),
+        }
+    }
+}
+
+mod abi {
+    use alloy_sol_types::sol;
+
+    sol! {
+        /// ABI representation of a block ID.
+        struct BlockId {
+            bytes32 hash;
+            uint64 number;
+        }
+
+        /// ABI representation of a header accumulator, all zero if absent.
+        struct HeaderAccumulator {
+            BlockId first;
+            BlockId last;
+            bytes32 hash;
+        }
+
+        /// ABI representation of an L1 anchor, all zero if absent.
+        struct L1Anchor {
+            BlockId opBlock;
+            BlockId ethBlock;
+        }
+
+        /// ABI representation of the provenance of a derived block; the channel ID and
+        /// the inclusion block are zero for default batches.
+        struct BatchProvenance {
+            BlockId opBlock;
+            uint128 channelId;
+            uint64 inclusionBlockNumber;
+        }
+
+        /// ABI representation of the derivation output; the rollup config hash is zero
+        /// for the chain the guests are built for, the prior output digest is zero if
+        /// the run does not continue a prior output, and the checkpoint hash is zero if
+        /// not requested.
+        struct DeriveOutput {
+            BlockId ethTail;
+            BlockId opHead;
+            BlockId[] derivedOpBlocks;
+            bytes32 blockImageId;
+            HeaderAccumulator ethAccumulator;
+            L1Anchor l1Anchor;
+            BatchProvenance[] batchProvenance;
+            bytes32 rollupConfigHash;
+            bytes32 priorOutputDigest;
+            bytes32 checkpointHash;
+        }
+
+        /// ABI representation of the derivation output of input version 1.
+        struct DeriveOutputV1 {
+            BlockId ethTail;
+            BlockId opHead;
+            BlockId[] derivedOpBlocks;
+            bytes32 blockImageId;
+        }
+    }
+}
+
+impl From<&BlockId> for abi::BlockId {
+    fn from(id: &BlockId) -> Self {
+        abi::BlockId {
+            hash: id.hash,
+            number: id.number,
+        }
+    }
+}
+
+impl From<abi::BlockId> for BlockId {
+    fn from(id: abi::BlockId) -> Self {
+        BlockId {
+            hash: id.hash,
+            number: id.number,
+        }
+    }
+}
+
+impl From<Option<&HeaderAccumulator>> for abi::HeaderAccumulator {
+    fn from(accumulator: Option<&HeaderAccumulator>) -> Self {
+        let accumulator = accumulator.copied().unwrap_or(HeaderAccumulator {
+            first: BlockId::default(),
+            last: BlockId::default(),
+            hash: B256::ZERO,
+        });
+        abi::HeaderAccumulator {
+            first: (&accumulator.first).into(),
+            last: (&accumulator.last).into(),
+            hash: accumulator.hash,
+        }
+    }
+}
+
+impl From<abi::HeaderAccumulator> for Option<HeaderAccumulator> {
+    fn from(accumulator: abi::HeaderAccumulator) -> Self {
+        // a keccak accumulator is never zero
+        (accumulator.hash != B256::ZERO).then(|| HeaderAccumulator {
+            first: accumulator.first.into(),
+            last: accumulator.last.into(),
+            hash: accumulator.hash,
+        })
+    }
+}
+
+impl From<Option<&L1Anchor>> for abi::L1Anchor {
+    fn from(anchor: Option<&L1Anchor>) -> Self {
+        let anchor = anchor.copied().unwrap_or(L1Anchor {
+            op_block: BlockId::default(),
+            eth_block: BlockId::default(),
+        });
+        abi::L1Anchor {
+            opBlock: (&anchor.op_block).into(),
+            ethBlock: (&anchor.eth_block).into(),
+        }
+    }
+}
+
+impl From<abi::L1Anchor> for Option<L1Anchor> {
+    fn from(anchor: abi::L1Anchor) -> Self {
+        // a block hash is never zero
+        (anchor.opBlock.hash != B256::ZERO).then(|| L1Anchor {
+            op_block: anchor.opBlock.into(),
+            eth_block: anchor.ethBlock.into(),
+        })
+    }
+}
+
+impl From<&BatchProvenance> for abi::BatchProvenance {
+    fn from(provenance: &BatchProvenance) -> Self {
+        let source = provenance.source.unwrap_or(BatchSource {
+            channel_id: 0,
+            inclusion_block_number: 0,
+        });
+        abi::BatchProvenance {
+            opBlock: (&provenance.op_block).into(),
+            channelId: source.channel_id,
+            inclusionBlockNumber: source.inclusion_block_number,
+        }
+    }
+}
+
+impl From<abi::BatchProvenance> for BatchProvenance {
+    fn from(provenance: abi::BatchProvenance) -> Self {
+        // batches are never included in the genesis block
+        let source = (provenance.inclusionBlockNumber != 0).then_some(BatchSource {
+            channel_id: provenance.channelId,
+            inclusion_block_number: provenance.inclusionBlockNumber,
+        });
+        BatchProvenance {
+            op_block: provenance.opBlock.into(),
+            source,
+        }
+    }
+}
+
+impl DeriveOutput {
+    /// Returns the Solidity ABI encoding of the output, as decoded by
+    /// `abi.decode(journal, (DeriveOutput))`.
+    pub fn abi_encode(&self) -> Vec<u8> {
+        let output = abi::DeriveOutput {
+            ethTail: (&self.eth_tail).into(),
+            opHead: (&self.op_head).into(),
+            derivedOpBlocks: self.derived_op_blocks.iter().map(Into::into).collect(),
+            blockImageId: image_id_to_bytes(&self.block_image_id).into(),
+            ethAccumulator: self.eth_accumulator.as_ref().into(),
+            l1Anchor: self.l1_anchor.as_ref().into(),
+            batchProvenance: self.batch_provenance.iter().map(Into::into).collect(),
+            rollupConfigHash: self.rollup_config_hash.unwrap_or_default(),
+            priorOutputDigest: self.prior_output_digest.unwrap_or_default(),
+            checkpointHash: self.checkpoint_hash.unwrap_or_default(),
+        };
+        <abi::DeriveOutput as alloy_sol_types::SolType>::abi_encode(&output)
+    }
+
+    /// Decodes an output from its Solidity ABI encoding.
+    pub fn abi_decode(data: &[u8]) -> Result<Self> {
+        let output = <abi::DeriveOutput as alloy_sol_types::SolType>::abi_decode(data, true)
+            .context("invalid ABI encoded derivation output")?;
+        Ok(DeriveOutput {
+            eth_tail: output.ethTail.into(),
+            op_head: output.opHead.into(),
+            derived_op_blocks: output.derivedOpBlocks.into_iter().map(Into::into).collect(),
+            block_image_id: image_id_from_bytes(&output.blockImageId.0),
+            eth_accumulator: output.ethAccumulator.into(),
+            l1_anchor: output.l1Anchor.into(),
+            batch_provenance: output.batchProvenance.into_iter().map(Into::into).collect(),
+            // neither a keccak hash nor a SHA-256 digest is ever zero
+            rollup_config_hash: (output.rollupConfigHash != B256::ZERO)
+                .then_some(output.rollupConfigHash),
+            prior_output_digest: (output.priorOutputDigest != B256::ZERO)
+                .then_some(output.priorOutputDigest),
+            checkpoint_hash: (output.checkpointHash != B256::ZERO).then_some(output.checkpointHash),
+        })
+    }
+
+    /// Returns the SHA-256 digest of the journal of the output, which is the journal
+    /// digest in the claim of the receipt committing the output.
+    pub fn journal_digest(&self) -> Result<B256> {
+        let journal = self.to_journal()?;
+        Ok(B256::from_slice(&Sha256::digest(journal)))
+    }
+}
+
+impl DeriveOutputV1 {
+    /// Returns the Solidity ABI encoding of the output.
+    pub fn abi_encode(&self) -> Vec<u8> {
+        let output = abi::DeriveOutputV1 {
+            ethTail: (&self.eth_tail).into(),
+            opHead: (&self.op_head).into(),
+            derivedOpBlocks: self.derived_op_blocks.iter().map(Into::into).collect(),
+            blockImageId: image_id_to_bytes(&self.block_image_id).into(),
+        };
+        <abi::DeriveOutputV1 as alloy_sol_types::SolType>::abi_encode(&output)
+    }
+
+    /// Decodes an output from its Solidity ABI encoding.
+    pub fn abi_decode(data: &[u8]) -> Result<Self> {
+        let output = <abi::DeriveOutputV1 as alloy_sol_types::SolType>::abi_decode(data, true)
+            .context("invalid ABI encoded derivation output")?;
+        Ok(DeriveOutputV1 {
+            eth_tail: output.ethTail.into(),
+            op_head: output.opHead.into(),
+            derived_op_blocks: output.derivedOpBlocks.into_iter().map(Into::into).collect(),
+            block_image_id: image_id_from_bytes(&output.blockImageId.0),
+        })
+    }
+}
+
+/// Returns the little-endian bytes of an image ID, matching its digest representation.
+fn image_id_to_bytes(image_id: &ImageId) -> [u8; 32] {
+    let mut bytes = [0u8; 32];
+    for (chunk, word) in bytes.as_chunks_mut::<4>().0.iter_mut().zip(image_id) {
+        *chunk = word.to_le_bytes();
+    }
+    bytes
+}
+
+fn image_id_from_bytes(bytes: &[u8; 32]) -> ImageId {
+    let mut image_id = ImageId::default();
+    for (word, chunk) in image_id.iter_mut().zip(bytes.as_chunks::<4>().0) {
+        *word = u32::from_le_bytes(*chunk);
+    }
+    image_id
+}
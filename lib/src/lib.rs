@@ -14,16 +14,26 @@
 
 extern crate core;
 
+pub mod abort;
 #[cfg(not(target_os = "zkvm"))]
 pub mod host;
 
+// the executor and the Optimism derivation can be left out of guests that do not use
+// them, while the host always builds both
+#[cfg(any(not(target_os = "zkvm"), feature = "executor"))]
 pub mod builder;
 pub mod consts;
 pub mod input;
+pub mod journal;
+#[cfg(any(not(target_os = "zkvm"), feature = "optimism"))]
+pub mod light_client;
+#[cfg(any(not(target_os = "zkvm"), feature = "executor"))]
 pub mod mem_db;
+#[cfg(any(not(target_os = "zkvm"), feature = "optimism"))]
 pub mod optimism;
 pub mod output;
 
+#[cfg(any(not(target_os = "zkvm"), feature = "optimism"))]
 mod utils;
 
 pub use zeth_primitives::transactions::{ethereum::EthereumTxEssence, optimism::OptimismTxEssence};
@@ -0,0 +1,535 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::{ensure, Context, Result};
+use ethers_core::k256::sha2::{Digest, Sha256};
+use serde::{Deserialize, Serialize};
+use zeth_primitives::{b256, BlockHash, FixedBytes, B256};
+
+/// Number of validators in a sync committee.
+pub const SYNC_COMMITTEE_SIZE: usize = 512;
+/// Number of slots in an epoch.
+const SLOTS_PER_EPOCH: u64 = 32;
+/// Number of epochs in a sync committee period.
+const EPOCHS_PER_SYNC_COMMITTEE_PERIOD: u64 = 256;
+/// Domain type of the sync committee signatures.
+const DOMAIN_SYNC_COMMITTEE: [u8; 4] = [7, 0, 0, 0];
+/// The BLS ciphersuite used by the beacon chain.
+#[cfg(any(not(target_os = "zkvm"), feature = "light-client"))]
+const BLS_DST: &[u8] = b"BLS_SIG_BLS12381G2_XMD:SHA-256_SSWU_RO_POP_";
+/// Generalized index of the execution payload in the beacon block body.
+const EXECUTION_PAYLOAD_GINDEX: u64 = 25;
+/// Index of the block hash field in the execution payload.
+const BLOCK_HASH_INDEX: u64 = 12;
+
+/// A compressed BLS public key.
+pub type BlsPublicKey = FixedBytes<48>;
+/// A compressed BLS signature.
+pub type BlsSignature = FixedBytes<96>;
+
+/// A fork of the beacon chain with an execution payload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BeaconFork {
+    /// The first epoch of the fork.
+    pub epoch: u64,
+    /// The fork version.
+    pub version: [u8; 4],
+    /// Depth of the Merkle tree of the execution payload fields.
+    pub execution_payload_depth: u32,
+}
+
+/// The parameters of a beacon chain relevant for its light clients.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BeaconSpec {
+    /// Root of the genesis validators, which is part of every signing domain.
+    pub genesis_validators_root: B256,
+    /// The forks starting with Capella, ordered by epoch.
+    pub forks: &'static [BeaconFork],
+}
+
+/// The Ethereum mainnet beacon chain.
+pub const MAINNET_BEACON_SPEC: BeaconSpec = BeaconSpec {
+    genesis_validators_root: b256!(
+        "4b363db94e286120d76eb905340fdd4e54bfe9f06bf33ff6cf5ad27f511bfe95"
+    ),
+    forks: &[
+        // Capella
+        BeaconFork {
+            epoch: 194048,
+            version: [3, 0, 0, 0],
+            execution_payload_depth: 4,
+        },
+        // Deneb
+        BeaconFork {
+            epoch: 269568,
+            version: [4, 0, 0, 0],
+            execution_payload_depth: 5,
+        },
+    ],
+};
+
+/// The Sepolia beacon chain.
+pub const SEPOLIA_BEACON_SPEC: BeaconSpec = BeaconSpec {
+    genesis_validators_root: b256!(
+        "d8ea171f3c94aea21ebc42a1ed61052acf3f9209c00e4efbaaddac09ed9b8078"
+    ),
+    forks: &[
+        // Capella
+        BeaconFork {
+            epoch: 56832,
+            version: [0x90, 0, 0, 0x72],
+            execution_payload_depth: 4,
+        },
+        // Deneb
+        BeaconFork {
+            epoch: 132608,
+            version: [0x90, 0, 0, 0x73],
+            execution_payload_depth: 5,
+        },
+    ],
+};
+
+/// The beacon chain the guests are built for: Sepolia with the `sepolia` feature, mainnet
+/// otherwise.
+#[cfg(not(feature = "sepolia"))]
+pub const BEACON_SPEC: &BeaconSpec = &MAINNET_BEACON_SPEC;
+/// The beacon chain the guests are built for: Sepolia with the `sepolia` feature, mainnet
+/// otherwise.
+#[cfg(feature = "sepolia")]
+pub const BEACON_SPEC: &BeaconSpec = &SEPOLIA_BEACON_SPEC;
+
+impl BeaconSpec {
+    /// Returns the fork active at the given slot.
+    pub fn fork(&self, slot: u64) -> Result<&BeaconFork> {
+        let epoch = slot / SLOTS_PER_EPOCH;
+        self.forks
+            .iter()
+            .rev()
+            .find(|fork| epoch >= fork.epoch)
+            .with_context(|| format!("slot {} precedes the supported forks", slot))
+    }
+
+    /// Returns the root of the fork data of the given fork version.
+    pub fn fork_data_root(&self, version: [u8; 4]) -> B256 {
+        hash_pair(&pad(&version), &self.genesis_validators_root)
+    }
+
+    /// Returns the domain of the sync committee signatures with the given fork version.
+    pub fn sync_committee_domain(&self, version: [u8; 4]) -> B256 {
+        let mut domain = B256::ZERO;
+        domain[..4].copy_from_slice(&DOMAIN_SYNC_COMMITTEE);
+        domain[4..].copy_from_slice(&self.fork_data_root(version)[..28]);
+        domain
+    }
+}
+
+/// The header of a beacon block.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BeaconBlockHeader {
+    pub slot: u64,
+    pub proposer_index: u64,
+    pub parent_root: B256,
+    pub state_root: B256,
+    pub body_root: B256,
+}
+
+impl BeaconBlockHeader {
+    /// Returns the SSZ hash tree root of the header, i.e. the beacon block root.
+    pub fn hash_tree_root(&self) -> B256 {
+        merkleize(&[
+            pad(&self.slot.to_le_bytes()),
+            pad(&self.proposer_index.to_le_bytes()),
+            self.parent_root,
+            self.state_root,
+            self.body_root,
+        ])
+    }
+}
+
+/// The validators of a sync committee.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncCommittee {
+    /// The public keys of the [SYNC_COMMITTEE_SIZE] members.
+    pub pubkeys: Vec<BlsPublicKey>,
+    /// The aggregate of all public keys.
+    pub aggregate_pubkey: BlsPublicKey,
+}
+
+impl SyncCommittee {
+    /// Returns the SSZ hash tree root of the committee.
+    pub fn hash_tree_root(&self) -> B256 {
+        let pubkey_roots: Vec<_> = self.pubkeys.iter().map(pubkey_root).collect();
+        hash_pair(
+            &merkleize(&pubkey_roots),
+            &pubkey_root(&self.aggregate_pubkey),
+        )
+    }
+}
+
+/// The aggregate signature of the participating sync committee members.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncAggregate {
+    /// Bit vector of the participating members, in SSZ order.
+    pub sync_committee_bits: FixedBytes<64>,
+    /// The aggregate signature of the participating members.
+    pub sync_committee_signature: BlsSignature,
+}
+
+impl SyncAggregate {
+    /// Returns the number of participating members.
+    pub fn participants(&self) -> usize {
+        self.sync_committee_bits
+            .iter()
+            .map(|byte| byte.count_ones() as usize)
+            .sum()
+    }
+
+    /// Returns whether the member with the given index participated.
+    pub fn participated(&self, index: usize) -> bool {
+        self.sync_committee_bits[index / 8] & (1 << (index % 8)) != 0
+    }
+}
+
+/// A proof that an Ethereum execution block is part of a beacon block signed by a
+/// supermajority of a sync committee, following the Altair light client protocol.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncCommitteeProof {
+    /// The header of the signed beacon block.
+    pub attested_header: BeaconBlockHeader,
+    /// Hash of the execution block of the signed beacon block.
+    pub execution_block_hash: BlockHash,
+    /// Merkle branch from the execution block hash to the body root of the header.
+    pub execution_branch: Vec<B256>,
+    /// The sync committee of the period of the signature.
+    pub sync_committee: SyncCommittee,
+    /// The signature of the header.
+    pub sync_aggregate: SyncAggregate,
+    /// The slot at which the header was signed.
+    pub signature_slot: u64,
+}
+
+/// The statement of a verified [SyncCommitteeProof].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LightClientAttestation {
+    /// The sync committee period of the signature.
+    pub sync_committee_period: u64,
+    /// Root of the sync committee that signed the beacon block.
+    pub sync_committee_root: B256,
+    /// Root of the signed beacon block.
+    pub beacon_block_root: B256,
+    /// Hash of the execution block of the signed beacon block.
+    pub execution_block_hash: BlockHash,
+}
+
+impl SyncCommitteeProof {
+    /// Verifies the proof against the given beacon chain. The returned attestation must
+    /// be checked against a trusted root of the sync committee of its period.
+    #[cfg(any(not(target_os = "zkvm"), feature = "light-client"))]
+    pub fn verify(&self, spec: &BeaconSpec) -> Result<LightClientAttestation> {
+        use blst::{
+            min_pk::{AggregatePublicKey, PublicKey, Signature},
+            BLST_ERROR,
+        };
+
+        let header = &self.attested_header;
+        ensure!(
+            self.signature_slot > header.slot,
+            "signature slot {} does not follow the attested slot {}",
+            self.signature_slot,
+            header.slot
+        );
+
+        // the execution block must be part of the attested block body
+        let depth = spec.fork(header.slot)?.execution_payload_depth;
+        let index = ((EXECUTION_PAYLOAD_GINDEX - 16) << depth) | BLOCK_HASH_INDEX;
+        ensure!(
+            is_valid_merkle_branch(
+                self.execution_block_hash,
+                &self.execution_branch,
+                4 + depth,
+                index,
+                header.body_root
+            ),
+            "invalid execution branch"
+        );
+
+        // a supermajority of the committee must have signed the header
+        let committee = &self.sync_committee;
+        ensure!(
+            committee.pubkeys.len() == SYNC_COMMITTEE_SIZE,
+            "sync committee has {} members",
+            committee.pubkeys.len()
+        );
+        let aggregate = &self.sync_aggregate;
+        let participants = aggregate.participants();
+        ensure!(
+            participants * 3 >= SYNC_COMMITTEE_SIZE * 2,
+            "only {} of {} sync committee members participated",
+            participants,
+            SYNC_COMMITTEE_SIZE
+        );
+
+        // the keys are part of the committee root, so they are not validated again
+        let pubkeys = committee
+            .pubkeys
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| aggregate.participated(*index))
+            .map(|(_, pubkey)| PublicKey::from_bytes(pubkey.as_slice()))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| anyhow::anyhow!("invalid public key: {:?}", err))?;
+        let pubkeys: Vec<_> = pubkeys.iter().collect();
+        let aggregate_pubkey = AggregatePublicKey::aggregate(&pubkeys, false)
+            .map_err(|err| anyhow::anyhow!("failed to aggregate public keys: {:?}", err))?
+            .to_public_key();
+        let signature = Signature::from_bytes(aggregate.sync_committee_signature.as_slice())
+            .map_err(|err| anyhow::anyhow!("invalid signature: {:?}", err))?;
+
+        let fork_slot = self.signature_slot.max(1) - 1;
+        let domain = spec.sync_committee_domain(spec.fork(fork_slot)?.version);
+        let beacon_block_root = header.hash_tree_root();
+        let signing_root = hash_pair(&beacon_block_root, &domain);
+        let result = signature.verify(
+            true,
+            signing_root.as_slice(),
+            BLS_DST,
+            &[],
+            &aggregate_pubkey,
+            false,
+        );
+        ensure!(
+            result == BLST_ERROR::BLST_SUCCESS,
+            "invalid sync committee signature: {:?}",
+            result
+        );
+
+        Ok(LightClientAttestation {
+            sync_committee_period: self.signature_slot
+                / SLOTS_PER_EPOCH
+                / EPOCHS_PER_SYNC_COMMITTEE_PERIOD,
+            sync_committee_root: committee.hash_tree_root(),
+            beacon_block_root,
+            execution_block_hash: self.execution_block_hash,
+        })
+    }
+}
+
+/// Returns whether the branch proves the leaf at the given index of a tree with the
+/// given depth and root.
+pub fn is_valid_merkle_branch(
+    leaf: B256,
+    branch: &[B256],
+    depth: u32,
+    index: u64,
+    root: B256,
+) -> bool {
+    if branch.len() != depth as usize {
+        return false;
+    }
+    let mut value = leaf;
+    for (i, sibling) in branch.iter().enumerate() {
+        value = if (index >> i) & 1 == 1 {
+            hash_pair(sibling, &value)
+        } else {
+            hash_pair(&value, sibling)
+        };
+    }
+    value == root
+}
+
+/// Returns the SSZ root of the given chunks, padded with zero chunks to a power of two.
+fn merkleize(chunks: &[B256]) -> B256 {
+    let mut layer = chunks.to_vec();
+    layer.resize(chunks.len().next_power_of_two(), B256::ZERO);
+    while layer.len() > 1 {
+        layer = layer
+            .chunks(2)
+            .map(|pair| hash_pair(&pair[0], &pair[1]))
+            .collect();
+    }
+    layer[0]
+}
+
+/// Returns the SSZ root of a compressed BLS public key.
+fn pubkey_root(pubkey: &BlsPublicKey) -> B256 {
+    hash_pair(&B256::from_slice(&pubkey[..32]), &pad(&pubkey[32..]))
+}
+
+/// Right-pads the given bytes to a chunk.
+fn pad(bytes: &[u8]) -> B256 {
+    let mut chunk = B256::ZERO;
+    chunk[..bytes.len()].copy_from_slice(bytes);
+    chunk
+}
+
+fn hash_pair(left: &B256, right: &B256) -> B256 {
+    let mut hasher = Sha256::new();
+    hasher.update(left);
+    hasher.update(right);
+    B256::from_slice(&hasher.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use blst::min_pk::{AggregatePublicKey, AggregateSignature, SecretKey};
+
+    use super::*;
+
+    #[test]
+    fn fork_digests() {
+        // the first four bytes of the fork data roots are the well-known fork digests
+        let digest = |spec: &BeaconSpec, slot: u64| {
+            let root = spec.fork_data_root(spec.fork(slot).unwrap().version);
+            zeth_primitives::hex::encode(&root[..4])
+        };
+        assert_eq!(digest(&MAINNET_BEACON_SPEC, 194048 * 32), "bba4da96");
+        assert_eq!(digest(&MAINNET_BEACON_SPEC, 269568 * 32), "6a95a1a9");
+        assert_eq!(digest(&SEPOLIA_BEACON_SPEC, 56832 * 32), "47eb72b3");
+        assert_eq!(digest(&SEPOLIA_BEACON_SPEC, 132608 * 32), "d31f6191");
+        assert!(MAINNET_BEACON_SPEC.fork(194048 * 32 - 1).is_err());
+    }
+
+    #[test]
+    fn merkle_branch() {
+        let leaves: Vec<_> = (0..8u8).map(B256::repeat_byte).collect();
+        let root = merkleize(&leaves);
+        let branch = [
+            leaves[4],
+            hash_pair(&leaves[6], &leaves[7]),
+            merkleize(&leaves[..4]),
+        ];
+        assert!(is_valid_merkle_branch(leaves[5], &branch, 3, 5, root));
+        assert!(!is_valid_merkle_branch(leaves[5], &branch, 3, 4, root));
+        assert!(!is_valid_merkle_branch(leaves[5], &branch[..2], 2, 5, root));
+    }
+
+    /// Returns a proof for a synthetic Deneb block signed by the given members.
+    fn signed_proof(signers: usize) -> SyncCommitteeProof {
+        let secret_keys: Vec<_> = (0..SYNC_COMMITTEE_SIZE)
+            .map(|i| {
+                let mut ikm = [0xff; 32];
+                ikm[..8].copy_from_slice(&(i as u64).to_le_bytes());
+                SecretKey::key_gen(&ikm, &[]).unwrap()
+            })
+            .collect();
+        let pubkeys: Vec<_> = secret_keys.iter().map(SecretKey::sk_to_pk).collect();
+
+        // a body whose execution payload only contains the block hash
+        let execution_block_hash = B256::repeat_byte(0xbb);
+        let mut payload_fields = vec![B256::ZERO; 32];
+        payload_fields[BLOCK_HASH_INDEX as usize] = execution_block_hash;
+        let mut body_fields = vec![B256::ZERO; 16];
+        body_fields[9] = merkleize(&payload_fields);
+        let mut execution_branch = Vec::new();
+        for (fields, index) in [(&payload_fields, 12usize), (&body_fields, 9)] {
+            let mut layer = fields.clone();
+            let mut index = index;
+            while layer.len() > 1 {
+                execution_branch.push(layer[index ^ 1]);
+                layer = layer
+                    .chunks(2)
+                    .map(|pair| hash_pair(&pair[0], &pair[1]))
+                    .collect();
+                index /= 2;
+            }
+        }
+
+        let attested_header = BeaconBlockHeader {
+            slot: 9_000_000,
+            proposer_index: 42,
+            parent_root: B256::repeat_byte(1),
+            state_root: B256::repeat_byte(2),
+            body_root: merkleize(&body_fields),
+        };
+        let signature_slot = attested_header.slot + 1;
+        let domain = MAINNET_BEACON_SPEC.sync_committee_domain([4, 0, 0, 0]);
+        let signing_root = hash_pair(&attested_header.hash_tree_root(), &domain);
+
+        let mut sync_committee_bits = FixedBytes::<64>::ZERO;
+        let signatures: Vec<_> = secret_keys[..signers]
+            .iter()
+            .map(|sk| sk.sign(signing_root.as_slice(), BLS_DST, &[]))
+            .collect();
+        for index in 0..signers {
+            sync_committee_bits[index / 8] |= 1 << (index % 8);
+        }
+        let signature =
+            AggregateSignature::aggregate(&signatures.iter().collect::<Vec<_>>(), false)
+                .unwrap()
+                .to_signature();
+        let aggregate_pubkey =
+            AggregatePublicKey::aggregate(&pubkeys.iter().collect::<Vec<_>>(), false)
+                .unwrap()
+                .to_public_key();
+
+        SyncCommitteeProof {
+            attested_header,
+            execution_block_hash,
+            execution_branch,
+            sync_committee: SyncCommittee {
+                pubkeys: pubkeys
+                    .iter()
+                    .map(|pk| BlsPublicKey::from(pk.compress()))
+                    .collect(),
+                aggregate_pubkey: BlsPublicKey::from(aggregate_pubkey.compress()),
+            },
+            sync_aggregate: SyncAggregate {
+                sync_committee_bits,
+                sync_committee_signature: BlsSignature::from(signature.compress()),
+            },
+            signature_slot,
+        }
+    }
+
+    #[test]
+    fn verify() {
+        let proof = signed_proof(342);
+        let attestation = proof.verify(&MAINNET_BEACON_SPEC).unwrap();
+        assert_eq!(attestation.execution_block_hash, proof.execution_block_hash);
+        assert_eq!(
+            attestation.sync_committee_root,
+            proof.sync_committee.hash_tree_root()
+        );
+        assert_eq!(
+            attestation.beacon_block_root,
+            proof.attested_header.hash_tree_root()
+        );
+        assert_eq!(attestation.sync_committee_period, 9_000_001 / 8192);
+
+        // the signature is bound to the chain
+        assert!(proof.verify(&SEPOLIA_BEACON_SPEC).is_err());
+
+        // the execution block hash is bound to the header
+        let mut invalid = proof.clone();
+        invalid.execution_block_hash = B256::repeat_byte(0xcc);
+        assert!(invalid.verify(&MAINNET_BEACON_SPEC).is_err());
+
+        // the header is bound to the signature
+        let mut invalid = proof.clone();
+        invalid.attested_header.proposer_index += 1;
+        assert!(invalid.verify(&MAINNET_BEACON_SPEC).is_err());
+
+        // the signers must match the participation bits
+        let mut invalid = proof;
+        invalid.sync_aggregate.sync_committee_bits[63] |= 0x80;
+        assert!(invalid.verify(&MAINNET_BEACON_SPEC).is_err());
+    }
+
+    #[test]
+    fn no_supermajority() {
+        let proof = signed_proof(341);
+        let err = proof.verify(&MAINNET_BEACON_SPEC).unwrap_err();
+        assert!(err.to_string().contains("341 of 512"));
+    }
+}
@@ -33,6 +33,9 @@ pub enum DbError {
     /// Returned when a block hash was accessed but not loaded into the DB.
     #[error("block {0} not loaded")]
     BlockNotFound(u64),
+    /// Returned when the code of an account was accessed but not loaded into the DB.
+    #[error("code {0} not loaded")]
+    CodeNotFound(B256),
     /// Unspecified error.
     #[error(transparent)]
     Unspecified(#[from] anyhow::Error),
@@ -16,21 +16,34 @@ use core::cmp::Ordering;
 use std::collections::{BTreeMap, VecDeque};
 
 use anyhow::{bail, ensure, Context, Result};
-use revm::primitives::SpecId;
 use serde::{Deserialize, Serialize};
 use zeth_primitives::{
     batch::{Batch, BatchEssence},
+    block::Header,
     transactions::{
         ethereum::EthereumTxEssence,
         optimism::{OptimismTxEssence, OPTIMISM_DEPOSITED_TX_TYPE},
         Transaction,
     },
-    BlockHash, BlockNumber, U256,
+    Address, BlockHash, BlockNumber, TxHash, U256,
 };
 
 use super::{
-    batcher_channel::BatcherChannels, batcher_db::BlockInput, config::ChainConfig, deposits,
+    batcher_channel::{
+        BatcherChannels, ChannelId, ChannelsCheckpoint, DroppedChannel, DroppedTransaction,
+        SkippedFrame,
+    },
+    batcher_db::{ensure_consecutive, BlockInput},
+    batches::{BatchData, BatchQueue, BatchStatus, SpanBatch},
+    config::ChainConfig,
+    deposits,
+    l1_block_info::blob_base_fee,
+    system_config::SystemConfig,
+    trace::{DebugTrace, TraceEvent},
 };
+use crate::consts::{derive_base_fee, derive_excess_blob_gas};
+#[cfg(not(target_os = "zkvm"))]
+use crate::host::channel_cache::ChannelCache;
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Default, Serialize, Deserialize, Ord, PartialOrd)]
 pub struct BlockId {
@@ -38,23 +51,27 @@ pub struct BlockId {
     pub number: BlockNumber,
 }
 
-#[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default, Serialize, Deserialize)]
 pub struct L2BlockInfo {
     pub hash: BlockHash,
     pub timestamp: u64,
     pub l1_origin: BlockId,
 }
 
-#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Epoch {
     pub number: BlockNumber,
     pub hash: BlockHash,
     pub timestamp: u64,
     pub base_fee_per_gas: U256,
-    pub deposits: Vec<Transaction<OptimismTxEssence>>,
+    /// The blob base fee of the L1 block, set by the Ecotone L1 attributes.
+    pub blob_base_fee: U256,
+    /// Hashes of the deposit transactions. The transactions themselves are only extracted
+    /// from the L1 block once the epoch is consumed.
+    pub deposits: Vec<TxHash>,
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct State {
     pub current_l1_block_number: BlockNumber,
     pub current_l1_block_hash: BlockHash,
@@ -110,30 +127,121 @@ impl State {
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
-enum BatchStatus {
-    Drop,
-    Accept,
-    Undecided,
-    Future,
+/// Returns the hashes of the deposit transactions of the given L1 block.
+fn deposit_hashes(
+    config: &ChainConfig,
+    eth_block: &BlockInput<EthereumTxEssence>,
+) -> Result<Vec<TxHash>> {
+    let deposits = deposits::extract_transactions(config, eth_block)?;
+    Ok(deposits.iter().map(Transaction::hash).collect())
+}
+
+/// The reason why a batch was dropped, following the batch status rules of the spec.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum BatchDropReason {
+    /// The timestamp precedes the next L2 timestamp.
+    TooOld,
+    /// The parent hash does not match the safe L2 head.
+    WrongParent,
+    /// The batch was included after the sequencing window of its epoch.
+    NotTimely,
+    /// The epoch precedes the current epoch.
+    EpochTooOld,
+    /// The epoch is more than one epoch ahead of the current epoch.
+    EpochTooNew,
+    /// The epoch hash does not match the L1 origin.
+    WrongEpochHash,
+    /// The timestamp precedes the timestamp of the L1 origin.
+    BeforeOrigin,
+    /// The batch exceeds the maximum sequencer drift.
+    SequencerDrift,
+    /// The batch contains an empty or a deposited transaction.
+    InvalidTransaction,
+    /// The span batch has an L1 origin before the Delta activation.
+    BeforeDelta,
+    /// The timestamp of the overlapping span batch is not aligned with the L2 blocks.
+    MisalignedTimestamp,
+    /// The span batch overlaps the L2 chain up to the safe head.
+    OverlapsSafeHead,
 }
 
-/// A [Batch] with inclusion information.
+/// A batch that was dropped during derivation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DroppedBatch {
+    /// Timestamp of the batch.
+    pub timestamp: u64,
+    /// Epoch number of the batch.
+    pub epoch_num: u64,
+    /// Number of the L1 block that included the batch.
+    pub inclusion_block_number: BlockNumber,
+    /// The reason why the batch was dropped.
+    pub reason: BatchDropReason,
+}
+
+/// A batch read from a channel, with inclusion information.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BatchWithInclusion {
-    pub essence: BatchEssence,
+    pub data: BatchData,
+    pub inclusion_block_number: BlockNumber,
+    /// The channel the batch was read from.
+    pub channel_id: ChannelId,
+}
+
+/// The L1 data a batch was read from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BatchSource {
+    /// The channel the batch was read from.
+    pub channel_id: ChannelId,
+    /// Number of the L1 block that included the batch.
     pub inclusion_block_number: BlockNumber,
 }
 
 pub struct Batcher {
     config: ChainConfig,
-    spec_id: SpecId,
 
     /// The current state of the batch derivation.
     pub state: State,
+    /// Header of the last processed L1 block.
+    l1_head: Header,
+    /// Batch senders keyed by the first L1 block in which they are authenticated.
+    batch_senders: BTreeMap<BlockNumber, Address>,
+    /// L2 gas limits keyed by the first L1 block whose system config sets them.
+    gas_limits: BTreeMap<BlockNumber, U256>,
 
-    /// Multimap of batches, keyed by timestamp
-    batches: BTreeMap<u64, VecDeque<BatchWithInclusion>>,
+    /// The batches read so far, in the order of their L1 inclusion.
+    batches: BatchQueue,
+    /// The remaining blocks of the last accepted span batch, without their parent hash.
+    next_span: VecDeque<BatchEssence>,
+    /// The source of the last accepted span batch.
+    next_span_source: Option<BatchSource>,
+    /// The source of the batch last returned by [Batcher::read_batch].
+    batch_source: Option<BatchSource>,
     batcher_channel: BatcherChannels,
+    /// All batches dropped so far.
+    dropped_batches: Vec<DroppedBatch>,
+    /// Decision points of the derivation, only recorded with the `debug-trace` feature.
+    debug_trace: DebugTrace,
+}
+
+/// The state of a [Batcher] between two L1 blocks, from which the derivation can be
+/// resumed with [Batcher::from_checkpoint], e.g. in a later guest execution.
+///
+/// The dropped data and the debug trace are not part of the checkpoint; a resumed batcher
+/// only reports what it dropped itself.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatcherCheckpoint {
+    /// The system config as updated by the processed L1 blocks.
+    system_config: SystemConfig,
+    /// The current state of the batch derivation.
+    pub state: State,
+    l1_head: Header,
+    batch_senders: BTreeMap<BlockNumber, Address>,
+    gas_limits: BTreeMap<BlockNumber, U256>,
+    batches: BatchQueue,
+    next_span: VecDeque<BatchEssence>,
+    next_span_source: Option<BatchSource>,
+    batch_source: Option<BatchSource>,
+    channels: ChannelsCheckpoint,
 }
 
 impl Batcher {
@@ -143,9 +251,7 @@ impl Batcher {
         eth_block: &BlockInput<EthereumTxEssence>,
     ) -> Result<Self> {
         let timestamp = eth_block.block_header.timestamp;
-        let spec_id = config.chain_spec.active_fork(0, &timestamp)?;
-
-        let batcher_channel = BatcherChannels::new(&config, spec_id);
+        let batcher_channel = BatcherChannels::new(&config);
 
         let eth_block_hash = eth_block.block_header.hash();
         let state = State::new(
@@ -157,38 +263,216 @@ impl Batcher {
                 hash: eth_block_hash,
                 timestamp: timestamp.try_into().unwrap(),
                 base_fee_per_gas: eth_block.block_header.base_fee_per_gas,
-                deposits: deposits::extract_transactions(&config, eth_block)?,
+                blob_base_fee: blob_base_fee(&eth_block.block_header),
+                deposits: deposit_hashes(&config, eth_block)?,
             },
         );
 
+        let batch_senders = BTreeMap::from([(
+            eth_block.block_header.number,
+            config.system_config.batch_sender,
+        )]);
+        let gas_limits = BTreeMap::from([(
+            eth_block.block_header.number,
+            config.system_config.gas_limit,
+        )]);
+
         Ok(Batcher {
             config,
-            spec_id,
             state,
-            batches: BTreeMap::new(),
+            l1_head: eth_block.block_header.clone(),
+            batch_senders,
+            gas_limits,
+            batches: BatchQueue::default(),
+            next_span: VecDeque::new(),
+            next_span_source: None,
+            batch_source: None,
             batcher_channel,
+            dropped_batches: Vec::new(),
+            debug_trace: DebugTrace::default(),
         })
     }
 
+    /// Creates a batcher for the given configuration, resuming from the checkpoint. The
+    /// system config of the configuration is replaced by the one of the checkpoint.
+    pub fn from_checkpoint(mut config: ChainConfig, checkpoint: BatcherCheckpoint) -> Self {
+        config.system_config = checkpoint.system_config;
+        let batcher_channel = BatcherChannels::from_checkpoint(&config, checkpoint.channels);
+
+        Batcher {
+            config,
+            state: checkpoint.state,
+            l1_head: checkpoint.l1_head,
+            batch_senders: checkpoint.batch_senders,
+            gas_limits: checkpoint.gas_limits,
+            batches: checkpoint.batches,
+            next_span: checkpoint.next_span,
+            next_span_source: checkpoint.next_span_source,
+            batch_source: checkpoint.batch_source,
+            batcher_channel,
+            dropped_batches: Vec::new(),
+            debug_trace: DebugTrace::default(),
+        }
+    }
+
+    /// Returns the state of the batcher, from which it can be resumed with
+    /// [Batcher::from_checkpoint].
+    pub fn checkpoint(&self) -> BatcherCheckpoint {
+        BatcherCheckpoint {
+            system_config: self.config.system_config.clone(),
+            state: self.state.clone(),
+            l1_head: self.l1_head.clone(),
+            batch_senders: self.batch_senders.clone(),
+            gas_limits: self.gas_limits.clone(),
+            batches: self.batches.clone(),
+            next_span: self.next_span.clone(),
+            next_span_source: self.next_span_source,
+            batch_source: self.batch_source,
+            channels: self.batcher_channel.checkpoint(),
+        }
+    }
+
+    /// Sets the cache of decompressed channel data, e.g. to speed up repeated runs over
+    /// overlapping L1 ranges.
+    #[cfg(not(target_os = "zkvm"))]
+    pub fn set_channel_cache(&mut self, channel_cache: Option<ChannelCache>) {
+        self.batcher_channel.set_channel_cache(channel_cache);
+    }
+
+    /// Sets the number of threads decompressing the channels of an L1 block, e.g. to
+    /// speed up the preflight of L1 ranges with a lot of batcher data.
+    #[cfg(not(target_os = "zkvm"))]
+    pub fn set_decompression_threads(&mut self, threads: usize) {
+        self.batcher_channel.set_decompression_threads(threads);
+    }
+
     /// Returns a reference to the chain configuration.
     pub fn config(&self) -> &ChainConfig {
         &self.config
     }
 
+    /// Returns the batch sender authenticated for the batcher transactions of the given
+    /// L1 block, i.e. the sender after applying all system config updates up to and
+    /// including that block. Blocks after the last processed L1 block use the current
+    /// sender, while blocks before the first L1 block of the batcher return `None`.
+    pub fn effective_batch_sender(&self, l1_block_no: BlockNumber) -> Option<Address> {
+        self.batch_senders
+            .range(..=l1_block_no)
+            .next_back()
+            .map(|(_, batch_sender)| *batch_sender)
+    }
+
+    /// Returns the gas limit of the L2 blocks whose L1 origin is the given L1 block, i.e.
+    /// the gas limit after applying all system config updates up to and including that
+    /// block. Blocks before the first L1 block of the batcher return `None`.
+    pub fn effective_gas_limit(&self, l1_block_no: BlockNumber) -> Option<U256> {
+        self.gas_limits
+            .range(..=l1_block_no)
+            .next_back()
+            .map(|(_, gas_limit)| *gas_limit)
+    }
+
+    /// Returns all transactions sent to the batch inbox that were dropped because their
+    /// signature or frames were invalid.
+    pub fn dropped_transactions(&self) -> &[DroppedTransaction] {
+        self.batcher_channel.dropped_transactions()
+    }
+
+    /// Returns all channels that were dropped, or only read partially.
+    pub fn dropped_channels(&self) -> &[DroppedChannel] {
+        self.batcher_channel.dropped_channels()
+    }
+
+    /// Returns all frames that were not added to their channel.
+    pub fn skipped_frames(&self) -> &[SkippedFrame] {
+        self.batcher_channel.skipped_frames()
+    }
+
+    /// Returns all batches that were dropped by the batch status rules.
+    pub fn dropped_batches(&self) -> &[DroppedBatch] {
+        &self.dropped_batches
+    }
+
+    /// Returns the source of the batch last returned by [Batcher::read_batch], or `None`
+    /// if it was a default batch that was not read from L1.
+    pub fn batch_source(&self) -> Option<BatchSource> {
+        self.batch_source
+    }
+
+    /// Returns the decision points of the derivation recorded so far, which is always
+    /// empty without the `debug-trace` feature.
+    pub fn debug_trace(&self) -> &[TraceEvent] {
+        self.debug_trace.events()
+    }
+
+    /// Records a decision point of the derivation, if the `debug-trace` feature is
+    /// enabled.
+    pub(crate) fn record_trace(&mut self, event: impl FnOnce() -> TraceEvent) {
+        self.debug_trace.record(event);
+    }
+
+    /// Extracts the deposits of the current epoch from its L1 block and verifies them
+    /// against the deposit hashes of the epoch.
+    pub fn epoch_deposits(
+        &self,
+        eth_block: &BlockInput<EthereumTxEssence>,
+    ) -> Result<Vec<Transaction<OptimismTxEssence>>> {
+        let epoch = &self.state.epoch;
+        ensure!(
+            eth_block.block_header.hash() == epoch.hash,
+            "Eth block does not match the epoch"
+        );
+
+        let deposits = deposits::extract_transactions(&self.config, eth_block)?;
+        ensure!(
+            deposits
+                .iter()
+                .map(Transaction::hash)
+                .eq(epoch.deposits.iter().copied()),
+            "Deposits do not match the epoch"
+        );
+
+        Ok(deposits)
+    }
+
     pub fn process_l1_block(&mut self, eth_block: &BlockInput<EthereumTxEssence>) -> Result<()> {
         let eth_block_hash = eth_block.block_header.hash();
+        let eth_block_timestamp: u64 = eth_block.block_header.timestamp.try_into().unwrap();
 
         // Ensure block has correct parent
+        ensure_consecutive(
+            self.state.current_l1_block_number + 1,
+            self.state.current_l1_block_hash,
+            &eth_block.block_header,
+        )?;
+
+        // Ensure the base fee follows from the parent, as it is passed on to the L2 in the
+        // L1 attributes
+        let expected_base_fee = derive_base_fee(&self.l1_head, &self.config.l1_eip1559_constants);
         ensure!(
-            eth_block.block_header.parent_hash == self.state.current_l1_block_hash,
-            "Eth block has invalid parent hash"
+            eth_block.block_header.base_fee_per_gas == expected_base_fee,
+            "Eth block has invalid base fee: expected {}, got {}",
+            expected_base_fee,
+            eth_block.block_header.base_fee_per_gas
         );
-
-        // Set the spec_id according to the L1 timestamp
-        self.spec_id = self
-            .config
-            .chain_spec
-            .active_fork(0, &eth_block.block_header.timestamp)?;
+        // Likewise for the excess blob gas, which determines the blob base fee passed on
+        // since Ecotone; the L1 has blobs whenever Ecotone is active
+        match eth_block.block_header.excess_blob_gas {
+            Some(excess_blob_gas) => {
+                let expected_excess_blob_gas = derive_excess_blob_gas(&self.l1_head);
+                ensure!(
+                    excess_blob_gas == expected_excess_blob_gas,
+                    "Eth block has invalid excess blob gas: expected {}, got {}",
+                    expected_excess_blob_gas,
+                    excess_blob_gas
+                );
+            }
+            None => ensure!(
+                self.l1_head.excess_blob_gas.is_none()
+                    && !self.config.is_ecotone(eth_block_timestamp),
+                "Eth block is missing the excess blob gas"
+            ),
+        }
 
         if eth_block.receipts.is_some() {
             // Update the system config. From the spec:
@@ -200,89 +484,134 @@ impl Batcher {
                 .update(&self.config.system_config_contract, eth_block)
                 .context("failed to update system config")?;
         }
+        let batch_sender = self.config.system_config.batch_sender;
+        if self.effective_batch_sender(eth_block.block_header.number) != Some(batch_sender) {
+            self.batch_senders
+                .insert(eth_block.block_header.number, batch_sender);
+        }
+        let gas_limit = self.config.system_config.gas_limit;
+        if self.effective_gas_limit(eth_block.block_header.number) != Some(gas_limit) {
+            self.gas_limits
+                .insert(eth_block.block_header.number, gas_limit);
+        }
 
         // Enqueue epoch
         self.state.push_epoch(Epoch {
             number: eth_block.block_header.number,
             hash: eth_block_hash,
-            timestamp: eth_block.block_header.timestamp.try_into().unwrap(),
+            timestamp: eth_block_timestamp,
             base_fee_per_gas: eth_block.block_header.base_fee_per_gas,
-            deposits: deposits::extract_transactions(&self.config, eth_block)?,
+            blob_base_fee: blob_base_fee(&eth_block.block_header),
+            deposits: deposit_hashes(&self.config, eth_block)?,
         })?;
 
-        // process all transactions of this block to generate batches
-        self.batcher_channel
-            .process_l1_transactions(
-                self.config.system_config.batch_sender,
-                eth_block.block_header.number,
-                &eth_block.transactions,
-            )
-            .context("failed to process transactions")?;
-
-        // Read batches
-        while let Some(batches) = self.batcher_channel.read_batches() {
-            batches.into_iter().for_each(|batch| {
-                #[cfg(not(target_os = "zkvm"))]
-                log::trace!(
-                    "received batch: timestamp={}, parent_hash={}, epoch={}",
-                    batch.essence.timestamp,
-                    batch.essence.parent_hash,
-                    batch.essence.epoch_num
-                );
-                self.batches
-                    .entry(batch.essence.timestamp)
-                    .or_default()
-                    .push_back(batch);
-            });
+        // Deposits-only chains have no batcher, so their blocks are only derived from the
+        // default batches once the sequencing window has expired
+        if !self.config.deposits_only {
+            // process all transactions of this block to generate batches
+            self.batcher_channel
+                .process_l1_transactions(
+                    batch_sender,
+                    eth_block.block_header.number,
+                    eth_block_timestamp,
+                    &eth_block.transactions,
+                    &eth_block.blob_sidecars,
+                )
+                .context("failed to process transactions")?;
+
+            // Read batches
+            while let Some(batches) = self.batcher_channel.read_batches() {
+                batches.into_iter().for_each(|batch| {
+                    #[cfg(not(target_os = "zkvm"))]
+                    match &batch.data {
+                        BatchData::Singular(essence) => log::trace!(
+                            "received batch: timestamp={}, parent_hash={}, epoch={}",
+                            essence.timestamp,
+                            essence.parent_hash,
+                            essence.epoch_num
+                        ),
+                        BatchData::Span(span_batch) => log::trace!(
+                            "received span batch: timestamp={}, blocks={}, epoch={}",
+                            span_batch.timestamp(),
+                            span_batch.blocks.len(),
+                            span_batch.start_epoch_num()
+                        ),
+                    }
+                    self.batches.push(batch);
+                });
+            }
         }
 
         self.state.current_l1_block_number = eth_block.block_header.number;
         self.state.current_l1_block_hash = eth_block_hash;
+        self.l1_head = eth_block.block_header.clone();
+        self.debug_trace.record(|| {
+            TraceEvent::L1Block(BlockId {
+                hash: eth_block_hash,
+                number: eth_block.block_header.number,
+            })
+        });
 
         Ok(())
     }
 
     pub fn read_batch(&mut self) -> Result<Option<Batch>> {
-        let epoch = &self.state.epoch;
         let safe_l2_head = self.state.safe_head;
 
         ensure!(
-            safe_l2_head.l1_origin.hash == epoch.hash
-                || safe_l2_head.l1_origin.number == epoch.number - 1,
+            safe_l2_head.l1_origin.hash == self.state.epoch.hash
+                || safe_l2_head.l1_origin.number == self.state.epoch.number - 1,
             "buffered L1 chain epoch does not match safe head origin"
         );
 
-        let mut next_batch = None;
-
-        // Grab the first accepted batch. From the spec:
-        // "The batches are processed in order of the inclusion on L1: if multiple batches can be
-        //  accept-ed the first is applied. An implementation can defer future batches a later
-        //  derivation step to reduce validation work."
-        'outer: while let Some((ts, mut batches)) = self.batches.pop_first() {
-            // iterate over all batches, in order of inclusion and find the first accepted batch
-            // retain batches that may be processed in the future, or those we are undecided on
-            while let Some(batch) = batches.pop_front() {
-                match self.batch_status(&batch) {
-                    BatchStatus::Accept => {
-                        next_batch = Some(batch);
-                        // if there are still batches left, insert them back into the map
-                        if !batches.is_empty() {
-                            self.batches.insert(ts, batches);
-                        }
-                        break 'outer;
-                    }
-                    BatchStatus::Drop => {}
-                    BatchStatus::Future | BatchStatus::Undecided => {
-                        batches.push_front(batch);
-                        self.batches.insert(ts, batches);
-                        break 'outer;
-                    }
+        // Continue with the remaining blocks of the last accepted span batch
+        if let Some(batch) = self.next_span_batch() {
+            self.batch_source = self.next_span_source;
+            return Ok(Some(batch));
+        }
+        self.batch_source = None;
+
+        // Grab the first accepted batch, checking the batches in the order of their L1
+        // inclusion like op-node
+        let mut batches = core::mem::take(&mut self.batches);
+        let mut dropped = Vec::new();
+        let next_batch = batches.next_accepted(
+            |batch| match &batch.data {
+                BatchData::Singular(essence) => {
+                    Ok(self.batch_status(essence, batch.inclusion_block_number))
                 }
-            }
+                BatchData::Span(span_batch) => {
+                    self.span_batch_status(span_batch, batch.inclusion_block_number)
+                }
+            },
+            &mut dropped,
+        );
+        self.batches = batches;
+        for dropped in dropped {
+            self.debug_trace
+                .record(|| TraceEvent::BatchDropped(dropped));
+            self.dropped_batches.push(dropped);
         }
 
-        if let Some(batch) = next_batch {
-            return Ok(Some(Batch(batch.essence)));
+        if let Some(batch) = next_batch? {
+            self.debug_trace.record(|| TraceEvent::BatchAccepted {
+                timestamp: batch.data.timestamp(),
+                epoch_num: batch.data.epoch_num(),
+                inclusion_block_number: batch.inclusion_block_number,
+            });
+            let source = BatchSource {
+                channel_id: batch.channel_id,
+                inclusion_block_number: batch.inclusion_block_number,
+            };
+            self.batch_source = Some(source);
+            return match batch.data {
+                BatchData::Singular(essence) => Ok(Some(Batch(essence))),
+                BatchData::Span(span_batch) => {
+                    self.next_span = self.singular_batches(span_batch)?;
+                    self.next_span_source = Some(source);
+                    Ok(self.next_span_batch())
+                }
+            };
         }
 
         // If there are no accepted batches, attempt to generate the default batch. From the spec:
@@ -290,11 +619,14 @@ impl Batcher {
         //  that can fully be read from the L1 block at height epoch.number +
         //  sequence_window_size, and the next_epoch is available, then an empty batch can be
         //  derived."
+        let epoch = &self.state.epoch;
         let current_l1_block = self.state.current_l1_block_number;
         let sequence_window_size = self.config.seq_window_size;
         let first_of_epoch = epoch.number == safe_l2_head.l1_origin.number + 1;
 
-        if current_l1_block > epoch.number + sequence_window_size {
+        // All batches of an L1 block are buffered once it has been processed, so like
+        // op-node, the window has expired as soon as its last L1 block is processed.
+        if current_l1_block >= epoch.number + sequence_window_size {
             if let Some(next_epoch) = &self.state.next_epoch {
                 let next_timestamp = safe_l2_head.timestamp + self.config.blocktime;
                 let batch_epoch = if next_timestamp < next_epoch.timestamp || first_of_epoch {
@@ -305,10 +637,19 @@ impl Batcher {
                     //  of advancing the epoch to ensure that there is at least one L2 block per
                     //  epoch."
                     epoch
-                } else {
+                } else if current_l1_block >= next_epoch.number + sequence_window_size {
                     next_epoch
+                } else {
+                    // Like op-node, the origin only advances to the next epoch here; its
+                    // default batch is forced once its own sequencing window has expired, so
+                    // that batches of a recovering batcher are still derived until then.
+                    return Ok(None);
                 };
 
+                self.debug_trace.record(|| TraceEvent::DefaultBatch {
+                    timestamp: next_timestamp,
+                    epoch_num: batch_epoch.number,
+                });
                 return Ok(Some(Batch::new(
                     safe_l2_head.hash,
                     batch_epoch.number,
@@ -321,7 +662,234 @@ impl Batcher {
         Ok(None)
     }
 
-    fn batch_status(&self, batch: &BatchWithInclusion) -> BatchStatus {
+    /// Returns the next block of the last accepted span batch, as long as it follows the
+    /// safe head. Otherwise, the remaining blocks of the span batch are dropped.
+    fn next_span_batch(&mut self) -> Option<Batch> {
+        let safe_l2_head = self.state.safe_head;
+        let batch = self.next_span.pop_front()?;
+        if batch.timestamp != safe_l2_head.timestamp + self.config.blocktime {
+            #[cfg(not(target_os = "zkvm"))]
+            log::warn!("Safe head does not match the span batch; dropping its remaining blocks");
+            self.next_span.clear();
+            return None;
+        }
+
+        // the parent hash is only checked for the first block of the span batch
+        Some(Batch(BatchEssence {
+            parent_hash: safe_l2_head.hash,
+            ..batch
+        }))
+    }
+
+    /// Converts the blocks of an accepted span batch following the safe head into
+    /// singular batches. Their parent hash is only set once they are read.
+    fn singular_batches(&self, span_batch: SpanBatch) -> Result<VecDeque<BatchEssence>> {
+        let safe_l2_head = self.state.safe_head;
+        span_batch
+            .blocks
+            .into_iter()
+            .filter(|block| block.timestamp > safe_l2_head.timestamp)
+            .map(|block| {
+                let l1_origin = self
+                    .l1_origin(block.epoch_num)
+                    .with_context(|| format!("unknown L1 origin: {}", block.epoch_num))?;
+                Ok(BatchEssence {
+                    parent_hash: BlockHash::ZERO,
+                    epoch_num: block.epoch_num,
+                    epoch_hash: l1_origin.hash,
+                    timestamp: block.timestamp,
+                    transactions: block.transactions,
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the traversed L1 block with the given number, starting at the current
+    /// epoch.
+    fn l1_origin(&self, number: BlockNumber) -> Option<&Epoch> {
+        std::iter::once(&self.state.epoch)
+            .chain(&self.state.next_epoch)
+            .chain(&self.state.op_epoch_queue)
+            .find(|epoch| epoch.number == number)
+    }
+
+    fn span_batch_status(
+        &self,
+        batch: &SpanBatch,
+        inclusion_block_number: BlockNumber,
+    ) -> Result<BatchStatus> {
+        // Apply the span batch rules of the spec, in the same order as op-node.
+
+        let epoch = &self.state.epoch;
+        let safe_l2_head = self.state.safe_head;
+        let next_timestamp = safe_l2_head.timestamp + self.config.blocktime;
+        let start_epoch_num = batch.start_epoch_num();
+
+        // batch_origin is determined like for singular batches, and must be after Delta
+        let batch_origin = if start_epoch_num == epoch.number + 1 {
+            match &self.state.next_epoch {
+                Some(next_epoch) => next_epoch,
+                None => return Ok(BatchStatus::Undecided),
+            }
+        } else {
+            epoch
+        };
+        if !self.config.is_delta(batch_origin.timestamp) {
+            #[cfg(not(target_os = "zkvm"))]
+            log::warn!("Span batch with L1 origin before Delta");
+            return Ok(BatchStatus::Drop(BatchDropReason::BeforeDelta));
+        }
+
+        // the span batch must start at or before next_timestamp, and contain new blocks
+        match batch.timestamp().cmp(&next_timestamp) {
+            Ordering::Greater => return Ok(BatchStatus::Future),
+            Ordering::Less => {
+                if batch.final_timestamp() < next_timestamp {
+                    #[cfg(not(target_os = "zkvm"))]
+                    log::trace!(
+                        "Span batch has no new blocks: {} < next_timestamp = {}",
+                        batch.final_timestamp(),
+                        next_timestamp
+                    );
+                    return Ok(BatchStatus::Drop(BatchDropReason::TooOld));
+                }
+                // an overlapping span batch must be aligned with the L2 blocks
+                if batch.timestamp() > safe_l2_head.timestamp
+                    || !(safe_l2_head.timestamp - batch.timestamp())
+                        .is_multiple_of(self.config.blocktime)
+                {
+                    #[cfg(not(target_os = "zkvm"))]
+                    log::warn!("Span batch has misaligned timestamp: {}", batch.timestamp());
+                    return Ok(BatchStatus::Drop(BatchDropReason::MisalignedTimestamp));
+                }
+                // The parent and the overlapping blocks would have to be checked against the
+                // L2 chain before the safe head, which is not part of the input.
+                #[cfg(not(target_os = "zkvm"))]
+                log::warn!(
+                    "Span batch overlaps the safe head: {} < next_timestamp = {}",
+                    batch.timestamp(),
+                    next_timestamp
+                );
+                return Ok(BatchStatus::Drop(BatchDropReason::OverlapsSafeHead));
+            }
+            Ordering::Equal => (),
+        }
+
+        // the parent_check must match the first 20 bytes of the parent hash
+        if !batch.check_parent_hash(&safe_l2_head.hash) {
+            #[cfg(not(target_os = "zkvm"))]
+            log::warn!("Incorrect span batch parent hash: {}", safe_l2_head.hash);
+            return Ok(BatchStatus::Drop(BatchDropReason::WrongParent));
+        }
+
+        // the span batch must be included within the sequencing window of its first block
+        if start_epoch_num + self.config.seq_window_size < inclusion_block_number {
+            #[cfg(not(target_os = "zkvm"))]
+            log::warn!(
+                "Span batch is not timely: {} + {} < {}",
+                start_epoch_num,
+                self.config.seq_window_size,
+                inclusion_block_number
+            );
+            return Ok(BatchStatus::Drop(BatchDropReason::NotTimely));
+        }
+
+        // the L1 origin cannot advance by more than one L1 block per L2 block
+        if start_epoch_num > safe_l2_head.l1_origin.number + 1 {
+            #[cfg(not(target_os = "zkvm"))]
+            log::warn!(
+                "Span batch epoch number is too large: {} > {}",
+                start_epoch_num,
+                safe_l2_head.l1_origin.number + 1
+            );
+            return Ok(BatchStatus::Drop(BatchDropReason::EpochTooNew));
+        }
+
+        // the l1_origin_check must match the first 20 bytes of the L1 origin of the last
+        // block, which is undecided until that L1 block has been traversed
+        match self.l1_origin(batch.final_epoch_num()) {
+            Some(l1_origin) if !batch.check_origin_hash(&l1_origin.hash) => {
+                #[cfg(not(target_os = "zkvm"))]
+                log::warn!("Span batch epoch hash mismatch: {}", l1_origin.hash);
+                return Ok(BatchStatus::Drop(BatchDropReason::WrongEpochHash));
+            }
+            Some(_) => (),
+            None => return Ok(BatchStatus::Undecided),
+        }
+
+        // the L1 origin cannot precede the L1 origin of the parent
+        if start_epoch_num < safe_l2_head.l1_origin.number {
+            #[cfg(not(target_os = "zkvm"))]
+            log::warn!(
+                "Span batch epoch number is too low: {} < {}",
+                start_epoch_num,
+                safe_l2_head.l1_origin.number
+            );
+            return Ok(BatchStatus::Drop(BatchDropReason::EpochTooOld));
+        }
+
+        // Apply the rules of singular batches to every new block of the span batch
+        for (i, block) in batch.blocks.iter().enumerate() {
+            if block.timestamp <= safe_l2_head.timestamp {
+                continue;
+            }
+            let Some(l1_origin) = self.l1_origin(block.epoch_num) else {
+                return Ok(BatchStatus::Undecided);
+            };
+            let origin_advanced = match i {
+                0 => block.epoch_num == safe_l2_head.l1_origin.number + 1,
+                _ => block.epoch_num > batch.blocks[i - 1].epoch_num,
+            };
+
+            // the timestamp cannot precede the L1 origin
+            if block.timestamp < l1_origin.timestamp {
+                #[cfg(not(target_os = "zkvm"))]
+                log::warn!(
+                    "Span batch block violates timestamp rule: {} < {}",
+                    block.timestamp,
+                    l1_origin.timestamp
+                );
+                return Ok(BatchStatus::Drop(BatchDropReason::BeforeOrigin));
+            }
+
+            // enforce the sequencer drift, except for empty blocks that cannot yet adopt the
+            // next L1 origin
+            if block.timestamp > l1_origin.timestamp + self.config.max_seq_drift {
+                if !block.transactions.is_empty() {
+                    #[cfg(not(target_os = "zkvm"))]
+                    log::warn!("Sequencer drift detected for non-empty span batch block; drop.");
+                    return Ok(BatchStatus::Drop(BatchDropReason::SequencerDrift));
+                }
+                if !origin_advanced {
+                    match self.l1_origin(block.epoch_num + 1) {
+                        Some(next_origin) if block.timestamp >= next_origin.timestamp => {
+                            #[cfg(not(target_os = "zkvm"))]
+                            log::warn!("Sequencer drift detected; drop; span batch block timestamp is too far into the future. {} >= {}", block.timestamp, next_origin.timestamp);
+                            return Ok(BatchStatus::Drop(BatchDropReason::SequencerDrift));
+                        }
+                        Some(_) => (),
+                        None => return Ok(BatchStatus::Undecided),
+                    }
+                }
+            }
+
+            for tx in &block.transactions {
+                if matches!(tx.first(), None | Some(&OPTIMISM_DEPOSITED_TX_TYPE)) {
+                    #[cfg(not(target_os = "zkvm"))]
+                    log::warn!("Span batch contains empty or invalid transaction");
+                    return Ok(BatchStatus::Drop(BatchDropReason::InvalidTransaction));
+                }
+            }
+        }
+
+        Ok(BatchStatus::Accept)
+    }
+
+    fn batch_status(
+        &self,
+        batch: &BatchEssence,
+        inclusion_block_number: BlockNumber,
+    ) -> BatchStatus {
         // Apply the batch status rules. The spec describes a precise order for these checks.
 
         let epoch = &self.state.epoch;
@@ -332,12 +900,12 @@ impl Batcher {
         // From the spec:
         // "batch.timestamp > next_timestamp -> future"
         // "batch.timestamp < next_timestamp -> drop"
-        match batch.essence.timestamp.cmp(&next_timestamp) {
+        match batch.timestamp.cmp(&next_timestamp) {
             Ordering::Greater => {
                 #[cfg(not(target_os = "zkvm"))]
                 log::trace!(
                     "Future batch: {} = batch.timestamp > next_timestamp = {}",
-                    &batch.essence.timestamp,
+                    &batch.timestamp,
                     &next_timestamp
                 );
                 return BatchStatus::Future;
@@ -346,56 +914,56 @@ impl Batcher {
                 #[cfg(not(target_os = "zkvm"))]
                 log::trace!(
                     "Batch too old: {} = batch.timestamp < next_timestamp = {}",
-                    &batch.essence.timestamp,
+                    &batch.timestamp,
                     &next_timestamp
                 );
-                return BatchStatus::Drop;
+                return BatchStatus::Drop(BatchDropReason::TooOld);
             }
             Ordering::Equal => (),
         }
 
         // From the spec:
         // "batch.parent_hash != safe_l2_head.hash -> drop"
-        if batch.essence.parent_hash != safe_l2_head.hash {
+        if batch.parent_hash != safe_l2_head.hash {
             #[cfg(not(target_os = "zkvm"))]
             log::warn!(
                 "Incorrect parent hash: {} != {}",
-                batch.essence.parent_hash,
+                batch.parent_hash,
                 safe_l2_head.hash
             );
-            return BatchStatus::Drop;
+            return BatchStatus::Drop(BatchDropReason::WrongParent);
         }
 
         // From the spec:
         // "batch.epoch_num + sequence_window_size < inclusion_block_number -> drop"
-        if batch.essence.epoch_num + self.config.seq_window_size < batch.inclusion_block_number {
+        if batch.epoch_num + self.config.seq_window_size < inclusion_block_number {
             #[cfg(not(target_os = "zkvm"))]
             log::warn!(
                 "Batch is not timely: {} + {} < {}",
-                batch.essence.epoch_num,
+                batch.epoch_num,
                 self.config.seq_window_size,
-                batch.inclusion_block_number
+                inclusion_block_number
             );
-            return BatchStatus::Drop;
+            return BatchStatus::Drop(BatchDropReason::NotTimely);
         }
 
         // From the spec:
         // "batch.epoch_num < epoch.number -> drop"
-        if batch.essence.epoch_num < epoch.number {
+        if batch.epoch_num < epoch.number {
             #[cfg(not(target_os = "zkvm"))]
             log::warn!(
                 "Batch epoch number is too low: {} < {}",
-                batch.essence.epoch_num,
+                batch.epoch_num,
                 epoch.number
             );
-            return BatchStatus::Drop;
+            return BatchStatus::Drop(BatchDropReason::EpochTooOld);
         }
 
-        let batch_origin = if batch.essence.epoch_num == epoch.number {
+        let batch_origin = if batch.epoch_num == epoch.number {
             // From the spec:
             // "batch.epoch_num == epoch.number: define batch_origin as epoch"
             epoch
-        } else if batch.essence.epoch_num == epoch.number + 1 {
+        } else if batch.epoch_num == epoch.number + 1 {
             // From the spec:
             // "batch.epoch_num == epoch.number+1:"
             // "  If known, then define batch_origin as next_epoch"
@@ -410,68 +978,68 @@ impl Batcher {
             #[cfg(not(target_os = "zkvm"))]
             log::warn!(
                 "Batch epoch number is too large: {} > {}",
-                batch.essence.epoch_num,
+                batch.epoch_num,
                 epoch.number + 1
             );
-            return BatchStatus::Drop;
+            return BatchStatus::Drop(BatchDropReason::EpochTooNew);
         };
 
         // From the spec:
         // "batch.epoch_hash != batch_origin.hash -> drop"
-        if batch.essence.epoch_hash != batch_origin.hash {
+        if batch.epoch_hash != batch_origin.hash {
             #[cfg(not(target_os = "zkvm"))]
             log::warn!(
                 "Epoch hash mismatch: {} != {}",
-                batch.essence.epoch_hash,
+                batch.epoch_hash,
                 batch_origin.hash
             );
-            return BatchStatus::Drop;
+            return BatchStatus::Drop(BatchDropReason::WrongEpochHash);
         }
 
         // From the spec:
         // "batch.timestamp < batch_origin.time -> drop"
-        if batch.essence.timestamp < batch_origin.timestamp {
+        if batch.timestamp < batch_origin.timestamp {
             #[cfg(not(target_os = "zkvm"))]
             log::warn!(
                 "Batch violates timestamp rule: {} < {}",
-                batch.essence.timestamp,
+                batch.timestamp,
                 batch_origin.timestamp
             );
-            return BatchStatus::Drop;
+            return BatchStatus::Drop(BatchDropReason::BeforeOrigin);
         }
 
         // From the spec:
         // "batch.timestamp > batch_origin.time + max_sequencer_drift: enforce the L2 timestamp
         //  drift rule, but with exceptions to preserve above min L2 timestamp invariant:"
-        if batch.essence.timestamp > batch_origin.timestamp + self.config.max_seq_drift {
+        if batch.timestamp > batch_origin.timestamp + self.config.max_seq_drift {
             #[cfg(not(target_os = "zkvm"))]
             log::debug!(
                 "Sequencer drift detected: {} > {} + {}",
-                batch.essence.timestamp,
+                batch.timestamp,
                 batch_origin.timestamp,
                 self.config.max_seq_drift
             );
 
             // From the spec:
             // "len(batch.transactions) > 0: -> drop"
-            if !batch.essence.transactions.is_empty() {
+            if !batch.transactions.is_empty() {
                 #[cfg(not(target_os = "zkvm"))]
                 log::warn!("Sequencer drift detected for non-empty batch; drop.");
-                return BatchStatus::Drop;
+                return BatchStatus::Drop(BatchDropReason::SequencerDrift);
             }
 
             // From the spec:
             // "len(batch.transactions) == 0:"
             //    epoch.number == batch.epoch_num: this implies the batch does not already
             //    advance the L1 origin, and must thus be checked against next_epoch."
-            if epoch.number == batch.essence.epoch_num {
+            if epoch.number == batch.epoch_num {
                 if let Some(next_epoch) = next_epoch {
                     // From the spec:
                     // "If batch.timestamp >= next_epoch.time -> drop"
-                    if batch.essence.timestamp >= next_epoch.timestamp {
+                    if batch.timestamp >= next_epoch.timestamp {
                         #[cfg(not(target_os = "zkvm"))]
-                        log::warn!("Sequencer drift detected; drop; batch timestamp is too far into the future. {} >= {}", batch.essence.timestamp, next_epoch.timestamp);
-                        return BatchStatus::Drop;
+                        log::warn!("Sequencer drift detected; drop; batch timestamp is too far into the future. {} >= {}", batch.timestamp, next_epoch.timestamp);
+                        return BatchStatus::Drop(BatchDropReason::SequencerDrift);
                     }
                 } else {
                     // From the spec:
@@ -488,14 +1056,571 @@ impl Batcher {
         //  invalid or derived by other means exclusively:
         //    any transaction that is empty (zero length byte string)
         //    any deposited transactions (identified by the transaction type prefix byte)"
-        for tx in &batch.essence.transactions {
+        for tx in &batch.transactions {
             if matches!(tx.first(), None | Some(&OPTIMISM_DEPOSITED_TX_TYPE)) {
                 #[cfg(not(target_os = "zkvm"))]
                 log::warn!("Batch contains empty or invalid transaction");
-                return BatchStatus::Drop;
+                return BatchStatus::Drop(BatchDropReason::InvalidTransaction);
             }
         }
 
         BatchStatus::Accept
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use zeth_primitives::{
+        b256,
+        receipt::{Log, Receipt},
+        uint, B256,
+    };
+
+    use super::*;
+    use crate::optimism::{batcher_db::EthGapError, batches::SpanBatchElement};
+
+    const L1_BLOCK_TIME: u64 = 12;
+
+    /// Returns a chain of synthetic L1 blocks without any transactions.
+    fn eth_chain(
+        first_number: BlockNumber,
+        timestamp: u64,
+        len: usize,
+    ) -> Vec<BlockInput<EthereumTxEssence>> {
+        let mut blocks: Vec<BlockInput<EthereumTxEssence>> = Vec::with_capacity(len);
+        for i in 0..len as u64 {
+            let parent_hash = blocks
+                .last()
+                .map(|b| b.block_header.hash())
+                .unwrap_or_default();
+            blocks.push(BlockInput {
+                block_header: Header {
+                    parent_hash,
+                    number: first_number + i,
+                    timestamp: U256::from(timestamp + i * L1_BLOCK_TIME),
+                    // blocks at the gas target keep the base fee constant
+                    gas_limit: uint!(30_000_000_U256),
+                    gas_used: uint!(15_000_000_U256),
+                    base_fee_per_gas: uint!(1_000_000_000_U256),
+                    ..Default::default()
+                },
+                transactions: vec![],
+                receipts: None,
+                blob_sidecars: vec![],
+            });
+        }
+        blocks
+    }
+
+    /// Returns an L2 head whose L1 origin is the given block.
+    fn op_head(eth_head: &BlockInput<EthereumTxEssence>) -> L2BlockInfo {
+        L2BlockInfo {
+            hash: BlockHash::with_last_byte(1),
+            timestamp: eth_head.block_header.timestamp.to(),
+            l1_origin: BlockId {
+                number: eth_head.block_header.number,
+                hash: eth_head.block_header.hash(),
+            },
+        }
+    }
+
+    /// Returns the receipt of a system config update of the batch sender.
+    fn batch_sender_update(config: &ChainConfig, batch_sender: Address) -> Receipt {
+        // ABI encoded bytes containing the batcher hash
+        let mut data = [0u8; 96];
+        data[31] = 32;
+        data[63] = 32;
+        data[76..96].copy_from_slice(batch_sender.as_slice());
+        let log = Log {
+            address: config.system_config_contract,
+            topics: vec![
+                // ConfigUpdate(uint256,uint8,bytes)
+                b256!("1d2b0bda21d56b8bd12d4f94ebacffdfb35f5e226f84b461103bb8beab6353be"),
+                // version 0
+                B256::ZERO,
+                // type 0: batcherHash
+                B256::ZERO,
+            ],
+            data: data.to_vec().into(),
+        };
+        Receipt::new(0, true, U256::ZERO, vec![log])
+    }
+
+    #[test]
+    fn batch_sender_rotation() {
+        let config = ChainConfig::optimism();
+        let old_sender = config.system_config.batch_sender;
+        let new_sender = Address::repeat_byte(0xba);
+
+        // rotate the batch sender in the third block
+        let mut eth_blocks = eth_chain(100, 1_700_000_000, 4);
+        let receipt = batch_sender_update(&config, new_sender);
+        eth_blocks[2].block_header.logs_bloom = receipt.payload.logs_bloom;
+        eth_blocks[2].receipts = Some(vec![receipt]);
+        eth_blocks[3].block_header.parent_hash = eth_blocks[2].block_header.hash();
+
+        let mut batcher = Batcher::new(config, op_head(&eth_blocks[0]), &eth_blocks[0]).unwrap();
+        batcher.process_l1_block(&eth_blocks[1]).unwrap();
+        assert_eq!(batcher.effective_batch_sender(101), Some(old_sender));
+        assert_eq!(batcher.effective_batch_sender(102), Some(old_sender));
+
+        for eth_block in &eth_blocks[2..] {
+            batcher.process_l1_block(eth_block).unwrap();
+        }
+        assert_eq!(batcher.effective_batch_sender(99), None);
+        assert_eq!(batcher.effective_batch_sender(100), Some(old_sender));
+        assert_eq!(batcher.effective_batch_sender(101), Some(old_sender));
+        assert_eq!(batcher.effective_batch_sender(102), Some(new_sender));
+        assert_eq!(batcher.effective_batch_sender(103), Some(new_sender));
+        assert_eq!(batcher.config().system_config.batch_sender, new_sender);
+    }
+
+    /// Returns the receipt of a system config update of the L2 gas limit.
+    fn gas_limit_update(config: &ChainConfig, gas_limit: U256) -> Receipt {
+        // ABI encoded bytes containing the gas limit
+        let mut data = [0u8; 96];
+        data[31] = 32;
+        data[63] = 32;
+        data[64..96].copy_from_slice(&gas_limit.to_be_bytes::<32>());
+        let log = Log {
+            address: config.system_config_contract,
+            topics: vec![
+                // ConfigUpdate(uint256,uint8,bytes)
+                b256!("1d2b0bda21d56b8bd12d4f94ebacffdfb35f5e226f84b461103bb8beab6353be"),
+                // version 0
+                B256::ZERO,
+                // type 2: gasLimit
+                B256::with_last_byte(2),
+            ],
+            data: data.to_vec().into(),
+        };
+        Receipt::new(0, true, U256::ZERO, vec![log])
+    }
+
+    #[test]
+    fn gas_limit_update_epoch() {
+        let config = ChainConfig::optimism();
+        let old_gas_limit = config.system_config.gas_limit;
+        let new_gas_limit = uint!(60_000_000_U256);
+
+        // raise the gas limit in the third block
+        let mut eth_blocks = eth_chain(100, 1_700_000_000, 4);
+        let receipt = gas_limit_update(&config, new_gas_limit);
+        eth_blocks[2].block_header.logs_bloom = receipt.payload.logs_bloom;
+        eth_blocks[2].receipts = Some(vec![receipt]);
+        eth_blocks[3].block_header.parent_hash = eth_blocks[2].block_header.hash();
+
+        let mut batcher = Batcher::new(config, op_head(&eth_blocks[0]), &eth_blocks[0]).unwrap();
+        for eth_block in &eth_blocks[1..] {
+            batcher.process_l1_block(eth_block).unwrap();
+        }
+        // the L2 blocks of an epoch use the gas limit of its L1 origin
+        assert_eq!(batcher.effective_gas_limit(99), None);
+        assert_eq!(batcher.effective_gas_limit(101), Some(old_gas_limit));
+        assert_eq!(batcher.effective_gas_limit(102), Some(new_gas_limit));
+        assert_eq!(batcher.effective_gas_limit(103), Some(new_gas_limit));
+    }
+
+    #[test]
+    fn eth_block_gap() {
+        let config = ChainConfig::optimism();
+        let eth_blocks = eth_chain(100, 1_700_000_000, 4);
+
+        let mut batcher = Batcher::new(config, op_head(&eth_blocks[0]), &eth_blocks[0]).unwrap();
+        let err = batcher.process_l1_block(&eth_blocks[3]).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<EthGapError>(),
+            Some(&EthGapError::SkippedBlocks {
+                first: 101,
+                last: 102,
+                last_hash: eth_blocks[2].block_header.hash(),
+            })
+        );
+
+        let mut orphan = eth_blocks[1].clone();
+        orphan.block_header.parent_hash = B256::repeat_byte(0xff);
+        let err = batcher.process_l1_block(&orphan).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<EthGapError>(),
+            Some(&EthGapError::InvalidParent {
+                number: 101,
+                parent_hash: B256::repeat_byte(0xff),
+                expected: eth_blocks[0].block_header.hash(),
+            })
+        );
+
+        batcher.process_l1_block(&eth_blocks[1]).unwrap();
+    }
+
+    #[test]
+    fn invalid_excess_blob_gas() {
+        let config = ChainConfig::optimism();
+        let mut eth_blocks = eth_chain(100, 1_700_000_000, 2);
+        eth_blocks[0].block_header.excess_blob_gas = Some(uint!(393_216_U256));
+        eth_blocks[0].block_header.blob_gas_used = Some(uint!(786_432_U256));
+        eth_blocks[1].block_header.parent_hash = eth_blocks[0].block_header.hash();
+
+        let mut batcher = Batcher::new(config, op_head(&eth_blocks[0]), &eth_blocks[0]).unwrap();
+        // the parent exceeded the target by one more target
+        for excess_blob_gas in [None, Some(uint!(393_216_U256))] {
+            let mut eth_block = eth_blocks[1].clone();
+            eth_block.block_header.excess_blob_gas = excess_blob_gas;
+            batcher.process_l1_block(&eth_block).unwrap_err();
+        }
+
+        eth_blocks[1].block_header.excess_blob_gas = Some(uint!(786_432_U256));
+        batcher.process_l1_block(&eth_blocks[1]).unwrap();
+    }
+
+    #[test]
+    fn missing_excess_blob_gas_after_ecotone() {
+        let config = ChainConfig::optimism();
+        let eth_blocks = eth_chain(100, config.ecotone_time.unwrap(), 2);
+
+        let mut batcher = Batcher::new(config, op_head(&eth_blocks[0]), &eth_blocks[0]).unwrap();
+        batcher.process_l1_block(&eth_blocks[1]).unwrap_err();
+    }
+
+    #[test]
+    fn l1_eip1559_constants() {
+        // an L1 targeting a third of its gas limit
+        let mut config = ChainConfig::optimism();
+        config.l1_eip1559_constants.elasticity_multiplier = uint!(3_U256);
+        let mut eth_blocks = eth_chain(100, 1_700_000_000, 2);
+
+        let mut batcher = Batcher::new(config, op_head(&eth_blocks[0]), &eth_blocks[0]).unwrap();
+        // the parent used 1.5 times its target, raising the base fee by 1/16
+        batcher.process_l1_block(&eth_blocks[1]).unwrap_err();
+        eth_blocks[1].block_header.base_fee_per_gas = uint!(1_062_500_000_U256);
+        batcher.process_l1_block(&eth_blocks[1]).unwrap();
+    }
+
+    #[test]
+    fn dropped_batches() {
+        let config = ChainConfig::optimism();
+        let eth_blocks = eth_chain(100, 1_700_000_000, 2);
+        let op_head = op_head(&eth_blocks[0]);
+        let mut batcher = Batcher::new(config, op_head, &eth_blocks[0]).unwrap();
+
+        let batch = |timestamp: u64, parent_hash: B256| BatchWithInclusion {
+            data: BatchData::Singular(BatchEssence {
+                parent_hash,
+                epoch_num: 100,
+                epoch_hash: op_head.l1_origin.hash,
+                timestamp,
+                transactions: vec![],
+            }),
+            inclusion_block_number: 101,
+            channel_id: 1,
+        };
+        for batch in [
+            batch(op_head.timestamp, op_head.hash),
+            batch(op_head.timestamp + 2, B256::ZERO),
+        ] {
+            batcher.batches.push(batch);
+        }
+
+        assert!(batcher.read_batch().unwrap().is_none());
+        let reasons: Vec<_> = batcher
+            .dropped_batches()
+            .iter()
+            .map(|dropped| (dropped.timestamp, dropped.reason))
+            .collect();
+        assert_eq!(
+            reasons,
+            [
+                (op_head.timestamp, BatchDropReason::TooOld),
+                (op_head.timestamp + 2, BatchDropReason::WrongParent),
+            ]
+        );
+    }
+
+    #[test]
+    fn span_batch() {
+        let mut config = ChainConfig::optimism();
+        config.delta_time = Some(0);
+        let eth_blocks = eth_chain(100, 1_700_000_000, 2);
+        let op_head = op_head(&eth_blocks[0]);
+        let mut batcher = Batcher::new(config, op_head, &eth_blocks[0]).unwrap();
+
+        let span_batch = |parent_hash: B256| SpanBatch {
+            parent_check: parent_hash[..20].try_into().unwrap(),
+            l1_origin_check: op_head.l1_origin.hash[..20].try_into().unwrap(),
+            blocks: (1..=2)
+                .map(|i| SpanBatchElement {
+                    epoch_num: 100,
+                    timestamp: op_head.timestamp + 2 * i,
+                    transactions: vec![],
+                })
+                .collect(),
+        };
+        for parent_hash in [B256::repeat_byte(0xff), op_head.hash] {
+            batcher.batches.push(BatchWithInclusion {
+                data: BatchData::Span(span_batch(parent_hash)),
+                inclusion_block_number: 101,
+                channel_id: parent_hash[0].into(),
+            });
+        }
+
+        // the first block follows the safe head
+        let batch = batcher.read_batch().unwrap().unwrap();
+        assert_eq!(batch.0.parent_hash, op_head.hash);
+        assert_eq!(batch.0.epoch_hash, op_head.l1_origin.hash);
+        assert_eq!(batch.0.timestamp, op_head.timestamp + 2);
+        assert_eq!(
+            batcher.dropped_batches()[0].reason,
+            BatchDropReason::WrongParent
+        );
+        let source = BatchSource {
+            channel_id: op_head.hash[0].into(),
+            inclusion_block_number: 101,
+        };
+        assert_eq!(batcher.batch_source(), Some(source));
+
+        // the second block follows the block derived from the first
+        batcher.state.safe_head = L2BlockInfo {
+            hash: BlockHash::with_last_byte(2),
+            timestamp: batch.0.timestamp,
+            l1_origin: op_head.l1_origin,
+        };
+        let batch = batcher.read_batch().unwrap().unwrap();
+        assert_eq!(batch.0.parent_hash, BlockHash::with_last_byte(2));
+        assert_eq!(batch.0.timestamp, op_head.timestamp + 4);
+        assert_eq!(batcher.batch_source(), Some(source));
+        assert!(batcher.read_batch().unwrap().is_none());
+    }
+
+    #[test]
+    fn overlapping_span_batch() {
+        let mut config = ChainConfig::optimism();
+        config.delta_time = Some(0);
+        let eth_blocks = eth_chain(100, 1_700_000_000, 2);
+        let op_head = op_head(&eth_blocks[0]);
+        let mut batcher = Batcher::new(config, op_head, &eth_blocks[0]).unwrap();
+
+        // the first block of the span batch is the safe head itself
+        batcher.batches.push(BatchWithInclusion {
+            data: BatchData::Span(SpanBatch {
+                parent_check: B256::repeat_byte(0xff)[..20].try_into().unwrap(),
+                l1_origin_check: op_head.l1_origin.hash[..20].try_into().unwrap(),
+                blocks: (0..=1)
+                    .map(|i| SpanBatchElement {
+                        epoch_num: 100,
+                        timestamp: op_head.timestamp + 2 * i,
+                        transactions: vec![],
+                    })
+                    .collect(),
+            }),
+            inclusion_block_number: 101,
+            channel_id: 1,
+        });
+
+        assert!(batcher.read_batch().unwrap().is_none());
+        assert_eq!(
+            batcher.dropped_batches()[0].reason,
+            BatchDropReason::OverlapsSafeHead
+        );
+    }
+
+    #[test]
+    fn deposits_only() {
+        for blocktime in [1, 2, 5] {
+            let mut config = ChainConfig::optimism();
+            config.deposits_only = true;
+            config.seq_window_size = 2;
+            config.blocktime = blocktime;
+
+            let eth_blocks = eth_chain(100, 1_700_000_000, 4);
+            let op_head = op_head(&eth_blocks[0]);
+            let mut batcher = Batcher::new(config, op_head, &eth_blocks[0]).unwrap();
+
+            // no batch can be derived while the sequencing window is open
+            batcher.process_l1_block(&eth_blocks[1]).unwrap();
+            assert!(batcher.read_batch().unwrap().is_none());
+
+            // once its last block is processed, the default batch repeats the current epoch
+            batcher.process_l1_block(&eth_blocks[2]).unwrap();
+            let batch = batcher.read_batch().unwrap().unwrap();
+            assert_eq!(
+                batch.0,
+                BatchEssence {
+                    parent_hash: op_head.hash,
+                    epoch_num: 100,
+                    epoch_hash: op_head.l1_origin.hash,
+                    timestamp: 1_700_000_000 + blocktime,
+                    transactions: vec![],
+                }
+            );
+            assert_eq!(batcher.batch_source(), None);
+        }
+    }
+
+    #[test]
+    fn silent_batcher() {
+        const T: u64 = 1_700_000_000;
+        let mut config = ChainConfig::optimism();
+        config.seq_window_size = 2;
+        config.blocktime = 6;
+
+        let eth_blocks = eth_chain(100, T, 4);
+        let mut batcher = Batcher::new(config, op_head(&eth_blocks[0]), &eth_blocks[0]).unwrap();
+        batcher.process_l1_block(&eth_blocks[1]).unwrap();
+        assert!(batcher.read_batch().unwrap().is_none());
+
+        // once the window of epoch 100 has expired, its remaining blocks are forced
+        batcher.process_l1_block(&eth_blocks[2]).unwrap();
+        let batch = batcher.read_batch().unwrap().unwrap();
+        assert_eq!((batch.0.epoch_num, batch.0.timestamp), (100, T + 6));
+        assert!(batch.0.transactions.is_empty());
+        batcher.state.safe_head.hash = BlockHash::with_last_byte(2);
+        batcher.state.safe_head.timestamp = batch.0.timestamp;
+
+        // the first block of epoch 101 waits for the window of epoch 101 to expire
+        assert!(batcher.read_batch().unwrap().is_none());
+        batcher.process_l1_block(&eth_blocks[3]).unwrap();
+        let batch = batcher.read_batch().unwrap().unwrap();
+        assert_eq!(
+            batch.0,
+            BatchEssence {
+                parent_hash: BlockHash::with_last_byte(2),
+                epoch_num: 101,
+                epoch_hash: eth_blocks[1].block_header.hash(),
+                timestamp: T + 12,
+                transactions: vec![],
+            }
+        );
+    }
+
+    #[test]
+    fn resume_from_checkpoint() {
+        const T: u64 = 1_700_000_000;
+        let mut config = ChainConfig::optimism();
+        config.seq_window_size = 2;
+        config.blocktime = 6;
+        let new_gas_limit = uint!(60_000_000_U256);
+
+        // raise the gas limit in the third block
+        let mut eth_blocks = eth_chain(100, T, 4);
+        let receipt = gas_limit_update(&config, new_gas_limit);
+        eth_blocks[2].block_header.logs_bloom = receipt.payload.logs_bloom;
+        eth_blocks[2].receipts = Some(vec![receipt]);
+        eth_blocks[3].block_header.parent_hash = eth_blocks[2].block_header.hash();
+
+        let mut batcher =
+            Batcher::new(config.clone(), op_head(&eth_blocks[0]), &eth_blocks[0]).unwrap();
+        batcher.process_l1_block(&eth_blocks[1]).unwrap();
+        batcher.process_l1_block(&eth_blocks[2]).unwrap();
+        let batch = batcher.read_batch().unwrap().unwrap();
+        batcher.state.safe_head.hash = BlockHash::with_last_byte(2);
+        batcher.state.safe_head.timestamp = batch.0.timestamp;
+
+        // resume from the serialized checkpoint with the initial configuration
+        let checkpoint = bincode::serialize(&batcher.checkpoint()).unwrap();
+        let mut resumed =
+            Batcher::from_checkpoint(config, bincode::deserialize(&checkpoint).unwrap());
+        assert_eq!(resumed.config().system_config.gas_limit, new_gas_limit);
+        assert_eq!(
+            resumed.effective_gas_limit(101),
+            batcher.effective_gas_limit(101)
+        );
+        assert_eq!(resumed.effective_gas_limit(102), Some(new_gas_limit));
+
+        // both batchers derive the same batches from the remaining blocks
+        for batcher in [&mut batcher, &mut resumed] {
+            assert!(batcher.read_batch().unwrap().is_none());
+            batcher.process_l1_block(&eth_blocks[3]).unwrap();
+        }
+        let batch = batcher.read_batch().unwrap().unwrap();
+        assert_eq!((batch.0.epoch_num, batch.0.timestamp), (101, T + 12));
+        assert_eq!(resumed.read_batch().unwrap(), Some(batch));
+    }
+
+    /// Returns a batcher at the start of epoch 100, optionally with the next epoch 101.
+    fn drift_batcher(next_epoch: bool) -> (Batcher, Vec<BlockInput<EthereumTxEssence>>) {
+        let mut config = ChainConfig::optimism();
+        config.max_seq_drift = 4;
+        config.seq_window_size = 2;
+        let eth_blocks = eth_chain(100, 1_700_000_000, 2);
+        let mut batcher = Batcher::new(config, op_head(&eth_blocks[0]), &eth_blocks[0]).unwrap();
+        if next_epoch {
+            batcher.process_l1_block(&eth_blocks[1]).unwrap();
+        }
+        (batcher, eth_blocks)
+    }
+
+    /// Returns a batch following the safe head at the given timestamp.
+    fn next_batch(
+        batcher: &mut Batcher,
+        epoch: &BlockInput<EthereumTxEssence>,
+        timestamp: u64,
+        transactions: usize,
+    ) -> BatchEssence {
+        batcher.state.safe_head.timestamp = timestamp - batcher.config.blocktime;
+        BatchEssence {
+            parent_hash: batcher.state.safe_head.hash,
+            epoch_num: epoch.block_header.number,
+            epoch_hash: epoch.block_header.hash(),
+            timestamp,
+            transactions: vec![vec![2u8; 8].into(); transactions],
+        }
+    }
+
+    #[test]
+    fn sequencing_window() {
+        let (mut batcher, eth_blocks) = drift_batcher(true);
+        let batch = next_batch(&mut batcher, &eth_blocks[0], 1_700_000_002, 1);
+
+        // the batch must be included within epoch.number + seq_window_size
+        assert_eq!(batcher.batch_status(&batch, 102), BatchStatus::Accept);
+        assert_eq!(
+            batcher.batch_status(&batch, 103),
+            BatchStatus::Drop(BatchDropReason::NotTimely)
+        );
+    }
+
+    #[test]
+    fn sequencer_drift() {
+        // L1 blocks 100 and 101 have timestamps T and T + 12, with a drift of 4
+        const T: u64 = 1_700_000_000;
+        let (mut batcher, eth_blocks) = drift_batcher(true);
+
+        // within the drift, any batch is accepted
+        let batch = next_batch(&mut batcher, &eth_blocks[0], T + 4, 1);
+        assert_eq!(batcher.batch_status(&batch, 101), BatchStatus::Accept);
+
+        // beyond the drift, only empty batches are accepted
+        let batch = next_batch(&mut batcher, &eth_blocks[0], T + 6, 1);
+        assert_eq!(
+            batcher.batch_status(&batch, 101),
+            BatchStatus::Drop(BatchDropReason::SequencerDrift)
+        );
+        let batch = next_batch(&mut batcher, &eth_blocks[0], T + 6, 0);
+        assert_eq!(batcher.batch_status(&batch, 101), BatchStatus::Accept);
+
+        // an empty batch must adopt the next L1 origin once its timestamp allows it
+        let batch = next_batch(&mut batcher, &eth_blocks[0], T + 12, 0);
+        assert_eq!(
+            batcher.batch_status(&batch, 101),
+            BatchStatus::Drop(BatchDropReason::SequencerDrift)
+        );
+        let batch = next_batch(&mut batcher, &eth_blocks[1], T + 12, 1);
+        assert_eq!(batcher.batch_status(&batch, 101), BatchStatus::Accept);
+
+        // the drift is relative to the L1 origin of the batch
+        let batch = next_batch(&mut batcher, &eth_blocks[1], T + 18, 1);
+        assert_eq!(
+            batcher.batch_status(&batch, 101),
+            BatchStatus::Drop(BatchDropReason::SequencerDrift)
+        );
+        let batch = next_batch(&mut batcher, &eth_blocks[1], T + 8, 0);
+        assert_eq!(
+            batcher.batch_status(&batch, 101),
+            BatchStatus::Drop(BatchDropReason::BeforeOrigin)
+        );
+
+        // without the next L1 origin, an empty batch beyond the drift is undecided
+        let (mut batcher, eth_blocks) = drift_batcher(false);
+        let batch = next_batch(&mut batcher, &eth_blocks[0], T + 6, 0);
+        assert_eq!(batcher.batch_status(&batch, 100), BatchStatus::Undecided);
+    }
+}
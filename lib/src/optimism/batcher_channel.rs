@@ -15,55 +15,224 @@
 use std::{
     collections::{BTreeMap, VecDeque},
     io::Read,
+    iter, mem,
 };
 
 use anyhow::{bail, ensure, Context, Result};
 use bytes::Buf;
 use libflate::zlib::Decoder;
-use revm::primitives::SpecId;
+use serde::{Deserialize, Serialize};
 use zeth_primitives::{
-    alloy_rlp::Decodable,
-    batch::Batch,
+    keccak::keccak,
     transactions::{ethereum::EthereumTxEssence, Transaction, TxEssence},
-    Address, BlockNumber,
+    Address, BlockNumber, B256,
 };
 
-use super::{batcher::BatchWithInclusion, config::ChainConfig};
+use super::{
+    batcher::BatchWithInclusion,
+    batches::BatchDecoder,
+    blob::{blob_to_data, BlobSidecar, BlobSource},
+    config::ChainConfig,
+    stage::Stage,
+    unsafe_payload::recover_address,
+};
+#[cfg(not(target_os = "zkvm"))]
+use crate::host::channel_cache::ChannelCache;
 use crate::utils::MultiReader;
 
+/// Stand-in for the host-only channel cache, which is never used inside the guest.
+#[cfg(target_os = "zkvm")]
+type ChannelCache = ();
+
 pub const MAX_RLP_BYTES_PER_CHANNEL: u64 = 10_000_000;
 
-pub struct BatcherChannels {
-    spec_id: SpecId,
+/// The maximum byte size of all pending channels of the rollup spec, which op-node does
+/// not make configurable.
+pub const MAX_CHANNEL_BANK_SIZE: u64 = 100_000_000;
+
+/// Byte length of the `r || s || v` signature prefixing the data of an authenticated
+/// channel.
+pub const CHANNEL_SIGNATURE_LEN: usize = 65;
+
+/// The reason why a transaction sent to the batch inbox was dropped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum DropReason {
+    /// The sender could not be recovered from the signature.
+    InvalidSignature,
+    /// The data could not be decoded into frames.
+    InvalidFrames,
+    /// A blob of the transaction does not encode valid data.
+    InvalidBlob,
+}
+
+/// A transaction sent to the batch inbox that was dropped during derivation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DroppedTransaction {
+    /// Number of the L1 block containing the transaction.
+    pub block_number: BlockNumber,
+    /// Index of the transaction in the L1 block.
+    pub tx_index: usize,
+    /// The reason why the transaction was dropped.
+    pub reason: DropReason,
+}
+
+/// The reason why a channel, or the rest of its data, was dropped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum ChannelDropReason {
+    /// The channel was not complete before the channel timeout.
+    TimedOut,
+    /// The channel was pruned to keep the channel bank below its maximum size.
+    Pruned,
+    /// The channel data was not signed by the batcher data signer.
+    InvalidSignature,
+    /// The channel data could not be decompressed or decoded into batches; the batches
+    /// before the invalid one are still read.
+    InvalidData,
+}
+
+/// A channel that was dropped during derivation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DroppedChannel {
+    /// The channel ID.
+    pub channel_id: ChannelId,
+    /// Number of the L1 block in which the channel was dropped.
+    pub block_number: BlockNumber,
+    /// The reason why the channel was dropped.
+    pub reason: ChannelDropReason,
+}
+
+/// The reason why a frame was not added to its channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum FrameSkipReason {
+    /// The channel of the frame has timed out.
+    ChannelTimedOut,
+    /// The frame is a duplicate, or conflicts with the closing frame of the channel.
+    Invalid,
+}
+
+/// A frame that was not added to its channel during derivation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SkippedFrame {
+    /// The channel ID of the frame.
+    pub channel_id: ChannelId,
+    /// The number of the frame within its channel.
+    pub frame_number: u16,
+    /// Number of the L1 block containing the frame.
+    pub block_number: BlockNumber,
+    /// The reason why the frame was skipped.
+    pub reason: FrameSkipReason,
+}
+
+/// The batcher transactions of an L1 block, read by the [FrameQueue].
+pub struct BatcherTransactions<'a> {
+    /// Number of the L1 block.
+    pub block_number: BlockNumber,
+    /// Timestamp of the L1 block.
+    pub block_timestamp: u64,
+    /// The batch sender authenticated for the L1 block.
+    pub batch_sender: Address,
+    /// All transactions of the L1 block.
+    pub transactions: &'a [Transaction<EthereumTxEssence>],
+    /// The sidecars of the blobs of the batcher transactions.
+    pub blob_sidecars: &'a [BlobSidecar],
+}
+
+/// The frames of a single batcher transaction.
+pub(crate) struct TransactionFrames {
+    /// Number of the L1 block containing the transaction.
+    pub block_number: BlockNumber,
+    /// Timestamp of the L1 block containing the transaction.
+    pub block_timestamp: u64,
+    /// The frames of the transaction.
+    pub frames: Vec<Frame>,
+}
+
+/// A channel that is ready to be read.
+pub(crate) struct ReadyChannel {
+    /// Number of the L1 block in which the channel became ready.
+    pub block_number: BlockNumber,
+    /// Timestamp of the L1 block in which the channel became ready.
+    pub block_timestamp: u64,
+    /// The channel.
+    channel: Channel,
+    /// The channel data decompressed ahead of reading the channel, only on the host.
+    decompressed: Option<Result<Vec<u8>, ChannelDropReason>>,
+}
+
+/// The stage retrieving the batcher transactions from the L1 blocks and parsing their
+/// frames.
+pub(crate) struct FrameQueue {
     batch_inbox: Address,
-    max_channel_bank_size: u64,
-    channel_timeout: u64,
-    channels: VecDeque<Channel>,
-    batches: VecDeque<Vec<BatchWithInclusion>>,
+    ecotone_time: Option<u64>,
+    frames: VecDeque<TransactionFrames>,
+    dropped_transactions: Vec<DroppedTransaction>,
 }
 
-impl BatcherChannels {
-    pub fn new(config: &ChainConfig, spec_id: SpecId) -> Self {
+impl FrameQueue {
+    pub fn new(config: &ChainConfig) -> Self {
         Self {
-            spec_id,
             batch_inbox: config.batch_inbox,
-            max_channel_bank_size: config.max_channel_bank_size,
-            channel_timeout: config.channel_timeout,
-            channels: VecDeque::new(),
-            batches: VecDeque::new(),
+            ecotone_time: config.ecotone_time,
+            frames: VecDeque::new(),
+            dropped_transactions: Vec::new(),
         }
     }
 
-    /// Processes all batcher transactions in the given block.
-    /// The given batch_sender must match the potentially updated batcher address loaded
-    /// from the system config.
-    pub fn process_l1_transactions(
+    /// Returns whether Ecotone is active at the given L1 timestamp.
+    fn is_ecotone(&self, timestamp: u64) -> bool {
+        matches!(self.ecotone_time, Some(ecotone_time) if timestamp >= ecotone_time)
+    }
+
+    /// Parses the frames of one piece of batcher data, i.e. the calldata or a single blob
+    /// of a batcher transaction.
+    fn push_data(
         &mut self,
-        batch_sender: Address,
         block_number: BlockNumber,
-        transactions: &Vec<Transaction<EthereumTxEssence>>,
-    ) -> Result<()> {
-        for tx in transactions {
+        block_timestamp: u64,
+        tx_index: usize,
+        data: &[u8],
+    ) {
+        // From the spec:
+        // "If any one frame fails to parse, the all frames in the transaction are rejected."
+        match Frame::process_batcher_data(data) {
+            Ok(frames) => self.frames.push_back(TransactionFrames {
+                block_number,
+                block_timestamp,
+                frames,
+            }),
+            Err(_err) => {
+                #[cfg(not(target_os = "zkvm"))]
+                log::warn!(
+                    "failed to decode all frames; skip entire batcher tx: {:#}",
+                    _err
+                );
+                self.dropped_transactions.push(DroppedTransaction {
+                    block_number,
+                    tx_index,
+                    reason: DropReason::InvalidFrames,
+                });
+            }
+        };
+    }
+}
+
+impl Stage for FrameQueue {
+    type Input<'a> = BatcherTransactions<'a>;
+    type Output = TransactionFrames;
+
+    /// Parses the frames of all batcher transactions in the given block.
+    /// The given batch_sender must match the potentially updated batcher address loaded
+    /// from the system config.
+    ///
+    /// As in op-node, the frames are queued in the order of their data in the block: by
+    /// transaction index and, within a blob transaction, by the index of the blob in its
+    /// versioned hashes. Calldata and blob transactions may be mixed in any order, the
+    /// order of the sidecars is irrelevant, and repeated data is queued again, leaving it
+    /// to the [ChannelBank] to skip the duplicate frames.
+    fn push(&mut self, input: BatcherTransactions<'_>) -> Result<()> {
+        let block_number = input.block_number;
+        let blobs = BlobSource::new(input.blob_sidecars);
+        for (tx_index, tx) in input.transactions.iter().enumerate() {
             // From the spec:
             // "The receiver must be the configured batcher inbox address."
             if tx.essence.to() != Some(self.batch_inbox) {
@@ -72,83 +241,91 @@ impl BatcherChannels {
             // From the spec:
             // "The sender must match the batcher address loaded from the system config matching
             //  the L1 block of the data."
-            if tx.recover_from().context("invalid signature")? != batch_sender {
-                continue;
-            }
-
-            #[cfg(not(target_os = "zkvm"))]
-            log::trace!("received batcher tx: {}", tx.hash());
-
-            // From the spec:
-            // "If any one frame fails to parse, the all frames in the transaction are rejected."
-            let frames = match Frame::process_batcher_transaction(&tx.essence) {
-                Ok(frames) => frames,
+            match tx.recover_from() {
+                Ok(sender) if sender == input.batch_sender => {}
+                Ok(_) => continue,
                 Err(_err) => {
                     #[cfg(not(target_os = "zkvm"))]
-                    log::warn!(
-                        "failed to decode all frames; skip entire batcher tx: {:#}",
-                        _err
-                    );
+                    log::warn!("invalid signature; skip batcher tx: {:#}", _err);
+                    self.dropped_transactions.push(DroppedTransaction {
+                        block_number,
+                        tx_index,
+                        reason: DropReason::InvalidSignature,
+                    });
                     continue;
                 }
-            };
-
-            // load received frames into the channel bank
-            for frame in frames {
-                #[cfg(not(target_os = "zkvm"))]
-                log::trace!(
-                    "received frame: channel_id={}, frame_number={}, is_last={}",
-                    frame.channel_id,
-                    frame.number,
-                    frame.is_last
-                );
-
-                self.add_frame(block_number, frame);
             }
 
-            // Remove all timed-out channels at the front of the queue. From the spec:
-            // "Upon reading, while the first opened channel is timed-out, remove it from the
-            // channel-bank."
-            while matches!(self.channels.front(), Some(channel) if block_number > channel.open_l1_block + self.channel_timeout)
-            {
-                let _channel = self.channels.pop_front().unwrap();
-                #[cfg(not(target_os = "zkvm"))]
-                log::debug!("timed-out channel: {}", _channel.id);
-            }
+            #[cfg(not(target_os = "zkvm"))]
+            log::trace!("received batcher tx: {}", tx.hash());
 
-            if self.spec_id >= SpecId::CANYON {
-                // From the spec:
-                // "After the Canyon network upgrade, the entire channel bank is scanned in FIFO
-                //  order and the first ready (i.e. not timed-out) channel will be returned."
-                self.channels.retain(|channel| {
-                    if channel.is_ready() {
-                        #[cfg(not(target_os = "zkvm"))]
-                        log::trace!("channel is ready: {}", channel.id);
-                        self.batches.push_back(channel.read_batches(block_number));
-                        false
-                    } else {
-                        true
+            // After Ecotone, the data of blob transactions is carried in their blobs, each
+            // holding its own frames, and their calldata is ignored
+            match &tx.essence {
+                EthereumTxEssence::Eip4844(blob_tx) if self.is_ecotone(input.block_timestamp) => {
+                    // a missing sidecar is an error of the host, not of the batcher
+                    let tx_blobs = blobs.blobs(blob_tx)?;
+                    for (versioned_hash, blob) in blob_tx.blob_versioned_hashes.iter().zip(tx_blobs)
+                    {
+                        match blob_to_data(blob) {
+                            Ok(data) => {
+                                self.push_data(block_number, input.block_timestamp, tx_index, &data)
+                            }
+                            Err(_err) => {
+                                #[cfg(not(target_os = "zkvm"))]
+                                log::warn!(
+                                    "invalid blob; skip blob {}: {:#}",
+                                    versioned_hash,
+                                    _err
+                                );
+                                self.dropped_transactions.push(DroppedTransaction {
+                                    block_number,
+                                    tx_index,
+                                    reason: DropReason::InvalidBlob,
+                                });
+                            }
+                        }
                     }
-                });
-            } else {
-                // From the spec:
-                // "Prior to the Canyon network upgrade, once the first opened channel, if any, is
-                //  not timed-out and is ready, then it is read and removed from the channel-bank."
-                while matches!(self.channels.front(), Some(channel) if channel.is_ready()) {
-                    let channel = self.channels.pop_front().unwrap();
-                    #[cfg(not(target_os = "zkvm"))]
-                    log::trace!("received channel: {}", channel.id);
-
-                    self.batches.push_back(channel.read_batches(block_number));
                 }
+                essence => self.push_data(
+                    block_number,
+                    input.block_timestamp,
+                    tx_index,
+                    essence.data(),
+                ),
             }
         }
 
         Ok(())
     }
 
-    pub fn read_batches(&mut self) -> Option<Vec<BatchWithInclusion>> {
-        self.batches.pop_front()
+    fn next(&mut self) -> Option<TransactionFrames> {
+        self.frames.pop_front()
+    }
+}
+
+/// The stage assembling the frames into channels, which are passed on once they are
+/// ready.
+pub(crate) struct ChannelBank {
+    config: ChainConfig,
+    channels: VecDeque<Channel>,
+    /// The total size of all pending channels.
+    size: usize,
+    ready: VecDeque<ReadyChannel>,
+    dropped_channels: Vec<DroppedChannel>,
+    skipped_frames: Vec<SkippedFrame>,
+}
+
+impl ChannelBank {
+    pub fn new(config: &ChainConfig) -> Self {
+        Self {
+            config: config.clone(),
+            channels: VecDeque::new(),
+            size: 0,
+            ready: VecDeque::new(),
+            dropped_channels: Vec::new(),
+            skipped_frames: Vec::new(),
+        }
     }
 
     /// Adds a frame to the channel bank. Frames that cannot be added are ignored.
@@ -159,39 +336,56 @@ impl BatcherChannels {
 
         match channel {
             Some(channel) => {
-                if block_number > channel.open_l1_block + self.channel_timeout {
+                let mut skipped = SkippedFrame {
+                    channel_id: frame.channel_id,
+                    frame_number: frame.number,
+                    block_number,
+                    reason: FrameSkipReason::ChannelTimedOut,
+                };
+                if channel.is_timed_out(block_number, self.config.channel_timeout) {
                     // From the spec:
                     // "New frames for timed-out channels are dropped instead of buffered."
                     #[cfg(not(target_os = "zkvm"))]
                     log::warn!("frame's channel is timed out; ignored");
+                    self.skipped_frames.push(skipped);
                     return;
-                } else if let Err(_err) = channel.add_frame(frame) {
+                }
+                // a closing frame can remove frames, so the size can also decrease
+                let previous_size = channel.size;
+                if let Err(_err) = channel.add_frame(frame) {
                     #[cfg(not(target_os = "zkvm"))]
                     log::warn!("failed to add frame to channel; ignored: {:#}", _err);
+                    skipped.reason = FrameSkipReason::Invalid;
+                    self.skipped_frames.push(skipped);
                     return;
                 }
+                self.size = self.size - previous_size + channel.size;
             }
             None => {
                 // Create new channel. From the spec:
                 // "When a channel ID referenced by a frame is not already present in the
                 //  Channel Bank, a new channel is opened, tagged with the current L1
                 //  block, and appended to the channel-queue"
-                self.channels.push_back(Channel::new(block_number, frame));
+                let channel = Channel::new(block_number, frame);
+                self.size += channel.size;
+                self.channels.push_back(channel);
             }
         }
 
         // From the spec:
         // "After successfully inserting a new frame, the ChannelBank is pruned: channels
         //  are dropped in FIFO order, until total_size <= MAX_CHANNEL_BANK_SIZE."
-        self.prune();
+        self.prune(block_number);
     }
 
     /// Enforces max_channel_bank_size by dropping channels in FIFO order.
-    fn prune(&mut self) {
-        let mut total_size = self.total_size();
-        while total_size as u64 > self.max_channel_bank_size {
-            let dropped_channel = self.channels.pop_front().unwrap();
-            total_size -= dropped_channel.size;
+    fn prune(&mut self, block_number: BlockNumber) {
+        debug_assert_eq!(
+            self.size,
+            self.channels.iter().map(|c| c.size).sum::<usize>()
+        );
+        while self.size as u64 > self.config.max_channel_bank_size {
+            let dropped_channel = self.pop_front().unwrap();
 
             #[cfg(not(target_os = "zkvm"))]
             log::debug!(
@@ -199,11 +393,72 @@ impl BatcherChannels {
                 dropped_channel.id,
                 dropped_channel.size
             );
+            self.dropped_channels.push(DroppedChannel {
+                channel_id: dropped_channel.id,
+                block_number,
+                reason: ChannelDropReason::Pruned,
+            });
+        }
+    }
+
+    /// Removes the timed-out channels and moves the ready channels to the ready queue.
+    fn read_channels(&mut self, block_number: BlockNumber, block_timestamp: u64) {
+        // Remove all timed-out channels at the front of the queue. From the spec:
+        // "Upon reading, while the first opened channel is timed-out, remove it from the
+        // channel-bank."
+        // As channels are opened in order, no other channel can be timed out.
+        while matches!(self.channels.front(), Some(channel) if channel.is_timed_out(block_number, self.config.channel_timeout))
+        {
+            let channel = self.pop_front().unwrap();
+            #[cfg(not(target_os = "zkvm"))]
+            log::debug!("timed-out channel: {}", channel.id);
+            self.dropped_channels.push(DroppedChannel {
+                channel_id: channel.id,
+                block_number,
+                reason: ChannelDropReason::TimedOut,
+            });
+        }
+
+        if self.config.is_canyon(block_timestamp) {
+            // From the spec:
+            // "After the Canyon network upgrade, the entire channel bank is scanned in FIFO
+            //  order and the first ready (i.e. not timed-out) channel will be returned."
+            let (ready, pending) = self.channels.drain(..).partition(Channel::is_ready);
+            self.channels = pending;
+            for channel in ready {
+                self.size -= channel.size;
+                #[cfg(not(target_os = "zkvm"))]
+                log::trace!("channel is ready: {}", channel.id);
+                self.ready.push_back(ReadyChannel {
+                    block_number,
+                    block_timestamp,
+                    channel,
+                    decompressed: None,
+                });
+            }
+        } else {
+            // From the spec:
+            // "Prior to the Canyon network upgrade, once the first opened channel, if any, is
+            //  not timed-out and is ready, then it is read and removed from the channel-bank."
+            while matches!(self.channels.front(), Some(channel) if channel.is_ready()) {
+                let channel = self.pop_front().unwrap();
+                #[cfg(not(target_os = "zkvm"))]
+                log::trace!("received channel: {}", channel.id);
+                self.ready.push_back(ReadyChannel {
+                    block_number,
+                    block_timestamp,
+                    channel,
+                    decompressed: None,
+                });
+            }
         }
     }
 
-    fn total_size(&self) -> usize {
-        self.channels.iter().map(|c| c.size).sum()
+    /// Removes the first opened channel from the channel bank.
+    fn pop_front(&mut self) -> Option<Channel> {
+        let channel = self.channels.pop_front()?;
+        self.size -= channel.size;
+        Some(channel)
     }
 
     fn channel_index(&self, channel_id: ChannelId) -> Option<usize> {
@@ -211,12 +466,258 @@ impl BatcherChannels {
     }
 }
 
+impl Stage for ChannelBank {
+    type Input<'a> = TransactionFrames;
+    type Output = ReadyChannel;
+
+    /// Loads the frames of a batcher transaction into the channel bank.
+    ///
+    /// As in op-node, the channel bank is read before every frame and after the last one,
+    /// so that timed-out channels are removed and ready channels are passed on before
+    /// the next frame is added.
+    fn push(&mut self, input: TransactionFrames) -> Result<()> {
+        let block_number = input.block_number;
+        let block_timestamp = input.block_timestamp;
+        self.read_channels(block_number, block_timestamp);
+        for frame in input.frames {
+            #[cfg(not(target_os = "zkvm"))]
+            log::trace!(
+                "received frame: channel_id={}, frame_number={}, is_last={}",
+                frame.channel_id,
+                frame.number,
+                frame.is_last
+            );
+
+            self.add_frame(block_number, frame);
+            self.read_channels(block_number, block_timestamp);
+        }
+
+        Ok(())
+    }
+
+    fn next(&mut self) -> Option<ReadyChannel> {
+        self.ready.pop_front()
+    }
+}
+
+/// The stage reading the batches from the ready channels.
+pub(crate) struct ChannelReader {
+    data_signer: Option<Address>,
+    decoder: BatchDecoder,
+    batches: VecDeque<Vec<BatchWithInclusion>>,
+    dropped_channels: Vec<DroppedChannel>,
+    /// Optional cache of decompressed channel data, only available on the host.
+    channel_cache: Option<ChannelCache>,
+    /// Number of threads decompressing the ready channels, only used on the host.
+    decompression_threads: usize,
+}
+
+impl ChannelReader {
+    pub fn new(config: &ChainConfig) -> Self {
+        Self {
+            data_signer: config.batcher_data_signer,
+            decoder: BatchDecoder::new(config),
+            batches: VecDeque::new(),
+            dropped_channels: Vec::new(),
+            channel_cache: None,
+            decompression_threads: 1,
+        }
+    }
+
+    /// Decompresses the given ready channels concurrently, so that reading them only
+    /// decodes their batches. Reading the channels in order still yields exactly the same
+    /// batches and dropped channels.
+    #[cfg(not(target_os = "zkvm"))]
+    fn decompress_all<'a>(&self, channels: impl Iterator<Item = &'a mut ReadyChannel>) {
+        let mut channels: Vec<_> = channels.collect();
+        if self.decompression_threads <= 1 || channels.len() <= 1 {
+            return;
+        }
+
+        let chunk_size = channels.len().div_ceil(self.decompression_threads);
+        let data_signer = self.data_signer;
+        let channel_cache = self.channel_cache.as_ref();
+        std::thread::scope(|scope| {
+            for chunk in channels.chunks_mut(chunk_size) {
+                scope.spawn(move || {
+                    for ready in chunk {
+                        ready.decompressed =
+                            Some(ready.channel.decompressed_data(data_signer, channel_cache));
+                    }
+                });
+            }
+        });
+    }
+}
+
+impl Stage for ChannelReader {
+    type Input<'a> = ReadyChannel;
+    type Output = Vec<BatchWithInclusion>;
+
+    fn push(&mut self, input: ReadyChannel) -> Result<()> {
+        let (batches, dropped) = input.channel.read_batches(
+            input.block_number,
+            input.block_timestamp,
+            self.data_signer,
+            &self.decoder,
+            self.channel_cache.as_ref(),
+            input.decompressed,
+        );
+        self.batches.push_back(batches);
+        self.dropped_channels.extend(dropped);
+
+        Ok(())
+    }
+
+    fn next(&mut self) -> Option<Vec<BatchWithInclusion>> {
+        self.batches.pop_front()
+    }
+}
+
+/// The pipeline of the [FrameQueue], [ChannelBank] and [ChannelReader] stages, which
+/// reads the batches from the batcher transactions of the L1 blocks.
+pub struct BatcherChannels {
+    frame_queue: FrameQueue,
+    channel_bank: ChannelBank,
+    channel_reader: ChannelReader,
+    /// The channels dropped by all stages, in the order they were dropped.
+    dropped_channels: Vec<DroppedChannel>,
+}
+
+impl BatcherChannels {
+    pub fn new(config: &ChainConfig) -> Self {
+        Self {
+            frame_queue: FrameQueue::new(config),
+            channel_bank: ChannelBank::new(config),
+            channel_reader: ChannelReader::new(config),
+            dropped_channels: Vec::new(),
+        }
+    }
+
+    /// Returns all transactions sent to the batch inbox that have been dropped so far.
+    pub fn dropped_transactions(&self) -> &[DroppedTransaction] {
+        &self.frame_queue.dropped_transactions
+    }
+
+    /// Returns all channels that have been dropped, or only read partially, so far.
+    pub fn dropped_channels(&self) -> &[DroppedChannel] {
+        &self.dropped_channels
+    }
+
+    /// Returns all frames that have not been added to their channel so far.
+    pub fn skipped_frames(&self) -> &[SkippedFrame] {
+        &self.channel_bank.skipped_frames
+    }
+
+    /// Returns the pending channels of the channel bank, from which the pipeline can be
+    /// resumed with [BatcherChannels::from_checkpoint]. Between two L1 blocks, all other
+    /// stages have passed on their data.
+    pub fn checkpoint(&self) -> ChannelsCheckpoint {
+        debug_assert!(self.frame_queue.frames.is_empty());
+        debug_assert!(self.channel_bank.ready.is_empty());
+        debug_assert!(self.channel_reader.batches.is_empty());
+        ChannelsCheckpoint {
+            channels: self.channel_bank.channels.clone(),
+        }
+    }
+
+    /// Creates the pipeline for the given configuration, resuming with the pending
+    /// channels of the checkpoint. The dropped data is only tracked from here on.
+    pub fn from_checkpoint(config: &ChainConfig, checkpoint: ChannelsCheckpoint) -> Self {
+        let mut batcher_channels = Self::new(config);
+        batcher_channels.channel_bank.size = checkpoint.channels.iter().map(|c| c.size).sum();
+        batcher_channels.channel_bank.channels = checkpoint.channels;
+        batcher_channels
+    }
+
+    /// Sets the cache used to look up and store decompressed channel data.
+    #[cfg(not(target_os = "zkvm"))]
+    pub fn set_channel_cache(&mut self, channel_cache: Option<ChannelCache>) {
+        self.channel_reader.channel_cache = channel_cache;
+    }
+
+    /// Sets the number of threads decompressing the channels that become ready in the
+    /// same L1 block; one thread decompresses them sequentially.
+    #[cfg(not(target_os = "zkvm"))]
+    pub fn set_decompression_threads(&mut self, threads: usize) {
+        self.channel_reader.decompression_threads = threads;
+    }
+
+    /// Processes all batcher transactions in the given block, reading the data of blob
+    /// transactions from the given sidecars.
+    /// The given batch_sender must match the potentially updated batcher address loaded
+    /// from the system config.
+    pub fn process_l1_transactions(
+        &mut self,
+        batch_sender: Address,
+        block_number: BlockNumber,
+        block_timestamp: u64,
+        transactions: &[Transaction<EthereumTxEssence>],
+        blob_sidecars: &[BlobSidecar],
+    ) -> Result<()> {
+        self.push(BatcherTransactions {
+            block_number,
+            block_timestamp,
+            batch_sender,
+            transactions,
+            blob_sidecars,
+        })
+    }
+
+    pub fn read_batches(&mut self) -> Option<Vec<BatchWithInclusion>> {
+        self.next()
+    }
+}
+
+impl Stage for BatcherChannels {
+    type Input<'a> = BatcherTransactions<'a>;
+    type Output = Vec<BatchWithInclusion>;
+
+    fn push(&mut self, input: BatcherTransactions<'_>) -> Result<()> {
+        self.frame_queue.push(input)?;
+        // every batcher transaction passes through the channel bank before the next one,
+        // as reading a channel does not change the channel bank
+        let mut steps = Vec::new();
+        while let Some(frames) = self.frame_queue.next() {
+            self.channel_bank.push(frames)?;
+            let dropped = mem::take(&mut self.channel_bank.dropped_channels);
+            let ready: Vec<_> = iter::from_fn(|| self.channel_bank.next()).collect();
+            steps.push((dropped, ready));
+        }
+        #[cfg(not(target_os = "zkvm"))]
+        self.channel_reader
+            .decompress_all(steps.iter_mut().flat_map(|(_, ready)| ready.iter_mut()));
+
+        // the channels are read in order, independent of how they were decompressed
+        for (mut dropped, ready) in steps {
+            self.dropped_channels.append(&mut dropped);
+            for channel in ready {
+                self.channel_reader.push(channel)?;
+                self.dropped_channels
+                    .append(&mut self.channel_reader.dropped_channels);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn next(&mut self) -> Option<Vec<BatchWithInclusion>> {
+        self.channel_reader.next()
+    }
+}
+
+/// The pending channels of the [BatcherChannels] between two L1 blocks.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChannelsCheckpoint {
+    channels: VecDeque<Channel>,
+}
+
 /// A [ChannelId] is a unique identifier for a [Channel].
-type ChannelId = u128;
+pub type ChannelId = u128;
 
 /// A [Channel] is a set of batches that are split into at least one, but possibly
 /// multiple frames. Frames are allowed to be ingested in any order.
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 struct Channel {
     /// The channel ID.
     id: ChannelId,
@@ -249,6 +750,12 @@ impl Channel {
         channel
     }
 
+    /// Returns true if the channel has timed out at the given L1 block, i.e. more than
+    /// `channel_timeout` blocks after the block that opened it.
+    fn is_timed_out(&self, block_number: BlockNumber, channel_timeout: u64) -> bool {
+        block_number > self.open_l1_block + channel_timeout
+    }
+
     /// Returns true if the channel is closed, i.e. the closing frame has been received.
     fn is_closed(&self) -> bool {
         self.close_frame_number.is_some()
@@ -302,69 +809,181 @@ impl Channel {
     }
 
     /// Reads all batches from an ready channel. If there is an invalid batch, the rest of
-    /// the channel is skipped, but previous batches are returned.
-    fn read_batches(&self, block_number: BlockNumber) -> Vec<BatchWithInclusion> {
+    /// the channel is skipped, but previous batches are returned. If a data signer is
+    /// given, channels without a valid signature of that signer are skipped entirely.
+    /// Skipped data is reported as a [DroppedChannel]. The channel data is only
+    /// decompressed if it has not been decompressed already.
+    fn read_batches(
+        &self,
+        block_number: BlockNumber,
+        block_timestamp: u64,
+        data_signer: Option<Address>,
+        decoder: &BatchDecoder,
+        channel_cache: Option<&ChannelCache>,
+        decompressed: Option<Result<Vec<u8>, ChannelDropReason>>,
+    ) -> (Vec<BatchWithInclusion>, Option<DroppedChannel>) {
         debug_assert!(self.is_ready());
 
+        let dropped = |reason| DroppedChannel {
+            channel_id: self.id,
+            block_number,
+            reason,
+        };
         let mut batches = Vec::new();
-        if let Err(_err) = self.decode_batches(block_number, &mut batches) {
+        let decompressed = match decompressed
+            .unwrap_or_else(|| self.decompressed_data(data_signer, channel_cache))
+        {
+            Ok(decompressed) => decompressed,
+            Err(reason) => return (batches, Some(dropped(reason))),
+        };
+
+        let mut channel_data = decompressed.as_slice();
+        while !channel_data.is_empty() {
+            match decoder.decode(&mut channel_data, block_timestamp) {
+                Ok(batch) => batches.push(BatchWithInclusion {
+                    data: batch,
+                    inclusion_block_number: block_number,
+                    channel_id: self.id,
+                }),
+                Err(_err) => {
+                    #[cfg(not(target_os = "zkvm"))]
+                    log::warn!(
+                        "failed to decode batch {}; skipping rest of channel: {:#}",
+                        batches.len(),
+                        _err
+                    );
+                    return (batches, Some(dropped(ChannelDropReason::InvalidData)));
+                }
+            }
+        }
+
+        (batches, None)
+    }
+
+    /// Returns the decompressed channel data, after verifying its signature if a data
+    /// signer is given.
+    fn decompressed_data(
+        &self,
+        data_signer: Option<Address>,
+        channel_cache: Option<&ChannelCache>,
+    ) -> Result<Vec<u8>, ChannelDropReason> {
+        let decompressed = match data_signer {
+            Some(signer) => match self.verify_signature(signer) {
+                Ok(data) => self.decompress(data.as_slice(), channel_cache),
+                Err(_err) => {
+                    #[cfg(not(target_os = "zkvm"))]
+                    log::warn!(
+                        "invalid batcher data signature; skipping channel: {:#}",
+                        _err
+                    );
+                    return Err(ChannelDropReason::InvalidSignature);
+                }
+            },
+            None => self.decompress(
+                MultiReader::new(self.frames.values().map(|frame| frame.data.as_slice())),
+                channel_cache,
+            ),
+        };
+
+        decompressed.map_err(|_err| {
             #[cfg(not(target_os = "zkvm"))]
             log::warn!(
-                "failed to decode all batches; skipping rest of channel: {:#}",
+                "failed to decompress channel data; skipping channel: {:#}",
                 _err
             );
-        }
-
-        batches
+            ChannelDropReason::InvalidData
+        })
     }
 
-    fn decode_batches(
+    /// Decompresses the given channel data, using the cache if one is given.
+    #[cfg(not(target_os = "zkvm"))]
+    fn decompress(
         &self,
-        block_number: BlockNumber,
-        batches: &mut Vec<BatchWithInclusion>,
-    ) -> Result<()> {
-        let decompressed = self
-            .decompress()
-            .context("failed to decompress channel data")?;
+        mut data: impl Read,
+        channel_cache: Option<&ChannelCache>,
+    ) -> Result<Vec<u8>> {
+        let Some(channel_cache) = channel_cache else {
+            return decompress(data);
+        };
 
-        let mut channel_data = decompressed.as_slice();
-        while !channel_data.is_empty() {
-            let batch = Batch::decode(&mut channel_data)
-                .with_context(|| format!("failed to decode batch {}", batches.len()))?;
+        // the compressed data fully determines the decompressed data
+        let mut compressed = Vec::new();
+        data.read_to_end(&mut compressed)?;
+        let data_hash = B256::from(keccak(&compressed));
+        if let Some(decompressed) = channel_cache.get(self.id, &data_hash) {
+            return Ok(decompressed);
+        }
 
-            batches.push(BatchWithInclusion {
-                essence: batch.0,
-                inclusion_block_number: block_number,
-            });
+        // only successfully decompressed data is cached
+        let decompressed = decompress(compressed.as_slice())?;
+        if let Err(err) = channel_cache.insert(self.id, &data_hash, &decompressed) {
+            log::warn!("could not cache channel {:#x}: {:#}", self.id, err);
         }
 
-        Ok(())
+        Ok(decompressed)
+    }
+
+    /// Decompresses the given channel data.
+    #[cfg(target_os = "zkvm")]
+    fn decompress(
+        &self,
+        data: impl Read,
+        _channel_cache: Option<&ChannelCache>,
+    ) -> Result<Vec<u8>> {
+        decompress(data)
     }
 
-    fn decompress(&self) -> Result<Vec<u8>> {
-        // chain all frames' data together
-        let data = MultiReader::new(self.frames.values().map(|frame| frame.data.as_slice()));
+    /// Verifies that the channel data is prefixed with a signature of the given signer
+    /// and returns the signed data.
+    fn verify_signature(&self, signer: Address) -> Result<Vec<u8>> {
+        let mut data: Vec<u8> = self
+            .frames
+            .values()
+            .flat_map(|frame| frame.data.iter().copied())
+            .collect();
+        ensure!(data.len() >= CHANNEL_SIGNATURE_LEN, "channel is not signed");
+        let signed_data = data.split_off(CHANNEL_SIGNATURE_LEN);
+        let recovered = recover_address(&channel_signing_hash(self.id, &signed_data), &data)?;
+        ensure!(
+            recovered == signer,
+            "channel signed by {} instead of {}",
+            recovered,
+            signer
+        );
 
-        // From the spec:
-        // "When decompressing a channel, we limit the amount of decompressed data to
-        //  MAX_RLP_BYTES_PER_CHANNEL (currently 10,000,000 bytes), in order to avoid "zip-bomb"
-        //  types of attack (where a small compressed input decompresses to a humongous amount
-        //  of data). If the decompressed data exceeds the limit, things proceeds as though the
-        //  channel contained only the first MAX_RLP_BYTES_PER_CHANNEL decompressed bytes."
-        let mut buf = Vec::new();
-        Decoder::new(data)?
-            .take(MAX_RLP_BYTES_PER_CHANNEL)
-            .read_to_end(&mut buf)?;
-
-        Ok(buf)
+        Ok(signed_data)
     }
 }
 
+/// Returns the hash signed by the batcher data signer of a chain: the keccak hash of the
+/// big-endian channel ID followed by the signed channel data.
+pub fn channel_signing_hash(channel_id: ChannelId, data: &[u8]) -> B256 {
+    let mut preimage = channel_id.to_be_bytes().to_vec();
+    preimage.extend_from_slice(data);
+    keccak(preimage).into()
+}
+
+/// Decompresses the given channel data.
+fn decompress(data: impl Read) -> Result<Vec<u8>> {
+    // From the spec:
+    // "When decompressing a channel, we limit the amount of decompressed data to
+    //  MAX_RLP_BYTES_PER_CHANNEL (currently 10,000,000 bytes), in order to avoid "zip-bomb"
+    //  types of attack (where a small compressed input decompresses to a humongous amount
+    //  of data). If the decompressed data exceeds the limit, things proceeds as though the
+    //  channel contained only the first MAX_RLP_BYTES_PER_CHANNEL decompressed bytes."
+    let mut buf = Vec::new();
+    Decoder::new(data)?
+        .take(MAX_RLP_BYTES_PER_CHANNEL)
+        .read_to_end(&mut buf)?;
+
+    Ok(buf)
+}
+
 /// A [Frame] is a chunk of data belonging to a [Channel]. Batcher transactions carry one
 /// or multiple frames. The reason to split a channel into frames is that a channel might
 /// too large to include in a single batcher transaction.
-#[derive(Debug, Default, Clone)]
-struct Frame {
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Frame {
     /// The channel ID this frame belongs to.
     pub channel_id: ChannelId,
     /// The index of this frame within the channel.
@@ -379,12 +998,10 @@ impl Frame {
     const HEADER_SIZE: usize = 22;
     const MAX_FRAME_DATA_LENGTH: u32 = 1_000_000;
 
-    /// Processes a batcher transaction and returns the list of contained frames.
-    pub fn process_batcher_transaction(tx_essence: &EthereumTxEssence) -> Result<Vec<Self>> {
-        let (version, mut rollup_payload) = tx_essence
-            .data()
-            .split_first()
-            .context("empty transaction data")?;
+    /// Processes the data of a batcher transaction, either its calldata or the data
+    /// decoded from one of its blobs, and returns the list of contained frames.
+    pub fn process_batcher_data(data: &[u8]) -> Result<Vec<Self>> {
+        let (version, mut rollup_payload) = data.split_first().context("empty transaction data")?;
         ensure!(version == &0, "invalid transaction version: {}", version);
 
         let mut frames = Vec::new();
@@ -598,7 +1215,353 @@ mod tests {
                 channel.add_frame(frame_b).unwrap();
                 assert_eq!(channel.size, 420);
                 assert!(channel.is_ready());
-                assert_eq!(channel.decompress().unwrap(), b"Hello World!");
+                let data = MultiReader::new(channel.frames.values().map(|f| f.data.as_slice()));
+                assert_eq!(decompress(data).unwrap(), b"Hello World!");
+            }
+        }
+
+        #[test]
+        fn signed_data() {
+            use ethers_core::k256::{
+                ecdsa::SigningKey, elliptic_curve::sec1::ToEncodedPoint, PublicKey,
+            };
+
+            let key = SigningKey::from_slice(&[1u8; 32]).unwrap();
+            let public_key = PublicKey::from(key.verifying_key()).to_encoded_point(false);
+            let signer = Address::from_slice(&keccak(&public_key.as_bytes()[1..])[12..]);
+
+            // "Hello World!" compressed
+            let compressed = [
+                120, 156, 243, 72, 205, 201, 201, 87, 8, 207, 47, 202, 73, 81, 4, 0, 28, 73, 4, 62,
+            ];
+            let (signature, recovery_id) = key
+                .sign_prehash_recoverable(channel_signing_hash(CHANNEL_ID, &compressed).as_slice())
+                .unwrap();
+            let mut data = signature.to_bytes().to_vec();
+            data.push(recovery_id.to_byte());
+            data.extend_from_slice(&compressed);
+
+            let mut channel = new_channel();
+            channel
+                .add_frame(Frame {
+                    channel_id: CHANNEL_ID,
+                    number: 0,
+                    data,
+                    is_last: true,
+                })
+                .unwrap();
+            let signed = channel.verify_signature(signer).unwrap();
+            assert_eq!(decompress(signed.as_slice()).unwrap(), b"Hello World!");
+            assert!(channel.verify_signature(Address::ZERO).is_err());
+
+            // unsigned data
+            let mut channel = new_channel();
+            channel
+                .add_frame(Frame {
+                    channel_id: CHANNEL_ID,
+                    number: 0,
+                    data: compressed.to_vec(),
+                    is_last: true,
+                })
+                .unwrap();
+            assert!(channel.verify_signature(signer).is_err());
+        }
+    }
+
+    mod transactions {
+        use zeth_primitives::{
+            transactions::{
+                ethereum::{TransactionKind, TxEssenceLegacy},
+                signature::TxSignature,
+            },
+            uint, Bytes, U256,
+        };
+
+        use super::*;
+
+        fn batcher_tx(to: Address, data: &[u8], r: U256) -> Transaction<EthereumTxEssence> {
+            Transaction {
+                essence: EthereumTxEssence::Legacy(TxEssenceLegacy {
+                    to: TransactionKind::Call(to),
+                    data: Bytes::copy_from_slice(data),
+                    ..Default::default()
+                }),
+                signature: TxSignature {
+                    v: 28,
+                    r,
+                    s: uint!(
+                        0x45e0aff800961cfce805daef7016b9b675c137a6a41a548f7b60a3484c06a33a_U256
+                    ),
+                },
+            }
+        }
+
+        #[test]
+        fn skip_invalid() {
+            let config = ChainConfig::optimism();
+            let r = uint!(0x88ff6cf0fefd94db46111149ae4bfc179e9b94721fffd821d38d16464b3f71d0_U256);
+
+            // invalid frames and an invalid signature are dropped
+            let transactions = vec![
+                batcher_tx(config.batch_inbox, &[0, 1, 2, 3], r),
+                batcher_tx(config.batch_inbox, &[0, 1, 2, 3], U256::ZERO),
+            ];
+            let batch_sender = transactions[0].recover_from().unwrap();
+            let mut channels = BatcherChannels::new(&config);
+            channels
+                .process_l1_transactions(batch_sender, 100, 0, &transactions, &[])
+                .unwrap();
+            assert_eq!(
+                channels.dropped_transactions(),
+                [
+                    DroppedTransaction {
+                        block_number: 100,
+                        tx_index: 0,
+                        reason: DropReason::InvalidFrames,
+                    },
+                    DroppedTransaction {
+                        block_number: 100,
+                        tx_index: 1,
+                        reason: DropReason::InvalidSignature,
+                    },
+                ]
+            );
+            assert!(channels.read_batches().is_none());
+
+            // a single closing frame
+            let mut data = vec![0];
+            data.extend_from_slice(&0xffu128.to_be_bytes());
+            data.extend_from_slice(&0u16.to_be_bytes());
+            data.extend_from_slice(&0u32.to_be_bytes());
+            data.push(1);
+            let transactions = vec![batcher_tx(config.batch_inbox, &data, r)];
+            let batch_sender = transactions[0].recover_from().unwrap();
+            channels
+                .process_l1_transactions(batch_sender, 101, 0, &transactions, &[])
+                .unwrap();
+            assert_eq!(channels.dropped_transactions().len(), 2);
+            // the valid channel is still read
+            assert!(channels.read_batches().is_some());
+        }
+
+        #[test]
+        fn timed_out_channel() {
+            let config = ChainConfig::optimism();
+            let r = uint!(0x88ff6cf0fefd94db46111149ae4bfc179e9b94721fffd821d38d16464b3f71d0_U256);
+            let frame = |number: u16| {
+                let mut data = vec![0];
+                data.extend_from_slice(&0xeeu128.to_be_bytes());
+                data.extend_from_slice(&number.to_be_bytes());
+                data.extend_from_slice(&0u32.to_be_bytes());
+                data.push(0);
+                batcher_tx(config.batch_inbox, &data, r)
+            };
+            let mut channels = BatcherChannels::new(&config);
+            // the synthetic signature recovers a different sender for every transaction
+            let transactions = vec![frame(0)];
+            let batch_sender = transactions[0].recover_from().unwrap();
+            channels
+                .process_l1_transactions(batch_sender, 100, 0, &transactions, &[])
+                .unwrap();
+            assert!(channels.dropped_channels().is_empty());
+
+            // the channel is dropped before the late frame is added, which opens a new
+            // channel with the same ID, as in op-node
+            let block_number = 101 + config.channel_timeout;
+            let transactions = vec![frame(1)];
+            let batch_sender = transactions[0].recover_from().unwrap();
+            channels
+                .process_l1_transactions(batch_sender, block_number, 0, &transactions, &[])
+                .unwrap();
+            assert!(channels.skipped_frames().is_empty());
+            assert_eq!(
+                channels.channel_bank.channels[0].open_l1_block,
+                block_number
+            );
+            assert_eq!(
+                channels.dropped_channels(),
+                [DroppedChannel {
+                    channel_id: 0xee,
+                    block_number,
+                    reason: ChannelDropReason::TimedOut,
+                }]
+            );
+            assert!(channels.read_batches().is_none());
+        }
+
+        #[test]
+        fn parallel_decompression() {
+            use crate::optimism::synthetic::{SyntheticChain, SyntheticChainOptions};
+
+            // many small interleaved channels, so that several become ready per block
+            let chain = SyntheticChain::generate(&SyntheticChainOptions {
+                eth_block_count: 24,
+                channel_batches: 2,
+                max_frame_size: 64,
+                interleaved_channels: 4,
+                max_tx_frames: 16,
+                max_block_batcher_txs: 6,
+                ..Default::default()
+            })
+            .unwrap();
+            let read_all = |threads: usize| {
+                let mut channels = BatcherChannels::new(&chain.config);
+                channels.set_decompression_threads(threads);
+                let mut batches = Vec::new();
+                for block in &chain.eth_blocks[1..] {
+                    channels
+                        .process_l1_transactions(
+                            chain.config.system_config.batch_sender,
+                            block.block_header.number,
+                            block.block_header.timestamp.to(),
+                            &block.transactions,
+                            &[],
+                        )
+                        .unwrap();
+                    while let Some(channel_batches) = channels.read_batches() {
+                        batches.extend(channel_batches.into_iter().map(|batch| {
+                            (batch.channel_id, batch.inclusion_block_number, batch.data)
+                        }));
+                    }
+                }
+                (batches, channels.dropped_channels().to_vec())
+            };
+
+            let sequential = read_all(1);
+            assert!(sequential.0.len() >= chain.batches.len());
+            assert_eq!(read_all(4), sequential);
+        }
+
+        #[test]
+        fn prune_oldest() {
+            // the bank holds two but not three of the channels
+            let mut config = ChainConfig::optimism();
+            config.max_channel_bank_size = 1_500;
+            let frame = |channel_id: ChannelId, number: u16, is_last: bool| Frame {
+                channel_id,
+                number,
+                data: vec![0; 500],
+                is_last,
+            };
+
+            let mut channel_bank = ChannelBank::new(&config);
+            channel_bank
+                .push(TransactionFrames {
+                    block_number: 100,
+                    block_timestamp: 0,
+                    frames: vec![frame(1, 1, false), frame(2, 1, false), frame(3, 1, false)],
+                })
+                .unwrap();
+            assert_eq!(
+                channel_bank.dropped_channels,
+                [DroppedChannel {
+                    channel_id: 1,
+                    block_number: 100,
+                    reason: ChannelDropReason::Pruned,
+                }]
+            );
+            assert_eq!(channel_bank.size, 1_400);
+
+            // a ready channel leaves the bank, making room for another channel
+            channel_bank
+                .push(TransactionFrames {
+                    block_number: 101,
+                    block_timestamp: 0,
+                    frames: vec![frame(2, 0, true), frame(4, 1, false)],
+                })
+                .unwrap();
+            assert_eq!(channel_bank.dropped_channels.len(), 1);
+            assert_eq!(channel_bank.next().unwrap().channel.id, 2);
+            assert_eq!(channel_bank.size, 1_400);
+
+            // a closing frame removes the frames after it, which completes the channel
+            channel_bank
+                .push(TransactionFrames {
+                    block_number: 102,
+                    block_timestamp: 0,
+                    frames: vec![frame(3, 0, true)],
+                })
+                .unwrap();
+            let ready = channel_bank.next().unwrap().channel;
+            assert_eq!((ready.id, ready.size), (3, 700));
+            assert_eq!(channel_bank.size, 700);
+            let channel_ids: Vec<_> = channel_bank.channels.iter().map(|c| c.id).collect();
+            assert_eq!(channel_ids, [4]);
+        }
+
+        #[test]
+        fn timeout_before_prune() {
+            // the bank holds two but not three of the channels
+            let mut config = ChainConfig::optimism();
+            config.max_channel_bank_size = 1_500;
+            let frames = |channel_id: ChannelId| {
+                vec![Frame {
+                    channel_id,
+                    number: 0,
+                    data: vec![0; 500],
+                    is_last: false,
+                }]
+            };
+
+            let mut channel_bank = ChannelBank::new(&config);
+            for (block_number, channel_id) in [(100, 1), (100 + config.channel_timeout, 2)] {
+                channel_bank
+                    .push(TransactionFrames {
+                        block_number,
+                        block_timestamp: 0,
+                        frames: frames(channel_id),
+                    })
+                    .unwrap();
+            }
+            assert!(channel_bank.dropped_channels.is_empty());
+
+            // the timed-out channel is removed before the new frame is added, so that it is
+            // not pruned instead
+            let block_number = 101 + config.channel_timeout;
+            channel_bank
+                .push(TransactionFrames {
+                    block_number,
+                    block_timestamp: 0,
+                    frames: frames(3),
+                })
+                .unwrap();
+            assert_eq!(
+                channel_bank.dropped_channels,
+                [DroppedChannel {
+                    channel_id: 1,
+                    block_number,
+                    reason: ChannelDropReason::TimedOut,
+                }]
+            );
+            let channel_ids: Vec<_> = channel_bank.channels.iter().map(|c| c.id).collect();
+            assert_eq!(channel_ids, [2, 3]);
+        }
+
+        #[test]
+        fn channel_bank_order() {
+            let config = ChainConfig::optimism();
+            let frame = |channel_id: ChannelId, is_last: bool| Frame {
+                channel_id,
+                number: 0,
+                data: vec![],
+                is_last,
+            };
+
+            // before Canyon, a ready channel waits for the channels opened before it
+            let canyon_time = config.canyon_time.unwrap();
+            for (block_timestamp, expected) in [(canyon_time - 1, vec![]), (canyon_time, vec![2])] {
+                let mut channel_bank = ChannelBank::new(&config);
+                channel_bank
+                    .push(TransactionFrames {
+                        block_number: 100,
+                        block_timestamp,
+                        frames: vec![frame(1, false), frame(2, true)],
+                    })
+                    .unwrap();
+                let ready: Vec<ChannelId> = std::iter::from_fn(|| channel_bank.next())
+                    .map(|ready| ready.channel.id)
+                    .collect();
+                assert_eq!(ready, expected, "{}", block_timestamp);
             }
         }
     }
@@ -12,21 +12,24 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use anyhow::{ensure, Context, Result};
 use serde::{Deserialize, Serialize};
+use thiserror::Error as ThisError;
 use zeth_primitives::{
     alloy_rlp,
     block::Header,
-    receipt::Receipt,
+    receipt::{validate_cumulative_gas_used, Receipt},
+    rlp::encode_list_with_lengths,
     transactions::{
         ethereum::EthereumTxEssence, optimism::OptimismTxEssence, Transaction, TxEssence,
     },
-    trie::MptNode,
+    trie::{MptNode, EMPTY_ROOT},
+    Bloom, B256,
 };
 
-use super::{config::ChainConfig, deposits, system_config};
+use super::{blob::BlobSidecar, config::ChainConfig, deposits, system_config};
 
 /// Input for extracting deposits.
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -37,13 +40,96 @@ pub struct BlockInput<E: TxEssence> {
     pub transactions: Vec<Transaction<E>>,
     /// Transaction receipts of the block or `None` if not required.
     pub receipts: Option<Vec<Receipt>>,
+    /// Sidecars of the blobs of the batcher transactions in the block.
+    pub blob_sidecars: Vec<BlobSidecar>,
+}
+
+/// Error returned when the Ethereum blocks of the witness do not form a chain.
+#[derive(Debug, Clone, PartialEq, Eq, ThisError)]
+pub enum EthGapError {
+    /// The block is not contained in the witness.
+    #[error("Eth block {0} is missing from the witness")]
+    MissingBlock(u64),
+    /// The witness continues with a later block.
+    #[error("Eth blocks {first} to {last} are missing from the witness (block {last} has hash {last_hash})")]
+    SkippedBlocks {
+        first: u64,
+        last: u64,
+        last_hash: B256,
+    },
+    /// The block does not extend the previous block.
+    #[error("Eth block {number} has parent hash {parent_hash}, expected {expected}")]
+    InvalidParent {
+        number: u64,
+        parent_hash: B256,
+        expected: B256,
+    },
+}
+
+/// Ensures that the given header is the block `block_no` and extends the block with hash
+/// `parent_hash`.
+pub fn ensure_consecutive(
+    block_no: u64,
+    parent_hash: B256,
+    header: &Header,
+) -> Result<(), EthGapError> {
+    if header.number > block_no {
+        return Err(EthGapError::SkippedBlocks {
+            first: block_no,
+            last: header.number - 1,
+            last_hash: header.parent_hash,
+        });
+    }
+    if header.number != block_no || header.parent_hash != parent_hash {
+        return Err(EthGapError::InvalidParent {
+            number: header.number,
+            parent_hash: header.parent_hash,
+            expected: parent_hash,
+        });
+    }
+
+    Ok(())
+}
+
+/// How a [BatcherDb] handles non-critical mismatches of its data, i.e. inconsistencies
+/// that are redundant with the checks binding the data to the block hashes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum ValidationPolicy {
+    /// Every mismatch is an error. This is the only policy accepted by the guests.
+    #[default]
+    Strict,
+    /// Non-critical mismatches are logged and ignored, e.g. to explore imperfect RPC data
+    /// on the host.
+    Permissive,
+}
+
+impl ValidationPolicy {
+    /// Returns the result of a non-critical check, whose error is only logged with the
+    /// permissive policy.
+    pub fn check(self, result: Result<()>) -> Result<()> {
+        match result {
+            #[cfg(not(target_os = "zkvm"))]
+            Err(err) if self == ValidationPolicy::Permissive => {
+                log::warn!("Ignoring non-critical mismatch: {:#}", err);
+                Ok(())
+            }
+            result => result,
+        }
+    }
 }
 
 pub trait BatcherDb {
     fn validate(&self, config: &ChainConfig) -> Result<()>;
+    /// Returns the policy for non-critical mismatches of the data.
+    fn validation_policy(&self) -> ValidationPolicy {
+        ValidationPolicy::Strict
+    }
     fn get_full_op_block(&mut self, block_no: u64) -> Result<BlockInput<OptimismTxEssence>>;
     fn get_op_block_header(&mut self, block_no: u64) -> Result<Header>;
     fn get_full_eth_block(&mut self, block_no: u64) -> Result<&BlockInput<EthereumTxEssence>>;
+    /// Releases all retained Eth blocks but the given one. A released block must be
+    /// provided again if it is requested later.
+    fn release_eth_blocks(&mut self, _retained_block_no: u64) {}
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -71,29 +157,174 @@ impl Default for MemDb {
     }
 }
 
-impl BatcherDb for MemDb {
-    fn validate(&self, config: &ChainConfig) -> Result<()> {
-        for (block_no, op_block) in &self.full_op_block {
-            let header = &op_block.block_header;
-            ensure!(*block_no == header.number, "Block number mismatch");
-
-            // Validate tx list
-            {
-                let mut tx_trie = MptNode::default();
-                for (tx_no, tx) in op_block.transactions.iter().enumerate() {
-                    tx_trie.insert_rlp(&alloy_rlp::encode(tx_no), tx)?;
-                }
-                ensure!(
-                    tx_trie.hash() == header.transactions_root,
-                    "Invalid op block transaction data!"
-                );
-            }
+/// Validates the header of a block without any transactions: Its transaction and receipt
+/// tries must be empty, and its bloom filter must not attest to any logs.
+fn validate_empty_block(header: &Header) -> Result<()> {
+    ensure!(
+        header.transactions_root == EMPTY_ROOT,
+        "Transactions root of an empty block must be the empty root"
+    );
+    ensure!(
+        header.receipts_root == EMPTY_ROOT,
+        "Receipts root of an empty block must be the empty root"
+    );
+    ensure!(
+        header.logs_bloom == Bloom::ZERO,
+        "Bloom filter of an empty block must be zero"
+    );
+    ensure!(
+        header.gas_used.is_zero(),
+        "Gas used of an empty block must be zero"
+    );
+
+    Ok(())
+}
+
+/// Validates the transactions of an Optimism block against its header.
+pub(crate) fn validate_op_block(
+    block_no: u64,
+    op_block: &BlockInput<OptimismTxEssence>,
+) -> Result<()> {
+    let header = &op_block.block_header;
+    ensure!(block_no == header.number, "Block number mismatch");
+
+    // Every op block starts with the deposit of its L1 attributes
+    ensure!(
+        !op_block.transactions.is_empty(),
+        "Op block does not contain any transactions"
+    );
+
+    // Validate tx list
+    {
+        let mut tx_trie = MptNode::default();
+        let encoded_txs = encode_list_with_lengths(&op_block.transactions);
+        for (tx_no, tx) in encoded_txs.into_items().into_iter().enumerate() {
+            tx_trie.insert(&alloy_rlp::encode(tx_no), tx)?;
+        }
+        ensure!(
+            tx_trie.hash() == header.transactions_root,
+            "Invalid op block transaction data!"
+        );
+    }
+
+    // Validate receipts
+    ensure!(
+        op_block.receipts.is_none(),
+        "Op blocks should not contain receipts"
+    );
+    ensure!(
+        op_block.blob_sidecars.is_empty(),
+        "Op blocks should not contain blob sidecars"
+    );
+
+    Ok(())
+}
+
+/// Validates the transactions and receipts of an Ethereum block against its header.
+pub(crate) fn validate_eth_block(
+    config: &ChainConfig,
+    block_no: u64,
+    eth_block: &BlockInput<EthereumTxEssence>,
+) -> Result<()> {
+    let header = &eth_block.block_header;
+    ensure!(block_no == header.number, "Block number mismatch");
+    ensure!(
+        header.has_valid_upgrade_fields(),
+        "Eth block header has an invalid combination of upgrade fields"
+    );
+
+    // Blocks without transactions have no tries to rebuild
+    if eth_block.transactions.is_empty() {
+        validate_empty_block(header).context("Invalid empty eth block")?;
+        ensure!(
+            eth_block.receipts.as_ref().is_none_or(Vec::is_empty),
+            "Eth block has no transactions, but contains receipts"
+        );
+        ensure!(
+            eth_block.blob_sidecars.is_empty(),
+            "Eth block has no transactions, but contains blob sidecars"
+        );
+        return Ok(());
+    }
+
+    // Validate tx list
+    {
+        let mut tx_trie = MptNode::default();
+        let encoded_txs = encode_list_with_lengths(&eth_block.transactions);
+        for (tx_no, tx) in encoded_txs.into_items().into_iter().enumerate() {
+            tx_trie.insert(&alloy_rlp::encode(tx_no), tx)?;
+        }
+        ensure!(
+            tx_trie.hash() == header.transactions_root,
+            "Invalid eth block transaction data!"
+        );
+    }
+
+    // Validate the versioned hashes of the blob transactions, which reference the blobs
+    for (tx_no, tx) in eth_block.transactions.iter().enumerate() {
+        if let EthereumTxEssence::Eip4844(blob_tx) = &tx.essence {
+            blob_tx
+                .validate_versioned_hashes()
+                .with_context(|| format!("Invalid eth block blob transaction {}", tx_no))?;
+        }
+    }
 
-            // Validate receipts
+    // Validate blob sidecars. Every sidecar must commit to a blob referenced by one of the
+    // transactions, so that the host cannot pass off blobs of other blocks.
+    if !eth_block.blob_sidecars.is_empty() {
+        let versioned_hashes: HashSet<B256> = eth_block
+            .transactions
+            .iter()
+            .filter_map(|tx| match &tx.essence {
+                EthereumTxEssence::Eip4844(blob_tx) => Some(&blob_tx.blob_versioned_hashes),
+                _ => None,
+            })
+            .flatten()
+            .copied()
+            .collect();
+        for sidecar in &eth_block.blob_sidecars {
             ensure!(
-                op_block.receipts.is_none(),
-                "Op blocks should not contain receipts"
+                versioned_hashes.contains(&sidecar.versioned_hash()),
+                "Eth block contains a blob sidecar not referenced by its transactions"
             );
+            sidecar.blob().context("Invalid eth block blob sidecar")?;
+        }
+    }
+
+    // Validate receipts
+    if let Some(receipts) = &eth_block.receipts {
+        let mut receipt_trie = MptNode::default();
+        for (tx_no, receipt) in receipts.iter().enumerate() {
+            receipt_trie.insert_rlp(&alloy_rlp::encode(tx_no), receipt)?;
+        }
+        ensure!(
+            receipt_trie.hash() == header.receipts_root,
+            "Invalid eth block receipt data!"
+        );
+        validate_cumulative_gas_used(receipts, header.gas_used)
+            .context("Invalid eth block receipt gas")?;
+    } else {
+        let can_contain_deposits =
+            deposits::can_contain(&config.deposit_contract, &header.logs_bloom);
+        let can_contain_config =
+            system_config::can_contain(&config.system_config_contract, &header.logs_bloom);
+        ensure!(
+            !can_contain_deposits,
+            "Eth block has no receipts, but bloom filter indicates it has deposits"
+        );
+        ensure!(
+            !can_contain_config,
+            "Eth block has no receipts, but bloom filter indicates it has config updates"
+        );
+    }
+
+    Ok(())
+}
+
+impl BatcherDb for MemDb {
+    fn validate(&self, config: &ChainConfig) -> Result<()> {
+        for (block_no, op_block) in &self.full_op_block {
+            validate_op_block(*block_no, op_block)?;
         }
 
         for (block_no, op_block) in &self.op_block_header {
@@ -101,68 +332,125 @@ impl BatcherDb for MemDb {
         }
 
         for (block_no, eth_block) in &self.full_eth_block {
-            let header = &eth_block.block_header;
-            ensure!(*block_no == header.number, "Block number mismatch");
-
-            // Validate tx list
-            {
-                let mut tx_trie = MptNode::default();
-                for (tx_no, tx) in eth_block.transactions.iter().enumerate() {
-                    tx_trie.insert_rlp(&alloy_rlp::encode(tx_no), tx)?;
-                }
-                ensure!(
-                    tx_trie.hash() == header.transactions_root,
-                    "Invalid eth block transaction data!"
-                );
-            }
-
-            // Validate receipts
-            if eth_block.receipts.is_some() {
-                let mut receipt_trie = MptNode::default();
-                for (tx_no, receipt) in eth_block.receipts.as_ref().unwrap().iter().enumerate() {
-                    receipt_trie.insert_rlp(&alloy_rlp::encode(tx_no), receipt)?;
-                }
-                ensure!(
-                    receipt_trie.hash() == header.receipts_root,
-                    "Invalid eth block receipt data!"
-                );
-            } else {
-                let can_contain_deposits =
-                    deposits::can_contain(&config.deposit_contract, &header.logs_bloom);
-                let can_contain_config =
-                    system_config::can_contain(&config.system_config_contract, &header.logs_bloom);
-                ensure!(
-                    !can_contain_deposits,
-                    "Eth block has no receipts, but bloom filter indicates it has deposits"
-                );
-                ensure!(
-                    !can_contain_config,
-                    "Eth block has no receipts, but bloom filter indicates it has config updates"
-                );
-            }
+            validate_eth_block(config, *block_no, eth_block)?;
         }
 
         Ok(())
     }
 
     fn get_full_op_block(&mut self, block_no: u64) -> Result<BlockInput<OptimismTxEssence>> {
-        let op_block = self.full_op_block.remove(&block_no).unwrap();
+        let op_block = self
+            .full_op_block
+            .remove(&block_no)
+            .with_context(|| format!("Op block {} is not or no longer in the db", block_no))?;
 
         Ok(op_block)
     }
 
     fn get_op_block_header(&mut self, block_no: u64) -> Result<Header> {
-        let op_block = self
-            .op_block_header
-            .remove(&block_no)
-            .context("not or no longer in db")?;
+        let op_block = self.op_block_header.remove(&block_no).with_context(|| {
+            format!("Op block header {} is not or no longer in the db", block_no)
+        })?;
 
         Ok(op_block)
     }
 
     fn get_full_eth_block(&mut self, block_no: u64) -> Result<&BlockInput<EthereumTxEssence>> {
-        let eth_block = self.full_eth_block.get(&block_no).unwrap();
+        let eth_block = self
+            .full_eth_block
+            .get(&block_no)
+            .ok_or(EthGapError::MissingBlock(block_no))?;
 
         Ok(eth_block)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use zeth_primitives::U256;
+
+    use super::*;
+
+    fn empty_eth_block(receipts: Option<Vec<Receipt>>) -> MemDb {
+        let mut db = MemDb::new();
+        db.full_eth_block.insert(
+            0,
+            BlockInput {
+                block_header: Header::default(),
+                transactions: vec![],
+                receipts,
+                blob_sidecars: vec![],
+            },
+        );
+        db
+    }
+
+    #[test]
+    fn empty_eth_block_without_receipts() {
+        let db = empty_eth_block(None);
+        db.validate(&ChainConfig::optimism()).unwrap();
+    }
+
+    #[test]
+    fn empty_eth_block_with_receipts() {
+        let db = empty_eth_block(Some(vec![]));
+        db.validate(&ChainConfig::optimism()).unwrap();
+    }
+
+    #[test]
+    fn empty_eth_block_with_logs() {
+        let mut db = empty_eth_block(Some(vec![]));
+        let header = &mut db.full_eth_block.get_mut(&0).unwrap().block_header;
+        header.logs_bloom = Bloom::repeat_byte(0xff);
+        db.validate(&ChainConfig::optimism()).unwrap_err();
+    }
+
+    #[test]
+    fn empty_eth_block_with_gas() {
+        let mut db = empty_eth_block(None);
+        let header = &mut db.full_eth_block.get_mut(&0).unwrap().block_header;
+        header.gas_used = U256::from(21_000);
+        db.validate(&ChainConfig::optimism()).unwrap_err();
+    }
+
+    #[test]
+    fn validation_policy() {
+        let mismatch = || Err(anyhow::anyhow!("mismatch"));
+        assert!(ValidationPolicy::Strict.check(mismatch()).is_err());
+        assert!(ValidationPolicy::Permissive.check(mismatch()).is_ok());
+        assert!(ValidationPolicy::Strict.check(Ok(())).is_ok());
+    }
+
+    #[test]
+    fn empty_op_block() {
+        let mut db = MemDb::new();
+        db.full_op_block.insert(
+            0,
+            BlockInput {
+                block_header: Header::default(),
+                transactions: vec![],
+                receipts: None,
+                blob_sidecars: vec![],
+            },
+        );
+        db.validate(&ChainConfig::optimism()).unwrap_err();
+    }
+
+    #[test]
+    fn missing_blocks() {
+        let mut db = empty_eth_block(None);
+        db.op_block_header.insert(0, Header::default());
+        db.get_op_block_header(0).unwrap();
+
+        // blocks can only be taken once
+        let err = db.get_op_block_header(0).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Op block header 0 is not or no longer in the db"
+        );
+        let err = db.get_full_op_block(1).unwrap_err();
+        assert_eq!(err.to_string(), "Op block 1 is not or no longer in the db");
+        let err = db.get_full_eth_block(2).unwrap_err();
+        assert_eq!(err.to_string(), "Eth block 2 is missing from the witness");
+    }
+}
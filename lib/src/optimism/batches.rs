@@ -0,0 +1,859 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Decoding of the batches read from a channel, in particular of the span batches
+//! introduced with the Delta hard fork.
+//!
+//! A span batch encodes a range of consecutive L2 blocks. Instead of one RLP list per
+//! block, the fields of all blocks and transactions are stored one after the other,
+//! which compresses considerably better:
+//!
+//! ```text
+//! span_batch = prefix ++ payload
+//! prefix     = rel_timestamp ++ l1_origin_num ++ parent_check ++ l1_origin_check
+//! payload    = block_count ++ origin_bits ++ block_tx_counts ++ txs
+//! txs        = contract_creation_bits ++ y_parity_bits ++ tx_sigs ++ tx_tos ++ tx_datas
+//!              ++ tx_nonces ++ tx_gases ++ protected_bits
+//! ```
+//!
+//! As only the first 20 bytes of the parent hash and of the hash of the last L1 origin
+//! are encoded, a span batch can only be converted into singular batches after it has
+//! been validated against the L2 safe head and the traversed L1 blocks.
+//!
+//! The decoded batches are buffered in a [BatchQueue] until they are accepted or dropped.
+
+use std::collections::VecDeque;
+
+use anyhow::{bail, ensure, Context, Result};
+use serde::{Deserialize, Serialize};
+use zeth_primitives::{
+    access_list::AccessList,
+    alloy_rlp::{self, Decodable},
+    batch::{BatchEssence, RawTransaction},
+    transactions::{
+        ethereum::{
+            EthereumTxEssence, TransactionKind, TxEssenceEip1559, TxEssenceEip2930, TxEssenceLegacy,
+        },
+        signature::TxSignature,
+        Transaction,
+    },
+    Address, BlockHash, BlockNumber, Bytes, ChainId, U256,
+};
+
+use super::{
+    batcher::{BatchDropReason, BatchWithInclusion, DroppedBatch},
+    batcher_channel::MAX_RLP_BYTES_PER_CHANNEL,
+    config::ChainConfig,
+};
+
+/// The type of a singular batch, encoding a single L2 block.
+pub const SINGULAR_BATCH_TYPE: u8 = 0;
+/// The type of a span batch, encoding a range of L2 blocks.
+pub const SPAN_BATCH_TYPE: u8 = 1;
+
+/// Maximum number of blocks and transactions of a span batch, and maximum size of any of
+/// its fields. A span batch cannot be larger than the channel containing it.
+const MAX_SPAN_BATCH_SIZE: u64 = MAX_RLP_BYTES_PER_CHANNEL;
+/// Number of bytes of the hash prefixes in a span batch.
+const HASH_CHECK_LEN: usize = 20;
+
+const LEGACY_TX_TYPE: u8 = 0x00;
+const EIP2930_TX_TYPE: u8 = 0x01;
+const EIP1559_TX_TYPE: u8 = 0x02;
+
+/// A batch read from a channel.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BatchData {
+    /// A singular batch, encoding a single L2 block.
+    Singular(BatchEssence),
+    /// A span batch, encoding a range of L2 blocks.
+    Span(SpanBatch),
+}
+
+impl BatchData {
+    /// Returns the timestamp of the first L2 block of the batch.
+    pub fn timestamp(&self) -> u64 {
+        match self {
+            BatchData::Singular(essence) => essence.timestamp,
+            BatchData::Span(span_batch) => span_batch.timestamp(),
+        }
+    }
+
+    /// Returns the epoch number of the first L2 block of the batch.
+    pub fn epoch_num(&self) -> u64 {
+        match self {
+            BatchData::Singular(essence) => essence.epoch_num,
+            BatchData::Span(span_batch) => span_batch.start_epoch_num(),
+        }
+    }
+}
+
+/// A single L2 block of a [SpanBatch].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SpanBatchElement {
+    /// The number of the L1 origin of the L2 block.
+    pub epoch_num: BlockNumber,
+    /// The timestamp of the L2 block.
+    pub timestamp: u64,
+    /// The EIP-2718 encoded transactions of the L2 block.
+    pub transactions: Vec<RawTransaction>,
+}
+
+/// A decoded span batch, containing at least one L2 block.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SpanBatch {
+    /// The first 20 bytes of the hash of the parent of the first L2 block.
+    pub parent_check: [u8; HASH_CHECK_LEN],
+    /// The first 20 bytes of the hash of the L1 origin of the last L2 block.
+    pub l1_origin_check: [u8; HASH_CHECK_LEN],
+    /// The L2 blocks of the span batch.
+    pub blocks: Vec<SpanBatchElement>,
+}
+
+impl SpanBatch {
+    /// Returns the timestamp of the first L2 block.
+    pub fn timestamp(&self) -> u64 {
+        self.blocks[0].timestamp
+    }
+
+    /// Returns the timestamp of the last L2 block.
+    pub fn final_timestamp(&self) -> u64 {
+        self.blocks[self.blocks.len() - 1].timestamp
+    }
+
+    /// Returns the epoch number of the first L2 block.
+    pub fn start_epoch_num(&self) -> BlockNumber {
+        self.blocks[0].epoch_num
+    }
+
+    /// Returns the epoch number of the last L2 block.
+    pub fn final_epoch_num(&self) -> BlockNumber {
+        self.blocks[self.blocks.len() - 1].epoch_num
+    }
+
+    /// Returns whether the given hash matches the parent of the first L2 block.
+    pub fn check_parent_hash(&self, hash: &BlockHash) -> bool {
+        hash[..HASH_CHECK_LEN] == self.parent_check
+    }
+
+    /// Returns whether the given hash matches the L1 origin of the last L2 block.
+    pub fn check_origin_hash(&self, hash: &BlockHash) -> bool {
+        hash[..HASH_CHECK_LEN] == self.l1_origin_check
+    }
+
+    /// Decodes a span batch, without its type byte, and derives the timestamps, L1
+    /// origins and transactions of its L2 blocks.
+    pub fn decode(
+        data: &[u8],
+        l2_genesis_time: u64,
+        blocktime: u64,
+        chain_id: ChainId,
+    ) -> Result<Self> {
+        let mut reader = SpanBatchReader(data);
+
+        // decode the prefix
+        let rel_timestamp = reader.read_uvarint().context("invalid rel_timestamp")?;
+        let l1_origin_num = reader.read_uvarint().context("invalid l1_origin_num")?;
+        let parent_check = reader.read_array().context("invalid parent_check")?;
+        let l1_origin_check = reader.read_array().context("invalid l1_origin_check")?;
+
+        // decode the payload
+        let block_count = reader.read_uvarint().context("invalid block_count")?;
+        ensure!(block_count <= MAX_SPAN_BATCH_SIZE, "too many blocks");
+        ensure!(block_count > 0, "span batch must not be empty");
+        let origin_bits = reader
+            .read_bits(block_count)
+            .context("invalid origin_bits")?;
+        let mut block_tx_counts = Vec::new();
+        for _ in 0..block_count {
+            let tx_count = reader.read_uvarint().context("invalid block_tx_counts")?;
+            ensure!(tx_count <= MAX_SPAN_BATCH_SIZE, "too many transactions");
+            block_tx_counts.push(tx_count);
+        }
+        let tx_count = block_tx_counts
+            .iter()
+            .try_fold(0u64, |sum, count| sum.checked_add(*count))
+            .filter(|sum| *sum <= MAX_SPAN_BATCH_SIZE)
+            .context("too many transactions")?;
+        let mut transactions = reader
+            .read_transactions(tx_count, chain_id)
+            .context("invalid txs")?
+            .into_iter();
+
+        // the L1 origin of the last block is given, and every set origin bit marks a block
+        // that advances the L1 origin of its predecessor
+        let mut epoch_nums = vec![0; block_count as usize];
+        let mut epoch_num = l1_origin_num;
+        for i in (0..block_count as usize).rev() {
+            epoch_nums[i] = epoch_num;
+            if i > 0 && bit(origin_bits, i) {
+                // like op-node, an underflow results in an invalid L1 origin
+                epoch_num = epoch_num.wrapping_sub(1);
+            }
+        }
+
+        let blocks = block_tx_counts
+            .into_iter()
+            .zip(epoch_nums)
+            .enumerate()
+            .map(|(i, (tx_count, epoch_num))| SpanBatchElement {
+                epoch_num,
+                timestamp: l2_genesis_time
+                    .wrapping_add(rel_timestamp)
+                    .wrapping_add(blocktime.wrapping_mul(i as u64)),
+                transactions: transactions.by_ref().take(tx_count as usize).collect(),
+            })
+            .collect();
+
+        Ok(SpanBatch {
+            parent_check,
+            l1_origin_check,
+            blocks,
+        })
+    }
+}
+
+/// The validity of a buffered batch, following the batch queue rules of the spec.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BatchStatus {
+    /// The batch is invalid and is removed from the queue.
+    Drop(BatchDropReason),
+    /// The batch is valid and derives the next L2 block.
+    Accept,
+    /// The batch cannot be checked before more L1 blocks have been traversed.
+    Undecided,
+    /// The batch starts after the next L2 block and is checked again later.
+    Future,
+}
+
+/// The batches read from the channels in the order of their L1 inclusion, like the batch
+/// queue of op-node.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BatchQueue {
+    batches: VecDeque<BatchWithInclusion>,
+}
+
+impl BatchQueue {
+    /// Appends a batch read from the last traversed L1 block.
+    pub fn push(&mut self, batch: BatchWithInclusion) {
+        self.batches.push_back(batch);
+    }
+
+    /// Removes and returns the first accepted batch, checking the batches in the order of
+    /// their L1 inclusion. From the spec:
+    /// "The batches are processed in order of the inclusion on L1: if multiple batches
+    /// can  be accept-ed the first is applied."
+    ///
+    /// Dropped batches are removed and appended to `dropped`, while future batches are
+    /// kept in their order. An undecided batch ends the search and keeps all batches from
+    /// it on, so that no later batch is accepted before it is decided.
+    pub fn next_accepted(
+        &mut self,
+        mut status: impl FnMut(&BatchWithInclusion) -> Result<BatchStatus>,
+        dropped: &mut Vec<DroppedBatch>,
+    ) -> Result<Option<BatchWithInclusion>> {
+        let mut remaining = VecDeque::with_capacity(self.batches.len());
+        let mut accepted = None;
+        while let Some(batch) = self.batches.pop_front() {
+            let batch_status = match status(&batch) {
+                Ok(batch_status) => batch_status,
+                Err(err) => {
+                    self.batches.push_front(batch);
+                    remaining.append(&mut self.batches);
+                    self.batches = remaining;
+                    return Err(err);
+                }
+            };
+            match batch_status {
+                BatchStatus::Accept => {
+                    accepted = Some(batch);
+                    break;
+                }
+                BatchStatus::Drop(reason) => dropped.push(DroppedBatch {
+                    timestamp: batch.data.timestamp(),
+                    epoch_num: batch.data.epoch_num(),
+                    inclusion_block_number: batch.inclusion_block_number,
+                    reason,
+                }),
+                BatchStatus::Future => remaining.push_back(batch),
+                BatchStatus::Undecided => {
+                    remaining.push_back(batch);
+                    break;
+                }
+            }
+        }
+        // keep the unchecked batches behind the future and undecided ones
+        remaining.append(&mut self.batches);
+        self.batches = remaining;
+
+        Ok(accepted)
+    }
+}
+
+/// Decodes the batches of a channel, accepting span batches only after the Delta hard
+/// fork.
+#[derive(Debug, Clone, Copy)]
+pub struct BatchDecoder {
+    l2_genesis_time: u64,
+    blocktime: u64,
+    chain_id: ChainId,
+    delta_time: Option<u64>,
+}
+
+impl BatchDecoder {
+    pub fn new(config: &ChainConfig) -> Self {
+        Self {
+            l2_genesis_time: config.l2_genesis_time,
+            blocktime: config.blocktime,
+            chain_id: config.chain_spec.chain_id(),
+            delta_time: config.delta_time,
+        }
+    }
+
+    fn is_delta(&self, timestamp: u64) -> bool {
+        matches!(self.delta_time, Some(delta_time) if timestamp >= delta_time)
+    }
+
+    /// Decodes the next batch from the given buffer, advancing the buffer's position.
+    /// The timestamp of the L1 block in which the channel is read determines whether span
+    /// batches are accepted.
+    pub fn decode(&self, buf: &mut &[u8], l1_timestamp: u64) -> Result<BatchData> {
+        let bytes = alloy_rlp::Header::decode_bytes(buf, false)?;
+        match bytes.split_first() {
+            Some((&SINGULAR_BATCH_TYPE, mut payload)) => {
+                Ok(BatchData::Singular(BatchEssence::decode(&mut payload)?))
+            }
+            Some((&SPAN_BATCH_TYPE, payload)) => {
+                // span batches read before the Delta activation invalidate the rest of the
+                // channel
+                ensure!(self.is_delta(l1_timestamp), "span batch before Delta");
+                let span_batch = SpanBatch::decode(
+                    payload,
+                    self.l2_genesis_time,
+                    self.blocktime,
+                    self.chain_id,
+                )?;
+                Ok(BatchData::Span(span_batch))
+            }
+            Some((batch_type, _)) => bail!("invalid batch type: {}", batch_type),
+            None => bail!("batch too short"),
+        }
+    }
+}
+
+/// Returns whether bit `i` of the given big-endian bitlist is set.
+fn bit(bits: &[u8], i: usize) -> bool {
+    bits[bits.len() - 1 - i / 8] & (1 << (i % 8)) != 0
+}
+
+/// The value, fees and data of a legacy transaction in a span batch.
+#[derive(alloy_rlp_derive::RlpDecodable)]
+struct SpanBatchLegacyTxData {
+    value: U256,
+    gas_price: U256,
+    data: Bytes,
+}
+
+/// The value, fees, data and access list of an EIP-2930 transaction in a span batch.
+#[derive(alloy_rlp_derive::RlpDecodable)]
+struct SpanBatchEip2930TxData {
+    value: U256,
+    gas_price: U256,
+    data: Bytes,
+    access_list: AccessList,
+}
+
+/// The value, fees, data and access list of an EIP-1559 transaction in a span batch.
+#[derive(alloy_rlp_derive::RlpDecodable)]
+struct SpanBatchEip1559TxData {
+    value: U256,
+    max_priority_fee_per_gas: U256,
+    max_fee_per_gas: U256,
+    data: Bytes,
+    access_list: AccessList,
+}
+
+/// The transaction type specific fields of a transaction in a span batch.
+enum SpanBatchTxData {
+    Legacy(SpanBatchLegacyTxData),
+    Eip2930(SpanBatchEip2930TxData),
+    Eip1559(SpanBatchEip1559TxData),
+}
+
+/// Reads the fields of a span batch, advancing the position in the underlying buffer.
+struct SpanBatchReader<'a>(&'a [u8]);
+
+impl<'a> SpanBatchReader<'a> {
+    fn read_bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        ensure!(self.0.len() >= len, "input too short");
+        let (bytes, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(bytes)
+    }
+
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        Ok(self.read_bytes(N)?.try_into().unwrap())
+    }
+
+    /// Reads an unsigned LEB128 varint of at most 64 bits.
+    fn read_uvarint(&mut self) -> Result<u64> {
+        let mut value = 0u64;
+        for i in 0..10 {
+            let byte = self.read_bytes(1)?[0];
+            // the 10th byte can only contain the highest bit of the value
+            ensure!(i < 9 || byte <= 1, "uvarint overflows a 64-bit integer");
+            value |= u64::from(byte & 0x7f) << (7 * i);
+            if byte < 0x80 {
+                return Ok(value);
+            }
+        }
+        bail!("uvarint overflows a 64-bit integer")
+    }
+
+    /// Reads a big-endian bitlist of the given length, padded to full bytes.
+    fn read_bits(&mut self, len: u64) -> Result<&'a [u8]> {
+        let byte_len = len.div_ceil(8);
+        ensure!(byte_len <= MAX_SPAN_BATCH_SIZE, "bitlist too long");
+        let bits = self.read_bytes(byte_len as usize)?;
+        // the padding bits must not be set
+        if !len.is_multiple_of(8) {
+            ensure!(
+                bits[0] >> (len % 8) == 0,
+                "bitlist longer than {} bits",
+                len
+            );
+        }
+        Ok(bits)
+    }
+
+    /// Reads the type specific fields of a transaction: an RLP list, which is prefixed
+    /// with the EIP-2718 transaction type for all but legacy transactions.
+    fn read_tx_data(&mut self) -> Result<SpanBatchTxData> {
+        let tx_type = match self.0.first() {
+            Some(&tx_type) if tx_type <= 0x7f => {
+                self.0 = &self.0[1..];
+                tx_type
+            }
+            _ => LEGACY_TX_TYPE,
+        };
+        let mut payload = self.read_rlp_list()?;
+        let tx_data = match tx_type {
+            LEGACY_TX_TYPE => SpanBatchTxData::Legacy(Decodable::decode(&mut payload)?),
+            EIP2930_TX_TYPE => SpanBatchTxData::Eip2930(Decodable::decode(&mut payload)?),
+            EIP1559_TX_TYPE => SpanBatchTxData::Eip1559(Decodable::decode(&mut payload)?),
+            _ => bail!("invalid transaction type: {}", tx_type),
+        };
+        ensure!(payload.is_empty(), "unexpected trailing transaction data");
+
+        Ok(tx_data)
+    }
+
+    /// Reads a complete RLP list, including its header.
+    fn read_rlp_list(&mut self) -> Result<&'a [u8]> {
+        let mut buf = self.0;
+        let header = alloy_rlp::Header::decode(&mut buf)?;
+        ensure!(header.list, "transaction data must be an RLP list");
+        let len = self.0.len() - buf.len() + header.payload_length;
+        self.read_bytes(len)
+    }
+
+    /// Reads the given number of transactions and returns their EIP-2718 encoding.
+    /// The allocations only grow with the data actually read, as the counts are
+    /// untrusted.
+    fn read_transactions(
+        &mut self,
+        tx_count: u64,
+        chain_id: ChainId,
+    ) -> Result<Vec<RawTransaction>> {
+        let contract_creation_bits = self.read_bits(tx_count)?;
+        let y_parity_bits = self.read_bits(tx_count)?;
+        let tx_count = tx_count as usize;
+        let mut signatures = Vec::new();
+        for _ in 0..tx_count {
+            let r = U256::from_be_bytes(self.read_array::<32>()?);
+            let s = U256::from_be_bytes(self.read_array::<32>()?);
+            signatures.push((r, s));
+        }
+        let mut tos = Vec::new();
+        for i in 0..tx_count {
+            if !bit(contract_creation_bits, i) {
+                tos.push(Address::from(self.read_array::<20>()?));
+            }
+        }
+        let mut tx_datas = Vec::new();
+        for _ in 0..tx_count {
+            tx_datas.push(self.read_tx_data()?);
+        }
+        let mut nonces = Vec::new();
+        for _ in 0..tx_count {
+            nonces.push(self.read_uvarint()?);
+        }
+        let mut gas_limits = Vec::new();
+        for _ in 0..tx_count {
+            gas_limits.push(U256::from(self.read_uvarint()?));
+        }
+        let legacy_tx_count = tx_datas
+            .iter()
+            .filter(|tx_data| matches!(tx_data, SpanBatchTxData::Legacy(_)))
+            .count();
+        let protected_bits = self.read_bits(legacy_tx_count as u64)?;
+
+        let mut tos = tos.into_iter();
+        let mut legacy_tx_index = 0;
+        let mut transactions = Vec::new();
+        for (i, tx_data) in tx_datas.into_iter().enumerate() {
+            let to = match bit(contract_creation_bits, i) {
+                true => TransactionKind::Create,
+                false => TransactionKind::Call(tos.next().unwrap()),
+            };
+            let y_parity = bit(y_parity_bits, i) as u64;
+            let (r, s) = signatures[i];
+            let (essence, v) = match tx_data {
+                SpanBatchTxData::Legacy(tx_data) => {
+                    let protected = bit(protected_bits, legacy_tx_index);
+                    legacy_tx_index += 1;
+                    let essence = EthereumTxEssence::Legacy(TxEssenceLegacy {
+                        chain_id: protected.then_some(chain_id),
+                        nonce: nonces[i],
+                        gas_price: tx_data.gas_price,
+                        gas_limit: gas_limits[i],
+                        to,
+                        value: tx_data.value,
+                        data: tx_data.data,
+                    });
+                    // EIP-155 encodes the chain ID in the V value of protected transactions
+                    let v = match protected {
+                        true => chain_id * 2 + 35 + y_parity,
+                        false => 27 + y_parity,
+                    };
+                    (essence, v)
+                }
+                SpanBatchTxData::Eip2930(tx_data) => {
+                    let essence = EthereumTxEssence::Eip2930(TxEssenceEip2930 {
+                        chain_id,
+                        nonce: nonces[i],
+                        gas_price: tx_data.gas_price,
+                        gas_limit: gas_limits[i],
+                        to,
+                        value: tx_data.value,
+                        data: tx_data.data,
+                        access_list: tx_data.access_list,
+                    });
+                    (essence, y_parity)
+                }
+                SpanBatchTxData::Eip1559(tx_data) => {
+                    let essence = EthereumTxEssence::Eip1559(TxEssenceEip1559 {
+                        chain_id,
+                        nonce: nonces[i],
+                        max_priority_fee_per_gas: tx_data.max_priority_fee_per_gas,
+                        max_fee_per_gas: tx_data.max_fee_per_gas,
+                        gas_limit: gas_limits[i],
+                        to,
+                        value: tx_data.value,
+                        data: tx_data.data,
+                        access_list: tx_data.access_list,
+                    });
+                    (essence, y_parity)
+                }
+            };
+            let transaction = Transaction {
+                essence,
+                signature: TxSignature { v, r, s },
+            };
+            transactions.push(alloy_rlp::encode(transaction).into());
+        }
+
+        Ok(transactions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use zeth_primitives::{address, b256, batch::Batch, B256};
+
+    use super::*;
+    use crate::optimism::batcher_channel::ChannelId;
+
+    /// Appends the given value as an unsigned LEB128 varint.
+    fn put_uvarint(out: &mut Vec<u8>, mut value: u64) {
+        while value >= 0x80 {
+            out.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        out.push(value as u8);
+    }
+
+    /// Appends the given fields as an RLP list.
+    fn put_list(out: &mut Vec<u8>, fields: &[&dyn alloy_rlp::Encodable]) {
+        alloy_rlp::encode_list::<_, dyn alloy_rlp::Encodable>(fields, out);
+    }
+
+    /// Appends the given bits as a big-endian bitlist.
+    fn put_bits(out: &mut Vec<u8>, bits: &[bool]) {
+        let mut bytes = vec![0u8; bits.len().div_ceil(8)];
+        let len = bytes.len();
+        for (i, _) in bits.iter().enumerate().filter(|(_, bit)| **bit) {
+            bytes[len - 1 - i / 8] |= 1 << (i % 8);
+        }
+        out.extend(bytes);
+    }
+
+    #[test]
+    fn uvarint() {
+        for value in [0, 1, 127, 128, 300, u32::MAX as u64, u64::MAX] {
+            let mut buf = Vec::new();
+            put_uvarint(&mut buf, value);
+            let mut reader = SpanBatchReader(&buf);
+            assert_eq!(reader.read_uvarint().unwrap(), value);
+            assert!(reader.0.is_empty());
+        }
+
+        let overflow = [0xff; 9].into_iter().chain([0x02]).collect::<Vec<_>>();
+        assert!(SpanBatchReader(&overflow).read_uvarint().is_err());
+        assert!(SpanBatchReader(&[0x80]).read_uvarint().is_err());
+    }
+
+    #[test]
+    fn bits() {
+        let mut buf = Vec::new();
+        let expected = [
+            true, false, false, true, false, false, false, false, true, true,
+        ];
+        put_bits(&mut buf, &expected);
+        assert_eq!(buf, [0b11, 0b1001]);
+
+        let bits = SpanBatchReader(&buf).read_bits(10).unwrap();
+        let decoded: Vec<_> = (0..10).map(|i| bit(bits, i)).collect();
+        assert_eq!(decoded, expected);
+
+        // padding bits must be zero
+        assert!(SpanBatchReader(&[0b111, 0]).read_bits(10).is_err());
+    }
+
+    #[test]
+    fn decode_span_batch() {
+        let chain_id = 10;
+        let parent_hash = b256!("dbf6a80fef073de06add9b0d14026d6e5a86c85f6d102c36d3d8e9cf89c2afd3");
+        let l1_origin_hash =
+            b256!("438335a20d98863a4c0c97999eb2481921ccd28553eac6f913af7c12aec04108");
+        let to = address!("4200000000000000000000000000000000000042");
+
+        // three blocks with origins 99, 100, 100, the second with a protected legacy tx and
+        // the third with an EIP-1559 contract creation
+        let mut data = Vec::new();
+        put_uvarint(&mut data, 10);
+        put_uvarint(&mut data, 100);
+        data.extend_from_slice(&parent_hash[..20]);
+        data.extend_from_slice(&l1_origin_hash[..20]);
+        put_uvarint(&mut data, 3);
+        put_bits(&mut data, &[false, true, false]);
+        for tx_count in [0, 1, 1] {
+            put_uvarint(&mut data, tx_count);
+        }
+        put_bits(&mut data, &[false, true]);
+        put_bits(&mut data, &[true, false]);
+        data.extend([0x11; 64]);
+        data.extend([0x22; 64]);
+        data.extend_from_slice(to.as_slice());
+        put_list(&mut data, &[&U256::from(1), &U256::from(2), &Bytes::new()]);
+        data.push(EIP1559_TX_TYPE);
+        put_list(
+            &mut data,
+            &[
+                &U256::ZERO,
+                &U256::from(3),
+                &U256::from(4),
+                &Bytes::from_static(&[0x60, 0x00]),
+                &AccessList::default(),
+            ],
+        );
+        for value in [5, 6, 21_000, 100_000] {
+            put_uvarint(&mut data, value);
+        }
+        put_bits(&mut data, &[true]);
+
+        let span_batch = SpanBatch::decode(&data, 1_000, 2, chain_id).unwrap();
+        assert!(span_batch.check_parent_hash(&parent_hash));
+        assert!(span_batch.check_origin_hash(&l1_origin_hash));
+        assert_eq!(span_batch.start_epoch_num(), 99);
+        assert_eq!(span_batch.final_epoch_num(), 100);
+        assert_eq!(span_batch.timestamp(), 1_010);
+        assert_eq!(span_batch.final_timestamp(), 1_014);
+
+        let legacy_tx = Transaction {
+            essence: EthereumTxEssence::Legacy(TxEssenceLegacy {
+                chain_id: Some(chain_id),
+                nonce: 5,
+                gas_price: U256::from(2),
+                gas_limit: U256::from(21_000),
+                to: TransactionKind::Call(to),
+                value: U256::from(1),
+                data: Bytes::new(),
+            }),
+            signature: TxSignature {
+                v: chain_id * 2 + 36,
+                r: U256::from_be_bytes([0x11; 32]),
+                s: U256::from_be_bytes([0x11; 32]),
+            },
+        };
+        let eip1559_tx = Transaction {
+            essence: EthereumTxEssence::Eip1559(TxEssenceEip1559 {
+                chain_id,
+                nonce: 6,
+                max_priority_fee_per_gas: U256::from(3),
+                max_fee_per_gas: U256::from(4),
+                gas_limit: U256::from(100_000),
+                to: TransactionKind::Create,
+                value: U256::ZERO,
+                data: Bytes::from_static(&[0x60, 0x00]),
+                access_list: AccessList::default(),
+            }),
+            signature: TxSignature {
+                v: 0,
+                r: U256::from_be_bytes([0x22; 32]),
+                s: U256::from_be_bytes([0x22; 32]),
+            },
+        };
+        assert_eq!(
+            span_batch.blocks,
+            [
+                SpanBatchElement {
+                    epoch_num: 99,
+                    timestamp: 1_010,
+                    transactions: vec![],
+                },
+                SpanBatchElement {
+                    epoch_num: 100,
+                    timestamp: 1_012,
+                    transactions: vec![alloy_rlp::encode(&legacy_tx).into()],
+                },
+                SpanBatchElement {
+                    epoch_num: 100,
+                    timestamp: 1_014,
+                    transactions: vec![alloy_rlp::encode(&eip1559_tx).into()],
+                },
+            ]
+        );
+
+        // the decoded transactions must be valid
+        let mut raw_tx = span_batch.blocks[1].transactions[0].as_ref();
+        let decoded = Transaction::<EthereumTxEssence>::decode(&mut raw_tx).unwrap();
+        assert_eq!(decoded, legacy_tx);
+    }
+
+    #[test]
+    fn empty_span_batch() {
+        let mut data = vec![0, 0];
+        data.extend([0; 2 * HASH_CHECK_LEN]);
+        put_uvarint(&mut data, 0);
+        assert!(SpanBatch::decode(&data, 0, 2, 10).is_err());
+    }
+
+    #[test]
+    fn span_batch_before_delta() {
+        let mut config = ChainConfig::optimism();
+        config.delta_time = Some(1_000);
+        let decoder = BatchDecoder::new(&config);
+
+        let mut data = vec![SPAN_BATCH_TYPE, 0, 0];
+        data.extend([0; 2 * HASH_CHECK_LEN]);
+        put_uvarint(&mut data, 1);
+        // no origin bits and no transactions
+        data.extend([0, 0]);
+        let batch = alloy_rlp::encode(Bytes::from(data));
+
+        let err = decoder.decode(&mut batch.as_slice(), 999).unwrap_err();
+        assert_eq!(err.to_string(), "span batch before Delta");
+        let batch = decoder.decode(&mut batch.as_slice(), 1_000).unwrap();
+        assert!(matches!(batch, BatchData::Span(span) if span.blocks.len() == 1));
+    }
+
+    /// Returns a queue of singular batches, identified by their channel ID.
+    fn singular_queue(len: usize) -> BatchQueue {
+        let mut queue = BatchQueue::default();
+        for i in 0..len {
+            queue.push(BatchWithInclusion {
+                data: BatchData::Singular(Batch::new(B256::ZERO, 1, B256::ZERO, i as u64).0),
+                inclusion_block_number: 1,
+                channel_id: i as ChannelId,
+            });
+        }
+        queue
+    }
+
+    fn channel_ids(queue: &BatchQueue) -> Vec<ChannelId> {
+        queue.batches.iter().map(|batch| batch.channel_id).collect()
+    }
+
+    #[test]
+    fn batch_queue() {
+        let status = [
+            BatchStatus::Future,
+            BatchStatus::Drop(BatchDropReason::TooOld),
+            BatchStatus::Future,
+            BatchStatus::Accept,
+            BatchStatus::Accept,
+        ];
+        let mut queue = singular_queue(status.len());
+        let mut dropped = vec![];
+        let batch = queue
+            .next_accepted(
+                |batch| Ok(status[batch.channel_id as usize].clone()),
+                &mut dropped,
+            )
+            .unwrap()
+            .unwrap();
+
+        // the first accepted batch is returned, while future batches are kept in order
+        assert_eq!(batch.channel_id, 3);
+        assert_eq!(channel_ids(&queue), [0, 2, 4]);
+        assert_eq!(dropped.len(), 1);
+        assert_eq!(dropped[0].timestamp, 1);
+        assert_eq!(dropped[0].reason, BatchDropReason::TooOld);
+    }
+
+    #[test]
+    fn undecided_batch() {
+        let status = [
+            BatchStatus::Future,
+            BatchStatus::Undecided,
+            BatchStatus::Drop(BatchDropReason::TooOld),
+            BatchStatus::Accept,
+        ];
+        let mut queue = singular_queue(status.len());
+        let mut dropped = vec![];
+        let batch = queue
+            .next_accepted(
+                |batch| Ok(status[batch.channel_id as usize].clone()),
+                &mut dropped,
+            )
+            .unwrap();
+
+        // no later batch is checked before the undecided batch is decided
+        assert!(batch.is_none());
+        assert!(dropped.is_empty());
+        assert_eq!(channel_ids(&queue), [0, 1, 2, 3]);
+
+        // an error keeps all batches
+        let err = queue.next_accepted(
+            |batch| match batch.channel_id {
+                0 => Ok(BatchStatus::Future),
+                _ => bail!("error"),
+            },
+            &mut dropped,
+        );
+        assert!(err.is_err());
+        assert_eq!(channel_ids(&queue), [0, 1, 2, 3]);
+    }
+}
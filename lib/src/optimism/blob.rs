@@ -0,0 +1,605 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Decoding of the rollup data posted in EIP-4844 blobs.
+//!
+//! A blob consists of 4096 field elements of 32 bytes each. As the two highest bits of
+//! every field element must be zero, each round of four field elements encodes 127 bytes:
+//! 4 × 31 bytes in the lower bytes of the field elements and 3 bytes split into 6-bit
+//! chunks stored in their first bytes. The first round additionally starts with the
+//! version byte and the 3-byte big-endian length of the data.
+
+use anyhow::{anyhow, bail, ensure, Context, Result};
+#[cfg(any(not(target_os = "zkvm"), feature = "kzg"))]
+use c_kzg::{KzgCommitment, KzgProof};
+#[cfg(any(not(target_os = "zkvm"), feature = "kzg"))]
+use revm::primitives::EnvKzgSettings;
+use serde::{Deserialize, Serialize};
+use zeth_primitives::{
+    transactions::ethereum::{kzg_to_versioned_hash, TxEssenceEip4844},
+    Bytes, FixedBytes, B256,
+};
+
+/// Number of field elements in a blob.
+pub const FIELD_ELEMENTS_PER_BLOB: usize = 4096;
+/// Size of a blob in bytes.
+pub const BLOB_SIZE: usize = FIELD_ELEMENTS_PER_BLOB * 32;
+/// Maximum number of data bytes that can be encoded in a single blob.
+pub const MAX_BLOB_DATA_SIZE: usize = (4 * 31 + 3) * ROUNDS - 4;
+/// The only supported version of the blob encoding.
+pub const BLOB_ENCODING_VERSION: u8 = 0;
+
+/// Number of rounds of four field elements.
+const ROUNDS: usize = FIELD_ELEMENTS_PER_BLOB / 4;
+/// Offset of the encoding version in the blob.
+const VERSION_OFFSET: usize = 1;
+
+/// An EIP-4844 blob.
+pub type Blob = [u8; BLOB_SIZE];
+
+/// A blob of an L1 transaction together with its KZG commitment, as published by the
+/// beacon chain.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlobSidecar {
+    /// The blob of [BLOB_SIZE] bytes.
+    pub blob: Bytes,
+    /// The KZG commitment to the blob.
+    pub kzg_commitment: FixedBytes<48>,
+    /// The KZG proof of the blob against its commitment.
+    pub kzg_proof: FixedBytes<48>,
+}
+
+impl BlobSidecar {
+    /// Returns the versioned hash of the commitment, which references the blob in its
+    /// transaction.
+    pub fn versioned_hash(&self) -> B256 {
+        kzg_to_versioned_hash(self.kzg_commitment.as_slice())
+    }
+
+    /// Returns the blob, if it has the correct size.
+    pub fn blob(&self) -> Result<&Blob> {
+        self.blob
+            .as_ref()
+            .try_into()
+            .with_context(|| format!("invalid blob size: {}", self.blob.len()))
+    }
+
+    /// Verifies the KZG proof of the blob against its commitment, using the trusted setup
+    /// of the Ethereum mainnet.
+    #[cfg(any(not(target_os = "zkvm"), feature = "kzg"))]
+    pub fn verify_kzg_proof(&self) -> Result<()> {
+        let blob = c_kzg::Blob::from_bytes(self.blob()?).map_err(kzg_error)?;
+        let valid = KzgProof::verify_blob_kzg_proof(
+            &blob,
+            &self.kzg_commitment.0.into(),
+            &self.kzg_proof.0.into(),
+            EnvKzgSettings::Default.get(),
+        )
+        .map_err(kzg_error)?;
+        ensure!(valid, "invalid KZG proof of blob {}", self.versioned_hash());
+        Ok(())
+    }
+
+    #[cfg(all(target_os = "zkvm", not(feature = "kzg")))]
+    pub fn verify_kzg_proof(&self) -> Result<()> {
+        bail!("Unsupported! The guest was built without the kzg feature")
+    }
+
+    /// Creates the sidecar of the given blob, computing its KZG commitment and proof.
+    #[cfg(not(target_os = "zkvm"))]
+    pub fn from_blob(blob: &Blob) -> Result<Self> {
+        let kzg_settings = EnvKzgSettings::Default.get();
+        let kzg_blob = c_kzg::Blob::from_bytes(blob).map_err(kzg_error)?;
+        let commitment = KzgCommitment::blob_to_kzg_commitment(&kzg_blob, kzg_settings)
+            .map_err(kzg_error)?
+            .to_bytes();
+        let proof = KzgProof::compute_blob_kzg_proof(&kzg_blob, &commitment, kzg_settings)
+            .map_err(kzg_error)?
+            .to_bytes();
+        Ok(Self {
+            blob: Bytes::copy_from_slice(blob),
+            kzg_commitment: FixedBytes(commitment.into_inner()),
+            kzg_proof: FixedBytes(proof.into_inner()),
+        })
+    }
+}
+
+#[cfg(any(not(target_os = "zkvm"), feature = "kzg"))]
+fn kzg_error(err: c_kzg::Error) -> anyhow::Error {
+    anyhow!("KZG error: {:?}", err)
+}
+
+/// The blobs of an L1 block, looked up by the versioned hashes of the block's blob
+/// transactions.
+///
+/// As the versioned hashes are part of the transactions, and thereby authenticated by the
+/// block header, matching them against the commitments binds every sidecar to its block.
+/// The KZG proof of every returned sidecar then binds its blob to the commitment.
+pub struct BlobSource<'a> {
+    sidecars: Vec<(B256, &'a BlobSidecar)>,
+}
+
+impl<'a> BlobSource<'a> {
+    pub fn new(sidecars: &'a [BlobSidecar]) -> Self {
+        Self {
+            sidecars: sidecars
+                .iter()
+                .map(|sidecar| (sidecar.versioned_hash(), sidecar))
+                .collect(),
+        }
+    }
+
+    /// Returns the blobs of the transaction in the order of its versioned hashes, or an
+    /// error if no sidecar commits to one of them.
+    pub fn blobs(&self, blob_tx: &TxEssenceEip4844) -> Result<Vec<&'a Blob>> {
+        let sidecars = blob_tx
+            .blob_versioned_hashes
+            .iter()
+            .map(|versioned_hash| {
+                self.sidecars
+                    .iter()
+                    .find(|(hash, _)| hash == versioned_hash)
+                    .map(|(_, sidecar)| *sidecar)
+                    .with_context(|| format!("missing blob sidecar for {}", versioned_hash))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let commitments: Vec<_> = sidecars
+            .iter()
+            .map(|sidecar| sidecar.kzg_commitment)
+            .collect();
+        blob_tx
+            .validate_blob_commitments(&commitments)
+            .context("blob sidecars do not match the transaction")?;
+        sidecars
+            .into_iter()
+            .map(|sidecar| {
+                sidecar.verify_kzg_proof()?;
+                sidecar.blob()
+            })
+            .collect()
+    }
+}
+
+/// Encodes the data into a blob, following the reference implementation of the spec.
+pub fn data_to_blob(data: &[u8]) -> Result<Box<Blob>> {
+    ensure!(
+        data.len() <= MAX_BLOB_DATA_SIZE,
+        "blob data too large: {}",
+        data.len()
+    );
+    // version and length, followed by the zero-padded data
+    let mut stream = vec![BLOB_ENCODING_VERSION];
+    stream.extend_from_slice(&(data.len() as u32).to_be_bytes()[1..]);
+    stream.extend_from_slice(data);
+    stream.resize(127 * ROUNDS, 0);
+
+    let mut blob = Box::new([0u8; BLOB_SIZE]);
+    for (round, chunk) in stream.as_chunks::<127>().0.iter().enumerate() {
+        let (x, y, z) = (chunk[31], chunk[63], chunk[95]);
+        let encoded = [
+            x & 0b0011_1111,
+            (y & 0b0000_1111) | ((x & 0b1100_0000) >> 2),
+            z & 0b0011_1111,
+            ((z & 0b1100_0000) >> 2) | ((y & 0b1111_0000) >> 4),
+        ];
+        for (j, encoded_byte) in encoded.into_iter().enumerate() {
+            let ipos = 128 * round + 32 * j;
+            blob[ipos] = encoded_byte;
+            blob[ipos + 1..ipos + 32].copy_from_slice(&chunk[32 * j..32 * j + 31]);
+        }
+    }
+    Ok(blob)
+}
+
+/// Decodes the data encoded in the given blob.
+pub fn blob_to_data(blob: &Blob) -> Result<Vec<u8>> {
+    ensure!(
+        blob[VERSION_OFFSET] == BLOB_ENCODING_VERSION,
+        "invalid blob encoding version: {}",
+        blob[VERSION_OFFSET]
+    );
+    // decode the 3-byte big-endian length
+    let len = u32::from_be_bytes([0, blob[2], blob[3], blob[4]]) as usize;
+    ensure!(
+        len <= MAX_BLOB_DATA_SIZE,
+        "invalid blob data length: {}",
+        len
+    );
+
+    let mut data = vec![0u8; MAX_BLOB_DATA_SIZE];
+    let mut encoded = [0u8; 4];
+
+    // the first round only contains 27 bytes in its first field element, as the first 5
+    // bytes are used for the version and the length
+    encoded[0] = decode_field_element(blob, 0, &mut [])?;
+    data[..27].copy_from_slice(&blob[5..32]);
+    let mut opos = 28;
+    let mut ipos = 32;
+    for encoded_byte in &mut encoded[1..] {
+        *encoded_byte = decode_field_element(blob, ipos, &mut data[opos..opos + 31])?;
+        opos += 32;
+        ipos += 32;
+    }
+    opos = reassemble_bytes(opos, &encoded, &mut data);
+
+    // every other round decodes 4 field elements into 127 bytes
+    for _ in 1..ROUNDS {
+        if opos >= len {
+            break;
+        }
+        for encoded_byte in &mut encoded {
+            *encoded_byte = decode_field_element(blob, ipos, &mut data[opos..opos + 31])?;
+            opos += 32;
+            ipos += 32;
+        }
+        opos = reassemble_bytes(opos, &encoded, &mut data);
+    }
+
+    // the remainder of the last round and of the blob must be empty
+    if let Some(pos) = data[len..].iter().position(|b| *b != 0) {
+        bail!("non-zero data after the blob data at {}", len + pos);
+    }
+    if let Some(pos) = blob[ipos..].iter().position(|b| *b != 0) {
+        bail!(
+            "non-zero data after the last field element at {}",
+            ipos + pos
+        );
+    }
+    data.truncate(len);
+
+    Ok(data)
+}
+
+/// Copies the lower 31 bytes of the field element at `ipos` into `out` and returns its
+/// first byte.
+fn decode_field_element(blob: &Blob, ipos: usize, out: &mut [u8]) -> Result<u8> {
+    // the two highest bits of the first byte must always be zero
+    ensure!(
+        blob[ipos] & 0b1100_0000 == 0,
+        "invalid field element at {}",
+        ipos
+    );
+    out.copy_from_slice(&blob[ipos + 1..ipos + 1 + out.len()]);
+    Ok(blob[ipos])
+}
+
+/// Reassembles the three bytes split into the 6-bit chunks of the last four field
+/// elements and returns the position of the next round in the output.
+fn reassemble_bytes(opos: usize, encoded: &[u8; 4], data: &mut [u8]) -> usize {
+    // there is no 128th byte in a round
+    let opos = opos - 1;
+    let x = (encoded[0] & 0b0011_1111) | ((encoded[1] & 0b0011_0000) << 2);
+    let y = (encoded[1] & 0b0000_1111) | ((encoded[3] & 0b0000_1111) << 4);
+    let z = (encoded[2] & 0b0011_1111) | ((encoded[3] & 0b0011_0000) << 2);
+    data[opos - 32] = z;
+    data[opos - 32 * 2] = y;
+    data[opos - 32 * 3] = x;
+    opos
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers_core::k256::ecdsa::SigningKey;
+    use zeth_primitives::{
+        transactions::{
+            ethereum::{EthereumTxEssence, TransactionKind, TxEssenceEip1559, TxEssenceEip4844},
+            signature::TxSignature,
+            Transaction,
+        },
+        uint, U256,
+    };
+
+    use super::*;
+    use crate::optimism::{
+        batcher_channel::{
+            BatcherChannels, BatcherTransactions, DropReason, DroppedTransaction, Frame, FrameQueue,
+        },
+        config::ChainConfig,
+        stage::Stage,
+    };
+
+    fn test_data(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i * 131 + 7) as u8).collect()
+    }
+
+    #[test]
+    fn layout() {
+        let mut blob = Box::new([0u8; BLOB_SIZE]);
+        blob[1] = BLOB_ENCODING_VERSION;
+        blob[4] = 3;
+        blob[5..8].copy_from_slice(b"abc");
+        assert_eq!(blob_to_data(&blob).unwrap(), b"abc");
+        assert_eq!(data_to_blob(b"abc").unwrap(), blob);
+
+        // the byte following the first 27 bytes is split into the first bytes of the
+        // first two field elements
+        let mut data = [0u8; 28];
+        data[27] = 0xff;
+        let blob = data_to_blob(&data).unwrap();
+        assert_eq!((blob[0], blob[32]), (0b0011_1111, 0b0011_0000));
+        assert_eq!(blob_to_data(&blob).unwrap(), data);
+    }
+
+    #[test]
+    fn round_trip() {
+        for len in [
+            0,
+            1,
+            26,
+            27,
+            28,
+            31,
+            32,
+            59,
+            60,
+            92,
+            123,
+            124,
+            127,
+            250,
+            251,
+            1000,
+            MAX_BLOB_DATA_SIZE - 1,
+            MAX_BLOB_DATA_SIZE,
+        ] {
+            let data = test_data(len);
+            assert_eq!(
+                blob_to_data(&data_to_blob(&data).unwrap()).unwrap(),
+                data,
+                "{}",
+                len
+            );
+        }
+        assert!(data_to_blob(&test_data(MAX_BLOB_DATA_SIZE + 1)).is_err());
+    }
+
+    #[test]
+    fn invalid() {
+        let data = test_data(1000);
+
+        let mut blob = data_to_blob(&data).unwrap();
+        blob[VERSION_OFFSET] = 1;
+        assert!(blob_to_data(&blob).is_err());
+
+        let mut blob = data_to_blob(&data).unwrap();
+        blob[2..5].copy_from_slice(&(MAX_BLOB_DATA_SIZE as u32 + 1).to_be_bytes()[1..]);
+        assert!(blob_to_data(&blob).is_err());
+
+        // highest bits of a field element set
+        let mut blob = data_to_blob(&data).unwrap();
+        blob[128] |= 0b1000_0000;
+        assert!(blob_to_data(&blob).is_err());
+        let mut blob = data_to_blob(&data).unwrap();
+        blob[0] |= 0b0100_0000;
+        assert!(blob_to_data(&blob).is_err());
+
+        // non-zero data in the last round after the data
+        let mut blob = data_to_blob(&test_data(1001)).unwrap();
+        blob[2..5].copy_from_slice(&1000u32.to_be_bytes()[1..]);
+        assert!(blob_to_data(&blob).is_err());
+
+        // non-zero data after the last decoded field element
+        let mut blob = data_to_blob(&data).unwrap();
+        blob[BLOB_SIZE - 1] = 1;
+        assert!(blob_to_data(&blob).is_err());
+    }
+
+    #[test]
+    fn frames() {
+        let frame = Frame {
+            channel_id: 0x0102,
+            number: 0,
+            data: test_data(500),
+            is_last: true,
+        };
+        // derivation version byte followed by the encoded frame
+        let mut data = vec![0];
+        data.extend_from_slice(&frame.channel_id.to_be_bytes());
+        data.extend_from_slice(&frame.number.to_be_bytes());
+        data.extend_from_slice(&(frame.data.len() as u32).to_be_bytes());
+        data.extend_from_slice(&frame.data);
+        data.push(frame.is_last as u8);
+
+        let decoded = blob_to_data(&data_to_blob(&data).unwrap()).unwrap();
+        let frames = Frame::process_batcher_data(&decoded).unwrap();
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].channel_id, frame.channel_id);
+        assert_eq!(frames[0].data, frame.data);
+        assert!(frames[0].is_last);
+    }
+
+    #[test]
+    fn blob_transactions() {
+        let mut config = ChainConfig::optimism();
+        config.ecotone_time = Some(1_000);
+
+        // a single closing frame
+        let mut data = vec![0];
+        data.extend_from_slice(&0xffu128.to_be_bytes());
+        data.extend_from_slice(&0u16.to_be_bytes());
+        data.extend_from_slice(&0u32.to_be_bytes());
+        data.push(1);
+        let sidecar = BlobSidecar::from_blob(&data_to_blob(&data).unwrap()).unwrap();
+
+        // the calldata of blob transactions is only used before Ecotone
+        let transactions = vec![Transaction {
+            essence: EthereumTxEssence::Eip4844(TxEssenceEip4844 {
+                to: config.batch_inbox,
+                data: Bytes::from_static(&[0, 1, 2, 3]),
+                blob_versioned_hashes: vec![sidecar.versioned_hash()],
+                ..Default::default()
+            }),
+            signature: TxSignature {
+                v: 1,
+                r: uint!(0x88ff6cf0fefd94db46111149ae4bfc179e9b94721fffd821d38d16464b3f71d0_U256),
+                s: uint!(0x45e0aff800961cfce805daef7016b9b675c137a6a41a548f7b60a3484c06a33a_U256),
+            },
+        }];
+        let batch_sender = transactions[0].recover_from().unwrap();
+        let sidecars = [sidecar];
+
+        let mut channels = BatcherChannels::new(&config);
+        channels
+            .process_l1_transactions(batch_sender, 100, 999, &transactions, &sidecars)
+            .unwrap();
+        assert_eq!(
+            channels.dropped_transactions(),
+            [DroppedTransaction {
+                block_number: 100,
+                tx_index: 0,
+                reason: DropReason::InvalidFrames,
+            }]
+        );
+        assert!(channels.read_batches().is_none());
+
+        // after Ecotone, the frames are read from the blob
+        channels
+            .process_l1_transactions(batch_sender, 101, 1_000, &transactions, &sidecars)
+            .unwrap();
+        assert_eq!(channels.dropped_transactions().len(), 1);
+        assert!(channels.read_batches().is_some());
+
+        // a missing sidecar cannot be skipped
+        channels
+            .process_l1_transactions(batch_sender, 102, 1_000, &transactions, &[])
+            .unwrap_err();
+    }
+
+    #[test]
+    fn kzg_proof() {
+        let sidecar = BlobSidecar::from_blob(&data_to_blob(&test_data(1000)).unwrap()).unwrap();
+        sidecar.verify_kzg_proof().unwrap();
+        let blob_tx = TxEssenceEip4844 {
+            blob_versioned_hashes: vec![sidecar.versioned_hash()],
+            ..Default::default()
+        };
+        let sidecars = [sidecar.clone()];
+        assert_eq!(
+            BlobSource::new(&sidecars).blobs(&blob_tx).unwrap(),
+            [sidecar.blob().unwrap()]
+        );
+
+        // the blob matches the versioned hash, but not the proof
+        let mut blob = sidecar.blob.to_vec();
+        blob[100] ^= 1;
+        let sidecars = [BlobSidecar {
+            blob: blob.into(),
+            ..sidecar.clone()
+        }];
+        assert!(sidecars[0].verify_kzg_proof().is_err());
+        assert!(BlobSource::new(&sidecars).blobs(&blob_tx).is_err());
+
+        let other = BlobSidecar::from_blob(&data_to_blob(&test_data(1001)).unwrap()).unwrap();
+        let sidecars = [BlobSidecar {
+            kzg_proof: other.kzg_proof,
+            ..sidecar
+        }];
+        assert!(BlobSource::new(&sidecars).blobs(&blob_tx).is_err());
+    }
+
+    /// Returns the batcher data of a single frame with the given number.
+    fn frame_data(channel_id: u128, number: u16) -> Vec<u8> {
+        let mut data = vec![0];
+        data.extend_from_slice(&channel_id.to_be_bytes());
+        data.extend_from_slice(&number.to_be_bytes());
+        data.extend_from_slice(&0u32.to_be_bytes());
+        data.push(0);
+        data
+    }
+
+    fn sign(key: &SigningKey, essence: EthereumTxEssence) -> Transaction<EthereumTxEssence> {
+        let (signature, recovery_id) = key
+            .sign_prehash_recoverable(essence.signing_hash().as_slice())
+            .unwrap();
+        Transaction {
+            essence,
+            signature: TxSignature {
+                v: recovery_id.is_y_odd() as u64,
+                r: U256::from_be_slice(&signature.r().to_bytes()),
+                s: U256::from_be_slice(&signature.s().to_bytes()),
+            },
+        }
+    }
+
+    #[test]
+    fn frame_order() {
+        let mut config = ChainConfig::optimism();
+        config.ecotone_time = Some(1_000);
+        let key = SigningKey::from_slice(&[1u8; 32]).unwrap();
+
+        let sidecars: Vec<_> = [(0xa, 1), (0xb, 0), (0xa, 2)]
+            .into_iter()
+            .map(|(channel_id, number)| {
+                BlobSidecar::from_blob(&data_to_blob(&frame_data(channel_id, number)).unwrap())
+                    .unwrap()
+            })
+            .collect();
+        let calldata_tx = |nonce: u64, data: Vec<u8>| {
+            sign(
+                &key,
+                EthereumTxEssence::Eip1559(TxEssenceEip1559 {
+                    nonce,
+                    to: TransactionKind::Call(config.batch_inbox),
+                    data: data.into(),
+                    ..Default::default()
+                }),
+            )
+        };
+        let blob_tx = |nonce: u64, blobs: &[usize]| {
+            sign(
+                &key,
+                EthereumTxEssence::Eip4844(TxEssenceEip4844 {
+                    nonce,
+                    to: config.batch_inbox,
+                    // the calldata of blob transactions is ignored after Ecotone
+                    data: frame_data(0xc, 0).into(),
+                    blob_versioned_hashes: blobs
+                        .iter()
+                        .map(|&i| sidecars[i].versioned_hash())
+                        .collect(),
+                    ..Default::default()
+                }),
+            )
+        };
+        let transactions = vec![
+            calldata_tx(0, frame_data(0xa, 0)),
+            // the blobs are read in the order of the versioned hashes, not the sidecars
+            blob_tx(1, &[2, 0]),
+            calldata_tx(2, frame_data(0xb, 1)),
+            blob_tx(3, &[1]),
+            // repeated data is queued again
+            calldata_tx(4, frame_data(0xa, 0)),
+        ];
+        let batch_sender = transactions[0].recover_from().unwrap();
+
+        let mut frame_queue = FrameQueue::new(&config);
+        frame_queue
+            .push(BatcherTransactions {
+                block_number: 100,
+                block_timestamp: 1_000,
+                batch_sender,
+                transactions: &transactions,
+                blob_sidecars: &sidecars,
+            })
+            .unwrap();
+        let frames: Vec<_> = std::iter::from_fn(|| frame_queue.next())
+            .flat_map(|tx_frames| tx_frames.frames)
+            .map(|frame| (frame.channel_id, frame.number))
+            .collect();
+        assert_eq!(
+            frames,
+            [(0xa, 0), (0xa, 2), (0xa, 1), (0xb, 1), (0xb, 0), (0xa, 0)]
+        );
+    }
+}
@@ -16,13 +16,20 @@ use anyhow::bail;
 #[cfg(target_os = "zkvm")]
 use risc0_zkvm::{guest::env, serde::to_vec, sha::Digest};
 use serde::{Deserialize, Serialize};
+use thiserror::Error as ThisError;
 use zeth_primitives::{
     block::Header,
     mmr,
     mmr::{MerkleMountainRange, MerkleProof},
+    BlockHash, B256,
 };
 
-use crate::optimism::{batcher::BlockId, DeriveOutput};
+#[cfg(target_os = "zkvm")]
+use crate::journal::Journal;
+use crate::{
+    light_client::{LightClientAttestation, SyncCommitteeProof},
+    optimism::{batcher::BlockId, config::ChainConfig, DeriveOutput},
+};
 
 /// Denotes a zkVM Image ID.
 pub type ImageId = [u32; 8];
@@ -80,12 +87,29 @@ pub enum ComposeInputOperation {
     /// root eth_chain_root, asserting its commitment to a continuous chain of
     /// ethereum blocks, and a composition “aggregation” proof about the derivation of
     /// a series of op-blocks from the ethereum blocks under eth_chain_root.
+    /// Optionally, the Bedrock genesis header and a commitment to the pre-Bedrock history
+    /// can be given to chain an aggregation starting at the Bedrock genesis to the legacy
+    /// chain preceding it.
+    /// Optionally, a sync committee proof of the ethereum tail block can be given, so
+    /// that the result only needs to be trusted relative to a sync committee root of the
+    /// beacon chain instead of to the ethereum tail hash.
     FINISH {
         prep: ComposeOutput,
         aggregate: ComposeOutput,
+        pre_bedrock: Option<Box<(Header, PreBedrockHistory)>>,
+        l1_light_client: Option<Box<SyncCommitteeProof>>,
     },
 }
 
+/// A commitment to the Optimism history before the Bedrock migration.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, Eq, PartialEq)]
+pub struct PreBedrockHistory {
+    /// Hash of the last legacy block, i.e. the parent of the Bedrock genesis.
+    pub bedrock_parent_hash: BlockHash,
+    /// Hash committing to the legacy chain data.
+    pub legacy_data_hash: B256,
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq)]
 pub struct ComposeOutput {
     pub block_image_id: ImageId,
@@ -105,9 +129,30 @@ pub enum ComposeOutputOperation {
         /// Whether the L1 block range has been validate as a correct Merkle commitment to
         /// a continuous chain of L1 blocks.
         eth_chain_continuity_validated: bool,
+        /// The pre-Bedrock history the Bedrock genesis `op_head` is chained to, if any.
+        pre_bedrock_history: Option<PreBedrockHistory>,
+        /// The sync committee attestation of the ethereum tail block, if any.
+        l1_attestation: Option<LightClientAttestation>,
     },
 }
 
+/// Error returned when the Op chain of two joined aggregations is not continuous, i.e.
+/// the right aggregation does not start at the block the left aggregation ends with.
+#[derive(Debug, Clone, PartialEq, Eq, ThisError)]
+#[error(
+    "Op chain gap: left segment ends at block {} ({}), right segment starts at block {} ({})",
+    left_op_tail.number,
+    left_op_tail.hash,
+    right_op_head.number,
+    right_op_head.hash
+)]
+pub struct OpChainGap {
+    /// The last Op block of the left aggregation.
+    pub left_op_tail: BlockId,
+    /// The first Op block of the right aggregation.
+    pub right_op_head: BlockId,
+}
+
 impl ComposeInput {
     pub fn process(self) -> anyhow::Result<ComposeOutput> {
         let output = match self.operation {
@@ -187,13 +232,19 @@ impl ComposeInput {
                 #[cfg(target_os = "zkvm")]
                 {
                     // Verify derivation receipt
-                    let derive_journal = to_vec(&derive_output)
+                    let derive_journal = derive_output
+                        .to_journal()
                         .expect("Failed to encode expected derivation journal");
                     env::verify(Digest::from(self.derive_image_id), &derive_journal)
                         .expect("Failed to lift derivation receipt");
                 }
                 // Verify usage of same block builder image id
                 assert_eq!(self.block_image_id, derive_output.block_image_id);
+                // Verify derivation of the chain the guests are built for
+                assert!(
+                    derive_output.rollup_config_hash.is_none(),
+                    "Cannot compose derivations using a rollup config"
+                );
                 // Verify inclusion of ethereum tail in Merkle root
                 assert!(
                     eth_tail_proof
@@ -207,11 +258,14 @@ impl ComposeInput {
                     compose_image_id: self.compose_image_id,
                     operation: ComposeOutputOperation::AGGREGATE {
                         op_head: derive_output.op_head,
+                        // a derivation without blocks only anchors its head
                         op_tail: *derive_output
                             .derived_op_blocks
                             .last()
-                            .expect("Expected at least one derived block to compose."),
+                            .unwrap_or(&derive_output.op_head),
                         eth_chain_continuity_validated: false,
+                        pre_bedrock_history: None,
+                        l1_attestation: None,
                     },
                     eth_chain_tail_block: derive_output.eth_tail,
                     eth_chain_merkle_root: self.eth_chain_merkle_root,
@@ -258,6 +312,8 @@ impl ComposeInput {
                     op_head: left_op_head,
                     op_tail: left_op_tail,
                     eth_chain_continuity_validated: left_validated,
+                    pre_bedrock_history: left_pre_bedrock_history,
+                    ..
                 } = left_compose_output.operation
                 else {
                     bail!("Unsupported! Expected ComposeOutput::AGGREGATE")
@@ -266,11 +322,21 @@ impl ComposeInput {
                     op_head: right_op_head,
                     op_tail: right_op_tail,
                     eth_chain_continuity_validated: right_validated,
+                    pre_bedrock_history: right_pre_bedrock_history,
+                    ..
                 } = right_compose_output.operation
                 else {
                     bail!("Unsupported! Expected ComposeOutput::AGGREGATE")
                 };
-                assert_eq!(&left_op_tail, &right_op_head);
+                if left_op_tail != right_op_head {
+                    return Err(OpChainGap {
+                        left_op_tail,
+                        right_op_head,
+                    }
+                    .into());
+                }
+                // The pre-Bedrock history can only precede the head of the joined chain
+                assert!(right_pre_bedrock_history.is_none());
 
                 ComposeOutput {
                     block_image_id: self.block_image_id,
@@ -280,6 +346,9 @@ impl ComposeInput {
                         op_head: left_op_head,
                         op_tail: right_op_tail,
                         eth_chain_continuity_validated: left_validated || right_validated,
+                        pre_bedrock_history: left_pre_bedrock_history,
+                        // the joined tail differs from the attested tails
+                        l1_attestation: None,
                     },
                     eth_chain_tail_block: core::cmp::max(
                         left_compose_output.eth_chain_tail_block,
@@ -288,7 +357,12 @@ impl ComposeInput {
                     eth_chain_merkle_root: self.eth_chain_merkle_root,
                 }
             }
-            ComposeInputOperation::FINISH { prep, aggregate } => {
+            ComposeInputOperation::FINISH {
+                prep,
+                aggregate,
+                pre_bedrock,
+                l1_light_client,
+            } => {
                 // Verify prep receipt
                 #[cfg(target_os = "zkvm")]
                 {
@@ -324,11 +398,40 @@ impl ComposeInput {
                     bail!("Unsupported! Expected ComposeOutput::PREP")
                 };
                 let ComposeOutputOperation::AGGREGATE {
-                    op_head, op_tail, ..
+                    op_head,
+                    op_tail,
+                    pre_bedrock_history,
+                    ..
                 } = aggregate.operation
                 else {
                     bail!("Unsupported! Expected ComposeOutput::AGGREGATE")
                 };
+                // Chain the Bedrock genesis to the pre-Bedrock history
+                let pre_bedrock_history = match pre_bedrock.map(|boxed| *boxed) {
+                    Some((bedrock_header, history)) => {
+                        let bedrock_genesis = ChainConfig::selected().bedrock_genesis;
+                        assert_eq!(
+                            op_head, bedrock_genesis,
+                            "Aggregation does not start at the Bedrock genesis"
+                        );
+                        assert_eq!(bedrock_header.hash(), bedrock_genesis.hash);
+                        assert_eq!(bedrock_header.parent_hash, history.bedrock_parent_hash);
+                        Some(history)
+                    }
+                    None => pre_bedrock_history,
+                };
+                // Attest the ethereum tail block by the beacon chain sync committee
+                let l1_attestation = match l1_light_client {
+                    Some(proof) => {
+                        let attestation = verify_light_client_proof(&proof)?;
+                        assert_eq!(
+                            attestation.execution_block_hash, prep.eth_chain_tail_block.hash,
+                            "Sync committee proof does not attest the ethereum tail"
+                        );
+                        Some(attestation)
+                    }
+                    None => None,
+                };
                 // Output new aggregate with validated chain root
                 ComposeOutput {
                     block_image_id: self.block_image_id,
@@ -338,6 +441,8 @@ impl ComposeInput {
                         op_head,
                         op_tail,
                         eth_chain_continuity_validated: true,
+                        pre_bedrock_history,
+                        l1_attestation,
                     },
                     eth_chain_tail_block: prep.eth_chain_tail_block,
                     eth_chain_merkle_root: self.eth_chain_merkle_root,
@@ -347,3 +452,89 @@ impl ComposeInput {
         Ok(output)
     }
 }
+
+#[cfg(any(not(target_os = "zkvm"), feature = "light-client"))]
+fn verify_light_client_proof(proof: &SyncCommitteeProof) -> anyhow::Result<LightClientAttestation> {
+    proof.verify(crate::light_client::BEACON_SPEC)
+}
+
+#[cfg(all(target_os = "zkvm", not(feature = "light-client")))]
+fn verify_light_client_proof(_: &SyncCommitteeProof) -> anyhow::Result<LightClientAttestation> {
+    bail!("Unsupported! The guest was built without the light-client feature")
+}
+
+#[cfg(test)]
+mod tests {
+    use zeth_primitives::b256;
+
+    use super::*;
+
+    fn aggregate(op_head: BlockId, op_tail: BlockId) -> ComposeOutput {
+        ComposeOutput {
+            block_image_id: [1; 8],
+            derive_image_id: [2; 8],
+            compose_image_id: [3; 8],
+            operation: ComposeOutputOperation::AGGREGATE {
+                op_head,
+                op_tail,
+                eth_chain_continuity_validated: false,
+                pre_bedrock_history: None,
+                l1_attestation: None,
+            },
+            eth_chain_tail_block: BlockId::default(),
+            eth_chain_merkle_root: Default::default(),
+        }
+    }
+
+    fn join(left: ComposeOutput, right: ComposeOutput) -> anyhow::Result<ComposeOutput> {
+        ComposeInput {
+            block_image_id: [1; 8],
+            derive_image_id: [2; 8],
+            compose_image_id: [3; 8],
+            operation: ComposeInputOperation::JOIN { left, right },
+            eth_chain_merkle_root: Default::default(),
+        }
+        .process()
+    }
+
+    #[test]
+    fn join_continuous() {
+        let a = BlockId {
+            hash: b256!("0000000000000000000000000000000000000000000000000000000000000001"),
+            number: 1,
+        };
+        let b = BlockId {
+            hash: b256!("0000000000000000000000000000000000000000000000000000000000000002"),
+            number: 2,
+        };
+        let c = BlockId {
+            hash: b256!("0000000000000000000000000000000000000000000000000000000000000003"),
+            number: 3,
+        };
+        let output = join(aggregate(a, b), aggregate(b, c)).unwrap();
+        assert_eq!(output.operation, aggregate(a, c).operation);
+    }
+
+    #[test]
+    fn join_gap() {
+        let a = BlockId {
+            hash: b256!("0000000000000000000000000000000000000000000000000000000000000001"),
+            number: 1,
+        };
+        let b = BlockId {
+            hash: b256!("0000000000000000000000000000000000000000000000000000000000000002"),
+            number: 2,
+        };
+        let b_prime = BlockId {
+            hash: b256!("00000000000000000000000000000000000000000000000000000000000000b2"),
+            number: 2,
+        };
+        let err = join(aggregate(a, b), aggregate(b_prime, b_prime)).unwrap_err();
+        let gap = err.downcast_ref::<OpChainGap>().unwrap();
+        assert_eq!(gap.left_op_tail, b);
+        assert_eq!(gap.right_op_head, b_prime);
+        let message = err.to_string();
+        assert!(message.contains(&b.hash.to_string()));
+        assert!(message.contains(&b_prime.hash.to_string()));
+    }
+}
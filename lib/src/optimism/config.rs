@@ -12,25 +12,56 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use revm::primitives::SpecId;
 use ruint::uint;
-use zeth_primitives::{address, Address};
+use thiserror::Error as ThisError;
+use zeth_primitives::{address, b256, Address, U256};
 
-use super::system_config::SystemConfig;
-use crate::consts::{ChainSpec, OP_MAINNET_CHAIN_SPEC};
+use super::{
+    batcher::BlockId, batcher_channel::MAX_CHANNEL_BANK_SIZE, system_config::SystemConfig,
+};
+use crate::consts::{
+    ChainSpec, Eip1559Constants, ForkCondition, BASE_MAINNET_CHAIN_SPEC,
+    ETH_MAINNET_EIP1559_CONSTANTS, OP_MAINNET_CHAIN_SPEC, OP_SEPOLIA_CHAIN_SPEC,
+};
+
+/// Addresses of the accounts and predeployed contracts of the OP Stack. Chains that
+/// customized them set different addresses in their [ChainConfig].
+pub mod predeploys {
+    use zeth_primitives::{address, Address};
+
+    /// The depositor account of the L1 attributes deposited transaction.
+    pub const L1_ATTRIBUTES_DEPOSITOR: Address =
+        address!("deaddeaddeaddeaddeaddeaddeaddeaddead0001");
+    /// The L1Block predeploy storing the L1 attributes.
+    pub const L1_BLOCK: Address = address!("4200000000000000000000000000000000000015");
+    /// The SequencerFeeVault predeploy accumulating the transaction priority fees.
+    pub const SEQUENCER_FEE_VAULT: Address = address!("4200000000000000000000000000000000000011");
+    /// The L2ToL1MessagePasser predeploy, whose storage root is committed to in the
+    /// output root.
+    pub const L2_TO_L1_MESSAGE_PASSER: Address =
+        address!("4200000000000000000000000000000000000016");
+}
 
 /// A Chain derivation configuration
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ChainConfig {
     /// The initial system config value
     pub system_config: SystemConfig,
     // The chain specification
     pub chain_spec: &'static ChainSpec,
+    /// The EIP-1559 constants of the L1 chain, used to verify the base fees of the L1
+    /// blocks
+    pub l1_eip1559_constants: Eip1559Constants,
     /// The L1 attributes depositor address
     pub l1_attributes_depositor: Address,
     /// The L1 attributes contract
     pub l1_attributes_contract: Address,
     /// The L2 address accumulating any transaction priority fee
     pub sequencer_fee_vault: Address,
+    /// The L2 contract storing the withdrawals, whose storage root is part of the output
+    /// root
+    pub message_passer: Address,
     /// The batch inbox address
     pub batch_inbox: Address,
     /// The deposit contract address
@@ -47,6 +78,24 @@ pub struct ChainConfig {
     pub max_seq_drift: u64,
     /// Network blocktime
     pub blocktime: u64,
+    /// The first block after the Bedrock migration
+    pub bedrock_genesis: BlockId,
+    /// The timestamp of the first block after the Bedrock migration
+    pub l2_genesis_time: u64,
+    /// The timestamp of the Canyon activation, changing the channel bank ordering, if
+    /// scheduled; it always matches the Canyon fork of the chain specification
+    pub canyon_time: Option<u64>,
+    /// The timestamp of the Delta activation, enabling span batches, if scheduled
+    pub delta_time: Option<u64>,
+    /// The timestamp of the Ecotone activation, moving the batcher data into blobs, if
+    /// scheduled
+    pub ecotone_time: Option<u64>,
+    /// Whether the chain has no batcher yet, i.e. all blocks only contain deposits and
+    /// are derived once the sequencing window of their epoch has expired
+    pub deposits_only: bool,
+    /// The key signing the data of every channel, if the chain authenticates its batcher
+    /// data; channels without a valid signature are then rejected
+    pub batcher_data_signer: Option<Address>,
 }
 
 impl ChainConfig {
@@ -61,17 +110,831 @@ impl ChainConfig {
                 unsafe_block_signer: address!("AAAA45d9549EDA09E70937013520214382Ffc4A2"),
             },
             chain_spec: &OP_MAINNET_CHAIN_SPEC,
-            l1_attributes_depositor: address!("deaddeaddeaddeaddeaddeaddeaddeaddead0001"),
-            l1_attributes_contract: address!("4200000000000000000000000000000000000015"),
-            sequencer_fee_vault: address!("4200000000000000000000000000000000000011"),
+            l1_eip1559_constants: ETH_MAINNET_EIP1559_CONSTANTS,
+            l1_attributes_depositor: predeploys::L1_ATTRIBUTES_DEPOSITOR,
+            l1_attributes_contract: predeploys::L1_BLOCK,
+            sequencer_fee_vault: predeploys::SEQUENCER_FEE_VAULT,
+            message_passer: predeploys::L2_TO_L1_MESSAGE_PASSER,
             batch_inbox: address!("ff00000000000000000000000000000000000010"),
             deposit_contract: address!("bEb5Fc579115071764c7423A4f12eDde41f106Ed"),
             system_config_contract: address!("229047fed2591dbec1eF1118d64F7aF3dB9EB290"),
-            max_channel_bank_size: 100_000_000,
+            max_channel_bank_size: MAX_CHANNEL_BANK_SIZE,
+            channel_timeout: 300,
+            seq_window_size: 3600,
+            max_seq_drift: 600,
+            blocktime: 2,
+            bedrock_genesis: BlockId {
+                hash: b256!("dbf6a80fef073de06add9b0d14026d6e5a86c85f6d102c36d3d8e9cf89c2afd3"),
+                number: 105235063,
+            },
+            l2_genesis_time: 1686068903,
+            // Canyon is activated 2024-01-11 at 17:00:01 UTC
+            canyon_time: Some(1704992401),
+            // Delta is activated 2024-02-22 at 00:00:00 UTC
+            delta_time: Some(1708560000),
+            // Ecotone is activated 2024-03-14 at 00:00:01 UTC
+            ecotone_time: Some(1710374401),
+            deposits_only: false,
+            batcher_data_signer: None,
+        }
+    }
+
+    /// Creates the Base mainnet chain configuration.
+    pub fn base() -> Self {
+        Self {
+            system_config: SystemConfig {
+                batch_sender: address!("5050F69a9786F081509234F1a7F4684b5E5b76C9"),
+                gas_limit: uint!(30_000_000_U256),
+                l1_fee_overhead: uint!(188_U256),
+                l1_fee_scalar: uint!(684000_U256),
+                unsafe_block_signer: address!("Af6E19BE0F9cE7f8afd49a1824851023A8249e8a"),
+            },
+            chain_spec: &BASE_MAINNET_CHAIN_SPEC,
+            l1_eip1559_constants: ETH_MAINNET_EIP1559_CONSTANTS,
+            l1_attributes_depositor: predeploys::L1_ATTRIBUTES_DEPOSITOR,
+            l1_attributes_contract: predeploys::L1_BLOCK,
+            sequencer_fee_vault: predeploys::SEQUENCER_FEE_VAULT,
+            message_passer: predeploys::L2_TO_L1_MESSAGE_PASSER,
+            batch_inbox: address!("ff00000000000000000000000000000000008453"),
+            deposit_contract: address!("49048044D57e1C92A77f79988d21Fa8fAF74E97e"),
+            system_config_contract: address!("73a79Fab69143498Ed3712e519A88a918e1f4072"),
+            max_channel_bank_size: MAX_CHANNEL_BANK_SIZE,
+            channel_timeout: 300,
+            seq_window_size: 3600,
+            max_seq_drift: 600,
+            blocktime: 2,
+            // Base started with Bedrock, so its genesis is the first Bedrock block
+            bedrock_genesis: BlockId {
+                hash: b256!("f712aa9241cc24369b143cf6dce85f0902a9731e70d66818a3a5845b296c73dd"),
+                number: 0,
+            },
+            l2_genesis_time: 1686789347,
+            // Canyon is activated 2024-01-11 at 17:00:01 UTC
+            canyon_time: Some(1704992401),
+            // Delta is activated 2024-02-22 at 00:00:00 UTC
+            delta_time: Some(1708560000),
+            // Ecotone is activated 2024-03-14 at 00:00:01 UTC
+            ecotone_time: Some(1710374401),
+            deposits_only: false,
+            batcher_data_signer: None,
+        }
+    }
+
+    /// Creates the OP Sepolia testnet chain configuration.
+    pub fn op_sepolia() -> Self {
+        Self {
+            system_config: SystemConfig {
+                batch_sender: address!("8F23BB38F531600e5d8FDDaAEC41F13FaB46E98c"),
+                gas_limit: uint!(30_000_000_U256),
+                l1_fee_overhead: uint!(188_U256),
+                l1_fee_scalar: uint!(684000_U256),
+                unsafe_block_signer: address!("57CACBB0d30b01eb2462e5dC940c161aff3230D3"),
+            },
+            chain_spec: &OP_SEPOLIA_CHAIN_SPEC,
+            l1_eip1559_constants: ETH_MAINNET_EIP1559_CONSTANTS,
+            l1_attributes_depositor: predeploys::L1_ATTRIBUTES_DEPOSITOR,
+            l1_attributes_contract: predeploys::L1_BLOCK,
+            sequencer_fee_vault: predeploys::SEQUENCER_FEE_VAULT,
+            message_passer: predeploys::L2_TO_L1_MESSAGE_PASSER,
+            batch_inbox: address!("ff00000000000000000000000000000011155420"),
+            deposit_contract: address!("16Fc5058F25648194471939df75CF27A2fdC48BC"),
+            system_config_contract: address!("034edD2A225f7f429A63E0f1D2084B9E0A93b538"),
+            max_channel_bank_size: MAX_CHANNEL_BANK_SIZE,
             channel_timeout: 300,
             seq_window_size: 3600,
             max_seq_drift: 600,
             blocktime: 2,
+            // OP Sepolia started with Bedrock, so its genesis is the first Bedrock block
+            bedrock_genesis: BlockId {
+                hash: b256!("102de6ffb001480cc9b8b548fd05c34cd4f46ae4aa91759393db90ea0409887d"),
+                number: 0,
+            },
+            l2_genesis_time: 1691802540,
+            // Canyon is activated 2023-11-14 at 17:00:00 UTC
+            canyon_time: Some(1699981200),
+            // Delta is activated 2023-12-22 at 00:00:00 UTC
+            delta_time: Some(1703203200),
+            // Ecotone is activated 2024-02-21 at 17:00:00 UTC
+            ecotone_time: Some(1708534800),
+            deposits_only: false,
+            batcher_data_signer: None,
         }
     }
+
+    /// Creates the configuration of the chain the guests are built for: OP Sepolia with
+    /// the `sepolia` feature, OP mainnet otherwise.
+    pub fn selected() -> Self {
+        if cfg!(feature = "sepolia") {
+            Self::op_sepolia()
+        } else {
+            Self::optimism()
+        }
+    }
+}
+
+/// Error returned by the [ChainConfigBuilder].
+#[derive(Debug, PartialEq, Eq, ThisError)]
+pub enum ChainConfigError {
+    /// Returned when a required value was not set.
+    #[error("missing value for {0}")]
+    Missing(&'static str),
+    /// Returned when an address is not a valid hex or EIP-55 checksummed address.
+    #[error("invalid address for {field} '{value}': {reason}")]
+    InvalidAddress {
+        field: &'static str,
+        value: String,
+        reason: String,
+    },
+    /// Returned when an address is the zero address.
+    #[error("{0} must not be the zero address")]
+    ZeroAddress(&'static str),
+    /// Returned when a size, timeout or interval is zero.
+    #[error("{0} must be greater than zero")]
+    Zero(&'static str),
+    /// Returned when a fork activates before the fork preceding it.
+    #[error("fork {fork:?} ({condition:?}) cannot activate before {previous:?} ({previous_condition:?})")]
+    ForkOrder {
+        fork: SpecId,
+        condition: ForkCondition,
+        previous: SpecId,
+        previous_condition: ForkCondition,
+    },
+    /// Returned when the Bedrock genesis does not match the chain specification.
+    #[error("inconsistent Bedrock genesis: {0}")]
+    Genesis(&'static str),
+    /// Returned when an OP Stack fork is not activated by timestamp.
+    #[error("fork {fork:?} must activate by timestamp, got {condition:?}")]
+    ForkCondition {
+        fork: SpecId,
+        condition: ForkCondition,
+    },
+}
+
+/// Builder for a custom [ChainConfig] that validates the configuration.
+#[derive(Debug, Clone, Default)]
+pub struct ChainConfigBuilder {
+    chain_spec: Option<&'static ChainSpec>,
+    l1_eip1559_constants: Option<Eip1559Constants>,
+    batch_sender: Option<String>,
+    unsafe_block_signer: Option<String>,
+    gas_limit: Option<U256>,
+    l1_fee_overhead: U256,
+    l1_fee_scalar: U256,
+    l1_attributes_depositor: Option<String>,
+    l1_attributes_contract: Option<String>,
+    sequencer_fee_vault: Option<String>,
+    message_passer: Option<String>,
+    batch_inbox: Option<String>,
+    deposit_contract: Option<String>,
+    system_config_contract: Option<String>,
+    max_channel_bank_size: Option<u64>,
+    channel_timeout: Option<u64>,
+    seq_window_size: Option<u64>,
+    max_seq_drift: Option<u64>,
+    blocktime: Option<u64>,
+    bedrock_genesis: Option<BlockId>,
+    l2_genesis_time: Option<u64>,
+    delta_time: Option<u64>,
+    ecotone_time: Option<u64>,
+    deposits_only: bool,
+    batcher_data_signer: Option<String>,
+}
+
+impl ChainConfig {
+    /// Returns whether Canyon is active at the given timestamp.
+    pub fn is_canyon(&self, timestamp: u64) -> bool {
+        matches!(self.canyon_time, Some(canyon_time) if timestamp >= canyon_time)
+    }
+
+    /// Returns whether Delta is active at the given timestamp.
+    pub fn is_delta(&self, timestamp: u64) -> bool {
+        matches!(self.delta_time, Some(delta_time) if timestamp >= delta_time)
+    }
+
+    /// Returns whether Ecotone is active at the given timestamp.
+    pub fn is_ecotone(&self, timestamp: u64) -> bool {
+        matches!(self.ecotone_time, Some(ecotone_time) if timestamp >= ecotone_time)
+    }
+
+    /// Returns whether the block with the given timestamp is the first block with Canyon
+    /// active, which deploys the Create2Deployer contract outside of any transaction.
+    pub fn is_canyon_activation_block(&self, timestamp: u64) -> bool {
+        self.is_canyon(timestamp) && !self.is_canyon(timestamp.saturating_sub(self.blocktime))
+    }
+
+    /// Returns whether the block with the given timestamp is the first block with Ecotone
+    /// active, which still uses the Bedrock L1 attributes and contains the network
+    /// upgrade transactions.
+    pub fn is_ecotone_activation_block(&self, timestamp: u64) -> bool {
+        self.is_ecotone(timestamp) && !self.is_ecotone(timestamp.saturating_sub(self.blocktime))
+    }
+
+    /// Returns a builder for a custom chain configuration.
+    pub fn builder() -> ChainConfigBuilder {
+        ChainConfigBuilder::default()
+    }
+}
+
+impl ChainConfigBuilder {
+    /// Sets the chain specification.
+    pub fn chain_spec(mut self, chain_spec: &'static ChainSpec) -> Self {
+        self.chain_spec = Some(chain_spec);
+        self
+    }
+
+    /// Sets the EIP-1559 constants of the L1 chain, defaulting to the ones of the
+    /// Ethereum mainnet.
+    pub fn l1_eip1559_constants(mut self, constants: Eip1559Constants) -> Self {
+        self.l1_eip1559_constants = Some(constants);
+        self
+    }
+
+    /// Sets the initial batch sender address.
+    pub fn batch_sender(mut self, address: &str) -> Self {
+        self.batch_sender = Some(address.to_string());
+        self
+    }
+
+    /// Sets the initial signer of unsafe blocks. Without a signer, no unsafe payload is
+    /// accepted.
+    pub fn unsafe_block_signer(mut self, address: &str) -> Self {
+        self.unsafe_block_signer = Some(address.to_string());
+        self
+    }
+
+    /// Sets the initial L2 gas limit.
+    pub fn gas_limit(mut self, gas_limit: U256) -> Self {
+        self.gas_limit = Some(gas_limit);
+        self
+    }
+
+    /// Sets the initial fee overhead and scalar.
+    pub fn l1_fee(mut self, overhead: U256, scalar: U256) -> Self {
+        self.l1_fee_overhead = overhead;
+        self.l1_fee_scalar = scalar;
+        self
+    }
+
+    /// Sets the L1 attributes depositor address.
+    pub fn l1_attributes_depositor(mut self, address: &str) -> Self {
+        self.l1_attributes_depositor = Some(address.to_string());
+        self
+    }
+
+    /// Sets the L1 attributes contract address.
+    pub fn l1_attributes_contract(mut self, address: &str) -> Self {
+        self.l1_attributes_contract = Some(address.to_string());
+        self
+    }
+
+    /// Sets the address of the sequencer fee vault.
+    pub fn sequencer_fee_vault(mut self, address: &str) -> Self {
+        self.sequencer_fee_vault = Some(address.to_string());
+        self
+    }
+
+    /// Sets the address of the message passer, defaulting to the standard
+    /// L2ToL1MessagePasser predeploy.
+    pub fn message_passer(mut self, address: &str) -> Self {
+        self.message_passer = Some(address.to_string());
+        self
+    }
+
+    /// Sets the batch inbox address.
+    pub fn batch_inbox(mut self, address: &str) -> Self {
+        self.batch_inbox = Some(address.to_string());
+        self
+    }
+
+    /// Sets the deposit contract address.
+    pub fn deposit_contract(mut self, address: &str) -> Self {
+        self.deposit_contract = Some(address.to_string());
+        self
+    }
+
+    /// Sets the address of the L1 system config contract.
+    pub fn system_config_contract(mut self, address: &str) -> Self {
+        self.system_config_contract = Some(address.to_string());
+        self
+    }
+
+    /// Sets the maximum byte size of all pending channels.
+    pub fn max_channel_bank_size(mut self, size: u64) -> Self {
+        self.max_channel_bank_size = Some(size);
+        self
+    }
+
+    /// Sets the channel timeout in L1 blocks.
+    pub fn channel_timeout(mut self, timeout: u64) -> Self {
+        self.channel_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the number of L1 blocks in a sequence window.
+    pub fn seq_window_size(mut self, size: u64) -> Self {
+        self.seq_window_size = Some(size);
+        self
+    }
+
+    /// Sets the maximum timestamp drift.
+    pub fn max_seq_drift(mut self, drift: u64) -> Self {
+        self.max_seq_drift = Some(drift);
+        self
+    }
+
+    /// Sets the network blocktime.
+    pub fn blocktime(mut self, blocktime: u64) -> Self {
+        self.blocktime = Some(blocktime);
+        self
+    }
+
+    /// Sets the first block after the Bedrock migration.
+    pub fn bedrock_genesis(mut self, bedrock_genesis: BlockId) -> Self {
+        self.bedrock_genesis = Some(bedrock_genesis);
+        self
+    }
+
+    /// Sets the timestamp of the first block after the Bedrock migration.
+    pub fn l2_genesis_time(mut self, timestamp: u64) -> Self {
+        self.l2_genesis_time = Some(timestamp);
+        self
+    }
+
+    /// Sets the timestamp of the Delta activation, enabling span batches.
+    pub fn delta_time(mut self, timestamp: u64) -> Self {
+        self.delta_time = Some(timestamp);
+        self
+    }
+
+    /// Sets the timestamp of the Ecotone activation, moving the batcher data into blobs.
+    pub fn ecotone_time(mut self, timestamp: u64) -> Self {
+        self.ecotone_time = Some(timestamp);
+        self
+    }
+
+    /// Sets whether the chain has no batcher yet. The batch sender is then optional.
+    pub fn deposits_only(mut self, deposits_only: bool) -> Self {
+        self.deposits_only = deposits_only;
+        self
+    }
+
+    /// Sets the key signing the channel data, enabling the verification of the batcher
+    /// data signatures.
+    pub fn batcher_data_signer(mut self, address: &str) -> Self {
+        self.batcher_data_signer = Some(address.to_string());
+        self
+    }
+
+    /// Validates the values and builds the chain configuration.
+    pub fn build(self) -> Result<ChainConfig, ChainConfigError> {
+        let chain_spec = self
+            .chain_spec
+            .ok_or(ChainConfigError::Missing("chain_spec"))?;
+        validate_fork_order(chain_spec)?;
+
+        let gas_limit = self
+            .gas_limit
+            .ok_or(ChainConfigError::Missing("gas_limit"))?;
+        if gas_limit == U256::ZERO {
+            return Err(ChainConfigError::Zero("gas_limit"));
+        }
+
+        let bedrock_genesis = self
+            .bedrock_genesis
+            .ok_or(ChainConfigError::Missing("bedrock_genesis"))?;
+        if bedrock_genesis.hash.is_zero() {
+            return Err(ChainConfigError::Genesis("missing block hash"));
+        }
+        match chain_spec.hard_forks().get(&SpecId::BEDROCK) {
+            None | Some(ForkCondition::TBD) => {
+                return Err(ChainConfigError::Genesis(
+                    "the chain specification does not activate Bedrock",
+                ))
+            }
+            Some(_) => {}
+        }
+        if chain_spec.gas_constants(SpecId::BEDROCK).is_none() {
+            return Err(ChainConfigError::Genesis(
+                "the chain specification has no EIP-1559 constants for Bedrock",
+            ));
+        }
+
+        let canyon_time = match chain_spec.hard_forks().get(&SpecId::CANYON) {
+            None | Some(ForkCondition::TBD) => None,
+            Some(ForkCondition::Timestamp(timestamp)) => Some(*timestamp),
+            Some(condition) => {
+                return Err(ChainConfigError::ForkCondition {
+                    fork: SpecId::CANYON,
+                    condition: condition.clone(),
+                })
+            }
+        };
+
+        let batch_sender = match (self.deposits_only, self.batch_sender) {
+            (true, None) => Address::ZERO,
+            (true, Some(value)) => parse_hex_address("batch_sender", value)?,
+            (false, value) => parse_address("batch_sender", value)?,
+        };
+
+        Ok(ChainConfig {
+            system_config: SystemConfig {
+                batch_sender,
+                gas_limit,
+                l1_fee_overhead: self.l1_fee_overhead,
+                l1_fee_scalar: self.l1_fee_scalar,
+                unsafe_block_signer: self
+                    .unsafe_block_signer
+                    .map(|value| parse_address("unsafe_block_signer", Some(value)))
+                    .transpose()?
+                    .unwrap_or_default(),
+            },
+            chain_spec,
+            l1_eip1559_constants: self
+                .l1_eip1559_constants
+                .unwrap_or(ETH_MAINNET_EIP1559_CONSTANTS),
+            l1_attributes_depositor: parse_address(
+                "l1_attributes_depositor",
+                self.l1_attributes_depositor,
+            )?,
+            l1_attributes_contract: parse_address(
+                "l1_attributes_contract",
+                self.l1_attributes_contract,
+            )?,
+            sequencer_fee_vault: parse_address("sequencer_fee_vault", self.sequencer_fee_vault)?,
+            message_passer: self
+                .message_passer
+                .map(|value| parse_address("message_passer", Some(value)))
+                .transpose()?
+                .unwrap_or(predeploys::L2_TO_L1_MESSAGE_PASSER),
+            batch_inbox: parse_address("batch_inbox", self.batch_inbox)?,
+            deposit_contract: parse_address("deposit_contract", self.deposit_contract)?,
+            system_config_contract: parse_address(
+                "system_config_contract",
+                self.system_config_contract,
+            )?,
+            max_channel_bank_size: non_zero("max_channel_bank_size", self.max_channel_bank_size)?,
+            channel_timeout: non_zero("channel_timeout", self.channel_timeout)?,
+            seq_window_size: non_zero("seq_window_size", self.seq_window_size)?,
+            max_seq_drift: non_zero("max_seq_drift", self.max_seq_drift)?,
+            blocktime: non_zero("blocktime", self.blocktime)?,
+            bedrock_genesis,
+            l2_genesis_time: self
+                .l2_genesis_time
+                .ok_or(ChainConfigError::Missing("l2_genesis_time"))?,
+            canyon_time,
+            delta_time: self.delta_time,
+            ecotone_time: self.ecotone_time,
+            deposits_only: self.deposits_only,
+            batcher_data_signer: self
+                .batcher_data_signer
+                .map(|value| parse_address("batcher_data_signer", Some(value)))
+                .transpose()?,
+        })
+    }
+}
+
+/// Parses a non-zero address, requiring a valid EIP-55 checksum for mixed-case input.
+fn parse_address(field: &'static str, value: Option<String>) -> Result<Address, ChainConfigError> {
+    let address = parse_hex_address(field, value.ok_or(ChainConfigError::Missing(field))?)?;
+    if address.is_zero() {
+        return Err(ChainConfigError::ZeroAddress(field));
+    }
+
+    Ok(address)
+}
+
+/// Parses an address, requiring a valid EIP-55 checksum for mixed-case input.
+fn parse_hex_address(field: &'static str, value: String) -> Result<Address, ChainConfigError> {
+    let hex = value.strip_prefix("0x").unwrap_or(&value);
+    let mixed_case =
+        hex.chars().any(|c| c.is_ascii_lowercase()) && hex.chars().any(|c| c.is_ascii_uppercase());
+    let result = if mixed_case {
+        Address::parse_checksummed(&value, None).map_err(|err| err.to_string())
+    } else {
+        value.parse::<Address>().map_err(|err| err.to_string())
+    };
+    result.map_err(|reason| ChainConfigError::InvalidAddress {
+        field,
+        value: value.clone(),
+        reason,
+    })
+}
+
+fn non_zero(field: &'static str, value: Option<u64>) -> Result<u64, ChainConfigError> {
+    match value.ok_or(ChainConfigError::Missing(field))? {
+        0 => Err(ChainConfigError::Zero(field)),
+        value => Ok(value),
+    }
+}
+
+/// Ensures that no fork activates before the fork preceding it.
+fn validate_fork_order(chain_spec: &ChainSpec) -> Result<(), ChainConfigError> {
+    let forks: Vec<_> = chain_spec.hard_forks().iter().collect();
+    for pair in forks.windows(2) {
+        let (previous, previous_condition) = pair[0];
+        let (fork, condition) = pair[1];
+        let ordered = match (previous_condition, condition) {
+            (_, ForkCondition::TBD) => true,
+            (ForkCondition::TBD, _) => false,
+            (ForkCondition::Block(previous), ForkCondition::Block(block)) => block >= previous,
+            (ForkCondition::Timestamp(previous), ForkCondition::Timestamp(ts)) => ts >= previous,
+            // forks switched from block numbers to timestamps with Shanghai
+            (ForkCondition::Block(_), ForkCondition::Timestamp(_)) => true,
+            (ForkCondition::Timestamp(_), ForkCondition::Block(_)) => false,
+        };
+        if !ordered {
+            return Err(ChainConfigError::ForkOrder {
+                fork: *fork,
+                condition: condition.clone(),
+                previous: *previous,
+                previous_condition: previous_condition.clone(),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    fn optimism_builder() -> ChainConfigBuilder {
+        ChainConfig::builder()
+            .chain_spec(&OP_MAINNET_CHAIN_SPEC)
+            .batch_sender("0x6887246668a3b87f54deb3b94ba47a6f63f32985")
+            .unsafe_block_signer("0xAAAA45d9549EDA09E70937013520214382Ffc4A2")
+            .gas_limit(uint!(30_000_000_U256))
+            .l1_fee(uint!(188_U256), uint!(684000_U256))
+            .l1_attributes_depositor("0xdeaddeaddeaddeaddeaddeaddeaddeaddead0001")
+            .l1_attributes_contract("0x4200000000000000000000000000000000000015")
+            .sequencer_fee_vault("0x4200000000000000000000000000000000000011")
+            .batch_inbox("0xff00000000000000000000000000000000000010")
+            .deposit_contract("0xbEb5Fc579115071764c7423A4f12eDde41f106Ed")
+            .system_config_contract("0x229047fed2591dbec1eF1118d64F7aF3dB9EB290")
+            .max_channel_bank_size(MAX_CHANNEL_BANK_SIZE)
+            .channel_timeout(300)
+            .seq_window_size(3600)
+            .max_seq_drift(600)
+            .blocktime(2)
+            .bedrock_genesis(ChainConfig::optimism().bedrock_genesis)
+            .l2_genesis_time(1686068903)
+            .delta_time(1708560000)
+            .ecotone_time(1710374401)
+    }
+
+    #[test]
+    fn build_optimism() {
+        let config = optimism_builder().build().unwrap();
+        assert_eq!(
+            format!("{:?}", config),
+            format!("{:?}", ChainConfig::optimism())
+        );
+    }
+
+    #[test]
+    fn build_base() {
+        let config = ChainConfig::builder()
+            .chain_spec(&BASE_MAINNET_CHAIN_SPEC)
+            .batch_sender("0x5050F69a9786F081509234F1a7F4684b5E5b76C9")
+            .unsafe_block_signer("0xAf6E19BE0F9cE7f8afd49a1824851023A8249e8a")
+            .gas_limit(uint!(30_000_000_U256))
+            .l1_fee(uint!(188_U256), uint!(684000_U256))
+            .l1_attributes_depositor("0xdeaddeaddeaddeaddeaddeaddeaddeaddead0001")
+            .l1_attributes_contract("0x4200000000000000000000000000000000000015")
+            .sequencer_fee_vault("0x4200000000000000000000000000000000000011")
+            .batch_inbox("0xff00000000000000000000000000000000008453")
+            .deposit_contract("0x49048044D57e1C92A77f79988d21Fa8fAF74E97e")
+            .system_config_contract("0x73a79Fab69143498Ed3712e519A88a918e1f4072")
+            .max_channel_bank_size(MAX_CHANNEL_BANK_SIZE)
+            .channel_timeout(300)
+            .seq_window_size(3600)
+            .max_seq_drift(600)
+            .blocktime(2)
+            .bedrock_genesis(ChainConfig::base().bedrock_genesis)
+            .l2_genesis_time(1686789347)
+            .delta_time(1708560000)
+            .ecotone_time(1710374401)
+            .build()
+            .unwrap();
+        assert_eq!(
+            format!("{:?}", config),
+            format!("{:?}", ChainConfig::base())
+        );
+    }
+
+    #[test]
+    fn build_op_sepolia() {
+        let config = ChainConfig::builder()
+            .chain_spec(&OP_SEPOLIA_CHAIN_SPEC)
+            .batch_sender("0x8F23BB38F531600e5d8FDDaAEC41F13FaB46E98c")
+            .unsafe_block_signer("0x57CACBB0d30b01eb2462e5dC940c161aff3230D3")
+            .gas_limit(uint!(30_000_000_U256))
+            .l1_fee(uint!(188_U256), uint!(684000_U256))
+            .l1_attributes_depositor("0xdeaddeaddeaddeaddeaddeaddeaddeaddead0001")
+            .l1_attributes_contract("0x4200000000000000000000000000000000000015")
+            .sequencer_fee_vault("0x4200000000000000000000000000000000000011")
+            .batch_inbox("0xff00000000000000000000000000000011155420")
+            .deposit_contract("0x16Fc5058F25648194471939df75CF27A2fdC48BC")
+            .system_config_contract("0x034edD2A225f7f429A63E0f1D2084B9E0A93b538")
+            .max_channel_bank_size(MAX_CHANNEL_BANK_SIZE)
+            .channel_timeout(300)
+            .seq_window_size(3600)
+            .max_seq_drift(600)
+            .blocktime(2)
+            .bedrock_genesis(ChainConfig::op_sepolia().bedrock_genesis)
+            .l2_genesis_time(1691802540)
+            .delta_time(1703203200)
+            .ecotone_time(1708534800)
+            .build()
+            .unwrap();
+        assert_eq!(
+            format!("{:?}", config),
+            format!("{:?}", ChainConfig::op_sepolia())
+        );
+    }
+
+    #[test]
+    fn invalid_values() {
+        let err = optimism_builder()
+            .batch_inbox("0xff000000000000000000000000000000000000")
+            .build()
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            ChainConfigError::InvalidAddress {
+                field: "batch_inbox",
+                ..
+            }
+        ));
+
+        // wrong EIP-55 checksum
+        let err = optimism_builder()
+            .deposit_contract("0xBEb5Fc579115071764c7423A4f12eDde41f106Ed")
+            .build()
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            ChainConfigError::InvalidAddress {
+                field: "deposit_contract",
+                ..
+            }
+        ));
+
+        let err = optimism_builder()
+            .sequencer_fee_vault("0x0000000000000000000000000000000000000000")
+            .build()
+            .unwrap_err();
+        assert_eq!(err, ChainConfigError::ZeroAddress("sequencer_fee_vault"));
+
+        let err = optimism_builder().seq_window_size(0).build().unwrap_err();
+        assert_eq!(err, ChainConfigError::Zero("seq_window_size"));
+
+        let err = ChainConfigBuilder {
+            blocktime: None,
+            ..optimism_builder()
+        }
+        .build()
+        .unwrap_err();
+        assert_eq!(err, ChainConfigError::Missing("blocktime"));
+    }
+
+    #[test]
+    fn deposits_only() {
+        let err = ChainConfigBuilder {
+            batch_sender: None,
+            ..optimism_builder()
+        }
+        .build()
+        .unwrap_err();
+        assert_eq!(err, ChainConfigError::Missing("batch_sender"));
+
+        let config = ChainConfigBuilder {
+            batch_sender: None,
+            ..optimism_builder()
+        }
+        .deposits_only(true)
+        .build()
+        .unwrap();
+        assert!(config.deposits_only);
+        assert!(config.system_config.batch_sender.is_zero());
+    }
+
+    #[test]
+    fn message_passer() {
+        let config = optimism_builder()
+            .message_passer("0x4200000000000000000000000000000000000042")
+            .build()
+            .unwrap();
+        assert_eq!(
+            config.message_passer,
+            address!("4200000000000000000000000000000000000042")
+        );
+
+        let err = optimism_builder()
+            .message_passer("0x0000000000000000000000000000000000000000")
+            .build()
+            .unwrap_err();
+        assert_eq!(err, ChainConfigError::ZeroAddress("message_passer"));
+    }
+
+    #[test]
+    fn batcher_data_signer() {
+        let config = optimism_builder()
+            .batcher_data_signer("0x6887246668a3b87f54deb3b94ba47a6f63f32985")
+            .build()
+            .unwrap();
+        assert_eq!(
+            config.batcher_data_signer,
+            Some(address!("6887246668a3b87f54deb3b94ba47a6f63f32985"))
+        );
+
+        let err = optimism_builder()
+            .batcher_data_signer("0x0000000000000000000000000000000000000000")
+            .build()
+            .unwrap_err();
+        assert_eq!(err, ChainConfigError::ZeroAddress("batcher_data_signer"));
+    }
+
+    #[test]
+    fn fork_order() {
+        let chain_spec: ChainSpec = serde_json::from_value(serde_json::json!({
+            "chain_id": 10,
+            "max_spec_id": "CANYON",
+            "hard_forks": {
+                "BEDROCK": { "Timestamp": 1679079600 },
+                "REGOLITH": { "Timestamp": 1679079599 },
+            },
+            "gas_constants": {},
+        }))
+        .unwrap();
+        let err = optimism_builder()
+            .chain_spec(Box::leak(Box::new(chain_spec)))
+            .build()
+            .unwrap_err();
+        assert_eq!(
+            err,
+            ChainConfigError::ForkOrder {
+                fork: SpecId::REGOLITH,
+                condition: ForkCondition::Timestamp(1679079599),
+                previous: SpecId::BEDROCK,
+                previous_condition: ForkCondition::Timestamp(1679079600),
+            }
+        );
+    }
+
+    #[test]
+    fn canyon_time() {
+        let config = optimism_builder().build().unwrap();
+        assert!(!config.is_canyon(1704992400));
+        assert!(config.is_canyon(1704992401));
+
+        let chain_spec = ChainSpec::new(
+            10,
+            SpecId::CANYON,
+            BTreeMap::from([
+                (SpecId::BEDROCK, ForkCondition::Block(105235063)),
+                (SpecId::CANYON, ForkCondition::Block(114696812)),
+            ]),
+            BTreeMap::from([(
+                SpecId::BEDROCK,
+                *OP_MAINNET_CHAIN_SPEC
+                    .gas_constants(SpecId::BEDROCK)
+                    .unwrap(),
+            )]),
+        );
+        let err = optimism_builder()
+            .chain_spec(Box::leak(Box::new(chain_spec)))
+            .build()
+            .unwrap_err();
+        assert_eq!(
+            err,
+            ChainConfigError::ForkCondition {
+                fork: SpecId::CANYON,
+                condition: ForkCondition::Block(114696812),
+            }
+        );
+    }
+
+    #[test]
+    fn genesis() {
+        let chain_spec = ChainSpec::new_single(
+            10,
+            SpecId::CANYON,
+            *OP_MAINNET_CHAIN_SPEC.gas_constants(SpecId::CANYON).unwrap(),
+        );
+        let err = optimism_builder()
+            .chain_spec(Box::leak(Box::new(chain_spec)))
+            .build()
+            .unwrap_err();
+        assert!(matches!(err, ChainConfigError::Genesis(_)));
+
+        let err = optimism_builder()
+            .bedrock_genesis(BlockId::default())
+            .build()
+            .unwrap_err();
+        assert!(matches!(err, ChainConfigError::Genesis(_)));
+    }
 }
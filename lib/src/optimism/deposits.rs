@@ -14,9 +14,10 @@
 
 use alloy_sol_types::{sol_data, SolType};
 use anyhow::{ensure, Context};
+use thiserror::Error as ThisError;
 use zeth_primitives::{
     fixed_bytes, keccak256,
-    receipt::Log,
+    receipt::{first_log_indices, Log},
     transactions::{
         ethereum::{EthereumTxEssence, TransactionKind},
         optimism::{OptimismTxEssence, TxEssenceOptimismDeposited},
@@ -29,7 +30,7 @@ use super::{batcher_db::BlockInput, config::ChainConfig};
 
 /// Signature of the deposit transaction event, i.e.
 /// keccak-256 hash of "TransactionDeposited(address,address,uint256,bytes)"
-const TRANSACTION_DEPOSITED_SIGNATURE: B256 =
+pub(crate) const TRANSACTION_DEPOSITED_SIGNATURE: B256 =
     fixed_bytes!("b3813568d9991fc951961fcb4c784893574240a28925604d09fc577c55bb7c32");
 /// Version of the deposit transaction event.
 const TRANSACTION_DEPOSITED_VERSION: B256 = B256::ZERO;
@@ -51,13 +52,11 @@ pub fn extract_transactions(
 
     let mut deposits = Vec::new();
 
-    let mut log_index = 0_usize;
-    for receipt in receipts {
+    for (receipt, first_log_index) in receipts.iter().zip(first_log_indices(receipts)) {
         let receipt = &receipt.payload;
 
         // skip failed transactions
         if !receipt.success {
-            log_index += receipt.logs.len();
             continue;
         }
         // we could skip the transaction if the Bloom filter does not contain the deposit log, but
@@ -65,7 +64,7 @@ pub fn extract_transactions(
         // logs
 
         // parse all the logs for deposit transactions
-        for log in &receipt.logs {
+        for (log_index, log) in (first_log_index..).zip(&receipt.logs) {
             if log.address == config.deposit_contract
                 && log.topics[0] == TRANSACTION_DEPOSITED_SIGNATURE
             {
@@ -74,14 +73,60 @@ pub fn extract_transactions(
                         .context("invalid deposit")?,
                 );
             }
-
-            log_index += 1;
         }
     }
 
     Ok(deposits)
 }
 
+/// Error returned when the transactions of a derived block are not ordered as mandated by
+/// the protocol.
+#[derive(Debug, PartialEq, Eq, ThisError)]
+pub enum DepositOrderError {
+    /// Returned when the block does not start with the L1 attributes deposited
+    /// transaction.
+    #[error("missing L1 attributes deposited transaction")]
+    MissingL1Attributes,
+    /// Returned when a user deposit is missing or not a deposited transaction.
+    #[error("transaction {0} is not a user-deposited transaction")]
+    MissingDeposit(usize),
+    /// Returned when a transaction from the batch data is a deposited transaction.
+    #[error("batch transaction {0} is a deposited transaction")]
+    DepositInBatch(usize),
+}
+
+/// Validates that the derived transactions consist of the L1 attributes deposited
+/// transaction, followed by exactly `deposit_count` user deposits and then only
+/// non-deposit batch transactions.
+pub fn validate_order(
+    transactions: &[Transaction<OptimismTxEssence>],
+    deposit_count: usize,
+) -> Result<(), DepositOrderError> {
+    let is_deposit = |tx: &Transaction<OptimismTxEssence>| {
+        matches!(tx.essence, OptimismTxEssence::OptimismDeposited(_))
+    };
+
+    match transactions.first() {
+        Some(tx) if is_deposit(tx) => {}
+        _ => return Err(DepositOrderError::MissingL1Attributes),
+    }
+    for index in 1..=deposit_count {
+        match transactions.get(index) {
+            Some(tx) if is_deposit(tx) => {}
+            _ => return Err(DepositOrderError::MissingDeposit(index)),
+        }
+    }
+    match transactions
+        .iter()
+        .enumerate()
+        .skip(1 + deposit_count)
+        .find(|(_, tx)| is_deposit(tx))
+    {
+        Some((index, _)) => Err(DepositOrderError::DepositInBatch(index)),
+        None => Ok(()),
+    }
+}
+
 /// Returns whether the given Bloom filter can contain a deposit log.
 pub fn can_contain(address: &Address, bloom: &Bloom) -> bool {
     let input = BloomInput::Raw(address.as_slice());
@@ -151,3 +196,63 @@ fn to_deposit_transaction(
         signature: Default::default(),
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use zeth_primitives::transactions::ethereum::TxEssenceLegacy;
+
+    use super::*;
+
+    fn deposit() -> Transaction<OptimismTxEssence> {
+        Transaction {
+            essence: OptimismTxEssence::OptimismDeposited(Default::default()),
+            signature: Default::default(),
+        }
+    }
+
+    fn user_tx() -> Transaction<OptimismTxEssence> {
+        Transaction {
+            essence: OptimismTxEssence::Ethereum(EthereumTxEssence::Legacy(
+                TxEssenceLegacy::default(),
+            )),
+            signature: Default::default(),
+        }
+    }
+
+    #[test]
+    fn valid_order() {
+        assert_eq!(validate_order(&[deposit()], 0), Ok(()));
+        assert_eq!(
+            validate_order(&[deposit(), deposit(), user_tx()], 1),
+            Ok(())
+        );
+        assert_eq!(
+            validate_order(&[deposit(), user_tx(), user_tx()], 0),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn invalid_order() {
+        assert_eq!(
+            validate_order(&[], 0),
+            Err(DepositOrderError::MissingL1Attributes)
+        );
+        assert_eq!(
+            validate_order(&[user_tx(), deposit()], 0),
+            Err(DepositOrderError::MissingL1Attributes)
+        );
+        assert_eq!(
+            validate_order(&[deposit(), user_tx(), deposit()], 2),
+            Err(DepositOrderError::MissingDeposit(1))
+        );
+        assert_eq!(
+            validate_order(&[deposit(), deposit()], 2),
+            Err(DepositOrderError::MissingDeposit(2))
+        );
+        assert_eq!(
+            validate_order(&[deposit(), deposit(), user_tx(), deposit()], 1),
+            Err(DepositOrderError::DepositInBatch(3))
+        );
+    }
+}
@@ -0,0 +1,386 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use anyhow::{bail, ensure, Context, Result};
+#[cfg(target_os = "zkvm")]
+use risc0_zkvm::guest::env;
+use serde::{ser::SerializeTuple, Deserialize, Serialize, Serializer};
+use zeth_primitives::{
+    block::Header,
+    transactions::{ethereum::EthereumTxEssence, optimism::OptimismTxEssence},
+};
+
+use super::{
+    batcher_db::{
+        validate_eth_block, validate_op_block, BatcherDb, BlockInput, EthGapError, ValidationPolicy,
+    },
+    config::ChainConfig,
+    DeriveInput,
+};
+
+/// A single block of the derivation input.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub enum Frame {
+    FullOpBlock(BlockInput<OptimismTxEssence>),
+    OpBlockHeader(Header),
+    FullEthBlock(BlockInput<EthereumTxEssence>),
+}
+
+/// A source of [Frame]s in the order they are accessed during derivation.
+pub trait FrameSource {
+    fn next_frame(&mut self) -> Result<Frame>;
+}
+
+impl FrameSource for VecDeque<Frame> {
+    fn next_frame(&mut self) -> Result<Frame> {
+        self.pop_front().context("no more input frames")
+    }
+}
+
+/// A [FrameSource] reading the frames from the guest input.
+#[cfg(target_os = "zkvm")]
+pub struct EnvFrameSource;
+
+#[cfg(target_os = "zkvm")]
+impl FrameSource for EnvFrameSource {
+    fn next_frame(&mut self) -> Result<Frame> {
+        Ok(env::read())
+    }
+}
+
+/// A [BatcherDb] reading the blocks on demand from a [FrameSource].
+///
+/// Each block is validated as soon as it is read. Only the Ethereum blocks are retained,
+/// as they can be requested more than once, and only until they are released. A released
+/// block that is requested again must be provided again by the source.
+pub struct FramedDb<S> {
+    config: ChainConfig,
+    source: S,
+    full_eth_block: HashMap<u64, BlockInput<EthereumTxEssence>>,
+}
+
+impl<S: FrameSource> FramedDb<S> {
+    pub fn new(config: ChainConfig, source: S) -> Self {
+        FramedDb {
+            config,
+            source,
+            full_eth_block: HashMap::new(),
+        }
+    }
+}
+
+impl<S: FrameSource> BatcherDb for FramedDb<S> {
+    fn validate(&self, config: &ChainConfig) -> Result<()> {
+        // frames are validated when they are read, so they must be checked against the
+        // contracts of the chain being derived
+        ensure!(
+            self.config.deposit_contract == config.deposit_contract,
+            "Deposit contract mismatch: expected {}, got {}",
+            config.deposit_contract,
+            self.config.deposit_contract
+        );
+        ensure!(
+            self.config.system_config_contract == config.system_config_contract,
+            "System config contract mismatch: expected {}, got {}",
+            config.system_config_contract,
+            self.config.system_config_contract
+        );
+
+        Ok(())
+    }
+
+    fn get_full_op_block(&mut self, block_no: u64) -> Result<BlockInput<OptimismTxEssence>> {
+        let Frame::FullOpBlock(op_block) = self.source.next_frame()? else {
+            bail!("Unexpected frame, expected full op block {}", block_no);
+        };
+        validate_op_block(block_no, &op_block)?;
+
+        Ok(op_block)
+    }
+
+    fn get_op_block_header(&mut self, block_no: u64) -> Result<Header> {
+        let Frame::OpBlockHeader(header) = self.source.next_frame()? else {
+            bail!("Unexpected frame, expected op block header {}", block_no);
+        };
+        ensure!(block_no == header.number, "Block number mismatch");
+
+        Ok(header)
+    }
+
+    fn get_full_eth_block(&mut self, block_no: u64) -> Result<&BlockInput<EthereumTxEssence>> {
+        if !self.full_eth_block.contains_key(&block_no) {
+            let Frame::FullEthBlock(eth_block) = self.source.next_frame()? else {
+                return Err(EthGapError::MissingBlock(block_no).into());
+            };
+            let header = &eth_block.block_header;
+            if header.number > block_no {
+                return Err(EthGapError::SkippedBlocks {
+                    first: block_no,
+                    last: header.number - 1,
+                    last_hash: header.parent_hash,
+                }
+                .into());
+            }
+            validate_eth_block(&self.config, block_no, &eth_block)?;
+            self.full_eth_block.insert(block_no, eth_block);
+        }
+
+        Ok(self.full_eth_block.get(&block_no).unwrap())
+    }
+
+    fn release_eth_blocks(&mut self, retained_block_no: u64) {
+        self.full_eth_block
+            .retain(|block_no, _| *block_no == retained_block_no);
+    }
+}
+
+/// A [BatcherDb] recording every block accessed in the wrapped database as a [Frame].
+pub struct FrameRecorder<D> {
+    pub db: D,
+    pub frames: Vec<Frame>,
+    eth_blocks: HashSet<u64>,
+}
+
+impl<D> FrameRecorder<D> {
+    pub fn new(db: D) -> Self {
+        FrameRecorder {
+            db,
+            frames: Vec::new(),
+            eth_blocks: HashSet::new(),
+        }
+    }
+}
+
+impl<D: BatcherDb> BatcherDb for FrameRecorder<D> {
+    fn validate(&self, config: &ChainConfig) -> Result<()> {
+        self.db.validate(config)
+    }
+
+    fn validation_policy(&self) -> ValidationPolicy {
+        self.db.validation_policy()
+    }
+
+    fn get_full_op_block(&mut self, block_no: u64) -> Result<BlockInput<OptimismTxEssence>> {
+        let op_block = self.db.get_full_op_block(block_no)?;
+        self.frames.push(Frame::FullOpBlock(op_block.clone()));
+
+        Ok(op_block)
+    }
+
+    fn get_op_block_header(&mut self, block_no: u64) -> Result<Header> {
+        let header = self.db.get_op_block_header(block_no)?;
+        self.frames.push(Frame::OpBlockHeader(header.clone()));
+
+        Ok(header)
+    }
+
+    fn get_full_eth_block(&mut self, block_no: u64) -> Result<&BlockInput<EthereumTxEssence>> {
+        let eth_block = self.db.get_full_eth_block(block_no)?;
+        if self.eth_blocks.insert(block_no) {
+            self.frames.push(Frame::FullEthBlock(eth_block.clone()));
+        }
+
+        Ok(eth_block)
+    }
+
+    fn release_eth_blocks(&mut self, retained_block_no: u64) {
+        // the replay releases the same blocks, so a later access must be recorded again
+        self.eth_blocks
+            .retain(|block_no| *block_no == retained_block_no);
+        self.db.release_eth_blocks(retained_block_no);
+    }
+}
+
+/// The input of the derivation guest with the database streamed as [Frame]s.
+#[derive(Debug, Clone)]
+pub struct FramedDeriveInput {
+    pub derive_input: DeriveInput<()>,
+    pub frames: Vec<Frame>,
+}
+
+impl Serialize for FramedDeriveInput {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        // serialize without a length prefix, so that each frame can be read on its own
+        let mut tuple = serializer.serialize_tuple(1 + self.frames.len())?;
+        tuple.serialize_element(&self.derive_input)?;
+        for frame in &self.frames {
+            tuple.serialize_element(frame)?;
+        }
+        tuple.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use zeth_primitives::{
+        rlp::encode_list_with_lengths, transactions::Transaction, trie::MptNode, Address, U256,
+    };
+
+    use super::*;
+    use crate::optimism::batcher_db::MemDb;
+
+    fn block<E: zeth_primitives::transactions::TxEssence>(number: u64) -> BlockInput<E> {
+        BlockInput {
+            block_header: Header {
+                number,
+                ..Default::default()
+            },
+            transactions: vec![],
+            receipts: None,
+            blob_sidecars: vec![],
+        }
+    }
+
+    /// Returns an op block containing a single deposit, as op blocks cannot be empty.
+    fn op_block(number: u64) -> BlockInput<OptimismTxEssence> {
+        let mut op_block = block(number);
+        op_block.transactions.push(Transaction {
+            essence: OptimismTxEssence::OptimismDeposited(Default::default()),
+            signature: Default::default(),
+        });
+        let mut tx_trie = MptNode::default();
+        let encoded_txs = encode_list_with_lengths(&op_block.transactions);
+        for (tx_no, tx) in encoded_txs.into_items().into_iter().enumerate() {
+            tx_trie.insert(&alloy_rlp::encode(tx_no), tx).unwrap();
+        }
+        op_block.block_header.transactions_root = tx_trie.hash();
+        op_block
+    }
+
+    fn access(db: &mut impl BatcherDb) -> Result<()> {
+        db.get_full_op_block(1)?;
+        db.get_full_eth_block(10)?;
+        db.get_full_eth_block(11)?;
+        db.get_full_eth_block(10)?;
+        db.get_op_block_header(2)?;
+        Ok(())
+    }
+
+    fn recorded_frames() -> Vec<Frame> {
+        let mut mem_db = MemDb::new();
+        mem_db.full_op_block.insert(1, op_block(1));
+        mem_db.full_eth_block.insert(10, block(10));
+        mem_db.full_eth_block.insert(11, block(11));
+        mem_db
+            .op_block_header
+            .insert(2, block::<OptimismTxEssence>(2).block_header);
+
+        let mut recorder = FrameRecorder::new(mem_db);
+        access(&mut recorder).unwrap();
+        recorder.frames
+    }
+
+    #[test]
+    fn replay_recorded_frames() {
+        let frames = recorded_frames();
+        assert_eq!(frames.len(), 4);
+
+        let mut framed_db = FramedDb::new(ChainConfig::optimism(), VecDeque::from(frames));
+        access(&mut framed_db).unwrap();
+        assert!(framed_db.source.is_empty());
+    }
+
+    #[test]
+    fn replay_released_blocks() {
+        let mut mem_db = MemDb::new();
+        mem_db.full_eth_block.insert(10, block(10));
+        mem_db.full_eth_block.insert(11, block(11));
+
+        let access = |db: &mut dyn BatcherDb| -> Result<()> {
+            db.get_full_eth_block(10)?;
+            db.get_full_eth_block(11)?;
+            db.release_eth_blocks(11);
+            db.get_full_eth_block(11)?;
+            db.get_full_eth_block(10)?;
+            Ok(())
+        };
+
+        // the released block is recorded again when it is accessed again
+        let mut recorder = FrameRecorder::new(mem_db);
+        access(&mut recorder).unwrap();
+        assert_eq!(recorder.frames.len(), 3);
+
+        let mut framed_db = FramedDb::new(ChainConfig::optimism(), VecDeque::from(recorder.frames));
+        access(&mut framed_db).unwrap();
+        assert!(framed_db.source.is_empty());
+
+        framed_db.release_eth_blocks(10);
+        assert_eq!(framed_db.full_eth_block.len(), 1);
+    }
+
+    #[test]
+    fn serialize_as_sequence() {
+        let framed_input = FramedDeriveInput {
+            derive_input: DeriveInput {
+                db: (),
+                op_head_block_no: 1,
+                op_derive_block_count: 1,
+                op_derive_max_timestamp: None,
+                op_block_outputs: vec![],
+                block_image_id: Default::default(),
+                eth_accumulator: false,
+                op_anchor_block_no: None,
+                batch_provenance: false,
+                rollup_config: None,
+                prior_output: None,
+                checkpoint: false,
+                resume_checkpoint: None,
+            },
+            frames: recorded_frames(),
+        };
+
+        let mut expected = bincode::serialize(&framed_input.derive_input).unwrap();
+        for frame in &framed_input.frames {
+            expected.extend(bincode::serialize(frame).unwrap());
+        }
+        assert_eq!(bincode::serialize(&framed_input).unwrap(), expected);
+    }
+
+    #[test]
+    fn reject_out_of_order_frames() {
+        let mut frames = recorded_frames();
+        frames.swap(1, 2);
+
+        let mut framed_db = FramedDb::new(ChainConfig::optimism(), VecDeque::from(frames));
+        access(&mut framed_db).unwrap_err();
+    }
+
+    #[test]
+    fn reject_other_chain() {
+        let framed_db = FramedDb::new(ChainConfig::optimism(), VecDeque::from(recorded_frames()));
+        framed_db.validate(&ChainConfig::optimism()).unwrap();
+
+        let mut config = ChainConfig::optimism();
+        config.deposit_contract = Address::ZERO;
+        framed_db.validate(&config).unwrap_err();
+
+        let mut config = ChainConfig::optimism();
+        config.system_config_contract = Address::ZERO;
+        framed_db.validate(&config).unwrap_err();
+    }
+
+    #[test]
+    fn reject_invalid_frame() {
+        let mut frames = recorded_frames();
+        let Frame::FullEthBlock(eth_block) = &mut frames[1] else {
+            unreachable!()
+        };
+        eth_block.block_header.gas_used = U256::from(21_000);
+
+        let mut framed_db = FramedDb::new(ChainConfig::optimism(), VecDeque::from(frames));
+        access(&mut framed_db).unwrap_err();
+    }
+}
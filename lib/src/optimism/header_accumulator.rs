@@ -0,0 +1,123 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::{ensure, Result};
+use serde::{Deserialize, Serialize};
+use zeth_primitives::{keccak::keccak, B256};
+
+use crate::optimism::batcher::BlockId;
+
+/// A running keccak commitment to a continuous segment of Ethereum blocks.
+///
+/// Instead of the full list of consumed block hashes, only the first and last block
+/// and the accumulated hash `acc_i = keccak(acc_{i-1} || hash_i)`, starting from
+/// `acc_0 = 0`, are committed. Verifiers can recompute the hash over the same segment
+/// from an external source of block hashes.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, Eq, PartialEq)]
+pub struct HeaderAccumulator {
+    /// First block of the segment.
+    pub first: BlockId,
+    /// Last block of the segment.
+    pub last: BlockId,
+    /// Accumulated hash over all blocks of the segment.
+    pub hash: B256,
+}
+
+impl HeaderAccumulator {
+    /// Creates a new accumulator containing only the given block.
+    pub fn new(first: BlockId) -> Self {
+        HeaderAccumulator {
+            first,
+            last: first,
+            hash: accumulate(&B256::ZERO, &first.hash),
+        }
+    }
+
+    /// Appends the block following the last block of the segment.
+    pub fn append(&mut self, block: BlockId) -> Result<()> {
+        ensure!(
+            block.number == self.last.number + 1,
+            "Block {} does not follow the accumulated block {}",
+            block.number,
+            self.last.number
+        );
+        self.hash = accumulate(&self.hash, &block.hash);
+        self.last = block;
+
+        Ok(())
+    }
+
+    /// Returns the accumulator over the given continuous segment, if it is not empty.
+    pub fn from_blocks(blocks: impl IntoIterator<Item = BlockId>) -> Result<Option<Self>> {
+        let mut blocks = blocks.into_iter();
+        let Some(first) = blocks.next() else {
+            return Ok(None);
+        };
+        let mut accumulator = HeaderAccumulator::new(first);
+        for block in blocks {
+            accumulator.append(block)?;
+        }
+
+        Ok(Some(accumulator))
+    }
+
+    /// Returns the number of accumulated blocks.
+    pub fn block_count(&self) -> u64 {
+        self.last.number - self.first.number + 1
+    }
+}
+
+fn accumulate(acc: &B256, block_hash: &B256) -> B256 {
+    let mut data = [0u8; 64];
+    data[..32].copy_from_slice(acc.as_slice());
+    data[32..].copy_from_slice(block_hash.as_slice());
+    keccak(data).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(number: u64) -> BlockId {
+        BlockId {
+            number,
+            hash: keccak(number.to_be_bytes()).into(),
+        }
+    }
+
+    #[test]
+    fn accumulate_segment() {
+        let accumulator = HeaderAccumulator::from_blocks((10..13).map(block))
+            .unwrap()
+            .unwrap();
+        assert_eq!(accumulator.first, block(10));
+        assert_eq!(accumulator.last, block(12));
+        assert_eq!(accumulator.block_count(), 3);
+
+        let mut expected = B256::ZERO;
+        for number in 10..13 {
+            expected = keccak([expected.as_slice(), block(number).hash.as_slice()].concat()).into();
+        }
+        assert_eq!(accumulator.hash, expected);
+    }
+
+    #[test]
+    fn reject_gap() {
+        let mut accumulator = HeaderAccumulator::new(block(1));
+        assert!(accumulator.append(block(3)).is_err());
+        assert!(accumulator.append(block(1)).is_err());
+        assert_eq!(accumulator, HeaderAccumulator::new(block(1)));
+        assert!(HeaderAccumulator::from_blocks([]).unwrap().is_none());
+    }
+}
@@ -0,0 +1,119 @@
+// Copyright 2023 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Conversions to and from the op-alloy types used by kona-derive, so that both
+//! derivation implementations can be fed with the same data.
+
+use anyhow::Context;
+use op_alloy_genesis::SystemConfig as KonaSystemConfig;
+use op_alloy_protocol::Frame as KonaFrame;
+use zeth_primitives::kona::{to_kona_address, to_kona_u256};
+
+use super::{batcher_channel::Frame, system_config::SystemConfig};
+
+/// Conversion from a kona [KonaFrame] to the local [Frame].
+impl From<KonaFrame> for Frame {
+    fn from(frame: KonaFrame) -> Self {
+        Frame {
+            channel_id: u128::from_be_bytes(frame.id),
+            number: frame.number,
+            data: frame.data,
+            is_last: frame.is_last,
+        }
+    }
+}
+
+/// Conversion from the local [Frame] to a kona [KonaFrame].
+impl From<Frame> for KonaFrame {
+    fn from(frame: Frame) -> Self {
+        KonaFrame {
+            id: frame.channel_id.to_be_bytes(),
+            number: frame.number,
+            data: frame.data,
+            is_last: frame.is_last,
+        }
+    }
+}
+
+/// Conversion from the local [SystemConfig] to a kona [KonaSystemConfig].
+///
+/// The unsafe block signer is not part of the kona system config, so there is no
+/// conversion in the other direction.
+impl TryFrom<&SystemConfig> for KonaSystemConfig {
+    type Error = anyhow::Error;
+
+    fn try_from(config: &SystemConfig) -> Result<Self, Self::Error> {
+        Ok(KonaSystemConfig {
+            batcher_address: to_kona_address(config.batch_sender),
+            overhead: to_kona_u256(config.l1_fee_overhead),
+            scalar: to_kona_u256(config.l1_fee_scalar),
+            gas_limit: config
+                .gas_limit
+                .try_into()
+                .context("gas limit does not fit into 64 bits")?,
+            ..Default::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use zeth_primitives::{Address, U256};
+
+    use super::*;
+
+    #[test]
+    fn frame_roundtrip() {
+        // a single last frame of channel 0xff..ff00 with the data 0xff
+        let mut data = vec![0x00];
+        data.extend([0xff; 15]);
+        data.extend([0x00; 3]);
+        data.extend([0x00, 0x00, 0x00, 0x01, 0xff, 0x01]);
+
+        let frames = Frame::process_batcher_data(&data).unwrap();
+        let kona_frames = KonaFrame::parse_frames(&data).unwrap();
+        assert_eq!(
+            frames
+                .iter()
+                .cloned()
+                .map(KonaFrame::from)
+                .collect::<Vec<_>>(),
+            kona_frames
+        );
+        assert_eq!(
+            kona_frames.into_iter().map(Frame::from).collect::<Vec<_>>(),
+            frames
+        );
+    }
+
+    #[test]
+    fn system_config() {
+        let config = SystemConfig {
+            batch_sender: Address::repeat_byte(0x42),
+            gas_limit: U256::from(30_000_000),
+            l1_fee_overhead: U256::from(188),
+            l1_fee_scalar: U256::from(684_000),
+            unsafe_block_signer: Address::ZERO,
+        };
+        let kona_config = KonaSystemConfig::try_from(&config).unwrap();
+        assert_eq!(kona_config.gas_limit, 30_000_000);
+        assert_eq!(kona_config.scalar, to_kona_u256(config.l1_fee_scalar));
+
+        let config = SystemConfig {
+            gas_limit: U256::MAX,
+            ..config
+        };
+        KonaSystemConfig::try_from(&config).unwrap_err();
+    }
+}
@@ -0,0 +1,344 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The calldata of the L1 attributes deposited transaction, in the ABI encoded format of
+//! Bedrock and the packed format introduced with Ecotone.
+
+use alloy_sol_types::SolInterface;
+use anyhow::{bail, ensure, Context, Result};
+use zeth_primitives::{block::Header, uint, Address, B256, U256};
+
+use super::OpSystemInfo;
+
+/// Selector of `setL1BlockValuesEcotone()`.
+pub const ECOTONE_SELECTOR: [u8; 4] = [0x44, 0x0a, 0x5e, 0x20];
+
+/// Length of the packed calldata of `setL1BlockValuesEcotone()`.
+const ECOTONE_CALLDATA_LEN: usize = 164;
+
+/// Version byte of a system config scalar in the Bedrock format.
+const SCALAR_VERSION_BEDROCK: u8 = 0;
+/// Version byte of a system config scalar in the Ecotone format.
+const SCALAR_VERSION_ECOTONE: u8 = 1;
+
+/// The minimum blob gas price, as defined by EIP-4844.
+const MIN_BLOB_GASPRICE: U256 = uint!(1_U256);
+/// The update fraction of the blob gas price, as defined by EIP-4844.
+const BLOB_GASPRICE_UPDATE_FRACTION: U256 = uint!(3338477_U256);
+
+/// The values of `setL1BlockValuesEcotone()`, whose calldata packs them after the
+/// selector instead of ABI encoding them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct L1BlockValuesEcotone {
+    /// Scalar applied to the L1 base fee.
+    pub base_fee_scalar: u32,
+    /// Scalar applied to the L1 blob base fee.
+    pub blob_base_fee_scalar: u32,
+    /// Sequence number in the current epoch.
+    pub sequence_number: u64,
+    /// L1 block attributes.
+    pub timestamp: u64,
+    pub number: u64,
+    pub basefee: U256,
+    pub blob_base_fee: U256,
+    pub hash: B256,
+    /// A versioned hash of the current authorized batcher sender.
+    pub batcher_hash: B256,
+}
+
+impl L1BlockValuesEcotone {
+    /// Returns the packed calldata.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(ECOTONE_CALLDATA_LEN);
+        data.extend_from_slice(&ECOTONE_SELECTOR);
+        data.extend_from_slice(&self.base_fee_scalar.to_be_bytes());
+        data.extend_from_slice(&self.blob_base_fee_scalar.to_be_bytes());
+        data.extend_from_slice(&self.sequence_number.to_be_bytes());
+        data.extend_from_slice(&self.timestamp.to_be_bytes());
+        data.extend_from_slice(&self.number.to_be_bytes());
+        data.extend_from_slice(&self.basefee.to_be_bytes::<32>());
+        data.extend_from_slice(&self.blob_base_fee.to_be_bytes::<32>());
+        data.extend_from_slice(self.hash.as_slice());
+        data.extend_from_slice(self.batcher_hash.as_slice());
+        data
+    }
+
+    /// Decodes the packed calldata.
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        ensure!(
+            data.len() == ECOTONE_CALLDATA_LEN,
+            "invalid Ecotone L1 attributes length: {}",
+            data.len()
+        );
+        let (selector, data) = data.split_at(4);
+        ensure!(
+            selector == ECOTONE_SELECTOR,
+            "invalid Ecotone L1 attributes selector"
+        );
+
+        let u32_at =
+            |offset: usize| u32::from_be_bytes(data[offset..offset + 4].try_into().unwrap());
+        let u64_at =
+            |offset: usize| u64::from_be_bytes(data[offset..offset + 8].try_into().unwrap());
+        let b256_at = |offset: usize| B256::from_slice(&data[offset..offset + 32]);
+        Ok(L1BlockValuesEcotone {
+            base_fee_scalar: u32_at(0),
+            blob_base_fee_scalar: u32_at(4),
+            sequence_number: u64_at(8),
+            timestamp: u64_at(16),
+            number: u64_at(24),
+            basefee: U256::from_be_bytes(b256_at(32).0),
+            blob_base_fee: U256::from_be_bytes(b256_at(64).0),
+            hash: b256_at(96),
+            batcher_hash: b256_at(128),
+        })
+    }
+
+    /// Returns the system config scalar in the Ecotone format encoding both scalars.
+    pub fn l1_fee_scalar(&self) -> U256 {
+        let mut scalar = [0u8; 32];
+        scalar[0] = SCALAR_VERSION_ECOTONE;
+        scalar[24..28].copy_from_slice(&self.blob_base_fee_scalar.to_be_bytes());
+        scalar[28..32].copy_from_slice(&self.base_fee_scalar.to_be_bytes());
+        U256::from_be_bytes(scalar)
+    }
+}
+
+/// The values set by an L1 attributes deposited transaction in either format.
+#[derive(Debug, Clone)]
+pub enum L1BlockValues {
+    /// `setL1BlockValues()`, used before Ecotone and in the Ecotone activation block.
+    Bedrock(OpSystemInfo::setL1BlockValuesCall),
+    /// `setL1BlockValuesEcotone()`, used after the Ecotone activation block.
+    Ecotone(L1BlockValuesEcotone),
+}
+
+impl L1BlockValues {
+    /// Decodes the calldata of an L1 attributes deposited transaction, selecting the
+    /// format by its selector.
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        if data.starts_with(&ECOTONE_SELECTOR) {
+            return Ok(L1BlockValues::Ecotone(L1BlockValuesEcotone::decode(data)?));
+        }
+        let call = OpSystemInfo::OpSystemInfoCalls::abi_decode(data, true)
+            .context("invalid L1 attributes data")?;
+        match call {
+            OpSystemInfo::OpSystemInfoCalls::setL1BlockValues(x) => Ok(L1BlockValues::Bedrock(x)),
+        }
+    }
+
+    /// Returns the calldata of the L1 attributes deposited transaction.
+    pub fn encode(&self) -> Vec<u8> {
+        match self {
+            L1BlockValues::Bedrock(call) => {
+                OpSystemInfo::OpSystemInfoCalls::setL1BlockValues(call.clone()).abi_encode()
+            }
+            L1BlockValues::Ecotone(values) => values.encode(),
+        }
+    }
+
+    /// Returns the number of the L1 origin.
+    pub fn number(&self) -> u64 {
+        match self {
+            L1BlockValues::Bedrock(call) => call.number,
+            L1BlockValues::Ecotone(values) => values.number,
+        }
+    }
+
+    /// Returns the timestamp of the L1 origin.
+    pub fn timestamp(&self) -> u64 {
+        match self {
+            L1BlockValues::Bedrock(call) => call.timestamp,
+            L1BlockValues::Ecotone(values) => values.timestamp,
+        }
+    }
+
+    /// Returns the hash of the L1 origin.
+    pub fn hash(&self) -> B256 {
+        match self {
+            L1BlockValues::Bedrock(call) => call.hash,
+            L1BlockValues::Ecotone(values) => values.hash,
+        }
+    }
+
+    /// Returns the sequence number in the epoch of the L1 origin.
+    pub fn sequence_number(&self) -> u64 {
+        match self {
+            L1BlockValues::Bedrock(call) => call.sequence_number,
+            L1BlockValues::Ecotone(values) => values.sequence_number,
+        }
+    }
+
+    /// Returns the authorized batcher sender.
+    pub fn batch_sender(&self) -> Address {
+        let batcher_hash = match self {
+            L1BlockValues::Bedrock(call) => call.batcher_hash,
+            L1BlockValues::Ecotone(values) => values.batcher_hash,
+        };
+        Address::from_slice(&batcher_hash.as_slice()[12..])
+    }
+
+    /// Returns the L1 fee overhead and scalar as stored in the system config. After
+    /// Ecotone, the overhead is unused and zero, and the scalar encodes both scalars.
+    pub fn l1_fee(&self) -> (U256, U256) {
+        match self {
+            L1BlockValues::Bedrock(call) => (call.l1_fee_overhead, call.l1_fee_scalar),
+            L1BlockValues::Ecotone(values) => (U256::ZERO, values.l1_fee_scalar()),
+        }
+    }
+}
+
+/// Returns the base fee scalar and the blob base fee scalar encoded in a system config
+/// scalar.
+///
+/// Scalars in the Bedrock format only contain the base fee scalar; if they do not fit
+/// into 32 bits, the base fee scalar saturates as mandated by the Ecotone specification.
+pub fn ecotone_scalars(l1_fee_scalar: U256) -> Result<(u32, u32)> {
+    let scalar = l1_fee_scalar.to_be_bytes::<32>();
+    let base_fee_scalar = u32::from_be_bytes(scalar[28..32].try_into().unwrap());
+    let blob_base_fee_scalar = u32::from_be_bytes(scalar[24..28].try_into().unwrap());
+    match scalar[0] {
+        SCALAR_VERSION_BEDROCK => {
+            if scalar[1..28].iter().any(|&b| b != 0) {
+                return Ok((u32::MAX, 0));
+            }
+            Ok((base_fee_scalar, 0))
+        }
+        SCALAR_VERSION_ECOTONE => {
+            ensure!(
+                scalar[1..24].iter().all(|&b| b == 0),
+                "invalid Ecotone scalar padding: {}",
+                l1_fee_scalar
+            );
+            Ok((base_fee_scalar, blob_base_fee_scalar))
+        }
+        version => bail!("unknown scalar version {}", version),
+    }
+}
+
+/// Returns the blob base fee of the given L1 block, as defined by EIP-4844; one for
+/// blocks before Cancun.
+pub fn blob_base_fee(header: &Header) -> U256 {
+    match header.excess_blob_gas {
+        Some(excess_blob_gas) => fake_exponential(
+            MIN_BLOB_GASPRICE,
+            excess_blob_gas,
+            BLOB_GASPRICE_UPDATE_FRACTION,
+        ),
+        None => MIN_BLOB_GASPRICE,
+    }
+}
+
+/// Approximates `factor * e ** (numerator / denominator)` using a Taylor expansion.
+fn fake_exponential(factor: U256, numerator: U256, denominator: U256) -> U256 {
+    let mut i = U256::from(1);
+    let mut output = U256::ZERO;
+    let mut numerator_accum = factor * denominator;
+    while numerator_accum > U256::ZERO {
+        output += numerator_accum;
+        numerator_accum = numerator_accum * numerator / (denominator * i);
+        i += U256::from(1);
+    }
+    output / denominator
+}
+
+#[cfg(test)]
+mod tests {
+    use zeth_primitives::{b256, hex};
+
+    use super::*;
+
+    #[test]
+    fn ecotone_round_trip() {
+        let values = L1BlockValuesEcotone {
+            base_fee_scalar: 1368,
+            blob_base_fee_scalar: 810949,
+            sequence_number: 3,
+            timestamp: 1710374399,
+            number: 19430000,
+            basefee: U256::from(41_000_000_000u64),
+            blob_base_fee: U256::from(1),
+            hash: B256::repeat_byte(0xab),
+            batcher_hash: b256!("0000000000000000000000006887246668a3b87f54deb3b94ba47a6f63f32985"),
+        };
+        let data = values.encode();
+        assert_eq!(data.len(), ECOTONE_CALLDATA_LEN);
+        assert_eq!(data[..4], hex!("440a5e20"));
+        assert_eq!(data[4..8], 1368u32.to_be_bytes());
+
+        let decoded = L1BlockValues::decode(&data).unwrap();
+        assert_eq!(decoded.encode(), data);
+        assert_eq!(decoded.number(), 19430000);
+        assert_eq!(decoded.sequence_number(), 3);
+        assert_eq!(
+            decoded.batch_sender(),
+            zeth_primitives::address!("6887246668a3b87f54deb3b94ba47a6f63f32985")
+        );
+        let (overhead, scalar) = decoded.l1_fee();
+        assert_eq!(overhead, U256::ZERO);
+        assert_eq!(ecotone_scalars(scalar).unwrap(), (1368, 810949));
+
+        assert!(L1BlockValues::decode(&data[..100]).is_err());
+    }
+
+    #[test]
+    fn bedrock_round_trip() {
+        let call = OpSystemInfo::setL1BlockValuesCall {
+            number: 17422590,
+            timestamp: 1686068903,
+            basefee: U256::from(7),
+            hash: B256::repeat_byte(1),
+            sequence_number: 0,
+            batcher_hash: B256::repeat_byte(2),
+            l1_fee_overhead: U256::from(188),
+            l1_fee_scalar: U256::from(684000),
+        };
+        let data = L1BlockValues::Bedrock(call).encode();
+        let decoded = L1BlockValues::decode(&data).unwrap();
+        assert!(matches!(decoded, L1BlockValues::Bedrock(_)));
+        assert_eq!(decoded.timestamp(), 1686068903);
+        assert_eq!(decoded.l1_fee(), (U256::from(188), U256::from(684000)));
+    }
+
+    #[test]
+    fn scalars() {
+        assert_eq!(ecotone_scalars(U256::from(684000)).unwrap(), (684000, 0));
+        // Bedrock scalars that do not fit into 32 bits saturate
+        assert_eq!(
+            ecotone_scalars(U256::from(u64::MAX)).unwrap(),
+            (u32::MAX, 0)
+        );
+        let scalar = U256::from_be_bytes(hex!(
+            "010000000000000000000000000000000000000000000000000c5fc500000558"
+        ));
+        assert_eq!(ecotone_scalars(scalar).unwrap(), (1368, 810949));
+        assert!(ecotone_scalars(U256::from(1) << 248 | U256::from(1) << 200).is_err());
+        assert!(ecotone_scalars(U256::from(2) << 248).is_err());
+    }
+
+    #[test]
+    fn blob_fee() {
+        let mut header = Header::default();
+        assert_eq!(blob_base_fee(&header), U256::from(1));
+        header.excess_blob_gas = Some(U256::ZERO);
+        assert_eq!(blob_base_fee(&header), U256::from(1));
+        // values of the EIP-4844 reference implementation
+        header.excess_blob_gas = Some(U256::from(2314057));
+        assert_eq!(blob_base_fee(&header), U256::from(1));
+        header.excess_blob_gas = Some(U256::from(2314058));
+        assert_eq!(blob_base_fee(&header), U256::from(2));
+        header.excess_blob_gas = Some(U256::from(10_000_000));
+        assert_eq!(blob_base_fee(&header), U256::from(19));
+    }
+}
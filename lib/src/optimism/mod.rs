@@ -14,16 +14,18 @@
 
 use core::iter::once;
 
-use alloy_sol_types::{sol, SolInterface};
+use alloy_sol_types::sol;
 use anyhow::{bail, ensure, Context, Result};
 #[cfg(target_os = "zkvm")]
 use risc0_zkvm::{guest::env, serde::to_vec, sha::Digest};
 use serde::{Deserialize, Serialize};
+use thiserror::Error as ThisError;
 use zeth_primitives::{
     alloy_rlp,
     batch::Batch,
     block::Header,
     keccak::keccak,
+    rlp::encode_list_with_lengths,
     transactions::{
         ethereum::TransactionKind,
         optimism::{OptimismTxEssence, TxEssenceOptimismDeposited},
@@ -36,17 +38,21 @@ use zeth_primitives::{
 #[cfg(not(target_os = "zkvm"))]
 use crate::{
     builder::{BlockBuilderStrategy, OptimismStrategy},
-    consts::OP_MAINNET_CHAIN_SPEC,
     host::{preflight::Preflight, provider_db::ProviderDb, ProviderFactory},
 };
 use crate::{
     consts::ONE,
     input::{BlockBuildInput, StateInput},
     optimism::{
-        batcher::{Batcher, BlockId, L2BlockInfo},
-        batcher_db::BatcherDb,
+        batcher::{BatchSource, Batcher, BatcherCheckpoint, BlockId, DroppedBatch, L2BlockInfo},
+        batcher_channel::{DroppedChannel, DroppedTransaction, SkippedFrame},
+        batcher_db::{BatcherDb, ValidationPolicy},
         composition::ImageId,
         config::ChainConfig,
+        header_accumulator::HeaderAccumulator,
+        l1_block_info::{L1BlockValues, L1BlockValuesEcotone},
+        rollup_config::RollupConfig,
+        trace::TraceEvent,
     },
     output::BlockBuildOutput,
 };
@@ -54,10 +60,25 @@ use crate::{
 pub mod batcher;
 pub mod batcher_channel;
 pub mod batcher_db;
+pub mod batches;
+pub mod blob;
 pub mod composition;
 pub mod config;
 pub mod deposits;
+pub mod framed_db;
+pub mod header_accumulator;
+#[cfg(feature = "kona")]
+pub mod kona;
+pub mod l1_block_info;
+#[cfg(not(target_os = "zkvm"))]
+pub mod registry;
+pub mod rollup_config;
+pub mod stage;
+#[cfg(not(target_os = "zkvm"))]
+pub mod synthetic;
 pub mod system_config;
+pub mod trace;
+pub mod unsafe_payload;
 
 sol! {
     /// The values stored by the L1 Attributes Predeployed Contract.
@@ -88,12 +109,66 @@ pub struct DeriveInput<D> {
     pub db: D,
     /// Block number of the L2 head.
     pub op_head_block_no: u64,
-    /// Block count for the operation.
+    /// Block count for the operation. Zero derives no blocks and only attests the L2
+    /// head and the L1 origin recorded in it.
     pub op_derive_block_count: u32,
+    /// Maximum timestamp of the derived blocks, if any; the derivation stops before the
+    /// first block with a later timestamp.
+    pub op_derive_max_timestamp: Option<u64>,
     /// Block building data for execution
     pub op_block_outputs: Vec<BlockBuildOutput>,
     /// Image id of block builder guest
     pub block_image_id: ImageId,
+    /// Whether to commit to a [HeaderAccumulator] of the consumed Ethereum blocks.
+    pub eth_accumulator: bool,
+    /// Block number of the Optimism block anchoring the Ethereum tail, if any.
+    pub op_anchor_block_no: Option<u64>,
+    /// Whether to commit to the [BatchProvenance] of every derived block.
+    pub batch_provenance: bool,
+    /// Configuration of the derived chain, if it is not the chain the guests are built
+    /// for.
+    pub rollup_config: Option<RollupConfig>,
+    /// Output of the derivation this run continues, if any. The run must start at its
+    /// Op tail and traverse its Eth tail, so that the outputs can be chained.
+    pub prior_output: Option<DeriveOutput>,
+    /// Whether to commit to the hash of the [DeriveCheckpoint] at the end of the run, so
+    /// that a later run can resume from it.
+    pub checkpoint: bool,
+    /// Checkpoint to resume the derivation from, if any. Its hash must be committed by
+    /// the prior output.
+    pub resume_checkpoint: Option<DeriveCheckpoint>,
+}
+
+impl<D> DeriveInput<D> {
+    /// Returns the same input with the database replaced.
+    pub fn map_db<E>(self, f: impl FnOnce(D) -> E) -> DeriveInput<E> {
+        DeriveInput {
+            db: f(self.db),
+            op_head_block_no: self.op_head_block_no,
+            op_derive_block_count: self.op_derive_block_count,
+            op_derive_max_timestamp: self.op_derive_max_timestamp,
+            op_block_outputs: self.op_block_outputs,
+            block_image_id: self.block_image_id,
+            eth_accumulator: self.eth_accumulator,
+            op_anchor_block_no: self.op_anchor_block_no,
+            batch_provenance: self.batch_provenance,
+            rollup_config: self.rollup_config,
+            prior_output: self.prior_output,
+            checkpoint: self.checkpoint,
+            resume_checkpoint: self.resume_checkpoint,
+        }
+    }
+
+    /// Returns the configuration of the derived chain: the one built from the
+    /// [RollupConfig] if given, the one of the chain the guests are built for otherwise.
+    pub fn chain_config(&self) -> Result<ChainConfig> {
+        match &self.rollup_config {
+            Some(rollup_config) => rollup_config
+                .chain_config()
+                .context("Invalid rollup config"),
+            None => Ok(ChainConfig::selected()),
+        }
+    }
 }
 
 /// Represents the output of the derivation process.
@@ -107,11 +182,275 @@ pub struct DeriveOutput {
     pub derived_op_blocks: Vec<BlockId>,
     /// Image id of block builder guest
     pub block_image_id: ImageId,
+    /// Accumulator of the consumed Ethereum blocks, if requested.
+    pub eth_accumulator: Option<HeaderAccumulator>,
+    /// Anchor of the Ethereum tail, if requested.
+    pub l1_anchor: Option<L1Anchor>,
+    /// Provenance of the derived Optimism blocks in the same order, if requested;
+    /// empty otherwise.
+    pub batch_provenance: Vec<BatchProvenance>,
+    /// Hash of the [RollupConfig] of the derived chain, if it is not the chain the
+    /// guests are built for.
+    pub rollup_config_hash: Option<B256>,
+    /// Journal digest of the prior output this run continues, if any. The prior output
+    /// is provided by the host, so a verifier chaining the outputs must match the digest
+    /// against the receipt of the prior derivation.
+    pub prior_output_digest: Option<B256>,
+    /// Hash of the [DeriveCheckpoint] at the end of the run, if requested.
+    pub checkpoint_hash: Option<B256>,
+}
+
+impl DeriveOutput {
+    /// Returns the last derived Optimism block, or the Optimism head if no block was
+    /// derived.
+    pub fn op_tail(&self) -> BlockId {
+        self.derived_op_blocks
+            .last()
+            .copied()
+            .unwrap_or(self.op_head)
+    }
+}
+
+/// The [DeriveOutput] committed by the derivation guests of input version 1, which only
+/// supported deriving the chain the guests are built for from its Optimism head.
+#[derive(Debug, Clone, Deserialize, Eq, PartialEq, Serialize)]
+pub struct DeriveOutputV1 {
+    pub eth_tail: BlockId,
+    pub op_head: BlockId,
+    pub derived_op_blocks: Vec<BlockId>,
+    pub block_image_id: ImageId,
+}
+
+impl TryFrom<DeriveOutput> for DeriveOutputV1 {
+    type Error = anyhow::Error;
+
+    fn try_from(output: DeriveOutput) -> Result<Self> {
+        ensure!(
+            output.eth_accumulator.is_none(),
+            "Derivation guests of input version 1 do not support the Ethereum accumulator"
+        );
+        ensure!(
+            output.l1_anchor.is_none(),
+            "Derivation guests of input version 1 do not support L1 anchors"
+        );
+        ensure!(
+            output.batch_provenance.is_empty(),
+            "Derivation guests of input version 1 do not support batch provenance"
+        );
+        ensure!(
+            output.rollup_config_hash.is_none(),
+            "Derivation guests of input version 1 do not support rollup configs"
+        );
+        ensure!(
+            output.prior_output_digest.is_none(),
+            "Derivation guests of input version 1 do not support prior outputs"
+        );
+        ensure!(
+            output.checkpoint_hash.is_none(),
+            "Derivation guests of input version 1 do not support checkpoints"
+        );
+        Ok(DeriveOutputV1 {
+            eth_tail: output.eth_tail,
+            op_head: output.op_head,
+            derived_op_blocks: output.derived_op_blocks,
+            block_image_id: output.block_image_id,
+        })
+    }
+}
+
+/// An Optimism block whose L1 attributes deposited transaction stored a descendant of the
+/// Ethereum tail in the L1Block predeploy. A verifier on the Optimism chain can check the
+/// anchor against the block hash or the state of the predeploy, without an L1 hash
+/// oracle.
+#[derive(Debug, Clone, Copy, Deserialize, Eq, PartialEq, Serialize)]
+pub struct L1Anchor {
+    /// The anchoring Optimism block.
+    pub op_block: BlockId,
+    /// The Ethereum block stored in the L1Block predeploy by the anchoring block.
+    pub eth_block: BlockId,
+}
+
+/// The L1 data a derived Optimism block was built from, linking the block to the channel
+/// and the Ethereum block that carried its batch.
+#[derive(Debug, Clone, Copy, Deserialize, Eq, PartialEq, Serialize)]
+pub struct BatchProvenance {
+    /// The derived Optimism block.
+    pub op_block: BlockId,
+    /// The source of its batch, or `None` for a default batch derived without L1 data
+    /// after the sequencing window expired.
+    pub source: Option<BatchSource>,
+}
+
+/// Details of the derivation that the guest writes to the host instead of committing them
+/// to the journal. They are not covered by the proof, but spare the journal verified on
+/// chain from data that only the operator is interested in.
+#[derive(Debug, Clone, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct DerivePrivateOutput {
+    /// Transactions sent to the batch inbox that were dropped.
+    pub dropped_transactions: Vec<DroppedTransaction>,
+    /// Frames that were not added to their channel.
+    pub skipped_frames: Vec<SkippedFrame>,
+    /// Channels that were dropped, or only read partially.
+    pub dropped_channels: Vec<DroppedChannel>,
+    /// Batches rejected by the batch status rules.
+    pub dropped_batches: Vec<DroppedBatch>,
+    /// Decision points of the derivation, only recorded with the `debug-trace` feature.
+    pub debug_trace: Vec<TraceEvent>,
+}
+
+/// The attributes of a derived Optimism block, following the payload attributes of the
+/// OP Stack engine API. The block is built from these attributes, and the result is then
+/// matched against the output of the block builder.
+#[derive(Debug, Clone, Deserialize, Eq, PartialEq, Serialize)]
+pub struct OpPayloadAttributes {
+    /// Timestamp of the block.
+    pub timestamp: u64,
+    /// Mix hash of the L1 origin of the block.
+    pub prev_randao: B256,
+    /// The address receiving the priority fees.
+    pub suggested_fee_recipient: Address,
+    /// The L1 attributes deposited transaction, followed by the user deposits and the
+    /// batch transactions.
+    pub transactions: Vec<Transaction<OptimismTxEssence>>,
+    /// Whether the sequencer may add transactions of its pool, which is never the case
+    /// for derived blocks.
+    pub no_tx_pool: bool,
+    /// Gas limit of the block.
+    pub gas_limit: U256,
+}
+
+impl OpPayloadAttributes {
+    /// Returns the input for building the block on top of the given parent.
+    pub fn into_block_build_input(
+        self,
+        parent_header: Header,
+    ) -> BlockBuildInput<OptimismTxEssence> {
+        BlockBuildInput {
+            state_input: StateInput {
+                parent_header,
+                beneficiary: self.suggested_fee_recipient,
+                gas_limit: self.gas_limit,
+                timestamp: U256::from(self.timestamp),
+                extra_data: Default::default(),
+                mix_hash: self.prev_randao,
+                transactions: self.transactions,
+                withdrawals: vec![],
+            },
+            // initializing these fields is not needed here
+            parent_state_trie: Default::default(),
+            parent_storage: Default::default(),
+            contracts: vec![],
+            ancestor_headers: vec![],
+        }
+    }
+}
+
+/// Error returned by the [DeriveMachine] when its input is malformed.
+#[derive(Debug, Clone, PartialEq, Eq, ThisError)]
+pub enum DeriveError {
+    /// The Op head does not contain any transactions.
+    #[error("Op head block {0} is empty")]
+    EmptyOpHead(u64),
+    /// The first transaction of the Op head is not an L1 attributes deposited
+    /// transaction.
+    #[error("First transaction in Op block {block_number} is not a valid L1 attributes deposited transaction: {reason}")]
+    InvalidL1Attributes { block_number: u64, reason: String },
+    /// The Eth head does not match the L1 origin of the Op head.
+    #[error("Eth head block {block_number} has hash {actual}, expected {expected}")]
+    EthHeadMismatch {
+        block_number: u64,
+        expected: B256,
+        actual: B256,
+    },
+    /// The Op head does not match the block number of the input.
+    #[error("Op head is block {actual}, expected {expected}")]
+    OpHeadMismatch { expected: u64, actual: u64 },
+    /// The timestamp of an Op block does not fit into 64 bits.
+    #[error("Op block {0} has an invalid timestamp")]
+    InvalidTimestamp(u64),
+    /// The input does not provide the output of a derived Op block.
+    #[error("Missing build output of Op block {0}")]
+    MissingBlockOutput(u64),
+    /// The build output of a derived Op block could not be verified.
+    #[error("Failed to verify the build output of Op block {0}")]
+    UnverifiedBlockOutput(u64),
+    /// The build output of a derived Op block was built from a different input.
+    #[error("Build output of Op block {0} has an invalid state input hash")]
+    StateInputMismatch(u64),
+    /// The checkpoint to resume from does not end at the Op head of the database.
+    #[error("Checkpoint ends at Op block {block_number} with hash {actual}, expected {expected}")]
+    CheckpointMismatch {
+        block_number: u64,
+        expected: B256,
+        actual: B256,
+    },
+    /// The Op head does not match the Op tail of the prior output.
+    #[error(
+        "Op head {} ({}) does not continue the prior output ending at {} ({})",
+        .actual.number, .actual.hash, .expected.number, .expected.hash
+    )]
+    PriorOpTailMismatch { expected: BlockId, actual: BlockId },
+    /// The traversed Eth chain does not continue the Eth tail of the prior output.
+    #[error(
+        "Eth block {} ({}) does not continue the prior output ending at {} ({})",
+        .actual.number, .actual.hash, .expected.number, .expected.hash
+    )]
+    PriorEthTailMismatch { expected: BlockId, actual: BlockId },
+    /// The prior output was derived with a different configuration.
+    #[error("Prior output was derived with a different {0}")]
+    PriorOutputMismatch(&'static str),
+    /// The checkpoint to resume from is not committed by the prior output.
+    #[error("Checkpoint with hash {0} is not committed by the prior output")]
+    UnverifiedCheckpoint(B256),
+    /// The next Op block activates a network upgrade with rules that are not supported.
+    #[error("Deriving the {upgrade} activation block {block_number} is not supported")]
+    UnsupportedActivationBlock {
+        upgrade: &'static str,
+        block_number: u64,
+    },
+}
+
+/// The Ethereum blocks a derived Optimism block originates from.
+#[derive(Debug, Clone, Copy, Deserialize, Eq, PartialEq, Serialize)]
+pub struct BlockProvenance {
+    /// Number of the derived Optimism block.
+    pub op_block_number: u64,
+    /// Number of the L1 origin of the block.
+    pub epoch_number: u64,
+    /// Number of the Ethereum block at which the batch of the block was derived.
+    pub batch_eth_block_number: u64,
 }
 
 #[cfg(target_os = "zkvm")]
 type ProviderFactory = ();
 
+/// The state of a [DeriveMachine] after a [DeriveMachine::derive] run, from which a new
+/// machine can continue the derivation given [DeriveInput::resume_checkpoint], so that a
+/// long range of blocks can be split across multiple guest executions without deriving
+/// again from the first Op head.
+///
+/// The Op head and the current Eth block of the checkpoint are verified against the
+/// database, but the pending batches and channels are not. Instead, the run ending at the
+/// checkpoint commits to its hash, which the run resuming from it checks against its
+/// prior output.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeriveCheckpoint {
+    /// Header of the last derived Op block.
+    pub op_head_block_header: Header,
+    /// Sequence number of the last derived Op block within its epoch.
+    pub op_block_seq_no: u64,
+    /// The state of the batcher, including its pending batches and channels.
+    pub op_batcher: BatcherCheckpoint,
+}
+
+impl DeriveCheckpoint {
+    /// Returns the hash committed to by the run ending at this checkpoint: the keccak
+    /// hash of its bincode encoding.
+    pub fn hash(&self) -> B256 {
+        keccak(bincode::serialize(self).expect("Failed to serialize checkpoint")).into()
+    }
+}
+
 /// Implementation of the actual derivation process.
 pub struct DeriveMachine<D> {
     /// Input for the derivation process.
@@ -120,17 +459,26 @@ pub struct DeriveMachine<D> {
     op_block_seq_no: u64,
     pub op_batcher: Batcher,
     pub provider_factory: Option<ProviderFactory>,
+    /// Provenance of all derived blocks, only recorded on the host.
+    #[cfg(not(target_os = "zkvm"))]
+    pub op_block_provenance: Vec<BlockProvenance>,
+    /// Payload attributes of all derived blocks, only recorded on the host.
+    #[cfg(not(target_os = "zkvm"))]
+    pub op_payload_attributes: Vec<OpPayloadAttributes>,
 }
 
 impl<D: BatcherDb> DeriveMachine<D> {
-    /// Creates a new instance of DeriveMachine.
+    /// Creates a new instance of DeriveMachine, resuming from the checkpoint of the input
+    /// if given.
     pub fn new(
         mut chain_config: ChainConfig,
         mut derive_input: DeriveInput<D>,
         provider_factory: Option<ProviderFactory>,
     ) -> Result<Self> {
-        derive_input.db.validate(&chain_config)?;
-
+        if let Some(checkpoint) = derive_input.resume_checkpoint.take() {
+            return Self::from_checkpoint(chain_config, derive_input, checkpoint, provider_factory);
+        }
+        validate_input(&chain_config, &derive_input)?;
         #[cfg(not(target_os = "zkvm"))]
         ensure!(provider_factory.is_some(), "Missing provider factory!");
 
@@ -151,53 +499,60 @@ impl<D: BatcherDb> DeriveMachine<D> {
         let l1_attributes_tx = &op_head
             .transactions
             .first()
-            .context("block is empty")?
+            .ok_or(DeriveError::EmptyOpHead(derive_input.op_head_block_no))?
             .essence;
         if let Err(err) = validate_l1_attributes_deposited_tx(&chain_config, l1_attributes_tx) {
-            bail!(
-                "First transaction in block is not a valid L1 attributes deposited transaction: {}",
-                err
-            )
-        }
-        // decode the L1 attributes deposited transaction
-        let set_l1_block_values = {
-            let call = OpSystemInfo::OpSystemInfoCalls::abi_decode(l1_attributes_tx.data(), true)
-                .context("invalid L1 attributes data")?;
-            match call {
-                OpSystemInfo::OpSystemInfoCalls::setL1BlockValues(x) => x,
+            return Err(DeriveError::InvalidL1Attributes {
+                block_number: derive_input.op_head_block_no,
+                reason: err.to_string(),
             }
-        };
+            .into());
+        }
+        // decode the L1 attributes deposited transaction in either format
+        let l1_block_values = L1BlockValues::decode(l1_attributes_tx.data())?;
 
-        let op_block_seq_no = set_l1_block_values.sequence_number;
+        let op_block_seq_no = l1_block_values.sequence_number();
 
         // check that the correct L1 block is in the database
-        let eth_block_no = set_l1_block_values.number;
+        let eth_block_no = l1_block_values.number();
         let eth_head = derive_input.db.get_full_eth_block(eth_block_no)?;
-        ensure!(
-            eth_head.block_header.hash() == set_l1_block_values.hash,
-            "Ethereum head block hash mismatch"
-        );
+        let eth_head_hash = eth_head.block_header.hash();
+        if eth_head_hash != l1_block_values.hash() {
+            return Err(DeriveError::EthHeadMismatch {
+                block_number: eth_block_no,
+                expected: l1_block_values.hash(),
+                actual: eth_head_hash,
+            }
+            .into());
+        }
         #[cfg(not(target_os = "zkvm"))]
         log::debug!(
             "Fetched Eth head (block no {}) {}",
             eth_block_no,
-            set_l1_block_values.hash
+            l1_block_values.hash()
         );
 
+        let op_head_timestamp: u64 = op_head
+            .block_header
+            .timestamp
+            .try_into()
+            .map_err(|_| DeriveError::InvalidTimestamp(op_head.block_header.number))?;
         let op_batcher = {
-            chain_config.system_config.batch_sender =
-                Address::from_slice(&set_l1_block_values.batcher_hash.as_slice()[12..]);
-            chain_config.system_config.l1_fee_overhead = set_l1_block_values.l1_fee_overhead;
-            chain_config.system_config.l1_fee_scalar = set_l1_block_values.l1_fee_scalar;
+            let (l1_fee_overhead, l1_fee_scalar) = l1_block_values.l1_fee();
+            chain_config.system_config.batch_sender = l1_block_values.batch_sender();
+            chain_config.system_config.l1_fee_overhead = l1_fee_overhead;
+            chain_config.system_config.l1_fee_scalar = l1_fee_scalar;
+            // the head uses the gas limit of the system config of its L1 origin
+            chain_config.system_config.gas_limit = op_head.block_header.gas_limit;
 
             Batcher::new(
                 chain_config,
                 L2BlockInfo {
                     hash: op_head_block_hash,
-                    timestamp: op_head.block_header.timestamp.try_into().unwrap(),
+                    timestamp: op_head_timestamp,
                     l1_origin: BlockId {
-                        number: set_l1_block_values.number,
-                        hash: set_l1_block_values.hash,
+                        number: l1_block_values.number(),
+                        hash: l1_block_values.hash(),
                     },
                 },
                 eth_head,
@@ -210,6 +565,83 @@ impl<D: BatcherDb> DeriveMachine<D> {
             op_block_seq_no,
             op_batcher,
             provider_factory,
+            #[cfg(not(target_os = "zkvm"))]
+            op_block_provenance: Vec::new(),
+            #[cfg(not(target_os = "zkvm"))]
+            op_payload_attributes: Vec::new(),
+        })
+    }
+
+    /// Creates a new instance of DeriveMachine that continues the derivation from the
+    /// given checkpoint of the prior run. The input must start at the last Op block
+    /// derived by that run.
+    fn from_checkpoint(
+        chain_config: ChainConfig,
+        mut derive_input: DeriveInput<D>,
+        checkpoint: DeriveCheckpoint,
+        provider_factory: Option<ProviderFactory>,
+    ) -> Result<Self> {
+        validate_input(&chain_config, &derive_input)?;
+        #[cfg(not(target_os = "zkvm"))]
+        ensure!(provider_factory.is_some(), "Missing provider factory!");
+        verify_checkpoint(derive_input.prior_output.as_ref(), checkpoint.hash())?;
+
+        let op_head_block_no = checkpoint.op_head_block_header.number;
+        if op_head_block_no != derive_input.op_head_block_no {
+            return Err(DeriveError::OpHeadMismatch {
+                expected: derive_input.op_head_block_no,
+                actual: op_head_block_no,
+            }
+            .into());
+        }
+        let op_head_hash = derive_input
+            .db
+            .get_full_op_block(op_head_block_no)?
+            .block_header
+            .hash();
+        let checkpoint_hash = checkpoint.op_head_block_header.hash();
+        if checkpoint_hash != op_head_hash {
+            return Err(DeriveError::CheckpointMismatch {
+                block_number: op_head_block_no,
+                expected: op_head_hash,
+                actual: checkpoint_hash,
+            }
+            .into());
+        }
+
+        // the next run continues with the Eth block following the current one
+        let state = &checkpoint.op_batcher.state;
+        let eth_head_hash = derive_input
+            .db
+            .get_full_eth_block(state.current_l1_block_number)?
+            .block_header
+            .hash();
+        if eth_head_hash != state.current_l1_block_hash {
+            return Err(DeriveError::EthHeadMismatch {
+                block_number: state.current_l1_block_number,
+                expected: state.current_l1_block_hash,
+                actual: eth_head_hash,
+            }
+            .into());
+        }
+
+        #[cfg(not(target_os = "zkvm"))]
+        log::debug!(
+            "Resuming from Op block {} at Eth block {}",
+            op_head_block_no,
+            state.current_l1_block_number
+        );
+
+        Ok(DeriveMachine {
+            derive_input,
+            op_head_block_header: checkpoint.op_head_block_header,
+            op_block_seq_no: checkpoint.op_block_seq_no,
+            op_batcher: Batcher::from_checkpoint(chain_config, checkpoint.op_batcher),
+            provider_factory,
+            #[cfg(not(target_os = "zkvm"))]
+            op_block_provenance: Vec::new(),
+            #[cfg(not(target_os = "zkvm"))]
+            op_payload_attributes: Vec::new(),
         })
     }
 
@@ -220,10 +652,13 @@ impl<D: BatcherDb> DeriveMachine<D> {
         #[cfg(target_os = "zkvm")]
         op_block_inputs.take();
 
-        ensure!(
-            self.op_head_block_header.number == self.derive_input.op_head_block_no,
-            "Op head block number mismatch!"
-        );
+        if self.op_head_block_header.number != self.derive_input.op_head_block_no {
+            return Err(DeriveError::OpHeadMismatch {
+                expected: self.derive_input.op_head_block_no,
+                actual: self.op_head_block_header.number,
+            }
+            .into());
+        }
         let target_block_no =
             self.derive_input.op_head_block_no + self.derive_input.op_derive_block_count as u64;
 
@@ -233,14 +668,46 @@ impl<D: BatcherDb> DeriveMachine<D> {
             hash: self.op_head_block_header.hash(),
         };
 
+        // A run continuing a prior output must start at its Op tail and must not skip or
+        // fork off its Eth tail
+        let rollup_config_hash = self
+            .derive_input
+            .rollup_config
+            .as_ref()
+            .map(RollupConfig::hash);
+        let (prior_eth_tail, prior_output_digest) = match &self.derive_input.prior_output {
+            Some(prior_output) => {
+                verify_prior_output(
+                    prior_output,
+                    op_head,
+                    self.current_eth_block(),
+                    self.derive_input.block_image_id,
+                    rollup_config_hash,
+                )?;
+                (
+                    Some(prior_output.eth_tail),
+                    Some(prior_output.journal_digest()?),
+                )
+            }
+            None => (None, None),
+        };
+
         let mut derived_op_blocks = Vec::new();
+        let mut batch_provenance = Vec::new();
         let mut process_next_eth_block = false;
+        let mut max_timestamp_reached = false;
+
+        // The current Eth block has already been consumed by Batcher::new()
+        let mut eth_accumulator = self
+            .derive_input
+            .eth_accumulator
+            .then(|| HeaderAccumulator::new(self.current_eth_block()));
 
         #[cfg(target_os = "zkvm")]
         let mut op_block_output_iter =
             core::mem::take(&mut self.derive_input.op_block_outputs).into_iter();
 
-        while self.op_head_block_header.number < target_block_no {
+        while !max_timestamp_reached && self.op_head_block_header.number < target_block_no {
             #[cfg(not(target_os = "zkvm"))]
             log::trace!(
                 "op_block_no = {}, eth_block_no = {}",
@@ -260,12 +727,26 @@ impl<D: BatcherDb> DeriveMachine<D> {
                 self.op_batcher
                     .process_l1_block(eth_block)
                     .context("failed to create batcher transactions")?;
+                // only the block of the current epoch can be requested again before the
+                // epoch advances, any other block is provided again when it is needed
+                self.derive_input
+                    .db
+                    .release_eth_blocks(self.op_batcher.state.epoch.number);
+
+                if let Some(prior_eth_tail) = prior_eth_tail {
+                    verify_prior_eth_tail(prior_eth_tail, self.current_eth_block())?;
+                }
+
+                if let Some(eth_accumulator) = &mut eth_accumulator {
+                    eth_accumulator.append(self.current_eth_block())?;
+                }
             }
             process_next_eth_block = true;
 
             // Process batches
             while let Some(op_batch) = self.op_batcher.read_batch()? {
                 // Process the batch
+                let batch_source = self.op_batcher.batch_source();
 
                 #[cfg(not(target_os = "zkvm"))]
                 log::debug!(
@@ -277,82 +758,40 @@ impl<D: BatcherDb> DeriveMachine<D> {
                     op_batch.0.parent_hash,
                 );
 
-                // Update sequence number (and fetch deposits if start of new epoch)
-                let l2_safe_head = &self.op_batcher.state.safe_head;
-                let deposits = if l2_safe_head.l1_origin.number != op_batch.0.epoch_num {
-                    self.op_block_seq_no = 0;
-                    self.op_batcher.state.do_next_epoch()?;
-
-                    self.op_batcher.state.epoch.deposits.clone()
-                } else {
-                    self.op_block_seq_no += 1;
-
-                    vec![]
-                };
-
-                let l1_epoch_header_mix_hash = self
-                    .derive_input
-                    .db
-                    .get_full_eth_block(op_batch.0.epoch_num)
-                    .context("eth block not found")?
-                    .block_header
-                    .mix_hash;
-
-                // From the spec:
-                // The first transaction MUST be a L1 attributes deposited transaction,
-                // followed by an array of zero-or-more user-deposited transactions.
-                let l1_attributes_tx = self.derive_l1_attributes_deposited_tx(&op_batch);
-
-                let mut decoded_batch_transactions = vec![];
-                let mut decoding_error = false;
-                for raw_tx in &op_batch.0.transactions {
-                    match Transaction::<OptimismTxEssence>::decode_bytes(raw_tx) {
-                        Ok(tx) => {
-                            decoded_batch_transactions.push(tx);
-                        }
-                        Err(_err) => {
-                            #[cfg(not(target_os = "zkvm"))]
-                            log::warn!("Skipping undecodable transaction: {:#}", _err);
-                            decoding_error = true;
-                            break;
-                        }
+                // Stop at the first block beyond the requested time window
+                if let Some(max_timestamp) = self.derive_input.op_derive_max_timestamp {
+                    if op_batch.0.timestamp > max_timestamp {
+                        #[cfg(not(target_os = "zkvm"))]
+                        log::info!(
+                            "Op block {} exceeds the maximum timestamp {}",
+                            self.op_head_block_header.number + 1,
+                            max_timestamp
+                        );
+                        max_timestamp_reached = true;
+                        break;
                     }
                 }
-                if decoding_error {
+
+                let Some(payload_attributes) = self.derive_payload_attributes(&op_batch)? else {
                     continue;
-                }
+                };
 
-                let derived_transactions: Vec<_> = once(l1_attributes_tx)
-                    .chain(deposits)
-                    .chain(decoded_batch_transactions)
-                    .collect();
-                let derived_transactions_rlp = derived_transactions
-                    .iter()
-                    .map(alloy_rlp::encode)
-                    .enumerate();
+                let derived_transactions_rlp =
+                    encode_list_with_lengths(&payload_attributes.transactions)
+                        .into_items()
+                        .into_iter()
+                        .enumerate();
 
                 let mut tx_trie = MptNode::default();
                 for (tx_no, tx) in derived_transactions_rlp {
                     tx_trie.insert(&alloy_rlp::encode(tx_no), tx)?;
                 }
 
-                let new_op_head_input = BlockBuildInput {
-                    state_input: StateInput {
-                        parent_header: self.op_head_block_header.clone(),
-                        beneficiary: self.op_batcher.config().sequencer_fee_vault,
-                        gas_limit: self.op_batcher.config().system_config.gas_limit,
-                        timestamp: U256::from(op_batch.0.timestamp),
-                        extra_data: Default::default(),
-                        mix_hash: l1_epoch_header_mix_hash,
-                        transactions: derived_transactions,
-                        withdrawals: vec![],
-                    },
-                    // initializing these fields is not needed here
-                    parent_state_trie: Default::default(),
-                    parent_storage: Default::default(),
-                    contracts: vec![],
-                    ancestor_headers: vec![],
-                };
+                #[cfg(not(target_os = "zkvm"))]
+                self.op_payload_attributes.push(payload_attributes.clone());
+
+                let new_op_head_input =
+                    payload_attributes.into_block_build_input(self.op_head_block_header.clone());
 
                 // host: go run the preflight and queue up the input data (using RLP decoded
                 // transactions)
@@ -363,12 +802,13 @@ impl<D: BatcherDb> DeriveMachine<D> {
                     let provider_db = ProviderDb::new(
                         self.provider_factory
                             .as_ref()
-                            .unwrap()
+                            .context("Missing provider factory!")?
                             .create_provider(self.op_head_block_header.number)?,
                         self.op_head_block_header.number,
                     );
+                    let chain_spec = self.op_batcher.config().chain_spec;
                     let preflight_data = OptimismStrategy::preflight_with_local_data(
-                        &OP_MAINNET_CHAIN_SPEC,
+                        chain_spec,
                         provider_db,
                         new_op_head_input.clone(),
                     )
@@ -392,36 +832,47 @@ impl<D: BatcherDb> DeriveMachine<D> {
                         inputs_vec.push(executable_input.clone());
                     }
 
-                    OptimismStrategy::build_from(&OP_MAINNET_CHAIN_SPEC, executable_input)?
-                        .with_state_hashed()
+                    OptimismStrategy::build_from(chain_spec, executable_input)?.with_state_hashed()
                 };
                 // guest: ask for receipt about provided block build output (compressed state trie
                 // expected)
                 #[cfg(target_os = "zkvm")]
                 let op_block_output = {
-                    let output = op_block_output_iter.next().unwrap();
+                    let block_number = self.op_head_block_header.number + 1;
+                    let output = op_block_output_iter
+                        .next()
+                        .ok_or(DeriveError::MissingBlockOutput(block_number))?;
                     // A valid receipt should be provided for block building results
                     let builder_journal =
-                        to_vec(&output).expect("Failed to encode builder journal");
+                        to_vec(&output).context("Failed to encode builder journal")?;
                     env::verify(
                         Digest::from(self.derive_input.block_image_id),
                         &builder_journal,
                     )
-                    .expect("Failed to validate block build output");
+                    .map_err(|_| DeriveError::UnverifiedBlockOutput(block_number))?;
                     output
                 };
 
                 // Ensure that the output came from the expected input
-                ensure!(
-                    new_op_head_input.state_input.hash() == op_block_output.state_input_hash(),
-                    "Invalid state input hash"
-                );
+                if new_op_head_input.state_input.hash() != op_block_output.state_input_hash() {
+                    return Err(DeriveError::StateInputMismatch(
+                        self.op_head_block_header.number + 1,
+                    )
+                    .into());
+                }
                 match op_block_output {
                     BlockBuildOutput::SUCCESS {
                         hash: new_block_hash,
                         head: new_block_head,
                         ..
                     } => {
+                        self.op_batcher.record_trace(|| {
+                            TraceEvent::BlockDerived(BlockId {
+                                number: new_block_head.number,
+                                hash: new_block_hash,
+                            })
+                        });
+
                         // obtain verified op block header
                         #[cfg(not(target_os = "zkvm"))]
                         log::info!(
@@ -430,19 +881,35 @@ impl<D: BatcherDb> DeriveMachine<D> {
                             new_block_hash
                         );
 
+                        #[cfg(not(target_os = "zkvm"))]
+                        self.op_block_provenance.push(BlockProvenance {
+                            op_block_number: new_block_head.number,
+                            epoch_number: self.op_batcher.state.epoch.number,
+                            batch_eth_block_number: self.op_batcher.state.current_l1_block_number,
+                        });
+
                         self.op_batcher.state.safe_head = L2BlockInfo {
                             hash: new_block_hash,
-                            timestamp: new_block_head.timestamp.try_into().unwrap(),
+                            timestamp: new_block_head.timestamp.try_into().map_err(|_| {
+                                DeriveError::InvalidTimestamp(new_block_head.number)
+                            })?,
                             l1_origin: BlockId {
                                 number: self.op_batcher.state.epoch.number,
                                 hash: self.op_batcher.state.epoch.hash,
                             },
                         };
 
-                        derived_op_blocks.push(BlockId {
+                        let op_block = BlockId {
                             number: new_block_head.number,
                             hash: new_block_hash,
-                        });
+                        };
+                        if self.derive_input.batch_provenance {
+                            batch_provenance.push(BatchProvenance {
+                                op_block,
+                                source: batch_source,
+                            });
+                        }
+                        derived_op_blocks.push(op_block);
                         self.op_head_block_header = new_block_head;
 
                         if self.op_head_block_header.number == target_block_no {
@@ -452,42 +919,276 @@ impl<D: BatcherDb> DeriveMachine<D> {
                     BlockBuildOutput::FAILURE { .. } => {
                         #[cfg(not(target_os = "zkvm"))]
                         log::warn!("Failed to build block from batch");
+                        self.op_batcher.record_trace(|| TraceEvent::BlockFailed {
+                            timestamp: op_batch.0.timestamp,
+                        });
                     }
                 };
             }
         }
 
+        let eth_tail = self.current_eth_block();
+        // the Eth tails of chained outputs never decrease
+        if let Some(prior_eth_tail) = prior_eth_tail {
+            if eth_tail.number < prior_eth_tail.number {
+                return Err(DeriveError::PriorEthTailMismatch {
+                    expected: prior_eth_tail,
+                    actual: eth_tail,
+                }
+                .into());
+            }
+        }
+        let l1_anchor = match self.derive_input.op_anchor_block_no {
+            Some(op_block_no) => Some(self.anchor_eth_tail(op_block_no, eth_tail)?),
+            None => None,
+        };
+        let checkpoint_hash = self
+            .derive_input
+            .checkpoint
+            .then(|| self.checkpoint().hash());
+
         Ok(DeriveOutput {
-            eth_tail: BlockId {
-                number: self.op_batcher.state.current_l1_block_number,
-                hash: self.op_batcher.state.current_l1_block_hash,
-            },
+            eth_tail,
             op_head,
             derived_op_blocks,
             block_image_id: self.derive_input.block_image_id,
+            eth_accumulator,
+            l1_anchor,
+            batch_provenance,
+            rollup_config_hash,
+            prior_output_digest,
+            checkpoint_hash,
         })
     }
 
+    /// Returns the last Eth block processed by the batcher.
+    fn current_eth_block(&self) -> BlockId {
+        BlockId {
+            number: self.op_batcher.state.current_l1_block_number,
+            hash: self.op_batcher.state.current_l1_block_hash,
+        }
+    }
+
+    /// Derives the payload attributes of the next Optimism block from the given batch,
+    /// advancing the epoch if the batch starts a new one. Returns `None` if the batch
+    /// contains a transaction that cannot be decoded.
+    fn derive_payload_attributes(
+        &mut self,
+        op_batch: &Batch,
+    ) -> Result<Option<OpPayloadAttributes>> {
+        verify_supported_block(
+            self.op_batcher.config(),
+            self.op_head_block_header.number + 1,
+            op_batch.0.timestamp,
+        )?;
+
+        // Update sequence number (and fetch deposits if start of new epoch)
+        let l2_safe_head = &self.op_batcher.state.safe_head;
+        let deposits = if l2_safe_head.l1_origin.number != op_batch.0.epoch_num {
+            self.op_block_seq_no = 0;
+            self.op_batcher.state.do_next_epoch()?;
+            let epoch = &self.op_batcher.state.epoch;
+            let next_epoch = BlockId {
+                number: epoch.number,
+                hash: epoch.hash,
+            };
+            self.op_batcher
+                .record_trace(|| TraceEvent::NextEpoch(next_epoch));
+
+            let eth_block = self
+                .derive_input
+                .db
+                .get_full_eth_block(self.op_batcher.state.epoch.number)
+                .context("eth block not found")?;
+            let deposits = self
+                .op_batcher
+                .epoch_deposits(eth_block)
+                .context("failed to extract epoch deposits")?;
+            // blocks of past epochs are never requested again
+            self.derive_input.db.release_eth_blocks(next_epoch.number);
+
+            deposits
+        } else {
+            self.op_block_seq_no += 1;
+
+            vec![]
+        };
+
+        // the epoch block may have been released and provided again, so verify it
+        let epoch = &self.op_batcher.state.epoch;
+        let l1_epoch_header = &self
+            .derive_input
+            .db
+            .get_full_eth_block(op_batch.0.epoch_num)
+            .context("eth block not found")?
+            .block_header;
+        ensure!(
+            l1_epoch_header.number == epoch.number && l1_epoch_header.hash() == epoch.hash,
+            "Eth block {} does not match the epoch",
+            op_batch.0.epoch_num
+        );
+        let l1_epoch_header_mix_hash = l1_epoch_header.mix_hash;
+
+        // From the spec:
+        // The first transaction MUST be a L1 attributes deposited transaction,
+        // followed by an array of zero-or-more user-deposited transactions.
+        let l1_attributes_tx = self.derive_l1_attributes_deposited_tx(op_batch)?;
+
+        let mut decoded_batch_transactions = vec![];
+        for raw_tx in &op_batch.0.transactions {
+            match Transaction::<OptimismTxEssence>::decode_bytes(raw_tx) {
+                Ok(tx) => {
+                    decoded_batch_transactions.push(tx);
+                }
+                Err(_err) => {
+                    #[cfg(not(target_os = "zkvm"))]
+                    log::warn!("Skipping undecodable transaction: {:#}", _err);
+                    return Ok(None);
+                }
+            }
+        }
+
+        let deposit_count = deposits.len();
+        let transactions: Vec<_> = once(l1_attributes_tx)
+            .chain(deposits)
+            .chain(decoded_batch_transactions)
+            .collect();
+        deposits::validate_order(&transactions, deposit_count)
+            .context("invalid order of derived transactions")?;
+
+        // the gas limit follows the system config of the L1 origin
+        let gas_limit = self
+            .op_batcher
+            .effective_gas_limit(op_batch.0.epoch_num)
+            .context("no gas limit for the epoch")?;
+
+        Ok(Some(OpPayloadAttributes {
+            timestamp: op_batch.0.timestamp,
+            prev_randao: l1_epoch_header_mix_hash,
+            suggested_fee_recipient: self.op_batcher.config().sequencer_fee_vault,
+            transactions,
+            no_tx_pool: true,
+            gas_limit,
+        }))
+    }
+
+    /// Returns the state of the derivation, from which a new machine can continue given
+    /// [DeriveInput::resume_checkpoint].
+    pub fn checkpoint(&self) -> DeriveCheckpoint {
+        DeriveCheckpoint {
+            op_head_block_header: self.op_head_block_header.clone(),
+            op_block_seq_no: self.op_block_seq_no,
+            op_batcher: self.op_batcher.checkpoint(),
+        }
+    }
+
+    /// Returns the details of the derivation so far that are not part of the
+    /// [DeriveOutput].
+    pub fn private_output(&self) -> DerivePrivateOutput {
+        DerivePrivateOutput {
+            dropped_transactions: self.op_batcher.dropped_transactions().to_vec(),
+            skipped_frames: self.op_batcher.skipped_frames().to_vec(),
+            dropped_channels: self.op_batcher.dropped_channels().to_vec(),
+            dropped_batches: self.op_batcher.dropped_batches().to_vec(),
+            debug_trace: self.op_batcher.debug_trace().to_vec(),
+        }
+    }
+
+    /// Verifies that the Ethereum block stored in the L1Block predeploy by the given
+    /// Optimism block descends from the Ethereum tail.
+    fn anchor_eth_tail(&mut self, op_block_no: u64, eth_tail: BlockId) -> Result<L1Anchor> {
+        let op_block = self.derive_input.db.get_full_op_block(op_block_no)?;
+        let l1_attributes_tx = &op_block
+            .transactions
+            .first()
+            .context("anchor block is empty")?
+            .essence;
+        validate_l1_attributes_deposited_tx(self.op_batcher.config(), l1_attributes_tx)
+            .context("anchor block has no valid L1 attributes deposited transaction")?;
+        let l1_block_values = L1BlockValues::decode(l1_attributes_tx.data())?;
+        let anchored = BlockId {
+            number: l1_block_values.number(),
+            hash: l1_block_values.hash(),
+        };
+        ensure!(
+            anchored.number >= eth_tail.number,
+            "Anchored Eth block {} precedes the Eth tail {}",
+            anchored.number,
+            eth_tail.number
+        );
+
+        // follow the parent hashes from the tail up to the anchored block
+        let mut hash = eth_tail.hash;
+        for block_no in (eth_tail.number + 1)..=anchored.number {
+            let header = &self
+                .derive_input
+                .db
+                .get_full_eth_block(block_no)?
+                .block_header;
+            ensure!(
+                header.parent_hash == hash,
+                "Eth block {} does not descend from the Eth tail",
+                block_no
+            );
+            hash = header.hash();
+        }
+        ensure!(
+            hash == anchored.hash,
+            "Anchored Eth block {} does not descend from the Eth tail",
+            anchored.number
+        );
+
+        Ok(L1Anchor {
+            op_block: BlockId {
+                number: op_block_no,
+                hash: op_block.block_header.hash(),
+            },
+            eth_block: anchored,
+        })
+    }
+
+    /// Derives the L1 attributes deposited transaction of the block of the given batch,
+    /// using the Ecotone format for all blocks after the Ecotone activation block.
     fn derive_l1_attributes_deposited_tx(
         &mut self,
         op_batch: &Batch,
-    ) -> Transaction<OptimismTxEssence> {
+    ) -> Result<Transaction<OptimismTxEssence>> {
+        let config = self.op_batcher.config();
+        let epoch = &self.op_batcher.state.epoch;
         let batcher_hash = {
             let all_zero: FixedBytes<12> = FixedBytes::ZERO;
-            all_zero.concat_const::<20, 32>(self.op_batcher.config().system_config.batch_sender.0)
+            all_zero.concat_const::<20, 32>(config.system_config.batch_sender.0)
         };
 
-        let set_l1_block_values =
-            OpSystemInfo::OpSystemInfoCalls::setL1BlockValues(OpSystemInfo::setL1BlockValuesCall {
-                number: self.op_batcher.state.epoch.number,
-                timestamp: self.op_batcher.state.epoch.timestamp,
-                basefee: self.op_batcher.state.epoch.base_fee_per_gas,
-                hash: self.op_batcher.state.epoch.hash,
-                sequence_number: self.op_block_seq_no,
-                batcher_hash,
-                l1_fee_overhead: self.op_batcher.config().system_config.l1_fee_overhead,
-                l1_fee_scalar: self.op_batcher.config().system_config.l1_fee_scalar,
-            });
+        let timestamp = op_batch.0.timestamp;
+        let l1_block_values =
+            if config.is_ecotone(timestamp) && !config.is_ecotone_activation_block(timestamp) {
+                let (base_fee_scalar, blob_base_fee_scalar) =
+                    l1_block_info::ecotone_scalars(config.system_config.l1_fee_scalar)
+                        .context("invalid L1 fee scalar")?;
+                L1BlockValues::Ecotone(L1BlockValuesEcotone {
+                    base_fee_scalar,
+                    blob_base_fee_scalar,
+                    sequence_number: self.op_block_seq_no,
+                    timestamp: epoch.timestamp,
+                    number: epoch.number,
+                    basefee: epoch.base_fee_per_gas,
+                    blob_base_fee: epoch.blob_base_fee,
+                    hash: epoch.hash,
+                    batcher_hash,
+                })
+            } else {
+                L1BlockValues::Bedrock(OpSystemInfo::setL1BlockValuesCall {
+                    number: epoch.number,
+                    timestamp: epoch.timestamp,
+                    basefee: epoch.base_fee_per_gas,
+                    hash: epoch.hash,
+                    sequence_number: self.op_block_seq_no,
+                    batcher_hash,
+                    l1_fee_overhead: config.system_config.l1_fee_overhead,
+                    l1_fee_scalar: config.system_config.l1_fee_scalar,
+                })
+            };
 
         let source_hash: B256 = {
             let l1_block_hash = op_batch.0.epoch_hash.0;
@@ -496,8 +1197,7 @@ impl<D: BatcherDb> DeriveMachine<D> {
             keccak([ONE.to_be_bytes::<32>(), source_hash_sequencing].concat()).into()
         };
 
-        let config = self.op_batcher.config();
-        Transaction {
+        Ok(Transaction {
             essence: OptimismTxEssence::OptimismDeposited(TxEssenceOptimismDeposited {
                 source_hash,
                 from: config.l1_attributes_depositor,
@@ -506,13 +1206,109 @@ impl<D: BatcherDb> DeriveMachine<D> {
                 value: Default::default(),
                 gas_limit: uint!(1_000_000_U256),
                 is_system_tx: false,
-                data: set_l1_block_values.abi_encode().into(),
+                data: l1_block_values.encode().into(),
             }),
             signature: Default::default(),
-        }
+        })
+    }
+}
+
+/// Verifies that the Op block with the given number and timestamp does not activate a
+/// network upgrade whose activation rules are not supported: the Canyon activation block
+/// deploys a contract outside of any transaction, the Ecotone activation block contains
+/// network upgrade transactions that are not derived.
+fn verify_supported_block(
+    config: &ChainConfig,
+    block_number: u64,
+    timestamp: u64,
+) -> Result<(), DeriveError> {
+    let upgrade = if config.is_canyon_activation_block(timestamp) {
+        "Canyon"
+    } else if config.is_ecotone_activation_block(timestamp) {
+        "Ecotone"
+    } else {
+        return Ok(());
+    };
+    Err(DeriveError::UnsupportedActivationBlock {
+        upgrade,
+        block_number,
+    })
+}
+
+/// Verifies that a derivation starting at the given Op and Eth heads, with the given
+/// configuration, continues the prior output.
+fn verify_prior_output(
+    prior_output: &DeriveOutput,
+    op_head: BlockId,
+    eth_head: BlockId,
+    block_image_id: ImageId,
+    rollup_config_hash: Option<B256>,
+) -> Result<(), DeriveError> {
+    if op_head != prior_output.op_tail() {
+        return Err(DeriveError::PriorOpTailMismatch {
+            expected: prior_output.op_tail(),
+            actual: op_head,
+        });
+    }
+    if prior_output.block_image_id != block_image_id {
+        return Err(DeriveError::PriorOutputMismatch("block image id"));
+    }
+    if prior_output.rollup_config_hash != rollup_config_hash {
+        return Err(DeriveError::PriorOutputMismatch("rollup config"));
+    }
+
+    // the Eth head has already been consumed, so it must not lie beyond the Eth tail
+    if eth_head.number > prior_output.eth_tail.number {
+        return Err(DeriveError::PriorEthTailMismatch {
+            expected: prior_output.eth_tail,
+            actual: eth_head,
+        });
+    }
+    verify_prior_eth_tail(prior_output.eth_tail, eth_head)
+}
+
+/// Verifies that the traversed Eth block does not fork off the Eth tail of the prior
+/// output.
+fn verify_prior_eth_tail(prior_eth_tail: BlockId, eth_block: BlockId) -> Result<(), DeriveError> {
+    if eth_block.number == prior_eth_tail.number && eth_block.hash != prior_eth_tail.hash {
+        return Err(DeriveError::PriorEthTailMismatch {
+            expected: prior_eth_tail,
+            actual: eth_block,
+        });
+    }
+    Ok(())
+}
+
+/// Verifies that the prior output commits to the checkpoint with the given hash.
+fn verify_checkpoint(
+    prior_output: Option<&DeriveOutput>,
+    checkpoint_hash: B256,
+) -> Result<(), DeriveError> {
+    match prior_output {
+        Some(prior_output) if prior_output.checkpoint_hash == Some(checkpoint_hash) => Ok(()),
+        _ => Err(DeriveError::UnverifiedCheckpoint(checkpoint_hash)),
     }
 }
 
+/// Validates the database of the derivation input.
+fn validate_input<D: BatcherDb>(
+    chain_config: &ChainConfig,
+    derive_input: &DeriveInput<D>,
+) -> Result<()> {
+    derive_input.db.validate(chain_config)?;
+    // ignoring mismatches is only meant for exploratory runs on the host
+    #[cfg(target_os = "zkvm")]
+    ensure!(
+        derive_input.db.validation_policy() == ValidationPolicy::Strict,
+        "The guest only supports the strict validation policy"
+    );
+    #[cfg(not(target_os = "zkvm"))]
+    if derive_input.db.validation_policy() == ValidationPolicy::Permissive {
+        log::warn!("Ignoring non-critical mismatches of the data; the result may not be provable");
+    }
+    Ok(())
+}
+
 fn validate_l1_attributes_deposited_tx(config: &ChainConfig, tx: &OptimismTxEssence) -> Result<()> {
     match tx {
         OptimismTxEssence::Ethereum(_) => {
@@ -536,3 +1332,157 @@ fn validate_l1_attributes_deposited_tx(config: &ChainConfig, tx: &OptimismTxEsse
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block_id(number: u64, byte: u8) -> BlockId {
+        BlockId {
+            number,
+            hash: B256::repeat_byte(byte),
+        }
+    }
+
+    fn prior_output() -> DeriveOutput {
+        DeriveOutput {
+            eth_tail: block_id(100, 1),
+            op_head: block_id(40, 2),
+            derived_op_blocks: vec![block_id(41, 3), block_id(42, 4)],
+            block_image_id: [7; 8],
+            eth_accumulator: None,
+            l1_anchor: None,
+            batch_provenance: vec![],
+            rollup_config_hash: None,
+            prior_output_digest: None,
+            checkpoint_hash: None,
+        }
+    }
+
+    #[test]
+    fn prior_output_continued() {
+        let prior = prior_output();
+        verify_prior_output(&prior, block_id(42, 4), block_id(98, 5), [7; 8], None).unwrap();
+        verify_prior_output(&prior, block_id(42, 4), block_id(100, 1), [7; 8], None).unwrap();
+
+        // without derived blocks, the run continues at the Op head
+        let prior = DeriveOutput {
+            derived_op_blocks: vec![],
+            ..prior_output()
+        };
+        verify_prior_output(&prior, block_id(40, 2), block_id(98, 5), [7; 8], None).unwrap();
+    }
+
+    #[test]
+    fn prior_op_tail_mismatch() {
+        let prior = prior_output();
+        for op_head in [block_id(41, 3), block_id(42, 5), block_id(43, 4)] {
+            assert_eq!(
+                verify_prior_output(&prior, op_head, block_id(98, 5), [7; 8], None),
+                Err(DeriveError::PriorOpTailMismatch {
+                    expected: block_id(42, 4),
+                    actual: op_head,
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn prior_eth_tail_mismatch() {
+        let prior = prior_output();
+        // the Eth head lies beyond the Eth tail
+        assert_eq!(
+            verify_prior_output(&prior, block_id(42, 4), block_id(101, 5), [7; 8], None),
+            Err(DeriveError::PriorEthTailMismatch {
+                expected: block_id(100, 1),
+                actual: block_id(101, 5),
+            })
+        );
+        // the Eth head forks off the Eth tail
+        assert_eq!(
+            verify_prior_output(&prior, block_id(42, 4), block_id(100, 6), [7; 8], None),
+            Err(DeriveError::PriorEthTailMismatch {
+                expected: block_id(100, 1),
+                actual: block_id(100, 6),
+            })
+        );
+        // a traversed Eth block forks off the Eth tail
+        assert_eq!(
+            verify_prior_eth_tail(prior.eth_tail, block_id(100, 6)),
+            Err(DeriveError::PriorEthTailMismatch {
+                expected: block_id(100, 1),
+                actual: block_id(100, 6),
+            })
+        );
+        verify_prior_eth_tail(prior.eth_tail, block_id(99, 6)).unwrap();
+        verify_prior_eth_tail(prior.eth_tail, block_id(101, 6)).unwrap();
+    }
+
+    #[test]
+    fn prior_output_mismatch() {
+        let prior = prior_output();
+        assert_eq!(
+            verify_prior_output(&prior, block_id(42, 4), block_id(98, 5), [8; 8], None),
+            Err(DeriveError::PriorOutputMismatch("block image id"))
+        );
+        assert_eq!(
+            verify_prior_output(
+                &prior,
+                block_id(42, 4),
+                block_id(98, 5),
+                [7; 8],
+                Some(B256::repeat_byte(9))
+            ),
+            Err(DeriveError::PriorOutputMismatch("rollup config"))
+        );
+    }
+
+    #[test]
+    fn unsupported_activation_block() {
+        let config = ChainConfig::optimism();
+        let canyon_time = config.canyon_time.unwrap();
+        assert_eq!(
+            verify_supported_block(&config, 42, canyon_time),
+            Err(DeriveError::UnsupportedActivationBlock {
+                upgrade: "Canyon",
+                block_number: 42,
+            })
+        );
+        let ecotone_time = config.ecotone_time.unwrap();
+        assert_eq!(
+            verify_supported_block(&config, 43, ecotone_time),
+            Err(DeriveError::UnsupportedActivationBlock {
+                upgrade: "Ecotone",
+                block_number: 43,
+            })
+        );
+        for timestamp in [
+            canyon_time - config.blocktime,
+            canyon_time + config.blocktime,
+        ] {
+            verify_supported_block(&config, 42, timestamp).unwrap();
+        }
+    }
+
+    #[test]
+    fn unverified_checkpoint() {
+        let checkpoint_hash = B256::repeat_byte(9);
+        let prior = DeriveOutput {
+            checkpoint_hash: Some(checkpoint_hash),
+            ..prior_output()
+        };
+        verify_checkpoint(Some(&prior), checkpoint_hash).unwrap();
+        assert_eq!(
+            verify_checkpoint(Some(&prior), B256::repeat_byte(8)),
+            Err(DeriveError::UnverifiedCheckpoint(B256::repeat_byte(8)))
+        );
+        assert_eq!(
+            verify_checkpoint(Some(&prior_output()), checkpoint_hash),
+            Err(DeriveError::UnverifiedCheckpoint(checkpoint_hash))
+        );
+        assert_eq!(
+            verify_checkpoint(None, checkpoint_hash),
+            Err(DeriveError::UnverifiedCheckpoint(checkpoint_hash))
+        );
+    }
+}
@@ -0,0 +1,252 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{collections::BTreeMap, fs, path::Path};
+
+use anyhow::{Context, Result};
+use once_cell::sync::Lazy;
+
+use super::{config::ChainConfig, rollup_config::RollupConfig};
+
+include!(concat!(env!("OUT_DIR"), "/chains.rs"));
+
+/// The registry of the built-in chains and the chains embedded at build time.
+static EMBEDDED_REGISTRY: Lazy<ChainRegistry> =
+    Lazy::new(|| ChainRegistry::embedded().expect("Invalid embedded chain config"));
+
+/// A chain of the [ChainRegistry].
+#[derive(Debug, Clone)]
+pub struct RegisteredChain {
+    /// The derivation configuration of the chain.
+    pub config: ChainConfig,
+    /// The rollup config the configuration was built from; `None` for built-in chains.
+    pub rollup_config: Option<RollupConfig>,
+}
+
+impl RegisteredChain {
+    /// Returns the L2 chain ID of the chain.
+    pub fn chain_id(&self) -> u64 {
+        self.config.chain_spec.chain_id()
+    }
+}
+
+/// The known OP Stack chains by name.
+///
+/// It contains the built-in chains, the op-node `rollup.json` files embedded by the build
+/// script and any files merged at runtime. A chain replaces every chain with the same
+/// name or chain ID, so that a file can override any of the chains.
+#[derive(Debug, Clone, Default)]
+pub struct ChainRegistry {
+    chains: BTreeMap<String, RegisteredChain>,
+}
+
+impl ChainRegistry {
+    /// Returns the registry of the built-in chains.
+    pub fn builtin() -> Self {
+        let mut registry = Self::default();
+        for (name, config) in [
+            ("optimism", ChainConfig::optimism()),
+            ("base", ChainConfig::base()),
+            ("op-sepolia", ChainConfig::op_sepolia()),
+        ] {
+            registry.insert(
+                name,
+                RegisteredChain {
+                    config,
+                    rollup_config: None,
+                },
+            );
+        }
+        registry
+    }
+
+    /// Returns the registry of the built-in chains and the chains embedded at build time.
+    pub fn embedded() -> Result<Self> {
+        let mut registry = Self::builtin();
+        for (name, json) in EMBEDDED_CHAINS {
+            registry
+                .insert_json(name, json)
+                .with_context(|| format!("Invalid embedded chain '{}'", name))?;
+        }
+        Ok(registry)
+    }
+
+    /// Adds the chain of the given op-node `rollup.json`, replacing every chain with the
+    /// same name or chain ID.
+    pub fn insert_json(&mut self, name: &str, json: &str) -> Result<()> {
+        let rollup_config = RollupConfig::from_json(json)?;
+        let config = rollup_config.chain_config()?;
+        self.insert(
+            name,
+            RegisteredChain {
+                config,
+                rollup_config: Some(rollup_config),
+            },
+        );
+        Ok(())
+    }
+
+    /// Merges the given op-node `rollup.json` file into the registry, naming the chain
+    /// after the file.
+    pub fn merge_file(&mut self, path: &Path) -> Result<()> {
+        let name = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .with_context(|| format!("Invalid chain file name '{}'", path.display()))?;
+        let json = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read '{}'", path.display()))?;
+        self.insert_json(name, &json)
+            .with_context(|| format!("Invalid chain file '{}'", path.display()))
+    }
+
+    /// Merges all `.json` files of the given directory into the registry.
+    pub fn merge_dir(&mut self, dir: &Path) -> Result<()> {
+        let entries =
+            fs::read_dir(dir).with_context(|| format!("Failed to read '{}'", dir.display()))?;
+        let mut paths = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "json") {
+                paths.push(path);
+            }
+        }
+        // merge in a deterministic order
+        paths.sort();
+        for path in paths {
+            self.merge_file(&path)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the chain with the given name.
+    pub fn by_name(&self, name: &str) -> Option<&RegisteredChain> {
+        self.chains.get(name)
+    }
+
+    /// Returns the chain with the given L2 chain ID.
+    pub fn by_chain_id(&self, chain_id: u64) -> Option<&RegisteredChain> {
+        self.chains
+            .values()
+            .find(|chain| chain.chain_id() == chain_id)
+    }
+
+    /// Returns the names of all chains in alphabetical order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.chains.keys().map(String::as_str)
+    }
+
+    fn insert(&mut self, name: &str, chain: RegisteredChain) {
+        let chain_id = chain.chain_id();
+        self.chains
+            .retain(|other_name, other| other_name != name && other.chain_id() != chain_id);
+        self.chains.insert(name.to_string(), chain);
+    }
+}
+
+impl ChainConfig {
+    /// Returns the configuration of the built-in or embedded chain with the given name.
+    pub fn by_name(name: &str) -> Option<ChainConfig> {
+        EMBEDDED_REGISTRY
+            .by_name(name)
+            .map(|chain| chain.config.clone())
+    }
+
+    /// Returns the configuration of the built-in or embedded chain with the given L2
+    /// chain ID.
+    pub fn by_chain_id(chain_id: u64) -> Option<ChainConfig> {
+        EMBEDDED_REGISTRY
+            .by_chain_id(chain_id)
+            .map(|chain| chain.config.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A minimal `rollup.json` of an OP Stack chain.
+    const CUSTOM_ROLLUP_JSON: &str = r#"{
+        "genesis": {
+            "l1": {
+                "hash": "0x438335a20d98863a4c0c97999eb2481921ccd28553eac6f913af7c12aec04108",
+                "number": 17422590
+            },
+            "l2": {
+                "hash": "0x0000000000000000000000000000000000000000000000000000000000000001",
+                "number": 0
+            },
+            "l2_time": 1700000000,
+            "system_config": {
+                "batcherAddr": "0x6887246668a3b87f54deb3b94ba47a6f63f32985",
+                "overhead": "0x00000000000000000000000000000000000000000000000000000000000000bc",
+                "scalar": "0x00000000000000000000000000000000000000000000000000000000000a6fe0",
+                "gasLimit": 30000000
+            }
+        },
+        "block_time": 2,
+        "max_sequencer_drift": 600,
+        "seq_window_size": 3600,
+        "channel_timeout": 300,
+        "l1_chain_id": 1,
+        "l2_chain_id": CHAIN_ID,
+        "regolith_time": 0,
+        "canyon_time": 0,
+        "batch_inbox_address": "0xff00000000000000000000000000000000000042",
+        "deposit_contract_address": "0xbEb5Fc579115071764c7423A4f12eDde41f106Ed",
+        "l1_system_config_address": "0x229047fed2591dbec1eF1118d64F7aF3dB9EB290"
+    }"#;
+
+    fn rollup_json(chain_id: u64) -> String {
+        CUSTOM_ROLLUP_JSON.replace("CHAIN_ID", &chain_id.to_string())
+    }
+
+    #[test]
+    fn builtin() {
+        let config = ChainConfig::by_name("base").unwrap();
+        assert_eq!(config.chain_spec.chain_id(), 8453);
+        let config = ChainConfig::by_chain_id(8453).unwrap();
+        assert_eq!(config.batch_inbox, ChainConfig::base().batch_inbox);
+        let config = ChainConfig::by_chain_id(11155420).unwrap();
+        assert_eq!(config.batch_inbox, ChainConfig::op_sepolia().batch_inbox);
+
+        assert!(ChainConfig::by_name("unknown").is_none());
+        assert!(ChainConfig::by_chain_id(0).is_none());
+    }
+
+    #[test]
+    fn merge() {
+        let mut registry = ChainRegistry::builtin();
+        registry.insert_json("custom", &rollup_json(4242)).unwrap();
+        let chain = registry.by_name("custom").unwrap();
+        assert_eq!(chain.chain_id(), 4242);
+        assert!(chain.rollup_config.is_some());
+        assert_eq!(registry.by_chain_id(4242).unwrap().chain_id(), 4242);
+        assert_eq!(
+            registry.names().collect::<Vec<_>>(),
+            ["base", "custom", "op-sepolia", "optimism"]
+        );
+
+        // a chain with the same chain ID replaces the built-in chain
+        registry.insert_json("my-base", &rollup_json(8453)).unwrap();
+        assert!(registry.by_name("base").is_none());
+        assert!(registry.by_chain_id(8453).unwrap().rollup_config.is_some());
+
+        // a chain with the same name replaces the previous chain
+        registry.insert_json("custom", &rollup_json(4343)).unwrap();
+        assert!(registry.by_chain_id(4242).is_none());
+        assert_eq!(registry.by_name("custom").unwrap().chain_id(), 4343);
+
+        assert!(registry.insert_json("invalid", "{}").is_err());
+    }
+}
@@ -0,0 +1,326 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+
+#[cfg(not(target_os = "zkvm"))]
+use anyhow::{Context, Result};
+use revm::primitives::SpecId;
+use serde::{Deserialize, Serialize};
+use zeth_primitives::{keccak::keccak, Address, B256, U256};
+
+use super::{
+    batcher::BlockId,
+    batcher_channel::MAX_CHANNEL_BANK_SIZE,
+    config::{predeploys, ChainConfig, ChainConfigError},
+};
+use crate::consts::{ChainSpec, ForkCondition, OP_MAINNET_CHAIN_SPEC};
+
+/// The rollup configuration of an OP Stack chain, in the `rollup.json` format of op-node.
+///
+/// Only the values relevant for the derivation are kept; unknown keys are ignored. The
+/// configuration is part of the [DeriveInput](super::DeriveInput), so that the guest can
+/// derive any OP Stack chain without being rebuilt.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RollupConfig {
+    /// The L1 and L2 blocks the rollup starts from.
+    pub genesis: RollupGenesis,
+    /// Seconds between two L2 blocks.
+    pub block_time: u64,
+    /// Maximum timestamp drift of an L2 block from its L1 origin.
+    pub max_sequencer_drift: u64,
+    /// Number of L1 blocks in a sequence window.
+    pub seq_window_size: u64,
+    /// Number of L1 blocks after which a channel times out.
+    pub channel_timeout: u64,
+    /// Chain ID of the L1 chain.
+    pub l1_chain_id: u64,
+    /// Chain ID of the L2 chain.
+    pub l2_chain_id: u64,
+    /// Activation time of Regolith, if scheduled.
+    pub regolith_time: Option<u64>,
+    /// Activation time of Canyon, if scheduled.
+    pub canyon_time: Option<u64>,
+    /// Activation time of Delta, if scheduled.
+    pub delta_time: Option<u64>,
+    /// Activation time of Ecotone, if scheduled.
+    pub ecotone_time: Option<u64>,
+    /// The L1 address receiving the batcher transactions.
+    pub batch_inbox_address: Address,
+    /// The L1 deposit contract.
+    pub deposit_contract_address: Address,
+    /// The L1 system config contract.
+    pub l1_system_config_address: Address,
+    /// Custom EIP-1559 parameters; OP mainnet's when not set.
+    pub chain_op_config: Option<RollupEip1559Config>,
+}
+
+/// The genesis of a [RollupConfig].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RollupGenesis {
+    /// The L1 block the derivation starts from.
+    pub l1: BlockId,
+    /// The first L2 block of the rollup.
+    pub l2: BlockId,
+    /// The timestamp of the first L2 block.
+    pub l2_time: u64,
+    /// The initial system config.
+    pub system_config: RollupSystemConfig,
+}
+
+/// The initial system config of a [RollupConfig].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RollupSystemConfig {
+    pub batcher_addr: Address,
+    pub overhead: B256,
+    pub scalar: B256,
+    pub gas_limit: u64,
+}
+
+/// The EIP-1559 parameters of a [RollupConfig].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RollupEip1559Config {
+    pub eip1559_elasticity: u64,
+    pub eip1559_denominator: u64,
+    pub eip1559_denominator_canyon: u64,
+}
+
+impl RollupConfig {
+    /// Parses the contents of an op-node `rollup.json` file.
+    #[cfg(not(target_os = "zkvm"))]
+    pub fn from_json(json: &str) -> Result<Self> {
+        serde_json::from_str(json).context("Invalid rollup config")
+    }
+
+    /// Returns the hash committed to by the derivations using this configuration: the
+    /// keccak hash of its bincode encoding.
+    pub fn hash(&self) -> B256 {
+        keccak(bincode::serialize(self).expect("Failed to serialize rollup config")).into()
+    }
+
+    /// Returns the specification used to build the L2 blocks.
+    pub fn chain_spec(&self) -> ChainSpec {
+        // forks scheduled before the genesis are active from the genesis
+        let genesis_time = self.genesis.l2_time;
+        let fork = |time: Option<u64>| match time {
+            Some(time) => ForkCondition::Timestamp(time.max(genesis_time)),
+            None => ForkCondition::TBD,
+        };
+        let hard_forks = BTreeMap::from([
+            (SpecId::BEDROCK, ForkCondition::Timestamp(genesis_time)),
+            (SpecId::REGOLITH, fork(self.regolith_time)),
+            (SpecId::CANYON, fork(self.canyon_time)),
+            (SpecId::LATEST, fork(self.ecotone_time)),
+        ]);
+
+        let mut bedrock = *OP_MAINNET_CHAIN_SPEC
+            .gas_constants(SpecId::BEDROCK)
+            .unwrap();
+        let mut canyon = *OP_MAINNET_CHAIN_SPEC.gas_constants(SpecId::CANYON).unwrap();
+        if let Some(eip1559) = &self.chain_op_config {
+            bedrock.elasticity_multiplier = U256::from(eip1559.eip1559_elasticity);
+            bedrock.base_fee_change_denominator = U256::from(eip1559.eip1559_denominator);
+            canyon.elasticity_multiplier = U256::from(eip1559.eip1559_elasticity);
+            canyon.base_fee_change_denominator = U256::from(eip1559.eip1559_denominator_canyon);
+        }
+        let gas_constants = BTreeMap::from([(SpecId::BEDROCK, bedrock), (SpecId::CANYON, canyon)]);
+
+        ChainSpec::new(self.l2_chain_id, SpecId::CANYON, hard_forks, gas_constants)
+    }
+
+    /// Validates the values and builds the derivation configuration of the chain.
+    ///
+    /// The rollup config does not contain the signer of unsafe blocks, so the returned
+    /// configuration accepts no unsafe payloads. Its chain specification is leaked to
+    /// obtain the static lifetime of [ChainConfig::chain_spec], so this should only be
+    /// called once per configuration.
+    pub fn chain_config(&self) -> Result<ChainConfig, ChainConfigError> {
+        let system_config = &self.genesis.system_config;
+        let chain_spec: &'static ChainSpec = Box::leak(Box::new(self.chain_spec()));
+        let mut builder = ChainConfig::builder()
+            .chain_spec(chain_spec)
+            .batch_sender(&system_config.batcher_addr.to_string())
+            .gas_limit(U256::from(system_config.gas_limit))
+            .l1_fee(
+                U256::from_be_bytes(system_config.overhead.0),
+                U256::from_be_bytes(system_config.scalar.0),
+            )
+            .l1_attributes_depositor(&predeploys::L1_ATTRIBUTES_DEPOSITOR.to_string())
+            .l1_attributes_contract(&predeploys::L1_BLOCK.to_string())
+            .sequencer_fee_vault(&predeploys::SEQUENCER_FEE_VAULT.to_string())
+            .batch_inbox(&self.batch_inbox_address.to_string())
+            .deposit_contract(&self.deposit_contract_address.to_string())
+            .system_config_contract(&self.l1_system_config_address.to_string())
+            .max_channel_bank_size(MAX_CHANNEL_BANK_SIZE)
+            .channel_timeout(self.channel_timeout)
+            .seq_window_size(self.seq_window_size)
+            .max_seq_drift(self.max_sequencer_drift)
+            .blocktime(self.block_time)
+            .bedrock_genesis(self.genesis.l2)
+            .l2_genesis_time(self.genesis.l2_time);
+        if let Some(delta_time) = self.delta_time {
+            builder = builder.delta_time(delta_time);
+        }
+        if let Some(ecotone_time) = self.ecotone_time {
+            builder = builder.ecotone_time(ecotone_time);
+        }
+
+        builder.build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use zeth_primitives::address;
+
+    use super::*;
+
+    /// The `rollup.json` of OP mainnet, as printed by op-node.
+    const OP_MAINNET_ROLLUP_JSON: &str = r#"{
+        "genesis": {
+            "l1": {
+                "hash": "0x438335a20d98863a4c0c97999eb2481921ccd28553eac6f913af7c12aec04108",
+                "number": 17422590
+            },
+            "l2": {
+                "hash": "0xdbf6a80fef073de06add9b0d14026d6e5a86c85f6d102c36d3d8e9cf89c2afd3",
+                "number": 105235063
+            },
+            "l2_time": 1686068903,
+            "system_config": {
+                "batcherAddr": "0x6887246668a3b87f54deb3b94ba47a6f63f32985",
+                "overhead": "0x00000000000000000000000000000000000000000000000000000000000000bc",
+                "scalar": "0x00000000000000000000000000000000000000000000000000000000000a6fe0",
+                "gasLimit": 30000000
+            }
+        },
+        "block_time": 2,
+        "max_sequencer_drift": 600,
+        "seq_window_size": 3600,
+        "channel_timeout": 300,
+        "l1_chain_id": 1,
+        "l2_chain_id": 10,
+        "regolith_time": 0,
+        "canyon_time": 1704992401,
+        "delta_time": 1708560000,
+        "ecotone_time": 1710374401,
+        "batch_inbox_address": "0xff00000000000000000000000000000000000010",
+        "deposit_contract_address": "0xbeb5fc579115071764c7423a4f12edde41f106ed",
+        "l1_system_config_address": "0x229047fed2591dbec1ef1118d64f7af3db9eb290",
+        "protocol_versions_address": "0x8062abc286f5e7d9428a0ccb9abd71e50d93b935"
+    }"#;
+
+    #[test]
+    fn op_mainnet() {
+        let rollup_config = RollupConfig::from_json(OP_MAINNET_ROLLUP_JSON).unwrap();
+        let config = rollup_config.chain_config().unwrap();
+        let expected = ChainConfig::optimism();
+
+        assert_eq!(
+            config.system_config.batch_sender,
+            expected.system_config.batch_sender
+        );
+        assert_eq!(
+            config.system_config.gas_limit,
+            expected.system_config.gas_limit
+        );
+        assert_eq!(
+            config.system_config.l1_fee_overhead,
+            expected.system_config.l1_fee_overhead
+        );
+        assert_eq!(
+            config.system_config.l1_fee_scalar,
+            expected.system_config.l1_fee_scalar
+        );
+        assert_eq!(config.system_config.unsafe_block_signer, Address::ZERO);
+        assert_eq!(config.batch_inbox, expected.batch_inbox);
+        assert_eq!(config.deposit_contract, expected.deposit_contract);
+        assert_eq!(
+            config.system_config_contract,
+            expected.system_config_contract
+        );
+        assert_eq!(config.max_channel_bank_size, expected.max_channel_bank_size);
+        assert_eq!(config.channel_timeout, expected.channel_timeout);
+        assert_eq!(config.seq_window_size, expected.seq_window_size);
+        assert_eq!(config.max_seq_drift, expected.max_seq_drift);
+        assert_eq!(config.blocktime, expected.blocktime);
+        assert_eq!(config.bedrock_genesis, expected.bedrock_genesis);
+        assert_eq!(config.l2_genesis_time, expected.l2_genesis_time);
+        assert_eq!(config.delta_time, expected.delta_time);
+        assert_eq!(config.ecotone_time, expected.ecotone_time);
+
+        let chain_spec = config.chain_spec;
+        assert_eq!(chain_spec.chain_id(), 10);
+        // Regolith was active from the genesis
+        assert_eq!(
+            chain_spec.hard_forks()[&SpecId::REGOLITH],
+            ForkCondition::Timestamp(1686068903)
+        );
+        assert_eq!(
+            chain_spec.hard_forks()[&SpecId::CANYON],
+            ForkCondition::Timestamp(1704992401)
+        );
+        assert_eq!(
+            chain_spec.gas_constants(SpecId::CANYON),
+            OP_MAINNET_CHAIN_SPEC.gas_constants(SpecId::CANYON)
+        );
+    }
+
+    #[test]
+    fn custom_chain() {
+        let mut rollup_config = RollupConfig::from_json(OP_MAINNET_ROLLUP_JSON).unwrap();
+        let hash = rollup_config.hash();
+        rollup_config.l2_chain_id = 901;
+        rollup_config.canyon_time = None;
+        rollup_config.batch_inbox_address = address!("ff00000000000000000000000000000000000901");
+        rollup_config.chain_op_config = Some(RollupEip1559Config {
+            eip1559_elasticity: 10,
+            eip1559_denominator: 100,
+            eip1559_denominator_canyon: 1000,
+        });
+        assert_ne!(rollup_config.hash(), hash);
+
+        // Ecotone cannot activate before Canyon
+        assert!(matches!(
+            rollup_config.chain_config(),
+            Err(ChainConfigError::ForkOrder { .. })
+        ));
+        rollup_config.delta_time = None;
+        rollup_config.ecotone_time = None;
+
+        let config = rollup_config.chain_config().unwrap();
+        assert_eq!(config.batch_inbox, rollup_config.batch_inbox_address);
+        assert_eq!(config.ecotone_time, None);
+        let chain_spec = config.chain_spec;
+        assert_eq!(chain_spec.chain_id(), 901);
+        assert_eq!(chain_spec.hard_forks()[&SpecId::CANYON], ForkCondition::TBD);
+        let bedrock = chain_spec.gas_constants(SpecId::BEDROCK).unwrap();
+        assert_eq!(bedrock.elasticity_multiplier, U256::from(10));
+        assert_eq!(bedrock.base_fee_change_denominator, U256::from(100));
+    }
+
+    #[test]
+    fn missing_value() {
+        let json = OP_MAINNET_ROLLUP_JSON.replace("\"block_time\": 2,", "");
+        assert!(RollupConfig::from_json(&json).is_err());
+        let json = OP_MAINNET_ROLLUP_JSON.replace("\"block_time\": 2,", "\"block_time\": 0,");
+        let rollup_config = RollupConfig::from_json(&json).unwrap();
+        assert_eq!(
+            rollup_config.chain_config().unwrap_err(),
+            ChainConfigError::Zero("blocktime")
+        );
+    }
+}
@@ -0,0 +1,31 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::Result;
+
+/// A stage of the derivation pipeline. Every stage consumes the items produced by the
+/// previous stage and buffers the items for the next one, so that stages can be tested
+/// and replaced individually.
+pub trait Stage {
+    /// The items consumed from the previous stage.
+    type Input<'a>;
+    /// The items produced for the next stage.
+    type Output;
+
+    /// Processes an item of the previous stage.
+    fn push(&mut self, input: Self::Input<'_>) -> Result<()>;
+
+    /// Returns the next item for the next stage, if one is available.
+    fn next(&mut self) -> Option<Self::Output>;
+}
@@ -0,0 +1,714 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Deterministic synthetic Ethereum chains for stress testing the derivation pipeline.
+//!
+//! A [SyntheticChain] is generated from a seed and consists of valid Ethereum blocks
+//! containing signed batcher transactions and deposits. The Optimism blocks are not
+//! executed: the hash of every Optimism block is the Keccak hash of its RLP-encoded
+//! batch, so the chain exercises the L1 retrieval, frame, channel and batch stages.
+
+use std::{collections::VecDeque, io::Write};
+
+use alloy_sol_types::{sol_data, SolType};
+use anyhow::{ensure, Context, Result};
+use k256::ecdsa::SigningKey;
+use libflate::zlib::Encoder;
+use zeth_primitives::{
+    alloy_rlp,
+    batch::{Batch, BatchEssence},
+    block::Header,
+    keccak::keccak,
+    receipt::{Log, Receipt},
+    transactions::{
+        ethereum::{EthereumTxEssence, TransactionKind, TxEssenceEip1559, TxEssenceEip4844},
+        optimism::OptimismTxEssence,
+        signature::TxSignature,
+        Transaction, TxEssence,
+    },
+    trie::{MptNode, EMPTY_ROOT},
+    uint, Address, Bloom, B256, U256,
+};
+
+use super::{
+    batcher::{Batcher, BlockId, L2BlockInfo},
+    batcher_channel::Frame,
+    batcher_db::BlockInput,
+    blob::{data_to_blob, BlobSidecar},
+    config::ChainConfig,
+    deposits::TRANSACTION_DEPOSITED_SIGNATURE,
+};
+use crate::consts::{derive_base_fee, derive_excess_blob_gas, GAS_PER_BLOB};
+
+/// Number of the Ethereum head of every synthetic chain.
+const ETH_HEAD_NUMBER: u64 = 19_000_000;
+/// Timestamp of the Ethereum head of every synthetic chain, between the Canyon and
+/// Ecotone upgrades of Optimism mainnet.
+const ETH_HEAD_TIMESTAMP: u64 = 1_706_000_000;
+/// Number of seconds between two Ethereum blocks.
+const ETH_BLOCK_TIME: u64 = 12;
+/// Gas limit of every synthetic Ethereum block.
+const ETH_GAS_LIMIT: U256 = uint!(30_000_000_U256);
+
+/// Options of the generated chain.
+#[derive(Debug, Clone)]
+pub struct SyntheticChainOptions {
+    /// Seed of the pseudo-random generator; the same options always yield the same chain.
+    pub seed: u64,
+    /// Number of Ethereum blocks following the Ethereum head.
+    pub eth_block_count: u64,
+    /// Block time of the Optimism chain in seconds.
+    pub blocktime: u64,
+    /// Maximum number of transactions in a batch.
+    pub max_batch_txs: usize,
+    /// Number of batches in each channel.
+    pub channel_batches: usize,
+    /// Maximum number of bytes of channel data in a single frame.
+    pub max_frame_size: usize,
+    /// Number of channels whose frames are posted interleaved.
+    pub interleaved_channels: usize,
+    /// Maximum number of frames in a single batcher transaction.
+    pub max_tx_frames: usize,
+    /// Maximum number of batcher transactions in a single Ethereum block.
+    pub max_block_batcher_txs: usize,
+    /// Probability in percent that an Ethereum block contains deposits.
+    pub deposit_density: u8,
+    /// Maximum number of deposits in an Ethereum block containing deposits.
+    pub max_block_deposits: usize,
+    /// Whether the batcher posts its data in blobs instead of calldata, which activates
+    /// Ecotone at the Ethereum head.
+    pub blob_batcher: bool,
+}
+
+impl Default for SyntheticChainOptions {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            eth_block_count: 32,
+            blocktime: 2,
+            max_batch_txs: 8,
+            channel_batches: 6,
+            max_frame_size: 1024,
+            interleaved_channels: 2,
+            max_tx_frames: 2,
+            max_block_batcher_txs: 3,
+            deposit_density: 25,
+            max_block_deposits: 3,
+            blob_batcher: false,
+        }
+    }
+}
+
+/// A synthetic Ethereum chain together with the batches that must be derived from it.
+#[derive(Debug, Clone)]
+pub struct SyntheticChain {
+    /// Chain configuration authenticating the synthetic batch sender.
+    pub config: ChainConfig,
+    /// The Optimism head, whose L1 origin is the Ethereum head.
+    pub op_head: L2BlockInfo,
+    /// The Ethereum head followed by all generated blocks.
+    pub eth_blocks: Vec<BlockInput<EthereumTxEssence>>,
+    /// The batches of all channels that were posted completely and in time, in the order
+    /// of their Optimism blocks.
+    pub batches: Vec<BatchEssence>,
+}
+
+impl SyntheticChain {
+    /// Generates the chain described by the given options.
+    pub fn generate(options: &SyntheticChainOptions) -> Result<Self> {
+        ensure!(options.blocktime > 0, "block time must not be zero");
+        ensure!(options.channel_batches > 0, "channels must contain batches");
+        ensure!(options.max_frame_size > 0, "frames must contain data");
+        ensure!(
+            options.interleaved_channels > 0,
+            "at least one channel must be posted"
+        );
+        ensure!(
+            options.max_tx_frames > 0,
+            "transactions must contain frames"
+        );
+        ensure!(
+            options.deposit_density <= 100,
+            "deposit density is a percentage"
+        );
+
+        let mut rng = SplitMix64(options.seed);
+        let batcher_key = signing_key(options.seed, b"batcher")?;
+        let depositor_key = signing_key(options.seed, b"depositor")?;
+
+        let mut config = ChainConfig::optimism();
+        config.system_config.batch_sender = key_address(&batcher_key);
+        config.blocktime = options.blocktime;
+        if options.blob_batcher {
+            config.ecotone_time = Some(ETH_HEAD_TIMESTAMP);
+        }
+
+        let eth_head = seal_block(
+            Header {
+                parent_hash: rng.b256(),
+                number: ETH_HEAD_NUMBER,
+                timestamp: U256::from(ETH_HEAD_TIMESTAMP),
+                base_fee_per_gas: uint!(1_000_000_000_U256),
+                excess_blob_gas: config.is_ecotone(ETH_HEAD_TIMESTAMP).then_some(U256::ZERO),
+                ..Default::default()
+            },
+            vec![],
+            vec![],
+            vec![],
+        );
+        let op_head = L2BlockInfo {
+            hash: rng.b256(),
+            timestamp: ETH_HEAD_TIMESTAMP,
+            l1_origin: BlockId {
+                number: ETH_HEAD_NUMBER,
+                hash: eth_head.block_header.hash(),
+            },
+        };
+
+        let mut eth_blocks = vec![eth_head];
+        let mut batches: Vec<BatchEssence> = Vec::new();
+        let mut pending_batches = 0;
+        let mut channels: VecDeque<PendingChannel> = VecDeque::new();
+        let mut posted_channels: Vec<PendingChannel> = Vec::new();
+        let mut nonces = (0, 0);
+
+        for n in 1..=options.eth_block_count {
+            let parent = &eth_blocks.last().unwrap().block_header;
+            let number = ETH_HEAD_NUMBER + n;
+            let timestamp = ETH_HEAD_TIMESTAMP + n * ETH_BLOCK_TIME;
+
+            // the sequencer produces all Optimism blocks older than this block
+            loop {
+                let op_timestamp =
+                    op_head.timestamp + (batches.len() as u64 + 1) * config.blocktime;
+                if op_timestamp >= timestamp {
+                    break;
+                }
+                let epoch =
+                    &eth_blocks[((op_timestamp - ETH_HEAD_TIMESTAMP) / ETH_BLOCK_TIME) as usize];
+                let parent_hash = batches.last().map_or(op_head.hash, op_block_hash);
+                let transactions = (0..rng.range(0, options.max_batch_txs))
+                    .map(|_| {
+                        // any non-deposit transaction type is accepted by the batcher
+                        let len = rng.range(16, 128);
+                        let mut tx = rng.bytes(len);
+                        tx[0] = 0x02;
+                        tx.into()
+                    })
+                    .collect();
+                batches.push(BatchEssence {
+                    parent_hash,
+                    epoch_num: epoch.block_header.number,
+                    epoch_hash: epoch.block_header.hash(),
+                    timestamp: op_timestamp,
+                    transactions,
+                });
+                pending_batches += 1;
+
+                // the batcher closes a channel once it contains enough batches
+                if pending_batches == options.channel_batches {
+                    let first = batches.len() - pending_batches;
+                    channels.push_back(PendingChannel::new(
+                        rng.next_u64() as u128 | (rng.next_u64() as u128) << 64,
+                        &batches,
+                        first,
+                        options.max_frame_size,
+                    )?);
+                    pending_batches = 0;
+                }
+            }
+
+            let mut transactions = Vec::new();
+            let mut logs = Vec::new();
+            let mut blob_sidecars = Vec::new();
+
+            // deposits
+            if rng.range(1, 100) <= options.deposit_density as usize {
+                for _ in 0..rng.range(1, options.max_block_deposits.max(1)) {
+                    let to = Address::from_slice(&rng.bytes(20));
+                    let data_len = rng.range(0, 64);
+                    let mint = U256::from(rng.next_u64());
+                    let essence = EthereumTxEssence::Eip1559(TxEssenceEip1559 {
+                        chain_id: 1,
+                        nonce: nonces.1,
+                        max_fee_per_gas: uint!(10_000_000_000_U256),
+                        gas_limit: uint!(200_000_U256),
+                        to: TransactionKind::Call(config.deposit_contract),
+                        value: mint,
+                        ..Default::default()
+                    });
+                    nonces.1 += 1;
+                    transactions.push(sign(&depositor_key, essence)?);
+                    logs.push(Some(deposit_log(
+                        &config,
+                        key_address(&depositor_key),
+                        to,
+                        mint,
+                        &rng.bytes(data_len),
+                    )));
+                }
+            }
+
+            // batcher transactions, each with frames of the first channels in round-robin
+            let mut channel_index = 0;
+            for _ in 0..rng.range(1, options.max_block_batcher_txs.max(1)) {
+                if channels.is_empty() {
+                    break;
+                }
+                let mut data = vec![0];
+                for _ in 0..rng.range(1, options.max_tx_frames) {
+                    let active = channels.len().min(options.interleaved_channels);
+                    if active == 0 {
+                        break;
+                    }
+                    channel_index %= active;
+                    let channel = &mut channels[channel_index];
+                    encode_frame(&channel.frames.pop_front().unwrap(), &mut data);
+                    channel.opened.get_or_insert(number);
+                    if channel.frames.is_empty() {
+                        channel.closed = Some(number);
+                        posted_channels.push(channels.remove(channel_index).unwrap());
+                    } else {
+                        channel_index += 1;
+                    }
+                }
+                let essence = if options.blob_batcher {
+                    let blob = data_to_blob(&data)?;
+                    let sidecar = BlobSidecar::from_blob(&blob)?;
+                    let essence = EthereumTxEssence::Eip4844(TxEssenceEip4844 {
+                        chain_id: 1,
+                        nonce: nonces.0,
+                        max_fee_per_gas: uint!(10_000_000_000_U256),
+                        gas_limit: uint!(21_000_U256),
+                        to: config.batch_inbox,
+                        max_fee_per_blob_gas: uint!(1_U256),
+                        blob_versioned_hashes: vec![sidecar.versioned_hash()],
+                        ..Default::default()
+                    });
+                    blob_sidecars.push(sidecar);
+                    essence
+                } else {
+                    EthereumTxEssence::Eip1559(TxEssenceEip1559 {
+                        chain_id: 1,
+                        nonce: nonces.0,
+                        max_fee_per_gas: uint!(10_000_000_000_U256),
+                        gas_limit: U256::from(21_000 + 16 * data.len()),
+                        to: TransactionKind::Call(config.batch_inbox),
+                        data: data.into(),
+                        ..Default::default()
+                    })
+                };
+                nonces.0 += 1;
+                transactions.push(sign(&batcher_key, essence)?);
+                logs.push(None);
+            }
+
+            let header = Header {
+                parent_hash: parent.hash(),
+                number,
+                timestamp: U256::from(timestamp),
+                base_fee_per_gas: derive_base_fee(parent, &config.l1_eip1559_constants),
+                excess_blob_gas: config
+                    .is_ecotone(timestamp)
+                    .then(|| derive_excess_blob_gas(parent)),
+                ..Default::default()
+            };
+            eth_blocks.push(seal_block(header, transactions, logs, blob_sidecars));
+        }
+
+        // only the batches of channels up to the first incomplete one can be derived
+        let mut derivable = 0;
+        posted_channels.sort_by_key(|channel| channel.first_batch);
+        for channel in posted_channels {
+            let (Some(opened), Some(closed)) = (channel.opened, channel.closed) else {
+                break;
+            };
+            if channel.first_batch != derivable || closed > opened + config.channel_timeout {
+                break;
+            }
+            derivable += channel.batch_count;
+        }
+        batches.truncate(derivable);
+
+        Ok(SyntheticChain {
+            config,
+            op_head,
+            eth_blocks,
+            batches,
+        })
+    }
+
+    /// Runs the Ethereum blocks through the [Batcher] and returns every derived batch
+    /// together with the deposits of its Optimism block.
+    pub fn derive_batches(
+        &self,
+    ) -> Result<Vec<(BatchEssence, Vec<Transaction<OptimismTxEssence>>)>> {
+        let eth_head = self.eth_blocks.first().context("chain is empty")?;
+        let mut batcher = Batcher::new(self.config.clone(), self.op_head, eth_head)?;
+
+        let mut derived = Vec::new();
+        for eth_block in &self.eth_blocks[1..] {
+            batcher.process_l1_block(eth_block)?;
+            while let Some(Batch(batch)) = batcher.read_batch()? {
+                // the deposits are part of the first block of each epoch
+                let deposits = if batcher.state.safe_head.l1_origin.number != batch.epoch_num {
+                    batcher.state.do_next_epoch()?;
+                    let index = batcher.state.epoch.number - ETH_HEAD_NUMBER;
+                    batcher.epoch_deposits(&self.eth_blocks[index as usize])?
+                } else {
+                    vec![]
+                };
+                batcher.state.safe_head = L2BlockInfo {
+                    hash: op_block_hash(&batch),
+                    timestamp: batch.timestamp,
+                    l1_origin: BlockId {
+                        number: batcher.state.epoch.number,
+                        hash: batcher.state.epoch.hash,
+                    },
+                };
+                derived.push((batch, deposits));
+            }
+        }
+
+        Ok(derived)
+    }
+}
+
+/// Returns the synthetic hash of the Optimism block built from the given batch.
+pub fn op_block_hash(batch: &BatchEssence) -> B256 {
+    keccak(alloy_rlp::encode(batch)).into()
+}
+
+/// A channel waiting to be posted by the batcher.
+struct PendingChannel {
+    /// Index of the first batch of the channel.
+    first_batch: usize,
+    /// Number of batches in the channel.
+    batch_count: usize,
+    /// Frames that have not been posted yet.
+    frames: VecDeque<Frame>,
+    /// Number of the Ethereum block containing the first frame.
+    opened: Option<u64>,
+    /// Number of the Ethereum block containing the last frame.
+    closed: Option<u64>,
+}
+
+impl PendingChannel {
+    /// Compresses the batches starting at `first_batch` into the frames of a channel.
+    fn new(
+        id: u128,
+        batches: &[BatchEssence],
+        first_batch: usize,
+        max_frame_size: usize,
+    ) -> Result<Self> {
+        let mut encoder = Encoder::new(Vec::new())?;
+        for batch in &batches[first_batch..] {
+            encoder.write_all(&alloy_rlp::encode(Batch(batch.clone())))?;
+        }
+        let data = encoder.finish().into_result()?;
+
+        let chunks: Vec<_> = data.chunks(max_frame_size).collect();
+        let frames = chunks
+            .iter()
+            .enumerate()
+            .map(|(number, chunk)| Frame {
+                channel_id: id,
+                number: number as u16,
+                data: chunk.to_vec(),
+                is_last: number == chunks.len() - 1,
+            })
+            .collect();
+
+        Ok(Self {
+            first_batch,
+            batch_count: batches.len() - first_batch,
+            frames,
+            opened: None,
+            closed: None,
+        })
+    }
+}
+
+/// SplitMix64, a small pseudo-random generator that is fully determined by its seed.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a value in `low..=high`.
+    fn range(&mut self, low: usize, high: usize) -> usize {
+        low + (self.next_u64() % (high.saturating_sub(low) as u64 + 1)) as usize
+    }
+
+    fn bytes(&mut self, len: usize) -> Vec<u8> {
+        (0..len).map(|_| self.next_u64() as u8).collect()
+    }
+
+    fn b256(&mut self) -> B256 {
+        B256::from_slice(&self.bytes(32))
+    }
+}
+
+/// Derives the signing key of the given role from the seed.
+fn signing_key(seed: u64, role: &[u8]) -> Result<SigningKey> {
+    let secret = keccak([&seed.to_be_bytes(), role].concat());
+    SigningKey::from_slice(&secret).context("invalid signing key")
+}
+
+/// Returns the address of the given key.
+fn key_address(key: &SigningKey) -> Address {
+    let public_key = key.verifying_key().to_encoded_point(false);
+    Address::from_slice(&keccak(&public_key.as_bytes()[1..])[12..])
+}
+
+/// Signs the transaction essence with the given key.
+fn sign(key: &SigningKey, essence: EthereumTxEssence) -> Result<Transaction<EthereumTxEssence>> {
+    let (signature, recovery_id) =
+        key.sign_prehash_recoverable(essence.signing_hash().as_slice())?;
+    Ok(Transaction {
+        essence,
+        signature: TxSignature {
+            v: recovery_id.is_y_odd() as u64,
+            r: U256::from_be_slice(&signature.r().to_bytes()),
+            s: U256::from_be_slice(&signature.s().to_bytes()),
+        },
+    })
+}
+
+/// Appends the encoding of the frame to the batcher transaction data.
+fn encode_frame(frame: &Frame, out: &mut Vec<u8>) {
+    out.extend_from_slice(&frame.channel_id.to_be_bytes());
+    out.extend_from_slice(&frame.number.to_be_bytes());
+    out.extend_from_slice(&(frame.data.len() as u32).to_be_bytes());
+    out.extend_from_slice(&frame.data);
+    out.push(frame.is_last as u8);
+}
+
+/// Returns the log emitted by the deposit contract for a deposit of `mint` wei.
+fn deposit_log(config: &ChainConfig, from: Address, to: Address, mint: U256, data: &[u8]) -> Log {
+    let mut opaque_data = Vec::with_capacity(73 + data.len());
+    opaque_data.extend_from_slice(&mint.to_be_bytes::<32>());
+    opaque_data.extend_from_slice(&mint.to_be_bytes::<32>());
+    opaque_data.extend_from_slice(&100_000u64.to_be_bytes());
+    opaque_data.push(0);
+    opaque_data.extend_from_slice(data);
+
+    Log {
+        address: config.deposit_contract,
+        topics: vec![
+            TRANSACTION_DEPOSITED_SIGNATURE,
+            from.into_word(),
+            to.into_word(),
+            B256::ZERO,
+        ],
+        data: sol_data::Bytes::abi_encode(&opaque_data).into(),
+    }
+}
+
+/// Completes the header with the roots, bloom filter and gas of the transactions. Each
+/// transaction may emit a log; the receipts are only included if there are any logs.
+/// Headers with an excess blob gas also get the other fields of a Cancun header.
+fn seal_block(
+    mut header: Header,
+    transactions: Vec<Transaction<EthereumTxEssence>>,
+    logs: Vec<Option<Log>>,
+    blob_sidecars: Vec<BlobSidecar>,
+) -> BlockInput<EthereumTxEssence> {
+    header.gas_limit = ETH_GAS_LIMIT;
+    if header.excess_blob_gas.is_some() {
+        header.withdrawals_root = Some(EMPTY_ROOT);
+        header.blob_gas_used = Some(GAS_PER_BLOB * U256::from(blob_sidecars.len()));
+        header.parent_beacon_block_root = Some(B256::ZERO);
+    }
+    if transactions.is_empty() {
+        header.transactions_root = EMPTY_ROOT;
+        header.receipts_root = EMPTY_ROOT;
+        return BlockInput {
+            block_header: header,
+            transactions,
+            receipts: None,
+            blob_sidecars: vec![],
+        };
+    }
+
+    let mut tx_trie = MptNode::default();
+    let mut receipt_trie = MptNode::default();
+    let mut receipts = Vec::with_capacity(transactions.len());
+    let mut logs_bloom = Bloom::default();
+    for (tx_no, (tx, log)) in transactions.iter().zip(logs).enumerate() {
+        header.gas_used += tx.essence.gas_limit();
+        let receipt = Receipt::new(2, true, header.gas_used, log.into_iter().collect());
+        logs_bloom.accrue_bloom(&receipt.payload.logs_bloom);
+        tx_trie.insert_rlp(&alloy_rlp::encode(tx_no), tx).unwrap();
+        receipt_trie
+            .insert_rlp(&alloy_rlp::encode(tx_no), &receipt)
+            .unwrap();
+        receipts.push(receipt);
+    }
+    header.transactions_root = tx_trie.hash();
+    header.receipts_root = receipt_trie.hash();
+    header.logs_bloom = logs_bloom;
+
+    let has_logs = receipts
+        .iter()
+        .any(|receipt| !receipt.payload.logs.is_empty());
+    BlockInput {
+        block_header: header,
+        transactions,
+        receipts: has_logs.then_some(receipts),
+        blob_sidecars,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimism::{batcher_db::validate_eth_block, deposits};
+
+    /// Returns the number of deposits in the given Ethereum blocks.
+    fn deposit_count(config: &ChainConfig, eth_blocks: &[BlockInput<EthereumTxEssence>]) -> usize {
+        eth_blocks
+            .iter()
+            .map(|block| deposits::extract_transactions(config, block).unwrap().len())
+            .sum()
+    }
+
+    #[test]
+    fn valid_eth_blocks() {
+        let chain = SyntheticChain::generate(&SyntheticChainOptions::default()).unwrap();
+        for (i, eth_block) in chain.eth_blocks.iter().enumerate() {
+            validate_eth_block(&chain.config, ETH_HEAD_NUMBER + i as u64, eth_block).unwrap();
+            if i > 0 {
+                let parent = &chain.eth_blocks[i - 1].block_header;
+                assert_eq!(eth_block.block_header.parent_hash, parent.hash());
+            }
+        }
+        assert!(deposit_count(&chain.config, &chain.eth_blocks) > 0);
+    }
+
+    #[test]
+    fn deterministic() {
+        let options = SyntheticChainOptions::default();
+        let chain = SyntheticChain::generate(&options).unwrap();
+        let again = SyntheticChain::generate(&options).unwrap();
+        assert_eq!(chain.batches, again.batches);
+        assert_eq!(
+            chain.eth_blocks.last().unwrap().block_header.hash(),
+            again.eth_blocks.last().unwrap().block_header.hash()
+        );
+
+        let other =
+            SyntheticChain::generate(&SyntheticChainOptions { seed: 1, ..options }).unwrap();
+        assert_ne!(chain.batches, other.batches);
+    }
+
+    #[test]
+    fn derive() {
+        let chain = SyntheticChain::generate(&SyntheticChainOptions::default()).unwrap();
+        assert!(!chain.batches.is_empty());
+
+        let derived = chain.derive_batches().unwrap();
+        let batches: Vec<_> = derived.iter().map(|(batch, _)| batch.clone()).collect();
+        assert_eq!(batches, chain.batches);
+
+        // every consumed epoch contributes its deposits exactly once
+        let last_epoch = batches.last().unwrap().epoch_num;
+        let epochs = &chain.eth_blocks[1..=(last_epoch - ETH_HEAD_NUMBER) as usize];
+        let derived_deposits: usize = derived.iter().map(|(_, deposits)| deposits.len()).sum();
+        assert_eq!(derived_deposits, deposit_count(&chain.config, epochs));
+    }
+
+    #[test]
+    fn derive_custom_block_times() {
+        for blocktime in [1, 5] {
+            let options = SyntheticChainOptions {
+                blocktime,
+                ..Default::default()
+            };
+            let chain = SyntheticChain::generate(&options).unwrap();
+            assert!(!chain.batches.is_empty());
+            for (i, batch) in chain.batches.iter().enumerate() {
+                assert_eq!(
+                    batch.timestamp,
+                    chain.op_head.timestamp + (i as u64 + 1) * blocktime
+                );
+            }
+
+            let derived = chain.derive_batches().unwrap();
+            let batches: Vec<_> = derived.into_iter().map(|(batch, _)| batch).collect();
+            assert_eq!(batches, chain.batches);
+        }
+    }
+
+    #[test]
+    fn derive_blobs() {
+        let options = SyntheticChainOptions {
+            blob_batcher: true,
+            ..Default::default()
+        };
+        let chain = SyntheticChain::generate(&options).unwrap();
+        assert!(!chain.batches.is_empty());
+        for (i, eth_block) in chain.eth_blocks.iter().enumerate() {
+            validate_eth_block(&chain.config, ETH_HEAD_NUMBER + i as u64, eth_block).unwrap();
+        }
+        let blob_count: usize = chain
+            .eth_blocks
+            .iter()
+            .map(|block| block.blob_sidecars.len())
+            .sum();
+        assert!(blob_count > 0);
+
+        let derived = chain.derive_batches().unwrap();
+        let batches: Vec<_> = derived.into_iter().map(|(batch, _)| batch).collect();
+        assert_eq!(batches, chain.batches);
+
+        // the frames cannot be derived from a blob that does not match its proof
+        let mut chain = chain;
+        let sidecar = chain
+            .eth_blocks
+            .iter_mut()
+            .find_map(|block| block.blob_sidecars.first_mut())
+            .unwrap();
+        let mut blob = sidecar.blob.to_vec();
+        blob[100] ^= 1;
+        sidecar.blob = blob.into();
+        assert!(chain.derive_batches().is_err());
+    }
+
+    #[test]
+    fn derive_interleaved_small_frames() {
+        let options = SyntheticChainOptions {
+            seed: 42,
+            eth_block_count: 48,
+            max_frame_size: 64,
+            interleaved_channels: 4,
+            max_tx_frames: 16,
+            max_block_batcher_txs: 6,
+            deposit_density: 80,
+            ..Default::default()
+        };
+        let chain = SyntheticChain::generate(&options).unwrap();
+        assert!(!chain.batches.is_empty());
+
+        let derived = chain.derive_batches().unwrap();
+        let batches: Vec<_> = derived.into_iter().map(|(batch, _)| batch).collect();
+        assert_eq!(batches, chain.batches);
+    }
+}
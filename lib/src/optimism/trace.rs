@@ -0,0 +1,64 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use serde::{Deserialize, Serialize};
+use zeth_primitives::BlockNumber;
+
+use super::batcher::{BlockId, DroppedBatch};
+
+/// A decision point of the derivation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TraceEvent {
+    /// An L1 block was traversed.
+    L1Block(BlockId),
+    /// A batch was accepted for the next block.
+    BatchAccepted {
+        timestamp: u64,
+        epoch_num: u64,
+        inclusion_block_number: BlockNumber,
+    },
+    /// A batch was dropped by the batch status rules.
+    BatchDropped(DroppedBatch),
+    /// An empty default batch was generated, as the sequencing window has expired.
+    DefaultBatch { timestamp: u64, epoch_num: u64 },
+    /// The derivation advanced to the next epoch.
+    NextEpoch(BlockId),
+    /// A block was derived.
+    BlockDerived(BlockId),
+    /// The block of a batch could not be built.
+    BlockFailed { timestamp: u64 },
+}
+
+/// The trace of the decision points of the derivation. The events are only recorded if
+/// the `debug-trace` feature is enabled, to debug failures of the guest that do not
+/// reproduce natively.
+#[derive(Debug, Clone, Default)]
+pub struct DebugTrace {
+    events: Vec<TraceEvent>,
+}
+
+impl DebugTrace {
+    /// Records the given event, if the `debug-trace` feature is enabled.
+    #[inline]
+    pub fn record(&mut self, event: impl FnOnce() -> TraceEvent) {
+        if cfg!(feature = "debug-trace") {
+            self.events.push(event());
+        }
+    }
+
+    /// Returns all recorded events.
+    pub fn events(&self) -> &[TraceEvent] {
+        &self.events
+    }
+}
@@ -0,0 +1,364 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::{ensure, Context, Result};
+use ethers_core::k256::{
+    ecdsa::{RecoveryId, Signature, VerifyingKey},
+    elliptic_curve::sec1::ToEncodedPoint,
+    PublicKey,
+};
+use serde::{Deserialize, Serialize};
+use zeth_primitives::{keccak::keccak, Address, Bytes, B256, U256};
+
+use super::{batcher::BlockId, config::ChainConfig, rollup_config::RollupConfig};
+
+/// Domain of the signatures over payloads gossiped by the sequencer.
+const PAYLOAD_SIGNATURE_DOMAIN: B256 = B256::ZERO;
+
+/// Byte length of the parent beacon block root preceding the payloads since Ecotone.
+const PARENT_BEACON_BLOCK_ROOT_LEN: usize = 32;
+/// Byte length of the fixed-size part of an SSZ-encoded Bellatrix execution payload.
+/// The Canyon payloads only append the offset of the withdrawals.
+const PAYLOAD_FIXED_LEN: usize = 508;
+/// Byte length of the fixed-size part of an SSZ-encoded Ecotone execution payload.
+const ECOTONE_PAYLOAD_FIXED_LEN: usize = 528;
+/// Offset of the parent hash in an SSZ-encoded execution payload.
+const PARENT_HASH_OFFSET: usize = 0;
+/// Offset of the block number in an SSZ-encoded execution payload.
+const BLOCK_NUMBER_OFFSET: usize = 404;
+/// Offset of the timestamp in an SSZ-encoded execution payload.
+const TIMESTAMP_OFFSET: usize = 428;
+/// Offset of the block hash in an SSZ-encoded execution payload.
+const BLOCK_HASH_OFFSET: usize = 472;
+
+/// An SSZ-encoded execution payload signed by the sequencer, as gossiped on the p2p
+/// network.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SignedPayload {
+    /// The SSZ-encoded execution payload. Starting with Ecotone, it is preceded by the
+    /// parent beacon block root.
+    pub payload: Bytes,
+    /// Signature of the sequencer as `r || s || v`.
+    pub signature: Bytes,
+}
+
+impl SignedPayload {
+    /// Returns the hash signed by the sequencer for the given chain.
+    pub fn signing_hash(&self, chain_id: u64) -> B256 {
+        let mut data = PAYLOAD_SIGNATURE_DOMAIN.to_vec();
+        data.extend_from_slice(&U256::from(chain_id).to_be_bytes::<32>());
+        data.extend_from_slice(&keccak(&self.payload));
+        keccak(data).into()
+    }
+
+    /// Recovers the address of the key that signed the payload.
+    pub fn recover_signer(&self, chain_id: u64) -> Result<Address> {
+        recover_address(&self.signing_hash(chain_id), &self.signature)
+    }
+
+    /// Returns the parent hash of the payload.
+    pub fn parent_hash(&self, config: &ChainConfig) -> Result<B256> {
+        let offset = self.payload_offset(config)? + PARENT_HASH_OFFSET;
+        Ok(B256::from_slice(&self.payload[offset..offset + 32]))
+    }
+
+    /// Returns the number and hash of the block of the payload.
+    pub fn block_id(&self, config: &ChainConfig) -> Result<BlockId> {
+        let payload_offset = self.payload_offset(config)?;
+        let hash_offset = payload_offset + BLOCK_HASH_OFFSET;
+        Ok(BlockId {
+            number: self.u64_at(payload_offset + BLOCK_NUMBER_OFFSET),
+            hash: B256::from_slice(&self.payload[hash_offset..hash_offset + 32]),
+        })
+    }
+
+    /// Returns the offset of the execution payload, which depends on the layout of the
+    /// fork active at the timestamp of the payload.
+    ///
+    /// The timestamp is first read at its position in the Ecotone layout. In the older
+    /// layouts, these bytes are the upper bytes of the base fee, which are always zero.
+    fn payload_offset(&self, config: &ChainConfig) -> Result<usize> {
+        if self.payload.len() >= PARENT_BEACON_BLOCK_ROOT_LEN + ECOTONE_PAYLOAD_FIXED_LEN
+            && config.is_ecotone(self.u64_at(PARENT_BEACON_BLOCK_ROOT_LEN + TIMESTAMP_OFFSET))
+        {
+            return Ok(PARENT_BEACON_BLOCK_ROOT_LEN);
+        }
+
+        ensure!(
+            self.payload.len() >= PAYLOAD_FIXED_LEN,
+            "Payload is too short"
+        );
+        ensure!(
+            !config.is_ecotone(self.u64_at(TIMESTAMP_OFFSET)),
+            "Ecotone payload is missing the parent beacon block root"
+        );
+        Ok(0)
+    }
+
+    fn u64_at(&self, offset: usize) -> u64 {
+        u64::from_le_bytes(self.payload[offset..offset + 8].try_into().unwrap())
+    }
+}
+
+/// Input for attesting a chain of unsafe payloads.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct UnsafeInput {
+    /// Consecutive signed payloads, starting with the lowest block.
+    pub payloads: Vec<SignedPayload>,
+    /// Configuration of the chain, if it is not the one the guests are built for.
+    pub rollup_config: Option<RollupConfig>,
+}
+
+/// Output of attesting a chain of unsafe payloads.
+///
+/// Unlike [super::DeriveOutput], the blocks are only attested by the sequencer and have
+/// not been derived from the Ethereum chain.
+#[derive(Debug, Clone, Deserialize, Eq, PartialEq, Serialize)]
+pub struct UnsafeOutput {
+    /// Address of the sequencer that signed the payloads.
+    pub signer: Address,
+    /// Hash of the parent of the first unsafe block.
+    pub parent_hash: B256,
+    /// Unsafe Optimism blocks.
+    pub unsafe_op_blocks: Vec<BlockId>,
+    /// Hash of the [RollupConfig] of the chain, if it was given.
+    pub rollup_config_hash: Option<B256>,
+}
+
+impl UnsafeInput {
+    /// Returns the configuration of the chain: the one built from the [RollupConfig] if
+    /// given, the one of the chain the guests are built for otherwise.
+    pub fn chain_config(&self) -> Result<ChainConfig> {
+        match &self.rollup_config {
+            Some(rollup_config) => rollup_config
+                .chain_config()
+                .context("Invalid rollup config"),
+            None => Ok(ChainConfig::selected()),
+        }
+    }
+
+    /// Verifies that the payloads form a chain signed by the unsafe block signer of the
+    /// given configuration.
+    pub fn process(&self, config: &ChainConfig) -> Result<UnsafeOutput> {
+        let chain_id = config.chain_spec.chain_id();
+        let signer = config.system_config.unsafe_block_signer;
+        ensure!(
+            !signer.is_zero(),
+            "The chain configuration has no unsafe block signer"
+        );
+        let first = self.payloads.first().context("No payloads")?;
+
+        let parent_hash = first.parent_hash(config)?;
+        let mut parent = None::<BlockId>;
+        let mut unsafe_op_blocks = Vec::with_capacity(self.payloads.len());
+        for payload in &self.payloads {
+            ensure!(
+                payload.recover_signer(chain_id)? == signer,
+                "Payload is not signed by the unsafe block signer"
+            );
+
+            let block = payload.block_id(config)?;
+            if let Some(parent) = parent {
+                ensure!(
+                    block.number == parent.number + 1
+                        && payload.parent_hash(config)? == parent.hash,
+                    "Payload {} does not extend block {}",
+                    block.number,
+                    parent.number
+                );
+            }
+            parent = Some(block);
+            unsafe_op_blocks.push(block);
+        }
+
+        Ok(UnsafeOutput {
+            signer,
+            parent_hash,
+            unsafe_op_blocks,
+            rollup_config_hash: self.rollup_config.as_ref().map(RollupConfig::hash),
+        })
+    }
+}
+
+/// Recovers the address of the key that signed the given hash, with the signature given
+/// as `r || s || v`.
+pub fn recover_address(hash: &B256, signature: &[u8]) -> Result<Address> {
+    ensure!(signature.len() == 65, "Invalid signature length");
+    let (rs, v) = signature.split_at(64);
+    let signature = Signature::from_slice(rs).context("r, s invalid")?;
+    let recovery_id = RecoveryId::from_byte(v[0]).context("v invalid")?;
+    let verify_key = VerifyingKey::recover_from_prehash(hash.as_slice(), &signature, recovery_id)
+        .context("invalid signature")?;
+
+    let public_key = PublicKey::from(&verify_key).to_encoded_point(false);
+    let hash = keccak(&public_key.as_bytes()[1..]);
+
+    Ok(Address::from_slice(&hash[12..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers_core::k256::ecdsa::SigningKey;
+
+    use super::*;
+
+    /// Pre-Ecotone timestamp of the Optimism mainnet.
+    const TIMESTAMP: u64 = 1_700_000_000;
+
+    fn signed_payload(
+        key: &SigningKey,
+        config: &ChainConfig,
+        number: u64,
+        timestamp: u64,
+        parent_hash: B256,
+    ) -> SignedPayload {
+        // the Ecotone payloads are preceded by the parent beacon block root
+        let (offset, len) = if config.is_ecotone(timestamp) {
+            (
+                PARENT_BEACON_BLOCK_ROOT_LEN,
+                PARENT_BEACON_BLOCK_ROOT_LEN + ECOTONE_PAYLOAD_FIXED_LEN,
+            )
+        } else {
+            (0, PAYLOAD_FIXED_LEN)
+        };
+        let mut payload = vec![0u8; len];
+        payload[..offset].fill(0xbb);
+        let payload_fields = &mut payload[offset..];
+        payload_fields[PARENT_HASH_OFFSET..PARENT_HASH_OFFSET + 32]
+            .copy_from_slice(parent_hash.as_slice());
+        payload_fields[BLOCK_NUMBER_OFFSET..BLOCK_NUMBER_OFFSET + 8]
+            .copy_from_slice(&number.to_le_bytes());
+        payload_fields[TIMESTAMP_OFFSET..TIMESTAMP_OFFSET + 8]
+            .copy_from_slice(&timestamp.to_le_bytes());
+        payload_fields[BLOCK_HASH_OFFSET..BLOCK_HASH_OFFSET + 32]
+            .copy_from_slice(&keccak(number.to_le_bytes()));
+
+        let mut signed = SignedPayload {
+            payload: payload.into(),
+            signature: Bytes::new(),
+        };
+        let (signature, recovery_id) = key
+            .sign_prehash_recoverable(signed.signing_hash(config.chain_spec.chain_id()).as_slice())
+            .unwrap();
+        let mut signature = signature.to_bytes().to_vec();
+        signature.push(recovery_id.to_byte());
+        signed.signature = signature.into();
+        signed
+    }
+
+    fn config_and_key() -> (ChainConfig, SigningKey) {
+        let key = SigningKey::from_slice(&[1u8; 32]).unwrap();
+        let public_key = PublicKey::from(key.verifying_key()).to_encoded_point(false);
+        let mut config = ChainConfig::optimism();
+        config.system_config.unsafe_block_signer =
+            Address::from_slice(&keccak(&public_key.as_bytes()[1..])[12..]);
+        (config, key)
+    }
+
+    fn unsafe_input(payloads: Vec<SignedPayload>) -> UnsafeInput {
+        UnsafeInput {
+            payloads,
+            rollup_config: None,
+        }
+    }
+
+    #[test]
+    fn attest_chain() {
+        let (config, key) = config_and_key();
+        let first = signed_payload(&key, &config, 10, TIMESTAMP, B256::repeat_byte(0x01));
+        let second = signed_payload(
+            &key,
+            &config,
+            11,
+            TIMESTAMP + 2,
+            first.block_id(&config).unwrap().hash,
+        );
+
+        let output = unsafe_input(vec![first, second]).process(&config).unwrap();
+        assert_eq!(output.signer, config.system_config.unsafe_block_signer);
+        assert_eq!(output.parent_hash, B256::repeat_byte(0x01));
+        assert_eq!(output.unsafe_op_blocks.len(), 2);
+        assert_eq!(output.unsafe_op_blocks[1].number, 11);
+        assert_eq!(output.rollup_config_hash, None);
+    }
+
+    #[test]
+    fn attest_ecotone_chain() {
+        let (config, key) = config_and_key();
+        let ecotone_time = config.ecotone_time.unwrap();
+        // the chain crosses the Ecotone activation
+        let first = signed_payload(&key, &config, 10, ecotone_time - 2, B256::repeat_byte(0x01));
+        let second = signed_payload(
+            &key,
+            &config,
+            11,
+            ecotone_time,
+            first.block_id(&config).unwrap().hash,
+        );
+        assert_eq!(
+            second.payload.len(),
+            PARENT_BEACON_BLOCK_ROOT_LEN + ECOTONE_PAYLOAD_FIXED_LEN
+        );
+        assert_eq!(
+            second.parent_hash(&config).unwrap(),
+            first.block_id(&config).unwrap().hash
+        );
+
+        let output = unsafe_input(vec![first, second]).process(&config).unwrap();
+        assert_eq!(output.parent_hash, B256::repeat_byte(0x01));
+        assert_eq!(output.unsafe_op_blocks[1].number, 11);
+        assert_eq!(
+            output.unsafe_op_blocks[1].hash,
+            B256::from(keccak(11u64.to_le_bytes()))
+        );
+    }
+
+    #[test]
+    fn reject_ecotone_without_beacon_root() {
+        let (config, key) = config_and_key();
+        let ecotone_time = config.ecotone_time.unwrap();
+        // a payload with the Bellatrix layout, but an Ecotone timestamp
+        let mut pre_ecotone_config = config.clone();
+        pre_ecotone_config.ecotone_time = None;
+        let payload = signed_payload(&key, &pre_ecotone_config, 10, ecotone_time, B256::ZERO);
+
+        unsafe_input(vec![payload]).process(&config).unwrap_err();
+    }
+
+    #[test]
+    fn reject_wrong_signer() {
+        let (config, _) = config_and_key();
+        let other_key = SigningKey::from_slice(&[2u8; 32]).unwrap();
+        let payload = signed_payload(&other_key, &config, 10, TIMESTAMP, B256::ZERO);
+
+        unsafe_input(vec![payload]).process(&config).unwrap_err();
+    }
+
+    #[test]
+    fn reject_gap() {
+        let (config, key) = config_and_key();
+        let first = signed_payload(&key, &config, 10, TIMESTAMP, B256::ZERO);
+        let third = signed_payload(
+            &key,
+            &config,
+            12,
+            TIMESTAMP + 4,
+            first.block_id(&config).unwrap().hash,
+        );
+
+        unsafe_input(vec![first, third])
+            .process(&config)
+            .unwrap_err();
+    }
+}
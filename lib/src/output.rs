@@ -72,3 +72,12 @@ impl BlockBuildOutput {
         self
     }
 }
+
+/// Output of building a bundle of consecutive blocks
+#[derive(Debug, Clone, Eq, PartialEq, Deserialize, Serialize)]
+pub struct BlockBundleOutput {
+    /// Hash of the parent of the first block in the bundle
+    pub parent_hash: B256,
+    /// Hash of the last block in the bundle
+    pub hash: B256,
+}
@@ -0,0 +1,117 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use serde_json::json;
+use zeth_primitives::{
+    keccak::keccak,
+    transactions::{ethereum::EthereumTxEssence, EthereumTransaction},
+    trie::MptNode,
+};
+
+/// Returns the mainnet transaction
+/// 0x4540eb9c46b1654c26353ac3c65e56451f711926982ce1b02f15c50e7459caf7.
+fn transaction() -> EthereumTransaction {
+    let essence: EthereumTxEssence = serde_json::from_value(json!({
+        "Legacy": {
+            "nonce": 537760,
+            "gas_price": "0x03c49bfa04",
+            "gas_limit": "0x019a28",
+            "to": { "Call": "0xf0ee707731d1be239f9f482e1b2ea5384c0c426f" },
+            "value": "0x06df842eaa9fb800",
+            "data": "0x",
+            "chain_id": 1
+        }
+    }))
+    .unwrap();
+    let signature = serde_json::from_value(json!({
+        "v": 38,
+        "r": "0xcadd790a37b78e5613c8cf44dc3002e3d7f06a5325d045963c708efe3f9fdf7a",
+        "s": "0x1f63adb9a2d5e020c6aa0ff64695e25d7d9a780ed8471abe716d2dc0bf7d4259"
+    }))
+    .unwrap();
+
+    EthereumTransaction { essence, signature }
+}
+
+fn bench_keccak(c: &mut Criterion) {
+    let mut group = c.benchmark_group("keccak");
+    for size in [32, 136, 1024] {
+        let data = vec![0xa5u8; size];
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_function(format!("{}_bytes", size), |b| {
+            b.iter(|| keccak(black_box(&data)))
+        });
+    }
+    group.finish();
+}
+
+fn bench_rlp_encode(c: &mut Criterion) {
+    let transaction = transaction();
+    c.bench_function("rlp_encode/transaction", |b| {
+        b.iter(|| alloy_rlp::encode(black_box(&transaction)))
+    });
+}
+
+fn bench_trie(c: &mut Criterion) {
+    let mut group = c.benchmark_group("trie");
+    for n in [100usize, 1000] {
+        let keys: Vec<_> = (0..n).map(|i| keccak(i.to_be_bytes())).collect();
+        group.throughput(Throughput::Elements(n as u64));
+
+        group.bench_function(format!("insert_{}", n), |b| {
+            b.iter(|| {
+                let mut trie = MptNode::default();
+                for (i, key) in keys.iter().enumerate() {
+                    trie.insert_rlp(key, i).unwrap();
+                }
+                trie
+            })
+        });
+        group.bench_function(format!("insert_and_hash_{}", n), |b| {
+            b.iter(|| {
+                let mut trie = MptNode::default();
+                for (i, key) in keys.iter().enumerate() {
+                    trie.insert_rlp(key, i).unwrap();
+                }
+                trie.hash()
+            })
+        });
+        // hashing caches the node references, so every run hashes a fresh clone
+        let mut trie = MptNode::default();
+        for (i, key) in keys.iter().enumerate() {
+            trie.insert_rlp(key, i).unwrap();
+        }
+        group.bench_function(format!("hash_{}", n), |b| {
+            b.iter_batched(|| trie.clone(), |trie| trie.hash(), BatchSize::SmallInput)
+        });
+    }
+    group.finish();
+}
+
+fn bench_recover_sender(c: &mut Criterion) {
+    let transaction = transaction();
+    c.bench_function("recover_sender/eip155", |b| {
+        b.iter(|| black_box(&transaction).recover_from().unwrap())
+    });
+}
+
+criterion_group!(
+    benches,
+    bench_keccak,
+    bench_rlp_encode,
+    bench_trie,
+    bench_recover_sender
+);
+criterion_main!(benches);
@@ -61,6 +61,21 @@ pub struct Header {
     /// Shanghai update.
     #[serde(default)]
     pub withdrawals_root: Option<B256>,
+    /// Total amount of blob gas consumed by the transactions in the block. Present after
+    /// the Cancun update.
+    #[serde(default)]
+    pub blob_gas_used: Option<U256>,
+    /// Running total of blob gas consumed in excess of the target. Present after the
+    /// Cancun update.
+    #[serde(default)]
+    pub excess_blob_gas: Option<U256>,
+    /// Root hash of the parent beacon block. Present after the Cancun update.
+    #[serde(default)]
+    pub parent_beacon_block_root: Option<B256>,
+    /// Hash of the execution layer requests of the block, i.e. deposits, withdrawals and
+    /// consolidations. Present after the Prague update.
+    #[serde(default)]
+    pub requests_hash: Option<B256>,
 }
 
 impl Default for Header {
@@ -84,6 +99,10 @@ impl Default for Header {
             nonce: B64::ZERO,
             base_fee_per_gas: U256::ZERO,
             withdrawals_root: None,
+            blob_gas_used: None,
+            excess_blob_gas: None,
+            parent_beacon_block_root: None,
+            requests_hash: None,
         }
     }
 }
@@ -93,6 +112,25 @@ impl Header {
     pub fn hash(&self) -> BlockHash {
         keccak(alloy_rlp::encode(self)).into()
     }
+
+    /// Returns whether the optional fields form a valid sequence of network upgrades,
+    /// i.e. the fields of an upgrade are only present together and if the fields of
+    /// all previous upgrades are present. Otherwise, the header cannot be
+    /// RLP-encoded.
+    pub fn has_valid_upgrade_fields(&self) -> bool {
+        let shanghai = self.withdrawals_root.is_some();
+        let cancun = [
+            self.blob_gas_used.is_some(),
+            self.excess_blob_gas.is_some(),
+            self.parent_beacon_block_root.is_some(),
+        ];
+        let prague = self.requests_hash.is_some();
+
+        if cancun.iter().any(|&present| present != cancun[0]) {
+            return false;
+        }
+        (shanghai || !cancun[0]) && (cancun[0] || !prague)
+    }
 }
 
 #[cfg(test)]
@@ -165,4 +203,73 @@ mod tests {
             header.hash().to_string()
         )
     }
+
+    /// Returns the payload of the RLP-encoded list.
+    fn list_payload(mut rlp: &[u8]) -> &[u8] {
+        let header = alloy_rlp::Header::decode(&mut rlp).unwrap();
+        assert!(header.list);
+        rlp
+    }
+
+    #[test]
+    fn prague() {
+        let cancun = Header {
+            withdrawals_root: Some(EMPTY_ROOT),
+            blob_gas_used: Some(U256::ZERO),
+            excess_blob_gas: Some(U256::from(0x20000)),
+            parent_beacon_block_root: Some(B256::repeat_byte(0x01)),
+            ..Default::default()
+        };
+        assert!(cancun.has_valid_upgrade_fields());
+        let prague = Header {
+            requests_hash: Some(B256::repeat_byte(0x02)),
+            ..cancun.clone()
+        };
+        assert!(prague.has_valid_upgrade_fields());
+
+        // the requests hash is appended to the fields of the Cancun header
+        let cancun_rlp = alloy_rlp::encode(&cancun);
+        let prague_rlp = alloy_rlp::encode(&prague);
+        assert_eq!(
+            list_payload(&prague_rlp),
+            [
+                list_payload(&cancun_rlp),
+                &alloy_rlp::encode(prague.requests_hash.unwrap())
+            ]
+            .concat()
+        );
+        assert_ne!(prague.hash(), cancun.hash());
+
+        // bincode serialization works with the additional fields
+        let decoded: Header = bincode::deserialize(&bincode::serialize(&prague).unwrap()).unwrap();
+        assert_eq!(decoded, prague);
+    }
+
+    #[test]
+    fn invalid_upgrade_fields() {
+        // Prague fields without Cancun fields
+        let header = Header {
+            withdrawals_root: Some(EMPTY_ROOT),
+            requests_hash: Some(B256::ZERO),
+            ..Default::default()
+        };
+        assert!(!header.has_valid_upgrade_fields());
+
+        // incomplete Cancun fields
+        let header = Header {
+            withdrawals_root: Some(EMPTY_ROOT),
+            blob_gas_used: Some(U256::ZERO),
+            ..Default::default()
+        };
+        assert!(!header.has_valid_upgrade_fields());
+
+        // Cancun fields without Shanghai fields
+        let header = Header {
+            blob_gas_used: Some(U256::ZERO),
+            excess_blob_gas: Some(U256::ZERO),
+            parent_beacon_block_root: Some(B256::ZERO),
+            ..Default::default()
+        };
+        assert!(!header.has_valid_upgrade_fields());
+    }
 }
@@ -29,10 +29,11 @@ use ethers_core::types::{
 use crate::{
     access_list::{AccessList, AccessListItem},
     block::Header,
-    receipt::{Log, Receipt, ReceiptPayload, OPTIMISM_DEPOSIT_NONCE_VERSION},
+    receipt::{Log, Receipt, ReceiptError, ReceiptPayload, OPTIMISM_DEPOSIT_NONCE_VERSION},
     transactions::{
         ethereum::{
-            EthereumTxEssence, TransactionKind, TxEssenceEip1559, TxEssenceEip2930, TxEssenceLegacy,
+            EthereumTxEssence, TransactionKind, TxEssenceEip1559, TxEssenceEip2930,
+            TxEssenceEip4844, TxEssenceLegacy,
         },
         optimism::{OptimismTxEssence, TxEssenceOptimismDeposited},
         signature::TxSignature,
@@ -131,6 +132,16 @@ impl<T> TryFrom<EthersBlock<T>> for Header {
                 block.base_fee_per_gas.context("base_fee_per_gas missing")?,
             ),
             withdrawals_root: block.withdrawals_root.map(from_ethers_h256),
+            blob_gas_used: block.blob_gas_used.map(from_ethers_u256),
+            excess_blob_gas: block.excess_blob_gas.map(from_ethers_u256),
+            parent_beacon_block_root: block.parent_beacon_block_root.map(from_ethers_h256),
+            // not yet supported by ethers
+            requests_hash: block
+                .other
+                .get_deserialized::<EthersH256>("requestsHash")
+                .transpose()
+                .context("invalid requestsHash")?
+                .map(from_ethers_h256),
         })
     }
 }
@@ -218,6 +229,44 @@ impl TryFrom<EthersTransaction> for EthereumTxEssence {
                 access_list: tx.access_list.context("access_list missing")?.into(),
                 data: tx.input.0.into(),
             }),
+            Some(3) => EthereumTxEssence::Eip4844(TxEssenceEip4844 {
+                chain_id: tx
+                    .chain_id
+                    .context("chain_id missing")?
+                    .try_into()
+                    .map_err(|err| anyhow!("invalid chain_id: {}", err))?,
+                nonce: tx
+                    .nonce
+                    .try_into()
+                    .map_err(|err| anyhow!("invalid nonce: {}", err))?,
+                max_priority_fee_per_gas: from_ethers_u256(
+                    tx.max_priority_fee_per_gas
+                        .context("max_priority_fee_per_gas missing")?,
+                ),
+                max_fee_per_gas: from_ethers_u256(
+                    tx.max_fee_per_gas.context("max_fee_per_gas missing")?,
+                ),
+                gas_limit: from_ethers_u256(tx.gas),
+                to: tx.to.context("to missing")?.0.into(),
+                value: from_ethers_u256(tx.value),
+                access_list: tx.access_list.context("access_list missing")?.into(),
+                data: tx.input.0.into(),
+                // ethers does not know the blob fields, they end up in the other fields
+                max_fee_per_blob_gas: from_ethers_u256(
+                    tx.other
+                        .get_deserialized::<EthersU256>("maxFeePerBlobGas")
+                        .context("max_fee_per_blob_gas missing")?
+                        .context("invalid max_fee_per_blob_gas")?,
+                ),
+                blob_versioned_hashes: tx
+                    .other
+                    .get_deserialized::<Vec<EthersH256>>("blobVersionedHashes")
+                    .context("blob_versioned_hashes missing")?
+                    .context("invalid blob_versioned_hashes")?
+                    .into_iter()
+                    .map(from_ethers_h256)
+                    .collect(),
+            }),
             _ => unreachable!(),
         };
         Ok(essence)
@@ -303,6 +352,37 @@ impl TryFrom<EthersReceipt> for Receipt {
     }
 }
 
+/// Validates that the logs of the given receipts of a block are indexed consecutively
+/// across the block, as the index of a log is not part of the local [Receipt].
+pub fn validate_log_indices(receipts: &[EthersReceipt]) -> anyhow::Result<()> {
+    let mut expected = 0;
+    for (receipt_index, receipt) in receipts.iter().enumerate() {
+        for (log_index, log) in receipt.logs.iter().enumerate() {
+            let actual = log
+                .log_index
+                .with_context(|| {
+                    format!(
+                        "log {} of receipt {}: log_index missing",
+                        log_index, receipt_index
+                    )
+                })?
+                .as_u64();
+            if actual != expected {
+                return Err(ReceiptError::LogIndexMismatch {
+                    receipt: receipt_index,
+                    log: log_index,
+                    expected,
+                    actual,
+                }
+                .into());
+            }
+            expected += 1;
+        }
+    }
+
+    Ok(())
+}
+
 /// Conversion from `EIP1186ProofResponse` to the local [StateAccount].
 impl From<EIP1186ProofResponse> for StateAccount {
     fn from(response: EIP1186ProofResponse) -> Self {
@@ -0,0 +1,121 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Convert from and to the op-alloy types used by kona-derive.
+
+use alloy_primitives::{Address, Bytes, B256, U256};
+use kona_alloy_primitives as kona;
+use op_alloy_protocol::SingleBatch;
+
+use crate::batch::BatchEssence;
+
+/// Convert a kona `U256` type to the `U256` type.
+#[inline]
+pub fn from_kona_u256(v: kona::U256) -> U256 {
+    U256::from_limbs(v.into_limbs())
+}
+
+/// Convert an `U256` type to the kona `U256` type.
+#[inline]
+pub fn to_kona_u256(v: U256) -> kona::U256 {
+    kona::U256::from_limbs(v.into_limbs())
+}
+
+/// Convert a kona `Address` type to the `Address` type.
+#[inline]
+pub fn from_kona_address(v: kona::Address) -> Address {
+    v.into_array().into()
+}
+
+/// Convert an `Address` type to the kona `Address` type.
+#[inline]
+pub fn to_kona_address(v: Address) -> kona::Address {
+    v.into_array().into()
+}
+
+/// Convert a kona `B256` type to the `B256` type.
+#[inline]
+pub fn from_kona_b256(v: kona::B256) -> B256 {
+    v.0.into()
+}
+
+/// Convert a `B256` type to the kona `B256` type.
+#[inline]
+pub fn to_kona_b256(v: B256) -> kona::B256 {
+    v.0.into()
+}
+
+/// Convert a kona `Bytes` type to the `Bytes` type.
+#[inline]
+pub fn from_kona_bytes(v: kona::Bytes) -> Bytes {
+    v.0.into()
+}
+
+/// Convert a `Bytes` type to the kona `Bytes` type.
+#[inline]
+pub fn to_kona_bytes(v: Bytes) -> kona::Bytes {
+    v.0.into()
+}
+
+/// Conversion from a kona [SingleBatch] to the local [BatchEssence].
+impl From<SingleBatch> for BatchEssence {
+    fn from(batch: SingleBatch) -> Self {
+        BatchEssence {
+            parent_hash: from_kona_b256(batch.parent_hash),
+            epoch_num: batch.epoch_num,
+            epoch_hash: from_kona_b256(batch.epoch_hash),
+            timestamp: batch.timestamp,
+            transactions: batch
+                .transactions
+                .into_iter()
+                .map(from_kona_bytes)
+                .collect(),
+        }
+    }
+}
+
+/// Conversion from the local [BatchEssence] to a kona [SingleBatch].
+impl From<BatchEssence> for SingleBatch {
+    fn from(batch: BatchEssence) -> Self {
+        SingleBatch {
+            parent_hash: to_kona_b256(batch.parent_hash),
+            epoch_num: batch.epoch_num,
+            epoch_hash: to_kona_b256(batch.epoch_hash),
+            timestamp: batch.timestamp,
+            transactions: batch.transactions.into_iter().map(to_kona_bytes).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn single_batch_roundtrip() {
+        let batch = BatchEssence {
+            parent_hash: B256::repeat_byte(1),
+            epoch_num: 42,
+            epoch_hash: B256::repeat_byte(2),
+            timestamp: 1710374401,
+            transactions: vec![Bytes::from(vec![0x02, 0xc0]), Bytes::from(vec![0x01])],
+        };
+        let kona_batch = SingleBatch::from(batch.clone());
+
+        // both encode the batch as the same RLP list
+        assert_eq!(alloy_rlp::encode(&batch), alloy_rlp::encode(&kona_batch));
+
+        assert_eq!(BatchEssence::from(kona_batch), batch);
+    }
+}
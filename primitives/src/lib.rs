@@ -18,12 +18,15 @@ pub mod access_list;
 pub mod block;
 pub mod keccak;
 pub mod receipt;
+pub mod rlp;
 pub mod transactions;
 pub mod trie;
 pub mod withdrawal;
 
 #[cfg(feature = "ethers")]
 pub mod ethers;
+#[cfg(feature = "kona")]
+pub mod kona;
 
 pub mod batch;
 pub mod mmr;
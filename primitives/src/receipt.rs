@@ -16,6 +16,7 @@ use alloy_primitives::{Address, Bloom, BloomInput, Bytes, TxNumber, B256, U256};
 use alloy_rlp::Encodable;
 use alloy_rlp_derive::RlpEncodable;
 use serde::{Deserialize, Serialize};
+use thiserror::Error as ThisError;
 
 /// Version of the deposit nonce field in the receipt.
 pub const OPTIMISM_DEPOSIT_NONCE_VERSION: u32 = 1;
@@ -119,6 +120,68 @@ impl Receipt {
     }
 }
 
+/// Error returned when the receipts of a block are inconsistent.
+#[derive(Debug, Clone, PartialEq, Eq, ThisError)]
+pub enum ReceiptError {
+    /// The cumulative gas used decreases from one receipt to the next.
+    #[error("cumulative gas used of receipt {index} decreases from {previous} to {current}")]
+    DecreasingGasUsed {
+        index: usize,
+        previous: U256,
+        current: U256,
+    },
+    /// The cumulative gas used of the last receipt differs from the gas used by the
+    /// block.
+    #[error("receipts use {actual} gas, but the block uses {expected}")]
+    GasUsedMismatch { expected: U256, actual: U256 },
+    /// A log does not have the expected index within the block.
+    #[error("log {log} of receipt {receipt} has index {actual}, expected {expected}")]
+    LogIndexMismatch {
+        receipt: usize,
+        log: usize,
+        expected: u64,
+        actual: u64,
+    },
+}
+
+/// Validates that the cumulative gas used never decreases across the receipts of a block
+/// and that the last receipt accounts for all the gas used by the block.
+pub fn validate_cumulative_gas_used(
+    receipts: &[Receipt],
+    gas_used: U256,
+) -> Result<(), ReceiptError> {
+    let mut previous = U256::ZERO;
+    for (index, receipt) in receipts.iter().enumerate() {
+        let current = receipt.payload.cumulative_gas_used;
+        if current < previous {
+            return Err(ReceiptError::DecreasingGasUsed {
+                index,
+                previous,
+                current,
+            });
+        }
+        previous = current;
+    }
+    if previous != gas_used {
+        return Err(ReceiptError::GasUsedMismatch {
+            expected: gas_used,
+            actual: previous,
+        });
+    }
+
+    Ok(())
+}
+
+/// Returns the index of the first log of every receipt within its block. Logs are indexed
+/// consecutively across all receipts of a block, starting at zero.
+pub fn first_log_indices(receipts: &[Receipt]) -> impl Iterator<Item = usize> + '_ {
+    receipts.iter().scan(0, |next_index, receipt| {
+        let index = *next_index;
+        *next_index += receipt.payload.logs.len();
+        Some(index)
+    })
+}
+
 // test vectors from https://github.com/ethereum/go-ethereum/blob/c40ab6af72ce282020d03c33e8273ea9b03d58f6/core/types/receipt_test.go
 #[cfg(test)]
 mod tests {
@@ -127,6 +190,48 @@ mod tests {
 
     use super::*;
 
+    fn receipt(cumulative_gas_used: u64, log_count: usize) -> Receipt {
+        Receipt::new(
+            2,
+            true,
+            U256::from(cumulative_gas_used),
+            vec![Log::default(); log_count],
+        )
+    }
+
+    #[test]
+    fn cumulative_gas_used() {
+        let receipts = [receipt(21_000, 0), receipt(21_000, 1), receipt(63_000, 0)];
+        validate_cumulative_gas_used(&receipts, U256::from(63_000)).unwrap();
+        validate_cumulative_gas_used(&[], U256::ZERO).unwrap();
+
+        assert_eq!(
+            validate_cumulative_gas_used(&receipts, U256::from(84_000)),
+            Err(ReceiptError::GasUsedMismatch {
+                expected: U256::from(84_000),
+                actual: U256::from(63_000),
+            })
+        );
+        let receipts = [receipt(42_000, 0), receipt(21_000, 0)];
+        assert_eq!(
+            validate_cumulative_gas_used(&receipts, U256::from(21_000)),
+            Err(ReceiptError::DecreasingGasUsed {
+                index: 1,
+                previous: U256::from(42_000),
+                current: U256::from(21_000),
+            })
+        );
+    }
+
+    #[test]
+    fn log_indices() {
+        let receipts = [receipt(1, 2), receipt(2, 0), receipt(3, 3), receipt(4, 1)];
+        assert_eq!(
+            first_log_indices(&receipts).collect::<Vec<_>>(),
+            [0, 2, 2, 5]
+        );
+    }
+
     #[test]
     fn legacy() {
         let expected = hex!("f901c58001b9010000000000000010000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000500000000000000000000000000000000000014000000000000000000000000000000000000000000000000000000000000000000000000000010000080000000000000000000004000000000000000000000000000040000000000000000000000000000800000000000000000000000000000000000000000000000000000400000000000000000000000000000000000000000000000000000000000010000000000000000000000000000000000000000000000000000000000000f8bef85d940000000000000000000000000000000000000011f842a0000000000000000000000000000000000000000000000000000000000000deada0000000000000000000000000000000000000000000000000000000000000beef830100fff85d940000000000000000000000000000000000000111f842a0000000000000000000000000000000000000000000000000000000000000deada0000000000000000000000000000000000000000000000000000000000000beef830100ff");
@@ -0,0 +1,120 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloy_rlp::Encodable;
+
+/// The RLP encodings of the items of a list, together with the payload length of the
+/// list they form.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EncodedList {
+    items: Vec<Vec<u8>>,
+    payload_length: usize,
+}
+
+impl EncodedList {
+    /// Returns the RLP encoding of each item, in list order.
+    #[inline]
+    pub fn items(&self) -> &[Vec<u8>] {
+        &self.items
+    }
+
+    /// Consumes the list and returns the RLP encoding of each item, in list order.
+    #[inline]
+    pub fn into_items(self) -> Vec<Vec<u8>> {
+        self.items
+    }
+
+    /// Returns the length of the RLP-encoding payload of the list in bytes.
+    #[inline]
+    pub fn payload_length(&self) -> usize {
+        self.payload_length
+    }
+}
+
+impl Encodable for EncodedList {
+    /// Encodes the list header followed by the already encoded items.
+    #[inline]
+    fn encode(&self, out: &mut dyn alloy_rlp::BufMut) {
+        alloy_rlp::Header {
+            list: true,
+            payload_length: self.payload_length,
+        }
+        .encode(out);
+        for item in &self.items {
+            out.put_slice(item);
+        }
+    }
+
+    #[inline]
+    fn length(&self) -> usize {
+        alloy_rlp::length_of_length(self.payload_length) + self.payload_length
+    }
+}
+
+/// Encodes every item of `items` exactly once and returns the encodings together with
+/// the payload length of the list.
+///
+/// Computing [Encodable::length] of a transaction recurses through its essence and
+/// signature, and [alloy_rlp::encode_list] does this for every item before encoding it
+/// again. Here the length of each item is taken from its encoding, so that the same
+/// encodings can be used for the list, the transaction trie and the EVM environment.
+pub fn encode_list_with_lengths<T: Encodable>(items: &[T]) -> EncodedList {
+    let mut payload_length = 0;
+    let items = items
+        .iter()
+        .map(|item| {
+            let mut buf = Vec::new();
+            item.encode(&mut buf);
+            payload_length += buf.len();
+            buf
+        })
+        .collect();
+
+    EncodedList {
+        items,
+        payload_length,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_primitives::Bytes;
+
+    use super::*;
+
+    #[test]
+    fn matches_encode_list() {
+        let items: Vec<Bytes> = vec![
+            Bytes::new(),
+            Bytes::from_static(&[0x01]),
+            Bytes::from(vec![0xaa; 60]),
+        ];
+        let list = encode_list_with_lengths(&items);
+
+        let mut expected = Vec::new();
+        alloy_rlp::encode_list::<_, Bytes>(&items, &mut expected);
+        assert_eq!(alloy_rlp::encode(&list), expected);
+        assert_eq!(list.length(), expected.len());
+        for (item, encoded) in items.iter().zip(list.items()) {
+            assert_eq!(&alloy_rlp::encode(item), encoded);
+        }
+    }
+
+    #[test]
+    fn empty_list() {
+        let list = encode_list_with_lengths::<Bytes>(&[]);
+        assert_eq!(list.payload_length(), 0);
+        assert_eq!(alloy_rlp::encode(&list), vec![alloy_rlp::EMPTY_LIST_CODE]);
+    }
+}
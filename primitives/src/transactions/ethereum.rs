@@ -23,6 +23,8 @@ use k256::{
     PublicKey as K256PublicKey,
 };
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error as ThisError;
 
 use super::signature::TxSignature;
 use crate::{
@@ -339,6 +341,175 @@ impl SignedDecodable<TxSignature> for TxEssenceEip1559 {
     }
 }
 
+/// Represents an Ethereum transaction carrying blobs, as detailed in [EIP-4844](https://eips.ethereum.org/EIPS/eip-4844).
+///
+/// The `TxEssenceEip4844` struct extends an EIP-1559 transaction with the fee for blob
+/// gas and the versioned hashes of the blobs. The blobs themselves are not part of the
+/// transaction, they are only committed to by the versioned hashes.
+#[derive(
+    Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize, RlpEncodable, RlpDecodable,
+)]
+pub struct TxEssenceEip4844 {
+    /// The network's chain ID, ensuring the transaction is valid on the intended chain,
+    /// as introduced in EIP-155.
+    pub chain_id: ChainId,
+    /// A numeric value representing the total number of transactions previously sent by
+    /// the sender.
+    pub nonce: TxNumber,
+    /// The maximum priority fee per unit of gas that the sender is willing to pay to the
+    /// miner.
+    pub max_priority_fee_per_gas: U256,
+    /// The combined maximum fee (base + priority) per unit of gas that the sender is
+    /// willing to pay for the transaction's execution.
+    pub max_fee_per_gas: U256,
+    /// The maximum amount of gas allocated for the transaction's execution.
+    pub gas_limit: U256,
+    /// The 160-bit address of the intended recipient for a message call. Blob
+    /// transactions cannot create contracts.
+    pub to: Address,
+    /// The amount, in Wei, to be transferred to the recipient of the message call.
+    pub value: U256,
+    /// The transaction's payload, represented as a variable-length byte array.
+    pub data: Bytes,
+    /// A list of addresses and storage keys that the transaction will access, aiding in
+    /// gas optimization.
+    pub access_list: AccessList,
+    /// The maximum fee per unit of blob gas that the sender is willing to pay.
+    pub max_fee_per_blob_gas: U256,
+    /// The versioned hashes of the KZG commitments to the blobs of the transaction.
+    pub blob_versioned_hashes: Vec<B256>,
+}
+
+#[derive(
+    Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize, RlpEncodable, RlpDecodable,
+)]
+struct TxEssenceEip4844TxSignature {
+    pub chain_id: ChainId,
+    pub nonce: TxNumber,
+    pub max_priority_fee_per_gas: U256,
+    pub max_fee_per_gas: U256,
+    pub gas_limit: U256,
+    pub to: Address,
+    pub value: U256,
+    pub data: Bytes,
+    pub access_list: AccessList,
+    pub max_fee_per_blob_gas: U256,
+    pub blob_versioned_hashes: Vec<B256>,
+    pub v: u64,
+    pub r: U256,
+    pub s: U256,
+}
+
+impl SignedDecodable<TxSignature> for TxEssenceEip4844 {
+    fn decode_signed(buf: &mut &[u8]) -> alloy_rlp::Result<(Self, TxSignature)> {
+        let signed_essence = TxEssenceEip4844TxSignature::decode(buf)?;
+        Ok((
+            Self {
+                chain_id: signed_essence.chain_id,
+                nonce: signed_essence.nonce,
+                max_priority_fee_per_gas: signed_essence.max_priority_fee_per_gas,
+                max_fee_per_gas: signed_essence.max_fee_per_gas,
+                gas_limit: signed_essence.gas_limit,
+                to: signed_essence.to,
+                value: signed_essence.value,
+                data: signed_essence.data,
+                access_list: signed_essence.access_list,
+                max_fee_per_blob_gas: signed_essence.max_fee_per_blob_gas,
+                blob_versioned_hashes: signed_essence.blob_versioned_hashes,
+            },
+            TxSignature {
+                v: signed_essence.v,
+                r: signed_essence.r,
+                s: signed_essence.s,
+            },
+        ))
+    }
+}
+
+/// Version byte of the versioned hash of a KZG commitment, as used by [TxEssenceEip4844].
+pub const VERSIONED_HASH_VERSION_KZG: u8 = 0x01;
+
+/// Computes the versioned hash of a KZG commitment, i.e. its SHA-256 hash with the first
+/// byte replaced by [VERSIONED_HASH_VERSION_KZG].
+pub fn kzg_to_versioned_hash(commitment: &[u8]) -> B256 {
+    let mut hash: [u8; 32] = Sha256::digest(commitment).into();
+    hash[0] = VERSIONED_HASH_VERSION_KZG;
+    hash.into()
+}
+
+/// Error returned when the versioned hashes of a blob transaction are invalid.
+#[derive(Debug, Clone, PartialEq, Eq, ThisError)]
+pub enum BlobHashError {
+    /// The transaction does not reference any blobs.
+    #[error("blob transaction without versioned hashes")]
+    Empty,
+    /// A versioned hash does not start with [VERSIONED_HASH_VERSION_KZG].
+    #[error("versioned hash {index} has unsupported version {version}")]
+    UnsupportedVersion { index: usize, version: u8 },
+    /// The number of commitments differs from the number of versioned hashes.
+    #[error("{actual} commitments provided for {expected} versioned hashes")]
+    CountMismatch { expected: usize, actual: usize },
+    /// A versioned hash does not match the commitment at the same index.
+    #[error("versioned hash {index} is {expected}, but the commitment hashes to {actual}")]
+    CommitmentMismatch {
+        index: usize,
+        expected: B256,
+        actual: B256,
+    },
+}
+
+/// Validates that the versioned hashes match the KZG commitments, in the same order.
+pub fn validate_blob_versioned_hashes<C: AsRef<[u8]>>(
+    versioned_hashes: &[B256],
+    commitments: &[C],
+) -> Result<(), BlobHashError> {
+    if versioned_hashes.len() != commitments.len() {
+        return Err(BlobHashError::CountMismatch {
+            expected: versioned_hashes.len(),
+            actual: commitments.len(),
+        });
+    }
+    for (index, (&expected, commitment)) in versioned_hashes.iter().zip(commitments).enumerate() {
+        let actual = kzg_to_versioned_hash(commitment.as_ref());
+        if actual != expected {
+            return Err(BlobHashError::CommitmentMismatch {
+                index,
+                expected,
+                actual,
+            });
+        }
+    }
+    Ok(())
+}
+
+impl TxEssenceEip4844 {
+    /// Validates the versioned hashes of the transaction on their own: it must reference
+    /// at least one blob, and every hash must be of a KZG commitment.
+    pub fn validate_versioned_hashes(&self) -> Result<(), BlobHashError> {
+        if self.blob_versioned_hashes.is_empty() {
+            return Err(BlobHashError::Empty);
+        }
+        for (index, hash) in self.blob_versioned_hashes.iter().enumerate() {
+            if hash[0] != VERSIONED_HASH_VERSION_KZG {
+                return Err(BlobHashError::UnsupportedVersion {
+                    index,
+                    version: hash[0],
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Validates that the versioned hashes of the transaction match the KZG commitments
+    /// to its blobs, in the same order.
+    pub fn validate_blob_commitments<C: AsRef<[u8]>>(
+        &self,
+        commitments: &[C],
+    ) -> Result<(), BlobHashError> {
+        validate_blob_versioned_hashes(&self.blob_versioned_hashes, commitments)
+    }
+}
+
 /// Represents the type of an Ethereum transaction: either a contract creation or a call
 /// to an existing contract.
 ///
@@ -437,6 +608,9 @@ pub enum EthereumTxEssence {
     /// This mechanism aims to improve the predictability of gas fees and enhances the
     /// overall user experience.
     Eip1559(TxEssenceEip1559),
+    /// Represents an Ethereum transaction that carries blobs, as detailed in [EIP-4844](https://eips.ethereum.org/EIPS/eip-4844).
+    /// The blobs are not included in the transaction, only their versioned hashes.
+    Eip4844(TxEssenceEip4844),
 }
 
 impl Encodable for EthereumTxEssence {
@@ -451,6 +625,7 @@ impl Encodable for EthereumTxEssence {
             EthereumTxEssence::Legacy(tx) => tx.encode(out),
             EthereumTxEssence::Eip2930(tx) => tx.encode(out),
             EthereumTxEssence::Eip1559(tx) => tx.encode(out),
+            EthereumTxEssence::Eip4844(tx) => tx.encode(out),
         }
     }
 
@@ -465,6 +640,7 @@ impl Encodable for EthereumTxEssence {
             EthereumTxEssence::Legacy(tx) => tx.length(),
             EthereumTxEssence::Eip2930(tx) => tx.length(),
             EthereumTxEssence::Eip1559(tx) => tx.length(),
+            EthereumTxEssence::Eip4844(tx) => tx.length(),
         }
     }
 }
@@ -481,6 +657,8 @@ impl SignedDecodable<TxSignature> for EthereumTxEssence {
                         .map(|(e, s)| (EthereumTxEssence::Eip2930(e), s)),
                     0x02 => TxEssenceEip1559::decode_signed(buf)
                         .map(|(e, s)| (EthereumTxEssence::Eip1559(e), s)),
+                    0x03 => TxEssenceEip4844::decode_signed(buf)
+                        .map(|(e, s)| (EthereumTxEssence::Eip4844(e), s)),
                     _ => Err(alloy_rlp::Error::Custom("Unsupported transaction type")),
                 }
             }
@@ -498,15 +676,15 @@ impl EthereumTxEssence {
     ///
     /// This method calculates the Keccak hash of the data that needs to be signed
     /// for the transaction, ensuring the integrity and authenticity of the transaction.
-    pub(crate) fn signing_hash(&self) -> B256 {
+    pub fn signing_hash(&self) -> B256 {
         keccak(self.signing_data()).into()
     }
 
     /// Retrieves the data that should be signed for the transaction essence.
     ///
     /// Depending on the variant of the [EthereumTxEssence] enum, this method prepares the
-    /// appropriate data for signing. For EIP-2930 and EIP-1559 transactions, a specific
-    /// prefix byte is added before the transaction data.
+    /// appropriate data for signing. For EIP-2930, EIP-1559 and EIP-4844 transactions, a
+    /// specific prefix byte is added before the transaction data.
     fn signing_data(&self) -> Vec<u8> {
         match self {
             EthereumTxEssence::Legacy(tx) => {
@@ -526,6 +704,12 @@ impl EthereumTxEssence {
                 tx.encode(&mut buf);
                 buf
             }
+            EthereumTxEssence::Eip4844(tx) => {
+                let mut buf = Vec::with_capacity(tx.length() + 1);
+                buf.push(0x03);
+                tx.encode(&mut buf);
+                buf
+            }
         }
     }
 
@@ -563,6 +747,7 @@ impl TxEssence for EthereumTxEssence {
             EthereumTxEssence::Legacy(_) => 0x00,
             EthereumTxEssence::Eip2930(_) => 0x01,
             EthereumTxEssence::Eip1559(_) => 0x02,
+            EthereumTxEssence::Eip4844(_) => 0x03,
         }
     }
     /// Returns the gas limit set for the transaction.
@@ -571,6 +756,7 @@ impl TxEssence for EthereumTxEssence {
             EthereumTxEssence::Legacy(tx) => tx.gas_limit,
             EthereumTxEssence::Eip2930(tx) => tx.gas_limit,
             EthereumTxEssence::Eip1559(tx) => tx.gas_limit,
+            EthereumTxEssence::Eip4844(tx) => tx.gas_limit,
         }
     }
     /// Returns the recipient address of the transaction, if available.
@@ -579,6 +765,7 @@ impl TxEssence for EthereumTxEssence {
             EthereumTxEssence::Legacy(tx) => tx.to.into(),
             EthereumTxEssence::Eip2930(tx) => tx.to.into(),
             EthereumTxEssence::Eip1559(tx) => tx.to.into(),
+            EthereumTxEssence::Eip4844(tx) => Some(tx.to),
         }
     }
     /// Recovers the Ethereum address of the sender from the transaction's signature.
@@ -609,6 +796,7 @@ impl TxEssence for EthereumTxEssence {
             EthereumTxEssence::Legacy(tx) => tx.payload_length(),
             EthereumTxEssence::Eip2930(tx) => tx._alloy_rlp_payload_length(),
             EthereumTxEssence::Eip1559(tx) => tx._alloy_rlp_payload_length(),
+            EthereumTxEssence::Eip4844(tx) => tx._alloy_rlp_payload_length(),
         }
     }
     /// Returns a reference to the transaction's call data
@@ -617,6 +805,34 @@ impl TxEssence for EthereumTxEssence {
             EthereumTxEssence::Legacy(tx) => &tx.data,
             EthereumTxEssence::Eip2930(tx) => &tx.data,
             EthereumTxEssence::Eip1559(tx) => &tx.data,
+            EthereumTxEssence::Eip4844(tx) => &tx.data,
+        }
+    }
+    /// Returns the amount of Wei transferred to the recipient of the transaction.
+    fn value(&self) -> U256 {
+        match self {
+            EthereumTxEssence::Legacy(tx) => tx.value,
+            EthereumTxEssence::Eip2930(tx) => tx.value,
+            EthereumTxEssence::Eip1559(tx) => tx.value,
+            EthereumTxEssence::Eip4844(tx) => tx.value,
+        }
+    }
+    /// Returns the nonce of the transaction, which every Ethereum transaction has.
+    fn nonce(&self) -> Option<TxNumber> {
+        match self {
+            EthereumTxEssence::Legacy(tx) => Some(tx.nonce),
+            EthereumTxEssence::Eip2930(tx) => Some(tx.nonce),
+            EthereumTxEssence::Eip1559(tx) => Some(tx.nonce),
+            EthereumTxEssence::Eip4844(tx) => Some(tx.nonce),
+        }
+    }
+    /// Returns the access list of the transaction, or `None` for Legacy transactions.
+    fn access_list(&self) -> Option<&AccessList> {
+        match self {
+            EthereumTxEssence::Legacy(_) => None,
+            EthereumTxEssence::Eip2930(tx) => Some(&tx.access_list),
+            EthereumTxEssence::Eip1559(tx) => Some(&tx.access_list),
+            EthereumTxEssence::Eip4844(tx) => Some(&tx.access_list),
         }
     }
 }
@@ -672,6 +888,10 @@ mod tests {
             recovered,
             address!("a1e4380a3b1f749673e270229993ee55f35663b4")
         );
+
+        assert_eq!(transaction.essence.value(), U256::from(0x7a69));
+        assert_eq!(transaction.essence.nonce(), Some(0));
+        assert_eq!(transaction.essence.access_list(), None);
     }
 
     #[test]
@@ -795,6 +1015,10 @@ mod tests {
             recovered,
             address!("79b7a69d90c82e014bf0315e164208119b510fa0")
         );
+
+        assert_eq!(transaction.essence.value(), U256::from(0x10d1471));
+        assert_eq!(transaction.essence.nonce(), Some(93847));
+        assert_eq!(transaction.essence.access_list().unwrap().0.len(), 4);
     }
 
     #[test]
@@ -841,4 +1065,110 @@ mod tests {
             address!("4b9f4114d50e7907bff87728a060ce8d53bf4cf7")
         );
     }
+
+    #[test]
+    fn eip4844() {
+        let essence: EthereumTxEssence = serde_json::from_value(json!({
+                "Eip4844": {
+                  "chain_id": 1,
+                  "nonce": 7,
+                  "max_priority_fee_per_gas": "0x3b9aca00",
+                  "max_fee_per_gas": "0x89d5f3200",
+                  "gas_limit": "0x5208",
+                  "to": "0xff00000000000000000000000000000000000010",
+                  "value": "0x0",
+                  "data": "0x",
+                  "access_list": [],
+                  "max_fee_per_blob_gas": "0x3b9aca00",
+                  "blob_versioned_hashes": [
+                    "0x01b2c8c5b6d3a8e6f0b1c2d3e4f5a6b7c8d9e0f1a2b3c4d5e6f7a8b9c0d1e2f3"
+                  ]
+                }
+        }))
+        .unwrap();
+
+        // sign the essence with a fixed key
+        let key = k256::ecdsa::SigningKey::from_slice(&[1u8; 32]).unwrap();
+        let (sig, recid) = key
+            .sign_prehash_recoverable(essence.signing_hash().as_slice())
+            .unwrap();
+        let signature = TxSignature {
+            v: recid.is_y_odd() as u64,
+            r: U256::from_be_slice(&sig.r().to_bytes()),
+            s: U256::from_be_slice(&sig.s().to_bytes()),
+        };
+        let transaction = EthereumTransaction { essence, signature };
+
+        // verify the RLP roundtrip of the EIP-2718 envelope
+        let encoded = alloy_rlp::encode(&transaction);
+        assert_eq!(encoded[0], 0x03);
+        assert_eq!(encoded.len(), transaction.length());
+        let decoded = Transaction::decode_bytes(&encoded).unwrap();
+        assert_eq!(transaction, decoded);
+
+        // verify that bincode serialization works
+        let _: EthereumTransaction =
+            bincode::deserialize(&bincode::serialize(&transaction).unwrap()).unwrap();
+
+        assert_eq!(
+            transaction.essence.to(),
+            Some(address!("ff00000000000000000000000000000000000010"))
+        );
+        let public_key = key.verifying_key().to_encoded_point(false);
+        let expected = Address::from_slice(&keccak(&public_key.as_bytes()[1..])[12..]);
+        assert_eq!(transaction.recover_from().unwrap(), expected);
+    }
+
+    #[test]
+    fn versioned_hash() {
+        // the commitment to the empty blob is the point at infinity
+        let mut commitment = [0u8; 48];
+        commitment[0] = 0xc0;
+        assert_eq!(
+            kzg_to_versioned_hash(&commitment),
+            b256!("010657f37554c781402a22917dee2f75def7ab966d7b770905398eba3c444014")
+        );
+    }
+
+    #[test]
+    fn blob_versioned_hashes() {
+        let commitments = [[0x01u8; 48], [0x02u8; 48]];
+        let tx = TxEssenceEip4844 {
+            blob_versioned_hashes: commitments
+                .iter()
+                .map(|commitment| kzg_to_versioned_hash(commitment))
+                .collect(),
+            ..Default::default()
+        };
+        tx.validate_versioned_hashes().unwrap();
+        tx.validate_blob_commitments(&commitments).unwrap();
+
+        // the commitments must be in the order of the versioned hashes
+        let swapped = [commitments[1], commitments[0]];
+        assert!(matches!(
+            tx.validate_blob_commitments(&swapped),
+            Err(BlobHashError::CommitmentMismatch { index: 0, .. })
+        ));
+        assert_eq!(
+            tx.validate_blob_commitments(&commitments[..1]),
+            Err(BlobHashError::CountMismatch {
+                expected: 2,
+                actual: 1
+            })
+        );
+
+        let mut invalid = tx.clone();
+        invalid.blob_versioned_hashes[1].0[0] = 0x02;
+        assert_eq!(
+            invalid.validate_versioned_hashes(),
+            Err(BlobHashError::UnsupportedVersion {
+                index: 1,
+                version: 0x02
+            })
+        );
+        assert_eq!(
+            TxEssenceEip4844::default().validate_versioned_hashes(),
+            Err(BlobHashError::Empty)
+        );
+    }
 }
@@ -14,7 +14,7 @@
 
 use std::fmt::Debug;
 
-use alloy_primitives::{Address, Bytes, TxHash};
+use alloy_primitives::{Address, Bytes, TxHash, TxNumber};
 use alloy_rlp::{Decodable, Encodable};
 use anyhow::ensure;
 use serde::{Deserialize, Serialize};
@@ -23,7 +23,9 @@ use self::{
     optimism::{OptimismTxEssence, OPTIMISM_DEPOSITED_TX_TYPE},
     signature::TxSignature,
 };
-use crate::{keccak::keccak, transactions::ethereum::EthereumTxEssence, U256};
+use crate::{
+    access_list::AccessList, keccak::keccak, transactions::ethereum::EthereumTxEssence, U256,
+};
 
 pub mod ethereum;
 pub mod optimism;
@@ -77,6 +79,16 @@ pub trait TxEssence: SignedDecodable<TxSignature> + Encodable + Clone {
     fn recover_from(&self, signature: &TxSignature) -> anyhow::Result<Address>;
     /// Returns a reference to the transaction's call data
     fn data(&self) -> &Bytes;
+    /// Returns the amount of Wei transferred to the recipient of the transaction.
+    fn value(&self) -> U256;
+    /// Returns the nonce of the transaction, if it has one.
+    ///
+    /// Optimism deposited transactions have no nonce. Instead, they use the nonce of
+    /// the sender's account at the time of execution, which is recorded in their receipt
+    /// starting with Canyon.
+    fn nonce(&self) -> Option<TxNumber>;
+    /// Returns the access list of the transaction, if its type supports one.
+    fn access_list(&self) -> Option<&AccessList>;
 
     /// Returns the length of the RLP-encoding payload in bytes.
     fn payload_length(&self) -> usize;
@@ -12,16 +12,19 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use alloy_primitives::{Address, Bytes, B256, U256};
+use alloy_primitives::{Address, Bytes, TxNumber, B256, U256};
 use alloy_rlp::{Decodable, Encodable};
 use alloy_rlp_derive::{RlpDecodable, RlpEncodable};
 use bytes::{Buf, BufMut};
 use serde::{Deserialize, Serialize};
 
 use super::signature::TxSignature;
-use crate::transactions::{
-    ethereum::{EthereumTxEssence, TransactionKind},
-    SignedDecodable, TxEssence,
+use crate::{
+    access_list::AccessList,
+    transactions::{
+        ethereum::{EthereumTxEssence, TransactionKind},
+        SignedDecodable, TxEssence,
+    },
 };
 
 /// The EIP-2718 transaction type for an Optimism deposited transaction.
@@ -29,6 +32,10 @@ pub const OPTIMISM_DEPOSITED_TX_TYPE: u8 = 0x7E;
 
 /// Represents an Optimism depositing transaction that is a L2 transaction that was
 /// derived from L1 and included in a L2 block.
+///
+/// A deposited transaction has neither a signature nor a nonce. It is executed with the
+/// current nonce of the `from` account, which is incremented as for any other
+/// transaction and, starting with Canyon, recorded as the deposit nonce of its receipt.
 #[derive(
     Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize, RlpEncodable, RlpDecodable,
 )]
@@ -145,6 +152,27 @@ impl TxEssence for OptimismTxEssence {
             OptimismTxEssence::OptimismDeposited(op) => &op.data,
         }
     }
+    /// Returns the amount of Wei transferred to the recipient of the transaction.
+    fn value(&self) -> U256 {
+        match self {
+            OptimismTxEssence::Ethereum(eth) => eth.value(),
+            OptimismTxEssence::OptimismDeposited(op) => op.value,
+        }
+    }
+    /// Returns the nonce of the transaction, or `None` for deposited transactions.
+    fn nonce(&self) -> Option<TxNumber> {
+        match self {
+            OptimismTxEssence::Ethereum(eth) => eth.nonce(),
+            OptimismTxEssence::OptimismDeposited(_) => None,
+        }
+    }
+    /// Returns the access list of the transaction, or `None` for deposited transactions.
+    fn access_list(&self) -> Option<&AccessList> {
+        match self {
+            OptimismTxEssence::Ethereum(eth) => eth.access_list(),
+            OptimismTxEssence::OptimismDeposited(_) => None,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -207,6 +235,10 @@ mod tests {
             recovered,
             address!("96dd9c6f1fd5b3fbaa70898f09bedff903237d6d")
         );
+
+        assert_eq!(transaction.essence.value(), U256::ZERO);
+        assert_eq!(transaction.essence.nonce(), Some(17));
+        assert_eq!(transaction.essence.access_list(), Some(&AccessList(vec![])));
     }
 
     #[test]
@@ -251,5 +283,12 @@ mod tests {
             recovered,
             address!("36bde71c97b33cc4729cf772ae268934f7ab70b2")
         );
+
+        assert_eq!(
+            transaction.essence.value(),
+            U256::from(0x030d98d59a960000u64)
+        );
+        assert_eq!(transaction.essence.nonce(), None);
+        assert_eq!(transaction.essence.access_list(), None);
     }
 }
@@ -0,0 +1,328 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Flat witness format for sparse Merkle Patricia Tries.
+//!
+//! Instead of the nodes themselves, a [FlatTrie] lists the key-value pairs of all
+//! resolved leaves and the digests of all unresolved sub-tries, both sorted by their
+//! path. As the structure of an MPT is fully determined by its content, the trie can be
+//! reconstructed from these entries and its root checked against the expected hash.
+//! Shared key prefixes are no longer repeated in every node, and the sorted entries
+//! compress considerably better than the nested nodes.
+
+extern crate alloc;
+
+use alloc::boxed::Box;
+use core::{array, cmp::Ordering};
+
+use alloy_primitives::B256;
+use serde::{Deserialize, Serialize};
+use thiserror::Error as ThisError;
+
+use crate::trie::{mpt::lcp, to_encoded_path, to_nibs, MptNode, MptNodeData};
+
+/// Errors that can occur when flattening or reconstructing a trie.
+#[derive(Debug, PartialEq, Eq, ThisError)]
+pub enum Error {
+    /// The key of a leaf does not consist of whole bytes.
+    #[error("leaf key of odd nibble length")]
+    OddKeyLength,
+    /// The entries are not strictly sorted by their path.
+    #[error("entries not strictly sorted")]
+    Unsorted,
+    /// The path of an entry is a prefix of the path of another entry.
+    #[error("overlapping entries")]
+    Overlapping,
+    /// The reconstructed trie does not have the expected root hash.
+    #[error("root mismatch: expected {expected}, got {got}")]
+    RootMismatch { expected: B256, got: B256 },
+}
+
+/// A sparse Merkle Patricia Trie represented by its sorted leaves and boundary digests.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FlatTrie {
+    /// Keys and values of all resolved leaves, sorted by key.
+    pub leaves: Vec<(Vec<u8>, Vec<u8>)>,
+    /// Nibble paths and digests of all unresolved sub-tries, sorted by path.
+    pub boundary: Vec<(Vec<u8>, B256)>,
+}
+
+/// A leaf or boundary entry of a [FlatTrie], located by its nibble path.
+enum Entry<'a> {
+    Leaf(&'a [u8]),
+    Digest(B256),
+}
+
+impl FlatTrie {
+    /// Flattens the given sparse trie.
+    pub fn from_trie(trie: &MptNode) -> Result<Self, Error> {
+        let mut flat = FlatTrie::default();
+        flat.collect(trie, &mut Vec::new())?;
+        Ok(flat)
+    }
+
+    /// Reconstructs the sparse trie and verifies that its root hash matches `root`.
+    pub fn reconstruct(&self, root: B256) -> Result<MptNode, Error> {
+        let entries = self.entries()?;
+        let trie = build(&entries, 0);
+        let got = trie.hash();
+        if got != root {
+            return Err(Error::RootMismatch {
+                expected: root,
+                got,
+            });
+        }
+        Ok(trie)
+    }
+
+    /// Appends the entries of the sub-trie `node` located at the nibble path `path`.
+    fn collect(&mut self, node: &MptNode, path: &mut Vec<u8>) -> Result<(), Error> {
+        match node.as_data() {
+            MptNodeData::Null => {}
+            MptNodeData::Branch(children) => {
+                for (i, child) in children.iter().enumerate() {
+                    if let Some(child) = child {
+                        path.push(i as u8);
+                        self.collect(child, path)?;
+                        path.pop();
+                    }
+                }
+            }
+            MptNodeData::Leaf(_, value) => {
+                let nibs = [path.as_slice(), &node.nibs()].concat();
+                if !nibs.len().is_multiple_of(2) {
+                    return Err(Error::OddKeyLength);
+                }
+                let key = nibs
+                    .as_chunks::<2>()
+                    .0
+                    .iter()
+                    .map(|[hi, lo]| (hi << 4) | lo)
+                    .collect();
+                self.leaves.push((key, value.clone()));
+            }
+            MptNodeData::Extension(_, child) => {
+                let len = path.len();
+                path.extend(node.nibs());
+                self.collect(child, path)?;
+                path.truncate(len);
+            }
+            MptNodeData::Digest(digest) => self.boundary.push((path.clone(), *digest)),
+        }
+        Ok(())
+    }
+
+    /// Merges leaves and boundary into a single list sorted by nibble path, verifying
+    /// that no path is a prefix of another.
+    fn entries(&self) -> Result<Vec<(Vec<u8>, Entry<'_>)>, Error> {
+        let mut entries: Vec<(Vec<u8>, Entry)> =
+            Vec::with_capacity(self.leaves.len() + self.boundary.len());
+        let mut leaves = self
+            .leaves
+            .iter()
+            .map(|(key, value)| (to_nibs(key), Entry::Leaf(value)))
+            .peekable();
+        let mut boundary = self
+            .boundary
+            .iter()
+            .map(|(path, digest)| (path.clone(), Entry::Digest(*digest)))
+            .peekable();
+        loop {
+            let next = match (leaves.peek(), boundary.peek()) {
+                (Some((a, _)), Some((b, _))) if a < b => leaves.next(),
+                (Some(_), Some(_)) => boundary.next(),
+                (Some(_), None) => leaves.next(),
+                (None, _) => boundary.next(),
+            };
+            let Some((path, entry)) = next else {
+                break;
+            };
+            if let Some((prev, _)) = entries.last() {
+                check_order(prev, &path)?;
+            }
+            entries.push((path, entry));
+        }
+        Ok(entries)
+    }
+}
+
+/// Verifies that `prev` is sorted strictly before `next` and is not a prefix of it.
+fn check_order(prev: &[u8], next: &[u8]) -> Result<(), Error> {
+    match prev.cmp(next) {
+        Ordering::Less if next.starts_with(prev) => Err(Error::Overlapping),
+        Ordering::Less => Ok(()),
+        _ => Err(Error::Unsorted),
+    }
+}
+
+/// Builds the sub-trie containing the sorted `entries`, which all share the first
+/// `depth` nibbles of their path.
+fn build(entries: &[(Vec<u8>, Entry)], depth: usize) -> MptNode {
+    let data = match entries {
+        [] => MptNodeData::Null,
+        [(path, Entry::Leaf(value))] => {
+            MptNodeData::Leaf(to_encoded_path(&path[depth..], true), value.to_vec())
+        }
+        [(path, Entry::Digest(digest))] if path.len() == depth => MptNodeData::Digest(*digest),
+        // an unresolved node below an extension
+        [(path, Entry::Digest(digest))] => MptNodeData::Extension(
+            to_encoded_path(&path[depth..], false),
+            Box::new(MptNodeData::Digest(*digest).into()),
+        ),
+        [(first, _), .., (last, _)] => {
+            // as the entries are sorted, the first and last path share the common prefix
+            let common = lcp(&first[depth..], &last[depth..]);
+            if common > 0 {
+                MptNodeData::Extension(
+                    to_encoded_path(&first[depth..depth + common], false),
+                    Box::new(build(entries, depth + common)),
+                )
+            } else {
+                // no path ends at this depth, as no path is a prefix of another
+                let mut children: [Option<Box<MptNode>>; 16] = array::from_fn(|_| None);
+                let mut rest = entries;
+                while let Some((path, _)) = rest.first() {
+                    let nib = path[depth];
+                    let len = rest.partition_point(|(path, _)| path[depth] == nib);
+                    let (group, tail) = rest.split_at(len);
+                    children[nib as usize] = Some(Box::new(build(group, depth + 1)));
+                    rest = tail;
+                }
+                MptNodeData::Branch(children)
+            }
+        }
+    };
+    data.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trie::{MptNodeReference, EMPTY_ROOT};
+
+    fn test_trie() -> MptNode {
+        let mut trie = MptNode::default();
+        for i in 0..64u8 {
+            trie.insert(&[i, i ^ 0xa5, 0x42], vec![i; 32]).unwrap();
+        }
+        trie
+    }
+
+    /// Returns a copy of the trie with all sub-tries below `prefix` replaced by digests.
+    fn prune(node: &MptNode, prefix: &[u8], path: &mut Vec<u8>) -> MptNode {
+        if path.starts_with(prefix) {
+            if let MptNodeReference::Digest(digest) = node.reference() {
+                return MptNodeData::Digest(digest).into();
+            }
+        }
+        match node.as_data() {
+            MptNodeData::Branch(children) => {
+                let children = array::from_fn(|i| {
+                    children[i].as_ref().map(|child| {
+                        path.push(i as u8);
+                        let child = prune(child, prefix, path);
+                        path.pop();
+                        Box::new(child)
+                    })
+                });
+                MptNodeData::Branch(children).into()
+            }
+            MptNodeData::Extension(encoded_path, child) => {
+                let len = path.len();
+                path.extend(node.nibs());
+                let child = prune(child, prefix, path);
+                path.truncate(len);
+                MptNodeData::Extension(encoded_path.clone(), Box::new(child)).into()
+            }
+            _ => node.clone(),
+        }
+    }
+
+    #[test]
+    fn empty() {
+        let flat = FlatTrie::from_trie(&MptNode::default()).unwrap();
+        assert_eq!(flat, FlatTrie::default());
+        assert!(flat.reconstruct(EMPTY_ROOT).unwrap().is_empty());
+    }
+
+    #[test]
+    fn round_trip() {
+        let trie = test_trie();
+        let flat = FlatTrie::from_trie(&trie).unwrap();
+        assert_eq!(flat.leaves.len(), 64);
+        assert!(flat.boundary.is_empty());
+        assert_eq!(flat.reconstruct(trie.hash()).unwrap(), trie);
+    }
+
+    #[test]
+    fn sparse_round_trip() {
+        let trie = test_trie();
+        let root = trie.hash();
+        for prefix in [&[][..], &[0], &[1, 0], &[2, 7], &[3, 13]] {
+            let sparse = prune(&trie, prefix, &mut Vec::new());
+            assert_eq!(sparse.hash(), root);
+
+            let flat = FlatTrie::from_trie(&sparse).unwrap();
+            assert!(!flat.boundary.is_empty());
+            let reconstructed = flat.reconstruct(root).unwrap();
+            assert_eq!(reconstructed, sparse);
+        }
+
+        // the unpruned leaves remain accessible
+        let sparse = prune(&trie, &[2], &mut Vec::new());
+        let flat = FlatTrie::from_trie(&sparse).unwrap();
+        let reconstructed = flat.reconstruct(root).unwrap();
+        assert_eq!(
+            reconstructed.get(&[3, 3 ^ 0xa5, 0x42]).unwrap(),
+            Some(&[3; 32][..])
+        );
+        assert!(reconstructed.get(&[32, 32 ^ 0xa5, 0x42]).is_err());
+    }
+
+    #[test]
+    fn invalid() {
+        let trie = test_trie();
+        let root = trie.hash();
+        let flat = FlatTrie::from_trie(&trie).unwrap();
+
+        // tampered value
+        let mut tampered = flat.clone();
+        tampered.leaves[7].1[0] ^= 1;
+        assert!(matches!(
+            tampered.reconstruct(root),
+            Err(Error::RootMismatch { .. })
+        ));
+
+        // omitted leaf
+        let mut omitted = flat.clone();
+        omitted.leaves.remove(7);
+        assert!(matches!(
+            omitted.reconstruct(root),
+            Err(Error::RootMismatch { .. })
+        ));
+
+        // unsorted leaves
+        let mut unsorted = flat.clone();
+        unsorted.leaves.swap(7, 8);
+        assert_eq!(unsorted.reconstruct(root).unwrap_err(), Error::Unsorted);
+
+        // boundary covering a leaf
+        let mut overlapping = flat;
+        overlapping.boundary.push((vec![0], B256::ZERO));
+        assert_eq!(
+            overlapping.reconstruct(root).unwrap_err(),
+            Error::Overlapping
+        );
+    }
+}
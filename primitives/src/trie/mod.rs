@@ -13,11 +13,12 @@
 // limitations under the License.
 
 pub mod account;
+pub mod flat;
 pub mod mpt;
 
 use alloy_primitives::{b256, B256};
 
-pub use self::{account::StateAccount, mpt::*};
+pub use self::{account::StateAccount, flat::FlatTrie, mpt::*};
 
 /// Root hash of an empty trie.
 pub const EMPTY_ROOT: B256 =
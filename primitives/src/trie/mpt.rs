@@ -808,7 +808,7 @@ pub fn to_encoded_path(mut nibs: &[u8], is_leaf: bool) -> Vec<u8> {
 }
 
 /// Returns the length of the common prefix.
-fn lcp(a: &[u8], b: &[u8]) -> usize {
+pub(crate) fn lcp(a: &[u8], b: &[u8]) -> usize {
     for (i, (a, b)) in iter::zip(a, b).enumerate() {
         if a != b {
             return i;
@@ -975,6 +975,46 @@ mod tests {
         trie.get(b"a0").unwrap_err();
     }
 
+    #[test]
+    pub fn test_unresolved() {
+        // values must be long enough for the nodes to be referenced by their digest
+        let mut trie = MptNode::default();
+        trie.insert(b"aa", vec![0; 32]).unwrap();
+        trie.insert(b"ab", vec![1; 32]).unwrap();
+        trie.insert(b"ba", vec![2; 32]).unwrap();
+        let exp_hash = trie.hash();
+
+        // only keep the path to "ba", replacing the sub-trie of "aa" and "ab" with its digest
+        let digest = {
+            let MptNodeData::Extension(_, branch) = &mut trie.data else {
+                panic!("extension expected")
+            };
+            let MptNodeData::Branch(children) = &mut branch.data else {
+                panic!("branch expected")
+            };
+            let node = children[1].as_mut().unwrap();
+            let digest = node.hash();
+            **node = MptNodeData::Digest(digest).into();
+            branch.invalidate_ref_cache();
+            digest
+        };
+        trie.invalidate_ref_cache();
+        assert_eq!(trie.hash(), exp_hash);
+
+        // resolved paths can still be accessed
+        assert_eq!(trie.get(b"ba").unwrap(), Some(&[2; 32][..]));
+        assert_eq!(trie.get(b"c").unwrap(), None);
+
+        // unresolved paths return the digest of the unresolved node
+        assert!(matches!(trie.get(b"aa"), Err(Error::NodeNotResolved(d)) if d == digest));
+        assert!(matches!(
+            trie.insert(b"ac", vec![3; 32]),
+            Err(Error::NodeNotResolved(d)) if d == digest
+        ));
+        assert!(matches!(trie.delete(b"ab"), Err(Error::NodeNotResolved(d)) if d == digest));
+        assert_eq!(trie.hash(), exp_hash);
+    }
+
     #[test]
     pub fn test_branch_value() {
         let mut trie = MptNode::default();
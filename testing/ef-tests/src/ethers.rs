@@ -15,9 +15,10 @@
 use std::collections::BTreeSet;
 
 use ethers_core::types::{
-    Block, Bloom, Bytes, EIP1186ProofResponse, StorageProof, Transaction, TransactionReceipt, H256,
-    U256,
+    Block, Bloom, Bytes, EIP1186ProofResponse, OtherFields, StorageProof, Transaction,
+    TransactionReceipt, H256, U256,
 };
+use serde_json::json;
 use zeth_primitives::U256 as LibU256;
 
 use super::*;
@@ -34,6 +35,10 @@ impl Provider for TestProvider {
         unimplemented!()
     }
 
+    fn resolve_block_number(&mut self, _: &BlockId) -> Result<u64, anyhow::Error> {
+        unimplemented!()
+    }
+
     fn get_full_block(&mut self, _: &BlockQuery) -> Result<Block<Transaction>, anyhow::Error> {
         unimplemented!()
     }
@@ -43,6 +48,11 @@ impl Provider for TestProvider {
             bail!("block {} not found", query.block_no);
         }
 
+        let mut other = OtherFields::default();
+        if let Some(requests_hash) = self.header.requests_hash {
+            other.insert("requestsHash".to_string(), json!(requests_hash));
+        }
+
         Ok(Block::<H256> {
             parent_hash: self.header.parent_hash.0.into(),
             uncles_hash: self.header.ommers_hash.0.into(),
@@ -61,7 +71,11 @@ impl Provider for TestProvider {
             nonce: Some(self.header.nonce.0.into()),
             base_fee_per_gas: Some(self.header.base_fee_per_gas.to_be_bytes().into()),
             withdrawals_root: self.header.withdrawals_root.map(|r| r.0.into()),
+            blob_gas_used: self.header.blob_gas_used.map(|v| v.to_be_bytes().into()),
+            excess_blob_gas: self.header.excess_blob_gas.map(|v| v.to_be_bytes().into()),
+            parent_beacon_block_root: self.header.parent_beacon_block_root.map(|r| r.0.into()),
             hash: Some(self.header.hash().0.into()),
+            other,
             ..Default::default()
         })
     }
@@ -23,7 +23,7 @@ use zeth_lib::{
     consts::ChainSpec,
     host::{
         preflight::Data,
-        provider::{AccountQuery, BlockQuery, ProofQuery, Provider, StorageQuery},
+        provider::{AccountQuery, BlockId, BlockQuery, ProofQuery, Provider, StorageQuery},
         provider_db::ProviderDb,
     },
     input::{BlockBuildInput, StateInput},
@@ -146,6 +146,10 @@ pub struct TestHeader {
     pub transactions_trie: B256,
     pub uncle_hash: B256,
     pub withdrawals_root: Option<B256>,
+    pub blob_gas_used: Option<U256>,
+    pub excess_blob_gas: Option<U256>,
+    pub parent_beacon_block_root: Option<B256>,
+    pub requests_hash: Option<B256>,
 }
 
 impl From<TestHeader> for Header {
@@ -168,6 +172,10 @@ impl From<TestHeader> for Header {
             nonce: header.nonce,
             base_fee_per_gas: header.base_fee_per_gas.unwrap(),
             withdrawals_root: header.withdrawals_root,
+            blob_gas_used: header.blob_gas_used,
+            excess_blob_gas: header.excess_blob_gas,
+            parent_beacon_block_root: header.parent_beacon_block_root,
+            requests_hash: header.requests_hash,
         }
     }
 }